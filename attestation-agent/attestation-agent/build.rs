@@ -15,6 +15,22 @@ fn main() -> std::io::Result<()> {
             .compile(&["../protos/attestation-agent.proto"], &["../protos"])?;
     }
 
+    #[cfg(feature = "coco_as_grpc")]
+    {
+        tonic_build::configure()
+            .build_server(false)
+            .compile(&["../protos/attestation-service.proto"], &["../protos"])?;
+
+        // The server is only used by the tests, which include it from its
+        // own directory, s.t. it is not compiled into AA
+        let out_dir = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("server");
+        std::fs::create_dir_all(&out_dir)?;
+        tonic_build::configure()
+            .build_client(false)
+            .out_dir(out_dir)
+            .compile(&["../protos/attestation-service.proto"], &["../protos"])?;
+    }
+
     #[cfg(feature = "ttrpc")]
     {
         let protos = vec!["../protos/attestation-agent.proto"];
@@ -52,7 +68,7 @@ fn main() -> std::io::Result<()> {
             enabled_features.join(", ")
         }
 
-        let token_plugins = feature_list(vec!["KBS", "COCO_AS"]);
+        let token_plugins = feature_list(vec!["KBS", "CERT", "COCO_AS", "COCO_AS_GRPC", "ITA"]);
         let attester = feature_list(vec![
             "TDX_ATTESTER",
             "SGX_ATTESTER",
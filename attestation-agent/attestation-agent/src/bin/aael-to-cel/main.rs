@@ -0,0 +1,40 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Convert an AAEL eventlog into the TCG Canonical Event Log in JSON, as
+//! written with `eventlog_config.format = "cel"`.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use attestation_agent::{aael_to_cel, HashAlgorithm};
+use clap::Parser;
+
+#[derive(Debug, Parser)]
+#[command(author, version)]
+struct Cli {
+    /// The AAEL eventlog, e.g. `/run/attestation-agent/eventlog`.
+    eventlog: String,
+
+    /// Hash algorithm the entries are extended with, i.e.
+    /// `eventlog_config.eventlog_algorithm`.
+    #[arg(long, default_value_t = HashAlgorithm::Sha384)]
+    algorithm: HashAlgorithm,
+
+    /// Register the entries are extended into, in PCR numbering. The
+    /// eventlog `<path>.pcr<N>` is the one of register `N`.
+    #[arg(long, default_value_t = 17)]
+    pcr: u64,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let aael = std::fs::read_to_string(&cli.eventlog)
+        .with_context(|| format!("read eventlog {}", cli.eventlog))?;
+    let cel = aael_to_cel(&aael, cli.algorithm, cli.pcr);
+    std::io::stdout()
+        .write_all(cel.as_bytes())
+        .context("write CEL records")
+}
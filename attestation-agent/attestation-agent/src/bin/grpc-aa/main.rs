@@ -6,12 +6,16 @@
 mod server;
 
 use anyhow::*;
-use attestation_agent::AttestationAgent;
+use attestation_agent::{
+    cli::{init_logger, ConfigArgs},
+    AttestationAgent,
+};
 use clap::Parser;
 use log::{debug, info};
 use tokio::signal::unix::{signal, SignalKind};
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 const DEFAULT_ATTESTATION_AGENT_ADDR: &str = "127.0.0.1:50002";
 
@@ -29,23 +33,34 @@ struct Cli {
     #[arg(default_value_t = DEFAULT_ATTESTATION_AGENT_ADDR.to_string(), short, long = "attestation_sock")]
     attestation_sock: String,
 
-    /// Configuration file for Attestation Agent
-    ///
-    /// Example:
-    /// `--config /etc/attestation-agent.conf`
-    #[arg(short, long)]
-    config_file: Option<String>,
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     let cli = Cli::parse();
+    init_logger(cli.config.log_format());
 
     let attestation_socket = cli.attestation_sock.parse::<SocketAddr>()?;
 
-    let mut aa = AttestationAgent::new(cli.config_file.as_deref()).context("start AA")?;
+    let aa = Arc::new(
+        AttestationAgent::new_with_overrides(
+            cli.config.config_file.as_deref(),
+            cli.config.config_overrides(),
+        )
+        .context("start AA")?,
+    );
     aa.init().await.context("init AA")?;
+    let _config_watcher = aa
+        .watch_configuration()
+        .await
+        .context("watch AA config file")?;
+    #[cfg(feature = "kbs")]
+    let _token_refresher = aa
+        .start_token_refresh()
+        .await
+        .context("start KBS token refresh")?;
     debug!(
         "Attestation gRPC service listening on: {:?}",
         cli.attestation_sock
@@ -53,11 +68,15 @@ pub async fn main() -> Result<()> {
 
     let mut interrupt = signal(SignalKind::interrupt())?;
     let mut hangup = signal(SignalKind::hangup())?;
+    let mut terminate = signal(SignalKind::terminate())?;
     tokio::select! {
         _ = hangup.recv() => info!("Client terminal disconnected."),
         _ = interrupt.recv() => info!("SIGINT received, gracefully shutdown."),
-        _ = server::start_grpc_service(attestation_socket, aa) => info!("AA exits."),
+        _ = terminate.recv() => info!("SIGTERM received, gracefully shutdown."),
+        _ = server::start_grpc_service(attestation_socket, aa.clone()) => info!("AA exits."),
     }
 
+    aa.shutdown().await.context("shutdown AA")?;
+
     Ok(())
 }
@@ -9,14 +9,16 @@ use attestation::attestation_agent_service_server::{
 };
 use attestation::{
     CheckInitDataRequest, CheckInitDataResponse, ExtendRuntimeMeasurementRequest,
-    ExtendRuntimeMeasurementResponse, GetEvidenceRequest, GetEvidenceResponse, GetTokenRequest,
-    GetTokenResponse, UpdateConfigurationRequest, UpdateConfigurationResponse,
+    ExtendRuntimeMeasurementResponse, GetCapabilitiesRequest, GetCapabilitiesResponse,
+    GetEventLogPathRequest, GetEventLogPathResponse, GetEventLogRequest, GetEventLogResponse,
+    GetEvidenceRequest, GetEvidenceResponse, GetTeeTypeRequest, GetTeeTypeResponse,
+    GetTokenRequest, GetTokenResponse, UpdateConfigurationRequest, UpdateConfigurationResponse,
 };
-use attestation_agent::{AttestationAPIs, AttestationAgent};
+use attestation_agent::{AttestationAPIs, AttestationAgent, Error, EventFilter};
 use log::{debug, error};
 use std::net::SocketAddr;
-use tokio::sync::Mutex;
-use tonic::{transport::Server, Request, Response, Status};
+use std::sync::Arc;
+use tonic::{transport::Server, Code, Request, Response, Status};
 
 mod attestation {
     tonic::include_proto!("attestation_agent");
@@ -25,7 +27,18 @@ mod attestation {
 pub const AGENT_NAME: &str = "attestation-agent";
 
 pub struct AA {
-    inner: Mutex<AttestationAgent>,
+    inner: Arc<AttestationAgent>,
+}
+
+/// Timeouts get their own code, s.t. callers can tell a hung service that
+/// is worth a retry from a failed attestation.
+fn error_status(e: &Error, message: String) -> Status {
+    let code = match e {
+        Error::Timeout { .. } => Code::DeadlineExceeded,
+        Error::InvalidArgument(_) => Code::InvalidArgument,
+        _ => Code::Internal,
+    };
+    Status::new(code, message)
 }
 
 #[tonic::async_trait]
@@ -36,16 +49,26 @@ impl AttestationAgentService for AA {
     ) -> Result<Response<GetTokenResponse>, Status> {
         let request = request.into_inner();
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
         debug!("AA (grpc): get token ...");
 
+        let params = match request.extra_params.as_str() {
+            "" => serde_json::Value::Null,
+            params => serde_json::from_str(params).map_err(|e| {
+                error!("AA (grpc): get token failed:\n{e:?}");
+                Status::invalid_argument(format!(
+                    "[ERROR:{AGENT_NAME}] AA get token failed: illegal extra params"
+                ))
+            })?,
+        };
+
         let token = attestation_agent
-            .get_token(&request.token_type)
+            .get_token_with_params(&request.token_type, params)
             .await
             .map_err(|e| {
                 error!("AA (grpc): get token failed:\n{e:?}");
-                Status::internal(format!("[ERROR:{AGENT_NAME}] AA get token failed"))
+                error_status(&e, format!("[ERROR:{AGENT_NAME}] AA get token failed"))
             })?;
 
         debug!("AA (grpc): Get token successfully!");
@@ -61,17 +84,22 @@ impl AttestationAgentService for AA {
     ) -> Result<Response<GetEvidenceResponse>, Status> {
         let request = request.into_inner();
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
         debug!("AA (grpc): get evidence ...");
 
-        let evidence = attestation_agent
-            .get_evidence(&request.runtime_data)
-            .await
-            .map_err(|e| {
-                error!("AA (grpc): get evidence failed:\n{e:?}");
-                Status::internal(format!("[ERROR:{AGENT_NAME}] AA get evidence failed"))
-            })?;
+        let evidence = if request.include_event_log {
+            attestation_agent
+                .get_evidence_with_eventlog(&request.runtime_data)
+                .await
+                .and_then(|evidence| serde_json::to_vec(&evidence).map_err(Error::from))
+        } else {
+            attestation_agent.get_evidence(&request.runtime_data).await
+        }
+        .map_err(|e| {
+            error!("AA (grpc): get evidence failed:\n{e:?}");
+            error_status(&e, format!("[ERROR:{AGENT_NAME}] AA get evidence failed"))
+        })?;
 
         debug!("AA (grpc): Get evidence successfully!");
 
@@ -86,28 +114,51 @@ impl AttestationAgentService for AA {
     ) -> Result<Response<ExtendRuntimeMeasurementResponse>, Status> {
         let request = request.into_inner();
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
         debug!("AA (grpc): extend runtime measurement ...");
 
-        attestation_agent
-            .extend_runtime_measurement(
-                &request.domain,
-                &request.operation,
-                &request.content,
-                request.register_index,
+        let res = match &request.binary_content {
+            Some(content) if request.content.is_empty() => {
+                attestation_agent
+                    .extend_runtime_measurement_binary(
+                        &request.domain,
+                        &request.operation,
+                        content,
+                        request.register_index,
+                    )
+                    .await
+            }
+            Some(_) => Err(Error::InvalidArgument(
+                "only one of Content and BinaryContent can be given".into(),
+            )),
+            None => {
+                attestation_agent
+                    .extend_runtime_measurement_ex(
+                        &request.domain,
+                        &request.operation,
+                        &request.content,
+                        request.register_index,
+                    )
+                    .await
+            }
+        };
+        let result = res.map_err(|e| {
+            error!("AA (grpc): extend runtime measurement failed:\n{e:?}");
+            error_status(
+                &e,
+                format!("[ERROR:{AGENT_NAME}] AA extend runtime measurement failed"),
             )
-            .await
-            .map_err(|e| {
-                error!("AA (grpc): extend runtime measurement failed:\n{e:?}");
-                Status::internal(format!(
-                    "[ERROR:{AGENT_NAME}] AA extend runtime measurement failed"
-                ))
-            })?;
+        })?;
 
         debug!("AA (grpc): extend runtime measurement succeeded.");
 
-        let reply = ExtendRuntimeMeasurementResponse {};
+        let reply = ExtendRuntimeMeasurementResponse {
+            digest: result.digest,
+            algorithm: result.algorithm.to_string(),
+            register_index: result.register_index,
+            register_value: result.register_value,
+        };
 
         Result::Ok(Response::new(reply))
     }
@@ -118,7 +169,7 @@ impl AttestationAgentService for AA {
     ) -> Result<Response<CheckInitDataResponse>, Status> {
         let request = request.into_inner();
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
         debug!("AA (grpc): check init data ...");
 
@@ -127,7 +178,10 @@ impl AttestationAgentService for AA {
             .await
             .map_err(|e| {
                 error!("AA (grpc): check init data failed:\n{e:?}");
-                Status::internal(format!("[ERROR:{AGENT_NAME}] AA check init data failed"))
+                error_status(
+                    &e,
+                    format!("[ERROR:{AGENT_NAME}] AA check init data failed"),
+                )
             })?;
 
         debug!("AA (grpc): Check init data successfully!");
@@ -143,29 +197,161 @@ impl AttestationAgentService for AA {
     ) -> Result<Response<UpdateConfigurationResponse>, Status> {
         let request = request.into_inner();
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
         debug!("AA (grpc): update configuration ...");
 
-        attestation_agent
+        let changed = attestation_agent
             .update_configuration(&request.config)
+            .await
             .map_err(|e| {
                 error!("AA (grpc): update configuration failed:\n{e:?}");
-                Status::internal(format!(
-                    "[ERROR:{AGENT_NAME}] AA update configuration failed"
-                ))
+                error_status(
+                    &e,
+                    format!("[ERROR:{AGENT_NAME}] AA update configuration failed"),
+                )
             })?;
 
-        debug!("AA (grpc): update configuration successfully!");
+        debug!("AA (grpc): update configuration successfully, changed {changed:?}!");
 
         let reply = UpdateConfigurationResponse {};
 
         Result::Ok(Response::new(reply))
     }
+
+    async fn get_tee_type(
+        &self,
+        _request: Request<GetTeeTypeRequest>,
+    ) -> Result<Response<GetTeeTypeResponse>, Status> {
+        debug!("AA (grpc): get tee type ...");
+
+        let tee = self.inner.tee_type_name();
+
+        debug!("AA (grpc): get tee type succeeded: {tee}");
+
+        let reply = GetTeeTypeResponse { tee };
+
+        Result::Ok(Response::new(reply))
+    }
+
+    async fn get_event_log(
+        &self,
+        request: Request<GetEventLogRequest>,
+    ) -> Result<Response<GetEventLogResponse>, Status> {
+        let request = request.into_inner();
+
+        let attestation_agent = &self.inner;
+
+        debug!("AA (grpc): get event log ...");
+
+        let filter = match request.filter.as_str() {
+            "" => None,
+            filter => Some(serde_json::from_str::<EventFilter>(filter).map_err(|e| {
+                error!("AA (grpc): get event log failed:\n{e:?}");
+                Status::invalid_argument(format!(
+                    "[ERROR:{AGENT_NAME}] AA get event log failed: illegal filter"
+                ))
+            })?),
+        };
+
+        let start_index = request.start_index as usize;
+        let reply = match request.format.as_str() {
+            "" | "lines" => attestation_agent
+                .get_register_event_log(start_index, request.register_index)
+                .await
+                .map(|entries| GetEventLogResponse {
+                    entries,
+                    json: String::new(),
+                }),
+            "json" => match filter {
+                Some(filter) => attestation_agent
+                    .query_event_log(EventFilter {
+                        register_index: request.register_index.or(filter.register_index),
+                        ..filter
+                    })
+                    .await
+                    .and_then(|records| {
+                        let records = records.get(start_index..).unwrap_or_default();
+                        serde_json::to_string(records).map_err(Error::from)
+                    }),
+                None => {
+                    attestation_agent
+                        .get_event_log_json(start_index, request.register_index)
+                        .await
+                }
+            }
+            .map(|json| GetEventLogResponse {
+                entries: Vec::new(),
+                json,
+            }),
+            "combined" => attestation_agent
+                .get_combined_event_log()
+                .await
+                .map(|json| GetEventLogResponse {
+                    entries: Vec::new(),
+                    json,
+                }),
+            format => {
+                error!("AA (grpc): get event log failed: unknown format `{format}`");
+                return Err(Status::invalid_argument(format!(
+                    "[ERROR:{AGENT_NAME}] AA get event log failed: unknown format"
+                )));
+            }
+        }
+        .map_err(|e| {
+            error!("AA (grpc): get event log failed:\n{e:?}");
+            error_status(&e, format!("[ERROR:{AGENT_NAME}] AA get event log failed"))
+        })?;
+
+        debug!("AA (grpc): get event log succeeded.");
+
+        Result::Ok(Response::new(reply))
+    }
+
+    async fn get_event_log_path(
+        &self,
+        _request: Request<GetEventLogPathRequest>,
+    ) -> Result<Response<GetEventLogPathResponse>, Status> {
+        debug!("AA (grpc): get event log path ...");
+
+        let path = self.inner.eventlog_path().await;
+
+        debug!("AA (grpc): get event log path succeeded: {path:?}");
+
+        let reply = GetEventLogPathResponse {
+            path: path.map(|path| path.to_string_lossy().into_owned()),
+        };
+
+        Result::Ok(Response::new(reply))
+    }
+
+    async fn get_capabilities(
+        &self,
+        _request: Request<GetCapabilitiesRequest>,
+    ) -> Result<Response<GetCapabilitiesResponse>, Status> {
+        debug!("AA (grpc): get capabilities ...");
+
+        let capabilities = self.inner.capabilities();
+
+        debug!("AA (grpc): get capabilities succeeded: {capabilities:?}");
+
+        let reply = GetCapabilitiesResponse {
+            supports_runtime_measurement: capabilities.supports_runtime_measurement,
+            report_data_len: capabilities.report_data_len as u64,
+            supported_hash_algorithms: capabilities
+                .supported_hash_algorithms
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            init_data_supported: capabilities.init_data_supported,
+        };
+
+        Result::Ok(Response::new(reply))
+    }
 }
 
-pub async fn start_grpc_service(socket: SocketAddr, aa: AttestationAgent) -> Result<()> {
-    let service = AA { inner: aa.into() };
+pub async fn start_grpc_service(socket: SocketAddr, aa: Arc<AttestationAgent>) -> Result<()> {
+    let service = AA { inner: aa };
     Server::builder()
         .add_service(AttestationAgentServiceServer::new(service))
         .serve(socket)
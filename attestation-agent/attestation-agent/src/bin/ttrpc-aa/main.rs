@@ -5,11 +5,15 @@
 
 use ::ttrpc::asynchronous::Server;
 use anyhow::*;
-use attestation_agent::AttestationAgent;
+use attestation_agent::{
+    cli::{init_logger, ConfigArgs},
+    AttestationAgent,
+};
 use clap::{arg, command, Parser};
 use const_format::concatcp;
 use log::{debug, info};
 use std::path::Path;
+use std::sync::Arc;
 use tokio::signal::unix::{signal, SignalKind};
 
 mod server;
@@ -37,18 +41,14 @@ struct Cli {
     #[arg(default_value_t = DEFAULT_ATTESTATION_SOCKET_ADDR.to_string(), short, long = "attestation_sock")]
     attestation_sock: String,
 
-    /// Configuration file for Attestation Agent
-    ///
-    /// Example:
-    /// `--config /etc/attestation-agent.conf`
-    #[arg(short, long)]
-    config_file: Option<String>,
+    #[command(flatten)]
+    config: ConfigArgs,
 }
 
 #[tokio::main]
 pub async fn main() -> Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
     let cli = Cli::parse();
+    init_logger(cli.config.log_format());
 
     if !Path::new(DEFAULT_UNIX_SOCKET_DIR).exists() {
         std::fs::create_dir_all(DEFAULT_UNIX_SOCKET_DIR).expect("Create unix socket dir failed");
@@ -57,9 +57,24 @@ pub async fn main() -> Result<()> {
     clean_previous_sock_file(&cli.attestation_sock)
         .context("clean previous attestation socket file")?;
 
-    let mut aa = AttestationAgent::new(cli.config_file.as_deref()).context("start AA")?;
+    let aa = Arc::new(
+        AttestationAgent::new_with_overrides(
+            cli.config.config_file.as_deref(),
+            cli.config.config_overrides(),
+        )
+        .context("start AA")?,
+    );
     aa.init().await.context("init AA")?;
-    let att = server::start_ttrpc_service(aa)?;
+    let _config_watcher = aa
+        .watch_configuration()
+        .await
+        .context("watch AA config file")?;
+    #[cfg(feature = "kbs")]
+    let _token_refresher = aa
+        .start_token_refresh()
+        .await
+        .context("start KBS token refresh")?;
+    let att = server::start_ttrpc_service(aa.clone())?;
 
     let mut atts = Server::new()
         .bind(&cli.attestation_sock)
@@ -74,17 +89,16 @@ pub async fn main() -> Result<()> {
 
     let mut interrupt = signal(SignalKind::interrupt())?;
     let mut hangup = signal(SignalKind::hangup())?;
+    let mut terminate = signal(SignalKind::terminate())?;
     tokio::select! {
-        _ = hangup.recv() => {
-            info!("Client terminal disconnected.");
-            atts.shutdown().await?;
-        }
-        _ = interrupt.recv() => {
-            info!("SIGINT received, gracefully shutdown.");
-            atts.shutdown().await?;
-        }
+        _ = hangup.recv() => info!("Client terminal disconnected."),
+        _ = interrupt.recv() => info!("SIGINT received, gracefully shutdown."),
+        _ = terminate.recv() => info!("SIGTERM received, gracefully shutdown."),
     };
 
+    atts.shutdown().await?;
+    aa.shutdown().await.context("shutdown AA")?;
+
     Ok(())
 }
 
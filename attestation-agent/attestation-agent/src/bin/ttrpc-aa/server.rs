@@ -7,16 +7,17 @@ use ::ttrpc::asynchronous::Service;
 use ::ttrpc::proto::Code;
 use anyhow::*;
 use async_trait::async_trait;
-use attestation_agent::{AttestationAPIs, AttestationAgent};
+use attestation_agent::{AttestationAPIs, AttestationAgent, Error, EventFilter};
 use log::{debug, error};
-use tokio::sync::Mutex;
 
 use std::collections::HashMap;
 use std::sync::Arc;
 
 use crate::ttrpc_protocol::attestation_agent::{
-    ExtendRuntimeMeasurementRequest, ExtendRuntimeMeasurementResponse, GetEvidenceRequest,
-    GetEvidenceResponse, GetTokenRequest, GetTokenResponse, UpdateConfigurationRequest,
+    ExtendRuntimeMeasurementRequest, ExtendRuntimeMeasurementResponse, GetCapabilitiesRequest,
+    GetCapabilitiesResponse, GetEventLogPathRequest, GetEventLogPathResponse, GetEventLogRequest,
+    GetEventLogResponse, GetEvidenceRequest, GetEvidenceResponse, GetTeeTypeRequest,
+    GetTeeTypeResponse, GetTokenRequest, GetTokenResponse, UpdateConfigurationRequest,
     UpdateConfigurationResponse,
 };
 use crate::ttrpc_protocol::attestation_agent_ttrpc::{
@@ -26,7 +27,21 @@ use crate::ttrpc_protocol::attestation_agent_ttrpc::{
 pub const AGENT_NAME: &str = "attestation-agent";
 
 pub struct AA {
-    inner: Mutex<AttestationAgent>,
+    inner: Arc<AttestationAgent>,
+}
+
+/// Timeouts get their own code, s.t. callers can tell a hung service that
+/// is worth a retry from a failed attestation.
+fn error_code(e: &Error) -> Code {
+    match e {
+        Error::Timeout { .. } => Code::DEADLINE_EXCEEDED,
+        Error::InvalidArgument(_) => Code::INVALID_ARGUMENT,
+        Error::UnknownPolicy { .. } | Error::UnsupportedTokenTee { .. } => {
+            Code::FAILED_PRECONDITION
+        }
+        Error::Shutdown => Code::UNAVAILABLE,
+        _ => Code::INTERNAL,
+    }
 }
 
 #[async_trait]
@@ -38,15 +53,28 @@ impl AttestationAgentService for AA {
     ) -> ::ttrpc::Result<GetTokenResponse> {
         debug!("AA (ttrpc): get token ...");
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
+
+        let params = match req.ExtraParams.as_str() {
+            "" => serde_json::Value::Null,
+            params => serde_json::from_str(params).map_err(|e| {
+                error!("AA (ttrpc): get token failed\n {e:?}");
+                let mut error_status = ::ttrpc::proto::Status::new();
+                error_status.set_code(Code::INVALID_ARGUMENT);
+                error_status.set_message(format!(
+                    "[ERROR:{AGENT_NAME}] AA-KBC get token failed: illegal extra params"
+                ));
+                ::ttrpc::Error::RpcStatus(error_status)
+            })?,
+        };
 
         let token = attestation_agent
-            .get_token(&req.TokenType)
+            .get_token_with_params(&req.TokenType, params)
             .await
             .map_err(|e| {
                 error!("AA (ttrpc): get token failed\n {e:?}");
                 let mut error_status = ::ttrpc::proto::Status::new();
-                error_status.set_code(Code::INTERNAL);
+                error_status.set_code(error_code(&e));
                 error_status.set_message(format!("[ERROR:{AGENT_NAME}] AA-KBC get token failed"));
                 ::ttrpc::Error::RpcStatus(error_status)
             })?;
@@ -66,19 +94,23 @@ impl AttestationAgentService for AA {
     ) -> ::ttrpc::Result<GetEvidenceResponse> {
         debug!("AA (ttrpc): get evidence ...");
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
-        let evidence = attestation_agent
-            .get_evidence(&req.RuntimeData)
-            .await
-            .map_err(|e| {
-                error!("AA (ttrpc): get evidence failed:\n {e:?}");
-                let mut error_status = ::ttrpc::proto::Status::new();
-                error_status.set_code(Code::INTERNAL);
-                error_status
-                    .set_message(format!("[ERROR:{AGENT_NAME}] AA-KBC get evidence failed"));
-                ::ttrpc::Error::RpcStatus(error_status)
-            })?;
+        let evidence = if req.IncludeEventLog {
+            attestation_agent
+                .get_evidence_with_eventlog(&req.RuntimeData)
+                .await
+                .and_then(|evidence| serde_json::to_vec(&evidence).map_err(Error::from))
+        } else {
+            attestation_agent.get_evidence(&req.RuntimeData).await
+        }
+        .map_err(|e| {
+            error!("AA (ttrpc): get evidence failed:\n {e:?}");
+            let mut error_status = ::ttrpc::proto::Status::new();
+            error_status.set_code(error_code(&e));
+            error_status.set_message(format!("[ERROR:{AGENT_NAME}] AA-KBC get evidence failed"));
+            ::ttrpc::Error::RpcStatus(error_status)
+        })?;
 
         debug!("AA (ttrpc): Get evidence successfully!");
 
@@ -95,28 +127,49 @@ impl AttestationAgentService for AA {
     ) -> ::ttrpc::Result<ExtendRuntimeMeasurementResponse> {
         debug!("AA (ttrpc): extend runtime measurement ...");
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
-        attestation_agent
-            .extend_runtime_measurement(
-                &req.Domain,
-                &req.Operation,
-                &req.Content,
-                req.RegisterIndex,
-            )
-            .await
-            .map_err(|e| {
-                error!("AA (ttrpc): extend runtime measurement failed:\n {e:?}");
-                let mut error_status = ::ttrpc::proto::Status::new();
-                error_status.set_code(Code::INTERNAL);
-                error_status.set_message(format!(
-                    "[ERROR:{AGENT_NAME}] AA extend runtime measurement failed"
-                ));
-                ::ttrpc::Error::RpcStatus(error_status)
-            })?;
+        let res = match &req.BinaryContent {
+            Some(content) if req.Content.is_empty() => {
+                attestation_agent
+                    .extend_runtime_measurement_binary(
+                        &req.Domain,
+                        &req.Operation,
+                        content,
+                        req.RegisterIndex,
+                    )
+                    .await
+            }
+            Some(_) => Err(Error::InvalidArgument(
+                "only one of Content and BinaryContent can be given".into(),
+            )),
+            None => {
+                attestation_agent
+                    .extend_runtime_measurement_ex(
+                        &req.Domain,
+                        &req.Operation,
+                        &req.Content,
+                        req.RegisterIndex,
+                    )
+                    .await
+            }
+        };
+        let result = res.map_err(|e| {
+            error!("AA (ttrpc): extend runtime measurement failed:\n {e:?}");
+            let mut error_status = ::ttrpc::proto::Status::new();
+            error_status.set_code(error_code(&e));
+            error_status.set_message(format!(
+                "[ERROR:{AGENT_NAME}] AA extend runtime measurement failed"
+            ));
+            ::ttrpc::Error::RpcStatus(error_status)
+        })?;
 
         debug!("AA (ttrpc): extend runtime measurement succeeded.");
-        let reply = ExtendRuntimeMeasurementResponse::new();
+        let mut reply = ExtendRuntimeMeasurementResponse::new();
+        reply.Digest = result.digest;
+        reply.Algorithm = result.algorithm.to_string();
+        reply.RegisterIndex = result.register_index;
+        reply.RegisterValue = result.register_value;
         ::ttrpc::Result::Ok(reply)
     }
 
@@ -127,10 +180,11 @@ impl AttestationAgentService for AA {
     ) -> ::ttrpc::Result<UpdateConfigurationResponse> {
         debug!("AA (ttrpc): update configuration ...");
 
-        let mut attestation_agent = self.inner.lock().await;
+        let attestation_agent = &self.inner;
 
-        attestation_agent
+        let changed = attestation_agent
             .update_configuration(&req.config)
+            .await
             .map_err(|e| {
                 error!("AA (ttrpc): update configuration failed:\n {e:?}");
                 let mut error_status = ::ttrpc::proto::Status::new();
@@ -141,15 +195,139 @@ impl AttestationAgentService for AA {
                 ::ttrpc::Error::RpcStatus(error_status)
             })?;
 
-        debug!("AA (ttrpc): update configuration succeeded.");
+        debug!("AA (ttrpc): update configuration succeeded, changed {changed:?}.");
         let reply = UpdateConfigurationResponse::new();
         ::ttrpc::Result::Ok(reply)
     }
+
+    async fn get_tee_type(
+        &self,
+        _ctx: &::ttrpc::r#async::TtrpcContext,
+        _req: GetTeeTypeRequest,
+    ) -> ::ttrpc::Result<GetTeeTypeResponse> {
+        debug!("AA (ttrpc): get tee type ...");
+
+        let tee = self.inner.tee_type_name();
+
+        debug!("AA (ttrpc): get tee type succeeded: {tee}");
+        let mut reply = GetTeeTypeResponse::new();
+        reply.tee = tee;
+        ::ttrpc::Result::Ok(reply)
+    }
+
+    async fn get_event_log(
+        &self,
+        _ctx: &::ttrpc::r#async::TtrpcContext,
+        req: GetEventLogRequest,
+    ) -> ::ttrpc::Result<GetEventLogResponse> {
+        debug!("AA (ttrpc): get event log ...");
+
+        let attestation_agent = &self.inner;
+
+        let filter = match req.Filter.as_str() {
+            "" => None,
+            filter => Some(serde_json::from_str::<EventFilter>(filter).map_err(|e| {
+                error!("AA (ttrpc): get event log failed\n {e:?}");
+                let mut error_status = ::ttrpc::proto::Status::new();
+                error_status.set_code(Code::INVALID_ARGUMENT);
+                error_status.set_message(format!(
+                    "[ERROR:{AGENT_NAME}] AA get event log failed: illegal filter"
+                ));
+                ::ttrpc::Error::RpcStatus(error_status)
+            })?),
+        };
+
+        let start_index = req.StartIndex as usize;
+        let mut reply = GetEventLogResponse::new();
+        let result = match req.Format.as_str() {
+            "" | "lines" => attestation_agent
+                .get_register_event_log(start_index, req.RegisterIndex)
+                .await
+                .map(|entries| reply.Entries = entries),
+            "json" => match filter {
+                Some(filter) => attestation_agent
+                    .query_event_log(EventFilter {
+                        register_index: req.RegisterIndex.or(filter.register_index),
+                        ..filter
+                    })
+                    .await
+                    .and_then(|records| {
+                        let records = records.get(start_index..).unwrap_or_default();
+                        serde_json::to_string(records).map_err(Error::from)
+                    }),
+                None => {
+                    attestation_agent
+                        .get_event_log_json(start_index, req.RegisterIndex)
+                        .await
+                }
+            }
+            .map(|json| reply.Json = json),
+            "combined" => attestation_agent
+                .get_combined_event_log()
+                .await
+                .map(|json| reply.Json = json),
+            format => {
+                error!("AA (ttrpc): get event log failed: unknown format `{format}`");
+                let mut error_status = ::ttrpc::proto::Status::new();
+                error_status.set_code(Code::INVALID_ARGUMENT);
+                error_status.set_message(format!(
+                    "[ERROR:{AGENT_NAME}] AA get event log failed: unknown format"
+                ));
+                return Err(::ttrpc::Error::RpcStatus(error_status));
+            }
+        };
+        result.map_err(|e| {
+            error!("AA (ttrpc): get event log failed:\n {e:?}");
+            let mut error_status = ::ttrpc::proto::Status::new();
+            error_status.set_code(Code::INTERNAL);
+            error_status.set_message(format!("[ERROR:{AGENT_NAME}] AA get event log failed"));
+            ::ttrpc::Error::RpcStatus(error_status)
+        })?;
+
+        debug!("AA (ttrpc): get event log succeeded.");
+        ::ttrpc::Result::Ok(reply)
+    }
+
+    async fn get_event_log_path(
+        &self,
+        _ctx: &::ttrpc::r#async::TtrpcContext,
+        _req: GetEventLogPathRequest,
+    ) -> ::ttrpc::Result<GetEventLogPathResponse> {
+        debug!("AA (ttrpc): get event log path ...");
+
+        let path = self.inner.eventlog_path().await;
+
+        debug!("AA (ttrpc): get event log path succeeded: {path:?}");
+        let mut reply = GetEventLogPathResponse::new();
+        reply.Path = path.map(|path| path.to_string_lossy().into_owned());
+        ::ttrpc::Result::Ok(reply)
+    }
+
+    async fn get_capabilities(
+        &self,
+        _ctx: &::ttrpc::r#async::TtrpcContext,
+        _req: GetCapabilitiesRequest,
+    ) -> ::ttrpc::Result<GetCapabilitiesResponse> {
+        debug!("AA (ttrpc): get capabilities ...");
+
+        let capabilities = self.inner.capabilities();
+
+        debug!("AA (ttrpc): get capabilities succeeded: {capabilities:?}");
+        let mut reply = GetCapabilitiesResponse::new();
+        reply.SupportsRuntimeMeasurement = capabilities.supports_runtime_measurement;
+        reply.ReportDataLen = capabilities.report_data_len as u64;
+        reply.SupportedHashAlgorithms = capabilities
+            .supported_hash_algorithms
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        reply.InitDataSupported = capabilities.init_data_supported;
+        ::ttrpc::Result::Ok(reply)
+    }
 }
 
-pub fn start_ttrpc_service(aa: AttestationAgent) -> Result<HashMap<String, Service>> {
-    let service =
-        Box::new(AA { inner: aa.into() }) as Box<dyn AttestationAgentService + Send + Sync>;
+pub fn start_ttrpc_service(aa: Arc<AttestationAgent>) -> Result<HashMap<String, Service>> {
+    let service = Box::new(AA { inner: aa }) as Box<dyn AttestationAgentService + Send + Sync>;
 
     let service = Arc::new(service);
     let get_resource_service = create_attestation_agent_service(service);
@@ -56,6 +56,26 @@ impl AttestationAgentServiceClient {
         let mut cres = super::attestation_agent::UpdateConfigurationResponse::new();
         ::ttrpc::async_client_request!(self, ctx, req, "attestation_agent.AttestationAgentService", "UpdateConfiguration", cres);
     }
+
+    pub async fn get_tee_type(&self, ctx: ttrpc::context::Context, req: &super::attestation_agent::GetTeeTypeRequest) -> ::ttrpc::Result<super::attestation_agent::GetTeeTypeResponse> {
+        let mut cres = super::attestation_agent::GetTeeTypeResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "attestation_agent.AttestationAgentService", "GetTeeType", cres);
+    }
+
+    pub async fn get_event_log(&self, ctx: ttrpc::context::Context, req: &super::attestation_agent::GetEventLogRequest) -> ::ttrpc::Result<super::attestation_agent::GetEventLogResponse> {
+        let mut cres = super::attestation_agent::GetEventLogResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "attestation_agent.AttestationAgentService", "GetEventLog", cres);
+    }
+
+    pub async fn get_event_log_path(&self, ctx: ttrpc::context::Context, req: &super::attestation_agent::GetEventLogPathRequest) -> ::ttrpc::Result<super::attestation_agent::GetEventLogPathResponse> {
+        let mut cres = super::attestation_agent::GetEventLogPathResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "attestation_agent.AttestationAgentService", "GetEventLogPath", cres);
+    }
+
+    pub async fn get_capabilities(&self, ctx: ttrpc::context::Context, req: &super::attestation_agent::GetCapabilitiesRequest) -> ::ttrpc::Result<super::attestation_agent::GetCapabilitiesResponse> {
+        let mut cres = super::attestation_agent::GetCapabilitiesResponse::new();
+        ::ttrpc::async_client_request!(self, ctx, req, "attestation_agent.AttestationAgentService", "GetCapabilities", cres);
+    }
 }
 
 struct GetEvidenceMethod {
@@ -113,6 +133,50 @@ impl ::ttrpc::r#async::MethodHandler for UpdateConfigurationMethod {
     }
 }
 
+struct GetTeeTypeMethod {
+    service: Arc<Box<dyn AttestationAgentService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GetTeeTypeMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, attestation_agent, GetTeeTypeRequest, get_tee_type);
+    }
+}
+
+struct GetEventLogMethod {
+    service: Arc<Box<dyn AttestationAgentService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GetEventLogMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, attestation_agent, GetEventLogRequest, get_event_log);
+    }
+}
+
+struct GetEventLogPathMethod {
+    service: Arc<Box<dyn AttestationAgentService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GetEventLogPathMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, attestation_agent, GetEventLogPathRequest, get_event_log_path);
+    }
+}
+
+struct GetCapabilitiesMethod {
+    service: Arc<Box<dyn AttestationAgentService + Send + Sync>>,
+}
+
+#[async_trait]
+impl ::ttrpc::r#async::MethodHandler for GetCapabilitiesMethod {
+    async fn handler(&self, ctx: ::ttrpc::r#async::TtrpcContext, req: ::ttrpc::Request) -> ::ttrpc::Result<::ttrpc::Response> {
+        ::ttrpc::async_request_handler!(self, ctx, req, attestation_agent, GetCapabilitiesRequest, get_capabilities);
+    }
+}
+
 #[async_trait]
 pub trait AttestationAgentService: Sync {
     async fn get_evidence(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::attestation_agent::GetEvidenceRequest) -> ::ttrpc::Result<super::attestation_agent::GetEvidenceResponse> {
@@ -130,6 +194,18 @@ pub trait AttestationAgentService: Sync {
     async fn update_configuration(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::attestation_agent::UpdateConfigurationRequest) -> ::ttrpc::Result<super::attestation_agent::UpdateConfigurationResponse> {
         Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/attestation_agent.AttestationAgentService/UpdateConfiguration is not supported".to_string())))
     }
+    async fn get_tee_type(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::attestation_agent::GetTeeTypeRequest) -> ::ttrpc::Result<super::attestation_agent::GetTeeTypeResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/attestation_agent.AttestationAgentService/GetTeeType is not supported".to_string())))
+    }
+    async fn get_event_log(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::attestation_agent::GetEventLogRequest) -> ::ttrpc::Result<super::attestation_agent::GetEventLogResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/attestation_agent.AttestationAgentService/GetEventLog is not supported".to_string())))
+    }
+    async fn get_event_log_path(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::attestation_agent::GetEventLogPathRequest) -> ::ttrpc::Result<super::attestation_agent::GetEventLogPathResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/attestation_agent.AttestationAgentService/GetEventLogPath is not supported".to_string())))
+    }
+    async fn get_capabilities(&self, _ctx: &::ttrpc::r#async::TtrpcContext, _: super::attestation_agent::GetCapabilitiesRequest) -> ::ttrpc::Result<super::attestation_agent::GetCapabilitiesResponse> {
+        Err(::ttrpc::Error::RpcStatus(::ttrpc::get_status(::ttrpc::Code::NOT_FOUND, "/attestation_agent.AttestationAgentService/GetCapabilities is not supported".to_string())))
+    }
 }
 
 pub fn create_attestation_agent_service(service: Arc<Box<dyn AttestationAgentService + Send + Sync>>) -> HashMap<String, ::ttrpc::r#async::Service> {
@@ -152,6 +228,18 @@ pub fn create_attestation_agent_service(service: Arc<Box<dyn AttestationAgentSer
     methods.insert("UpdateConfiguration".to_string(),
                     Box::new(UpdateConfigurationMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
 
+    methods.insert("GetTeeType".to_string(),
+                    Box::new(GetTeeTypeMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("GetEventLog".to_string(),
+                    Box::new(GetEventLogMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("GetEventLogPath".to_string(),
+                    Box::new(GetEventLogPathMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
+    methods.insert("GetCapabilities".to_string(),
+                    Box::new(GetCapabilitiesMethod{service: service.clone()}) as Box<dyn ::ttrpc::r#async::MethodHandler + Send + Sync>);
+
     ret.insert("attestation_agent.AttestationAgentService".to_string(), ::ttrpc::r#async::Service{ methods, streams });
     ret
 }
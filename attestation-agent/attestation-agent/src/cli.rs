@@ -0,0 +1,188 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Command line options and logging shared by the AA binaries.
+
+use anyhow::{anyhow, bail, Result};
+use clap::Args;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::{self, Config, LogFormat};
+
+/// The options of the AA binaries about the configuration.
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    /// Configuration file for Attestation Agent. If not given, the file in
+    /// `$AA_CONFIG_PATH`, `/etc/attestation-agent/config.toml` or
+    /// `/etc/attestation-agent.conf` is used, whichever is found first.
+    ///
+    /// Example:
+    /// `--config /etc/attestation-agent.conf`
+    #[arg(short, long)]
+    pub config_file: Option<String>,
+
+    /// Format of the log output. Takes precedence over `log_format` of the
+    /// configuration.
+    #[arg(value_enum, long = "log_format")]
+    pub log_format: Option<LogFormat>,
+
+    /// Set `token_configs.kbs.url` of the configuration
+    #[arg(long = "kbs_url", value_name = "URL")]
+    pub kbs_url: Option<String>,
+
+    /// Set `token_configs.coco_as.url` of the configuration
+    #[arg(long = "coco_as_url", value_name = "URL")]
+    pub coco_as_url: Option<String>,
+
+    /// Set `attester.tee_override` of the configuration
+    #[arg(long = "tee_override", value_name = "TEE")]
+    pub tee_override: Option<String>,
+
+    /// Set `eventlog_config.file_path` of the configuration
+    #[arg(long = "eventlog_path", value_name = "PATH")]
+    pub eventlog_path: Option<String>,
+
+    /// Set any field of the configuration by its dotted key.
+    ///
+    /// The command line takes precedence over the environment, which takes
+    /// precedence over the config file. This is applied after the other
+    /// options, and may be given more than once, for example:
+    ///
+    /// `--set token_configs.cache=true --set eventlog_config.init_pcr=16`
+    #[arg(long = "set", value_name = "KEY=VALUE", value_parser = parse_override)]
+    pub set: Vec<(String, String)>,
+}
+
+impl ConfigArgs {
+    /// The fields of the configuration set on the command line, as
+    /// `(key, value)` in the order they are applied.
+    pub fn config_overrides(&self) -> Vec<(String, String)> {
+        let options = [
+            ("token_configs.kbs.url", &self.kbs_url),
+            ("token_configs.coco_as.url", &self.coco_as_url),
+            ("attester.tee_override", &self.tee_override),
+            ("eventlog_config.file_path", &self.eventlog_path),
+        ];
+
+        options
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+            .chain(self.set.iter().cloned())
+            .collect()
+    }
+
+    /// The format of the log output, from the command line or else from the
+    /// configuration. The logger is set up before AA is created, s.t. the
+    /// configuration is read here on its own. A configuration that cannot
+    /// be read logs as text, and fails when AA is created.
+    pub fn log_format(&self) -> LogFormat {
+        if let Some(format) = self.log_format {
+            return format;
+        }
+
+        let config = match self.config_file.clone().or_else(config::find_config_file) {
+            Some(config_path) => Config::try_from(config_path.as_str()).ok(),
+            None => Config::new().ok(),
+        };
+        config
+            .and_then(|config| config.apply_overrides(&self.config_overrides()).ok())
+            .map(|config| config.log_format)
+            .unwrap_or_default()
+    }
+}
+
+fn parse_override(arg: &str) -> Result<(String, String)> {
+    let (key, value) = arg
+        .split_once('=')
+        .ok_or_else(|| anyhow!("expected KEY=VALUE, got `{arg}`"))?;
+    if key.is_empty() {
+        bail!("no key in `{arg}`");
+    }
+    Ok((key.to_string(), value.to_string()))
+}
+
+/// Log with the level filter of `RUST_LOG`, `info` by default. Events of
+/// the `log` facade, e.g. from the attesters, are logged as well.
+pub fn init_logger(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::ConfigArgs;
+    use crate::config::LogFormat;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        config: ConfigArgs,
+    }
+
+    #[test]
+    fn config_overrides() {
+        let cli = Cli::parse_from([
+            "aa",
+            "--set",
+            "token_configs.cache=true",
+            "--kbs_url",
+            "https://kbs",
+            "--set",
+            "eventlog_config.init_pcr=16",
+        ]);
+        assert_eq!(
+            cli.config.config_overrides(),
+            [
+                ("token_configs.kbs.url", "https://kbs"),
+                ("token_configs.cache", "true"),
+                ("eventlog_config.init_pcr", "16"),
+            ]
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+        );
+        assert!(Cli::try_parse_from(["aa", "--set", "=true"]).is_err());
+        assert!(Cli::try_parse_from(["aa", "--set", "token_configs.cache"]).is_err());
+    }
+
+    #[test]
+    fn log_format_from_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+log_format = "json"
+
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+"#,
+        )
+        .unwrap();
+        let config_path = config_path.to_str().unwrap();
+
+        let cli = Cli::parse_from(["aa", "--config-file", config_path]);
+        assert_eq!(cli.config.log_format(), LogFormat::Json);
+
+        let cli = Cli::parse_from(["aa", "--config-file", config_path, "--log_format", "text"]);
+        assert_eq!(cli.config.log_format(), LogFormat::Text);
+
+        let cli = Cli::parse_from([
+            "aa",
+            "--config-file",
+            config_path,
+            "--set",
+            "log_format=text",
+        ]);
+        assert_eq!(cli.config.log_format(), LogFormat::Text);
+    }
+}
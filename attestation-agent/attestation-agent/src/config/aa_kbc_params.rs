@@ -1,6 +1,8 @@
-use log::debug;
 use std::env;
 use thiserror::Error;
+use tracing::debug;
+
+use crate::telemetry;
 
 #[derive(Error, Debug)]
 pub enum ParamError {
@@ -49,7 +51,7 @@ impl AaKbcParams {
     fn get_value() -> Result<String, ParamError> {
         // first check env
         if let Ok(params) = env::var("AA_KBC_PARAMS") {
-            debug!("get aa_kbc_params from env.");
+            debug!(target: telemetry::CONFIG, "get aa_kbc_params from env.");
             return Ok(params);
         }
 
@@ -59,7 +61,7 @@ impl AaKbcParams {
 
     pub fn new() -> Result<Self, ParamError> {
         let Ok(value) = Self::get_value() else {
-            debug!("failed to get aa_kbc_params in either both env or kernel cmdline, use `offline_fs_kbc::null` as default.");
+            debug!(target: telemetry::CONFIG, "failed to get aa_kbc_params in either both env or kernel cmdline, use `offline_fs_kbc::null` as default.");
             return Ok(Self::default());
         };
 
@@ -67,7 +69,7 @@ impl AaKbcParams {
     }
 
     fn from_cmdline() -> Result<String, ParamError> {
-        debug!("get aa_kbc_params from kernel cmdline");
+        debug!(target: telemetry::CONFIG, "get aa_kbc_params from kernel cmdline");
         let cmdline = std::fs::read_to_string("/proc/cmdline")?;
         let value = cmdline
             .split_ascii_whitespace()
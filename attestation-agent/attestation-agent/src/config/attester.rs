@@ -0,0 +1,53 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use ::attester::AttesterOptions;
+use kbs_types::Tee;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AttesterConfig {
+    /// Use the attester of the given TEE type instead of detecting the
+    /// platform, e.g. `tee_override = "tdx"`. The platform is still checked
+    /// to provide the given TEE. It takes precedence over the `AA_TEE_TYPE`
+    /// environment variable.
+    pub tee_override: Option<Tee>,
+
+    /// Device attesters that provide additional evidence, by device class,
+    /// see `attester::device::new_device_attester` for the classes built
+    /// in.
+    #[serde(default)]
+    pub extra_attesters: Vec<String>,
+
+    /// Seconds to wait for the attester to generate evidence, `0` means no
+    /// limit.
+    #[serde(default = "super::default_timeout_seconds")]
+    pub evidence_timeout_seconds: u64,
+
+    /// Use the sample attester if the attester of the detected or
+    /// configured TEE cannot be created, e.g. on development machines
+    /// without TEE. The evidence is then not backed by any hardware, so
+    /// this MUST NOT be set in production. By default AA fails instead.
+    #[serde(default)]
+    pub allow_fallback: bool,
+
+    /// Options of the attesters, one table per TEE type, e.g.
+    /// `[attester.tdx]`. The tables of attesters that are not built in
+    /// are ignored with a warning.
+    #[serde(flatten)]
+    pub options: AttesterOptions,
+}
+
+impl Default for AttesterConfig {
+    fn default() -> Self {
+        Self {
+            tee_override: None,
+            extra_attesters: Vec::new(),
+            evidence_timeout_seconds: super::DEFAULT_TIMEOUT_SECONDS,
+            allow_fallback: false,
+            options: AttesterOptions::default(),
+        }
+    }
+}
@@ -0,0 +1,159 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::net::IpAddr;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Algorithm of the key pair of a workload identity certificate.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KeyAlgorithm {
+    /// ECDSA on the NIST P-256 curve, signing with SHA-256.
+    #[default]
+    EcdsaP256,
+
+    /// ECDSA on the NIST P-384 curve, signing with SHA-384.
+    EcdsaP384,
+
+    /// 2048 bit RSA, signing with PKCS#1 v1.5 and SHA-256.
+    #[serde(rename = "rsa-2048")]
+    Rsa2048,
+
+    /// 3072 bit RSA, signing with PKCS#1 v1.5 and SHA-256.
+    #[serde(rename = "rsa-3072")]
+    Rsa3072,
+
+    /// 4096 bit RSA, signing with PKCS#1 v1.5 and SHA-256.
+    #[serde(rename = "rsa-4096")]
+    Rsa4096,
+}
+
+/// Config of the workload identity certificates of the `cert` token type.
+/// The key pair is generated in the guest, and its certificate is
+/// requested from the KBS of `token_configs.kbs` over the session of the
+/// RCAR handshake, s.t. it is only issued to an attested guest.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CertConfig {
+    /// Algorithm of the key pair generated for each certificate,
+    /// `"ecdsa-p256"` by default, or `"ecdsa-p384"`, `"rsa-2048"`,
+    /// `"rsa-3072"` or `"rsa-4096"`.
+    #[serde(default)]
+    pub key_algorithm: KeyAlgorithm,
+
+    /// Common name of the subject of the certificate, none by default.
+    #[serde(default)]
+    pub common_name: Option<String>,
+
+    /// Subject alternative names of the certificate: IP addresses, URIs,
+    /// e.g. the SPIFFE ID `spiffe://example.org/workload`, and DNS names
+    /// otherwise, e.g. `workload.example.org` or `*.example.org`.
+    #[serde(default)]
+    pub sans: Vec<String>,
+
+    /// Path of the endpoint that issues the certificates, under `/kbs/v0/`
+    /// of KBS, `"certificate"` by default. KBS does not serve one itself,
+    /// s.t. this is the path of a KBS plugin or a proxy in front of KBS
+    /// that does. It is sent `{"csr": "<PEM CSR>"}` in a POST over the
+    /// attested session, and answers with the PEM certificate chain, the
+    /// certificate of the key first.
+    #[serde(default = "default_kbs_path")]
+    pub kbs_path: String,
+}
+
+impl Default for CertConfig {
+    fn default() -> Self {
+        Self {
+            key_algorithm: KeyAlgorithm::default(),
+            common_name: None,
+            sans: Vec::new(),
+            kbs_path: default_kbs_path(),
+        }
+    }
+}
+
+fn default_kbs_path() -> String {
+    "certificate".to_string()
+}
+
+impl CertConfig {
+    /// The subject alternative names of `sans`, by their kind.
+    pub fn subject_alt_names(&self) -> Result<Vec<SubjectAltName>> {
+        self.sans
+            .iter()
+            .map(|san| SubjectAltName::parse(san))
+            .collect()
+    }
+}
+
+/// A subject alternative name of [`CertConfig::sans`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubjectAltName {
+    Dns(String),
+    Ip(IpAddr),
+    Uri(String),
+}
+
+impl SubjectAltName {
+    pub fn parse(san: &str) -> Result<Self> {
+        if let Ok(ip) = san.parse() {
+            return Ok(Self::Ip(ip));
+        }
+
+        if san.contains("://") {
+            let Ok(uri) = Url::parse(san) else {
+                bail!("`{san}` is not a valid URI");
+            };
+            return Ok(Self::Uri(uri.to_string()));
+        }
+
+        // A wildcard is only the leftmost label
+        let host = san.strip_prefix("*.").unwrap_or(san);
+        let valid_label = |label: &str| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        };
+        if host.len() > 253 || !host.split('.').all(valid_label) {
+            bail!("`{san}` is neither a DNS name, an IP address nor a URI");
+        }
+        Ok(Self::Dns(san.to_ascii_lowercase()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::SubjectAltName;
+
+    #[rstest]
+    #[case("workload.example.org", SubjectAltName::Dns("workload.example.org".into()))]
+    #[case("*.Example.org", SubjectAltName::Dns("*.example.org".into()))]
+    #[case("10.0.0.1", SubjectAltName::Ip("10.0.0.1".parse().unwrap()))]
+    #[case("fd00::1", SubjectAltName::Ip("fd00::1".parse().unwrap()))]
+    #[case(
+        "spiffe://example.org/workload",
+        SubjectAltName::Uri("spiffe://example.org/workload".into())
+    )]
+    fn parse_subject_alt_name(#[case] san: &str, #[case] expected: SubjectAltName) {
+        assert_eq!(SubjectAltName::parse(san).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("workload..example.org")]
+    #[case("-workload.example.org")]
+    #[case("work_load.example.org")]
+    #[case("workload.*.example.org")]
+    #[case("spiffe://exa mple.org")]
+    fn invalid_subject_alt_name(#[case] san: &str) {
+        SubjectAltName::parse(san).unwrap_err();
+    }
+}
@@ -0,0 +1,63 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Keys whose values differ between two configurations, s.t. an update can
+//! be audited.
+
+use serde_json::Value;
+
+/// Dotted paths of the fields that differ between `old` and `new`, e.g.
+/// `token_configs.kbs.url`. Tables are compared field by field, while
+/// arrays and scalars are compared as a whole.
+pub(super) fn changed_keys(old: &Value, new: &Value, section: &str) -> Vec<String> {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        if old == new {
+            return Vec::new();
+        }
+        return vec![section.to_string()];
+    };
+
+    let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .flat_map(|key| {
+            let path = match section {
+                "" => key.clone(),
+                section => format!("{section}.{key}"),
+            };
+            let old = old.get(key).unwrap_or(&Value::Null);
+            let new = new.get(key).unwrap_or(&Value::Null);
+            changed_keys(old, new, &path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::changed_keys;
+
+    #[test]
+    fn nested_changes() {
+        let old = json!({
+            "token_configs": {"cache": false, "kbs": {"url": "https://a", "certs": ["a"]}},
+            "eventlog_config": {"init_pcr": 17},
+        });
+        let new = json!({
+            "token_configs": {"cache": false, "kbs": {"url": "https://b", "certs": ["a", "b"]}},
+            "eventlog_config": {"init_pcr": 17},
+            "strict": true,
+        });
+
+        assert_eq!(
+            changed_keys(&old, &new, ""),
+            vec!["strict", "token_configs.kbs.certs", "token_configs.kbs.url"]
+        );
+        assert!(changed_keys(&old, &old, "").is_empty());
+    }
+}
@@ -3,15 +3,131 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use anyhow::Result;
-use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use super::aa_kbc_params::AaKbcParams;
+use anyhow::{anyhow, bail, Context, Result};
+use pkcs8::{EncryptedPrivateKeyInfo, LineEnding, SecretDocument};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize)]
+use super::{aa_kbc_params::AaKbcParams, secret::Secret};
+
+const PEM_PREFIX: &str = "-----BEGIN ";
+
+const ENCRYPTED_KEY_LABEL: &str = "ENCRYPTED PRIVATE KEY";
+
+/// Schemes of the URLs that select the gRPC API, and the schemes they are
+/// connected to with.
+const GRPC_SCHEMES: &[(&str, &str)] = &[("grpc://", "http://"), ("grpcs://", "https://")];
+
+/// API of Attestation Service that the token is requested with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CoCoASProtocol {
+    /// JSON over HTTP, i.e. `POST /attestation`.
+    #[default]
+    Rest,
+
+    /// The `AttestationService` gRPC service. This needs the
+    /// `coco_as_grpc` feature.
+    Grpc,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CoCoASConfig {
-    /// URL Address of Attestation Service.
+    /// URL Address of Attestation Service, as `token_configs.kbs.url`.
+    /// `grpc://` and `grpcs://` URLs are of the gRPC API, over plaintext
+    /// HTTP/2 and TLS respectively.
     pub url: String,
+
+    /// API of Attestation Service, `"rest"` or `"grpc"`. By default, it is
+    /// told by the scheme of `url`.
+    #[serde(default)]
+    pub protocol: Option<CoCoASProtocol>,
+
+    /// Seconds to wait for a token from Attestation Service, `0` means no
+    /// limit.
+    #[serde(default = "super::default_timeout_seconds")]
+    pub timeout_seconds: u64,
+
+    /// Seconds to wait for the connection to Attestation Service, as
+    /// `token_configs.kbs.connect_timeout_seconds`.
+    #[serde(default = "super::default_connect_timeout_seconds")]
+    pub connect_timeout_seconds: u64,
+
+    /// Seconds to wait for the attestation request, as
+    /// `token_configs.kbs.request_timeout_seconds`.
+    #[serde(default = "super::default_request_timeout_seconds")]
+    pub request_timeout_seconds: u64,
+
+    /// Attempts of a token request, as `token_configs.kbs.max_attempts`.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+
+    /// URL of the HTTP(S) proxy to Attestation Service, as
+    /// `token_configs.kbs.proxy`.
+    #[serde(default)]
+    pub proxy: Option<Secret>,
+
+    /// Hosts connected directly rather than through `proxy`, as
+    /// `token_configs.kbs.no_proxy`.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+
+    /// HTTP headers sent with the attestation request, as
+    /// `token_configs.kbs.extra_headers`.
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, Secret>,
+
+    /// IDs of the policies of Attestation Service to check the evidence
+    /// against, e.g. the one of the workload. The policy of Attestation
+    /// Service is used if it is empty. The `policy_ids` of the params of a
+    /// token request replace them.
+    #[serde(default)]
+    pub policy_ids: Vec<String>,
+
+    /// Audience of the token, i.e. its `aud` claim, which the verifiers of
+    /// a service check, e.g. `https://api.example.com`. It is sent as the
+    /// `audience` of the attestation request. The `audience` of the params
+    /// of a token request replaces it.
+    #[serde(default)]
+    pub audience: Option<String>,
+
+    /// Custom claims for Attestation Service to put into the token, sent as
+    /// the `claims` of the attestation request. The `claims` of the params
+    /// of a token request are added to them, replacing the ones of the same
+    /// name.
+    #[serde(default)]
+    pub claims: BTreeMap<String, String>,
+
+    /// Path of a PEM bundle of trusted root certs of Attestation Service,
+    /// e.g. of an internal CA, as `token_configs.kbs.cert_path`.
+    #[serde(default)]
+    pub cert_path: Option<PathBuf>,
+
+    /// Client certificate chain in PEM, or the path of the file holding
+    /// it, to authenticate AA to Attestation Service with mutual TLS.
+    #[serde(default)]
+    pub client_cert: Option<String>,
+
+    /// PKCS#8 private key of `client_cert` in PEM, or the path of the file
+    /// holding it. The key may be encrypted, i.e. an `ENCRYPTED PRIVATE
+    /// KEY`, with `client_key_passphrase`. An inline key is redacted
+    /// when the configuration is dumped.
+    #[serde(default)]
+    pub client_key: Option<Secret>,
+
+    /// Passphrase of the encrypted `client_key`, usually given as the
+    /// `file://` URI of the file holding it, see [`Secret`].
+    #[serde(default)]
+    pub client_key_passphrase: Option<Secret>,
+
+    /// Path of the file the fetched token is persisted in, as
+    /// `token_configs.kbs.cache_path`. This needs `token_configs.cache`.
+    #[serde(default)]
+    pub cache_path: Option<PathBuf>,
 }
 
 impl CoCoASConfig {
@@ -19,6 +135,279 @@ impl CoCoASConfig {
         let aa_kbc_params = AaKbcParams::new()?;
         Ok(Self {
             url: aa_kbc_params.uri,
+            protocol: None,
+            timeout_seconds: super::DEFAULT_TIMEOUT_SECONDS,
+            connect_timeout_seconds: super::DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            request_timeout_seconds: super::DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            max_attempts: None,
+            proxy: None,
+            no_proxy: None,
+            extra_headers: BTreeMap::new(),
+            policy_ids: Vec::new(),
+            audience: None,
+            claims: BTreeMap::new(),
+            cert_path: None,
+            client_cert: None,
+            client_key: None,
+            client_key_passphrase: None,
+            cache_path: None,
         })
     }
+
+    /// Config of the Attestation Service at the given URL, with the other
+    /// fields at their defaults.
+    pub fn with_url(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            protocol: None,
+            timeout_seconds: super::DEFAULT_TIMEOUT_SECONDS,
+            connect_timeout_seconds: super::DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            request_timeout_seconds: super::DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            max_attempts: None,
+            proxy: None,
+            no_proxy: None,
+            extra_headers: BTreeMap::new(),
+            policy_ids: Vec::new(),
+            audience: None,
+            claims: BTreeMap::new(),
+            cert_path: None,
+            client_cert: None,
+            client_key: None,
+            client_key_passphrase: None,
+            cache_path: None,
+        }
+    }
+
+    /// The API of Attestation Service, with the URL it is connected to at,
+    /// see [`CoCoASConfig::url`].
+    pub fn service(&self) -> Result<(CoCoASProtocol, String)> {
+        service(&self.url, self.protocol)
+    }
+
+    /// The identity of AA for mutual TLS with Attestation Service, from
+    /// `client_cert` and the decrypted `client_key`. `None` if no client
+    /// certificate is configured. The files are read on each call, s.t. a
+    /// renewed certificate is used without restarting AA.
+    pub fn client_identity(&self) -> Result<Option<reqwest::Identity>> {
+        load_identity(
+            self.client_cert.as_deref(),
+            self.client_key.as_deref(),
+            self.client_key_passphrase.as_deref(),
+        )
+    }
+}
+
+/// See [`CoCoASConfig::service`].
+pub(crate) fn service(
+    url: &str,
+    protocol: Option<CoCoASProtocol>,
+) -> Result<(CoCoASProtocol, String)> {
+    let grpc_url = GRPC_SCHEMES.iter().find_map(|(scheme, connected)| {
+        url.strip_prefix(scheme)
+            .map(|rest| format!("{connected}{rest}"))
+    });
+    match (protocol, grpc_url) {
+        (Some(CoCoASProtocol::Rest), Some(_)) => {
+            bail!("a grpc:// or grpcs:// URL is of the gRPC API, not the REST one")
+        }
+        (_, Some(url)) => Ok((CoCoASProtocol::Grpc, url)),
+        (protocol, None) => Ok((protocol.unwrap_or_default(), url.to_string())),
+    }
+}
+
+/// Whether the client key is given inline rather than by its path, s.t.
+/// it is redacted.
+pub(crate) fn is_inline_pem(value: &str) -> bool {
+    value.trim_start().starts_with(PEM_PREFIX)
+}
+
+/// The trusted root certs in the PEM bundle at the path, if any, see
+/// [`CoCoASConfig::cert_path`]. The file is read on each call, as the
+/// client certificate.
+pub(crate) fn read_root_certs(path: Option<&Path>) -> Result<Option<String>> {
+    path.map(|path| {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("read AS root certs {}", path.display()))
+    })
+    .transpose()
+}
+
+/// The identity for mutual TLS from the client certificate and its key,
+/// see [`CoCoASConfig::client_identity`].
+pub(crate) fn load_identity(
+    cert: Option<&str>,
+    key: Option<&str>,
+    passphrase: Option<&str>,
+) -> Result<Option<reqwest::Identity>> {
+    let Some((cert, key)) = load_identity_pem(cert, key, passphrase)? else {
+        return Ok(None);
+    };
+
+    #[cfg(feature = "rust-crypto")]
+    let identity = reqwest::Identity::from_pem(format!("{cert}\n{key}").as_bytes());
+    #[cfg(not(feature = "rust-crypto"))]
+    let identity = reqwest::Identity::from_pkcs8_pem(cert.as_bytes(), key.as_bytes());
+    identity
+        .map(Some)
+        .context("client_cert and client_key are not a valid identity")
+}
+
+/// The client certificate chain and its decrypted key in PEM, see
+/// [`load_identity`].
+pub(crate) fn load_identity_pem(
+    cert: Option<&str>,
+    key: Option<&str>,
+    passphrase: Option<&str>,
+) -> Result<Option<(String, String)>> {
+    let (cert, key) = match (cert, key) {
+        (Some(cert), Some(key)) => (cert, key),
+        (None, None) if passphrase.is_none() => return Ok(None),
+        (None, _) => bail!("client_cert is not set"),
+        (Some(_), None) => bail!("client_key is not set"),
+    };
+
+    let cert = read_pem(cert).context("read client_cert")?;
+    let key = read_pem(key).context("read client_key")?;
+    let key = decrypt_key(&key, passphrase)?;
+    Ok(Some((cert, key)))
+}
+
+/// The PEM value itself, or the content of the file at the path.
+fn read_pem(value: &str) -> Result<String> {
+    if is_inline_pem(value) {
+        return Ok(value.to_string());
+    }
+
+    std::fs::read_to_string(value).with_context(|| format!("read `{value}`"))
+}
+
+/// The PKCS#8 private key in PEM, decrypted with the passphrase if it is
+/// encrypted.
+fn decrypt_key(key: &str, passphrase: Option<&str>) -> Result<String> {
+    let encrypted = key.contains(&format!("{PEM_PREFIX}{ENCRYPTED_KEY_LABEL}-----"));
+    let passphrase = match (encrypted, passphrase) {
+        (false, None) => return Ok(key.to_string()),
+        (false, Some(_)) => bail!("client_key_passphrase is set, but client_key is not encrypted"),
+        (true, None) => bail!("client_key is encrypted, but client_key_passphrase is not set"),
+        (true, Some(passphrase)) => passphrase,
+    };
+
+    // The errors of der carry no secret
+    let (_, document) = SecretDocument::from_pem(key.trim())
+        .map_err(|e| anyhow!("parse encrypted client_key: {e}"))?;
+    let decrypted = EncryptedPrivateKeyInfo::try_from(document.as_bytes())
+        .and_then(|info| info.decrypt(passphrase))
+        .map_err(|e| anyhow!("decrypt client_key: {e}"))?;
+    let pem = decrypted
+        .to_pem("PRIVATE KEY", LineEnding::LF)
+        .map_err(|e| anyhow!("encode decrypted client_key: {e}"))?;
+    Ok(pem.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{decrypt_key, CoCoASConfig, CoCoASProtocol};
+
+    const CLIENT_KEY: &str = include_str!("../../test/tls/client.key");
+
+    const ENCRYPTED_CLIENT_KEY: &str = include_str!("../../test/tls/client.encrypted.key");
+
+    const PASSPHRASE: &str = "aa-test-passphrase";
+
+    #[test]
+    fn decrypt_client_key() {
+        let decrypted = decrypt_key(ENCRYPTED_CLIENT_KEY, Some(PASSPHRASE)).unwrap();
+        assert_eq!(decrypted.trim(), CLIENT_KEY.trim());
+        assert_eq!(decrypt_key(CLIENT_KEY, None).unwrap(), CLIENT_KEY);
+    }
+
+    #[rstest]
+    #[case(ENCRYPTED_CLIENT_KEY, Some("wrong"), "decrypt client_key")]
+    #[case(ENCRYPTED_CLIENT_KEY, None, "client_key_passphrase is not set")]
+    #[case(CLIENT_KEY, Some(PASSPHRASE), "client_key is not encrypted")]
+    fn invalid_client_key(
+        #[case] key: &str,
+        #[case] passphrase: Option<&str>,
+        #[case] reason: &str,
+    ) {
+        let e = decrypt_key(key, passphrase).unwrap_err();
+        assert!(e.to_string().contains(reason), "{e}");
+    }
+
+    #[rstest]
+    #[case("https://as:8000", None, CoCoASProtocol::Rest, "https://as:8000")]
+    #[case(
+        "unix:///run/as.sock",
+        None,
+        CoCoASProtocol::Rest,
+        "unix:///run/as.sock"
+    )]
+    #[case("grpc://as:50004", None, CoCoASProtocol::Grpc, "http://as:50004")]
+    #[case("grpcs://as:50004", None, CoCoASProtocol::Grpc, "https://as:50004")]
+    #[case(
+        "grpcs://as:50004",
+        Some(CoCoASProtocol::Grpc),
+        CoCoASProtocol::Grpc,
+        "https://as:50004"
+    )]
+    #[case(
+        "https://as:50004",
+        Some(CoCoASProtocol::Grpc),
+        CoCoASProtocol::Grpc,
+        "https://as:50004"
+    )]
+    #[case(
+        "unix:///run/as.sock",
+        Some(CoCoASProtocol::Grpc),
+        CoCoASProtocol::Grpc,
+        "unix:///run/as.sock"
+    )]
+    fn service(
+        #[case] url: &str,
+        #[case] protocol: Option<CoCoASProtocol>,
+        #[case] expected_protocol: CoCoASProtocol,
+        #[case] expected_url: &str,
+    ) {
+        let config = CoCoASConfig {
+            protocol,
+            ..CoCoASConfig::with_url(url)
+        };
+        let (protocol, url) = config.service().unwrap();
+        assert_eq!(protocol, expected_protocol);
+        assert_eq!(url, expected_url);
+    }
+
+    #[test]
+    fn grpc_url_of_rest_service() {
+        let config = CoCoASConfig {
+            protocol: Some(CoCoASProtocol::Rest),
+            ..CoCoASConfig::with_url("grpc://as:50004")
+        };
+        let e = config.service().unwrap_err();
+        assert!(e.to_string().contains("gRPC API"), "{e}");
+    }
+
+    #[test]
+    fn client_identity() {
+        let config = CoCoASConfig {
+            client_cert: Some("test/tls/client.pem".into()),
+            client_key: Some(ENCRYPTED_CLIENT_KEY.into()),
+            client_key_passphrase: Some(PASSPHRASE.into()),
+            ..CoCoASConfig::with_url("https://127.0.0.1:8000")
+        };
+        assert!(config.client_identity().unwrap().is_some());
+        assert!(CoCoASConfig::with_url("https://127.0.0.1:8000")
+            .client_identity()
+            .unwrap()
+            .is_none());
+
+        let config = CoCoASConfig {
+            client_cert: Some("test/tls/missing.pem".into()),
+            ..config
+        };
+        let e = config.client_identity().unwrap_err();
+        assert!(format!("{e:#}").contains("missing.pem"), "{e:#}");
+    }
 }
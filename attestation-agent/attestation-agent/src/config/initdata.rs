@@ -0,0 +1,31 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use serde::{Deserialize, Serialize};
+
+use super::HashAlgorithm;
+
+/// How initdata documents are checked against the host data field of the
+/// platform, see [`crate::initdata::InitData`].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct InitdataConfig {
+    /// Hash algorithm of the digest of initdata documents that do not
+    /// declare an `algorithm` themselves. The digest must fit into the
+    /// host data field, e.g. `sha512` does not fit into MRCONFIGID of TDX.
+    #[serde(default = "default_algorithm")]
+    pub algorithm: HashAlgorithm,
+}
+
+fn default_algorithm() -> HashAlgorithm {
+    HashAlgorithm::Sha256
+}
+
+impl Default for InitdataConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: default_algorithm(),
+        }
+    }
+}
@@ -0,0 +1,85 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use serde::{Deserialize, Serialize};
+
+use super::secret::Secret;
+
+/// Base URL of the Intel Trust Authority API in the US region.
+pub const DEFAULT_ITA_URL: &str = "https://api.trustauthority.intel.com";
+
+/// Config of the `ita` token type, whose tokens are issued by Intel Trust
+/// Authority for the evidence of TDX or SGX.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ItaConfig {
+    /// Base URL of the Intel Trust Authority API, e.g.
+    /// `https://api.eu.trustauthority.intel.com` in the EU region. The US
+    /// region by default.
+    #[serde(default = "default_url")]
+    pub url: String,
+
+    /// API key of the attestation API, sent as the `x-api-key` header. It
+    /// is usually given as the `file://` URI of the file holding it, see
+    /// [`Secret`].
+    pub api_key: Secret,
+
+    /// IDs of the appraisal policies of Intel Trust Authority to check the
+    /// evidence against. The `policy_ids` of the params of a token request
+    /// replace them.
+    #[serde(default)]
+    pub policy_ids: Vec<String>,
+
+    /// Whether the token is only issued if the evidence matches all of
+    /// `policy_ids`, rather than carrying the policies it does not match.
+    #[serde(default)]
+    pub policy_must_match: bool,
+
+    /// Seconds to wait for a token from Intel Trust Authority, `0` means no
+    /// limit.
+    #[serde(default = "super::default_timeout_seconds")]
+    pub timeout_seconds: u64,
+
+    /// Seconds to wait for the connection to Intel Trust Authority, as
+    /// `token_configs.kbs.connect_timeout_seconds`.
+    #[serde(default = "super::default_connect_timeout_seconds")]
+    pub connect_timeout_seconds: u64,
+
+    /// Seconds to wait for each request to Intel Trust Authority, as
+    /// `token_configs.kbs.request_timeout_seconds`.
+    #[serde(default = "super::default_request_timeout_seconds")]
+    pub request_timeout_seconds: u64,
+
+    /// URL of the HTTP(S) proxy to Intel Trust Authority, as
+    /// `token_configs.kbs.proxy`.
+    #[serde(default)]
+    pub proxy: Option<Secret>,
+
+    /// Hosts connected directly rather than through `proxy`, as
+    /// `token_configs.kbs.no_proxy`.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+fn default_url() -> String {
+    DEFAULT_ITA_URL.to_string()
+}
+
+impl ItaConfig {
+    /// Config of Intel Trust Authority at the given URL with the API key,
+    /// with the other fields at their defaults.
+    pub fn with_url(url: impl Into<String>, api_key: impl Into<Secret>) -> Self {
+        Self {
+            url: url.into(),
+            api_key: api_key.into(),
+            policy_ids: Vec::new(),
+            policy_must_match: false,
+            timeout_seconds: super::DEFAULT_TIMEOUT_SECONDS,
+            connect_timeout_seconds: super::DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            request_timeout_seconds: super::DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            proxy: None,
+            no_proxy: None,
+        }
+    }
+}
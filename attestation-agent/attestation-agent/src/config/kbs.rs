@@ -3,18 +3,143 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::{collections::BTreeMap, path::PathBuf};
+
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use super::{aa_kbc_params::AaKbcParams, secret::Secret};
+
+/// The KBS token is refreshed this many seconds before it expires by
+/// default, see [`KbsConfig::refresh_margin_seconds`].
+pub const DEFAULT_REFRESH_MARGIN_SECONDS: u64 = 300;
+
+/// The handshake with a KBS endpoint is restarted this many times by
+/// default once the session of its challenge is stale, see
+/// [`KbsConfig::stale_session_retries`].
+pub const DEFAULT_STALE_SESSION_RETRIES: u32 = 2;
 
-use super::aa_kbc_params::AaKbcParams;
+/// At most this many restarts of a stale handshake can be configured, s.t.
+/// a KBS that keeps expiring the sessions fails the endpoint eventually.
+pub const MAX_STALE_SESSION_RETRIES: u32 = 5;
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct KbsConfig {
-    /// URL Address of KBS.
+    /// URL Address of KBS, `http://` or `https://`. Unlike AS, KBS cannot
+    /// be reached over a unix socket or vsock.
+    #[serde(default)]
     pub url: String,
 
+    /// URL Addresses of the endpoints of the same KBS, e.g. in different
+    /// regions, tried in order until one serves the token. `url` is ignored
+    /// if this is not empty.
+    #[serde(default)]
+    pub urls: Vec<String>,
+
     /// Cert of KBS
     pub cert: Option<String>,
+
+    /// Path of a PEM bundle of trusted root certs of KBS, e.g. of an
+    /// internal CA. It is read on each token request, s.t. a rotated bundle
+    /// is used without restarting AA.
+    #[serde(default)]
+    pub cert_path: Option<PathBuf>,
+
+    /// Trusted root certs of KBS in PEM, in addition to `cert`.
+    #[serde(default)]
+    pub certs: Vec<String>,
+
+    /// Seconds to wait for a token from KBS, `0` means no limit. This
+    /// limits all the attempts together, see `[retry]`.
+    #[serde(default = "super::default_timeout_seconds")]
+    pub timeout_seconds: u64,
+
+    /// Seconds to wait for the connection to KBS, including the TLS
+    /// handshake, `0` means no limit.
+    #[serde(default = "super::default_connect_timeout_seconds")]
+    pub connect_timeout_seconds: u64,
+
+    /// Seconds to wait for each request of the RCAR handshake, from
+    /// connecting to reading the response, `0` means no limit. An attempt
+    /// that times out is retried as given by `max_attempts`.
+    #[serde(default = "super::default_request_timeout_seconds")]
+    pub request_timeout_seconds: u64,
+
+    /// Attempts of a token request including the first one, in place of
+    /// `retry.max_attempts`, s.t. retries are enabled for KBS only. The
+    /// backoff between them is as configured by `[retry]`.
+    #[serde(default)]
+    pub max_attempts: Option<u32>,
+
+    /// URL of the HTTP(S) proxy to connect through, which may carry
+    /// credentials, or a `file://` URI of the file holding it, see
+    /// [`Secret`]. Without it, the `HTTPS_PROXY`, `HTTP_PROXY`, `ALL_PROXY`
+    /// and `NO_PROXY` environment variables apply.
+    #[serde(default)]
+    pub proxy: Option<Secret>,
+
+    /// Comma separated hosts that are connected directly rather than
+    /// through `proxy`, as with `NO_PROXY`: a host name matches the host
+    /// and its subdomains, an IP address or CIDR matches the URLs of such
+    /// addresses, and either may be qualified with a port, e.g.
+    /// `.svc.cluster.local,10.0.0.0/8,[fd00::1]:8080`. An invalid entry is
+    /// skipped with a warning. Without it, `NO_PROXY` applies.
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+
+    /// HTTP headers sent with every request to KBS, e.g. the
+    /// `X-Tenant-Id` an API gateway in front of KBS routes on. A value may
+    /// be the `file://` URI of the file holding it, see [`Secret`], e.g.
+    /// for an API key. The headers of the protocol, e.g. `Content-Type`,
+    /// cannot be set.
+    #[serde(default)]
+    pub extra_headers: BTreeMap<String, Secret>,
+
+    /// Whether to get a new token in the background before the cached one
+    /// expires, s.t. `get_token` keeps serving a valid token without
+    /// attesting. Only a token that a caller got is refreshed. This needs
+    /// `token_configs.cache`.
+    #[serde(default)]
+    pub auto_refresh: bool,
+
+    /// With `auto_refresh`, the token is refreshed this many seconds
+    /// before it expires. This must be more than
+    /// `token_configs.cache_skew_seconds`, s.t. the cached token is served
+    /// until the new one replaces it.
+    #[serde(default = "default_refresh_margin_seconds")]
+    pub refresh_margin_seconds: u64,
+
+    /// Path of the file the fetched token is persisted in, s.t. it is
+    /// served after AA restarts rather than attesting again. The file is
+    /// only readable and writable by AA. This needs `token_configs.cache`
+    /// and `persist_tee_keypair`.
+    #[serde(default)]
+    pub cache_path: Option<PathBuf>,
+
+    /// Whether the TEE key pair is persisted in `cache_path` together with
+    /// the token. KBS binds the token to the key pair, and encrypts the
+    /// resources to it, so the token cannot be used without it. The private
+    /// key then leaves the memory of the TEE, so only enable this if
+    /// `cache_path` is on storage protected like it, e.g. encrypted storage
+    /// of the guest.
+    #[serde(default)]
+    pub persist_tee_keypair: bool,
+
+    /// Times the handshake with an endpoint is restarted, with a new
+    /// challenge and new evidence, once KBS tells the session of the
+    /// challenge is stale, e.g. it expired while the evidence was being
+    /// generated. At most [`MAX_STALE_SESSION_RETRIES`]. These restarts are
+    /// not counted by `[retry]`.
+    #[serde(default = "default_stale_session_retries")]
+    pub stale_session_retries: u32,
+}
+
+fn default_refresh_margin_seconds() -> u64 {
+    DEFAULT_REFRESH_MARGIN_SECONDS
+}
+
+fn default_stale_session_retries() -> u32 {
+    DEFAULT_STALE_SESSION_RETRIES
 }
 
 impl KbsConfig {
@@ -22,7 +147,55 @@ impl KbsConfig {
         let aa_kbc_params = AaKbcParams::new()?;
         Ok(Self {
             url: aa_kbc_params.uri,
+            urls: Vec::new(),
             cert: None,
+            cert_path: None,
+            certs: Vec::new(),
+            timeout_seconds: super::DEFAULT_TIMEOUT_SECONDS,
+            connect_timeout_seconds: super::DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            request_timeout_seconds: super::DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            max_attempts: None,
+            proxy: None,
+            no_proxy: None,
+            extra_headers: BTreeMap::new(),
+            auto_refresh: false,
+            refresh_margin_seconds: DEFAULT_REFRESH_MARGIN_SECONDS,
+            cache_path: None,
+            persist_tee_keypair: false,
+            stale_session_retries: DEFAULT_STALE_SESSION_RETRIES,
         })
     }
+
+    /// Config of the KBS at the given URL, with the other fields at their
+    /// defaults.
+    pub fn with_url(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            urls: Vec::new(),
+            cert: None,
+            cert_path: None,
+            certs: Vec::new(),
+            timeout_seconds: super::DEFAULT_TIMEOUT_SECONDS,
+            connect_timeout_seconds: super::DEFAULT_CONNECT_TIMEOUT_SECONDS,
+            request_timeout_seconds: super::DEFAULT_REQUEST_TIMEOUT_SECONDS,
+            max_attempts: None,
+            proxy: None,
+            no_proxy: None,
+            extra_headers: BTreeMap::new(),
+            auto_refresh: false,
+            refresh_margin_seconds: DEFAULT_REFRESH_MARGIN_SECONDS,
+            cache_path: None,
+            persist_tee_keypair: false,
+            stale_session_retries: DEFAULT_STALE_SESSION_RETRIES,
+        }
+    }
+
+    /// The URLs of KBS in the order to try them.
+    pub fn endpoints(&self) -> &[String] {
+        if self.urls.is_empty() {
+            std::slice::from_ref(&self.url)
+        } else {
+            &self.urls
+        }
+    }
 }
@@ -0,0 +1,85 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Migration of config documents of older layouts to the current one. The
+//! layout of a document is given by its top-level `version`, and documents
+//! without one have the layout of version 1, which is the current one.
+
+use config::ConfigError;
+use serde_json::Value;
+
+/// Version of the current layout of [`super::Config`], which is the one
+/// the config files without a `version` have. Bump this, and migrate the
+/// documents of the previous version in [`migrate`], when a key of a
+/// released layout is renamed or moved.
+pub const CONFIG_VERSION: u64 = 1;
+
+/// Key of the layout version in a config document.
+pub(super) const VERSION_KEY: &str = "version";
+
+/// Migrate the document to the layout of [`CONFIG_VERSION`]. No key of a
+/// released layout has been renamed or moved yet, s.t. this only checks
+/// the version. Documents of a newer layout are an error.
+pub(super) fn migrate(mut document: Value) -> Result<Value, ConfigError> {
+    let Value::Object(map) = &mut document else {
+        return Ok(document);
+    };
+
+    let version = match map.get(VERSION_KEY) {
+        None => CONFIG_VERSION,
+        Some(version) => version.as_u64().ok_or_else(|| {
+            ConfigError::Message(format!("`{VERSION_KEY}` must be a positive integer"))
+        })?,
+    };
+    if version == 0 || version > CONFIG_VERSION {
+        return Err(ConfigError::Message(format!(
+            "config version {version} is not supported, the latest one is {CONFIG_VERSION}"
+        )));
+    }
+
+    map.insert(VERSION_KEY.into(), CONFIG_VERSION.into());
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::{migrate, CONFIG_VERSION};
+
+    #[test]
+    fn current_layout_is_kept() {
+        let document = json!({
+            "version": CONFIG_VERSION,
+            "eventlog_config": {"enabled": false},
+            "attester": {"tee_override": "tdx"},
+        });
+        assert_eq!(migrate(document.clone()).unwrap(), document);
+    }
+
+    #[test]
+    fn unversioned_document_is_current() {
+        let migrated = migrate(json!({
+            "eventlog_config": {"eventlog_algorithm": "sha384", "init_pcr": 17},
+        }))
+        .unwrap();
+        assert_eq!(
+            migrated,
+            json!({
+                "version": CONFIG_VERSION,
+                "eventlog_config": {"eventlog_algorithm": "sha384", "init_pcr": 17},
+            })
+        );
+    }
+
+    #[rstest]
+    #[case(json!(0))]
+    #[case(json!(CONFIG_VERSION + 1))]
+    #[case(json!("1"))]
+    fn unsupported_versions(#[case] version: serde_json::Value) {
+        assert!(migrate(json!({ "version": version })).is_err());
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// Retry policy of the requests to remote services, e.g. getting a token
+/// from KBS. Requests are not retried by default, which a service opts in
+/// to with its own `max_attempts`, e.g. `token_configs.kbs.max_attempts`,
+/// or all services with `max_attempts` here. Only failures that may pass
+/// by themselves are retried, s.t. the service is unreachable, timed out,
+/// answered 429 or 5xx, or the challenge of KBS went stale. Each attempt
+/// generates new evidence. The `timeout_seconds` of the service limits all
+/// the attempts together.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Attempts of a request including the first one, `1`, the default,
+    /// means no retry. The RCAR handshake with KBS is only retried as given
+    /// here, or by `token_configs.kbs.max_attempts`.
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Milliseconds to wait before the first retry. It is doubled for each
+    /// following retry up to `max_backoff_ms`.
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    /// Milliseconds to wait at most between two attempts.
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// Wait a random time between half and all of the backoff, s.t. the
+    /// agents that failed together, e.g. when KBS restarted, do not retry
+    /// together.
+    #[serde(default = "default_jitter")]
+    pub jitter: bool,
+}
+
+fn default_max_attempts() -> u32 {
+    1
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_jitter() -> bool {
+    true
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            jitter: default_jitter(),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// The policy with the `max_attempts` of a service in place of the
+    /// one here, if it has any.
+    pub fn with_max_attempts(&self, max_attempts: Option<u32>) -> Self {
+        Self {
+            max_attempts: max_attempts.unwrap_or(self.max_attempts),
+            ..self.clone()
+        }
+    }
+
+    /// The time to wait after the given failed attempt, counted from 1.
+    /// `random` in `[0, 1)` picks the jitter, if enabled.
+    pub fn backoff(&self, attempt: u32, random: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(63);
+        let backoff = self
+            .initial_backoff_ms
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff_ms);
+        let backoff = Duration::from_millis(backoff);
+        if !self.jitter {
+            return backoff;
+        }
+
+        backoff.mul_f64(0.5 + random / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rstest::rstest;
+
+    use super::RetryConfig;
+
+    #[rstest]
+    #[case(1, 100)]
+    #[case(2, 200)]
+    #[case(3, 400)]
+    #[case(4, 500)]
+    #[case(40, 500)]
+    #[case(100, 500)]
+    fn exponential_backoff(#[case] attempt: u32, #[case] millis: u64) {
+        let config = RetryConfig {
+            max_attempts: 5,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 500,
+            jitter: false,
+        };
+        assert_eq!(config.backoff(attempt, 0.9), Duration::from_millis(millis));
+    }
+
+    #[test]
+    fn jitter_stays_within_backoff() {
+        let config = RetryConfig {
+            initial_backoff_ms: 400,
+            ..Default::default()
+        };
+        assert_eq!(config.backoff(1, 0.0), Duration::from_millis(200));
+        assert_eq!(config.backoff(1, 0.5), Duration::from_millis(300));
+        assert!(config.backoff(1, 0.999) < Duration::from_millis(400));
+    }
+}
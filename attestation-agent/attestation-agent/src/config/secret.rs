@@ -0,0 +1,228 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Config values that carry secrets, i.e. the proxies with credentials, the
+//! extra headers of the token requests, e.g. bearer tokens, the passphrase
+//! of the CoCoAS client key and the Intel Trust Authority API key. They can
+//! be given as a `file:///path` URI rather than in the config document,
+//! which may be logged or be part of initdata.
+
+use std::{fmt, ops::Deref, os::unix::fs::PermissionsExt};
+
+use config::ConfigError;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use url::Url;
+
+/// Prefix of a value that is read from a file.
+pub const SECRET_FILE_SCHEME: &str = "file://";
+
+/// Permission bits of the group and others, none of which may be set on a
+/// secret file.
+const SHARED_MODE_BITS: u32 = 0o077;
+
+/// A config value given either literally or by the `file://` URI of a file
+/// holding it. The file is read when the configuration is parsed, and again
+/// on each update or reload. The trailing whitespace of the file, e.g. the
+/// newline of an editor, is not part of the value.
+///
+/// The value is serialized as given, s.t. a dumped configuration keeps the
+/// URI rather than the content of the file.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Secret {
+    value: String,
+
+    /// The URI the value is read from, if any
+    uri: Option<String>,
+}
+
+impl Secret {
+    /// Read the value from the file at the `file://` URI. The file must not
+    /// be accessible by the group or others, i.e. have mode `0600` or
+    /// stricter.
+    pub fn from_uri(uri: &str) -> Result<Self, ConfigError> {
+        let path = Url::parse(uri)
+            .ok()
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| ConfigError::Message(format!("`{uri}` is not a file URI")))?;
+
+        let error = |e: std::io::Error| ConfigError::Message(format!("read `{uri}`: {e}"));
+        let mode = std::fs::metadata(&path)
+            .map_err(error)?
+            .permissions()
+            .mode();
+        if mode & SHARED_MODE_BITS != 0 {
+            return Err(ConfigError::Message(format!(
+                "`{uri}` must not be accessible by the group or others, its mode is {:o} \
+                 rather than 600",
+                mode & 0o777
+            )));
+        }
+
+        let mut value = std::fs::read_to_string(&path).map_err(error)?;
+        value.truncate(value.trim_end().len());
+        Ok(Self {
+            value,
+            uri: Some(uri.to_string()),
+        })
+    }
+
+    /// The URI the value is read from, if it is not given literally.
+    pub fn uri(&self) -> Option<&str> {
+        self.uri.as_deref()
+    }
+
+    /// Apply `f` to a literal value, e.g. to redact the credentials in it.
+    /// A value read from a file is kept, as it is serialized as the URI.
+    pub fn map_literal(&self, f: impl FnOnce(&str) -> String) -> Self {
+        match self.uri {
+            Some(_) => self.clone(),
+            None => Self::from(f(&self.value)),
+        }
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Self { value, uri: None }
+    }
+}
+
+impl From<&str> for Secret {
+    fn from(value: &str) -> Self {
+        Self::from(value.to_string())
+    }
+}
+
+impl Deref for Secret {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Only the URI is shown, a literal value is redacted.
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.uri {
+            Some(uri) => f.debug_tuple("Secret").field(uri).finish(),
+            None => f.write_str("Secret(***)"),
+        }
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.uri.as_deref().unwrap_or(&self.value))
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        if value.starts_with(SECRET_FILE_SCHEME) {
+            return Self::from_uri(&value).map_err(de::Error::custom);
+        }
+
+        Ok(Self::from(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{os::unix::fs::PermissionsExt, path::Path};
+
+    use rstest::rstest;
+    use serde::Deserialize;
+
+    use super::Secret;
+
+    fn write_secret(path: &Path, content: &str, mode: u32) -> String {
+        std::fs::write(path, content).unwrap();
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).unwrap();
+        format!("file://{}", path.display())
+    }
+
+    #[rstest]
+    #[case(0o600)]
+    #[case(0o400)]
+    fn read_from_file(#[case] mode: u32) {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = write_secret(&dir.path().join("proxy"), "http://u:p@proxy:3128\n", mode);
+
+        let secret = Secret::from_uri(&uri).unwrap();
+        assert_eq!(&*secret, "http://u:p@proxy:3128");
+        assert_eq!(secret.uri(), Some(uri.as_str()));
+        assert_eq!(
+            serde_json::to_value(&secret).unwrap(),
+            serde_json::Value::String(uri)
+        );
+    }
+
+    #[rstest]
+    #[case(0o640)]
+    #[case(0o604)]
+    #[case(0o644)]
+    fn permissive_mode_is_rejected(#[case] mode: u32) {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = write_secret(&dir.path().join("proxy"), "secret", mode);
+
+        let e = Secret::from_uri(&uri).unwrap_err();
+        assert!(e.to_string().contains("group or others"), "{e}");
+    }
+
+    #[rstest]
+    #[case("file:///nonexistent/proxy", "read `file:///nonexistent/proxy`")]
+    #[case("file://proxy", "is not a file URI")]
+    fn unreadable_uris(#[case] uri: &str, #[case] expected: &str) {
+        let e = Secret::from_uri(uri).unwrap_err();
+        assert!(e.to_string().contains(expected), "{e}");
+    }
+
+    #[derive(Deserialize)]
+    struct Document {
+        proxy: Secret,
+    }
+
+    #[test]
+    fn literal_values_are_kept() {
+        let document: Document = toml::from_str("proxy = \"http://proxy:3128\"").unwrap();
+        assert_eq!(&*document.proxy, "http://proxy:3128");
+        assert_eq!(document.proxy.uri(), None);
+    }
+
+    #[test]
+    fn debug_redacts_literal_values() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = write_secret(&dir.path().join("proxy"), "http://u:p@proxy:3128", 0o600);
+
+        assert_eq!(
+            format!("{:?}", Secret::from("http://u:p@proxy:3128")),
+            "Secret(***)"
+        );
+        let secret = Secret::from_uri(&uri).unwrap();
+        assert_eq!(format!("{secret:?}"), format!("Secret({uri:?})"));
+    }
+
+    #[test]
+    fn missing_file_fails_parsing() {
+        let e = toml::from_str::<Document>("proxy = \"file:///nonexistent/proxy\"")
+            .err()
+            .unwrap();
+        assert!(e.to_string().contains("file:///nonexistent/proxy"), "{e}");
+    }
+
+    #[test]
+    fn map_literal_keeps_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let uri = write_secret(&dir.path().join("proxy"), "secret", 0o600);
+        let redact = |_: &str| "***".to_string();
+
+        let secret = Secret::from_uri(&uri).unwrap().map_literal(redact);
+        assert_eq!(secret.uri(), Some(uri.as_str()));
+        assert_eq!(&*Secret::from("secret").map_literal(redact), "***");
+    }
+}
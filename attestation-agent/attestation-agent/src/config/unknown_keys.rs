@@ -0,0 +1,237 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Keys of a config document that are no field of [`Config`]. Serde ignores
+//! them, s.t. a typo silently leaves the field at its default value.
+
+use std::fmt;
+
+use config::ConfigError;
+use serde_json::{Map, Value};
+use tracing::warn;
+
+use super::Config;
+use crate::telemetry;
+
+/// Sections of the optional features. The same config file is used by any
+/// build, so these are accepted even when the feature is disabled.
+const FEATURE_SECTIONS: &[&str] = &[
+    "token_configs.cert",
+    "token_configs.coco_as",
+    "token_configs.ita",
+    "token_configs.kbs",
+];
+
+/// Section of the options of the attesters, see
+/// [`attester::ATTESTER_OPTION_SECTIONS`].
+const ATTESTER_SECTION: &str = "attester";
+
+/// A key further than this from every field gets no suggestion.
+const MAX_SUGGESTION_DISTANCE: usize = 3;
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct UnknownKey {
+    /// Dotted path of the key in the document, e.g. `token_configs.kbs.ulr`
+    path: String,
+
+    /// The closest field in the same section
+    suggestion: Option<String>,
+}
+
+impl fmt::Display for UnknownKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`", self.path)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " (did you mean `{suggestion}`?)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the keys of the document against the fields of `config` that was
+/// parsed from it. Unknown keys are an error with [`Config::strict`], and
+/// only warned about otherwise.
+pub(super) fn check(document: &Value, config: &Config) -> Result<(), ConfigError> {
+    let known = serde_json::to_value(config).map_err(|e| ConfigError::Foreign(Box::new(e)))?;
+
+    for section in disabled_attester_sections(document, &known) {
+        warn!(
+            target: telemetry::CONFIG,
+            "Ignoring the options `{ATTESTER_SECTION}.{section}`, the attester is not built in"
+        );
+    }
+
+    let unknown = find_unknown(document, &known, "");
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    if config.strict {
+        let keys: Vec<String> = unknown.iter().map(ToString::to_string).collect();
+        return Err(ConfigError::Message(format!(
+            "unknown configuration keys: {}",
+            keys.join(", ")
+        )));
+    }
+
+    for key in &unknown {
+        warn!(
+            target: telemetry::CONFIG,
+            "Ignoring unknown configuration key {key}. Set `strict = true` to reject unknown \
+             keys, which will be the default in a future release."
+        );
+    }
+    Ok(())
+}
+
+fn find_unknown(document: &Value, known: &Value, section: &str) -> Vec<UnknownKey> {
+    let (Value::Object(document), Value::Object(known)) = (document, known) else {
+        return Vec::new();
+    };
+
+    let mut unknown = Vec::new();
+    for (key, value) in document {
+        let path = match section {
+            "" => key.clone(),
+            section => format!("{section}.{key}"),
+        };
+
+        match known.get(key) {
+            Some(known) => unknown.extend(find_unknown(value, known, &path)),
+            None if FEATURE_SECTIONS.contains(&path.as_str()) => {}
+            None if section == ATTESTER_SECTION
+                && ::attester::ATTESTER_OPTION_SECTIONS.contains(&key.as_str()) => {}
+            None => unknown.push(UnknownKey {
+                suggestion: closest_field(key, known),
+                path,
+            }),
+        }
+    }
+
+    unknown
+}
+
+/// Option tables in the document of the attesters that are not built in.
+fn disabled_attester_sections<'a>(document: &'a Value, known: &Value) -> Vec<&'a str> {
+    let Some(Value::Object(options)) = document.get(ATTESTER_SECTION) else {
+        return Vec::new();
+    };
+    let known = known.get(ATTESTER_SECTION);
+
+    options
+        .keys()
+        .map(String::as_str)
+        .filter(|key| ::attester::ATTESTER_OPTION_SECTIONS.contains(key))
+        .filter(|key| known.and_then(|known| known.get(key)).is_none())
+        .collect()
+}
+
+fn closest_field(key: &str, known: &Map<String, Value>) -> Option<String> {
+    known
+        .keys()
+        .map(|field| (edit_distance(key, field), field))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field.clone())
+}
+
+/// Levenshtein distance of the two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::{disabled_attester_sections, edit_distance, find_unknown, UnknownKey};
+
+    #[rstest]
+    #[case("", "", 0)]
+    #[case("eventlog_algoritm", "eventlog_algorithm", 1)]
+    #[case("ulr", "url", 2)]
+    #[case("kitten", "sitting", 3)]
+    fn distance(#[case] a: &str, #[case] b: &str, #[case] expected: usize) {
+        assert_eq!(edit_distance(a, b), expected);
+        assert_eq!(edit_distance(b, a), expected);
+    }
+
+    #[test]
+    fn nested_unknown_keys() {
+        let known = json!({
+            "token_configs": {"cache": false, "kbs": {"url": "", "cert": null}},
+            "eventlog_config": {"eventlog_algorithm": "sha384", "init_pcr": 17},
+        });
+        let document = json!({
+            "token_configs": {"kbs": {"ulr": "https://127.0.0.1:8080", "cert": "cert"}},
+            "eventlog_config": {"eventlog_algoritm": "sha384"},
+            "unrelated": 1,
+        });
+
+        assert_eq!(
+            find_unknown(&document, &known, ""),
+            vec![
+                UnknownKey {
+                    path: "eventlog_config.eventlog_algoritm".into(),
+                    suggestion: Some("eventlog_algorithm".into()),
+                },
+                UnknownKey {
+                    path: "token_configs.kbs.ulr".into(),
+                    suggestion: Some("url".into()),
+                },
+                UnknownKey {
+                    path: "unrelated".into(),
+                    suggestion: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn disabled_feature_sections_are_known() {
+        let known = json!({"token_configs": {"cache": false}});
+        let document = json!({"token_configs": {"coco_as": {"url": ""}, "kbs": {"url": ""}}});
+        assert!(find_unknown(&document, &known, "").is_empty());
+    }
+
+    #[test]
+    fn disabled_attester_sections_are_warned() {
+        let known = json!({"attester": {"allow_fallback": false, "tdx": {"ccel_path": ""}}});
+        let document = json!({
+            "attester": {
+                "tdx": {"ccel_path": "/ccel"},
+                "snp": {"vmpl": 1},
+                "sgx": {"aesm_socket": "/var/run/aesmd/aesm.socket"},
+                "tpm": {},
+            },
+        });
+
+        assert_eq!(
+            disabled_attester_sections(&document, &known),
+            vec!["sgx", "snp"]
+        );
+        assert_eq!(
+            find_unknown(&document, &known, ""),
+            vec![UnknownKey {
+                path: "attester.tpm".into(),
+                suggestion: None,
+            }]
+        );
+    }
+}
@@ -0,0 +1,477 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Checks of the values of a [`Config`] that deserialization cannot tell,
+//! e.g. whether a URL is well formed or a referenced file exists.
+
+#[cfg(any(feature = "kbs", feature = "coco_as"))]
+use std::collections::BTreeMap;
+#[cfg(feature = "kbs")]
+use std::path::Path;
+
+use url::Url;
+
+#[cfg(feature = "cert")]
+use super::cert::SubjectAltName;
+#[cfg(feature = "coco_as")]
+use super::coco_as::CoCoASProtocol;
+#[cfg(feature = "kbs")]
+use super::kbs::MAX_STALE_SESSION_RETRIES;
+#[cfg(any(feature = "kbs", feature = "coco_as"))]
+use super::secret::Secret;
+use super::{Config, EventlogBackend, EventlogRotation};
+#[cfg(feature = "coco_as")]
+use crate::token::transport::Endpoint;
+
+/// The highest PCR index. A TPM has 24 PCRs, and the registers of the other
+/// TEEs are mapped into the same numbering.
+pub const MAX_PCR_INDEX: u64 = 23;
+
+/// Smallest `eventlog_config.max_size_bytes`, which holds the INIT or
+/// `CONTINUE` entry and a few runtime measurements.
+const MIN_EVENTLOG_SIZE: u64 = 1024;
+
+/// Smallest `eventlog_config.max_entries`, the INIT or `CONTINUE` entry
+/// and a runtime measurement.
+const MIN_EVENTLOG_ENTRIES: u64 = 2;
+
+/// A problem of a config field, see [`Config::validate`].
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("`{key}`: {reason}")]
+pub struct ValidationError {
+    /// Dotted path of the field, e.g. `token_configs.kbs.url`
+    pub key: String,
+
+    pub reason: String,
+}
+
+#[derive(Default)]
+struct Errors(Vec<ValidationError>);
+
+impl Errors {
+    fn push(&mut self, key: &str, reason: impl Into<String>) {
+        self.0.push(ValidationError {
+            key: key.to_string(),
+            reason: reason.into(),
+        });
+    }
+
+    #[cfg(any(feature = "kbs", feature = "coco_as", feature = "ita"))]
+    fn check_url(&mut self, key: &str, url: &str, schemes: &[&str]) {
+        if url.is_empty() {
+            return self.push(key, "no URL is configured");
+        }
+
+        // The error of the URL would show the credentials in it
+        let Ok(parsed) = Url::parse(url) else {
+            return self.push(key, "not a valid URL");
+        };
+        if !schemes.contains(&parsed.scheme()) {
+            self.push(
+                key,
+                format!(
+                    "scheme `{}` is not one of {}",
+                    parsed.scheme(),
+                    schemes.join(", ")
+                ),
+            );
+        } else if parsed.host().is_none() {
+            self.push(key, "no host in the URL");
+        }
+    }
+
+    /// The URL of AS, over HTTP(S), or a unix socket or vsock, see
+    /// [`Endpoint`].
+    #[cfg(feature = "coco_as")]
+    fn check_service_url(&mut self, key: &str, url: &str) {
+        if url.is_empty() {
+            return self.push(key, "no URL is configured");
+        }
+        match Endpoint::parse(url) {
+            Ok(Endpoint::Tcp) => self.check_url(key, url, &["http", "https"]),
+            Ok(_) => {}
+            Err(e) => self.push(key, e.to_string()),
+        }
+    }
+
+    /// A `vsock://<cid>:<port>` or `tcp://<host>:<port>` address.
+    fn check_stream_address(&mut self, key: &str, address: &str) {
+        let Ok(parsed) = Url::parse(address) else {
+            return self.push(key, "not a valid address");
+        };
+        match parsed.scheme() {
+            "vsock"
+                if parsed
+                    .host_str()
+                    .is_some_and(|cid| cid.parse::<u32>().is_err()) =>
+            {
+                self.push(key, "the host of a vsock address is a CID")
+            }
+            "vsock" | "tcp" if parsed.host().is_none() || parsed.port().is_none() => {
+                self.push(key, "no host and port in the address")
+            }
+            "vsock" | "tcp" => {}
+            scheme => self.push(key, format!("scheme `{scheme}` is not one of tcp, vsock")),
+        }
+    }
+
+    fn check_pcr(&mut self, key: &str, index: u64) {
+        if index > MAX_PCR_INDEX {
+            self.push(
+                key,
+                format!("PCR index {index} is out of range 0..={MAX_PCR_INDEX}"),
+            );
+        }
+    }
+
+    /// The values are not in the reasons, as they may be credentials.
+    #[cfg(any(feature = "kbs", feature = "coco_as"))]
+    fn check_headers(&mut self, key: &str, headers: &BTreeMap<String, Secret>) {
+        for (name, value) in headers {
+            let key = format!("{key}.{name}");
+            let is_token_char =
+                |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+            if name.is_empty() || !name.chars().all(is_token_char) {
+                self.push(&key, "not a valid header name");
+            } else if RESERVED_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                self.push(&key, "is set by the token protocol");
+            }
+            if !value.chars().all(|c| c == '\t' || (' '..='~').contains(&c)) {
+                self.push(&key, "value has characters other than visible ASCII");
+            }
+        }
+    }
+
+    #[cfg(feature = "kbs")]
+    fn check_readable(&mut self, key: &str, path: &Path) {
+        if let Err(e) = std::fs::File::open(path) {
+            self.push(key, format!("cannot read {}: {e}", path.display()));
+        }
+    }
+}
+
+/// Headers of the token protocols, which are not to be set by
+/// `extra_headers`.
+#[cfg(any(feature = "kbs", feature = "coco_as"))]
+const RESERVED_HEADERS: &[&str] = &[
+    "authorization",
+    "content-length",
+    "content-type",
+    "cookie",
+    "host",
+];
+
+#[cfg(any(feature = "kbs", feature = "coco_as", feature = "ita"))]
+const PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+pub(super) fn validate(config: &Config) -> Vec<ValidationError> {
+    let mut errors = Errors::default();
+
+    let token_configs = &config.token_configs;
+    if token_configs.default_type.as_deref() == Some("") {
+        errors.push("token_configs.default_type", "must not be empty");
+    }
+
+    // Such tokens would be about to expire once cached
+    if token_configs.cache_opaque_max_age_seconds != 0
+        && token_configs.cache_opaque_max_age_seconds <= token_configs.cache_skew_seconds
+    {
+        errors.push(
+            "token_configs.cache_opaque_max_age_seconds",
+            "must be more than token_configs.cache_skew_seconds",
+        );
+    }
+
+    // A section without a URL is not in use, unless it is the default type
+    // that requests without a type go to
+    #[cfg(any(feature = "kbs", feature = "coco_as"))]
+    let in_use = |token_type: &str, has_url: bool| {
+        has_url || token_configs.default_type.as_deref() == Some(token_type)
+    };
+
+    #[cfg(feature = "kbs")]
+    let kbs = &config.token_configs.kbs;
+    #[cfg(feature = "kbs")]
+    if in_use("kbs", !kbs.url.is_empty() || !kbs.urls.is_empty()) {
+        if kbs.urls.is_empty() {
+            errors.check_url("token_configs.kbs.url", &kbs.url, &["http", "https"]);
+        }
+        for (index, url) in kbs.urls.iter().enumerate() {
+            let key = format!("token_configs.kbs.urls.{index}");
+            errors.check_url(&key, url, &["http", "https"]);
+        }
+        if let Some(proxy) = &kbs.proxy {
+            errors.check_url("token_configs.kbs.proxy", proxy, PROXY_SCHEMES);
+        }
+        if let Some(cert_path) = &kbs.cert_path {
+            errors.check_readable("token_configs.kbs.cert_path", cert_path);
+        }
+        errors.check_headers("token_configs.kbs.extra_headers", &kbs.extra_headers);
+        if kbs.max_attempts == Some(0) {
+            errors.push("token_configs.kbs.max_attempts", "must be at least 1");
+        }
+        if kbs.stale_session_retries > MAX_STALE_SESSION_RETRIES {
+            errors.push(
+                "token_configs.kbs.stale_session_retries",
+                format!("must be at most {MAX_STALE_SESSION_RETRIES}"),
+            );
+        }
+        if kbs.auto_refresh {
+            if !config.token_configs.cache {
+                errors.push(
+                    "token_configs.kbs.auto_refresh",
+                    "needs token_configs.cache",
+                );
+            }
+            if kbs.refresh_margin_seconds <= config.token_configs.cache_skew_seconds {
+                errors.push(
+                    "token_configs.kbs.refresh_margin_seconds",
+                    "must be more than token_configs.cache_skew_seconds",
+                );
+            }
+        }
+        if kbs.cache_path.is_some() {
+            if !config.token_configs.cache {
+                errors.push("token_configs.kbs.cache_path", "needs token_configs.cache");
+            }
+            // The token is of no use without the key pair it is bound to
+            if !kbs.persist_tee_keypair {
+                errors.push(
+                    "token_configs.kbs.cache_path",
+                    "needs token_configs.kbs.persist_tee_keypair",
+                );
+            }
+        }
+    }
+
+    #[cfg(feature = "cert")]
+    {
+        let cert = &config.token_configs.cert;
+        for (index, san) in cert.sans.iter().enumerate() {
+            if let Err(e) = SubjectAltName::parse(san) {
+                errors.push(&format!("token_configs.cert.sans.{index}"), e.to_string());
+            }
+        }
+        if cert.kbs_path.trim_matches('/').is_empty() {
+            errors.push("token_configs.cert.kbs_path", "no path is configured");
+        }
+    }
+
+    #[cfg(feature = "coco_as")]
+    let coco_as = &config.token_configs.coco_as;
+    #[cfg(feature = "coco_as")]
+    if in_use("coco_as", !coco_as.url.is_empty()) {
+        match coco_as.service() {
+            Ok((protocol, url)) => {
+                errors.check_service_url("token_configs.coco_as.url", &url);
+                if protocol == CoCoASProtocol::Grpc {
+                    if !cfg!(feature = "coco_as_grpc") {
+                        errors.push(
+                            "token_configs.coco_as.protocol",
+                            "gRPC needs AA built with the `coco_as_grpc` feature",
+                        );
+                    }
+                    if coco_as.proxy.is_some() {
+                        errors.push("token_configs.coco_as.proxy", "is not supported with gRPC");
+                    }
+                    // The gRPC API of AS has no fields for them
+                    if coco_as.audience.is_some() {
+                        errors.push(
+                            "token_configs.coco_as.audience",
+                            "is not supported with gRPC",
+                        );
+                    }
+                    if !coco_as.claims.is_empty() {
+                        errors.push("token_configs.coco_as.claims", "is not supported with gRPC");
+                    }
+                }
+            }
+            Err(e) => errors.push("token_configs.coco_as.url", e.to_string()),
+        }
+        if let Some(proxy) = &coco_as.proxy {
+            errors.check_url("token_configs.coco_as.proxy", proxy, PROXY_SCHEMES);
+        }
+        if let Some(cert_path) = &coco_as.cert_path {
+            errors.check_readable("token_configs.coco_as.cert_path", cert_path);
+        }
+        errors.check_headers(
+            "token_configs.coco_as.extra_headers",
+            &coco_as.extra_headers,
+        );
+        if coco_as.max_attempts == Some(0) {
+            errors.push("token_configs.coco_as.max_attempts", "must be at least 1");
+        }
+
+        // Fail on a certificate that would fail every token request
+        if let Err(e) = coco_as.client_identity() {
+            errors.push("token_configs.coco_as.client_cert", format!("{e:#}"));
+        }
+        if coco_as.cache_path.is_some() && !config.token_configs.cache {
+            errors.push(
+                "token_configs.coco_as.cache_path",
+                "needs token_configs.cache",
+            );
+        }
+    }
+
+    #[cfg(feature = "ita")]
+    if let Some(ita) = &config.token_configs.ita {
+        errors.check_url("token_configs.ita.url", &ita.url, &["http", "https"]);
+        if ita.api_key.is_empty() {
+            errors.push("token_configs.ita.api_key", "no API key is configured");
+        } else if !ita.api_key.chars().all(|c| c.is_ascii_graphic()) {
+            // The key is not in the reason, as it is a credential
+            errors.push(
+                "token_configs.ita.api_key",
+                "has characters other than visible ASCII",
+            );
+        }
+        if let Some(proxy) = &ita.proxy {
+            errors.check_url("token_configs.ita.proxy", proxy, PROXY_SCHEMES);
+        }
+    }
+
+    let eventlog = &config.eventlog_config;
+    errors.check_pcr("eventlog_config.init_pcr", eventlog.init_pcr);
+    errors.check_pcr(
+        "eventlog_config.default_register_index",
+        eventlog.default_register_index,
+    );
+    for (domain, index) in &eventlog.domain_pcr_map {
+        errors.check_pcr(&format!("eventlog_config.domain_pcr_map.{domain}"), *index);
+    }
+    match eventlog.backend {
+        EventlogBackend::File if eventlog.enabled && eventlog.file_path.is_dir() => {
+            errors.push("eventlog_config.file_path", "is a directory");
+        }
+        EventlogBackend::Stream if eventlog.enabled => match &eventlog.stream_address {
+            Some(address) => errors.check_stream_address("eventlog_config.stream_address", address),
+            None => errors.push(
+                "eventlog_config.stream_address",
+                "no address is configured for the `stream` backend",
+            ),
+        },
+        _ => {}
+    }
+    if eventlog.backend != EventlogBackend::File
+        && (eventlog.max_size_bytes.is_some() || eventlog.max_entries.is_some())
+        && eventlog.rotation == EventlogRotation::Rotate
+    {
+        errors.push(
+            "eventlog_config.rotation",
+            format!("the `{}` backend cannot rotate", eventlog.backend),
+        );
+    }
+    if eventlog
+        .max_size_bytes
+        .is_some_and(|max_size| max_size < MIN_EVENTLOG_SIZE)
+    {
+        errors.push(
+            "eventlog_config.max_size_bytes",
+            format!("must be at least {MIN_EVENTLOG_SIZE}"),
+        );
+    }
+    if eventlog
+        .max_entries
+        .is_some_and(|max_entries| max_entries < MIN_EVENTLOG_ENTRIES)
+    {
+        errors.push(
+            "eventlog_config.max_entries",
+            format!("must be at least {MIN_EVENTLOG_ENTRIES}"),
+        );
+    }
+
+    #[cfg(feature = "snp-attester")]
+    {
+        let vmpl = config.attester.options.snp.vmpl;
+        if vmpl > ::attester::snp::MAX_VMPL {
+            errors.push(
+                "attester.snp.vmpl",
+                format!("{vmpl} is out of range 0..={}", ::attester::snp::MAX_VMPL),
+            );
+        }
+    }
+
+    let retry = &config.retry;
+    if retry.max_attempts == 0 {
+        errors.push("retry.max_attempts", "must be at least 1");
+    }
+    if retry.initial_backoff_ms > retry.max_backoff_ms {
+        errors.push(
+            "retry.initial_backoff_ms",
+            format!(
+                "is longer than `max_backoff_ms` of {}",
+                retry.max_backoff_ms
+            ),
+        );
+    }
+
+    errors.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Errors, ValidationError};
+
+    #[cfg(feature = "coco_as")]
+    #[rstest::rstest]
+    #[case("https://as.example.com:8080", None)]
+    #[case("http://127.0.0.1:8080/prefix", None)]
+    #[case("unix:///run/as.sock", None)]
+    #[case("", Some("no URL is configured"))]
+    #[case(
+        "as.example.com:8080",
+        Some("scheme `as.example.com` is not one of http, https, unix, vsock")
+    )]
+    #[case("127.0.0.1:8080", Some("not a valid URL"))]
+    #[case(
+        "ftp://as.example.com",
+        Some("scheme `ftp` is not one of http, https, unix, vsock")
+    )]
+    fn service_url(#[case] url: &str, #[case] reason: Option<&str>) {
+        let mut errors = Errors::default();
+        errors.check_service_url("token_configs.coco_as.url", url);
+        let expected: Vec<ValidationError> = reason
+            .into_iter()
+            .map(|reason| ValidationError {
+                key: "token_configs.coco_as.url".into(),
+                reason: reason.into(),
+            })
+            .collect();
+        assert_eq!(errors.0, expected);
+    }
+
+    #[cfg(any(feature = "kbs", feature = "coco_as"))]
+    #[test]
+    fn credentials_are_not_shown() {
+        let mut errors = Errors::default();
+        errors.check_url(
+            "token_configs.kbs.proxy",
+            "http://user:secret@[::1",
+            &["http"],
+        );
+        assert!(!errors.0[0].to_string().contains("secret"));
+    }
+
+    #[rstest::rstest]
+    #[case("vsock://2:1025", None)]
+    #[case("tcp://collector.example.com:9000", None)]
+    #[case("vsock://host:1025", Some("the host of a vsock address is a CID"))]
+    #[case("tcp://collector.example.com", Some("no host and port in the address"))]
+    #[case("udp://127.0.0.1:9000", Some("scheme `udp` is not one of tcp, vsock"))]
+    #[case("127.0.0.1:9000", Some("not a valid address"))]
+    fn stream_address(#[case] address: &str, #[case] reason: Option<&str>) {
+        let mut errors = Errors::default();
+        errors.check_stream_address("eventlog_config.stream_address", address);
+        let expected: Vec<ValidationError> = reason
+            .into_iter()
+            .map(|reason| ValidationError {
+                key: "eventlog_config.stream_address".into(),
+                reason: reason.into(),
+            })
+            .collect();
+        assert_eq!(errors.0, expected);
+    }
+}
@@ -0,0 +1,257 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::ops::RangeInclusive;
+
+use kbs_types::Tee;
+use thiserror::Error;
+
+use crate::{
+    config::{HashAlgorithm, ValidationError},
+    eventlog::{EventEntryError, EventLogFull, EventLogStorageError},
+};
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors of the [`crate::AttestationAPIs`]. The variants carry the
+/// context to act on, e.g. the URL of the service or the register index,
+/// while the underlying cause is kept as the source.
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("invalid AA configuration")]
+    ConfigParse(#[source] anyhow::Error),
+
+    #[error("invalid AA configuration: {}", join_errors(.0))]
+    ConfigInvalid(Vec<ValidationError>),
+
+    /// The field is used by the eventlog and registers that are already set
+    /// up, so it can only change with a restart of AA.
+    #[error("`{0}` cannot be changed while AA is running")]
+    ConfigImmutable(&'static str),
+
+    #[error("failed to watch the AA config file")]
+    ConfigWatch(#[source] notify::Error),
+
+    #[error("TEE type {0:?} is not available on current platform, or the attester is not enabled in this build")]
+    TeeUnavailable(Tee),
+
+    #[error("unknown device attester `{0}`")]
+    UnknownDeviceAttester(String),
+
+    #[error("unsupported token type `{0}`")]
+    UnsupportedTokenType(String),
+
+    /// The token service does not appraise the evidence of the TEE, e.g.
+    /// Intel Trust Authority the one of TEEs other than TDX and SGX.
+    #[error("{token_type} tokens cannot be got for the evidence of {tee:?}")]
+    UnsupportedTokenTee { token_type: String, tee: Tee },
+
+    #[error("failed to get {token_type} token from {url}")]
+    TokenFetch {
+        token_type: String,
+        url: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// The token service, or the registered token getter, returned a token
+    /// that cannot be parsed into a [`crate::TokenResponse`].
+    #[error("invalid {token_type} token")]
+    InvalidToken {
+        token_type: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// Attestation Service does not know a policy to check the evidence
+    /// against, see `token_configs.coco_as.policy_ids`.
+    #[error("CoCoAS at {url} does not know the policies {policy_ids:?}")]
+    UnknownPolicy {
+        url: String,
+        policy_ids: Vec<String>,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    #[error("{tee:?} attester failed to {operation}")]
+    AttesterFailure {
+        tee: Tee,
+        operation: &'static str,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// The attester of current platform, or the AA configuration, does not
+    /// allow the operation.
+    #[error("{0} is not supported")]
+    Unsupported(&'static str),
+
+    #[error("device attester {class} failed")]
+    DeviceAttesterFailure {
+        class: String,
+        #[source]
+        source: anyhow::Error,
+    },
+
+    #[error("invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error(
+        "runtime data is {got} bytes, longer than the {max} bytes report data of current platform"
+    )]
+    RuntimeDataTooLong { max: usize, got: usize },
+
+    #[error("{algorithm} digest of the initdata is {got} bytes, but the host data field of {tee:?} takes at most {max} bytes")]
+    InitdataDigestTooLong {
+        tee: Tee,
+        algorithm: HashAlgorithm,
+        max: usize,
+        got: usize,
+    },
+
+    #[error("initdata declares unsupported digest algorithm `{0}`")]
+    InitdataAlgorithm(String),
+
+    #[error("register index {register_index} is not supported on {tee:?}, {}", valid_registers(.valid))]
+    UnsupportedRegister {
+        tee: Tee,
+        register_index: u64,
+        valid: Option<RangeInclusive<u64>>,
+    },
+
+    #[error("{algorithm} eventlog is not supported on {tee:?}, {}", register_banks(.supported))]
+    UnsupportedRegisterBank {
+        tee: Tee,
+        algorithm: HashAlgorithm,
+        supported: Vec<HashAlgorithm>,
+    },
+
+    #[error("attestation agent is shut down")]
+    Shutdown,
+
+    #[error("attestation agent is not initialized, `init()` must be called first")]
+    NotInitialized,
+
+    #[error("invalid runtime measurement event {index}")]
+    InvalidEvent {
+        index: usize,
+        #[source]
+        source: EventEntryError,
+    },
+
+    #[error(transparent)]
+    BatchExtend(#[from] crate::BatchExtendError),
+
+    #[error("{operation} timed out after {seconds} seconds waiting for {endpoint}")]
+    Timeout {
+        operation: &'static str,
+        endpoint: String,
+        seconds: u64,
+    },
+
+    #[error("eventlog does not replay to the current value of register {register_index}{}", diverging_entry(.entry))]
+    EventLogMismatch {
+        register_index: u64,
+
+        /// Index of the first entry that is not extended into the register,
+        /// if the entries before it replay to its value.
+        entry: Option<usize>,
+    },
+
+    #[error("eventlog I/O failed")]
+    EventLogIo(#[source] anyhow::Error),
+
+    /// The register is extended, but the entries are not written to the
+    /// storage yet, see [`EventLogStorageError`].
+    #[error(transparent)]
+    EventLogStorage(#[from] EventLogStorageError),
+
+    #[error("eventlog of register {register_index} has {recorded} entries, which cannot be mixed with {algorithm} ones as {tee:?} does not have both register banks")]
+    EventLogAlgorithmMix {
+        tee: Tee,
+        register_index: u64,
+        recorded: HashAlgorithm,
+        algorithm: HashAlgorithm,
+    },
+
+    /// The runtime measurement is refused before the register is extended.
+    #[error(transparent)]
+    EventLogFull(#[from] EventLogFull),
+
+    #[error("failed to serialize evidence")]
+    Serialize(#[from] serde_json::Error),
+}
+
+fn valid_registers(valid: &Option<RangeInclusive<u64>>) -> String {
+    match valid {
+        Some(registers) => format!(
+            "valid values are {}..={}",
+            registers.start(),
+            registers.end()
+        ),
+        None => "the attester cannot extend runtime measurement".into(),
+    }
+}
+
+fn register_banks(supported: &[HashAlgorithm]) -> String {
+    let banks: Vec<String> = supported.iter().map(ToString::to_string).collect();
+    format!("the registers have the {} banks", banks.join(", "))
+}
+
+fn diverging_entry(entry: &Option<usize>) -> String {
+    match entry {
+        Some(entry) => format!(", diverging from entry {entry}"),
+        None => String::new(),
+    }
+}
+
+fn join_errors(errors: &[ValidationError]) -> String {
+    let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+    errors.join("; ")
+}
+
+impl Error {
+    /// Wrap an error returned by the attester. Capabilities the attester
+    /// does not have are reported as [`Error::Unsupported`].
+    pub(crate) fn attester(tee: Tee, operation: &'static str, source: anyhow::Error) -> Self {
+        match source.downcast_ref::<attester::AttesterError>() {
+            Some(attester::AttesterError::Unsupported(what)) => Self::Unsupported(*what),
+            None => Self::AttesterFailure {
+                tee,
+                operation,
+                source,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+    use attester::AttesterError;
+    use kbs_types::Tee;
+
+    use super::Error;
+
+    #[test]
+    fn attester_error() {
+        let e = Error::attester(
+            Tee::Sample,
+            "derive key",
+            AttesterError::Unsupported("key derivation").into(),
+        );
+        assert!(matches!(e, Error::Unsupported("key derivation")));
+
+        let e = Error::attester(Tee::Tdx, "get evidence", anyhow!("quote device missing"));
+        assert!(matches!(e, Error::AttesterFailure { tee: Tee::Tdx, .. }));
+
+        // Existing callers using anyhow keep the whole chain
+        let e: anyhow::Error = e.into();
+        assert_eq!(
+            format!("{e:#}"),
+            "Tdx attester failed to get evidence: quote device missing"
+        );
+    }
+}
@@ -3,95 +3,2348 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{fmt::Display, fs::File, io::Write};
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{SecondsFormat, Utc};
 use const_format::concatcp;
+use nix::errno::Errno;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::sync::{Mutex, MutexGuard};
+use tracing::warn;
 
-use crate::config::HashAlgorithm;
+use crate::{
+    config::{
+        EventlogBackend, EventlogConfig, EventlogFormat, EventlogRecovery, EventlogRotation,
+        EventlogSync, HashAlgorithm,
+    },
+    telemetry,
+};
+
+pub use aael::cel;
+pub mod combined;
+pub mod firmware;
+pub mod index;
+pub mod writer;
+
+pub use aael::{
+    diverging_bank_entry, diverging_entry, replay, replay_bank, split_metadata, AaelHeader,
+    EntryMetadata, EventEntry, EventEntryError,
+};
+use aael::{
+    entry_algorithm, is_header, AAEL_VERSION, CONTINUE_ENTRY_PREFIX, FIELD_SEPARATOR,
+    INIT_ENTRY_PREFIX,
+};
+use index::{EventFilter, EventIndex, IndexedEntry};
+use writer::{EventLogWriter, FileWriter, LineLocation};
 
 /// AA's eventlog will be put into this parent directory
 pub const EVENTLOG_PARENT_DIR_PATH: &str = "/run/attestation-agent";
 
-/// AA's eventlog will be stored inside the file
+/// AA's eventlog will be stored inside the file, unless
+/// `eventlog_config.file_path` is set
 pub const EVENTLOG_PATH: &str = concatcp!(EVENTLOG_PARENT_DIR_PATH, "/eventlog");
 
+/// The entries do not fit in `eventlog_config.max_size_bytes` or
+/// `max_entries`.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EventLogFull {
+    #[error("eventlog would exceed its limit of {max_size} bytes")]
+    Size { max_size: u64 },
+
+    #[error("eventlog would exceed its limit of {max_entries} entries")]
+    Entries { max_entries: u64 },
+}
+
+/// A snapshot of the eventlogs, see [`RegisterLogs::snapshot`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventLogSnapshot {
+    /// The eventlog files are copied, the one of `init_pcr` to the
+    /// destination, and those of the other registers next to it with the
+    /// suffix `.pcr<register index>`.
+    Files(Vec<PathBuf>),
+
+    /// The lines of the eventlogs, which are not kept in files, each with
+    /// its line break, in order of their register.
+    Content(String),
+}
+
+/// Writing the recorded entries to the storage of the eventlog failed,
+/// e.g. as it is full. The entries that are not written are discarded, and
+/// their events are not extended.
+#[derive(Error, Debug)]
+#[error("eventlog storage failed: {kind}")]
+pub struct EventLogStorageError {
+    pub kind: StorageErrorKind,
+    #[source]
+    pub source: anyhow::Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, strum::Display)]
+pub enum StorageErrorKind {
+    /// `ENOSPC` or `EDQUOT`
+    #[strum(serialize = "no space left")]
+    NoSpace,
+
+    /// `EIO`
+    #[strum(serialize = "I/O error")]
+    Io,
+}
+
+impl StorageErrorKind {
+    /// The label of the kind in metrics.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::NoSpace => "no_space",
+            Self::Io => "io",
+        }
+    }
+}
+
+/// The writer of an eventlog, which is shared with the blocking thread that
+/// flushes it, s.t. it is kept if the future waiting for the flush is
+/// dropped.
+type SharedWriter = Arc<Mutex<Box<dyn EventLogWriter>>>;
+
+/// Lock the writer without waiting, as the [`EventLog`] is borrowed by the
+/// caller. It is only locked by a blocking thread that a dropped future
+/// was waiting for, see [`on_blocking_thread`].
+fn lock_writer(writer: &SharedWriter) -> Result<MutexGuard<'_, Box<dyn EventLogWriter>>> {
+    writer
+        .try_lock()
+        .context("the eventlog is still being written for a cancelled call")
+}
+
+/// Report a failure of the writer due to its storage as an
+/// [`EventLogStorageError`], and other failures as they are.
+fn storage_error(e: anyhow::Error) -> anyhow::Error {
+    let kind = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .and_then(|e| match e.kind() {
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => Some(StorageErrorKind::NoSpace),
+            _ if e.raw_os_error() == Some(Errno::EIO as i32) => Some(StorageErrorKind::Io),
+            _ => None,
+        });
+    match kind {
+        Some(kind) => EventLogStorageError { kind, source: e }.into(),
+        None => e,
+    }
+}
+
+/// Limits of the eventlog file, see [`EventlogConfig::max_size_bytes`] and
+/// [`EventlogConfig::max_entries`].
+struct Limit {
+    max_size: Option<u64>,
+    max_entries: Option<u64>,
+    rotation: EventlogRotation,
+
+    /// Value of the register before the INIT entry, which the first file
+    /// replays from.
+    initial: Vec<u8>,
+}
+
 pub struct EventLog {
-    file: File,
+    /// `None` if the eventlog is disabled, s.t. nothing is recorded.
+    writer: Option<SharedWriter>,
+    format: EventlogFormat,
+
+    /// Hash algorithm the entries are extended with, of the digests in CEL
+    /// records, and of the register value in the `CONTINUE` entry.
+    algorithm: HashAlgorithm,
+
+    /// Hash algorithm of the entries in the file that do not record one,
+    /// and of the chain digests. It differs from `algorithm` when the file
+    /// was started with another `eventlog_config.eventlog_algorithm`.
+    file_algorithm: HashAlgorithm,
+
+    /// Hash algorithms the entries are extended with, including the ones
+    /// of rotated files.
+    algorithms: Vec<HashAlgorithm>,
+
+    /// Whether the INIT entry has been recorded.
+    initialized: bool,
+
+    /// Size of the lines in bytes, as in the file
+    size: u64,
+
+    /// Number of entries in the file, which is the `recnum` of the next CEL
+    /// record.
+    entries: u64,
+
+    /// Whether each entry is recorded with a timestamp in its
+    /// [`EntryMetadata`].
+    record_timestamps: bool,
+
+    /// Whether each entry is recorded with the chain digest of the entries
+    /// before it in its [`EntryMetadata`].
+    chain_entries: bool,
+
+    /// Sequence number of the next entry, which counts on over rotations
+    /// and restarts.
+    seq: u64,
+
+    /// Chain digest of the entries so far, which the next entry records,
+    /// see [`chain_digest`].
+    chain: Vec<u8>,
+
+    /// Register the entries are extended into.
+    register_index: u64,
+
+    /// Header of the AAEL file, `None` for CEL, an empty file, or a legacy
+    /// AAEL file without one.
+    header: Option<AaelHeader>,
+
+    /// Entries in the file, with the digest extended into the register for
+    /// each, as given when it was recorded.
+    index: EventIndex,
+
+    limit: Option<Limit>,
+
+    /// The state before the last entry was written, see
+    /// [`EventLog::discard_last`].
+    checkpoint: Option<Checkpoint>,
+}
+
+/// The state of an [`EventLog`] before an entry is written, s.t. the entry
+/// can be discarded.
+struct Checkpoint {
+    /// Where the lines of the entry start, with the header of the file if
+    /// it is written with the entry.
+    location: LineLocation,
+    initialized: bool,
+    entries: u64,
+    seq: u64,
+    chain: Vec<u8>,
+    header: Option<AaelHeader>,
+    algorithms: usize,
 }
 
 impl EventLog {
-    pub fn new() -> Result<Self> {
-        std::fs::create_dir_all(EVENTLOG_PARENT_DIR_PATH).context("create eventlog parent dir")?;
-        let file = File::create(EVENTLOG_PATH).context("create eventlog")?;
-        Ok(Self { file })
+    /// Open the eventlog as configured. `initial` is the value of the
+    /// register of the eventlog before anything is extended, which a rotated
+    /// eventlog is replayed from.
+    pub fn new(config: &EventlogConfig, initial: Vec<u8>) -> Result<Self> {
+        let writer = writer::open(config, &config.file_path)?;
+        let mut eventlog = Self::continue_writer(
+            writer,
+            config.format,
+            config.eventlog_algorithm,
+            config.on_corrupt,
+            config.init_pcr,
+        )?;
+        eventlog.record_timestamps = config.record_timestamps;
+        eventlog.chain_entries = config.chain_entries;
+        eventlog.limit =
+            (config.max_size_bytes.is_some() || config.max_entries.is_some()).then(|| Limit {
+                max_size: config.max_size_bytes,
+                max_entries: config.max_entries,
+                rotation: config.rotation,
+                initial,
+            });
+        Ok(eventlog)
+    }
+
+    /// Open the eventlog file at the given path, see [`FileWriter::open`].
+    /// Entries of an existing eventlog are kept, as the measurement register
+    /// still carries them, e.g. when AA restarts. An existing eventlog that
+    /// is not well formed is an error, see [`check_existing`].
+    pub fn create(path: &Path, sync: EventlogSync) -> Result<Self> {
+        Self::with_writer(
+            Box::new(FileWriter::open(path, sync)?),
+            EventlogFormat::Aael,
+            HashAlgorithm::default(),
+            EventlogRecovery::Error,
+        )
+    }
+
+    /// The eventlog kept by the writer, which continues from the lines the
+    /// writer already has.
+    pub fn with_writer(
+        writer: Box<dyn EventLogWriter>,
+        format: EventlogFormat,
+        algorithm: HashAlgorithm,
+        recovery: EventlogRecovery,
+    ) -> Result<Self> {
+        Self::continue_writer(
+            writer,
+            format,
+            algorithm,
+            recovery,
+            crate::DEFAULT_PCR_INDEX,
+        )
     }
 
-    pub fn write_log(&mut self, log: &str) -> Result<()> {
-        writeln!(self.file, "{log}").context("failed to write log")?;
-        self.file
-            .flush()
-            .context("failed to flush log to I/O media")?;
+    fn continue_writer(
+        mut writer: Box<dyn EventLogWriter>,
+        format: EventlogFormat,
+        algorithm: HashAlgorithm,
+        recovery: EventlogRecovery,
+        register_index: u64,
+    ) -> Result<Self> {
+        let existing = writer.read_all().and_then(|lines| {
+            let checked = check_existing(&lines, format, algorithm, register_index, 0)?;
+            Ok((checked, lines))
+        });
+        let (size, existing) = match existing {
+            Ok((checked, lines)) => (
+                lines.iter().map(|line| line_len(line)).sum::<u64>(),
+                checked,
+            ),
+            Err(e) if recovery == EventlogRecovery::Archive => {
+                let archived = writer.archive()?;
+                warn!(
+                    target: telemetry::EVENTLOG,
+                    error = format!("{e:#}"),
+                    archived = %archived,
+                    "Existing eventlog is corrupt, archived it and start a new one. It no \
+                     longer replays to the registers extended before."
+                );
+                (0, ExistingLog::empty(algorithm))
+            }
+            Err(e) => return Err(e.context("existing eventlog is corrupt")),
+        };
+        Ok(Self {
+            writer: Some(Arc::new(Mutex::new(writer))),
+            format,
+            algorithm,
+            file_algorithm: existing.file_algorithm,
+            algorithms: existing.algorithms,
+            initialized: existing.entries > 0,
+            size,
+            entries: existing.entries,
+            record_timestamps: false,
+            chain_entries: false,
+            seq: existing.seq,
+            chain: existing.chain,
+            register_index,
+            header: existing.header,
+            index: existing.index,
+            limit: None,
+            checkpoint: None,
+        })
+    }
+
+    /// An eventlog that records nothing, for platforms without registers to
+    /// extend runtime measurements into.
+    pub fn disabled() -> Self {
+        Self {
+            writer: None,
+            format: EventlogFormat::Aael,
+            algorithm: HashAlgorithm::default(),
+            file_algorithm: HashAlgorithm::default(),
+            algorithms: Vec::new(),
+            initialized: false,
+            size: 0,
+            entries: 0,
+            record_timestamps: false,
+            chain_entries: false,
+            seq: 0,
+            chain: Vec::new(),
+            register_index: 0,
+            header: None,
+            index: EventIndex::default(),
+            limit: None,
+            checkpoint: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized
+    }
+
+    /// Hash algorithms the entries are extended with. There is more than
+    /// one if `eventlog_config.eventlog_algorithm` was changed.
+    pub fn algorithms(&self) -> &[HashAlgorithm] {
+        &self.algorithms
+    }
+
+    /// Major version of the AAEL format of the file, which is 0 for a
+    /// legacy file without an [`AaelHeader`].
+    pub fn version(&self) -> u32 {
+        self.header.as_ref().map_or(0, |header| header.version.0)
+    }
+
+    /// Record the INIT entry, which must be the first entry. It is a batch
+    /// of its own.
+    pub fn write_init_log(&mut self, log: &str, register_index: u64, digest: &[u8]) -> Result<()> {
+        self.write_log(log, register_index, digest)?;
+        self.end_batch()?;
+        self.initialized = true;
+        Ok(())
+    }
+
+    /// Check that the entries fit in the limits before they are extended,
+    /// s.t. a full eventlog does not fail a runtime measurement after the
+    /// register was extended. With [`EventlogRotation::Rotate`] each entry
+    /// only has to fit in a new file, after the `CONTINUE` entry.
+    pub fn check_capacity(
+        &self,
+        entries: &[EventEntry<'_>],
+        register_index: u64,
+    ) -> Result<(), EventLogFull> {
+        let Some(limit) = self.limit.as_ref().filter(|_| self.is_enabled()) else {
+            return Ok(());
+        };
+
+        let (added, first) = match limit.rotation {
+            EventlogRotation::Error => (entries.len() as u64, self.entries),
+            EventlogRotation::Rotate => (u64::from(!entries.is_empty()), 1),
+        };
+        if let Some(max_entries) = limit.max_entries.filter(|max| first + added > *max) {
+            return Err(EventLogFull::Entries { max_entries });
+        }
+        let Some(max_size) = limit.max_size else {
+            return Ok(());
+        };
+
+        let full = EventLogFull::Size { max_size };
+        let lines = entries.iter().zip(0..).map(|(entry, offset)| {
+            let digest = entry.digest_with(self.algorithm);
+            let line = self.serialize(
+                offset,
+                &entry.to_string(),
+                Some((register_index, digest.as_slice())),
+            );
+            line_len(&line)
+        });
+        match limit.rotation {
+            EventlogRotation::Error => {
+                if self.size + lines.sum::<u64>() > max_size {
+                    return Err(full);
+                }
+            }
+            EventlogRotation::Rotate => {
+                let header = self
+                    .new_header()
+                    .map_or(0, |header| line_len(&header.to_string()))
+                    + line_len(&self.serialize(0, &self.continue_entry(&limit.initial), None));
+                if lines.into_iter().any(|line| header + line > max_size) {
+                    return Err(full);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Share of the limit of the eventlog file that is used, of the limit
+    /// closest to be reached. `None` without limits.
+    pub fn usage(&self) -> Option<f64> {
+        let limit = self.limit.as_ref().filter(|_| self.is_enabled())?;
+        let size = limit.max_size.map(|max| self.size as f64 / max as f64);
+        let entries = limit
+            .max_entries
+            .map(|max| self.entries as f64 / max as f64);
+        size.into_iter().chain(entries).reduce(f64::max)
+    }
+
+    /// Whether the file is rotated once it reaches its limit, rather than
+    /// refusing entries.
+    pub fn rotates(&self) -> bool {
+        self.limit
+            .as_ref()
+            .is_some_and(|limit| limit.rotation == EventlogRotation::Rotate)
+    }
+
+    /// Record the entry, which is extended into the register of
+    /// `register_index` with `digest`.
+    pub fn write_log(&mut self, log: &str, register_index: u64, digest: &[u8]) -> Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let extended = Some((register_index, digest));
+        let rotate = self.limit.as_ref().is_some_and(|limit| {
+            let line = self.serialize(0, log, extended);
+            limit.rotation == EventlogRotation::Rotate
+                && self.size > 0
+                && (limit
+                    .max_size
+                    .is_some_and(|max_size| self.size + line_len(&line) > max_size)
+                    || limit
+                        .max_entries
+                        .is_some_and(|max_entries| self.entries >= max_entries))
+        });
+        if rotate {
+            self.rotate()?;
+        }
+        self.checkpoint = Some(Checkpoint {
+            location: LineLocation {
+                index: self.entries as usize + usize::from(self.header.is_some()),
+                offset: self.size,
+                len: 0,
+            },
+            initialized: self.initialized,
+            entries: self.entries,
+            seq: self.seq,
+            chain: self.chain.clone(),
+            header: self.header.clone(),
+            algorithms: self.algorithms.len(),
+        });
+        self.append(log, extended)
+    }
+
+    /// Discard the entry written last with [`EventLog::write_log`], e.g. as
+    /// its digest failed to be extended, s.t. the eventlog keeps replaying
+    /// to the register. The writer drops its lines on a blocking thread.
+    pub async fn discard_last(&mut self) -> Result<()> {
+        let (Some(writer), Some(checkpoint)) = (self.writer.clone(), self.checkpoint.take()) else {
+            return Ok(());
+        };
+        let location = checkpoint.location;
+        on_blocking_thread(&writer, move |writer| writer.truncate(location)).await?;
+
+        self.initialized = checkpoint.initialized;
+        self.size = location.offset;
+        self.entries = checkpoint.entries;
+        self.seq = checkpoint.seq;
+        self.chain = checkpoint.chain;
+        self.header = checkpoint.header;
+        self.algorithms.truncate(checkpoint.algorithms);
+        self.index.truncate(location.index);
         Ok(())
     }
+
+    fn append(&mut self, log: &str, extended: Option<(u64, &[u8])>) -> Result<()> {
+        let line = self.serialize(0, log, extended);
+        let header = self.new_header().filter(|_| self.size == 0);
+        let Some(writer) = self.writer.clone() else {
+            return Ok(());
+        };
+        let mut writer = lock_writer(&writer)?;
+        if let Some(header) = header {
+            writer.append(&header.to_string())?;
+            self.size += line_len(&header.to_string());
+            self.header = Some(header);
+        }
+        writer.append(&line)?;
+        if extended.is_some() && !self.algorithms.contains(&self.algorithm) {
+            self.algorithms.push(self.algorithm);
+        }
+        let location = LineLocation {
+            index: self.entries as usize + usize::from(self.header.is_some()),
+            offset: self.size,
+            len: line.len(),
+        };
+        self.index
+            .push(log, location, self.seq, self.algorithm, extended.is_some());
+        // A CEL record carries the digest, while the digest of an AAEL
+        // entry is calculated from it
+        if let Some((_, digest)) = extended {
+            if self.format == EventlogFormat::Aael
+                && digest != self.algorithm.digest(log.as_bytes())
+            {
+                self.index.keep_digest(location.index, digest);
+            }
+        }
+        self.size += line_len(&line);
+        self.entries += 1;
+        self.seq += 1;
+        self.chain = chain_digest(self.file_algorithm, &self.chain, log);
+        Ok(())
+    }
+
+    /// The header a new AAEL file starts with, `None` for CEL.
+    fn new_header(&self) -> Option<AaelHeader> {
+        (self.format == EventlogFormat::Aael).then_some(AaelHeader {
+            version: AAEL_VERSION,
+            algorithm: self.algorithm,
+            init_pcr: self.register_index,
+        })
+    }
+
+    /// The line in the file of the entry `offset` entries after the next
+    /// one, see [`EventlogFormat`]. Extended entries come with the register
+    /// and the digest extended into it.
+    fn serialize(&self, offset: u64, log: &str, extended: Option<(u64, &[u8])>) -> String {
+        // An AAEL entry records its algorithm if the file was started with
+        // another one, while a CEL record has it with its digest
+        let algorithm = (self.format == EventlogFormat::Aael
+            && self.algorithm != self.file_algorithm)
+            .then_some(self.algorithm);
+        let metadata =
+            (self.record_timestamps || self.chain_entries || algorithm.is_some()).then(|| {
+                EntryMetadata {
+                    seq: self.seq + offset,
+                    timestamp: self
+                        .record_timestamps
+                        .then(|| Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+                    prev_chain_digest: self.chain_entries.then(|| hex::encode(&self.chain)),
+                    algorithm,
+                }
+            });
+        match self.format {
+            EventlogFormat::Aael => match metadata {
+                Some(metadata) => format!("{metadata}{log}"),
+                None => log.to_string(),
+            },
+            EventlogFormat::Cel => {
+                let extended = extended.map(|(pcr, digest)| (pcr, self.algorithm, digest));
+                let mut record = cel::Record::new(self.entries + offset, log, extended);
+                record.metadata = metadata;
+                record.to_line()
+            }
+        }
+    }
+
+    /// Move the entries aside with [`EventLogWriter::rotate`], e.g. rename
+    /// the file to the next free index, and continue with a `CONTINUE`
+    /// entry.
+    fn rotate(&mut self) -> Result<()> {
+        let Some(limit) = &self.limit else {
+            return Ok(());
+        };
+        let register = self.replay(self.algorithm, limit.initial.clone())?;
+        let header = self.continue_entry(&register)?;
+
+        if let Some(writer) = &self.writer {
+            lock_writer(writer)?.rotate()?;
+        }
+        self.size = 0;
+        self.entries = 0;
+        self.header = None;
+        self.file_algorithm = self.algorithm;
+        self.index.clear();
+        self.append(&header, None)
+    }
+
+    /// The first entry of the file that continues from the current one,
+    /// whose entries replay to `register`.
+    fn continue_entry(&self, register: &[u8]) -> Result<String> {
+        let algorithm = self.algorithm;
+        let rotated = match &self.writer {
+            Some(writer) => lock_writer(writer)?.rotated_name().unwrap_or_default(),
+            None => String::new(),
+        };
+        Ok(format!(
+            "{CONTINUE_ENTRY_PREFIX}{algorithm}/{} {rotated}",
+            hex::encode(register)
+        ))
+    }
+
+    /// Mark the end of a batch of entries, which are flushed by the
+    /// writer, e.g. synced to the storage with [`EventlogSync::OnBatch`].
+    /// A failure of the storage is an [`EventLogStorageError`].
+    pub fn end_batch(&mut self) -> Result<()> {
+        match &self.writer {
+            Some(writer) => lock_writer(writer)?.flush().map_err(storage_error),
+            None => Ok(()),
+        }
+    }
+
+    /// Like [`EventLog::end_batch`], but the writer is flushed on a
+    /// blocking thread, s.t. the I/O does not block the async runtime.
+    pub async fn end_batch_async(&mut self) -> Result<()> {
+        let Some(writer) = self.writer.clone() else {
+            return Ok(());
+        };
+        on_blocking_thread(&writer, |writer| writer.flush()).await
+    }
+
+    /// Flush the recorded entries to the storage and close the writer.
+    /// Nothing is recorded afterwards.
+    pub fn close(&mut self) -> Result<()> {
+        match self.writer.take() {
+            Some(writer) => lock_writer(&writer)?.close().map_err(storage_error),
+            None => Ok(()),
+        }
+    }
+
+    /// Like [`EventLog::close`], but the writer is closed on a blocking
+    /// thread.
+    pub async fn close_async(&mut self) -> Result<()> {
+        let Some(writer) = self.writer.take() else {
+            return Ok(());
+        };
+        on_blocking_thread(&writer, |writer| writer.close()).await
+    }
+
+    /// Read back the recorded entries in order, skipping the first
+    /// `start_index` ones. The [`AaelHeader`] is not an entry.
+    pub fn read_log(&self, start_index: usize) -> Result<Vec<String>> {
+        let header = usize::from(self.header.is_some());
+        Ok(self
+            .read_lines()?
+            .into_iter()
+            .skip(header + start_index)
+            .collect())
+    }
+
+    /// Read back the recorded lines as in the file, including the
+    /// [`AaelHeader`].
+    pub fn read_lines(&self) -> Result<Vec<String>> {
+        let Some(writer) = &self.writer else {
+            return Ok(Vec::new());
+        };
+        lock_writer(writer)?
+            .read_all()
+            .context("failed to read eventlog")
+    }
+
+    /// Read back the AAEL entries of the recorded lines in order, as
+    /// [`replay`] takes them. The digest of each CEL record is checked
+    /// against its entry.
+    pub fn read_entries(&self) -> Result<Vec<String>> {
+        Ok(self
+            .read_algorithm_entries()?
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    /// Read back the AAEL entries with the hash algorithm each is extended
+    /// with, as [`replay_bank`] takes them.
+    pub fn read_algorithm_entries(&self) -> Result<Vec<(HashAlgorithm, String)>> {
+        self.read_log(0)?
+            .iter()
+            .map(|line| {
+                let algorithm = self.line_algorithm(line);
+                Ok((algorithm, parse_entry(line, self.format, algorithm)?))
+            })
+            .collect()
+    }
+
+    /// The hash algorithm the entry of the line is extended with.
+    fn line_algorithm(&self, line: &str) -> HashAlgorithm {
+        recorded_algorithm(line, self.format).unwrap_or(self.file_algorithm)
+    }
+
+    /// The recorded entries with the digests they were extended with, see
+    /// [`EventRecord`].
+    pub fn records(&self) -> Result<Vec<EventRecord>> {
+        let lines = self.read_log(0)?;
+        if lines.len() != self.index.entries().len() {
+            bail!("the eventlog was changed by another process");
+        }
+        lines
+            .iter()
+            .zip(self.index.entries())
+            .map(|(line, indexed)| self.record(&self.index, line, indexed))
+            .collect()
+    }
+
+    /// The recorded entries that match the filter, found with the index
+    /// s.t. only their lines are read back.
+    pub fn query(&self, filter: &EventFilter) -> Result<Vec<EventRecord>> {
+        let Some(writer) = &self.writer else {
+            return Ok(Vec::new());
+        };
+        if filter
+            .register_index
+            .is_some_and(|register_index| register_index != self.register_index)
+        {
+            return Ok(Vec::new());
+        }
+
+        let writer = lock_writer(writer)?;
+        let mut records = self.query_rotated(&**writer, filter)?;
+        let matching: Vec<_> = self.index.matching(filter).collect();
+        let locations: Vec<_> = matching.iter().map(|indexed| indexed.location).collect();
+        let lines = writer
+            .read_lines(&locations)
+            .context("failed to read eventlog")?;
+        for (line, indexed) in lines.iter().zip(matching) {
+            records.push(self.record(&self.index, line, indexed)?);
+        }
+        Ok(records)
+    }
+
+    /// The records of the entries of the rotated files that match the
+    /// filter, oldest first. The files are found by following the
+    /// `CONTINUE` entries back, and are indexed when queried rather than
+    /// kept in memory, as they are not appended to. Entries without
+    /// metadata are counted from the INIT entry of the oldest file.
+    fn query_rotated(
+        &self,
+        writer: &dyn EventLogWriter,
+        filter: &EventFilter,
+    ) -> Result<Vec<EventRecord>> {
+        let mut continued = match self.index.entries().first() {
+            Some(first) => continued_file(&writer.read_lines(&[first.location])?[0], self.format),
+            None => None,
+        };
+        let mut files: Vec<(String, Vec<String>)> = Vec::new();
+        while let Some(name) = continued {
+            if files.iter().any(|(rotated, _)| *rotated == name) {
+                bail!("the rotated eventlog {name} continues from itself");
+            }
+            let lines = writer.read_rotated(&name)?;
+            continued = lines
+                .iter()
+                .find(|line| !is_header(line))
+                .and_then(|line| continued_file(line, self.format));
+            files.push((name, lines));
+        }
+
+        let mut records = Vec::new();
+        let mut seq = 0;
+        for (name, lines) in files.iter().rev() {
+            let rotated =
+                check_existing(lines, self.format, self.algorithm, self.register_index, seq)
+                    .with_context(|| format!("rotated eventlog {name} is corrupt"))?;
+            seq = rotated.seq;
+            for indexed in rotated.index.matching(filter) {
+                let line = &lines[indexed.location.index];
+                records.push(self.record(&rotated.index, line, indexed)?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// The record of the entry of the line. Its digest is the one kept in
+    /// the index of its file, else the one of the CEL record, else the
+    /// digest of the AAEL entry, which is what was extended.
+    fn record(
+        &self,
+        index: &EventIndex,
+        line: &str,
+        indexed: &IndexedEntry,
+    ) -> Result<EventRecord> {
+        let line_index = indexed.location.index;
+        let algorithm = indexed.algorithm;
+        let (entry, digest) = match self.format {
+            EventlogFormat::Aael => {
+                let entry = parse_entry(line, self.format, algorithm)
+                    .with_context(|| format!("entry {line_index}"))?;
+                let digest = indexed
+                    .extended
+                    .then(|| match index.kept_digest(line_index) {
+                        Some(digest) => digest.to_vec(),
+                        None => algorithm.digest(entry.as_bytes()),
+                    });
+                (entry, digest)
+            }
+            EventlogFormat::Cel => {
+                let record =
+                    cel::Record::parse(line).with_context(|| format!("entry {line_index}"))?;
+                let digest = record
+                    .digests
+                    .iter()
+                    .find(|digest| digest.hash_alg == algorithm)
+                    .map(|digest| hex::decode(&digest.digest))
+                    .transpose()
+                    .with_context(|| format!("entry {line_index}"))?;
+                let entry = record
+                    .into_entry(algorithm)
+                    .with_context(|| format!("entry {line_index}"))?;
+                (entry, digest)
+            }
+        };
+        Ok(EventRecord::new(
+            &entry,
+            indexed.seq,
+            digest.as_deref(),
+            algorithm,
+            self.register_index,
+        ))
+    }
+
+    /// The recorded entries as a JSON array of [`EventRecord`]s, s.t. they
+    /// can be processed without parsing the lines.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(&self.records()?)?)
+    }
+
+    /// Replay the recorded entries of `algorithm` with [`replay_bank`],
+    /// starting from `init_value`, which gives the value of the register
+    /// bank of `algorithm` they are extended into.
+    pub fn replay(&self, algorithm: HashAlgorithm, init_value: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(replay_bank(
+            &self.read_algorithm_entries()?,
+            algorithm,
+            init_value,
+        ))
+    }
+
+    /// Check the chain digests recorded with
+    /// `eventlog_config.chain_entries`. Returns the index of the first entry
+    /// whose `prev_chain_digest` does not match the entries before it, i.e.
+    /// an entry before it was changed, removed or inserted. Entries without
+    /// a chain digest are not checked, but the chain goes on over them.
+    pub fn verify_chain(&self) -> Result<Option<usize>> {
+        let mut chain: Option<Vec<u8>> = None;
+        let algorithm = self.file_algorithm;
+        for (index, line) in self.read_log(0)?.iter().enumerate() {
+            let entry = parse_entry(line, self.format, self.line_algorithm(line))?;
+            if index == 0 && entry.starts_with(INIT_ENTRY_PREFIX) {
+                chain = Some(vec![0; algorithm.digest_len()]);
+            }
+
+            let recorded = parse_metadata(line, self.format)
+                .and_then(|metadata| metadata.prev_chain_digest)
+                .map(hex::decode)
+                .transpose()
+                .with_context(|| format!("entry {index}"))?;
+            let prev = match (recorded, chain) {
+                (Some(recorded), Some(chain)) if recorded != chain => return Ok(Some(index)),
+                (Some(prev), _) | (None, Some(prev)) => prev,
+                (None, None) => vec![0; algorithm.digest_len()],
+            };
+            chain = Some(chain_digest(algorithm, &prev, &entry));
+        }
+
+        Ok(None)
+    }
 }
 
-pub struct EventEntry<'a> {
-    domain: &'a str,
-    operation: &'a str,
-    content: &'a str,
+/// The eventlogs of the registers runtime measurements are extended into,
+/// one file per register. Each file starts with an INIT entry of its own,
+/// s.t. it replays to the value of its register without filtering. The
+/// eventlog of `init_pcr` is kept at `eventlog_config.file_path`, and the
+/// one of any other register next to it, see [`register_path`].
+pub struct RegisterLogs {
+    /// `None` if the eventlog is disabled.
+    config: Option<EventlogConfig>,
+    init_pcr: u64,
+    logs: BTreeMap<u64, EventLog>,
 }
 
-impl<'a> EventEntry<'a> {
-    pub fn new(domain: &'a str, operation: &'a str, content: &'a str) -> Self {
+impl RegisterLogs {
+    /// Open the eventlog of `init_pcr`, and the existing ones of the other
+    /// registers, e.g. when AA restarts. `initial` gives the value of a
+    /// register before anything is extended, see [`EventLog::new`].
+    pub fn new(config: &EventlogConfig, initial: impl Fn(u64) -> Vec<u8>) -> Result<Self> {
+        let init_pcr = config.init_pcr;
+        let mut logs = BTreeMap::new();
+        logs.insert(init_pcr, EventLog::new(config, initial(init_pcr))?);
+        let existing = match config.backend {
+            EventlogBackend::File => existing_registers(&config.file_path)?,
+            _ => Vec::new(),
+        };
+        for register_index in existing {
+            if register_index == init_pcr {
+                continue;
+            }
+            let config = register_config(config, register_index);
+            logs.insert(
+                register_index,
+                EventLog::new(&config, initial(register_index))?,
+            );
+        }
+
+        Ok(Self {
+            config: Some(config.clone()),
+            init_pcr,
+            logs,
+        })
+    }
+
+    /// Eventlogs that record nothing, see [`EventLog::disabled`].
+    pub fn disabled() -> Self {
         Self {
-            domain,
-            operation,
-            content,
+            config: None,
+            init_pcr: 0,
+            logs: BTreeMap::from([(0, EventLog::disabled())]),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    pub fn init_pcr(&self) -> u64 {
+        self.init_pcr
+    }
+
+    /// Path of the eventlog file of `init_pcr`, see
+    /// [`EventlogConfig::file_path`]. `None` if the eventlog is disabled or
+    /// not kept in files.
+    pub fn path(&self) -> Option<&Path> {
+        self.config
+            .as_ref()
+            .filter(|config| config.backend == EventlogBackend::File)
+            .map(|config| config.file_path.as_path())
+    }
+
+    /// The eventlog of `init_pcr`, which carries the INIT entry of AA.
+    pub fn init_log(&self) -> &EventLog {
+        &self.logs[&self.init_pcr]
+    }
+
+    /// The eventlog of the register, which is opened if the register has
+    /// none yet. `initial` is the value of the register before anything is
+    /// extended.
+    pub fn open(&mut self, register_index: u64, initial: Vec<u8>) -> Result<&mut EventLog> {
+        let Some(config) = &self.config else {
+            return Ok(self
+                .logs
+                .entry(self.init_pcr)
+                .or_insert_with(EventLog::disabled));
+        };
+        if !self.logs.contains_key(&register_index) {
+            let eventlog = EventLog::new(&register_config(config, register_index), initial)?;
+            self.logs.insert(register_index, eventlog);
+        }
+        Ok(self.logs.get_mut(&register_index).expect("opened above"))
+    }
+
+    /// The eventlogs in order of their registers.
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &EventLog)> {
+        self.logs.iter().map(|(index, eventlog)| (*index, eventlog))
+    }
+
+    /// Read back the recorded entries of the register, or of all registers
+    /// in order of their index, skipping the first `start_index` ones.
+    pub fn read_log(&self, register_index: Option<u64>, start_index: usize) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        for (index, eventlog) in self.iter() {
+            if register_index.is_none_or(|register_index| register_index == index) {
+                lines.extend(eventlog.read_log(0)?);
+            }
         }
+        Ok(lines.into_iter().skip(start_index).collect())
     }
 
-    /// Calculate the EventEntry's digest with the given [`HashAlgorithm`]
-    pub fn digest_with(&self, hash_alg: HashAlgorithm) -> Vec<u8> {
-        let log_entry = self.to_string();
-        hash_alg.digest(log_entry.as_bytes())
+    /// The records of the register, or of all registers in order of their
+    /// index, see [`EventLog::records`].
+    pub fn records(&self, register_index: Option<u64>) -> Result<Vec<EventRecord>> {
+        let mut records = Vec::new();
+        for (index, eventlog) in self.iter() {
+            if register_index.is_none_or(|register_index| register_index == index) {
+                records.extend(eventlog.records()?);
+            }
+        }
+        Ok(records)
+    }
+
+    /// The recorded entries of all registers that match the filter, in
+    /// order of their register, see [`EventLog::query`].
+    pub fn query(&self, filter: &EventFilter) -> Result<Vec<EventRecord>> {
+        let mut records = Vec::new();
+        for (_, eventlog) in self.iter() {
+            records.extend(eventlog.query(filter)?);
+        }
+        Ok(records)
+    }
+
+    /// Copy the eventlogs of all registers as recorded so far, including
+    /// the lines not flushed yet, to `dest`. Each copy is written to a
+    /// temporary file that then replaces it, s.t. it is complete when it
+    /// appears, and is only readable by the owner like the eventlog.
+    /// Rotated files are not copied, the `CONTINUE` entry names them. The
+    /// eventlogs of other backends are returned instead.
+    ///
+    /// A copy must not replace a file of the eventlogs, also when `dest`
+    /// names it by another path, e.g. through a symlink.
+    pub async fn snapshot(&self, dest: &Path) -> Result<EventLogSnapshot> {
+        let mut lines = Vec::new();
+        for (index, eventlog) in self.iter() {
+            lines.push((index, eventlog.read_lines()?));
+        }
+        let Some(path) = self.path() else {
+            let content = lines
+                .into_iter()
+                .flat_map(|(_, lines)| lines)
+                .map(|line| line + "\n")
+                .collect();
+            return Ok(EventLogSnapshot::Content(content));
+        };
+        let register_file = |path: &Path, index| {
+            if index == self.init_pcr {
+                path.to_path_buf()
+            } else {
+                register_path(path, index)
+            }
+        };
+        let eventlogs: Vec<PathBuf> = lines
+            .iter()
+            .map(|(index, _)| resolved_path(&register_file(path, *index)))
+            .collect();
+        let files: Vec<(PathBuf, Vec<String>)> = lines
+            .into_iter()
+            .map(|(index, lines)| (register_file(dest, index), lines))
+            .collect();
+        for (file, _) in &files {
+            let file = resolved_path(file);
+            if let Some(eventlog) = eventlogs
+                .iter()
+                .find(|eventlog| is_eventlog_file(&file, eventlog))
+            {
+                bail!(
+                    "the snapshot would replace the eventlog {}",
+                    eventlog.display()
+                );
+            }
+        }
+
+        tokio::task::spawn_blocking(move || {
+            for (path, lines) in &files {
+                write_atomically(path, lines)?;
+            }
+            Ok(files.into_iter().map(|(path, _)| path).collect())
+        })
+        .await
+        .context("eventlog snapshot task failed")?
+        .map(EventLogSnapshot::Files)
     }
+
+    /// Close the eventlogs of all registers on a blocking thread, see
+    /// [`EventLog::close_async`].
+    pub async fn close(&mut self) -> Result<()> {
+        for eventlog in self.logs.values_mut() {
+            eventlog.close_async().await?;
+        }
+        Ok(())
+    }
+}
+
+/// Run the I/O of the writer on a blocking thread, which holds the lock of
+/// the writer until it is done, also if the future is dropped. A failure of
+/// the storage is an [`EventLogStorageError`].
+async fn on_blocking_thread(
+    writer: &SharedWriter,
+    io: impl FnOnce(&mut dyn EventLogWriter) -> Result<()> + Send + 'static,
+) -> Result<()> {
+    let mut writer = writer.clone().lock_owned().await;
+    tokio::task::spawn_blocking(move || io(&mut **writer))
+        .await
+        .context("eventlog I/O task failed")?
+        .map_err(storage_error)
 }
 
-impl<'a> Display for EventEntry<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} {} {}", self.domain, self.operation, self.content)
+/// The path with its links and its parent dir resolved, s.t. the paths of
+/// the same file compare equal, also if it does not exist yet.
+fn resolved_path(path: &Path) -> PathBuf {
+    if let Ok(resolved) = path.canonicalize() {
+        return resolved;
+    }
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    match parent.canonicalize() {
+        Ok(parent) => parent.join(name),
+        Err(_) => path.to_path_buf(),
     }
 }
 
+/// Whether `file` is the file of the eventlog at `eventlog`, or one of the
+/// files rotated from it, `<eventlog>.<index>`.
+fn is_eventlog_file(file: &Path, eventlog: &Path) -> bool {
+    if file == eventlog {
+        return true;
+    }
+    let (Some(file), Some(eventlog)) = (file.to_str(), eventlog.to_str()) else {
+        return false;
+    };
+    file.strip_prefix(eventlog)
+        .and_then(|suffix| suffix.strip_prefix('.'))
+        .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// The path of the eventlog of a register other than `init_pcr`, which is
+/// `<path>.pcr<index>` for the eventlog of `init_pcr` at `path`.
+fn register_path(path: &Path, register_index: u64) -> PathBuf {
+    let mut path = path.to_path_buf().into_os_string();
+    path.push(format!(".pcr{register_index}"));
+    PathBuf::from(path)
+}
+
+/// Write the lines to a temporary file next to `path`, which then replaces
+/// the file at `path`. The temporary file is removed if this fails.
+fn write_atomically(path: &Path, lines: &[String]) -> Result<()> {
+    let mut temp = path.to_path_buf().into_os_string();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    let write = || -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp)?;
+        for line in lines {
+            writeln!(file, "{line}")?;
+        }
+        file.sync_all()?;
+        std::fs::rename(&temp, path)
+    };
+    write().or_else(|e| {
+        let _ = std::fs::remove_file(&temp);
+        Err(e).with_context(|| format!("write eventlog snapshot {}", path.display()))
+    })
+}
+
+/// The config of the eventlog of a register other than `init_pcr`.
+fn register_config(config: &EventlogConfig, register_index: u64) -> EventlogConfig {
+    EventlogConfig {
+        file_path: register_path(&config.file_path, register_index),
+        init_pcr: register_index,
+        ..config.clone()
+    }
+}
+
+/// The registers that have an eventlog next to the one at `path`. Rotated
+/// and archived files of the eventlogs are not counted.
+fn existing_registers(path: &Path) -> Result<Vec<u64>> {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(Vec::new());
+    };
+    let prefix = format!("{}.pcr", name.to_string_lossy());
+    let dir = match std::fs::read_dir(parent) {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("read eventlog dir {}", parent.display())),
+    };
+
+    let mut registers = Vec::new();
+    for entry in dir {
+        let entry = entry.with_context(|| format!("read eventlog dir {}", parent.display()))?;
+        let file_name = entry.file_name();
+        let index = file_name
+            .to_str()
+            .and_then(|file_name| file_name.strip_prefix(&prefix))
+            .filter(|index| index.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|index| index.parse().ok());
+        registers.extend(index);
+    }
+    Ok(registers)
+}
+
+/// What is continued from an existing eventlog file.
+struct ExistingLog {
+    entries: u64,
+
+    /// Sequence number of the next entry
+    seq: u64,
+
+    /// Chain digest the next entry records
+    chain: Vec<u8>,
+
+    header: Option<AaelHeader>,
+
+    /// See [`EventLog::file_algorithm`].
+    file_algorithm: HashAlgorithm,
+
+    /// See [`EventLog::algorithms`].
+    algorithms: Vec<HashAlgorithm>,
+
+    /// Index of the entries, see [`EventLog::index`]. An AAEL file does not
+    /// record the digests, so they are calculated from its entries, as the
+    /// digests of CEL records are checked to be.
+    index: EventIndex,
+}
+
+impl ExistingLog {
+    fn empty(algorithm: HashAlgorithm) -> Self {
+        Self {
+            entries: 0,
+            seq: 0,
+            chain: vec![0; algorithm.digest_len()],
+            header: None,
+            file_algorithm: algorithm,
+            algorithms: Vec::new(),
+            index: EventIndex::default(),
+        }
+    }
+}
+
+/// Check that the lines of an existing eventlog are well formed: lines of
+/// the format, the first of which is the INIT or a `CONTINUE` entry and the
+/// others runtime measurements. The [`AaelHeader`] of an AAEL file must be
+/// of a known version and match the configured register, as the entries
+/// are appended to. Entries of another algorithm than the configured one
+/// are kept, see [`EventLog::algorithms`]. The file backend checks that the
+/// lines are complete, see [`FileWriter`]. Entries without metadata are
+/// counted from `first_seq`.
+fn check_existing(
+    existing: &[String],
+    format: EventlogFormat,
+    algorithm: HashAlgorithm,
+    register_index: u64,
+    first_seq: u64,
+) -> Result<ExistingLog> {
+    let mut checked = ExistingLog::empty(algorithm);
+    checked.seq = first_seq;
+    let mut offset = 0;
+    let mut lines = existing.iter().enumerate().peekable();
+    if format == EventlogFormat::Aael {
+        if let Some((_, line)) = lines.next_if(|(_, line)| is_header(line)) {
+            let header = AaelHeader::parse(line)?;
+            if header.init_pcr != register_index {
+                bail!(
+                    "the eventlog is of register {} rather than {register_index}",
+                    header.init_pcr
+                );
+            }
+            offset = line_len(line);
+            checked.header = Some(header);
+        }
+    }
+
+    // The algorithm of the entries that do not record one is the one the
+    // file was started with, which the INIT or `CONTINUE` entry of a
+    // legacy file without header tells
+    checked.file_algorithm = match &checked.header {
+        Some(header) => header.algorithm,
+        None => lines
+            .peek()
+            .and_then(|(_, line)| recorded_algorithm(line, format))
+            .unwrap_or(algorithm),
+    };
+    checked.chain = vec![0; checked.file_algorithm.digest_len()];
+
+    let first = usize::from(checked.header.is_some());
+    for (index, line) in lines {
+        let algorithm = recorded_algorithm(line, format).unwrap_or(checked.file_algorithm);
+        // A CEL record is a JSON object, which neither the INIT nor a
+        // `CONTINUE` entry starts with
+        if index == first && line.starts_with('{') != (format == EventlogFormat::Cel) {
+            bail!("the eventlog is not of the format `{format:?}`");
+        }
+        let entry =
+            parse_entry(line, format, algorithm).with_context(|| format!("entry {index}"))?;
+        let well_formed = if index == first {
+            entry.starts_with(INIT_ENTRY_PREFIX) || entry.starts_with(CONTINUE_ENTRY_PREFIX)
+        } else {
+            EventEntry::parse(&entry).is_ok()
+        };
+        if !well_formed {
+            bail!("entry {index} is malformed");
+        }
+        // Entries recorded without metadata count as well, and the chain
+        // continues from the last entry that records it
+        let metadata = parse_metadata(line, format);
+        let seq = metadata
+            .as_ref()
+            .map_or(checked.seq, |metadata| metadata.seq);
+        checked.entries += 1;
+        checked.seq = seq + 1;
+        if let Some(prev) = metadata.and_then(|metadata| metadata.prev_chain_digest) {
+            checked.chain = hex::decode(prev).with_context(|| format!("entry {index}"))?;
+        }
+        checked.chain = chain_digest(checked.file_algorithm, &checked.chain, &entry);
+        let extended = !entry.starts_with(CONTINUE_ENTRY_PREFIX);
+        if extended && !checked.algorithms.contains(&algorithm) {
+            checked.algorithms.push(algorithm);
+        }
+        let location = LineLocation {
+            index,
+            offset,
+            len: line.len(),
+        };
+        checked
+            .index
+            .push(&entry, location, seq, algorithm, extended);
+        offset += line_len(line);
+    }
+
+    Ok(checked)
+}
+
+/// The hash algorithm recorded with the entry of the line, if any: in its
+/// [`EntryMetadata`], with the digest of a CEL record, or in the INIT and
+/// `CONTINUE` entries.
+fn recorded_algorithm(line: &str, format: EventlogFormat) -> Option<HashAlgorithm> {
+    let entry = match format {
+        EventlogFormat::Aael => {
+            let (metadata, entry) = split_metadata(line).ok()?;
+            if let Some(algorithm) = metadata.and_then(|metadata| metadata.algorithm) {
+                return Some(algorithm);
+            }
+            entry.to_string()
+        }
+        EventlogFormat::Cel => {
+            let record = cel::Record::parse(line).ok()?;
+            if let Some(digest) = record.digests.first() {
+                return Some(digest.hash_alg);
+            }
+            record.entry().ok()?
+        }
+    };
+    entry_algorithm(&entry)
+}
+
+/// The chain digest after `entry`, which the next entry records as its
+/// `prev_chain_digest`. It is calculated like extending a register, from
+/// zeros before the INIT entry, s.t. an entry that is changed, removed or
+/// inserted breaks the chain at the entry after it.
+fn chain_digest(algorithm: HashAlgorithm, prev: &[u8], entry: &str) -> Vec<u8> {
+    let digest = algorithm.digest(entry.as_bytes());
+    algorithm.digest(&[prev, &digest].concat())
+}
+
+/// The name of the rotated file the `CONTINUE` entry of the line continues
+/// from, `None` for other entries.
+fn continued_file(line: &str, format: EventlogFormat) -> Option<String> {
+    // The `CONTINUE` entry is not extended, s.t. no digest is checked
+    let entry = parse_entry(line, format, HashAlgorithm::default()).ok()?;
+    let (_, name) = entry
+        .strip_prefix(CONTINUE_ENTRY_PREFIX)?
+        .split_once(FIELD_SEPARATOR)?;
+    Some(name.to_string())
+}
+
+/// The AAEL entry of a line of an eventlog file of the format, without the
+/// metadata.
+fn parse_entry(line: &str, format: EventlogFormat, algorithm: HashAlgorithm) -> Result<String> {
+    match format {
+        EventlogFormat::Aael => Ok(split_metadata(line)?.1.to_string()),
+        EventlogFormat::Cel => cel::Record::parse(line)?.into_entry(algorithm),
+    }
+}
+
+/// An entry of the eventlog with the digest it was extended with, as
+/// exported by [`EventLog::to_json`]. The INIT and `CONTINUE` entries are
+/// split into the fields like the others, e.g. the domain `INIT` and the
+/// operation `sha384/<hex register value>`.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct EventRecord {
+    /// Sequence number of the entry, see [`EntryMetadata::seq`]. Counted
+    /// as well for entries recorded without metadata.
+    pub seq: u64,
+
+    pub domain: String,
+    pub operation: String,
+
+    /// The content as recorded, i.e. binary content, and text that starts
+    /// with `base64url:`, is `base64url:` encoded, see [`EventEntry::new`].
+    pub content: String,
+
+    /// Hex encoded digest extended into the register, as calculated when
+    /// the entry was recorded. `None` for the `CONTINUE` entry, which is
+    /// not extended.
+    pub digest: Option<String>,
+
+    pub algorithm: HashAlgorithm,
+
+    /// Register the entry is extended into, `None` for the `CONTINUE`
+    /// entry.
+    pub register_index: Option<u64>,
+}
+
+impl EventRecord {
+    fn new(
+        entry: &str,
+        seq: u64,
+        digest: Option<&[u8]>,
+        algorithm: HashAlgorithm,
+        register_index: u64,
+    ) -> Self {
+        let mut fields = entry.splitn(3, FIELD_SEPARATOR);
+        let mut field = || fields.next().unwrap_or_default().to_string();
+        Self {
+            seq,
+            domain: field(),
+            operation: field(),
+            content: field(),
+            digest: digest.map(hex::encode),
+            algorithm,
+            register_index: digest.map(|_| register_index),
+        }
+    }
+}
+
+/// The metadata of a line of an eventlog file of the format, if recorded.
+fn parse_metadata(line: &str, format: EventlogFormat) -> Option<EntryMetadata> {
+    match format {
+        EventlogFormat::Aael => split_metadata(line).ok()?.0,
+        EventlogFormat::Cel => cel::Record::parse(line).ok()?.metadata,
+    }
+}
+
+/// Size in bytes of the entry in the file, with its line break.
+fn line_len(entry: &str) -> u64 {
+    entry.len() as u64 + 1
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
 
-    use crate::config::HashAlgorithm;
+    use std::path::Path;
+
+    use crate::config::{
+        EventlogBackend, EventlogConfig, EventlogFormat, EventlogRecovery, EventlogRotation,
+        EventlogSync, HashAlgorithm,
+    };
+
+    use super::{
+        cel, diverging_entry, replay, AaelHeader, EventEntry, EventFilter, EventLog, EventLogFull,
+    };
+
+    fn init_entry() -> String {
+        format!("INIT sha384/{}", "0".repeat(96))
+    }
+
+    fn limited_log(path: &Path, max_size: u64, rotation: EventlogRotation) -> EventLog {
+        let config = EventlogConfig {
+            file_path: path.into(),
+            max_size_bytes: Some(max_size),
+            rotation,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        eventlog.write_init_log(&init_entry(), 17, &[]).unwrap();
+        eventlog
+    }
+
+    fn events(contents: &[String]) -> Vec<EventEntry<'_>> {
+        contents
+            .iter()
+            .map(|content| EventEntry::new("domain", "operation", content).unwrap())
+            .collect()
+    }
+
+    #[rstest]
+    #[case(false, false)]
+    #[case(true, false)]
+    #[case(false, true)]
+    #[case(true, true)]
+    fn test_written_log_parses_back(#[case] record_timestamps: bool, #[case] chain_entries: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            record_timestamps,
+            chain_entries,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        eventlog.write_init_log(&init_entry(), 17, &[]).unwrap();
+
+        // Arbitrary entries are checked by the property tests of the
+        // parser, these are the ones easily mishandled by the writer
+        let mut written = vec![init_entry()];
+        let events = [
+            EventEntry::new("domain", "operation", "content with spaces"),
+            EventEntry::new("a]", "[b", "[0 alg=sha256] c"),
+            EventEntry::new("é✓🦀", "\\\"{", "\t="),
+            EventEntry::new("domain", "operation", ""),
+            EventEntry::new("domain", "operation", "base64url:text"),
+            EventEntry::new_binary("domain", "operation", &[0, 0xff, b'\n']),
+        ];
+        for event in events {
+            let entry = event.unwrap().to_string();
+            eventlog.write_log(&entry, 17, &[]).unwrap();
+            written.push(entry);
+        }
+        eventlog.end_batch().unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let parsed = aael::parse_str(&log).unwrap();
+        assert_eq!(
+            parsed.header,
+            Some(AaelHeader {
+                version: (1, 0),
+                algorithm: HashAlgorithm::Sha384,
+                init_pcr: 17,
+            })
+        );
+        assert_eq!(parsed.entries(), written);
+        for line in &parsed.lines[1..] {
+            let event = EventEntry::parse(&line.entry).unwrap();
+            assert_eq!(event.to_string(), line.entry);
+        }
+        assert!(parsed
+            .lines
+            .iter()
+            .all(|line| line.metadata.is_some() == (record_timestamps || chain_entries)));
+        assert_eq!(
+            parsed.replay(HashAlgorithm::Sha384, vec![0; 48]),
+            eventlog.replay(HashAlgorithm::Sha384, vec![0; 48]).unwrap()
+        );
+        assert_eq!(aael::parse_reader(log.as_bytes()).unwrap(), parsed);
+    }
 
-    use super::EventEntry;
+    #[test]
+    fn test_read_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut eventlog =
+            EventLog::create(&dir.path().join("eventlog"), EventlogSync::Always).unwrap();
+        assert!(eventlog.read_log(0).unwrap().is_empty());
+
+        eventlog.write_log("INIT sha384/00", 17, &[]).unwrap();
+        eventlog
+            .write_log("domain operation content", 17, &[])
+            .unwrap();
+        assert_eq!(
+            eventlog.read_log(0).unwrap(),
+            vec!["INIT sha384/00", "domain operation content"]
+        );
+        assert_eq!(
+            eventlog.read_log(1).unwrap(),
+            vec!["domain operation content"]
+        );
+        assert!(eventlog.read_log(5).unwrap().is_empty());
+    }
+
+    #[rstest]
+    #[case::legacy("", 0)]
+    #[case::current("AAEL/1.0 algorithm=sha384 init_pcr=17\n", 1)]
+    #[case::later_minor("AAEL/1.1 algorithm=sha384 init_pcr=17 escaping=v2\n", 1)]
+    fn test_aael_header(#[case] header: &str, #[case] version: u32) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let existing = format!("{header}INIT sha384/00\ndomain operation content\n");
+        std::fs::write(&path, &existing).unwrap();
+
+        let mut eventlog = EventLog::create(&path, EventlogSync::Never).unwrap();
+        assert_eq!(eventlog.version(), version);
+        assert!(eventlog.is_initialized());
+        eventlog
+            .write_log("domain operation more", 17, &[])
+            .unwrap();
+
+        // The header is kept as it is, and is not an entry
+        let entries = vec![
+            "INIT sha384/00",
+            "domain operation content",
+            "domain operation more",
+        ];
+        assert_eq!(eventlog.read_entries().unwrap(), entries);
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(log, format!("{existing}domain operation more\n"));
+        let lines: Vec<String> = log.lines().map(ToString::to_string).collect();
+        let entries: Vec<String> = entries.into_iter().map(ToString::to_string).collect();
+        assert_eq!(
+            replay(&lines, HashAlgorithm::Sha384, vec![0; 48]),
+            replay(&entries, HashAlgorithm::Sha384, vec![0; 48])
+        );
+        assert_eq!(
+            eventlog.query(&EventFilter::default()).unwrap()[2].content,
+            "more"
+        );
+    }
+
+    #[test]
+    fn test_new_log_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            init_pcr: 16,
+            eventlog_algorithm: HashAlgorithm::Sha256,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 32]).unwrap();
+        assert_eq!(eventlog.version(), 0);
+        eventlog.write_init_log("INIT sha256/00", 16, &[]).unwrap();
+        assert_eq!(eventlog.version(), 1);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "AAEL/1.0 algorithm=sha256 init_pcr=16\nINIT sha256/00\n"
+        );
+
+        // Not appended to for another register
+        let config = EventlogConfig {
+            init_pcr: 17,
+            ..config
+        };
+        let e = EventLog::new(&config, vec![0; 32]).err().unwrap();
+        assert!(
+            format!("{e:#}").contains("of register 16 rather than 17"),
+            "{e:#}"
+        );
+    }
+
+    #[rstest]
+    #[case::header("AAEL/1.0 algorithm=sha256 init_pcr=17\n")]
+    #[case::legacy("")]
+    fn test_mixed_algorithm_log(#[case] header: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let init = format!("INIT sha256/{}", "0".repeat(64));
+        let existing = format!("{header}{init}\ndomain operation old\n");
+        std::fs::write(&path, &existing).unwrap();
+
+        // The eventlog was started with sha256 before the upgrade
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            eventlog_algorithm: HashAlgorithm::Sha384,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert_eq!(eventlog.algorithms(), [HashAlgorithm::Sha256]);
+        eventlog.write_log("domain operation new", 17, &[]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            format!("{existing}[2 alg=sha384] domain operation new\n")
+        );
+        drop(eventlog);
+
+        let eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert_eq!(
+            eventlog.algorithms(),
+            [HashAlgorithm::Sha256, HashAlgorithm::Sha384]
+        );
+        let entries = eventlog.read_algorithm_entries().unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                (HashAlgorithm::Sha256, init.clone()),
+                (HashAlgorithm::Sha256, "domain operation old".to_string()),
+                (HashAlgorithm::Sha384, "domain operation new".to_string()),
+            ]
+        );
+        let records = eventlog.records().unwrap();
+        assert_eq!(records[1].algorithm, HashAlgorithm::Sha256);
+        assert_eq!(records[2].algorithm, HashAlgorithm::Sha384);
+
+        // Each bank is replayed from the entries of its algorithm
+        let sha256 = vec![init, "domain operation old".to_string()];
+        assert_eq!(
+            eventlog.replay(HashAlgorithm::Sha256, vec![0; 32]).unwrap(),
+            replay(&sha256, HashAlgorithm::Sha256, vec![0; 32])
+        );
+        let sha384 = vec!["domain operation new".to_string()];
+        assert_eq!(
+            eventlog.replay(HashAlgorithm::Sha384, vec![0; 48]).unwrap(),
+            replay(&sha384, HashAlgorithm::Sha384, vec![0; 48])
+        );
+    }
+
+    #[test]
+    fn test_create_restricted_log() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("run/attestation-agent/eventlog");
+        EventLog::create(&path, EventlogSync::Never).unwrap();
+
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+    }
+
+    #[test]
+    fn test_disabled_log() {
+        let mut eventlog = EventLog::disabled();
+        assert!(!eventlog.is_enabled());
+        eventlog
+            .write_log("domain operation content", 17, &[])
+            .unwrap();
+        assert!(eventlog.read_log(0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_memory_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            backend: EventlogBackend::Memory,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert!(eventlog.is_enabled());
+        eventlog.write_init_log(&init_entry(), 17, &[]).unwrap();
+        eventlog
+            .write_log("domain operation content", 17, &[])
+            .unwrap();
+        assert_eq!(
+            eventlog.read_entries().unwrap(),
+            vec![init_entry().as_str(), "domain operation content"]
+        );
+        assert!(!config.file_path.exists());
+
+        // Nothing is kept over a restart
+        let eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert!(!eventlog.is_initialized());
+    }
+
+    #[test]
+    fn test_backend_init_failure() {
+        let config = EventlogConfig {
+            backend: EventlogBackend::Stream,
+            stream_address: None,
+            ..Default::default()
+        };
+        let Err(e) = EventLog::new(&config, vec![0; 48]) else {
+            panic!("the stream backend needs an address");
+        };
+        assert!(
+            format!("{e:#}").contains("initialize the `stream` eventlog backend"),
+            "{e:#}"
+        );
+    }
+
+    #[test]
+    fn test_reopen_initialized_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut eventlog = EventLog::create(&path, EventlogSync::OnBatch).unwrap();
+        assert!(!eventlog.is_initialized());
+
+        eventlog.write_init_log("INIT sha384/00", 17, &[]).unwrap();
+        eventlog
+            .write_log("domain operation content", 17, &[])
+            .unwrap();
+        assert!(eventlog.is_initialized());
+        drop(eventlog);
+
+        let eventlog = EventLog::create(&path, EventlogSync::OnBatch).unwrap();
+        assert!(eventlog.is_initialized());
+        assert_eq!(eventlog.read_log(0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_json_export_golden() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        std::fs::copy("../deps/aael/test/aael.log", &path).unwrap();
+
+        let eventlog = EventLog::create(&path, EventlogSync::OnBatch).unwrap();
+        let exported: serde_json::Value =
+            serde_json::from_str(&eventlog.to_json().unwrap()).unwrap();
+        let golden: serde_json::Value =
+            serde_json::from_str(include_str!("../test/eventlog/export.json")).unwrap();
+        assert_eq!(exported, golden);
+    }
+
+    #[test]
+    fn test_json_export_write_time_digests() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut eventlog = EventLog::create(&path, EventlogSync::OnBatch).unwrap();
+        eventlog
+            .write_init_log(&init_entry(), 17, &[0xaa; 48])
+            .unwrap();
+        eventlog
+            .write_log("domain operation content", 17, &[0xbb; 48])
+            .unwrap();
+        let other = "domain operation other";
+        let digest = HashAlgorithm::Sha384.digest(other.as_bytes());
+        eventlog.write_log(other, 17, &digest).unwrap();
+
+        // The digests that were extended are exported as they are, even if
+        // they are not the digests of the entries.
+        let records = eventlog.records().unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].digest, Some("aa".repeat(48)));
+        assert_eq!(records[1].digest, Some("bb".repeat(48)));
+        assert_eq!(records[2].digest, Some(hex::encode(&digest)));
+        // Only those are kept in the index, after the header and the INIT
+        // entry
+        assert!(eventlog.index.kept_digest(2).is_some());
+        assert!(eventlog.index.kept_digest(3).is_none());
+        assert_eq!(
+            (
+                records[1].domain.as_str(),
+                records[1].operation.as_str(),
+                records[1].content.as_str(),
+                records[1].register_index,
+            ),
+            ("domain", "operation", "content", Some(17))
+        );
+
+        // Written by another process behind the back of the eventlog
+        std::fs::write(&path, "INIT sha384/00\n").unwrap();
+        assert!(eventlog.records().is_err());
+    }
+
+    /// An eventlog of `count` entries after the INIT entry, alternating
+    /// between two domains.
+    fn large_log(path: &Path, count: u64) -> EventLog {
+        let mut eventlog = EventLog::create(path, EventlogSync::Never).unwrap();
+        eventlog.write_init_log(&init_entry(), 17, &[]).unwrap();
+        for seq in 1..=count {
+            let entry = match seq % 2 {
+                0 => format!("kata-containers CreateContainer {seq}"),
+                _ => format!("github.com/confidential-containers PullImage image-{seq}"),
+            };
+            eventlog.write_log(&entry, 17, &[]).unwrap();
+        }
+        eventlog.end_batch().unwrap();
+        eventlog
+    }
+
+    #[test]
+    fn test_query_large_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let eventlog = large_log(&path, 30_000);
+
+        let filter = EventFilter {
+            domain: Some("kata-containers".into()),
+            seq_start: Some(29_000),
+            ..Default::default()
+        };
+        let records = eventlog.query(&filter).unwrap();
+        assert_eq!(records.len(), 501);
+        assert!(records
+            .iter()
+            .all(|record| record.seq >= 29_000 && record.domain == "kata-containers"));
+        assert_eq!(records[0].content, "29000");
+        assert_eq!(records[500].content, "30000");
+
+        // The index keeps each domain and operation once, rather than the
+        // entries
+        assert_eq!(eventlog.index.names(), 6);
+
+        // The index is rebuilt from the existing entries
+        drop(eventlog);
+        let eventlog = EventLog::create(&path, EventlogSync::Never).unwrap();
+        assert_eq!(eventlog.query(&filter).unwrap(), records);
+    }
+
+    #[rstest]
+    #[case(EventFilter::default(), 5)]
+    #[case(EventFilter { domain: Some("INIT".into()), ..Default::default() }, 1)]
+    #[case(EventFilter { domain: Some("unknown".into()), ..Default::default() }, 0)]
+    #[case(EventFilter { operation_prefix: Some("Pull".into()), ..Default::default() }, 2)]
+    #[case(EventFilter { operation_prefix: Some("P".into()), domain: Some("kata-containers".into()), ..Default::default() }, 0)]
+    #[case(EventFilter { register_index: Some(17), ..Default::default() }, 5)]
+    #[case(EventFilter { register_index: Some(16), ..Default::default() }, 0)]
+    #[case(EventFilter { seq_start: Some(1), seq_end: Some(3), ..Default::default() }, 2)]
+    #[case(EventFilter { seq_end: Some(0), ..Default::default() }, 0)]
+    fn test_query_filter(#[case] filter: EventFilter, #[case] expected: usize) {
+        let dir = tempfile::tempdir().unwrap();
+        let eventlog = large_log(&dir.path().join("eventlog"), 4);
+        assert_eq!(eventlog.query(&filter).unwrap().len(), expected);
+
+        let json = serde_json::to_string(&filter).unwrap();
+        assert_eq!(serde_json::from_str::<EventFilter>(&json).unwrap(), filter);
+    }
+
+    #[rstest]
+    #[case(b"INIT sha384/00\ndomain operation content".as_slice())]
+    #[case(b"domain operation content\n".as_slice())]
+    #[case(b"INIT sha384/00\nmalformed\n".as_slice())]
+    #[case(b"INIT sha384/00\n\xff\n".as_slice())]
+    #[case(b"{\"recnum\":0}\n".as_slice())]
+    #[case(b"AAEL/2.0 algorithm=sha384 init_pcr=17\nINIT sha384/00\n".as_slice())]
+    #[case(b"AAEL/1.0 algorithm=sha384 init_pcr=16\nINIT sha384/00\n".as_slice())]
+    #[case(b"AAEL/1.0 algorithm=sha384\nINIT sha384/00\n".as_slice())]
+    #[case(b"AAEL/one algorithm=sha384 init_pcr=17\nINIT sha384/00\n".as_slice())]
+    fn test_reopen_corrupt_log(#[case] existing: &[u8]) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        std::fs::write(&path, existing).unwrap();
+
+        let e = EventLog::create(&path, EventlogSync::Never).err().unwrap();
+        assert!(format!("{e:#}").contains("is corrupt"), "{e:#}");
+        assert_eq!(std::fs::read(&path).unwrap(), existing);
+
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            on_corrupt: EventlogRecovery::Archive,
+            ..Default::default()
+        };
+        let eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert!(!eventlog.is_initialized());
+        assert!(eventlog.read_log(0).unwrap().is_empty());
+        let archived = dir.path().join("eventlog.corrupt.1");
+        assert_eq!(std::fs::read(archived).unwrap(), existing);
+    }
+
+    #[test]
+    fn test_cel_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            format: EventlogFormat::Cel,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        let init = init_entry();
+        let digest = HashAlgorithm::Sha384.digest(init.as_bytes());
+        eventlog.write_init_log(&init, 17, &digest).unwrap();
+        let event = EventEntry::new("domain", "operation", "content").unwrap();
+        let digest = event.digest_with(HashAlgorithm::Sha384);
+        eventlog.write_log(&event.to_string(), 17, &digest).unwrap();
+        drop(eventlog);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let record = cel::Record::parse(log.lines().nth(1).unwrap()).unwrap();
+        assert_eq!((record.recnum, record.pcr), (1, Some(17)));
+        assert_eq!(record.digests[0].digest, hex::encode(&digest));
+
+        let eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert!(eventlog.is_initialized());
+        assert_eq!(
+            eventlog.read_entries().unwrap(),
+            vec![init, event.to_string()]
+        );
+
+        // An AAEL file is not taken for a CEL one, and the other way around
+        assert!(EventLog::create(&path, EventlogSync::Never).is_err());
+        let aael = dir.path().join("aael");
+        EventLog::create(&aael, EventlogSync::Never)
+            .unwrap()
+            .write_init_log("INIT sha384/00", 17, &[])
+            .unwrap();
+        let config = EventlogConfig {
+            file_path: aael,
+            ..config
+        };
+        assert!(EventLog::new(&config, vec![0; 48]).is_err());
+    }
+
+    #[rstest]
+    #[case(EventlogFormat::Aael)]
+    #[case(EventlogFormat::Cel)]
+    fn test_timestamped_log(#[case] format: EventlogFormat) {
+        let dir = tempfile::tempdir().unwrap();
+        let event = EventEntry::new("domain", "operation", "content").unwrap();
+        let digest = event.digest_with(HashAlgorithm::Sha384);
+        let write = |record_timestamps: bool| {
+            let config = EventlogConfig {
+                file_path: dir.path().join(format!("eventlog-{record_timestamps}")),
+                format,
+                record_timestamps,
+                ..Default::default()
+            };
+            let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+            let init = init_entry();
+            let init_digest = HashAlgorithm::Sha384.digest(init.as_bytes());
+            eventlog.write_init_log(&init, 17, &init_digest).unwrap();
+            eventlog.write_log(&event.to_string(), 17, &digest).unwrap();
+            drop(eventlog);
+            EventLog::new(&config, vec![0; 48]).unwrap()
+        };
+
+        let plain = write(false);
+        let timestamped = write(true);
+        assert_eq!(timestamped.seq, 2);
+        assert_ne!(timestamped.read_log(0).unwrap(), plain.read_log(0).unwrap());
+        assert_eq!(
+            timestamped.read_entries().unwrap(),
+            plain.read_entries().unwrap()
+        );
+        assert_eq!(
+            timestamped
+                .replay(HashAlgorithm::Sha384, vec![0; 48])
+                .unwrap(),
+            plain.replay(HashAlgorithm::Sha384, vec![0; 48]).unwrap()
+        );
+
+        let line = &timestamped.read_log(0).unwrap()[1];
+        let metadata = super::parse_metadata(line, format).unwrap();
+        assert_eq!(metadata.seq, 1);
+        if format == EventlogFormat::Aael {
+            let timestamp = metadata.timestamp.unwrap();
+            assert!(line.starts_with(&format!("[1 {timestamp}] ")));
+            let parsed = EventEntry::parse(line).unwrap();
+            assert_eq!(parsed.digest_with(HashAlgorithm::Sha384), digest);
+        }
+    }
+
+    #[rstest]
+    #[case(EventlogFormat::Aael)]
+    #[case(EventlogFormat::Cel)]
+    fn test_chained_log(#[case] format: EventlogFormat) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            format,
+            chain_entries: true,
+            ..Default::default()
+        };
+        let write = |contents: &[&str]| {
+            let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+            for content in contents {
+                let event = EventEntry::new("domain", "operation", content).unwrap();
+                let digest = event.digest_with(HashAlgorithm::Sha384);
+                eventlog.write_log(&event.to_string(), 17, &digest).unwrap();
+            }
+            eventlog
+        };
+
+        let mut eventlog = write(&[]);
+        let init = init_entry();
+        let digest = HashAlgorithm::Sha384.digest(init.as_bytes());
+        eventlog.write_init_log(&init, 17, &digest).unwrap();
+        drop(eventlog);
+        // The chain goes on after a restart
+        drop(write(&["event-1", "event-2"]));
+        let eventlog = write(&["event-3"]);
+        assert_eq!(eventlog.verify_chain().unwrap(), None);
+
+        let lines = eventlog.read_log(0).unwrap();
+        let metadata = super::parse_metadata(&lines[3], format).unwrap();
+        let expected = replay(
+            &eventlog.read_entries().unwrap()[..3],
+            HashAlgorithm::Sha384,
+            vec![0; 48],
+        );
+        assert_eq!(metadata.prev_chain_digest, Some(hex::encode(expected)));
+
+        // A CEL record is tampered with along with its digest, s.t. only the
+        // chain tells
+        let mut tampered = lines.clone();
+        tampered[1] = match format {
+            EventlogFormat::Aael => lines[1].replace("event-1", "event-X"),
+            EventlogFormat::Cel => {
+                let record = cel::Record::parse(&lines[1]).unwrap();
+                let entry = record.entry().unwrap().replace("event-1", "event-X");
+                let digest = HashAlgorithm::Sha384.digest(entry.as_bytes());
+                let extended = Some((17, HashAlgorithm::Sha384, &digest[..]));
+                let mut forged = cel::Record::new(record.recnum, &entry, extended);
+                forged.metadata = record.metadata;
+                forged.to_line()
+            }
+        };
+        assert_ne!(tampered[1], lines[1]);
+        std::fs::write(&path, tampered.join("\n") + "\n").unwrap();
+        assert_eq!(eventlog.verify_chain().unwrap(), Some(2));
+
+        let removed = [&lines[..1], &lines[2..]].concat().join("\n") + "\n";
+        std::fs::write(&path, removed).unwrap();
+        assert_eq!(eventlog.verify_chain().unwrap(), Some(1));
+    }
+
+    #[test]
+    fn test_full_log_refuses_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        // The header takes 38 bytes, the INIT entry 110 bytes, and each
+        // event 25 bytes
+        let mut eventlog = limited_log(&path, 238, EventlogRotation::Error);
+
+        let contents: Vec<String> = (0..4).map(|i| format!("event-{i}")).collect();
+        let batch = events(&contents);
+        assert!(eventlog.check_capacity(&batch[..3], 17).is_ok());
+        assert_eq!(
+            eventlog.check_capacity(&batch, 17),
+            Err(EventLogFull::Size { max_size: 238 })
+        );
+
+        for event in &batch[..3] {
+            eventlog.write_log(&event.to_string(), 17, &[]).unwrap();
+        }
+        assert_eq!(
+            eventlog.check_capacity(&batch[3..], 17),
+            Err(EventLogFull::Size { max_size: 238 })
+        );
+        assert_eq!(eventlog.read_log(0).unwrap().len(), 4);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 223);
+    }
+
+    #[test]
+    fn test_rotate_full_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut eventlog = limited_log(&path, 256, EventlogRotation::Rotate);
+
+        // Rotated twice in the middle of the batch
+        let contents: Vec<String> = (0..10).map(|i| format!("event-{i}")).collect();
+        let batch = events(&contents);
+        assert!(eventlog.check_capacity(&batch, 17).is_ok());
+        let mut entries = vec![init_entry()];
+        for event in &batch {
+            eventlog.write_log(&event.to_string(), 17, &[]).unwrap();
+            entries.push(event.to_string());
+        }
+
+        // Each file starts with a header of its own
+        let first = std::fs::read_to_string(dir.path().join("eventlog.1")).unwrap();
+        assert!(first.starts_with("AAEL/1.0 algorithm=sha384 init_pcr=17\nINIT "));
+        let second = std::fs::read_to_string(dir.path().join("eventlog.2")).unwrap();
+        assert!(second.starts_with("AAEL/1.0 algorithm=sha384 init_pcr=17\nCONTINUE sha384/"));
+        assert!(second.lines().nth(1).unwrap().ends_with(" eventlog.1"));
+        assert!(!dir.path().join("eventlog.3").exists());
+
+        let active = eventlog.read_log(0).unwrap();
+        assert!(active[0].ends_with(" eventlog.2"));
+        assert!(std::fs::metadata(&path).unwrap().len() <= 256);
+        assert_eq!(
+            replay(&active, HashAlgorithm::Sha384, vec![0; 48]),
+            replay(&entries, HashAlgorithm::Sha384, vec![0; 48])
+        );
+
+        drop(eventlog);
+        let eventlog = EventLog::create(&path, EventlogSync::Never).unwrap();
+        assert!(eventlog.is_initialized());
+    }
+
+    #[test]
+    fn test_query_rotated_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut eventlog = limited_log(&path, 256, EventlogRotation::Rotate);
+        let contents: Vec<String> = (0..10).map(|i| format!("event-{i}")).collect();
+        for event in events(&contents) {
+            eventlog.write_log(&event.to_string(), 17, &[]).unwrap();
+        }
+        assert!(dir.path().join("eventlog.2").exists());
+
+        // The INIT entry, the events and a `CONTINUE` entry for each rotation
+        let all = eventlog.query(&EventFilter::default()).unwrap();
+        assert_eq!(all.len(), 13);
+        assert!(all
+            .iter()
+            .enumerate()
+            .all(|(seq, record)| record.seq == seq as u64));
+
+        let filter = EventFilter {
+            domain: Some("domain".into()),
+            ..Default::default()
+        };
+        let found: Vec<_> = eventlog
+            .query(&filter)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.content)
+            .collect();
+        assert_eq!(found, contents);
+        let filter = EventFilter {
+            register_index: Some(17),
+            ..Default::default()
+        };
+        assert_eq!(eventlog.query(&filter).unwrap().len(), 11);
+
+        // A rotated file that is gone is an error rather than a gap
+        std::fs::remove_file(dir.path().join("eventlog.1")).unwrap();
+        assert!(eventlog.query(&EventFilter::default()).is_err());
+    }
+
+    #[test]
+    fn test_entry_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            max_entries: Some(5),
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert_eq!(eventlog.usage(), Some(0.0));
+        eventlog.write_init_log(&init_entry(), 17, &[]).unwrap();
+
+        let contents: Vec<String> = (0..5).map(|i| format!("event-{i}")).collect();
+        let batch = events(&contents);
+        assert!(eventlog.check_capacity(&batch[..4], 17).is_ok());
+        assert_eq!(
+            eventlog.check_capacity(&batch, 17),
+            Err(EventLogFull::Entries { max_entries: 5 })
+        );
+
+        for event in &batch[..3] {
+            eventlog.write_log(&event.to_string(), 17, &[]).unwrap();
+        }
+        assert_eq!(eventlog.usage(), Some(0.8));
+        assert!(eventlog.check_capacity(&batch[3..4], 17).is_ok());
+        assert_eq!(
+            eventlog.check_capacity(&batch[3..], 17),
+            Err(EventLogFull::Entries { max_entries: 5 })
+        );
+    }
+
+    #[test]
+    fn test_rotate_entry_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let config = EventlogConfig {
+            file_path: path.clone(),
+            max_entries: Some(3),
+            rotation: EventlogRotation::Rotate,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        eventlog.write_init_log(&init_entry(), 17, &[]).unwrap();
+
+        let contents: Vec<String> = (0..4).map(|i| format!("event-{i}")).collect();
+        let batch = events(&contents);
+        assert!(eventlog.check_capacity(&batch, 17).is_ok());
+        let mut entries = vec![init_entry()];
+        for event in &batch {
+            eventlog.write_log(&event.to_string(), 17, &[]).unwrap();
+            entries.push(event.to_string());
+        }
+
+        // A rotating eventlog is never full, the usage is of the new file
+        assert!(eventlog.rotates());
+        assert_eq!(eventlog.usage(), Some(1.0));
+        let active = eventlog.read_log(0).unwrap();
+        assert_eq!(active.len(), 3);
+        assert!(active[0].ends_with(" eventlog.1"));
+        assert_eq!(
+            replay(&active, HashAlgorithm::Sha384, vec![0; 48]),
+            replay(&entries, HashAlgorithm::Sha384, vec![0; 48])
+        );
+    }
+
+    #[test]
+    fn test_rotate_oversized_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let eventlog = limited_log(&dir.path().join("eventlog"), 256, EventlogRotation::Rotate);
+
+        let contents = vec!["x".repeat(128)];
+        assert_eq!(
+            eventlog.check_capacity(&events(&contents), 17),
+            Err(EventLogFull::Size { max_size: 256 })
+        );
+    }
 
     #[rstest]
     #[case(
-        "domain",
-        "operation",
-        "content",
-        "65aad3b1620d4fe224d727579db2db87ff5c033f3e4424ae0fd72eb1149d3bd5",
-        HashAlgorithm::Sha256
+        HashAlgorithm::Sha256,
+        [
+            "b06eb37351451acf6922cb526bd800da14e43f0bd20a55ea2ad6a5e9b4349b95",
+            "fd8ea607826e8caad37b09e990f14ad5293a12212f77a7971c44d3443a7afa78",
+            "bf27f4ada139a05c84726588d0098d61c510a245653ff41692e29471ea78a485",
+        ]
     )]
-    #[case("domain", "operation", "content", "26d944cb8d99096590252283b8c807b9508329b068703bdb7bac7eb6efe5b32fc0fadf1462662b95d2c708aa49c0bfe1", HashAlgorithm::Sha384)]
-    #[case("domain", "operation", "content", "6e75837e0fbf8367fa4550254b8f0f52eb659be0901340357ed91dda97f0ebca10537540a021eec78df9d29ade51609a01eaaa46d32e0218cdac1644dc9933b0", HashAlgorithm::Sha512)]
-    fn test_event_digest(
-        #[case] domain: &str,
-        #[case] operation: &str,
-        #[case] content: &str,
-        #[case] digest: &str,
-        #[case] hash_alg: HashAlgorithm,
-    ) {
-        let event = EventEntry::new(domain, operation, content);
-        let dig = event.digest_with(hash_alg);
-        let dig_hex = dig.iter().map(|c| format!("{c:02x}")).collect::<String>();
-        assert_eq!(dig_hex, digest);
+    #[case(
+        HashAlgorithm::Sha384,
+        [
+            "6c365f76661093e45396aa72e4b9da060ba558a71df61eda609c51ae4b47908d2d04abde885a1501689217d0a2419613",
+            "3687c45c9321e0e7522cbec7b231ac359f5df54971373c2cb2d9a71d2822c1903bfb9fc54517457500a50c2aa9538a03",
+            "fb059ced1b17ccfc7a96e0d725c938c24ab04138ebf4541688b064900e9d21c37a3adab0554506b842a847ba1287a94b",
+        ]
+    )]
+    fn test_replay_known_answers(#[case] hash_alg: HashAlgorithm, #[case] expected: [&str; 3]) {
+        let dir = tempfile::tempdir().unwrap();
+        let mut eventlog =
+            EventLog::create(&dir.path().join("eventlog"), EventlogSync::Never).unwrap();
+        let initial = vec![0; hash_alg.digest_len()];
+        let init = format!("INIT {hash_alg}/{}", hex::encode(&initial));
+        eventlog.write_init_log(&init, 17, &[]).unwrap();
+        eventlog
+            .write_log("domain operation content", 17, &[])
+            .unwrap();
+        eventlog
+            .write_log(
+                "github.com/confidential-containers PullImage docker.io/library/busybox:latest",
+                17,
+                &[],
+            )
+            .unwrap();
+
+        let entries = eventlog.read_entries().unwrap();
+        let current = hex::decode(expected[2]).unwrap();
+        assert_eq!(eventlog.replay(hash_alg, initial.clone()).unwrap(), current);
+        assert_eq!(
+            diverging_entry(&entries, hash_alg, initial.clone(), &current),
+            None
+        );
+
+        // The register lacks the last entries
+        for (index, expected) in expected[..2].iter().enumerate() {
+            let current = hex::decode(expected).unwrap();
+            assert_eq!(
+                diverging_entry(&entries, hash_alg, initial.clone(), &current),
+                Some(index + 1)
+            );
+        }
+        // Nothing is extended
+        assert_eq!(
+            diverging_entry(&entries, hash_alg, initial.clone(), &initial),
+            Some(0)
+        );
+        // The register cannot be reached from the entries
+        assert_eq!(
+            diverging_entry(&entries, hash_alg, initial, &[0xff; 32]),
+            None
+        );
     }
 }
@@ -0,0 +1,201 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! The eventlog of the firmware together with the eventlogs of AA, grouped
+//! by the register of the platform they are extended into, s.t. a verifier
+//! can explain the whole value of each register, e.g. of the RTMRs of TDX.
+
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{firmware::FirmwareEvent, EventRecord, CONTINUE_ENTRY_PREFIX};
+use crate::config::HashAlgorithm;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CombinedEventLog {
+    /// The registers in order of their index.
+    pub registers: Vec<CombinedRegister>,
+}
+
+/// The events extended into a register of the platform, in order.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct CombinedRegister {
+    /// Index of the register in the numbering of the firmware eventlog,
+    /// see [`FirmwareEvent::register_index`], or in the numbering of AA if
+    /// the platform has no firmware eventlog.
+    pub register_index: u64,
+
+    pub events: Vec<CombinedEvent>,
+}
+
+/// An event of a register, marked with the eventlog it is from.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum CombinedEvent {
+    Firmware(FirmwareEvent),
+
+    /// An entry of the eventlog of AA, whose `register_index` is in the
+    /// numbering of AA.
+    Aael(EventRecord),
+}
+
+impl CombinedEventLog {
+    /// Group the events by their register. `firmware_register` gives the
+    /// register of the firmware eventlog that a register of AA is extended
+    /// into. The events of the firmware, which are extended at boot, come
+    /// before the entries of AA, which are in order of the registers of AA.
+    pub fn new(
+        firmware: Vec<FirmwareEvent>,
+        aael: Vec<(u64, Vec<EventRecord>)>,
+        firmware_register: impl Fn(u64) -> u64,
+    ) -> Self {
+        let mut registers: BTreeMap<u64, Vec<CombinedEvent>> = BTreeMap::new();
+        for event in firmware {
+            registers
+                .entry(event.register_index)
+                .or_default()
+                .push(CombinedEvent::Firmware(event));
+        }
+        for (register_index, records) in aael {
+            registers
+                .entry(firmware_register(register_index))
+                .or_default()
+                .extend(records.into_iter().map(CombinedEvent::Aael));
+        }
+
+        Self {
+            registers: registers
+                .into_iter()
+                .map(|(register_index, events)| CombinedRegister {
+                    register_index,
+                    events,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn register(&self, register_index: u64) -> Option<&CombinedRegister> {
+        self.registers
+            .iter()
+            .find(|register| register.register_index == register_index)
+    }
+}
+
+impl CombinedRegister {
+    /// Replay the events into the register bank of `algorithm`, starting
+    /// from `initial`. Events without a digest of the algorithm are
+    /// extended into other banks, and events of the type `EV_NO_ACTION`
+    /// are not extended. A `CONTINUE` entry of AA sets the value instead.
+    pub fn replay(&self, algorithm: HashAlgorithm, initial: Vec<u8>) -> Result<Vec<u8>> {
+        let mut register = initial;
+        for event in &self.events {
+            let digest = match event {
+                CombinedEvent::Firmware(event) if event.is_extended() => {
+                    event.digests.get(&algorithm.to_string())
+                }
+                CombinedEvent::Aael(record) if record.algorithm == algorithm => {
+                    match &record.digest {
+                        Some(digest) => Some(digest),
+                        None => {
+                            register = continued_register(record, algorithm)?;
+                            continue;
+                        }
+                    }
+                }
+                _ => None,
+            };
+            if let Some(digest) = digest {
+                let digest = hex::decode(digest).context("digest is not hex encoded")?;
+                register = algorithm.digest(&[register, digest].concat());
+            }
+        }
+        Ok(register)
+    }
+}
+
+/// The register value of the record of a `CONTINUE` entry, whose operation
+/// is `<algorithm>/<hex register value>`.
+fn continued_register(record: &EventRecord, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    let register = record
+        .operation
+        .strip_prefix(&format!("{algorithm}/"))
+        .filter(|_| record.domain == CONTINUE_ENTRY_PREFIX.trim_end())
+        .context("an entry without digest is not a `CONTINUE` entry")?;
+    hex::decode(register).context("the register value is not hex encoded")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::{EventlogSync, HashAlgorithm},
+        eventlog::EventLog,
+    };
+
+    use super::CombinedEventLog;
+
+    /// The CCEL is made up rather than recorded from a TD, and the RTMRs
+    /// it replays to with the eventlog of AA are calculated apart from AA
+    /// by `test/eventlog/tdx_rtmrs.py`.
+    #[cfg(feature = "tdx-attester")]
+    #[test]
+    fn test_replay_tdx_registers() {
+        use attester::{tdx::TdxAttester, Attester};
+
+        use super::CombinedEvent;
+        use crate::eventlog::firmware;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        std::fs::copy("../deps/aael/test/aael.log", &path).unwrap();
+        let eventlog = EventLog::create(&path, EventlogSync::OnBatch).unwrap();
+
+        // The eventlog of AA is of PCR 17, which is extended into RTMR 3
+        let attester = TdxAttester::new(Default::default());
+        let firmware = firmware::parse(include_bytes!("../../test/eventlog/ccel.bin")).unwrap();
+        let combined = CombinedEventLog::new(
+            firmware,
+            vec![(17, eventlog.records().unwrap())],
+            |register_index| attester.firmware_register_index(register_index),
+        );
+        let json = serde_json::to_string(&combined).unwrap();
+        let combined: CombinedEventLog = serde_json::from_str(&json).unwrap();
+
+        let rtmrs: Vec<String> =
+            serde_json::from_str(include_str!("../../test/eventlog/tdx_rtmrs.json")).unwrap();
+        for (rtmr, expected) in rtmrs.iter().enumerate() {
+            let register = combined.register(rtmr as u64 + 1).unwrap();
+            let replayed = register.replay(HashAlgorithm::Sha384, vec![0; 48]).unwrap();
+            assert_eq!(hex::encode(replayed), *expected, "RTMR {rtmr}");
+        }
+        assert!(combined.register(4).unwrap().events.iter().all(|event| {
+            matches!(event, CombinedEvent::Aael(record) if record.register_index == Some(17))
+        }));
+    }
+
+    #[test]
+    fn test_aael_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        std::fs::copy("../deps/aael/test/aael.log", &path).unwrap();
+        let eventlog = EventLog::create(&path, EventlogSync::OnBatch).unwrap();
+
+        let combined = CombinedEventLog::new(
+            Vec::new(),
+            vec![(17, eventlog.records().unwrap())],
+            |index| index,
+        );
+        assert_eq!(combined.registers.len(), 1);
+        assert_eq!(
+            combined
+                .register(17)
+                .unwrap()
+                .replay(HashAlgorithm::Sha384, vec![0; 48])
+                .unwrap(),
+            eventlog.replay(HashAlgorithm::Sha384, vec![0; 48]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,206 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! The eventlog of the firmware in the TCG2 crypto agile format, e.g. the
+//! CC eventlog (CCEL) ACPI table of TDX, see the TCG PC Client Platform
+//! Firmware Profile. The first event is the Spec ID event in the SHA-1
+//! format, which gives the digest sizes of the algorithms of the others.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+pub use aael::cel::EV_NO_ACTION;
+
+const SPEC_ID_SIGNATURE: &[u8] = b"Spec ID Event03\0";
+
+/// Size of the SHA-1 digest of the Spec ID event.
+const SHA1_DIGEST_SIZE: usize = 20;
+
+/// An event of the firmware eventlog.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct FirmwareEvent {
+    /// Register the event is extended into, in the numbering of the
+    /// eventlog, e.g. the CC measurement register index on TDX, where
+    /// RTMR `n` is `n + 1`.
+    pub register_index: u64,
+
+    pub event_type: u32,
+
+    /// Hex encoded digests by the name of their algorithm, e.g. `sha384`.
+    pub digests: BTreeMap<String, String>,
+
+    /// Base64 encoded event data.
+    pub data: String,
+}
+
+impl FirmwareEvent {
+    /// Whether the event is extended into its register.
+    pub fn is_extended(&self) -> bool {
+        self.event_type != EV_NO_ACTION
+    }
+}
+
+/// Parse the events of the eventlog. The unused space after the last
+/// event, which is filled with `0xff` or zeros, is skipped.
+pub fn parse(log: &[u8]) -> Result<Vec<FirmwareEvent>> {
+    let mut reader = Reader { log, offset: 0 };
+    let (spec_id, digest_sizes) = reader.spec_id_event().context("Spec ID event")?;
+    let mut events = vec![spec_id];
+    while !reader.at_end() {
+        let event = reader
+            .event(&digest_sizes)
+            .with_context(|| format!("event {}", events.len()))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+/// Name of the algorithm of the TCG algorithm ID, as of [`HashAlgorithm`]
+/// for the ones it has.
+///
+/// [`HashAlgorithm`]: crate::config::HashAlgorithm
+fn algorithm_name(id: u16) -> String {
+    match id {
+        0x0004 => "sha1".into(),
+        0x000b => "sha256".into(),
+        0x000c => "sha384".into(),
+        0x000d => "sha512".into(),
+        0x0012 => "sm3_256".into(),
+        _ => format!("0x{id:04x}"),
+    }
+}
+
+struct Reader<'a> {
+    log: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.log.get(self.offset..self.offset + len) else {
+            bail!("truncated at offset {}", self.offset);
+        };
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    /// Whether the rest of the log is unused space.
+    fn at_end(&self) -> bool {
+        let rest = &self.log[self.offset..];
+        rest.iter().all(|byte| *byte == 0xff) || rest.iter().all(|byte| *byte == 0)
+    }
+
+    /// The Spec ID event with the digest size of each algorithm ID.
+    fn spec_id_event(&mut self) -> Result<(FirmwareEvent, BTreeMap<u16, usize>)> {
+        let register_index = self.u32()?.into();
+        let event_type = self.u32()?;
+        let digest = self.bytes(SHA1_DIGEST_SIZE)?;
+        let size = self.u32()? as usize;
+        let data = self.bytes(size)?;
+        if event_type != EV_NO_ACTION || !data.starts_with(SPEC_ID_SIGNATURE) {
+            bail!("the eventlog is not in the crypto agile format");
+        }
+
+        // Platform class, spec version, errata and uintn size
+        let mut spec_id = Reader {
+            log: data,
+            offset: SPEC_ID_SIGNATURE.len() + 8,
+        };
+        let mut digest_sizes = BTreeMap::new();
+        for _ in 0..spec_id.u32()? {
+            let id = spec_id.u16()?;
+            digest_sizes.insert(id, spec_id.u16()?.into());
+        }
+        let vendor_info_size = spec_id.u8()?;
+        spec_id.bytes(vendor_info_size.into())?;
+
+        let event = FirmwareEvent {
+            register_index,
+            event_type,
+            digests: BTreeMap::from([(algorithm_name(0x0004), hex::encode(digest))]),
+            data: STANDARD.encode(data),
+        };
+        Ok((event, digest_sizes))
+    }
+
+    fn event(&mut self, digest_sizes: &BTreeMap<u16, usize>) -> Result<FirmwareEvent> {
+        let register_index = self.u32()?.into();
+        let event_type = self.u32()?;
+        let mut digests = BTreeMap::new();
+        for _ in 0..self.u32()? {
+            let id = self.u16()?;
+            let Some(size) = digest_sizes.get(&id) else {
+                bail!("algorithm 0x{id:04x} is not in the Spec ID event");
+            };
+            digests.insert(algorithm_name(id), hex::encode(self.bytes(*size)?));
+        }
+        let size = self.u32()? as usize;
+        let data = self.bytes(size)?;
+        Ok(FirmwareEvent {
+            register_index,
+            event_type,
+            digests,
+            data: STANDARD.encode(data),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{parse, EV_NO_ACTION};
+
+    #[test]
+    fn test_parse_ccel() {
+        let events = parse(include_bytes!("../../test/eventlog/ccel.bin")).unwrap();
+        assert_eq!(events.len(), 9);
+        assert_eq!(events[0].event_type, EV_NO_ACTION);
+        assert!(events
+            .iter()
+            .skip(1)
+            .all(|event| event.digests.len() == 1 && event.digests["sha384"].len() == 96));
+        assert_eq!(
+            events
+                .iter()
+                .filter(|event| event.is_extended())
+                .map(|event| event.register_index)
+                .collect::<Vec<_>>(),
+            vec![1, 1, 1, 1, 2, 2, 3]
+        );
+    }
+
+    #[rstest]
+    #[case::truncated(600, "event 7")]
+    #[case::spec_id(10, "Spec ID event")]
+    fn test_parse_malformed(#[case] len: usize, #[case] context: &str) {
+        let log = include_bytes!("../../test/eventlog/ccel.bin");
+        let e = parse(&log[..len]).unwrap_err();
+        assert!(format!("{e:#}").contains(context), "{e:#}");
+    }
+
+    #[test]
+    fn test_parse_not_crypto_agile() {
+        let mut log = include_bytes!("../../test/eventlog/ccel.bin").to_vec();
+        log[32] = b'X';
+        let e = parse(&log).unwrap_err();
+        assert!(format!("{e:#}").contains("not in the crypto agile format"));
+    }
+}
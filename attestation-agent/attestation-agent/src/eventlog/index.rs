@@ -0,0 +1,168 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Index of the entries of an eventlog, s.t. they can be queried with an
+//! [`EventFilter`] without reading and parsing all of them. The index
+//! keeps where each line is, and the domain and operation of its entry
+//! interned, rather than the lines themselves.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{writer::LineLocation, FIELD_SEPARATOR};
+use crate::config::HashAlgorithm;
+
+/// Which entries [`super::EventLog::query`] returns. Fields that are not
+/// set match any entry.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct EventFilter {
+    /// Domain of the entries, e.g. `github.com/confidential-containers`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+
+    /// Prefix of the operation of the entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation_prefix: Option<String>,
+
+    /// Register the entries are extended into. The `CONTINUE` entry, which
+    /// is not extended, does not match any register.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub register_index: Option<u64>,
+
+    /// First sequence number of the entries, see
+    /// [`super::EntryMetadata::seq`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq_start: Option<u64>,
+
+    /// Sequence number after the last one of the entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seq_end: Option<u64>,
+}
+
+/// An entry of the eventlog as indexed.
+pub struct IndexedEntry {
+    pub location: LineLocation,
+    pub seq: u64,
+
+    /// Hash algorithm the entry is extended with.
+    pub algorithm: HashAlgorithm,
+
+    /// Whether the entry is extended into the register, i.e. it is not the
+    /// `CONTINUE` entry.
+    pub extended: bool,
+
+    domain: u32,
+    operation: u32,
+}
+
+#[derive(Default)]
+pub struct EventIndex {
+    entries: Vec<IndexedEntry>,
+
+    /// Domains and operations of the entries, each kept once, as there are
+    /// far fewer of them than entries.
+    names: Vec<String>,
+    name_ids: HashMap<String, u32>,
+
+    /// Digests extended for entries by the index of their line, as given
+    /// when they were recorded, where they are not the ones the lines
+    /// carry, see [`EventIndex::keep_digest`].
+    digests: HashMap<usize, Vec<u8>>,
+}
+
+impl EventIndex {
+    /// Index the entry recorded in the line at `location`.
+    pub fn push(
+        &mut self,
+        entry: &str,
+        location: LineLocation,
+        seq: u64,
+        algorithm: HashAlgorithm,
+        extended: bool,
+    ) {
+        let mut fields = entry.splitn(3, FIELD_SEPARATOR);
+        let domain = self.intern(fields.next().unwrap_or_default());
+        let operation = self.intern(fields.next().unwrap_or_default());
+        self.entries.push(IndexedEntry {
+            location,
+            seq,
+            algorithm,
+            extended,
+            domain,
+            operation,
+        });
+    }
+
+    fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.name_ids.get(name) {
+            return *id;
+        }
+        let id = self.names.len() as u32;
+        self.names.push(name.to_string());
+        self.name_ids.insert(name.to_string(), id);
+        id
+    }
+
+    /// Keep the digest extended for the entry at line `index`. Only the
+    /// digests that cannot be told from the line are kept, s.t. the index
+    /// does not grow with a digest per entry.
+    pub fn keep_digest(&mut self, index: usize, digest: &[u8]) {
+        self.digests.insert(index, digest.to_vec());
+    }
+
+    /// The digest kept for the entry at line `index`.
+    pub fn kept_digest(&self, index: usize) -> Option<&[u8]> {
+        self.digests.get(&index).map(Vec::as_slice)
+    }
+
+    /// Forget the entries from line `index` on, e.g. when they are
+    /// discarded.
+    pub fn truncate(&mut self, index: usize) {
+        let len = self
+            .entries
+            .partition_point(|entry| entry.location.index < index);
+        self.entries.truncate(len);
+        self.digests.retain(|line, _| *line < index);
+    }
+
+    /// Forget the entries, e.g. when the eventlog continues in a new file.
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn entries(&self) -> &[IndexedEntry] {
+        &self.entries
+    }
+
+    /// The entries that match the filter. The register of the entries is
+    /// the one of the eventlog, which is not checked here, apart from the
+    /// `CONTINUE` entry not matching any register.
+    pub fn matching<'a>(
+        &'a self,
+        filter: &'a EventFilter,
+    ) -> impl Iterator<Item = &'a IndexedEntry> + 'a {
+        let domain = filter
+            .domain
+            .as_ref()
+            .map(|domain| self.name_ids.get(domain).copied());
+        self.entries.iter().filter(move |entry| {
+            domain.is_none_or(|domain| domain == Some(entry.domain))
+                && filter.operation_prefix.as_ref().is_none_or(|prefix| {
+                    self.names[entry.operation as usize].starts_with(prefix.as_str())
+                })
+                && filter.seq_start.is_none_or(|start| entry.seq >= start)
+                && filter.seq_end.is_none_or(|end| entry.seq < end)
+                && (filter.register_index.is_none() || entry.extended)
+        })
+    }
+
+    /// Number of distinct domains and operations kept.
+    #[cfg(test)]
+    pub fn names(&self) -> usize {
+        self.names.len()
+    }
+}
@@ -0,0 +1,750 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Backends that keep the lines of an eventlog, selected by
+//! `eventlog_config.backend`. [`super::EventLog`] serializes the entries
+//! and leaves storing them to an [`EventLogWriter`].
+
+use std::{
+    collections::BTreeMap,
+    fs::{DirBuilder, File, OpenOptions},
+    io::{ErrorKind, Write},
+    net::TcpStream,
+    os::{
+        fd::AsRawFd,
+        unix::fs::{DirBuilderExt, FileExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex, PoisonError, Weak,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use nix::sys::socket::{connect, socket, AddressFamily, SockFlag, SockType, VsockAddr};
+use tracing::warn;
+use url::Url;
+
+use crate::config::{EventlogBackend, EventlogConfig, EventlogSync};
+
+/// Where the lines of an eventlog are kept. The lines are given without
+/// the line break.
+pub trait EventLogWriter: Send + Sync {
+    fn append(&mut self, line: &str) -> Result<()>;
+
+    /// Make the lines appended so far durable, at the end of a batch of
+    /// entries.
+    fn flush(&mut self) -> Result<()>;
+
+    /// All the lines appended so far, including the ones kept from before
+    /// AA restarted if the backend keeps any.
+    fn read_all(&self) -> Result<Vec<String>>;
+
+    /// The lines at the locations, in the order given.
+    fn read_lines(&self, locations: &[LineLocation]) -> Result<Vec<String>> {
+        read_kept_lines(&self.read_all()?, locations)
+    }
+
+    /// Drop the line at the location and the ones after it, e.g. the lines
+    /// of an entry whose digest failed to be extended.
+    fn truncate(&mut self, location: LineLocation) -> Result<()>;
+
+    /// Flush the lines and release the backend. Nothing is appended
+    /// afterwards.
+    fn close(&mut self) -> Result<()> {
+        self.flush()
+    }
+
+    /// Name of where [`EventLogWriter::rotate`] moves the lines to, which
+    /// the `CONTINUE` entry records. `None` if the backend cannot rotate.
+    fn rotated_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Move the lines so far aside, s.t. the eventlog continues empty.
+    fn rotate(&mut self) -> Result<()> {
+        bail!("the eventlog backend cannot rotate")
+    }
+
+    /// The lines that [`EventLogWriter::rotate`] moved to `name`.
+    fn read_rotated(&self, name: &str) -> Result<Vec<String>> {
+        bail!("the eventlog backend has no rotated eventlog {name}")
+    }
+
+    /// Move the lines so far aside as they are corrupt, s.t. the eventlog
+    /// starts anew. Returns where they are moved to.
+    fn archive(&mut self) -> Result<String> {
+        bail!("the eventlog backend cannot archive a corrupt eventlog")
+    }
+}
+
+/// Where a line is among the lines appended so far.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineLocation {
+    /// Index of the line, from 0.
+    pub index: usize,
+
+    /// Offset of the line in bytes, counting the line breaks.
+    pub offset: u64,
+
+    /// Length of the line in bytes, without the line break.
+    pub len: usize,
+}
+
+/// Create the writer of the backend of `config` that keeps the eventlog at
+/// `path`, which is the path of the file of the [`EventlogBackend::File`]
+/// backend. Failures carry the name of the backend.
+pub fn open(config: &EventlogConfig, path: &Path) -> Result<Box<dyn EventLogWriter>> {
+    let writer: Result<Box<dyn EventLogWriter>> = match config.backend {
+        EventlogBackend::File => {
+            FileWriter::open(path, config.sync).map(|writer| Box::new(writer) as _)
+        }
+        EventlogBackend::Memory => Ok(Box::<MemoryWriter>::default()),
+        EventlogBackend::Stream => config
+            .stream_address
+            .as_deref()
+            .ok_or_else(|| anyhow!("no `eventlog_config.stream_address` is configured"))
+            .and_then(|address| StreamWriter::connect(address, config.init_pcr))
+            .map(|writer| Box::new(writer) as _),
+    };
+    writer.with_context(|| format!("initialize the `{}` eventlog backend", config.backend))
+}
+
+/// The eventlog file, which is kept over restarts of AA. Unless synced
+/// with [`EventlogSync::Always`], the lines of a batch are buffered and
+/// written at once when it is flushed.
+pub struct FileWriter {
+    file: File,
+    path: PathBuf,
+    sync: EventlogSync,
+
+    /// Size of the file in bytes, as written so far.
+    written: u64,
+
+    /// Lines appended but not written yet, each with its line break. They
+    /// are kept when writing them fails, e.g. as the storage is full, and
+    /// written again at the next flush.
+    buffer: String,
+}
+
+impl FileWriter {
+    /// Open the file, creating it with the parent dirs if it does not
+    /// exist. New dirs and the file are only accessible by the user of AA,
+    /// as anyone who can write the eventlog can forge the entries a
+    /// verifier replays.
+    pub fn open(path: &Path, sync: EventlogSync) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            DirBuilder::new()
+                .recursive(true)
+                .mode(0o700)
+                .create(parent)
+                .with_context(|| format!("create eventlog parent dir {}", parent.display()))?;
+        }
+        let file = Self::create_file(path, false)?;
+        let written = file
+            .metadata()
+            .with_context(|| format!("stat eventlog {}", path.display()))?
+            .len();
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+            sync,
+            written,
+            buffer: String::new(),
+        })
+    }
+
+    fn create_file(path: &Path, create_new: bool) -> Result<File> {
+        OpenOptions::new()
+            .create(!create_new)
+            .create_new(create_new)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("create eventlog {}", path.display()))
+    }
+
+    /// Write the buffered lines to the file. If that fails, the part that
+    /// made it into the file is truncated, s.t. the file does not end in
+    /// the middle of a line, and the lines are kept to be written again.
+    fn write_buffer(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        if let Err(e) = self.file.write_all(self.buffer.as_bytes()) {
+            // Shrinking the file does not need space on the storage
+            let _ = self.file.set_len(self.written);
+            return Err(e).context("failed to write log");
+        }
+        self.written += self.buffer.len() as u64;
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+impl EventLogWriter for FileWriter {
+    fn append(&mut self, line: &str) -> Result<()> {
+        self.buffer.push_str(line);
+        self.buffer.push('\n');
+        if self.sync == EventlogSync::Always {
+            self.write_buffer()?;
+            self.file.sync_data().context("failed to sync eventlog")?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.write_buffer()?;
+        if self.sync == EventlogSync::OnBatch {
+            self.file.sync_data().context("failed to sync eventlog")?;
+        }
+        Ok(())
+    }
+
+    /// The lines of the file, followed by the buffered ones. A file that is
+    /// not UTF-8, or ends in the middle of a line, is an error.
+    fn read_all(&self) -> Result<Vec<String>> {
+        let existing = match std::fs::read(&self.path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("read eventlog {}", self.path.display()))
+            }
+        };
+        let existing = String::from_utf8(existing).context("the eventlog is not UTF-8")?;
+        if !existing.is_empty() && !existing.ends_with('\n') {
+            bail!("the last entry is incomplete");
+        }
+        Ok(existing
+            .lines()
+            .chain(self.buffer.lines())
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    /// Read the lines at their offsets, rather than the whole file.
+    fn read_lines(&self, locations: &[LineLocation]) -> Result<Vec<String>> {
+        let file = File::open(&self.path)
+            .with_context(|| format!("read eventlog {}", self.path.display()))?;
+        locations
+            .iter()
+            .map(|location| {
+                if let Some(start) = location.offset.checked_sub(self.written) {
+                    let start = start as usize;
+                    return self
+                        .buffer
+                        .get(start..start + location.len)
+                        .map(ToString::to_string)
+                        .with_context(|| format!("no line {} in the eventlog", location.index));
+                }
+                let mut line = vec![0; location.len];
+                file.read_exact_at(&mut line, location.offset)
+                    .with_context(|| format!("read line {} of the eventlog", location.index))?;
+                String::from_utf8(line).context("the eventlog is not UTF-8")
+            })
+            .collect()
+    }
+
+    /// Drop the lines from the buffer, or truncate the file if they were
+    /// written, and sync it unless [`EventlogSync::Never`].
+    fn truncate(&mut self, location: LineLocation) -> Result<()> {
+        if let Some(start) = location.offset.checked_sub(self.written) {
+            self.buffer.truncate(start as usize);
+            return Ok(());
+        }
+        self.buffer.clear();
+        self.file
+            .set_len(location.offset)
+            .context("failed to truncate eventlog")?;
+        self.written = location.offset;
+        if self.sync != EventlogSync::Never {
+            self.file.sync_data().context("failed to sync eventlog")?;
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) -> Result<()> {
+        self.write_buffer()?;
+        self.file.sync_all().context("failed to sync eventlog")
+    }
+
+    fn rotated_name(&self) -> Option<String> {
+        let rotated = first_free_path(&self.path, "");
+        Some(
+            rotated
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned(),
+        )
+    }
+
+    /// Rename the file to the next free index and continue in a new file.
+    fn rotate(&mut self) -> Result<()> {
+        let rotated = first_free_path(&self.path, "");
+        self.close()?;
+        std::fs::rename(&self.path, &rotated)
+            .with_context(|| format!("rotate eventlog to {}", rotated.display()))?;
+        self.file = Self::create_file(&self.path, true)?;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Read the rotated file `name`, which is next to the file.
+    fn read_rotated(&self, name: &str) -> Result<Vec<String>> {
+        if Path::new(name).file_name() != Some(std::ffi::OsStr::new(name)) {
+            bail!("`{name}` is not the name of a rotated eventlog");
+        }
+        let path = self.path.with_file_name(name);
+        let rotated = std::fs::read_to_string(&path)
+            .with_context(|| format!("read rotated eventlog {}", path.display()))?;
+        Ok(rotated.lines().map(ToString::to_string).collect())
+    }
+
+    /// Rename the file to the next free index of `<path>.corrupt`.
+    fn archive(&mut self) -> Result<String> {
+        let archived = first_free_path(&self.path, ".corrupt");
+        std::fs::rename(&self.path, &archived)
+            .with_context(|| format!("archive eventlog to {}", archived.display()))?;
+        self.file = Self::create_file(&self.path, true)?;
+        self.written = 0;
+        self.buffer.clear();
+        Ok(archived.display().to_string())
+    }
+}
+
+/// The first free path of the form `<path><suffix>.<index>`, counting
+/// from 1.
+fn first_free_path(path: &Path, suffix: &str) -> PathBuf {
+    (1..)
+        .map(|index| {
+            let mut path = path.to_path_buf().into_os_string();
+            path.push(format!("{suffix}.{index}"));
+            PathBuf::from(path)
+        })
+        .find(|path| !path.exists())
+        .expect("a free index")
+}
+
+/// Lines kept in memory, which are lost when AA exits, e.g. for tests or
+/// when there is no writable filesystem.
+#[derive(Default)]
+pub struct MemoryWriter {
+    lines: Vec<String>,
+}
+
+impl EventLogWriter for MemoryWriter {
+    fn append(&mut self, line: &str) -> Result<()> {
+        self.lines.push(line.to_string());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn read_all(&self) -> Result<Vec<String>> {
+        Ok(self.lines.clone())
+    }
+
+    fn read_lines(&self, locations: &[LineLocation]) -> Result<Vec<String>> {
+        read_kept_lines(&self.lines, locations)
+    }
+
+    fn truncate(&mut self, location: LineLocation) -> Result<()> {
+        self.lines.truncate(location.index);
+        Ok(())
+    }
+}
+
+/// Lines queued for a collector at most. While the collector cannot be
+/// reached, the lines beyond are dropped rather than kept in memory.
+const STREAM_QUEUE_LINES: usize = 4096;
+
+/// Backoff before connecting to a collector again, doubled after each
+/// failure up to [`STREAM_MAX_BACKOFF`].
+const STREAM_MIN_BACKOFF: Duration = Duration::from_millis(100);
+const STREAM_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The streams to the collectors by address, s.t. the eventlogs of all the
+/// registers share one connection.
+static STREAMS: Mutex<BTreeMap<String, Weak<Stream>>> = Mutex::new(BTreeMap::new());
+
+/// Lines streamed to a collector, e.g. on the host for archival, over a
+/// connection to `vsock://<cid>:<port>` or `tcp://<host>:<port>`. Each
+/// line is sent as `<register index> <line>` with its line break, as the
+/// eventlogs of all the registers share the connection. The lines are also
+/// kept in memory, as the collector is not read back.
+///
+/// The lines are sent by a thread of the connection, s.t. appending does
+/// not wait for the collector. It connects again with a backoff when the
+/// connection fails, and drops the lines beyond [`STREAM_QUEUE_LINES`]
+/// meanwhile.
+pub struct StreamWriter {
+    stream: Arc<Stream>,
+    register_index: u64,
+    lines: Vec<String>,
+}
+
+impl StreamWriter {
+    /// The writer of the eventlog of `register_index`. A malformed address
+    /// is an error, while a collector that cannot be reached yet is not.
+    pub fn connect(address: &str, register_index: u64) -> Result<Self> {
+        let mut streams = STREAMS.lock().unwrap_or_else(PoisonError::into_inner);
+        let stream = match streams.get(address).and_then(Weak::upgrade) {
+            Some(stream) => stream,
+            None => {
+                let stream = Arc::new(Stream::spawn(StreamAddress::parse(address)?)?);
+                streams.insert(address.to_string(), Arc::downgrade(&stream));
+                stream
+            }
+        };
+
+        Ok(Self {
+            stream,
+            register_index,
+            lines: Vec::new(),
+        })
+    }
+}
+
+impl EventLogWriter for StreamWriter {
+    fn append(&mut self, line: &str) -> Result<()> {
+        self.stream
+            .send(format!("{} {line}\n", self.register_index));
+        self.lines.push(line.to_string());
+        Ok(())
+    }
+
+    /// The lines are sent as they are appended, and the collector is not
+    /// waited for.
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn read_all(&self) -> Result<Vec<String>> {
+        Ok(self.lines.clone())
+    }
+
+    fn read_lines(&self, locations: &[LineLocation]) -> Result<Vec<String>> {
+        read_kept_lines(&self.lines, locations)
+    }
+
+    /// The lines that were sent are kept by the collector, which has to
+    /// tell them from the lines that follow by their register value.
+    fn truncate(&mut self, location: LineLocation) -> Result<()> {
+        self.lines.truncate(location.index);
+        Ok(())
+    }
+}
+
+/// The queue of a connection to a collector, whose thread sends the lines.
+struct Stream {
+    address: String,
+    queue: SyncSender<String>,
+
+    /// Set when the writers are gone, s.t. the thread stops connecting.
+    closed: Arc<AtomicBool>,
+}
+
+impl Stream {
+    fn spawn(address: StreamAddress) -> Result<Self> {
+        let (queue, lines) = mpsc::sync_channel(STREAM_QUEUE_LINES);
+        let closed = Arc::new(AtomicBool::new(false));
+        let stream = Self {
+            address: address.to_string(),
+            queue,
+            closed: closed.clone(),
+        };
+        std::thread::Builder::new()
+            .name("eventlog-stream".into())
+            .spawn(move || send_lines(&address, lines, &closed))
+            .context("spawn the eventlog stream thread")?;
+        Ok(stream)
+    }
+
+    fn send(&self, line: String) {
+        match self.queue.try_send(line) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                warn!(
+                    address = %self.address,
+                    "eventlog stream is full, dropping an entry"
+                )
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                warn!(
+                    address = %self.address,
+                    "eventlog stream is gone, dropping an entry"
+                )
+            }
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Send the lines of the queue to the collector until the writers are gone,
+/// connecting again after a failure. A line that fails to be sent is sent
+/// again on the new connection.
+fn send_lines(address: &StreamAddress, lines: Receiver<String>, closed: &AtomicBool) {
+    let mut connection = None;
+    let mut backoff = STREAM_MIN_BACKOFF;
+    for line in lines {
+        loop {
+            let mut stream = match connection.take() {
+                Some(stream) => stream,
+                None => match address.connect() {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!(%address, "failed to connect to the eventlog collector: {e:#}");
+                        if closed.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        std::thread::sleep(backoff);
+                        backoff = (backoff * 2).min(STREAM_MAX_BACKOFF);
+                        continue;
+                    }
+                },
+            };
+            match stream.write_all(line.as_bytes()) {
+                Ok(()) => {
+                    connection = Some(stream);
+                    backoff = STREAM_MIN_BACKOFF;
+                    break;
+                }
+                Err(e) => warn!(%address, "failed to stream the eventlog: {e}"),
+            }
+        }
+    }
+}
+
+/// A `vsock://<cid>:<port>` or `tcp://<host>:<port>` address.
+enum StreamAddress {
+    Tcp(String, u16),
+    Vsock(u32, u16),
+}
+
+impl StreamAddress {
+    fn parse(address: &str) -> Result<Self> {
+        let url = Url::parse(address).with_context(|| format!("invalid address `{address}`"))?;
+        let (Some(host), Some(port)) = (url.host_str(), url.port()) else {
+            bail!("address `{address}` has no host and port");
+        };
+        match url.scheme() {
+            "tcp" => Ok(Self::Tcp(host.to_string(), port)),
+            "vsock" => {
+                let cid = host
+                    .parse()
+                    .with_context(|| format!("invalid vsock CID `{host}`"))?;
+                Ok(Self::Vsock(cid, port))
+            }
+            scheme => bail!("scheme `{scheme}` is not one of tcp, vsock"),
+        }
+    }
+
+    fn connect(&self) -> Result<Box<dyn Write + Send>> {
+        match self {
+            Self::Tcp(host, port) => Ok(Box::new(TcpStream::connect((host.as_str(), *port))?)),
+            Self::Vsock(cid, port) => {
+                let fd = socket(
+                    AddressFamily::Vsock,
+                    SockType::Stream,
+                    SockFlag::SOCK_CLOEXEC,
+                    None,
+                )
+                .context("create vsock socket")?;
+                connect(fd.as_raw_fd(), &VsockAddr::new(*cid, (*port).into()))?;
+                Ok(Box::new(File::from(fd)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for StreamAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tcp(host, port) => write!(f, "tcp://{host}:{port}"),
+            Self::Vsock(cid, port) => write!(f, "vsock://{cid}:{port}"),
+        }
+    }
+}
+
+/// The lines at the locations among all the lines.
+fn read_kept_lines(lines: &[String], locations: &[LineLocation]) -> Result<Vec<String>> {
+    locations
+        .iter()
+        .map(|location| {
+            lines
+                .get(location.index)
+                .cloned()
+                .with_context(|| format!("no line {} in the eventlog", location.index))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, ErrorKind},
+        path::Path,
+    };
+
+    use super::{EventLogWriter, FileWriter, LineLocation, MemoryWriter, StreamWriter};
+    use crate::config::EventlogSync;
+
+    #[test]
+    fn test_file_writer_buffers_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut writer = FileWriter::open(&path, EventlogSync::OnBatch).unwrap();
+        writer.append("INIT sha384/00").unwrap();
+        writer.append("domain operation content").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+
+        let lines = vec!["INIT sha384/00", "domain operation content"];
+        let second = LineLocation {
+            index: 1,
+            offset: 15,
+            len: 24,
+        };
+        assert_eq!(writer.read_all().unwrap(), lines);
+        assert_eq!(writer.read_lines(&[second]).unwrap(), vec![lines[1]]);
+
+        writer.flush().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "INIT sha384/00\ndomain operation content\n"
+        );
+        assert_eq!(writer.read_all().unwrap(), lines);
+        assert_eq!(writer.read_lines(&[second]).unwrap(), vec![lines[1]]);
+    }
+
+    #[test]
+    fn test_file_writer_truncate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut writer = FileWriter::open(&path, EventlogSync::OnBatch).unwrap();
+        let second = LineLocation {
+            index: 1,
+            offset: 15,
+            len: 0,
+        };
+
+        // A line in the buffer
+        writer.append("INIT sha384/00").unwrap();
+        writer.append("domain operation content").unwrap();
+        writer.truncate(second).unwrap();
+        writer.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "INIT sha384/00\n");
+
+        // A line in the file
+        writer.append("domain operation content").unwrap();
+        writer.flush().unwrap();
+        writer.truncate(second).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "INIT sha384/00\n");
+        writer.append("domain operation other").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "INIT sha384/00\ndomain operation other\n"
+        );
+    }
+
+    #[test]
+    #[cfg_attr(not(target_os = "linux"), ignore = "needs /dev/full")]
+    fn test_file_writer_no_space() {
+        // Any write to /dev/full fails with ENOSPC
+        let path = Path::new("/dev/full");
+        assert!(path.exists(), "/dev/full is missing");
+        let mut writer = FileWriter::open(path, EventlogSync::Never).unwrap();
+        writer.append("domain operation content").unwrap();
+        let e = writer.flush().unwrap_err();
+        let e = e.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(e.kind(), ErrorKind::StorageFull);
+
+        // The line is kept, and written again
+        assert!(writer.flush().is_err());
+        assert!(writer.close().is_err());
+    }
+
+    #[test]
+    fn test_memory_writer() {
+        let mut writer = MemoryWriter::default();
+        writer.append("INIT sha384/00").unwrap();
+        writer.append("domain operation content").unwrap();
+        writer.flush().unwrap();
+        assert_eq!(
+            writer.read_all().unwrap(),
+            vec!["INIT sha384/00", "domain operation content"]
+        );
+        assert!(writer.rotated_name().is_none());
+        assert!(writer.rotate().is_err());
+    }
+
+    #[test]
+    fn test_stream_writer() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("tcp://{}", listener.local_addr().unwrap());
+
+        // The registers share the connection
+        let mut writer = StreamWriter::connect(&address, 17).unwrap();
+        let mut other = StreamWriter::connect(&address, 18).unwrap();
+        writer.append("INIT sha384/00").unwrap();
+        other.append("INIT sha384/01").unwrap();
+        writer.append("domain operation content").unwrap();
+        writer.flush().unwrap();
+        let (collector, _) = listener.accept().unwrap();
+        assert_eq!(other.read_all().unwrap(), vec!["INIT sha384/01"]);
+        drop((writer, other));
+
+        let lines: Vec<String> = BufReader::new(collector)
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "17 INIT sha384/00",
+                "18 INIT sha384/01",
+                "17 domain operation content"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_writer_reconnects() {
+        // The collector is not up yet
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        drop(listener);
+        let address = format!("tcp://127.0.0.1:{port}");
+        let mut writer = StreamWriter::connect(&address, 17).unwrap();
+        writer.append("INIT sha384/00").unwrap();
+
+        let listener = std::net::TcpListener::bind(("127.0.0.1", port)).unwrap();
+        let (collector, _) = listener.accept().unwrap();
+        drop(writer);
+        let lines: Vec<String> = BufReader::new(collector)
+            .lines()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(lines, vec!["17 INIT sha384/00"]);
+    }
+
+    #[test]
+    fn test_stream_writer_address() {
+        for address in ["udp://127.0.0.1:1", "tcp://127.0.0.1", "vsock://host:1"] {
+            assert!(StreamWriter::connect(address, 17).is_err(), "{address}");
+        }
+    }
+}
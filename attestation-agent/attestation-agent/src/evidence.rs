@@ -0,0 +1,340 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{config::HashAlgorithm, Error};
+
+/// Version of the [`Evidence`] schema. Bump this when fields are changed
+/// in an incompatible way.
+pub const EVIDENCE_FORMAT_VERSION: u32 = 1;
+
+/// [`Evidence::tee`] of composite evidence, whose content is a
+/// [`CompositeEvidence`] rather than the evidence of a single TEE type.
+pub const COMPOSITE_EVIDENCE_TEE: &str = "composite";
+
+/// Evidence produced by the attester together with the information a
+/// verifier needs to dispatch it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Evidence {
+    /// Name of the TEE type that produced the evidence, e.g. `tdx`, or
+    /// [`COMPOSITE_EVIDENCE_TEE`] in the composite evidence mode.
+    pub tee: String,
+
+    /// Version of this struct's schema, s.t. [`EVIDENCE_FORMAT_VERSION`].
+    pub format_version: u32,
+
+    /// The raw evidence produced by the attester. It is base64 encoded
+    /// when serialized.
+    #[serde(with = "base64_bytes")]
+    pub evidence: Vec<u8>,
+}
+
+impl Evidence {
+    pub fn new(tee: String, evidence: Vec<u8>) -> Self {
+        Self {
+            tee,
+            format_version: EVIDENCE_FORMAT_VERSION,
+            evidence,
+        }
+    }
+
+    /// Return the raw evidence, s.t. the output of the `get_evidence` API.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.evidence
+    }
+}
+
+/// Evidence together with the eventlog entries that were recorded when it
+/// was generated, s.t. a verifier can replay the eventlog against the
+/// registers in the evidence without fetching the eventlog separately.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvidenceWithEventLog {
+    #[serde(flatten)]
+    pub evidence: Evidence,
+
+    /// All the eventlog entries of `init_pcr` in order, starting from the
+    /// INIT entry, or the `CONTINUE` entry of a rotated eventlog. Empty if
+    /// the eventlog is disabled.
+    pub eventlog: Vec<String>,
+
+    /// The eventlogs of the other registers that runtime measurements were
+    /// extended into, keyed by the register index. Each starts with an INIT
+    /// entry of its own.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub register_eventlogs: BTreeMap<u64, Vec<String>>,
+}
+
+/// Evidence of all the attesters that the platform provides, generated over
+/// the same runtime data. This is the content of [`Evidence::evidence`] when
+/// the composite evidence mode is configured.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompositeEvidence {
+    /// Name of the TEE type of the primary attester.
+    pub primary: String,
+
+    /// Evidence of each attester keyed by the name of the TEE type.
+    pub evidence: BTreeMap<String, CompositeEntry>,
+}
+
+/// The evidence or the failure of one attester of [`CompositeEvidence`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompositeEntry {
+    Evidence(String),
+    Error(String),
+}
+
+impl CompositeEvidence {
+    pub fn new(primary: String, primary_evidence: String) -> Self {
+        let mut evidence = BTreeMap::new();
+        evidence.insert(primary.clone(), CompositeEntry::Evidence(primary_evidence));
+        Self { primary, evidence }
+    }
+}
+
+/// Runtime data to be bound into the report data field of the evidence.
+#[derive(Clone, Debug)]
+pub enum RuntimeData {
+    /// Used as the report data, padded with zeros to the size of the report
+    /// data field of the platform. It must not be longer than the field.
+    Raw(Vec<u8>),
+
+    /// Canonicalized (object keys sorted, no whitespace) and hashed with
+    /// the configured [`HashAlgorithm`]. The digest is used as the report
+    /// data, s.t. a verifier can reproduce it from the same JSON value.
+    Structured(Value),
+}
+
+impl RuntimeData {
+    /// Convert the runtime data into the report data of a platform whose
+    /// report data field is `report_data_len` bytes. `None` means the
+    /// platform accepts any size, and the data is used as is.
+    ///
+    /// Shorter data is padded with zeros at the end to exactly
+    /// `report_data_len` bytes, s.t. a verifier reproduces the report data as
+    /// `data || 0x00 * (report_data_len - data.len())`. Longer data is
+    /// rejected with [`Error::RuntimeDataTooLong`], unless `hash_oversized`
+    /// is set, in which case raw data is replaced by its digest with
+    /// `hash_algorithm` before padding.
+    pub fn into_report_data(
+        self,
+        hash_algorithm: HashAlgorithm,
+        report_data_len: Option<usize>,
+        hash_oversized: bool,
+    ) -> Result<Vec<u8>, Error> {
+        let mut report_data = match self {
+            RuntimeData::Raw(data) => data,
+            RuntimeData::Structured(value) => {
+                hash_algorithm.digest(canonical_json(&value).as_bytes())
+            }
+        };
+
+        let Some(max) = report_data_len else {
+            return Ok(report_data);
+        };
+
+        if report_data.len() > max && hash_oversized {
+            report_data = hash_algorithm.digest(&report_data);
+        }
+
+        if report_data.len() > max {
+            return Err(Error::RuntimeDataTooLong {
+                max,
+                got: report_data.len(),
+            });
+        }
+
+        report_data.resize(max, 0);
+        Ok(report_data)
+    }
+}
+
+/// Serialize the JSON value with object keys sorted and no whitespace.
+pub fn canonical_json(value: &Value) -> String {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            let fields: Vec<String> = entries
+                .into_iter()
+                .map(|(k, v)| format!("{}:{}", Value::String(k.clone()), canonical_json(v)))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+mod base64_bytes {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use rstest::rstest;
+    use serde_json::json;
+
+    use crate::{config::HashAlgorithm, Error};
+
+    use super::{
+        canonical_json, CompositeEntry, CompositeEvidence, Evidence, EvidenceWithEventLog,
+        RuntimeData, EVIDENCE_FORMAT_VERSION,
+    };
+
+    #[test]
+    fn evidence_round_trip() {
+        let evidence = Evidence::new("sample".into(), br#"{"svn":"1"}"#.to_vec());
+        let json = serde_json::to_value(&evidence).unwrap();
+        assert_eq!(json["tee"], "sample");
+        assert_eq!(json["format_version"], EVIDENCE_FORMAT_VERSION);
+
+        let parsed: Evidence = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, evidence);
+        assert_eq!(parsed.into_bytes(), br#"{"svn":"1"}"#);
+    }
+
+    #[test]
+    fn evidence_with_eventlog_schema() {
+        let evidence = EvidenceWithEventLog {
+            evidence: Evidence::new("tdx".into(), b"quote".to_vec()),
+            eventlog: vec!["INIT sha384/00".into(), "domain operation content".into()],
+            register_eventlogs: BTreeMap::new(),
+        };
+        let json = serde_json::to_value(&evidence).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "tee": "tdx",
+                "format_version": EVIDENCE_FORMAT_VERSION,
+                "evidence": "cXVvdGU=",
+                "eventlog": ["INIT sha384/00", "domain operation content"],
+            })
+        );
+
+        let parsed: EvidenceWithEventLog = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, evidence);
+
+        let evidence = EvidenceWithEventLog {
+            register_eventlogs: BTreeMap::from([(18, vec!["INIT sha384/00".into()])]),
+            ..evidence
+        };
+        let json = serde_json::to_value(&evidence).unwrap();
+        assert_eq!(
+            json["register_eventlogs"],
+            json!({"18": ["INIT sha384/00"]})
+        );
+        let parsed: EvidenceWithEventLog = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, evidence);
+    }
+
+    #[test]
+    fn canonicalize_structured_data() {
+        let value =
+            json!({"nonce": "abc", "tee-pubkey": {"kty": "RSA", "alg": "RSA1_5"}, "a": [2, 1]});
+        assert_eq!(
+            canonical_json(&value),
+            r#"{"a":[2,1],"nonce":"abc","tee-pubkey":{"alg":"RSA1_5","kty":"RSA"}}"#
+        );
+    }
+
+    #[rstest]
+    #[case(HashAlgorithm::Sha256, 32)]
+    #[case(HashAlgorithm::Sha384, 48)]
+    #[case(HashAlgorithm::Sha512, 64)]
+    fn structured_data_is_hashed(#[case] alg: HashAlgorithm, #[case] len: usize) {
+        let report_data = RuntimeData::Structured(json!({"b": 1, "a": 2}))
+            .into_report_data(alg, Some(64), false)
+            .unwrap();
+        let digest = alg.digest(br#"{"a":2,"b":1}"#);
+        assert_eq!(digest.len(), len);
+        assert_eq!(report_data[..len], digest);
+        assert_eq!(report_data[len..], vec![0; 64 - len]);
+    }
+
+    #[rstest]
+    #[case(vec![1; 64], Some(64), vec![1; 64])]
+    #[case(vec![1; 48], Some(64), [vec![1; 48], vec![0; 16]].concat())]
+    #[case(vec![], Some(32), vec![0; 32])]
+    #[case(vec![1; 4096], None, vec![1; 4096])]
+    fn raw_data_is_padded(
+        #[case] data: Vec<u8>,
+        #[case] len: Option<usize>,
+        #[case] expected: Vec<u8>,
+    ) {
+        let report_data = RuntimeData::Raw(data)
+            .into_report_data(HashAlgorithm::Sha384, len, false)
+            .unwrap();
+        assert_eq!(report_data, expected);
+    }
+
+    #[rstest]
+    #[case(65, 64)]
+    #[case(48, 32)]
+    fn raw_data_too_long(#[case] got: usize, #[case] max: usize) {
+        let e = RuntimeData::Raw(vec![1; got])
+            .into_report_data(HashAlgorithm::Sha384, Some(max), false)
+            .unwrap_err();
+        assert!(matches!(e, Error::RuntimeDataTooLong { max: m, got: g } if m == max && g == got));
+    }
+
+    #[test]
+    fn oversized_raw_data_is_hashed() {
+        let data = vec![1; 100];
+        let report_data = RuntimeData::Raw(data.clone())
+            .into_report_data(HashAlgorithm::Sha256, Some(64), true)
+            .unwrap();
+        assert_eq!(
+            report_data,
+            [HashAlgorithm::Sha256.digest(&data), vec![0; 32]].concat()
+        );
+
+        // The digest does not fit either
+        let e = RuntimeData::Raw(data)
+            .into_report_data(HashAlgorithm::Sha512, Some(32), true)
+            .unwrap_err();
+        assert!(matches!(e, Error::RuntimeDataTooLong { max: 32, got: 64 }));
+    }
+
+    #[test]
+    fn composite_evidence_schema() {
+        let mut composite = CompositeEvidence::new("snp".into(), "snp evidence".into());
+        composite.evidence.insert(
+            "azsnpvtpm".into(),
+            CompositeEntry::Error("vTPM not found".into()),
+        );
+
+        let json = serde_json::to_value(&composite).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "primary": "snp",
+                "evidence": {
+                    "snp": { "evidence": "snp evidence" },
+                    "azsnpvtpm": { "error": "vTPM not found" },
+                }
+            })
+        );
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use kbs_types::Tee;
+use serde_json::Value;
+
+use crate::{config::HashAlgorithm, Error};
+
+/// Key of an initdata document that declares the digest algorithm.
+const ALGORITHM_KEY: &str = "algorithm";
+
+/// Init data to be checked against the host data field of the platform,
+/// e.g. MRCONFIGID of TDX or HOSTDATA of SNP.
+#[derive(Clone, Debug)]
+pub enum InitData {
+    /// Digest of the init data computed by the caller. It is compared with
+    /// the host data field as is, padded with zeros.
+    Digest(Vec<u8>),
+
+    /// The initdata document. It is hashed with the `algorithm` that the
+    /// document declares, e.g. `algorithm = "sha384"`, or with
+    /// `initdata.algorithm` of the config if it declares none.
+    Document(Vec<u8>),
+}
+
+impl InitData {
+    /// Convert the init data into the digest to be compared with the host
+    /// data field of `tee`, which is `host_data_len` bytes.
+    ///
+    /// The digest of a document is padded with zeros at the end to exactly
+    /// `host_data_len` bytes, e.g. a SHA-256 digest takes the first 32 bytes
+    /// of MRCONFIGID. A digest longer than the field is rejected with
+    /// [`Error::InitdataDigestTooLong`] instead of being truncated.
+    pub fn into_digest(
+        self,
+        default_algorithm: HashAlgorithm,
+        tee: Tee,
+        host_data_len: usize,
+    ) -> Result<Vec<u8>, Error> {
+        let document = match self {
+            InitData::Digest(digest) => return Ok(digest),
+            InitData::Document(document) => document,
+        };
+
+        let algorithm = declared_algorithm(&document)?.unwrap_or(default_algorithm);
+        let mut digest = algorithm.digest(&document);
+        if digest.len() > host_data_len {
+            return Err(Error::InitdataDigestTooLong {
+                tee,
+                algorithm,
+                max: host_data_len,
+                got: digest.len(),
+            });
+        }
+
+        digest.resize(host_data_len, 0);
+        Ok(digest)
+    }
+}
+
+/// The digest algorithm declared by a TOML initdata document. Documents in
+/// other formats declare none.
+fn declared_algorithm(document: &[u8]) -> Result<Option<HashAlgorithm>, Error> {
+    let Some(document) = std::str::from_utf8(document)
+        .ok()
+        .and_then(|document| document.parse::<toml::Table>().ok())
+    else {
+        return Ok(None);
+    };
+
+    match document.get(ALGORITHM_KEY) {
+        None => Ok(None),
+        Some(toml::Value::String(name)) => serde_json::from_value(Value::String(name.clone()))
+            .map(Some)
+            .map_err(|_| Error::InitdataAlgorithm(name.clone())),
+        Some(other) => Err(Error::InitdataAlgorithm(other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kbs_types::Tee;
+    use rstest::rstest;
+
+    use crate::{config::HashAlgorithm, Error};
+
+    use super::InitData;
+
+    /// Size of HOSTDATA of SNP
+    const SNP_HOST_DATA_LEN: usize = 32;
+
+    /// Size of MRCONFIGID of TDX
+    const TDX_HOST_DATA_LEN: usize = 48;
+
+    const DOCUMENT: &str = r#"version = "0.1.0"
+
+[data]
+"policy.rego" = "default allow = true"
+"#;
+
+    #[rstest]
+    #[case(HashAlgorithm::Sha256, Tee::Snp, SNP_HOST_DATA_LEN, Some(32))]
+    #[case(HashAlgorithm::Sha384, Tee::Snp, SNP_HOST_DATA_LEN, None)]
+    #[case(HashAlgorithm::Sha512, Tee::Snp, SNP_HOST_DATA_LEN, None)]
+    #[case(HashAlgorithm::Sha256, Tee::Tdx, TDX_HOST_DATA_LEN, Some(32))]
+    #[case(HashAlgorithm::Sha384, Tee::Tdx, TDX_HOST_DATA_LEN, Some(48))]
+    #[case(HashAlgorithm::Sha512, Tee::Tdx, TDX_HOST_DATA_LEN, None)]
+    fn document_digest(
+        #[case] algorithm: HashAlgorithm,
+        #[case] tee: Tee,
+        #[case] host_data_len: usize,
+        #[case] digest_len: Option<usize>,
+    ) {
+        let res = InitData::Document(DOCUMENT.into()).into_digest(algorithm, tee, host_data_len);
+        let digest = algorithm.digest(DOCUMENT.as_bytes());
+
+        match digest_len {
+            Some(len) => {
+                let host_data = res.unwrap();
+                assert_eq!(host_data.len(), host_data_len);
+                assert_eq!(host_data[..len], digest);
+                assert!(host_data[len..].iter().all(|b| *b == 0));
+            }
+            None => assert!(matches!(
+                res,
+                Err(Error::InitdataDigestTooLong { tee: t, algorithm: a, max, got })
+                    if t == tee && a == algorithm && max == host_data_len && got == digest.len()
+            )),
+        }
+    }
+
+    #[rstest]
+    #[case("sha256", HashAlgorithm::Sha256)]
+    #[case("sha384", HashAlgorithm::Sha384)]
+    #[case("sha512", HashAlgorithm::Sha512)]
+    fn declared_algorithm_wins(#[case] name: &str, #[case] algorithm: HashAlgorithm) {
+        let document = format!("algorithm = \"{name}\"\n{DOCUMENT}");
+        let host_data = InitData::Document(document.clone().into_bytes())
+            .into_digest(HashAlgorithm::Sha256, Tee::Tdx, 64)
+            .unwrap();
+        let digest = algorithm.digest(document.as_bytes());
+        assert_eq!(host_data[..digest.len()], digest);
+    }
+
+    #[test]
+    fn unknown_declared_algorithm() {
+        let document = format!("algorithm = \"sm3\"\n{DOCUMENT}");
+        let e = InitData::Document(document.into_bytes())
+            .into_digest(HashAlgorithm::Sha256, Tee::Tdx, TDX_HOST_DATA_LEN)
+            .unwrap_err();
+        assert!(matches!(e, Error::InitdataAlgorithm(name) if name == "sm3"));
+    }
+
+    #[test]
+    fn digest_is_kept() {
+        let host_data = InitData::Digest(vec![1; 64])
+            .into_digest(HashAlgorithm::Sha256, Tee::Snp, SNP_HOST_DATA_LEN)
+            .unwrap();
+        assert_eq!(host_data, vec![1; 64]);
+    }
+}
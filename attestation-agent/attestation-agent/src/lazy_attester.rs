@@ -0,0 +1,236 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::sync::OnceLock;
+
+use attester::{AttesterCapabilities, AttesterOptions, BoxedAttester};
+use kbs_types::Tee;
+use tracing::warn;
+
+use crate::{telemetry, Error, Result};
+
+/// The primary attester of AA, created on first use. A failed creation is
+/// not remembered, s.t. a TEE device that is not ready yet when AA starts
+/// only fails the requests until it is.
+pub(crate) struct LazyAttester {
+    /// The TEE type that was detected or configured
+    tee: Tee,
+
+    /// Use the sample attester if the attester of `tee` cannot be created
+    allow_fallback: bool,
+
+    /// Options of the attesters, from the `[attester]` table of the config
+    options: AttesterOptions,
+
+    create: fn(Tee, &AttesterOptions) -> Result<BoxedAttester>,
+
+    attester: OnceLock<Created>,
+}
+
+/// The attester in use, with what is resolved of it once.
+struct Created {
+    /// The TEE type of the attester, which is [`Tee::Sample`] after falling
+    /// back
+    tee: Tee,
+
+    attester: BoxedAttester,
+
+    /// The capabilities of the attester, which might ask the device, e.g.
+    /// for the PCR banks of a TPM
+    capabilities: AttesterCapabilities,
+}
+
+impl LazyAttester {
+    pub(crate) fn new(tee: Tee, allow_fallback: bool, options: AttesterOptions) -> Self {
+        Self::with_constructor(tee, allow_fallback, options, create_attester)
+    }
+
+    pub(crate) fn with_constructor(
+        tee: Tee,
+        allow_fallback: bool,
+        options: AttesterOptions,
+        create: fn(Tee, &AttesterOptions) -> Result<BoxedAttester>,
+    ) -> Self {
+        Self {
+            tee,
+            allow_fallback,
+            options,
+            create,
+            attester: OnceLock::new(),
+        }
+    }
+
+    /// Get the attester, creating it if this is the first successful use.
+    pub(crate) fn get(&self) -> Result<&BoxedAttester> {
+        self.get_created().map(|created| &created.attester)
+    }
+
+    /// The capabilities of the attester, which are resolved once when it
+    /// is created.
+    pub(crate) fn capabilities(&self) -> Result<&AttesterCapabilities> {
+        self.get_created().map(|created| &created.capabilities)
+    }
+
+    /// Options of the attesters, s.t. the other attesters of AA are created
+    /// with the same ones.
+    pub(crate) fn options(&self) -> &AttesterOptions {
+        &self.options
+    }
+
+    /// The TEE type of the attester without creating it.
+    pub(crate) fn current_tee(&self) -> Tee {
+        self.attester
+            .get()
+            .map(|created| created.tee)
+            .unwrap_or(self.tee)
+    }
+
+    fn get_created(&self) -> Result<&Created> {
+        if let Some(attester) = self.attester.get() {
+            return Ok(attester);
+        }
+
+        let (tee, attester) = match (self.create)(self.tee, &self.options) {
+            Ok(attester) => (self.tee, attester),
+            Err(e) if self.allow_fallback => {
+                warn!(
+                    target: telemetry::CONFIG,
+                    tee = ?self.tee,
+                    error = %e,
+                    "FALLING BACK TO THE SAMPLE ATTESTER. The evidence is not backed by any TEE \
+                     and MUST NOT be trusted."
+                );
+                (Tee::Sample, (self.create)(Tee::Sample, &self.options)?)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let created = Created {
+            tee,
+            capabilities: attester.capabilities(),
+            attester,
+        };
+
+        // Concurrent first uses might both create an attester, only one of
+        // which is kept.
+        Ok(self.attester.get_or_init(|| created))
+    }
+}
+
+/// Create the attester of the given TEE type. The platform must provide the
+/// TEE, s.t. a forced TEE type that does not match the platform is rejected
+/// here rather than failing on the first hardware request.
+fn create_attester(tee_type: Tee, options: &AttesterOptions) -> Result<BoxedAttester> {
+    if !attester::tee_available(tee_type) {
+        return Err(Error::TeeUnavailable(tee_type));
+    }
+
+    attester::new_attester(tee_type, options)
+        .map_err(|e| Error::attester(tee_type, "initialize", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use attester::{
+        sample::SampleAttester, Attester, AttesterCapabilities, AttesterOptions, BoxedAttester,
+    };
+    use kbs_types::Tee;
+    use rstest::rstest;
+
+    use super::LazyAttester;
+    use crate::{Error, Result};
+
+    fn sample_only(tee: Tee, _: &AttesterOptions) -> Result<BoxedAttester> {
+        match tee {
+            Tee::Sample => Ok(Box::<SampleAttester>::default()),
+            tee => Err(Error::TeeUnavailable(tee)),
+        }
+    }
+
+    #[rstest]
+    #[case(false, None)]
+    #[case(true, Some(Tee::Sample))]
+    fn unavailable_tee(#[case] allow_fallback: bool, #[case] expected: Option<Tee>) {
+        let attester = LazyAttester::with_constructor(
+            Tee::Tdx,
+            allow_fallback,
+            AttesterOptions::default(),
+            sample_only,
+        );
+        assert_eq!(attester.current_tee(), Tee::Tdx);
+
+        let res = attester.get();
+        match expected {
+            Some(tee) => {
+                assert!(res.is_ok());
+                assert_eq!(attester.current_tee(), tee);
+            }
+            None => {
+                assert!(matches!(res, Err(Error::TeeUnavailable(Tee::Tdx))));
+                assert_eq!(attester.current_tee(), Tee::Tdx);
+            }
+        }
+    }
+
+    static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+
+    fn ready_on_second_attempt(tee: Tee, _: &AttesterOptions) -> Result<BoxedAttester> {
+        match ATTEMPTS.fetch_add(1, Ordering::SeqCst) {
+            0 => Err(Error::TeeUnavailable(tee)),
+            _ => Ok(Box::<SampleAttester>::default()),
+        }
+    }
+
+    #[test]
+    fn failure_is_retried() {
+        let attester = LazyAttester::with_constructor(
+            Tee::Tdx,
+            false,
+            AttesterOptions::default(),
+            ready_on_second_attempt,
+        );
+        assert!(attester.get().is_err());
+        assert!(attester.get().is_ok());
+        assert_eq!(attester.current_tee(), Tee::Tdx);
+
+        // Created once, never again
+        assert!(attester.get().is_ok());
+        assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 2);
+    }
+
+    static CAPABILITY_QUERIES: AtomicUsize = AtomicUsize::new(0);
+
+    /// An attester that counts the queries of its capabilities, which might
+    /// ask the device.
+    struct CountingAttester;
+
+    #[async_trait::async_trait]
+    impl Attester for CountingAttester {
+        async fn get_evidence(&self, _report_data: Vec<u8>) -> anyhow::Result<String> {
+            Ok(String::new())
+        }
+
+        fn capabilities(&self) -> AttesterCapabilities {
+            CAPABILITY_QUERIES.fetch_add(1, Ordering::SeqCst);
+            AttesterCapabilities::default()
+        }
+    }
+
+    fn counting(_: Tee, _: &AttesterOptions) -> Result<BoxedAttester> {
+        Ok(Box::new(CountingAttester))
+    }
+
+    #[test]
+    fn capabilities_are_resolved_once() {
+        let attester =
+            LazyAttester::with_constructor(Tee::Tdx, false, AttesterOptions::default(), counting);
+        for _ in 0..3 {
+            assert!(attester.capabilities().is_ok());
+        }
+        assert_eq!(CAPABILITY_QUERIES.load(Ordering::SeqCst), 1);
+    }
+}
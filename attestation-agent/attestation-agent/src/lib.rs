@@ -3,25 +3,77 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use std::{io::Write, str::FromStr};
+use std::{
+    fmt,
+    future::Future,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    time::Duration,
+};
 
-use anyhow::{Context, Result};
 use async_trait::async_trait;
-use attester::{detect_tee_type, BoxedAttester};
-use tokio::sync::Mutex;
+use attester::{
+    detect_tee_type_verbose,
+    device::{new_device_attester, BoxedDeviceAttester},
+    BoxedAttester,
+};
+use tokio::sync::{Mutex, RwLock};
 
-pub use attester::InitdataResult;
+pub use attester::{AttesterCapabilities, AttesterError, InitdataResult};
+#[cfg(feature = "coco_as")]
+pub use config::coco_as::CoCoASConfig;
+#[cfg(feature = "kbs")]
+pub use config::kbs::KbsConfig;
+pub use config::{
+    attester::AttesterConfig, initdata::InitdataConfig, retry::RetryConfig, Config,
+    EventlogBackend, EventlogConfig, EventlogFormat, EventlogRecovery, EventlogRotation,
+    EventlogSync, EvidenceConfig, EvidenceMode, HashAlgorithm, TokenConfigs,
+};
+pub use config_watcher::ConfigWatcher;
+pub use error::Error;
+pub use eventlog::{
+    cel::aael_to_cel,
+    combined::{CombinedEvent, CombinedEventLog, CombinedRegister},
+    firmware::FirmwareEvent,
+    index::EventFilter,
+    split_metadata,
+    writer::{EventLogWriter, LineLocation},
+    AaelHeader, EntryMetadata, EventEntry, EventEntryError, EventLog, EventLogFull,
+    EventLogSnapshot, EventLogStorageError, EventRecord, StorageErrorKind,
+};
+pub use evidence::{Evidence, EvidenceWithEventLog, RuntimeData};
+pub use initdata::InitData;
+pub use kbs_types::Tee;
+#[cfg(feature = "kbs")]
+pub use token::refresh::TokenRefresher;
+pub use token::{BoxedTokenGetter, TokenGetter, TokenResponse, TokenType};
 
+#[cfg(feature = "bin")]
+pub mod cli;
 pub mod config;
+mod config_watcher;
+mod error;
 mod eventlog;
+pub mod evidence;
+pub mod initdata;
+mod lazy_attester;
+pub mod metrics;
+#[cfg(feature = "token")]
+mod retry;
+mod telemetry;
 pub mod token;
 
-use config::HashAlgorithm;
-use eventlog::{EventEntry, EventLog};
-use log::{info, warn};
+use error::Result;
+use eventlog::RegisterLogs;
+use evidence::{CompositeEntry, CompositeEvidence, COMPOSITE_EVIDENCE_TEE};
+use lazy_attester::LazyAttester;
+use telemetry::Elapsed;
+#[cfg(feature = "token")]
 use token::*;
-
-use crate::config::Config;
+use tracing::{info, instrument, warn};
 
 /// Attestation Agent (AA for short) is a rust library crate for attestation procedure
 /// in confidential containers. It provides kinds of service APIs related to attestation,
@@ -43,7 +95,7 @@ use crate::config::Config;
 /// use attestation_agent::AttestationAPIs;
 ///
 /// // initialize with empty config
-/// let mut aa = AttestationAgent::new(None).unwrap();
+/// let aa = AttestationAgent::new(None).unwrap();
 ///
 /// let _quote = aa.get_evidence(&[0;64]);
 /// ```
@@ -55,172 +107,4046 @@ use crate::config::Config;
 /// attestation agent which KBC module it should use and `kbs_uri` specifies the KBS address.
 #[async_trait]
 pub trait AttestationAPIs {
-    /// Get attestation Token
-    async fn get_token(&mut self, token_type: &str) -> Result<Vec<u8>>;
+    /// Get attestation Token. If `token_configs.cache` is enabled, a cached
+    /// token is returned until it is about to expire. An empty token type
+    /// stands for `token_configs.default_type`, or the only token type
+    /// available if it is not set.
+    async fn get_token(&self, token_type: &str) -> Result<Vec<u8>>;
+
+    /// Get attestation Token with extra parameters for the token service,
+    /// e.g. `{"policy_ids": ["default"]}` for CoCoAS. The params must be a
+    /// JSON object, and are passed through as is, s.t. with KBS they are
+    /// the `extra-params` of the auth request. Such tokens are not cached,
+    /// but CoCoAS tokens requested with only an `audience`, e.g.
+    /// `{"audience": "https://kbs.example.com"}`, are cached per audience.
+    async fn get_token_with_params(
+        &self,
+        token_type: &str,
+        params: serde_json::Value,
+    ) -> Result<Vec<u8>>;
+
+    /// Get attestation Token whose evidence binds a nonce of the caller,
+    /// e.g. a session identifier, besides the nonce of the token service.
+    /// With KBS the `client-nonce` field of the runtime data carries
+    /// `base64(SHA-384(nonce || tee-pubkey))`, see
+    /// `kbs_protocol::client_nonce_binding`. With CoCoAS the report data is
+    /// `SHA-384(nonce)` and the nonce is the raw runtime data, s.t. it shows
+    /// up in the token claims. Such tokens are not cached.
+    async fn get_token_with_nonce(
+        &self,
+        token_type: &str,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>>;
+
+    /// Get a new attestation token bypassing the token cache, e.g. when the
+    /// cached one was rejected. The new token replaces the cached one.
+    async fn refresh_token(&self, token_type: &str) -> Result<Vec<u8>>;
+
+    /// Get attestation Token like `get_token`, with the TEE key pair that
+    /// comes with KBS tokens and the expiry of the token parsed out of it.
+    async fn get_token_ex(&self, token_type: &str) -> Result<TokenResponse> {
+        let token = self.get_token(token_type).await?;
+        TokenResponse::from_bytes(&token).map_err(|source| Error::InvalidToken {
+            token_type: token_type.to_string(),
+            source,
+        })
+    }
 
     /// Get TEE hardware signed evidence that includes the runtime data.
-    async fn get_evidence(&mut self, runtime_data: &[u8]) -> Result<Vec<u8>>;
+    /// The runtime data is padded with zeros to the report data size of the
+    /// platform, see [`RuntimeData::into_report_data`].
+    async fn get_evidence(&self, runtime_data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Get TEE hardware signed evidence that includes the runtime data,
+    /// together with the TEE type that produced it.
+    async fn get_evidence_ex(&self, runtime_data: &[u8]) -> Result<Evidence>;
+
+    /// Get TEE hardware signed evidence that includes the given raw or
+    /// structured runtime data. See [`RuntimeData::into_report_data`] for how
+    /// it is turned into the report data.
+    async fn get_evidence_with(&self, runtime_data: RuntimeData) -> Result<Evidence>;
+
+    /// Get TEE hardware signed evidence that includes the runtime data,
+    /// together with the eventlog. No entry is recorded while the evidence
+    /// is generated, s.t. the eventlog matches the registers in the
+    /// evidence.
+    async fn get_evidence_with_eventlog(&self, runtime_data: &[u8])
+        -> Result<EvidenceWithEventLog>;
+
+    /// Get evidence of the devices attached to the TEE, e.g. GPUs, that
+    /// includes the runtime data. The output is a JSON object keyed by the
+    /// device class, e.g. `{"nvidia_gpu": ...}`, and is empty when no device
+    /// attester is configured.
+    async fn get_additional_evidence(&self, runtime_data: &[u8]) -> Result<Vec<u8>>;
+
+    /// Derive a key of `length` bytes bound to the TEE from the hardware
+    /// sealing/derivation key. The `context` is mixed into the derivation.
+    /// Only SNP and the sample attester can derive keys, TDX has no
+    /// derivation key for TDs.
+    async fn get_derived_key(&self, context: &[u8], length: usize) -> Result<Vec<u8>>;
 
     /// Extend runtime measurement register
     async fn extend_runtime_measurement(
-        &mut self,
+        &self,
         domain: &str,
         operation: &str,
         content: &str,
         register_index: Option<u64>,
     ) -> Result<()>;
 
+    /// Extend runtime measurement register, and get the digest that was
+    /// extended, see [`ExtendResult`]. Not supported by default.
+    async fn extend_runtime_measurement_ex(
+        &self,
+        _domain: &str,
+        _operation: &str,
+        _content: &str,
+        _register_index: Option<u64>,
+    ) -> Result<ExtendResult> {
+        Err(Error::Unsupported("the result of a runtime measurement"))
+    }
+
+    /// Extend runtime measurement register with an event of binary content,
+    /// which is recorded `base64url:` encoded, see [`EventEntry::new_binary`].
+    /// Not supported by default.
+    async fn extend_runtime_measurement_binary(
+        &self,
+        _domain: &str,
+        _operation: &str,
+        _content: &[u8],
+        _register_index: Option<u64>,
+    ) -> Result<ExtendResult> {
+        Err(Error::Unsupported("binary runtime measurement events"))
+    }
+
+    /// Extend runtime measurement register with a batch of `(domain, operation, content)`
+    /// events in order. If it fails mid-batch, [`Error::BatchExtend`] tells which events
+    /// were applied.
+    async fn extend_runtime_measurement_batch(
+        &self,
+        events: &[(&str, &str, &str)],
+        register_index: Option<u64>,
+    ) -> Result<()>;
+
+    /// Extend runtime measurement register with a batch of events like
+    /// [`AttestationAPIs::extend_runtime_measurement_batch`], and get an
+    /// [`ExtendResult`] of each event in order. Not supported by default.
+    async fn extend_runtime_measurement_batch_ex(
+        &self,
+        _events: &[(&str, &str, &str)],
+        _register_index: Option<u64>,
+    ) -> Result<Vec<ExtendResult>> {
+        Err(Error::Unsupported(
+            "the results of a runtime measurement batch",
+        ))
+    }
+
+    /// Get the recorded eventlog entries in order, i.e. the eventlogs of
+    /// all the registers concatenated in order of the register index. The
+    /// first `start_index` entries are skipped, s.t. a large eventlog can be
+    /// fetched page by page. The entries are the lines as recorded, i.e.
+    /// with their metadata if `eventlog_config.record_timestamps` is set.
+    async fn get_event_log(&self, start_index: usize) -> Result<Vec<String>>;
+
+    /// Get the recorded eventlog entries of the register in order, starting
+    /// from its INIT entry, or from the `CONTINUE` entry once the eventlog
+    /// was rotated. Without `register_index`, this is
+    /// [`AttestationAPIs::get_event_log`]. By default the eventlog of a
+    /// single register is not supported.
+    async fn get_register_event_log(
+        &self,
+        start_index: usize,
+        register_index: Option<u64>,
+    ) -> Result<Vec<String>> {
+        match register_index {
+            None => self.get_event_log(start_index).await,
+            Some(_) => Err(Error::Unsupported("the eventlog of a single register")),
+        }
+    }
+
+    /// Get the same entries as [`AttestationAPIs::get_register_event_log`]
+    /// as a JSON array of [`EventRecord`]s, with the digest each entry was
+    /// extended with. Not supported by default.
+    async fn get_event_log_json(
+        &self,
+        _start_index: usize,
+        _register_index: Option<u64>,
+    ) -> Result<String> {
+        Err(Error::Unsupported("the eventlog as JSON"))
+    }
+
+    /// Get the entries of all registers that match the filter, in order of
+    /// their register. Not supported by default.
+    async fn query_event_log(&self, _filter: EventFilter) -> Result<Vec<EventRecord>> {
+        Err(Error::Unsupported("querying the eventlog"))
+    }
+
+    /// Get the eventlog of the firmware, e.g. the CCEL of TDX, together
+    /// with the eventlogs of all registers as a JSON [`CombinedEventLog`],
+    /// whose events are grouped by the register of the firmware eventlog
+    /// they are extended into and marked with the eventlog they are from.
+    /// On platforms without a firmware eventlog, only the entries of AA are
+    /// in it, grouped by their register. Not supported by default.
+    async fn get_combined_event_log(&self) -> Result<String> {
+        Err(Error::Unsupported("the combined eventlog"))
+    }
+
+    /// Read the current value of a runtime measurement register, in PCR
+    /// numbering, from the bank of the given hash algorithm. Registers that
+    /// runtime measurements cannot be extended into are rejected with
+    /// [`Error::UnsupportedRegister`].
+    async fn get_runtime_measurement(
+        &self,
+        register_index: u64,
+        algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>>;
+
+    /// Replay the eventlog of each register with each hash algorithm its
+    /// entries are extended with, and compare the result with the current
+    /// value of the register bank of the algorithm. A mismatch
+    /// is reported as [`Error::EventLogMismatch`], with the index of the
+    /// first entry that is not extended into the register if the entries
+    /// before it replay to its value.
+    async fn verify_event_log(&self) -> Result<()>;
+
     /// Check the initdata binding
-    async fn check_init_data(&mut self, init_data: &[u8]) -> Result<InitdataResult>;
+    async fn check_init_data(&self, init_data: &[u8]) -> Result<InitdataResult>;
+
+    /// Check the initdata binding of the given digest or initdata document.
+    /// See [`InitData::into_digest`] for how a document is hashed. By default
+    /// a digest is checked with [`AttestationAPIs::check_init_data`], and a
+    /// document is not supported.
+    async fn check_init_data_with(&self, init_data: InitData) -> Result<InitdataResult> {
+        match init_data {
+            InitData::Digest(digest) => self.check_init_data(&digest).await,
+            InitData::Document(_) => Err(Error::Unsupported("checking an initdata document")),
+        }
+    }
+}
+
+/// An event extended into a runtime measurement register.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExtendResult {
+    /// Register the event is extended into, in PCR numbering.
+    pub register_index: u64,
+
+    /// Algorithm of the digest, i.e. the register bank it is extended into.
+    pub algorithm: HashAlgorithm,
+
+    /// Digest of the event that was extended, as recorded in the eventlog.
+    pub digest: Vec<u8>,
+
+    /// Value of the register bank read back after the event is extended.
+    /// Only the last event of a batch has it, as the register is read once
+    /// after the batch. `None` for the other events, or if the attester
+    /// cannot read the register back. The register is not read for the
+    /// APIs that do not return this.
+    pub register_value: Option<Vec<u8>>,
+}
+
+/// Error of a batch of runtime measurement extensions that failed in the
+/// middle. The first `extended` events of the batch were extended into the
+/// register, and the first `logged` were recorded in the eventlog. Each
+/// entry is recorded before it is extended, s.t. `logged` is one more than
+/// `extended` only if the entry of the failed extension could not be
+/// discarded.
+#[derive(thiserror::Error, Debug)]
+#[error("extend runtime measurement batch failed after {extended} events extended and {logged} events logged")]
+pub struct BatchExtendError {
+    pub extended: usize,
+    pub logged: usize,
+    #[source]
+    pub source: anyhow::Error,
 }
 
 /// Attestation agent to provide attestation service.
 pub struct AttestationAgent {
-    config: Config,
-    attester: BoxedAttester,
-    eventlog: Mutex<EventLog>,
+    config: RwLock<Config>,
+    /// The config file AA was created from, if any
+    config_path: Option<String>,
+    /// Fields set on the command line, see [`Config::apply_overrides`]
+    overrides: Vec<(String, String)>,
+    attester: LazyAttester,
+    /// Attesters of the other TEE types of the platform, created on the
+    /// first use of the composite evidence mode
+    secondary_attesters: OnceLock<Vec<(Tee, BoxedAttester)>>,
+    device_attesters: Vec<(String, BoxedDeviceAttester)>,
+    /// Getters of the token types that are not built in
+    token_getters: token::TokenGetters,
+    eventlog: Mutex<RegisterLogs>,
+    closed: AtomicBool,
+    #[cfg(feature = "token")]
+    token_cache: Mutex<cache::TokenCache>,
+    /// Held while a persisted token is written or removed, which is not
+    /// done under the lock of the cache
+    #[cfg(feature = "token")]
+    token_files: Mutex<()>,
+    #[cfg(feature = "token")]
+    token_fetches: single_flight::SingleFlight<(TokenType, String), TokenResponse>,
+    #[cfg(feature = "kbs")]
+    kbs_preferred: token::kbs::PreferredEndpoint,
+    /// The private key of the last certificate of the `cert` token type
+    #[cfg(feature = "cert")]
+    certificate_key: Mutex<Option<token::cert::PrivateKey>>,
+    /// Set on shutdown, s.t. the background tasks stop
+    #[cfg(feature = "kbs")]
+    shutdown_signal: tokio::sync::watch::Sender<bool>,
+    /// Signaled when the configuration or the cached KBS token changes,
+    /// s.t. the token refresher reschedules
+    #[cfg(feature = "kbs")]
+    refresh_signal: tokio::sync::watch::Sender<()>,
 }
 
-impl AttestationAgent {
-    pub async fn init(&mut self) -> Result<()> {
-        // We should get the current platform's evidence to see the RTMR value.
-        // Here we assume RTMR is not polluted thus all be set `\0`
-        let init_entry = match self.config.eventlog_config.eventlog_algorithm {
-            HashAlgorithm::Sha256 => "INIT sha256/0000000000000000000000000000000000000000000000000000000000000000",
-            HashAlgorithm::Sha384 => "INIT sha384/000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
-            HashAlgorithm::Sha512 => "INIT sha512/00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
-        };
+impl fmt::Debug for AttestationAgent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AttestationAgent")
+            .field("tee", &self.attester.current_tee())
+            .finish_non_exhaustive()
+    }
+}
 
-        let event_digest = self
-            .config
-            .eventlog_config
-            .eventlog_algorithm
-            .digest(init_entry.as_bytes());
+impl AttestationAgent {
+    /// Record the INIT entry into the eventlog and the `init_pcr` register.
+    /// This must be called before extending runtime measurements. Calling it
+    /// again, or after AA restarts with the INIT entry already recorded, is a
+    /// no-op, s.t. the register does not get duplicated INIT measurements.
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee()))]
+    pub async fn init(&self) -> Result<()> {
+        let _elapsed = Elapsed::start();
+        let eventlog_config = self.config.read().await.eventlog_config.clone();
 
         let mut eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        if !eventlog.is_enabled() {
+            info!(target: telemetry::EVENTLOG, "Eventlog is disabled, skip initialization");
+            return Ok(());
+        }
 
-        self.attester
-            .extend_runtime_measurement(event_digest, self.config.eventlog_config.init_pcr)
-            .await
-            .context("write INIT entry")?;
-        eventlog.write_log(init_entry).context("write INIT log")?;
+        // Fail at startup if the platform cannot honor the registers in use
+        let attester = self.attester.get()?;
+        let capabilities = self.attester.capabilities()?;
+        let tee = self.tee_type();
+        check_register_index(attester, tee, eventlog_config.init_pcr)?;
+        check_register_index(attester, tee, eventlog_config.default_register_index)?;
+        check_register_bank(capabilities, tee, eventlog_config.eventlog_algorithm)?;
+
+        if eventlog.init_log().is_initialized() {
+            info!(
+                target: telemetry::EVENTLOG,
+                "INIT entry is already recorded, skip initialization"
+            );
+            return Ok(());
+        }
+
+        open_register_log(
+            attester,
+            capabilities,
+            tee,
+            &mut eventlog,
+            &eventlog_config,
+            eventlog_config.init_pcr,
+        )
+        .await?;
 
         Ok(())
     }
 
-    /// Create a new instance of [AttestationAgent].
+    /// Create a new instance of [AttestationAgent]. The TEE type is detected
+    /// unless `attester.tee_override` is set in the config, or else the
+    /// `AA_TEE_TYPE` environment variable. The probes of the detection are
+    /// logged at debug level.
+    ///
+    /// Without `config_path`, the config file is searched as by
+    /// [`config::find_config_file`], and the defaults are used if there is
+    /// none. A configuration that is invalid, including by
+    /// [`Config::validate`], is an error.
     pub fn new(config_path: Option<&str>) -> Result<Self> {
-        let config = match config_path {
+        Self::new_with_overrides(config_path, Vec::new())
+    }
+
+    /// Create a new instance of [AttestationAgent] like
+    /// [`AttestationAgent::new`], with the fields of `overrides` set over
+    /// the config file and the environment, see [`Config::apply_overrides`].
+    /// The overrides stay in effect across updates and reloads of the
+    /// configuration.
+    pub fn new_with_overrides(
+        config_path: Option<&str>,
+        overrides: Vec<(String, String)>,
+    ) -> Result<Self> {
+        let (config, config_path) = Self::load_config(config_path, &overrides)?;
+        let mut aa = Self::new_with_config(config)?;
+        aa.config_path = config_path;
+        aa.overrides = overrides;
+        Ok(aa)
+    }
+
+    /// Create a new instance of [AttestationAgent] from a configuration
+    /// built in code, without reading any config file. The TEE type is
+    /// detected as by [`AttestationAgent::new`], and the configuration is
+    /// checked by [`Config::validate`].
+    pub fn new_with_config(config: Config) -> Result<Self> {
+        let tee_type = match config.attester.tee_override {
+            Some(tee) => {
+                info!(target: telemetry::CONFIG, ?tee, "TEE type is overridden by AA config");
+                tee
+            }
+            None => {
+                let detection = detect_tee_type_verbose();
+                tracing::debug!(target: telemetry::CONFIG, %detection, "Detected TEE type");
+                if detection.source == attester::detect::DetectionSource::Fallback {
+                    warn!(target: telemetry::CONFIG, "No TEE platform detected. Sample Attester will be used.");
+                }
+                detection.tee
+            }
+        };
+
+        Self::with_config_and_tee(config, tee_type)
+    }
+
+    /// Create a new instance of [AttestationAgent] that uses the attester of
+    /// the given TEE type instead of detecting the platform.
+    pub fn new_with_tee(config_path: Option<&str>, tee: Tee) -> Result<Self> {
+        let (config, config_path) = Self::load_config(config_path, &[])?;
+        let mut aa = Self::with_config_and_tee(config, tee)?;
+        aa.config_path = config_path;
+        Ok(aa)
+    }
+
+    /// Load the given config file, or the one found by searching the
+    /// default paths, and set the overrides over it. Return the path of the
+    /// file in use, if any.
+    fn load_config(
+        config_path: Option<&str>,
+        overrides: &[(String, String)],
+    ) -> Result<(Config, Option<String>)> {
+        let config_path = match config_path {
+            Some(config_path) => Some(config_path.to_string()),
+            None => config::find_config_file(),
+        };
+
+        let config = match &config_path {
             Some(config_path) => {
-                info!("Using AA config file: {config_path}");
-                Config::try_from(config_path)?
+                info!(target: telemetry::CONFIG, config_path, "Using AA config file");
+                Config::try_from(config_path.as_str()).map_err(|e| Error::ConfigParse(e.into()))?
             }
             None => {
-                warn!("No AA config file specified. Using a default configuration.");
-                Config::new()?
+                warn!(
+                    target: telemetry::CONFIG,
+                    "No AA config file specified or found. Using a default configuration."
+                );
+                Config::new().map_err(Error::ConfigParse)?
             }
         };
 
-        let tee_type = detect_tee_type();
-        let attester: BoxedAttester = tee_type.try_into()?;
-        let eventlog = Mutex::new(EventLog::new()?);
+        let config = config
+            .apply_overrides(overrides)
+            .map_err(|e| Error::ConfigParse(e.into()))?;
+
+        Ok((config, config_path))
+    }
+
+    fn with_config_and_tee(config: Config, tee_type: Tee) -> Result<Self> {
+        config.validate().map_err(Error::ConfigInvalid)?;
+
+        // Only the device of the attester is opened on first use. A platform
+        // without the TEE fails right away unless falling back is allowed.
+        let allow_fallback = config.attester.allow_fallback;
+        if !allow_fallback && !attester::tee_available(tee_type) {
+            return Err(Error::TeeUnavailable(tee_type));
+        }
+        let attester = LazyAttester::new(tee_type, allow_fallback, attester_options(&config));
+        Self::with_attester(config, tee_type, attester)
+    }
+
+    fn with_attester(config: Config, tee_type: Tee, attester: LazyAttester) -> Result<Self> {
+        let device_attesters = create_device_attesters(&config.attester.extra_attesters)?;
+        let eventlog = if config.eventlog_config.enabled {
+            let eventlog_config = &config.eventlog_config;
+            let len = eventlog_config.eventlog_algorithm.digest_len();
+            RegisterLogs::new(eventlog_config, |register_index| {
+                initial_register_value(tee_type, register_index, len)
+            })
+            .map_err(Error::EventLogIo)?
+        } else {
+            info!(target: telemetry::CONFIG, "Eventlog is disabled by AA config");
+            RegisterLogs::disabled()
+        };
 
         Ok(AttestationAgent {
-            config,
+            config: RwLock::new(config),
+            config_path: None,
+            overrides: Vec::new(),
             attester,
-            eventlog,
+            secondary_attesters: OnceLock::new(),
+            device_attesters,
+            token_getters: token::TokenGetters::default(),
+            eventlog: Mutex::new(eventlog),
+            closed: AtomicBool::new(false),
+            #[cfg(feature = "token")]
+            token_cache: Mutex::default(),
+            #[cfg(feature = "token")]
+            token_files: Mutex::default(),
+            #[cfg(feature = "token")]
+            token_fetches: Default::default(),
+            #[cfg(feature = "kbs")]
+            kbs_preferred: Default::default(),
+            #[cfg(feature = "cert")]
+            certificate_key: Mutex::default(),
+            #[cfg(feature = "kbs")]
+            shutdown_signal: tokio::sync::watch::Sender::new(false),
+            #[cfg(feature = "kbs")]
+            refresh_signal: tokio::sync::watch::Sender::new(()),
         })
     }
 
+    /// Register a device attester whose evidence will be included in the
+    /// output of `get_additional_evidence` under the given device class.
+    pub fn register_device_attester(&mut self, class: &str, attester: BoxedDeviceAttester) {
+        self.device_attesters.push((class.to_string(), attester));
+    }
+
+    /// Register the getter of the tokens of the given type, s.t.
+    /// `get_token` of a token type that is not built in gets the token
+    /// from it. The built-in token types, i.e. `kbs`, `coco_as`, `cert` and
+    /// `ita` if enabled in this build, cannot be replaced. A getter registered
+    /// before for the token type is replaced.
+    ///
+    /// The tokens of registered getters are neither cached nor retried,
+    /// and cannot be requested with params or a nonce.
+    pub fn register_token_getter(&self, token_type: &str, getter: BoxedTokenGetter) -> Result<()> {
+        if !self.token_getters.register(token_type, getter) {
+            return Err(Error::InvalidArgument(format!(
+                "token type `{token_type}` is built in"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The PEM private key of the certificate that `get_token` of the
+    /// `cert` token type got last, if any. The key is generated in the
+    /// guest for each certificate and never leaves AA otherwise. It is
+    /// zeroized once dropped.
+    #[cfg(feature = "cert")]
+    pub async fn certificate_private_key(&self) -> Option<token::cert::PrivateKey> {
+        self.certificate_key.lock().await.clone()
+    }
+
+    /// The path of the config file in use, if AA is created from one.
+    pub fn config_path(&self) -> Option<&str> {
+        self.config_path.as_deref()
+    }
+
+    /// The TEE type of the attester in use. This is [`Tee::Sample`] if
+    /// no TEE platform is detected. The attester is not created by this,
+    /// s.t. the detected or configured TEE type is returned until the
+    /// attester falls back to the sample one on its first use.
+    pub fn tee_type(&self) -> Tee {
+        self.attester.current_tee()
+    }
+
+    /// The name of the TEE type of the attester in use, e.g. `tdx`. This is
+    /// the same name as used in the config and by the KBS protocol.
+    pub fn tee_type_name(&self) -> String {
+        tee_name(self.tee_type())
+    }
+
+    /// What the attester in use supports, e.g. whether runtime measurement
+    /// can be extended and the size of the report data. Nothing is supported
+    /// while the attester cannot be created, e.g. as the TEE device is not
+    /// ready yet.
+    pub fn capabilities(&self) -> AttesterCapabilities {
+        self.attester.capabilities().cloned().unwrap_or_default()
+    }
+
+    /// Path of the eventlog file of `init_pcr`, e.g. for tools on the host
+    /// that collect it. The eventlogs of the other registers are next to
+    /// it, see [`EventlogConfig::file_path`]. `None` if the eventlog is
+    /// disabled or not kept in files.
+    pub async fn eventlog_path(&self) -> Option<PathBuf> {
+        self.eventlog.lock().await.path().map(Path::to_path_buf)
+    }
+
+    /// Copy the eventlog to `dest`, see [`EventLogSnapshot`]. Nothing is
+    /// extended meanwhile, s.t. the copy replays to the registers as they
+    /// were when it was taken. The eventlog is returned instead if it is
+    /// not kept in files.
+    pub async fn snapshot_event_log(&self, dest: &Path) -> Result<EventLogSnapshot> {
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        eventlog.snapshot(dest).await.map_err(Error::EventLogIo)
+    }
+
+    /// Flush the eventlog to the storage and close it, and drop the cached
+    /// tokens. Any API called afterwards fails with [`Error::Shutdown`].
+    /// Runtime measurements being extended finish first, s.t. the eventlog
+    /// ends with a complete entry.
+    pub async fn shutdown(&self) -> Result<()> {
+        // Set before taking the eventlog lock, which the APIs check the flag
+        // under, s.t. nothing is extended once the eventlog is closed
+        self.closed.store(true, Ordering::SeqCst);
+        self.eventlog
+            .lock()
+            .await
+            .close()
+            .await
+            .map_err(|e| eventlog_write_failed(self.tee_type(), e))?;
+
+        #[cfg(feature = "kbs")]
+        self.shutdown_signal.send_replace(true);
+        #[cfg(feature = "token")]
+        self.token_cache.lock().await.clear();
+        info!(target: telemetry::API, "Attestation agent is shut down");
+        Ok(())
+    }
+
+    fn ensure_open(&self) -> Result<()> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Err(Error::Shutdown);
+        }
+        Ok(())
+    }
+
+    /// Extend the register of the domain, unless given, with a single entry
+    /// and record it in the eventlog. The register is read back only for
+    /// `read_back`, see [`ExtendResult::register_value`].
+    async fn extend_entry(
+        &self,
+        log_entry: EventEntry<'_>,
+        domain: &str,
+        register_index: Option<u64>,
+        read_back: bool,
+    ) -> Result<ExtendResult> {
+        let eventlog_config = self.config.read().await.eventlog_config.clone();
+        let eventlog_algorithm = eventlog_config.eventlog_algorithm;
+        let register_index = resolve_register_index(register_index, domain, &eventlog_config);
+        tracing::Span::current().record("register", register_index);
+
+        let mut eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        ensure_initialized(eventlog.init_log())?;
+        let attester = self.attester.get()?;
+        let capabilities = self.attester.capabilities()?;
+        let tee = self.tee_type();
+        check_register_index(attester, tee, register_index)?;
+        check_register_bank(capabilities, tee, eventlog_config.eventlog_algorithm)?;
+        let register_log = open_register_log(
+            attester,
+            capabilities,
+            tee,
+            &mut eventlog,
+            &eventlog_config,
+            register_index,
+        )
+        .await?;
+        let log_entries = [log_entry];
+        register_log.check_capacity(&log_entries, register_index)?;
+
+        let usage = register_log.usage();
+        let res = extend_events(
+            attester,
+            register_log,
+            eventlog_algorithm,
+            &log_entries,
+            register_index,
+            read_back,
+        )
+        .await;
+        record_logged_events(tee, &res, 1);
+        report_eventlog_usage(tee, register_index, usage, register_log);
+        let mut results = res.map_err(|e| {
+            if e.extended == 0 {
+                Error::attester(tee, "extend runtime measurement", e.source)
+            } else {
+                eventlog_error(e.source)
+            }
+        })?;
+
+        Ok(results.remove(0))
+    }
+
+    /// Extend runtime measurement register with a batch of events. All the
+    /// events are extended into the same register under one eventlog lock.
+    /// The register is read back after the batch only for `read_back`, see
+    /// [`ExtendResult::register_value`].
+    #[instrument(name = "extend_runtime_measurement_batch", skip_all, fields(tee = ?self.attester.current_tee(), events = events.len() as u64, register_index = ?register_index, register = tracing::field::Empty))]
+    async fn extend_batch(
+        &self,
+        events: &[(&str, &str, &str)],
+        register_index: Option<u64>,
+        read_back: bool,
+    ) -> Result<Vec<ExtendResult>> {
+        let _elapsed = Elapsed::start();
+        let eventlog_config = self.config.read().await.eventlog_config.clone();
+        let eventlog_algorithm = eventlog_config.eventlog_algorithm;
+        let register_index = resolve_batch_register_index(
+            register_index,
+            events.iter().map(|(domain, ..)| *domain),
+            &eventlog_config,
+        )?;
+        tracing::Span::current().record("register", register_index);
+        let log_entries = events
+            .iter()
+            .enumerate()
+            .map(|(index, (domain, operation, content))| {
+                EventEntry::new(domain, operation, content)
+                    .map_err(|source| Error::InvalidEvent { index, source })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        ensure_initialized(eventlog.init_log())?;
+        let attester = self.attester.get()?;
+        let capabilities = self.attester.capabilities()?;
+        let tee = self.tee_type();
+        check_register_index(attester, tee, register_index)?;
+        check_register_bank(capabilities, tee, eventlog_config.eventlog_algorithm)?;
+        let register_log = open_register_log(
+            attester,
+            capabilities,
+            tee,
+            &mut eventlog,
+            &eventlog_config,
+            register_index,
+        )
+        .await?;
+        register_log.check_capacity(&log_entries, register_index)?;
+
+        let usage = register_log.usage();
+        let res = extend_events(
+            attester,
+            register_log,
+            eventlog_algorithm,
+            &log_entries,
+            register_index,
+            read_back,
+        )
+        .await;
+        record_logged_events(tee, &res, log_entries.len());
+        report_eventlog_usage(tee, register_index, usage, register_log);
+        Ok(res?)
+    }
+
+    /// Update the configuration with a TOML or JSON document. Tables in the
+    /// document are merged into the current ones recursively, where the
+    /// scalars and arrays present override the current values, while absent
+    /// fields keep them, e.g. only `token_configs.kbs.url` can be given. The
+    /// environment and the command line still override the document, see
+    /// [`Config::apply_env`] and [`Config::apply_overrides`].
+    ///
+    /// The updated configuration is checked by [`Config::validate`], and
+    /// the keys whose values changed are returned, see
+    /// [`Config::changed_keys`]. Changing
+    /// `eventlog_config.eventlog_algorithm`, or `init_pcr` once the INIT
+    /// entry is recorded, is rejected with [`Error::ConfigImmutable`].
+    ///
     /// This is a workaround API for initdata in CoCo. Once
     /// a better design is implemented we can deprecate the API.
     /// See https://github.com/kata-containers/kata-containers/issues/9468
-    pub fn update_configuration(&mut self, conf: &str) -> Result<()> {
-        let mut tmpfile = tempfile::NamedTempFile::new()?;
-        let _ = tmpfile.write(conf.as_bytes())?;
-        tmpfile.flush()?;
-
-        let config = Config::try_from(
-            tmpfile
-                .path()
-                .as_os_str()
-                .to_str()
-                .expect("tempfile will not create non-unicode char"),
-            // Here we can use `expect()` because tempfile crate will generate file name
-            // only including numbers and alphabet (0-9, a-z, A-Z)
+    pub async fn update_configuration(&self, conf: &str) -> Result<Vec<String>> {
+        self.ensure_open()?;
+        let initialized = self.eventlog.lock().await.init_log().is_initialized();
+        let mut config = self.config.write().await;
+        let updated = config
+            .merge_document(conf)
+            .and_then(Config::apply_env)
+            .and_then(|config| config.apply_overrides(&self.overrides))
+            .map_err(|e| Error::ConfigParse(e.into()))?;
+        updated.validate().map_err(Error::ConfigInvalid)?;
+        check_immutable(
+            &config.eventlog_config,
+            &updated.eventlog_config,
+            initialized,
         )?;
-        self.config = config;
+        let changed = config.changed_keys(&updated);
+        let previous = std::mem::replace(&mut *config, updated);
+        drop(config);
+        info!(target: telemetry::CONFIG, ?changed, "Updated AA configuration");
+
+        // Tokens might be issued by the services of the old configuration
+        self.config_changed(&previous).await;
+        Ok(changed)
+    }
+
+    /// Read the config file that AA was created from again, and replace the
+    /// configuration with it. The environment and the command line still
+    /// override the file. Keys removed from the file return to their
+    /// defaults, and updates by [`AttestationAgent::update_configuration`]
+    /// are replaced as well. The eventlog is only set up when AA is created,
+    /// so changes of `eventlog_config` are ignored with a warning. An invalid
+    /// config file is rejected and the current configuration is kept.
+    pub async fn reload_configuration(&self) -> Result<()> {
+        self.ensure_open()?;
+        let Some(config_path) = &self.config_path else {
+            return Err(Error::ConfigParse(anyhow::anyhow!(
+                "AA is not created from a config file"
+            )));
+        };
+
+        let mut config = self.config.write().await;
+        let mut reloaded = Config::try_from(config_path.as_str())
+            .and_then(|config| config.apply_overrides(&self.overrides))
+            .map_err(|e| Error::ConfigParse(e.into()))?;
+        reloaded.validate().map_err(Error::ConfigInvalid)?;
+        if reloaded.eventlog_config != config.eventlog_config {
+            warn!(
+                target: telemetry::CONFIG,
+                config_path,
+                "`eventlog_config` cannot be reloaded, the change takes effect after AA restarts"
+            );
+            reloaded.eventlog_config = config.eventlog_config.clone();
+        }
+        let changed = config.changed_keys(&reloaded);
+        let previous = std::mem::replace(&mut *config, reloaded);
+        drop(config);
+
+        self.config_changed(&previous).await;
+        info!(
+            target: telemetry::CONFIG,
+            config_path,
+            ?changed,
+            "Reloaded AA config file"
+        );
         Ok(())
     }
-}
 
-/// Default PCR index used by AA. `17` is selected for its usage of dynamic root of trust for measurement.
-/// - [Linux TPM PCR Registry](https://uapi-group.org/specifications/specs/linux_tpm_pcr_registry/)
-/// - [TCG TRUSTED BOOT CHAIN IN EDK II](https://tianocore-docs.github.io/edk2-TrustedBootChain/release-1.00/3_TCG_Trusted_Boot_Chain_in_EDKII.html)
-const DEFAULT_PCR_INDEX: u64 = 17;
+    /// Reload the configuration whenever the config file changes, if
+    /// `watch_config` is set and AA is created from a config file. The file
+    /// is watched until the returned watcher is dropped, or until a reloaded
+    /// file unsets `watch_config`.
+    pub async fn watch_configuration(self: &Arc<Self>) -> Result<Option<ConfigWatcher>> {
+        let Some(config_path) = &self.config_path else {
+            return Ok(None);
+        };
+        if !self.config.read().await.watch_config {
+            return Ok(None);
+        }
 
-#[async_trait]
-impl AttestationAPIs for AttestationAgent {
-    async fn get_token(&mut self, token_type: &str) -> Result<Vec<u8>> {
-        let token_type = TokenType::from_str(token_type).context("Unsupported token type")?;
+        ConfigWatcher::start(Arc::downgrade(self), config_path.as_ref()).map(Some)
+    }
+
+    /// Start refreshing the cached KBS token in the background before it
+    /// expires, while `token_configs.kbs.auto_refresh` and
+    /// `token_configs.cache` are set. Only a token that a caller got is
+    /// refreshed, and refreshing follows configuration updates, i.e. it
+    /// pauses while `auto_refresh` is unset and resumes once it is set
+    /// again. The refresh backs off as configured by `retry` while it
+    /// fails, and stops on [`AttestationAgent::shutdown`], or once the
+    /// returned refresher is dropped.
+    #[cfg(feature = "kbs")]
+    pub async fn start_token_refresh(self: &Arc<Self>) -> Result<TokenRefresher> {
+        self.ensure_open()?;
+        Ok(TokenRefresher::start(
+            Arc::downgrade(self),
+            self.shutdown_signal.subscribe(),
+            self.refresh_signal.subscribe(),
+        ))
+    }
+
+    /// The configuration in use as a TOML document, with the credentials in
+    /// it redacted, see [`Config::redacted`]. This is the result of the
+    /// defaults, the config file, the environment, the command line and any
+    /// updates.
+    pub async fn dump_config(&self) -> Result<String> {
+        self.config
+            .read()
+            .await
+            .redacted()
+            .to_toml_string()
+            .map_err(|e| Error::ConfigParse(e.into()))
+    }
+
+    /// Replace the whole configuration with a TOML or JSON document. Absent
+    /// fields get their default values rather than keeping the current ones.
+    /// The changed keys are returned and the same fields are immutable as by
+    /// [`AttestationAgent::update_configuration`].
+    pub async fn replace_configuration(&self, conf: &str) -> Result<Vec<String>> {
+        self.ensure_open()?;
+        let initialized = self.eventlog.lock().await.init_log().is_initialized();
+        let updated = Config::parse_document(conf)
+            .and_then(Config::apply_env)
+            .and_then(|config| config.apply_overrides(&self.overrides))
+            .map_err(|e| Error::ConfigParse(e.into()))?;
+        updated.validate().map_err(Error::ConfigInvalid)?;
+        let mut config = self.config.write().await;
+        check_immutable(
+            &config.eventlog_config,
+            &updated.eventlog_config,
+            initialized,
+        )?;
+        let changed = config.changed_keys(&updated);
+        let previous = std::mem::replace(&mut *config, updated);
+        drop(config);
+        info!(target: telemetry::CONFIG, ?changed, "Replaced AA configuration");
+
+        self.config_changed(&previous).await;
+        Ok(changed)
+    }
+
+    /// Persist the cached token at `path`, unless it has been evicted
+    /// meanwhile. The token is served anyway if this fails.
+    #[cfg(feature = "token")]
+    #[cfg_attr(not(feature = "kbs"), allow(unused_variables))]
+    async fn persist_token(
+        &self,
+        token_configs: &config::TokenConfigs,
+        token_type: TokenType,
+        cache_key: &str,
+        path: &Path,
+    ) {
+        #[cfg(feature = "kbs")]
+        let persist_tee_keypair = token_configs.kbs.persist_tee_keypair;
+        #[cfg(not(feature = "kbs"))]
+        let persist_tee_keypair = false;
+
+        let _files = self.token_files.lock().await;
+        let persisted =
+            self.token_cache
+                .lock()
+                .await
+                .persisted(token_type, cache_key, persist_tee_keypair);
+        let Some(persisted) = persisted else {
+            return;
+        };
+        if let Err(e) = cache::persist(persisted, path).await {
+            warn!(
+                target: telemetry::TOKEN,
+                path = %path.display(),
+                error = ?e,
+                "Persisting token failed"
+            );
+        }
+    }
+
+    /// Follow a change of the configuration from `previous` by the tokens:
+    /// the cached ones of the services that changed are dropped, and the
+    /// KBS token refresh is rescheduled.
+    #[cfg_attr(not(feature = "token"), allow(unused_variables))]
+    async fn config_changed(&self, previous: &Config) {
+        #[cfg(feature = "token")]
+        self.evict_changed_tokens(&previous.token_configs).await;
+        #[cfg(feature = "kbs")]
+        self.refresh_signal.send_replace(());
+    }
+
+    /// Drop the cached tokens of the services that are no longer the ones
+    /// of their token type, by the URLs of `previous`. The tokens of the
+    /// other services are still served, also if other fields of their
+    /// configuration changed.
+    #[cfg(feature = "token")]
+    async fn evict_changed_tokens(&self, previous: &config::TokenConfigs) {
+        let moved: Vec<(TokenType, String)> = {
+            let config = self.config.read().await;
+            TokenType::built_in()
+                .into_iter()
+                .map(|token_type| (token_type, token_url(previous, token_type)))
+                .filter(|(token_type, url)| *url != token_url(&config.token_configs, *token_type))
+                .collect()
+        };
+        let mut cache = self.token_cache.lock().await;
+        for (token_type, url) in moved {
+            let evicted = cache.evict(token_type, &url);
+            tracing::debug!(target: telemetry::TOKEN, ?token_type, evicted, "Evicted tokens of changed service");
+        }
+    }
+
+    /// Drop the cached tokens of the given type from its current service,
+    /// for any audience, and the token persisted for it, s.t. the next
+    /// request fetches a new one, e.g. as the relying party rejected the
+    /// token. The tokens of the other services are kept. Whether any token
+    /// was cached. The tokens of registered getters are never cached.
+    #[cfg(feature = "token")]
+    pub async fn evict_token(&self, token_type: &str) -> Result<bool> {
+        self.ensure_open()?;
+        let name = self.resolve_token_type(token_type).await?;
+        let token_type = match self.token_getters.resolve(&name) {
+            Some(token::ResolvedTokenType::BuiltIn(token_type)) => token_type,
+            Some(token::ResolvedTokenType::Registered(_)) => return Ok(false),
+            None => return Err(Error::UnsupportedTokenType(name)),
+        };
+        let (url, cache_path) = {
+            let config = self.config.read().await;
+            let token_configs = &config.token_configs;
+            (
+                token_url(token_configs, token_type),
+                token_cache_path(token_configs, token_type).map(Path::to_path_buf),
+            )
+        };
+
+        // No token is persisted meanwhile
+        let _files = self.token_files.lock().await;
+        let mut evicted = self.token_cache.lock().await.evict(token_type, &url) != 0;
+        if let Some(path) = cache_path {
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => evicted = true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    warn!(target: telemetry::TOKEN, path = %path.display(), error = %e, "Removing persisted token failed")
+                }
+            }
+        }
+        info!(target: telemetry::TOKEN, ?token_type, evicted, "Evicted cached tokens");
+        Ok(evicted)
+    }
+
+    #[cfg(feature = "token")]
+    #[instrument(name = "get_token", skip_all, fields(tee = ?self.attester.current_tee(), token_type = token_type, refresh = refresh))]
+    async fn get_token_with_cache(
+        &self,
+        token_type: &str,
+        params: serde_json::Value,
+        nonce: Option<Vec<u8>>,
+        refresh: bool,
+    ) -> Result<TokenResponse> {
+        let _elapsed = Elapsed::start();
+        self.ensure_open()?;
+        let name = &self.resolve_token_type(token_type).await?;
+        tracing::Span::current().record("token_type", name.as_str());
+        let token_type = match self.token_getters.resolve(name) {
+            Some(token::ResolvedTokenType::BuiltIn(token_type)) => token_type,
+            Some(token::ResolvedTokenType::Registered(getter)) => {
+                return self
+                    .get_registered_token(name, &getter, &params, nonce)
+                    .await;
+            }
+            None => return Err(Error::UnsupportedTokenType(name.clone())),
+        };
+        let (token_configs, retry_config) = {
+            let config = self.config.read().await;
+            (config.token_configs.clone(), config.retry.clone())
+        };
+        let url = token_url(&token_configs, token_type);
+
+        // A token for an audience of the params is cached and shared per
+        // audience, s.t. the tokens of different audiences do not collide
+        let audience = audience_param(token_type, &params);
+        let per_caller = (!params.is_null() && audience.is_none()) || nonce.is_some();
+        let cache_key = cache::cache_key(&url, audience);
+        // Only the private key of the last certificate is kept, which would
+        // not be of a cached certificate after a per caller one
+        #[cfg(feature = "cert")]
+        if token_type == TokenType::Certificate && per_caller {
+            return Err(Error::InvalidArgument(format!(
+                "token type `{name}` takes neither params nor a nonce"
+            )));
+        }
+        // Intel Trust Authority only appraises the evidence of TDX and SGX,
+        // which is told before any request to it
+        #[cfg(feature = "ita")]
+        if token_type == TokenType::Ita {
+            if token_configs.ita.is_none() {
+                return Err(Error::ConfigInvalid(vec![config::ValidationError {
+                    key: "token_configs.ita".into(),
+                    reason: format!("is not set, which token type `{name}` needs"),
+                }]));
+            }
+            let tee = self.tee_type();
+            if token::ita::report_data_algorithm(tee).is_none() {
+                return Err(Error::UnsupportedTokenTee {
+                    token_type: name.to_string(),
+                    tee,
+                });
+            }
+        }
+        // Only the token without an audience of the params is persisted
+        let cache_path =
+            token_cache_path(&token_configs, token_type).filter(|_| audience.is_none());
+        // The private key is only kept for the last certificate, which a
+        // cached one may not be
+        #[cfg(feature = "cert")]
+        let cacheable = token_configs.cache && token_type != TokenType::Certificate;
+        #[cfg(not(feature = "cert"))]
+        let cacheable = token_configs.cache;
+        if cacheable && !refresh && !per_caller {
+            let mut cache = self.token_cache.lock().await;
+            if let Some(path) = cache_path {
+                // The token fetched before AA restarted
+                if cache.expiry(token_type, &cache_key).is_none() {
+                    cache.load(token_type, &cache_key, path);
+                }
+            }
+            let cached = cache.get(
+                token_type,
+                &cache_key,
+                token_configs.cache_skew_seconds,
+                token_configs.cache_leeway_seconds,
+            );
+            metrics::token_cache_looked_up(token_type.as_ref(), &url, cached.is_some());
+            if let Some(token) = cached {
+                tracing::debug!(target: telemetry::TOKEN, "Use cached token");
+                return Ok(token);
+            }
+        }
+
+        let fetch = async {
+            // The token depends on the params or the nonce of the caller,
+            // s.t. it is neither cached nor shared with other callers
+            if per_caller {
+                return self
+                    .fetch_token(&token_configs, &retry_config, token_type, &params, nonce)
+                    .await;
+            }
+
+            // Concurrent requests for the same token share one attestation
+            self.token_fetches
+                .run((token_type, cache_key.clone()), || async {
+                    let token = self
+                        .fetch_token(&token_configs, &retry_config, token_type, &params, None)
+                        .await?;
+                    if cacheable {
+                        self.token_cache.lock().await.insert(
+                            token_type,
+                            cache_key.clone(),
+                            token.clone(),
+                            token_configs.cache_opaque_max_age_seconds,
+                        );
+                        #[cfg(feature = "kbs")]
+                        if token_type == TokenType::Kbs {
+                            self.refresh_signal.send_replace(());
+                        }
+                        if let Some(path) = cache_path {
+                            self.persist_token(&token_configs, token_type, &cache_key, path)
+                                .await;
+                        }
+                    }
+                    Ok(token)
+                })
+                .await
+        };
+        let timeout = token_timeout(&token_configs, token_type);
+        with_timeout(timeout, "get token", &url, async {
+            fetch
+                .await
+                .map_err(|source| token_fetch_error(name, &url, source))
+        })
+        .await
+    }
+
+    /// Fetch a new token of the given type from the configured service,
+    /// retrying transient failures as configured by `retry_config`, or by
+    /// the `max_attempts` of the service.
+    #[cfg(feature = "token")]
+    async fn fetch_token(
+        &self,
+        token_configs: &config::TokenConfigs,
+        retry_config: &RetryConfig,
+        token_type: TokenType,
+        params: &serde_json::Value,
+        nonce: Option<Vec<u8>>,
+    ) -> anyhow::Result<TokenResponse> {
+        let retry_config =
+            retry_config.with_max_attempts(token_max_attempts(token_configs, token_type));
+        retry::retry(&retry_config, "get token", token::is_retryable, move || {
+            self.fetch_token_once(token_configs, token_type, params, nonce.clone())
+        })
+        .await
+    }
 
-        match token_type {
+    #[cfg(feature = "token")]
+    async fn fetch_token_once(
+        &self,
+        token_configs: &config::TokenConfigs,
+        token_type: TokenType,
+        params: &serde_json::Value,
+        nonce: Option<Vec<u8>>,
+    ) -> anyhow::Result<TokenResponse> {
+        let token = match token_type {
             #[cfg(feature = "kbs")]
             token::TokenType::Kbs => {
-                token::kbs::KbsTokenGetter::new(&self.config.token_configs.kbs)
+                token::kbs::KbsTokenGetter::new(&token_configs.kbs)
+                    .with_preferred(&self.kbs_preferred)
+                    .with_params(params.clone())
+                    .with_nonce(nonce)
                     .get_token()
                     .await
             }
             #[cfg(feature = "coco_as")]
             token::TokenType::CoCoAS => {
-                token::coco_as::CoCoASTokenGetter::new(&self.config.token_configs.coco_as)
+                token::coco_as::CoCoASTokenGetter::new(&token_configs.coco_as)
+                    .with_params(params.clone())
+                    .with_nonce(nonce)
                     .get_token()
                     .await
             }
+            #[cfg(feature = "cert")]
+            token::TokenType::Certificate => {
+                let getter =
+                    token::cert::CertTokenGetter::new(&token_configs.cert, &token_configs.kbs)
+                        .with_preferred(&self.kbs_preferred);
+                let chain = getter.get_token().await;
+                if chain.is_ok() {
+                    *self.certificate_key.lock().await = getter.private_key();
+                }
+                chain
+            }
+            #[cfg(feature = "ita")]
+            token::TokenType::Ita => match (&token_configs.ita, self.attester.get()) {
+                (Some(config), Ok(attester)) => {
+                    token::ita::ItaTokenGetter::new(config, self.tee_type(), attester)
+                        .with_params(params.clone())
+                        .with_nonce(nonce)
+                        .get_token()
+                        .await
+                }
+                (None, _) => Err(anyhow::anyhow!("`token_configs.ita` is not set")),
+                (_, Err(e)) => Err(e.into()),
+            },
+        };
+        metrics::token_fetched(token_type.as_ref(), token.is_ok());
+        token
+    }
+
+    #[cfg(not(feature = "token"))]
+    async fn get_token_with_cache(
+        &self,
+        token_type: &str,
+        params: serde_json::Value,
+        nonce: Option<Vec<u8>>,
+        _refresh: bool,
+    ) -> Result<TokenResponse> {
+        // No token type is built in, but getters can be registered
+        self.ensure_open()?;
+        let token_type = self.resolve_token_type(token_type).await?;
+        match self.token_getters.resolve(&token_type) {
+            Some(token::ResolvedTokenType::Registered(getter)) => {
+                self.get_registered_token(&token_type, &getter, &params, nonce)
+                    .await
+            }
+            _ => Err(Error::UnsupportedTokenType(token_type)),
         }
     }
 
-    /// Get TEE hardware signed evidence that includes the runtime data.
-    async fn get_evidence(&mut self, runtime_data: &[u8]) -> Result<Vec<u8>> {
-        let evidence = self.attester.get_evidence(runtime_data.to_vec()).await?;
-        Ok(evidence.into_bytes())
+    /// The token type of the request: the given one, or if it is empty,
+    /// `token_configs.default_type` or the only token type available.
+    async fn resolve_token_type(&self, token_type: &str) -> Result<String> {
+        if !token_type.is_empty() {
+            return Ok(token_type.to_string());
+        }
+
+        let (default_type, built_in) = {
+            let config = self.config.read().await;
+            let token_configs = &config.token_configs;
+            #[cfg(feature = "token")]
+            let built_in = configured_token_types(token_configs);
+            #[cfg(not(feature = "token"))]
+            let built_in = token::TokenType::built_in();
+            (token_configs.default_type.clone(), built_in)
+        };
+        let registered = self.token_getters.names();
+        let available: Vec<&str> = built_in
+            .iter()
+            .map(AsRef::as_ref)
+            .chain(registered.iter().map(String::as_str))
+            .collect();
+        default_token_type(default_type.as_deref(), &available)
     }
 
-    /// Extend runtime measurement register. Parameters
-    /// - `events`: a event slice. Any single event will be calculated into a hash digest to extend the current
-    /// platform's RTMR.
-    /// - `register_index`: a target PCR that will be used to extend RTMR. Note that different platform
-    /// would have its own strategy to map a PCR index into a architectual RTMR index. If not given, a default one
-    /// will be used.
-    async fn extend_runtime_measurement(
-        &mut self,
-        domain: &str,
-        operation: &str,
-        content: &str,
-        register_index: Option<u64>,
-    ) -> Result<()> {
-        let register_index = register_index.unwrap_or_else(|| {
-            info!("No PCR index provided, use default {DEFAULT_PCR_INDEX}");
-            DEFAULT_PCR_INDEX
-        });
+    /// Get a token of a type that is not built in from the getter
+    /// registered for it, see [`AttestationAgent::register_token_getter`].
+    async fn get_registered_token(
+        &self,
+        token_type: &str,
+        getter: &token::SharedTokenGetter,
+        params: &serde_json::Value,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<TokenResponse> {
+        if !params.is_null() || nonce.is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "token type `{token_type}` takes neither params nor a nonce"
+            )));
+        }
 
-        let log_entry = EventEntry::new(domain, operation, content);
-        let event_digest = log_entry.digest_with(self.config.eventlog_config.eventlog_algorithm);
+        tracing::debug!(target: telemetry::TOKEN, getter = getter.name(), "Get token from registered getter");
+        let token = getter.get_token().await;
+        metrics::token_fetched(token_type, token.is_ok());
+        token.map_err(|source| Error::TokenFetch {
+            token_type: token_type.to_string(),
+            url: getter.name().to_string(),
+            source,
+        })
+    }
+}
 
-        let mut eventlog = self.eventlog.lock().await;
+/// The token as the `get_token` API returns it, see
+/// [`TokenResponse::into_bytes`].
+fn token_bytes(token_type: &str, token: TokenResponse) -> Result<Vec<u8>> {
+    token.into_bytes().map_err(|source| Error::InvalidToken {
+        token_type: token_type.to_string(),
+        source,
+    })
+}
 
-        self.attester
-            .extend_runtime_measurement(event_digest, register_index)
-            .await?;
+/// The token type that an empty one of the caller stands for: the
+/// configured default, or the only one of the available token types.
+fn default_token_type(default_type: Option<&str>, available: &[&str]) -> Result<String> {
+    if let Some(default_type) = default_type {
+        return Ok(default_type.to_string());
+    }
 
-        eventlog.write_log(&log_entry.to_string())?;
+    match available {
+        [token_type] => Ok(token_type.to_string()),
+        [] => Err(Error::InvalidArgument(
+            "no token type is given, and none is available".into(),
+        )),
+        _ => Err(Error::InvalidArgument(format!(
+            "no token type is given, and `token_configs.default_type` is not set, available token types: {}",
+            available.join(", ")
+        ))),
+    }
+}
 
-        Ok(())
+/// The built-in token types whose config is present, s.t. an empty token
+/// type may stand for them. The others are built in, but not configured
+/// for this guest.
+#[cfg(feature = "token")]
+fn configured_token_types(token_configs: &config::TokenConfigs) -> Vec<TokenType> {
+    #[cfg(feature = "kbs")]
+    let kbs_configured = token_configs
+        .kbs
+        .endpoints()
+        .iter()
+        .any(|url| !url.is_empty());
+    TokenType::built_in()
+        .into_iter()
+        .filter(|token_type| match token_type {
+            #[cfg(feature = "kbs")]
+            TokenType::Kbs => kbs_configured,
+            #[cfg(feature = "coco_as")]
+            TokenType::CoCoAS => !token_configs.coco_as.url.is_empty(),
+            // The certificates are issued by the KBS of `kbs`, for the
+            // names of `cert`
+            #[cfg(feature = "cert")]
+            TokenType::Certificate => {
+                let cert = &token_configs.cert;
+                kbs_configured && (cert.common_name.is_some() || !cert.sans.is_empty())
+            }
+            #[cfg(feature = "ita")]
+            TokenType::Ita => token_configs.ita.is_some(),
+        })
+        .collect()
+}
+
+/// The URL of the service that issues the tokens of the given type, or the
+/// URLs of all its endpoints joined by `,`.
+#[cfg(feature = "token")]
+fn token_url(token_configs: &config::TokenConfigs, token_type: TokenType) -> String {
+    match token_type {
+        #[cfg(feature = "kbs")]
+        token::TokenType::Kbs => token_configs.kbs.endpoints().join(","),
+        #[cfg(feature = "coco_as")]
+        token::TokenType::CoCoAS => token_configs.coco_as.url.clone(),
+        #[cfg(feature = "cert")]
+        token::TokenType::Certificate => token_configs.kbs.endpoints().join(","),
+        #[cfg(feature = "ita")]
+        token::TokenType::Ita => token_configs
+            .ita
+            .as_ref()
+            .map(|ita| ita.url.clone())
+            .unwrap_or_default(),
     }
+}
 
-    /// Check the initdata binding. If current platform does not support initdata
-    /// injection, return `InitdataResult::Unsupported`.
-    async fn check_init_data(&mut self, init_data: &[u8]) -> Result<InitdataResult> {
-        self.attester.check_init_data(init_data).await
+/// The audience of the params of a CoCoAS token request that has no other
+/// params, s.t. the token is cached and shared per audience rather than
+/// fetched for each caller.
+#[cfg(feature = "token")]
+#[cfg_attr(not(feature = "coco_as"), allow(unused_variables))]
+fn audience_param(token_type: TokenType, params: &serde_json::Value) -> Option<&str> {
+    #[cfg(feature = "coco_as")]
+    if token_type == TokenType::CoCoAS {
+        return match params.as_object() {
+            Some(params) if params.len() == 1 => {
+                params.get("audience").and_then(serde_json::Value::as_str)
+            }
+            _ => None,
+        };
+    }
+    None
+}
+
+/// The error of a token that could not be fetched. AS not knowing the
+/// policies of the request is told apart, as it is not fixed by retrying.
+#[cfg(feature = "token")]
+fn token_fetch_error(token_type: &str, url: &str, source: anyhow::Error) -> Error {
+    #[cfg(feature = "coco_as")]
+    if let Some(e) = source
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<token::coco_as::UnknownPolicyError>())
+    {
+        return Error::UnknownPolicy {
+            url: url.to_string(),
+            policy_ids: e.policy_ids.clone(),
+            source,
+        };
+    }
+
+    Error::TokenFetch {
+        token_type: token_type.to_string(),
+        url: url.to_string(),
+        source,
+    }
+}
+
+/// Seconds to wait for a token of the given type, `0` meaning no limit.
+#[cfg(feature = "token")]
+fn token_timeout(token_configs: &config::TokenConfigs, token_type: TokenType) -> u64 {
+    match token_type {
+        #[cfg(feature = "kbs")]
+        token::TokenType::Kbs => token_configs.kbs.timeout_seconds,
+        #[cfg(feature = "coco_as")]
+        token::TokenType::CoCoAS => token_configs.coco_as.timeout_seconds,
+        #[cfg(feature = "cert")]
+        token::TokenType::Certificate => token_configs.kbs.timeout_seconds,
+        #[cfg(feature = "ita")]
+        token::TokenType::Ita => token_configs
+            .ita
+            .as_ref()
+            .map_or(config::DEFAULT_TIMEOUT_SECONDS, |ita| ita.timeout_seconds),
+    }
+}
+
+/// Attempts of a token request of the given type set for its service, if
+/// any, in place of `retry.max_attempts`.
+#[cfg(feature = "token")]
+fn token_max_attempts(token_configs: &config::TokenConfigs, token_type: TokenType) -> Option<u32> {
+    match token_type {
+        #[cfg(feature = "kbs")]
+        token::TokenType::Kbs => token_configs.kbs.max_attempts,
+        #[cfg(feature = "coco_as")]
+        token::TokenType::CoCoAS => token_configs.coco_as.max_attempts,
+        #[cfg(feature = "cert")]
+        token::TokenType::Certificate => token_configs.kbs.max_attempts,
+        #[cfg(feature = "ita")]
+        token::TokenType::Ita => None,
+    }
+}
+
+/// Path of the file the tokens of the given type are persisted in, if any.
+#[cfg(feature = "token")]
+fn token_cache_path(
+    token_configs: &config::TokenConfigs,
+    token_type: TokenType,
+) -> Option<&std::path::Path> {
+    match token_type {
+        #[cfg(feature = "kbs")]
+        token::TokenType::Kbs => token_configs.kbs.cache_path.as_deref(),
+        #[cfg(feature = "coco_as")]
+        token::TokenType::CoCoAS => token_configs.coco_as.cache_path.as_deref(),
+        // The private key is never persisted, so neither is its certificate
+        #[cfg(feature = "cert")]
+        token::TokenType::Certificate => None,
+        #[cfg(feature = "ita")]
+        token::TokenType::Ita => None,
+    }
+}
+
+/// Reject changes of the eventlog fields that the recorded entries depend
+/// on. The hash algorithm of the entries cannot change, and neither can the
+/// register of the INIT entry once it is recorded.
+fn check_immutable(
+    current: &EventlogConfig,
+    updated: &EventlogConfig,
+    initialized: bool,
+) -> Result<()> {
+    if updated.eventlog_algorithm != current.eventlog_algorithm {
+        return Err(Error::ConfigImmutable("eventlog_config.eventlog_algorithm"));
+    }
+    if initialized && updated.init_pcr != current.init_pcr {
+        return Err(Error::ConfigImmutable("eventlog_config.init_pcr"));
+    }
+    Ok(())
+}
+
+fn tee_name(tee: Tee) -> String {
+    match serde_json::to_value(tee) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => format!("{tee:?}").to_lowercase(),
+    }
+}
+
+/// The options of the attesters in the config, with the paths of the
+/// eventlog of AA that the attesters put into the evidence.
+#[cfg_attr(not(feature = "tdx-attester"), allow(unused_mut))]
+fn attester_options(config: &Config) -> attester::AttesterOptions {
+    let mut options = config.attester.options.clone();
+    #[cfg(feature = "tdx-attester")]
+    {
+        options.tdx.aa_eventlog_path = config.eventlog_config.file_path.clone();
+    }
+    options
+}
+
+/// Create the attesters of all the other TEE types that the platform
+/// provides, used by the composite evidence mode.
+fn create_secondary_attesters(
+    primary: Tee,
+    options: &attester::AttesterOptions,
+) -> Vec<(Tee, BoxedAttester)> {
+    attester::detect_all_tee_types()
+        .into_iter()
+        .filter(|tee| *tee != primary)
+        .filter_map(|tee| match attester::new_attester(tee, options) {
+            Ok(attester) => Some((tee, attester)),
+            Err(e) => {
+                warn!(
+                    target: telemetry::EVIDENCE,
+                    ?tee,
+                    error = ?e,
+                    "Failed to create secondary attester"
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+async fn get_secondary_evidence(
+    tee: Tee,
+    attester: &BoxedAttester,
+    runtime_data: RuntimeData,
+    evidence_config: &EvidenceConfig,
+    timeout: u64,
+) -> anyhow::Result<String> {
+    // Secondary attesters might take report data of another size than the
+    // primary one, so the runtime data is fitted for each of them.
+    let report_data = runtime_data.into_report_data(
+        evidence_config.runtime_data_algorithm,
+        attester.report_data_len(),
+        evidence_config.hash_oversized_runtime_data,
+    )?;
+    let endpoint = format!("{} attester", tee_name(tee));
+    let evidence = with_timeout(timeout, "get evidence", &endpoint, async {
+        attester
+            .get_evidence(report_data)
+            .await
+            .map_err(|e| Error::attester(tee, "get evidence", e))
+    })
+    .await?;
+
+    Ok(evidence)
+}
+
+/// Await the operation for at most `seconds`, `0` meaning no limit.
+async fn with_timeout<T>(
+    seconds: u64,
+    operation: &'static str,
+    endpoint: &str,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    if seconds == 0 {
+        return fut.await;
+    }
+
+    tokio::time::timeout(Duration::from_secs(seconds), fut)
+        .await
+        .map_err(|_| Error::Timeout {
+            operation,
+            endpoint: endpoint.to_string(),
+            seconds,
+        })?
+}
+
+fn create_device_attesters(classes: &[String]) -> Result<Vec<(String, BoxedDeviceAttester)>> {
+    classes
+        .iter()
+        .map(|class| {
+            let attester =
+                new_device_attester(class).map_err(|source| Error::DeviceAttesterFailure {
+                    class: class.clone(),
+                    source,
+                })?;
+            let attester = attester.ok_or_else(|| Error::UnknownDeviceAttester(class.clone()))?;
+            Ok((class.clone(), attester))
+        })
+        .collect()
+}
+
+/// Collect the evidence of all the given device attesters into a JSON object
+/// keyed by device class.
+async fn collect_device_evidence(
+    device_attesters: &[(String, BoxedDeviceAttester)],
+    report_data: &[u8],
+) -> Result<Vec<u8>> {
+    let mut evidence = serde_json::Map::new();
+    for (class, attester) in device_attesters {
+        let device_evidence =
+            attester
+                .get_evidence(report_data.to_vec())
+                .await
+                .map_err(|source| Error::DeviceAttesterFailure {
+                    class: class.clone(),
+                    source,
+                })?;
+        evidence.insert(class.clone(), device_evidence);
+    }
+
+    Ok(serde_json::to_vec(&evidence)?)
+}
+
+/// Check the register index against the registers that the attester can
+/// extend, s.t. a wrong index fails with the valid values listed rather than
+/// with an error from the device.
+fn check_register_index(attester: &BoxedAttester, tee: Tee, register_index: u64) -> Result<()> {
+    match attester.runtime_measurement_registers() {
+        Some(registers) if registers.contains(&register_index) => Ok(()),
+        valid => Err(Error::UnsupportedRegister {
+            tee,
+            register_index,
+            valid,
+        }),
+    }
+}
+
+/// Check the eventlog algorithm against the register banks that the
+/// attester reports in its capabilities, s.t. the digests are not extended
+/// into a bank the platform does not have. Attesters that do not report
+/// their banks are not checked.
+fn check_register_bank(
+    capabilities: &AttesterCapabilities,
+    tee: Tee,
+    algorithm: HashAlgorithm,
+) -> Result<()> {
+    let supported = &capabilities.supported_hash_algorithms;
+    if supported.is_empty() || supported.contains(&algorithm) {
+        return Ok(());
+    }
+    Err(Error::UnsupportedRegisterBank {
+        tee,
+        algorithm,
+        supported: supported.clone(),
+    })
+}
+
+/// The register of a runtime measurement of `domain`: the one given by the
+/// caller, or else the one of the domain in `domain_pcr_map`, or else
+/// `default_register_index`.
+fn resolve_register_index(
+    register_index: Option<u64>,
+    domain: &str,
+    eventlog_config: &EventlogConfig,
+) -> u64 {
+    register_index
+        .or_else(|| {
+            eventlog_config
+                .domain_pcr_map
+                .iter()
+                .find(|(mapped, _)| mapped.eq_ignore_ascii_case(domain))
+                .map(|(_, index)| *index)
+        })
+        .unwrap_or(eventlog_config.default_register_index)
+}
+
+/// The register of a batch, which is extended into one register. Without an
+/// index given by the caller, all the domains must map to the same one.
+fn resolve_batch_register_index<'a>(
+    register_index: Option<u64>,
+    domains: impl IntoIterator<Item = &'a str>,
+    eventlog_config: &EventlogConfig,
+) -> Result<u64> {
+    let mut resolved = None;
+    for domain in domains {
+        let index = resolve_register_index(register_index, domain, eventlog_config);
+        match resolved {
+            Some(other) if other != index => {
+                return Err(Error::InvalidArgument(format!(
+                    "the domains of the batch map to registers {other} and {index}, give the register explicitly"
+                )));
+            }
+            _ => resolved = Some(index),
+        }
+    }
+
+    Ok(
+        resolved
+            .unwrap_or_else(|| register_index.unwrap_or(eventlog_config.default_register_index)),
+    )
+}
+
+/// Runtime measurements must follow the INIT entry in the eventlog, or a
+/// verifier cannot replay the register. For the same reason nothing is
+/// extended while the eventlog is disabled.
+fn ensure_initialized(eventlog: &EventLog) -> Result<()> {
+    if !eventlog.is_enabled() {
+        return Err(Error::Unsupported(
+            "runtime measurement with the eventlog disabled",
+        ));
+    }
+    if !eventlog.is_initialized() {
+        return Err(Error::NotInitialized);
+    }
+    Ok(())
+}
+
+/// The INIT entry, which starts the eventlog of each register. We should get
+/// the current platform's evidence to see the RTMR value. Here we assume
+/// RTMR is not polluted thus all be set `\0`
+fn init_entry(algorithm: HashAlgorithm) -> &'static str {
+    match algorithm {
+        HashAlgorithm::Sha256 => "INIT sha256/0000000000000000000000000000000000000000000000000000000000000000",
+        HashAlgorithm::Sha384 => "INIT sha384/000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        HashAlgorithm::Sha512 => "INIT sha512/00000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+    }
+}
+
+/// The eventlog of the register. When the register has none yet, it is
+/// opened and the INIT entry is extended into the register and recorded as
+/// its first entry.
+async fn open_register_log<'a>(
+    attester: &BoxedAttester,
+    capabilities: &AttesterCapabilities,
+    tee: Tee,
+    eventlog: &'a mut RegisterLogs,
+    eventlog_config: &EventlogConfig,
+    register_index: u64,
+) -> Result<&'a mut EventLog> {
+    let algorithm = eventlog_config.eventlog_algorithm;
+    let initial = initial_register_value(tee, register_index, algorithm.digest_len());
+    let register_log = eventlog
+        .open(register_index, initial)
+        .map_err(Error::EventLogIo)?;
+    if register_log.is_initialized() {
+        ensure_single_bank(capabilities, tee, register_log, algorithm, register_index)?;
+        return Ok(register_log);
+    }
+
+    let init_entry = init_entry(algorithm);
+    let event_digest = algorithm.digest(init_entry.as_bytes());
+    register_log
+        .write_init_log(init_entry, register_index, &event_digest)
+        .map_err(|e| eventlog_write_failed(tee, e))?;
+    if let Err(e) = attester
+        .extend_runtime_measurement(event_digest, register_index)
+        .await
+    {
+        if let Err(discard) = register_log.discard_last().await {
+            warn!(
+                target: telemetry::EVENTLOG,
+                register_index,
+                error = ?discard,
+                "Discarding the INIT entry failed"
+            );
+        }
+        return Err(Error::attester(tee, "extend the INIT entry", e));
+    }
+    metrics::eventlog_entries_written(&tee_name(tee), 1);
+    Ok(register_log)
+}
+
+/// Entries of another algorithm, recorded before `eventlog_algorithm` was
+/// changed, are extended into another bank of the register. A verifier can
+/// only replay both if the attester has both banks, e.g. a TPM, otherwise
+/// the register is not extended with the configured algorithm.
+fn ensure_single_bank(
+    capabilities: &AttesterCapabilities,
+    tee: Tee,
+    register_log: &EventLog,
+    algorithm: HashAlgorithm,
+    register_index: u64,
+) -> Result<()> {
+    let Some(recorded) = register_log
+        .algorithms()
+        .iter()
+        .find(|recorded| **recorded != algorithm)
+    else {
+        return Ok(());
+    };
+    let banks = &capabilities.supported_hash_algorithms;
+    if banks.contains(recorded) && banks.contains(&algorithm) {
+        return Ok(());
+    }
+    Err(Error::EventLogAlgorithmMix {
+        tee,
+        register_index,
+        recorded: *recorded,
+        algorithm,
+    })
+}
+
+/// Record the events in the eventlog and extend them into the register in
+/// order. The digests are all calculated before touching the register.
+///
+/// A register cannot be rolled back, so each entry is written to the
+/// storage, off the async runtime, before its event is extended. If that
+/// fails, e.g. as the storage is full, the batch stops with the register
+/// untouched. If extending fails, the entry is discarded again, s.t. the
+/// eventlog keeps replaying to the register.
+///
+/// With `read_back`, the register is read back once after the batch, if
+/// the attester can, to give its value with the last event. It is not
+/// calculated from the events, as the register may also be extended
+/// outside of AA. Reading it may be costly, e.g. a TDREPORT on TDX, so it
+/// is only read for the callers that return it.
+async fn extend_events(
+    attester: &BoxedAttester,
+    eventlog: &mut EventLog,
+    hash_algorithm: HashAlgorithm,
+    events: &[EventEntry<'_>],
+    register_index: u64,
+    read_back: bool,
+) -> std::result::Result<Vec<ExtendResult>, BatchExtendError> {
+    let digests: Vec<Vec<u8>> = events
+        .iter()
+        .map(|event| event.digest_with(hash_algorithm))
+        .collect();
+
+    let mut results = Vec::with_capacity(events.len());
+    for (applied, (event, digest)) in events.iter().zip(digests).enumerate() {
+        let not_logged = |source| BatchExtendError {
+            extended: applied,
+            logged: applied,
+            source,
+        };
+        eventlog
+            .write_log(&event.to_string(), register_index, &digest)
+            .map_err(not_logged)?;
+        if let Err(e) = eventlog.end_batch_async().await {
+            // The lines that failed to be written must not be written later
+            if let Err(discard) = eventlog.discard_last().await {
+                warn!(
+                    target: telemetry::EVENTLOG,
+                    register_index,
+                    error = ?discard,
+                    "Discarding an eventlog entry failed"
+                );
+            }
+            return Err(not_logged(e));
+        }
+
+        // The entry is durable, the register can be extended
+        if let Err(source) = attester
+            .extend_runtime_measurement(digest.clone(), register_index)
+            .await
+        {
+            let logged = match eventlog.discard_last().await {
+                Ok(()) => applied,
+                Err(discard) => {
+                    warn!(
+                        target: telemetry::EVENTLOG,
+                        register_index,
+                        error = ?discard,
+                        "Discarding an eventlog entry failed"
+                    );
+                    applied + 1
+                }
+            };
+            return Err(BatchExtendError {
+                extended: applied,
+                logged,
+                source,
+            });
+        }
+
+        results.push(ExtendResult {
+            register_index,
+            algorithm: hash_algorithm,
+            digest,
+            register_value: None,
+        });
+    }
+
+    if let Some(last) = results.last_mut().filter(|_| read_back) {
+        last.register_value = attester
+            .get_runtime_measurement(register_index, hash_algorithm)
+            .await
+            .ok();
+    }
+
+    Ok(results)
+}
+
+/// Value of a register before anything is extended into it. TPM PCRs 17 to
+/// 22 are reset to all ones at boot, while the others and TDX RTMRs start
+/// from zeros.
+fn initial_register_value(tee: Tee, register_index: u64, len: usize) -> Vec<u8> {
+    match tee {
+        Tee::AzSnpVtpm | Tee::AzTdxVtpm if (17..=22).contains(&register_index) => vec![0xff; len],
+        _ => vec![0; len],
+    }
+}
+
+/// Count the events that made it into the eventlog, also when the batch
+/// failed halfway, and the failure of the storage of the eventlog if any.
+fn record_logged_events<T>(
+    tee: Tee,
+    res: &std::result::Result<T, BatchExtendError>,
+    events: usize,
+) {
+    let logged = match res {
+        Ok(_) => events,
+        Err(e) => {
+            count_storage_failure(tee, &e.source);
+            e.logged
+        }
+    };
+    metrics::eventlog_entries_written(&tee_name(tee), logged);
+}
+
+/// Warn when the eventlog of the register crosses
+/// [`EVENTLOG_USAGE_WARNING`] of its limit, before it refuses entries with
+/// [`Error::EventLogFull`], or before the file is rotated.
+fn report_eventlog_usage(
+    tee: Tee,
+    register_index: u64,
+    before: Option<f64>,
+    register_log: &EventLog,
+) {
+    let (Some(before), Some(after)) = (before, register_log.usage()) else {
+        return;
+    };
+    if before < EVENTLOG_USAGE_WARNING && after >= EVENTLOG_USAGE_WARNING {
+        let once_full = if register_log.rotates() {
+            "the file is rotated"
+        } else {
+            "runtime measurements are refused"
+        };
+        warn!(
+            register_index,
+            "eventlog is {:.0}% full, {once_full} once it is full",
+            after * 100.0
+        );
+        metrics::eventlog_near_full(&tee_name(tee));
+    }
+}
+
+fn count_storage_failure(tee: Tee, source: &anyhow::Error) {
+    if let Some(e) = source.downcast_ref::<EventLogStorageError>() {
+        metrics::eventlog_storage_failed(&tee_name(tee), e.kind.label());
+    }
+}
+
+/// The error of a failed write of the eventlog, which is
+/// [`Error::EventLogStorage`] for a failure of the storage.
+fn eventlog_error(source: anyhow::Error) -> Error {
+    match source.downcast::<EventLogStorageError>() {
+        Ok(e) => Error::EventLogStorage(e),
+        Err(source) => Error::EventLogIo(source),
+    }
+}
+
+/// Like [`eventlog_error`], with a failure of the storage counted.
+fn eventlog_write_failed(tee: Tee, source: anyhow::Error) -> Error {
+    count_storage_failure(tee, &source);
+    eventlog_error(source)
+}
+
+/// Default PCR index used by AA for both the INIT entry and runtime
+/// measurements, unless configured otherwise. `17` is selected for its usage of dynamic root of trust for measurement.
+/// - [Linux TPM PCR Registry](https://uapi-group.org/specifications/specs/linux_tpm_pcr_registry/)
+/// - [TCG TRUSTED BOOT CHAIN IN EDK II](https://tianocore-docs.github.io/edk2-TrustedBootChain/release-1.00/3_TCG_Trusted_Boot_Chain_in_EDKII.html)
+const DEFAULT_PCR_INDEX: u64 = 17;
+
+/// Share of `eventlog_config.max_size_bytes` or `max_entries` at which AA
+/// warns that the eventlog is about to refuse runtime measurements.
+const EVENTLOG_USAGE_WARNING: f64 = 0.8;
+
+#[async_trait]
+impl AttestationAPIs for AttestationAgent {
+    async fn get_token(&self, token_type: &str) -> Result<Vec<u8>> {
+        self.get_token_with_params(token_type, serde_json::Value::Null)
+            .await
+    }
+
+    async fn get_token_with_params(
+        &self,
+        token_type: &str,
+        params: serde_json::Value,
+    ) -> Result<Vec<u8>> {
+        if !params.is_null() && !params.is_object() {
+            return Err(Error::InvalidArgument(
+                "token params must be a JSON object".into(),
+            ));
+        }
+
+        let token = self
+            .get_token_with_cache(token_type, params, None, false)
+            .await?;
+        token_bytes(token_type, token)
+    }
+
+    async fn get_token_with_nonce(
+        &self,
+        token_type: &str,
+        nonce: Option<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        let token = self
+            .get_token_with_cache(token_type, serde_json::Value::Null, nonce, false)
+            .await?;
+        token_bytes(token_type, token)
+    }
+
+    async fn refresh_token(&self, token_type: &str) -> Result<Vec<u8>> {
+        let token = self
+            .get_token_with_cache(token_type, serde_json::Value::Null, None, true)
+            .await?;
+        token_bytes(token_type, token)
+    }
+
+    async fn get_token_ex(&self, token_type: &str) -> Result<TokenResponse> {
+        self.get_token_with_cache(token_type, serde_json::Value::Null, None, false)
+            .await
+    }
+
+    /// Get TEE hardware signed evidence that includes the runtime data.
+    async fn get_evidence(&self, runtime_data: &[u8]) -> Result<Vec<u8>> {
+        let evidence = self.get_evidence_ex(runtime_data).await?;
+        Ok(evidence.into_bytes())
+    }
+
+    /// Get TEE hardware signed evidence that includes the runtime data,
+    /// together with the TEE type that produced it.
+    async fn get_evidence_ex(&self, runtime_data: &[u8]) -> Result<Evidence> {
+        self.get_evidence_with(RuntimeData::Raw(runtime_data.to_vec()))
+            .await
+    }
+
+    /// Get TEE hardware signed evidence that includes the given raw or
+    /// structured runtime data.
+    #[instrument(name = "get_evidence", skip_all, fields(tee = ?self.attester.current_tee()))]
+    async fn get_evidence_with(&self, runtime_data: RuntimeData) -> Result<Evidence> {
+        let _elapsed = Elapsed::start();
+        self.ensure_open()?;
+        let (evidence_config, timeout) = {
+            let config = self.config.read().await;
+            (
+                config.evidence_config.clone(),
+                config.attester.evidence_timeout_seconds,
+            )
+        };
+        let attester = self.attester.get()?;
+        let report_data = runtime_data.clone().into_report_data(
+            evidence_config.runtime_data_algorithm,
+            attester.report_data_len(),
+            evidence_config.hash_oversized_runtime_data,
+        )?;
+        let endpoint = format!("{} attester", self.tee_type_name());
+        let timer = metrics::Timer::start();
+        let evidence = with_timeout(timeout, "get evidence", &endpoint, async {
+            attester
+                .get_evidence(report_data)
+                .await
+                .map_err(|e| Error::attester(self.tee_type(), "get evidence", e))
+        })
+        .await?;
+        metrics::evidence_generated(&self.tee_type_name(), timer.elapsed());
+
+        let evidence = match evidence_config.evidence_mode {
+            EvidenceMode::Primary => return Ok(Evidence::new(self.tee_type_name(), evidence)),
+            EvidenceMode::Composite => {
+                let mut composite = CompositeEvidence::new(self.tee_type_name(), evidence);
+                let secondary_attesters = self.secondary_attesters.get_or_init(|| {
+                    create_secondary_attesters(self.tee_type(), self.attester.options())
+                });
+                for (tee, attester) in secondary_attesters {
+                    let entry = match get_secondary_evidence(
+                        *tee,
+                        attester,
+                        runtime_data.clone(),
+                        &evidence_config,
+                        timeout,
+                    )
+                    .await
+                    {
+                        Ok(evidence) => CompositeEntry::Evidence(evidence),
+                        Err(e) => {
+                            warn!(
+                                target: telemetry::EVIDENCE,
+                                ?tee,
+                                error = ?e,
+                                "Get evidence from secondary attester failed"
+                            );
+                            CompositeEntry::Error(format!("{e:#}"))
+                        }
+                    };
+                    composite.evidence.insert(tee_name(*tee), entry);
+                }
+
+                serde_json::to_vec(&composite)?
+            }
+        };
+
+        Ok(Evidence::new(COMPOSITE_EVIDENCE_TEE.to_string(), evidence))
+    }
+
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee()))]
+    async fn get_evidence_with_eventlog(
+        &self,
+        runtime_data: &[u8],
+    ) -> Result<EvidenceWithEventLog> {
+        let _elapsed = Elapsed::start();
+        // Extending runtime measurements takes the lock too, s.t. holding it
+        // over the attester call keeps the eventlog and the registers in sync
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        let evidence = self
+            .get_evidence_with(RuntimeData::Raw(runtime_data.to_vec()))
+            .await?;
+        let register_eventlogs = eventlog
+            .iter()
+            .filter(|(register_index, _)| *register_index != eventlog.init_pcr())
+            .map(|(register_index, register_log)| Ok((register_index, register_log.read_log(0)?)))
+            .collect::<anyhow::Result<_>>()
+            .map_err(Error::EventLogIo)?;
+        let eventlog = eventlog.init_log().read_log(0).map_err(Error::EventLogIo)?;
+
+        Ok(EvidenceWithEventLog {
+            evidence,
+            eventlog,
+            register_eventlogs,
+        })
+    }
+
+    /// Get evidence of the devices attached to the TEE that includes the
+    /// runtime data.
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee()))]
+    async fn get_additional_evidence(&self, runtime_data: &[u8]) -> Result<Vec<u8>> {
+        let _elapsed = Elapsed::start();
+        self.ensure_open()?;
+        collect_device_evidence(&self.device_attesters, runtime_data).await
+    }
+
+    /// Derive a key bound to the TEE. Platforms without key derivation
+    /// capability return [`Error::Unsupported`].
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee(), length = length as u64))]
+    async fn get_derived_key(&self, context: &[u8], length: usize) -> Result<Vec<u8>> {
+        let _elapsed = Elapsed::start();
+        self.ensure_open()?;
+        if length == 0 {
+            return Err(Error::InvalidArgument(
+                "derived key length must not be zero".into(),
+            ));
+        }
+
+        self.attester
+            .get()?
+            .get_derived_key(context, length)
+            .await
+            .map_err(|e| Error::attester(self.tee_type(), "derive key", e))
+    }
+
+    /// Extend runtime measurement register. Parameters
+    /// - `events`: a event slice. Any single event will be calculated into a hash digest to extend the current
+    /// platform's RTMR.
+    /// - `register_index`: a target PCR that will be used to extend RTMR. Note that different platform
+    /// would have its own strategy to map a PCR index into a architectual RTMR index. If not given,
+    /// `eventlog_config.default_register_index` will be used.
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee(), domain = domain, operation = operation, register_index = ?register_index, register = tracing::field::Empty))]
+    async fn extend_runtime_measurement(
+        &self,
+        domain: &str,
+        operation: &str,
+        content: &str,
+        register_index: Option<u64>,
+    ) -> Result<()> {
+        let _elapsed = Elapsed::start();
+        let log_entry = EventEntry::new(domain, operation, content)
+            .map_err(|source| Error::InvalidEvent { index: 0, source })?;
+        self.extend_entry(log_entry, domain, register_index, false)
+            .await?;
+        Ok(())
+    }
+
+    #[instrument(name = "extend_runtime_measurement", skip_all, fields(tee = ?self.attester.current_tee(), domain = domain, operation = operation, register_index = ?register_index, register = tracing::field::Empty))]
+    async fn extend_runtime_measurement_ex(
+        &self,
+        domain: &str,
+        operation: &str,
+        content: &str,
+        register_index: Option<u64>,
+    ) -> Result<ExtendResult> {
+        let _elapsed = Elapsed::start();
+        let log_entry = EventEntry::new(domain, operation, content)
+            .map_err(|source| Error::InvalidEvent { index: 0, source })?;
+        self.extend_entry(log_entry, domain, register_index, true)
+            .await
+    }
+
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee(), domain = domain, operation = operation, register_index = ?register_index, register = tracing::field::Empty))]
+    async fn extend_runtime_measurement_binary(
+        &self,
+        domain: &str,
+        operation: &str,
+        content: &[u8],
+        register_index: Option<u64>,
+    ) -> Result<ExtendResult> {
+        let _elapsed = Elapsed::start();
+        let log_entry = EventEntry::new_binary(domain, operation, content)
+            .map_err(|source| Error::InvalidEvent { index: 0, source })?;
+        self.extend_entry(log_entry, domain, register_index, true)
+            .await
+    }
+
+    async fn extend_runtime_measurement_batch(
+        &self,
+        events: &[(&str, &str, &str)],
+        register_index: Option<u64>,
+    ) -> Result<()> {
+        self.extend_batch(events, register_index, false).await?;
+        Ok(())
+    }
+
+    async fn extend_runtime_measurement_batch_ex(
+        &self,
+        events: &[(&str, &str, &str)],
+        register_index: Option<u64>,
+    ) -> Result<Vec<ExtendResult>> {
+        self.extend_batch(events, register_index, true).await
+    }
+
+    async fn get_event_log(&self, start_index: usize) -> Result<Vec<String>> {
+        self.get_register_event_log(start_index, None).await
+    }
+
+    #[instrument(name = "get_event_log", skip_all, fields(start_index = start_index as u64, register_index = ?register_index))]
+    async fn get_register_event_log(
+        &self,
+        start_index: usize,
+        register_index: Option<u64>,
+    ) -> Result<Vec<String>> {
+        let _elapsed = Elapsed::start();
+        // Hold the lock s.t. no entry is being extended while reading
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        if !eventlog.is_enabled() {
+            return Ok(Vec::new());
+        }
+        ensure_initialized(eventlog.init_log())?;
+        eventlog
+            .read_log(register_index, start_index)
+            .map_err(Error::EventLogIo)
+    }
+
+    #[instrument(skip_all, fields(start_index = start_index as u64, register_index = ?register_index))]
+    async fn get_event_log_json(
+        &self,
+        start_index: usize,
+        register_index: Option<u64>,
+    ) -> Result<String> {
+        let _elapsed = Elapsed::start();
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        if !eventlog.is_enabled() {
+            return Ok("[]".into());
+        }
+        ensure_initialized(eventlog.init_log())?;
+        let records = eventlog
+            .records(register_index)
+            .map_err(Error::EventLogIo)?;
+        let records: Vec<EventRecord> = records.into_iter().skip(start_index).collect();
+        Ok(serde_json::to_string(&records)?)
+    }
+
+    #[instrument(skip_all, fields(filter = ?filter))]
+    async fn query_event_log(&self, filter: EventFilter) -> Result<Vec<EventRecord>> {
+        let _elapsed = Elapsed::start();
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        if !eventlog.is_enabled() {
+            return Ok(Vec::new());
+        }
+        ensure_initialized(eventlog.init_log())?;
+        eventlog.query(&filter).map_err(Error::EventLogIo)
+    }
+
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee()))]
+    async fn get_combined_event_log(&self) -> Result<String> {
+        let _elapsed = Elapsed::start();
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        let attester = self.attester.get()?;
+        let firmware = attester
+            .get_firmware_eventlog()
+            .await
+            .map_err(|e| Error::attester(self.tee_type(), "get firmware eventlog", e))?
+            .map(|log| eventlog::firmware::parse(&log))
+            .transpose()
+            .map_err(Error::EventLogIo)?
+            .unwrap_or_default();
+
+        let mut aael = Vec::new();
+        if eventlog.is_enabled() {
+            ensure_initialized(eventlog.init_log())?;
+            for (register_index, register_log) in eventlog.iter() {
+                let records = register_log.records().map_err(Error::EventLogIo)?;
+                aael.push((register_index, records));
+            }
+        }
+
+        let combined = CombinedEventLog::new(firmware, aael, |register_index| {
+            attester.firmware_register_index(register_index)
+        });
+        Ok(serde_json::to_string(&combined)?)
+    }
+
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee(), register_index = register_index, algorithm = %algorithm))]
+    async fn get_runtime_measurement(
+        &self,
+        register_index: u64,
+        algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        let _elapsed = Elapsed::start();
+        self.ensure_open()?;
+        let attester = self.attester.get()?;
+        check_register_index(attester, self.tee_type(), register_index)?;
+        attester
+            .get_runtime_measurement(register_index, algorithm)
+            .await
+            .map_err(|e| Error::attester(self.tee_type(), "read runtime measurement", e))
+    }
+
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee()))]
+    async fn verify_event_log(&self) -> Result<()> {
+        let _elapsed = Elapsed::start();
+
+        // Hold the lock s.t. no entry is extended between reading the
+        // eventlogs and the registers
+        let eventlog = self.eventlog.lock().await;
+        self.ensure_open()?;
+        ensure_initialized(eventlog.init_log())?;
+        for (register_index, register_log) in eventlog.iter() {
+            let entries = register_log
+                .read_algorithm_entries()
+                .map_err(Error::EventLogIo)?;
+            // Each algorithm the entries are extended with is a bank of
+            // the register
+            for &algorithm in register_log.algorithms() {
+                let current = self
+                    .get_runtime_measurement(register_index, algorithm)
+                    .await?;
+
+                let initial =
+                    initial_register_value(self.tee_type(), register_index, current.len());
+                if eventlog::replay_bank(&entries, algorithm, initial.clone()) != current {
+                    let entry =
+                        eventlog::diverging_bank_entry(&entries, algorithm, initial, &current);
+                    return Err(Error::EventLogMismatch {
+                        register_index,
+                        entry,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the initdata binding. If current platform does not support initdata
+    /// injection, return `InitdataResult::Unsupported`.
+    async fn check_init_data(&self, init_data: &[u8]) -> Result<InitdataResult> {
+        self.check_init_data_with(InitData::Digest(init_data.to_vec()))
+            .await
+    }
+
+    #[instrument(skip_all, fields(tee = ?self.attester.current_tee()))]
+    async fn check_init_data_with(&self, init_data: InitData) -> Result<InitdataResult> {
+        let _elapsed = Elapsed::start();
+        self.ensure_open()?;
+        let attester = self.attester.get()?;
+        let tee = self.tee_type();
+        let digest = match init_data {
+            InitData::Digest(digest) => digest,
+            document => {
+                let Some(host_data_len) = attester.init_data_len() else {
+                    return Ok(InitdataResult::Unsupported);
+                };
+                let algorithm = self.config.read().await.initdata.algorithm;
+                document.into_digest(algorithm, tee, host_data_len)?
+            }
+        };
+
+        attester
+            .check_init_data(&digest)
+            .await
+            .map_err(|e| Error::attester(tee, "check init data", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        ops::RangeInclusive,
+        os::unix::fs::PermissionsExt,
+        path::Path,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use anyhow::{bail, Result};
+    use attester::{
+        device::{BoxedDeviceAttester, DeviceAttester},
+        Attester, BoxedAttester,
+    };
+    use kbs_types::Tee;
+    use serde_json::{json, Value};
+
+    use crate::{
+        config::{
+            EventlogBackend, EventlogConfig, EventlogFormat, EventlogRecovery, EventlogSync,
+            HashAlgorithm,
+        },
+        eventlog::{
+            self,
+            combined::CombinedEventLog,
+            writer::{EventLogWriter, LineLocation},
+            EventEntry, EventLog, EventLogFull, EventLogSnapshot, EventLogStorageError,
+            RegisterLogs, StorageErrorKind,
+        },
+        evidence::CompositeEvidence,
+        lazy_attester::LazyAttester,
+    };
+
+    use super::{
+        check_register_bank, check_register_index, collect_device_evidence, eventlog_error,
+        extend_events, open_register_log, with_timeout, AttestationAPIs, AttestationAgent,
+        BatchExtendError, Config, Error, EventFilter, Evidence, EvidenceWithEventLog, InitData,
+        InitdataResult, RuntimeData, TokenGetter, TokenResponse, TokenType,
+    };
+
+    /// Extends sha384 registers like TDX RTMRs, and fails after
+    /// `max_extends` extensions.
+    struct MockAttester {
+        registers: Mutex<HashMap<u64, Vec<u8>>>,
+        remaining: Mutex<usize>,
+    }
+
+    impl MockAttester {
+        fn new(max_extends: usize) -> Self {
+            Self {
+                registers: Mutex::default(),
+                remaining: Mutex::new(max_extends),
+            }
+        }
+
+        fn register(&self, register_index: u64) -> Vec<u8> {
+            let registers = self.registers.lock().unwrap();
+            registers
+                .get(&register_index)
+                .cloned()
+                .unwrap_or_else(|| vec![0; 48])
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Attester for MockAttester {
+        async fn get_evidence(&self, _report_data: Vec<u8>) -> Result<String> {
+            Ok(String::new())
+        }
+
+        fn runtime_measurement_registers(&self) -> Option<RangeInclusive<u64>> {
+            Some(8..=23)
+        }
+
+        async fn extend_runtime_measurement(
+            &self,
+            event_digest: Vec<u8>,
+            register_index: u64,
+        ) -> Result<()> {
+            let mut remaining = self.remaining.lock().unwrap();
+            if *remaining == 0 {
+                bail!("register is locked");
+            }
+            *remaining -= 1;
+
+            let register = self.register(register_index);
+            let extended = HashAlgorithm::Sha384.digest(&[register, event_digest].concat());
+            self.registers
+                .lock()
+                .unwrap()
+                .insert(register_index, extended);
+            Ok(())
+        }
+
+        async fn get_runtime_measurement(
+            &self,
+            register_index: u64,
+            _algorithm: HashAlgorithm,
+        ) -> Result<Vec<u8>> {
+            Ok(self.register(register_index))
+        }
+    }
+
+    struct MockDeviceAttester;
+
+    #[async_trait::async_trait]
+    impl DeviceAttester for MockDeviceAttester {
+        async fn get_evidence(&self, report_data: Vec<u8>) -> Result<Value> {
+            Ok(json!({ "report_data": report_data }))
+        }
+    }
+
+    #[test]
+    fn new_with_mismatched_tee() {
+        // A TEE that the host of the tests does not have
+        let tee = [
+            Tee::Se,
+            Tee::Cca,
+            Tee::Csv,
+            Tee::Sgx,
+            Tee::Snp,
+            Tee::Tdx,
+            Tee::AzSnpVtpm,
+            Tee::AzTdxVtpm,
+        ]
+        .into_iter()
+        .find(|tee| !attester::tee_available(*tee))
+        .expect("every TEE is available on the host");
+
+        let Err(e) = AttestationAgent::new_with_tee(None, tee) else {
+            panic!("creating AA with a mismatched TEE type should fail");
+        };
+        assert!(matches!(e, Error::TeeUnavailable(unavailable) if unavailable == tee));
+        assert!(e.to_string().contains(&format!("{tee:?}")), "{e}");
+    }
+
+    #[rstest::rstest]
+    #[case(Tee::Sample, "sample")]
+    #[case(Tee::Tdx, "tdx")]
+    #[case(Tee::AzSnpVtpm, "azsnpvtpm")]
+    fn tee_name(#[case] tee: Tee, #[case] name: &str) {
+        assert_eq!(super::tee_name(tee), name);
+    }
+
+    #[tokio::test]
+    async fn additional_evidence() {
+        let evidence = collect_device_evidence(&[], b"runtime data").await.unwrap();
+        assert_eq!(evidence, b"{}");
+
+        let device_attesters: Vec<(String, BoxedDeviceAttester)> =
+            vec![("mock_gpu".into(), Box::new(MockDeviceAttester))];
+        let evidence = collect_device_evidence(&device_attesters, &[1, 2])
+            .await
+            .unwrap();
+        let evidence: Value = serde_json::from_slice(&evidence).unwrap();
+        assert_eq!(evidence, json!({ "mock_gpu": { "report_data": [1, 2] } }));
+    }
+
+    #[test]
+    fn default_register_index_is_independent_of_init_pcr() {
+        let config = |eventlog_config: &str| {
+            let conf = format!(
+                r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+{eventlog_config}"#
+            );
+            Config::from_toml_str(&conf).unwrap().eventlog_config
+        };
+
+        let eventlog_config = config("[eventlog_config]\ninit_pcr = 16\n");
+        assert_eq!(eventlog_config.init_pcr, 16);
+        assert_eq!(
+            super::resolve_register_index(None, "domain", &eventlog_config),
+            17
+        );
+
+        let eventlog_config = config("[eventlog_config]\ndefault_register_index = 23\n");
+        assert_eq!(eventlog_config.init_pcr, 17);
+        assert_eq!(
+            super::resolve_register_index(None, "domain", &eventlog_config),
+            23
+        );
+        assert_eq!(
+            super::resolve_register_index(Some(11), "domain", &eventlog_config),
+            11
+        );
+    }
+
+    #[test]
+    fn domain_pcr_map() {
+        let conf = r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+default_register_index = 17
+
+[eventlog_config.domain_pcr_map]
+"github.com/confidential-containers" = 16
+"example.com/app" = 18
+"#;
+        let eventlog_config = Config::from_toml_str(conf).unwrap().eventlog_config;
+        let resolve = |register_index, domain| {
+            super::resolve_register_index(register_index, domain, &eventlog_config)
+        };
+        assert_eq!(resolve(None, "github.com/confidential-containers"), 16);
+        assert_eq!(resolve(None, "example.com/app"), 18);
+        assert_eq!(resolve(None, "Example.com/App"), 18);
+        assert_eq!(resolve(None, "example.com/other"), 17);
+        assert_eq!(resolve(Some(11), "example.com/app"), 11);
+
+        let batch = |register_index, domains: &[&'static str]| {
+            super::resolve_batch_register_index(
+                register_index,
+                domains.iter().copied(),
+                &eventlog_config,
+            )
+        };
+        assert_eq!(
+            batch(None, &["example.com/app", "example.com/app"]).unwrap(),
+            18
+        );
+        assert_eq!(batch(None, &[]).unwrap(), 17);
+        assert_eq!(
+            batch(Some(11), &["example.com/app", "example.com/other"]).unwrap(),
+            11
+        );
+        assert!(matches!(
+            batch(None, &["example.com/app", "example.com/other"]),
+            Err(Error::InvalidArgument(_))
+        ));
+
+        // The dots of the domains are no key separators when merging
+        let merged = Config::from_toml_str(conf)
+            .unwrap()
+            .merge_toml_str("[eventlog_config]\ninit_pcr = 16\n")
+            .unwrap();
+        assert_eq!(
+            merged.eventlog_config.domain_pcr_map,
+            eventlog_config.domain_pcr_map
+        );
+
+        // The keys of a config built in code may be of mixed case
+        let mut eventlog_config = eventlog_config.clone();
+        eventlog_config
+            .domain_pcr_map
+            .insert("Example.org/Mixed".into(), 19);
+        assert_eq!(
+            super::resolve_register_index(None, "example.org/mixed", &eventlog_config),
+            19
+        );
+    }
+
+    #[test]
+    fn unknown_device_attester() {
+        assert!(super::create_device_attesters(&["foo_gpu".into()]).is_err());
+        assert!(super::create_device_attesters(&[]).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn extend_batch_replays_to_register() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut eventlog = EventLog::create(&path, EventlogSync::Never).unwrap();
+        let mock = MockAttester::new(usize::MAX);
+        let register = mock.register(17);
+        let attester: BoxedAttester = Box::new(mock);
+
+        let events = [
+            ("github.com/confidential-containers", "PullImage", "busybox"),
+            (
+                "github.com/confidential-containers",
+                "CreateContainer",
+                "c1",
+            ),
+            ("github.com/confidential-containers", "StartContainer", "c1"),
+        ]
+        .map(|(domain, operation, content)| EventEntry::new(domain, operation, content).unwrap());
+        extend_events(
+            &attester,
+            &mut eventlog,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        let replayed = log.lines().skip(1).fold(register, |register, line| {
+            let event = EventEntry::parse(line).unwrap();
+            let digest = event.digest_with(HashAlgorithm::Sha384);
+            HashAlgorithm::Sha384.digest(&[register, digest].concat())
+        });
+
+        let expected = HashAlgorithm::Sha384
+            .digest(&[vec![0; 48], events[0].digest_with(HashAlgorithm::Sha384)].concat());
+        let expected = HashAlgorithm::Sha384
+            .digest(&[expected, events[1].digest_with(HashAlgorithm::Sha384)].concat());
+        let expected = HashAlgorithm::Sha384
+            .digest(&[expected, events[2].digest_with(HashAlgorithm::Sha384)].concat());
+        assert_eq!(log.lines().count(), 4);
+        assert_eq!(replayed, expected);
+
+        let entries = eventlog.read_log(0).unwrap();
+        let current = attester
+            .get_runtime_measurement(17, HashAlgorithm::Sha384)
+            .await
+            .unwrap();
+        assert_eq!(
+            eventlog::replay(&entries, HashAlgorithm::Sha384, vec![0; 48]),
+            current
+        );
+        assert_eq!(super::initial_register_value(Tee::Tdx, 17, 48), vec![0; 48]);
+        assert_eq!(
+            super::initial_register_value(Tee::AzSnpVtpm, 17, 32),
+            vec![0xff; 32]
+        );
+    }
+
+    #[tokio::test]
+    async fn extend_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut eventlog =
+            EventLog::create(&dir.path().join("eventlog"), EventlogSync::Never).unwrap();
+        let attester: BoxedAttester = Box::new(MockAttester::new(usize::MAX));
+
+        let events =
+            ["c1", "c2"].map(|content| EventEntry::new("domain", "operation", content).unwrap());
+        let results = extend_events(
+            &attester,
+            &mut eventlog,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            true,
+        )
+        .await
+        .unwrap();
+        assert_eq!(results.len(), 2);
+        for (result, event) in results.iter().zip(&events) {
+            assert_eq!(result.register_index, 17);
+            assert_eq!(result.algorithm, HashAlgorithm::Sha384);
+            assert_eq!(result.digest, event.digest_with(HashAlgorithm::Sha384));
+        }
+        let register = attester
+            .get_runtime_measurement(17, HashAlgorithm::Sha384)
+            .await
+            .unwrap();
+        assert_eq!(results[0].register_value, None);
+        assert_eq!(results[1].register_value, Some(register));
+        assert_eq!(
+            eventlog.records().unwrap()[1].digest,
+            Some(hex::encode(&results[1].digest))
+        );
+
+        // The register is only read for the callers that return it
+        let results = extend_events(
+            &attester,
+            &mut eventlog,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            false,
+        )
+        .await
+        .unwrap();
+        assert!(results.iter().all(|result| result.register_value.is_none()));
+    }
+
+    #[tokio::test]
+    async fn restart_continues_eventlog() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            ..Default::default()
+        };
+        let attester: BoxedAttester = Box::new(MockAttester::new(usize::MAX));
+
+        let init = format!("INIT sha384/{}", "0".repeat(96));
+        let digest = HashAlgorithm::Sha384.digest(init.as_bytes());
+        attester
+            .extend_runtime_measurement(digest.clone(), 17)
+            .await
+            .unwrap();
+        EventLog::new(&config, vec![0; 48])
+            .unwrap()
+            .write_init_log(&init, 17, &digest)
+            .unwrap();
+
+        // AA restarts before each runtime measurement, and the register
+        // keeps what was extended before
+        for content in ["c1", "c2"] {
+            let mut eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+            assert!(eventlog.is_initialized());
+            let events = [EventEntry::new("domain", "operation", content).unwrap()];
+            extend_events(
+                &attester,
+                &mut eventlog,
+                HashAlgorithm::Sha384,
+                &events,
+                17,
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let eventlog = EventLog::new(&config, vec![0; 48]).unwrap();
+        assert_eq!(
+            eventlog.read_entries().unwrap(),
+            vec![init.as_str(), "domain operation c1", "domain operation c2"]
+        );
+        let current = attester
+            .get_runtime_measurement(17, HashAlgorithm::Sha384)
+            .await
+            .unwrap();
+        assert_eq!(
+            eventlog.replay(HashAlgorithm::Sha384, vec![0; 48]).unwrap(),
+            current
+        );
+    }
+
+    #[tokio::test]
+    async fn interleaved_registers_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            ..Default::default()
+        };
+        let attester: BoxedAttester = Box::new(MockAttester::new(usize::MAX));
+        let mut eventlogs = RegisterLogs::new(&config, |_| vec![0; 48]).unwrap();
+
+        let contents = ["c1", "c2", "c3", "c4", "c5"];
+        for (content, register_index) in contents.iter().zip([17, 18].into_iter().cycle()) {
+            let register_log = open_register_log(
+                &attester,
+                &attester.capabilities(),
+                Tee::Tdx,
+                &mut eventlogs,
+                &config,
+                register_index,
+            )
+            .await
+            .unwrap();
+            let events = [EventEntry::new("domain", "operation", content).unwrap()];
+            extend_events(
+                &attester,
+                register_log,
+                HashAlgorithm::Sha384,
+                &events,
+                register_index,
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let init = format!("INIT sha384/{}", "0".repeat(96));
+        for (register_index, expected) in [
+            (
+                17,
+                vec![
+                    init.as_str(),
+                    "domain operation c1",
+                    "domain operation c3",
+                    "domain operation c5",
+                ],
+            ),
+            (
+                18,
+                vec![init.as_str(), "domain operation c2", "domain operation c4"],
+            ),
+        ] {
+            let entries = eventlogs.read_log(Some(register_index), 0).unwrap();
+            assert_eq!(entries, expected);
+            let current = attester
+                .get_runtime_measurement(register_index, HashAlgorithm::Sha384)
+                .await
+                .unwrap();
+            assert_eq!(
+                eventlog::replay(&entries, HashAlgorithm::Sha384, vec![0; 48]),
+                current
+            );
+        }
+        assert_eq!(eventlogs.read_log(None, 0).unwrap().len(), 7);
+
+        // The eventlog of each register is found again on restart, and no
+        // INIT entry is extended twice
+        drop(eventlogs);
+        let mut eventlogs = RegisterLogs::new(&config, |_| vec![0; 48]).unwrap();
+        let register_log = open_register_log(
+            &attester,
+            &attester.capabilities(),
+            Tee::Tdx,
+            &mut eventlogs,
+            &config,
+            18,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            register_log
+                .replay(HashAlgorithm::Sha384, vec![0; 48])
+                .unwrap(),
+            attester
+                .get_runtime_measurement(18, HashAlgorithm::Sha384)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn snapshot_while_appending() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            ..Default::default()
+        };
+        let attester: Arc<BoxedAttester> = Arc::new(Box::new(MockAttester::new(usize::MAX)));
+        let eventlogs = Arc::new(tokio::sync::Mutex::new(
+            RegisterLogs::new(&config, |_| vec![0; 48]).unwrap(),
+        ));
+
+        let writer = tokio::spawn({
+            let (attester, eventlogs, config) =
+                (attester.clone(), eventlogs.clone(), config.clone());
+            async move {
+                for i in 0..200 {
+                    let register_index = 17 + i % 2;
+                    let content = format!("event-{i}");
+                    let events = [EventEntry::new("domain", "operation", &content).unwrap()];
+                    let mut eventlogs = eventlogs.lock().await;
+                    let register_log = open_register_log(
+                        &attester,
+                        &attester.capabilities(),
+                        Tee::Tdx,
+                        &mut eventlogs,
+                        &config,
+                        register_index,
+                    )
+                    .await
+                    .unwrap();
+                    extend_events(
+                        &attester,
+                        register_log,
+                        HashAlgorithm::Sha384,
+                        &events,
+                        register_index,
+                        false,
+                    )
+                    .await
+                    .unwrap();
+                }
+            }
+        });
+
+        // Each snapshot replays to the registers as they were when it was
+        // taken, also the last one once the writer is done
+        let dest = dir.path().join("snapshot");
+        loop {
+            let done = writer.is_finished();
+            let eventlogs = eventlogs.lock().await;
+            let EventLogSnapshot::Files(files) = eventlogs.snapshot(&dest).await.unwrap() else {
+                panic!("the eventlog is kept in files");
+            };
+            for file in &files {
+                let log = aael::parse_str(&std::fs::read_to_string(file).unwrap()).unwrap();
+                let register_index = log.header.as_ref().unwrap().init_pcr;
+                assert_eq!(
+                    log.replay(HashAlgorithm::Sha384, vec![0; 48]),
+                    attester
+                        .get_runtime_measurement(register_index, HashAlgorithm::Sha384)
+                        .await
+                        .unwrap()
+                );
+            }
+            if done {
+                assert_eq!(files, vec![dest.clone(), dir.path().join("snapshot.pcr18")]);
+                break;
+            }
+        }
+        writer.await.unwrap();
+        for (copy, eventlog) in [
+            ("snapshot", "eventlog"),
+            ("snapshot.pcr18", "eventlog.pcr18"),
+        ] {
+            assert_eq!(
+                std::fs::read(dir.path().join(copy)).unwrap(),
+                std::fs::read(dir.path().join(eventlog)).unwrap()
+            );
+        }
+        assert!(!dir.path().join("snapshot.tmp").exists());
+        let mode = std::fs::metadata(&dest).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Neither the eventlogs nor the files rotated from them are
+        // replaced, however they are named
+        std::os::unix::fs::symlink(&config.file_path, dir.path().join("link")).unwrap();
+        for dest in [
+            config.file_path.clone(),
+            dir.path().join(".").join("eventlog"),
+            dir.path().join("link"),
+            dir.path().join("eventlog.pcr18"),
+            dir.path().join("eventlog.1"),
+        ] {
+            let e = eventlogs.lock().await.snapshot(&dest).await.unwrap_err();
+            assert!(e.to_string().contains("would replace the eventlog"), "{e}");
+        }
+    }
+
+    #[tokio::test]
+    async fn snapshot_memory_eventlog() {
+        let config = EventlogConfig {
+            backend: EventlogBackend::Memory,
+            ..Default::default()
+        };
+        let attester: BoxedAttester = Box::new(MockAttester::new(usize::MAX));
+        let mut eventlogs = RegisterLogs::new(&config, |_| vec![0; 48]).unwrap();
+        assert_eq!(eventlogs.path(), None);
+        let register_log = open_register_log(
+            &attester,
+            &attester.capabilities(),
+            Tee::Tdx,
+            &mut eventlogs,
+            &config,
+            17,
+        )
+        .await
+        .unwrap();
+        let events = [EventEntry::new("domain", "operation", "content").unwrap()];
+        extend_events(
+            &attester,
+            register_log,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            false,
+        )
+        .await
+        .unwrap();
+
+        let snapshot = eventlogs.snapshot(Path::new("/no/such/dir")).await.unwrap();
+        let EventLogSnapshot::Content(content) = snapshot else {
+            panic!("the eventlog is not kept in files");
+        };
+        let log = aael::parse_str(&content).unwrap();
+        assert_eq!(
+            log.entries(),
+            vec![
+                format!("INIT sha384/{}", "0".repeat(96)),
+                "domain operation content".to_string()
+            ]
+        );
+    }
+
+    fn mock_attester(_: Tee, _: &attester::AttesterOptions) -> crate::error::Result<BoxedAttester> {
+        Ok(Box::new(MockAttester::new(usize::MAX)))
+    }
+
+    /// AA of TDX with a [`MockAttester`] and the eventlog config.
+    fn mock_agent(eventlog_config: EventlogConfig) -> AttestationAgent {
+        let mut config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+"#,
+        )
+        .unwrap();
+        config.eventlog_config = eventlog_config;
+        let attester =
+            LazyAttester::with_constructor(Tee::Tdx, false, Default::default(), mock_attester);
+        AttestationAgent::with_attester(config, Tee::Tdx, attester).unwrap()
+    }
+
+    #[tokio::test]
+    async fn full_eventlog_keeps_register() {
+        let dir = tempfile::tempdir().unwrap();
+        let aa = mock_agent(EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            init_pcr: 17,
+            max_entries: Some(3),
+            ..Default::default()
+        });
+        aa.init().await.unwrap();
+        for content in ["c1", "c2"] {
+            aa.extend_runtime_measurement("domain", "operation", content, Some(17))
+                .await
+                .unwrap();
+        }
+        let full = aa
+            .get_runtime_measurement(17, HashAlgorithm::Sha384)
+            .await
+            .unwrap();
+
+        // The entry is refused before the register is extended, s.t. the
+        // eventlog still replays to it
+        let e = aa
+            .extend_runtime_measurement("domain", "operation", "c3", Some(17))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            Error::EventLogFull(EventLogFull::Entries { max_entries: 3 })
+        ));
+        assert_eq!(
+            aa.get_runtime_measurement(17, HashAlgorithm::Sha384)
+                .await
+                .unwrap(),
+            full
+        );
+        let entries = aa.get_register_event_log(0, Some(17)).await.unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            eventlog::replay(&entries, HashAlgorithm::Sha384, vec![0; 48]),
+            full
+        );
+    }
+
+    #[tokio::test]
+    async fn algorithm_change_needs_both_banks() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = EventlogConfig {
+            file_path: dir.path().join("eventlog"),
+            eventlog_algorithm: HashAlgorithm::Sha256,
+            ..Default::default()
+        };
+        let attester: BoxedAttester = Box::new(MockAttester::new(usize::MAX));
+        let mut eventlogs = RegisterLogs::new(&config, |_| vec![0; 32]).unwrap();
+        open_register_log(
+            &attester,
+            &attester.capabilities(),
+            Tee::Tdx,
+            &mut eventlogs,
+            &config,
+            17,
+        )
+        .await
+        .unwrap();
+        drop(eventlogs);
+
+        // The attester only has the bank of the configured algorithm
+        config.eventlog_algorithm = HashAlgorithm::Sha384;
+        let mut eventlogs = RegisterLogs::new(&config, |_| vec![0; 48]).unwrap();
+        let err = open_register_log(
+            &attester,
+            &attester.capabilities(),
+            Tee::Tdx,
+            &mut eventlogs,
+            &config,
+            17,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::EventLogAlgorithmMix {
+                register_index: 17,
+                recorded: HashAlgorithm::Sha256,
+                algorithm: HashAlgorithm::Sha384,
+                ..
+            }
+        ));
+    }
+
+    #[tokio::test]
+    async fn extend_batch_reports_applied_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let mut eventlog = EventLog::create(&path, EventlogSync::Never).unwrap();
+        let attester: BoxedAttester = Box::new(MockAttester::new(1));
+
+        let events = [
+            EventEntry::new("domain", "operation", "first").unwrap(),
+            EventEntry::new("domain", "operation", "second").unwrap(),
+        ];
+        let e = extend_events(
+            &attester,
+            &mut eventlog,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            e,
+            BatchExtendError {
+                extended: 1,
+                logged: 1,
+                ..
+            }
+        ));
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "AAEL/1.0 algorithm=sha384 init_pcr=17\ndomain operation first\n"
+        );
+    }
+
+    /// Keeps the lines in memory, but its storage is full for the first
+    /// `full_flushes` flushes, which keep the lines appended since the last
+    /// flush pending until they are truncated.
+    #[derive(Default)]
+    struct FullStorageWriter {
+        stored: Arc<Mutex<Vec<String>>>,
+        pending: Vec<String>,
+        full_flushes: usize,
+    }
+
+    impl EventLogWriter for FullStorageWriter {
+        fn append(&mut self, line: &str) -> Result<()> {
+            self.pending.push(line.to_string());
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            if self.full_flushes > 0 {
+                self.full_flushes -= 1;
+                return Err(std::io::Error::from(std::io::ErrorKind::StorageFull).into());
+            }
+            self.stored.lock().unwrap().append(&mut self.pending);
+            Ok(())
+        }
+
+        fn read_all(&self) -> Result<Vec<String>> {
+            Ok([self.stored.lock().unwrap().clone(), self.pending.clone()].concat())
+        }
+
+        fn truncate(&mut self, location: LineLocation) -> Result<()> {
+            let stored = self.stored.lock().unwrap().len();
+            self.pending.truncate(location.index - stored);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn full_storage_leaves_register_untouched() {
+        let stored = Arc::new(Mutex::new(Vec::new()));
+        let writer = FullStorageWriter {
+            stored: stored.clone(),
+            full_flushes: 1,
+            ..Default::default()
+        };
+        let mut eventlog = EventLog::with_writer(
+            Box::new(writer),
+            EventlogFormat::Aael,
+            HashAlgorithm::Sha384,
+            EventlogRecovery::Error,
+        )
+        .unwrap();
+        let attester: BoxedAttester = Box::new(MockAttester::new(usize::MAX));
+
+        let events = [
+            EventEntry::new("domain", "operation", "first").unwrap(),
+            EventEntry::new("domain", "operation", "second").unwrap(),
+        ];
+        let e = extend_events(
+            &attester,
+            &mut eventlog,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            false,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            e,
+            BatchExtendError {
+                extended: 0,
+                logged: 0,
+                ..
+            }
+        ));
+        let e = eventlog_error(e.source);
+        assert!(
+            matches!(
+                e,
+                Error::EventLogStorage(EventLogStorageError {
+                    kind: StorageErrorKind::NoSpace,
+                    ..
+                })
+            ),
+            "{e:?}"
+        );
+        assert!(stored.lock().unwrap().is_empty());
+        assert_eq!(
+            attester
+                .get_runtime_measurement(17, HashAlgorithm::Sha384)
+                .await
+                .unwrap(),
+            vec![0; 48]
+        );
+
+        // The entries that failed are not written later, and the eventlog
+        // keeps replaying to the register
+        let events = [EventEntry::new("domain", "operation", "third").unwrap()];
+        extend_events(
+            &attester,
+            &mut eventlog,
+            HashAlgorithm::Sha384,
+            &events,
+            17,
+            false,
+        )
+        .await
+        .unwrap();
+        let entries = eventlog.read_entries().unwrap();
+        assert_eq!(entries, vec!["domain operation third"]);
+        assert_eq!(stored.lock().unwrap().len(), 2);
+        assert_eq!(
+            eventlog::replay(&entries, HashAlgorithm::Sha384, vec![0; 48]),
+            attester
+                .get_runtime_measurement(17, HashAlgorithm::Sha384)
+                .await
+                .unwrap()
+        );
+    }
+
+    /// Extends `max_extends` times and then hangs, telling each extension
+    /// it starts, s.t. a batch can be interrupted halfway
+    struct HangingAttester {
+        max_extends: usize,
+        extends: tokio::sync::mpsc::UnboundedSender<usize>,
+        started: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl Attester for HangingAttester {
+        async fn get_evidence(&self, _report_data: Vec<u8>) -> Result<String> {
+            Ok(String::new())
+        }
+
+        async fn extend_runtime_measurement(
+            &self,
+            _event_digest: Vec<u8>,
+            _register_index: u64,
+        ) -> Result<()> {
+            let started = self
+                .started
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let _ = self.extends.send(started);
+            if started == self.max_extends {
+                std::future::pending::<()>().await;
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn killed_writer_leaves_parseable_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("eventlog");
+        let eventlog = Arc::new(tokio::sync::Mutex::new(
+            EventLog::create(&path, EventlogSync::Never).unwrap(),
+        ));
+
+        let (extends, mut started) = tokio::sync::mpsc::unbounded_channel();
+        let writer = tokio::spawn({
+            let eventlog = eventlog.clone();
+            async move {
+                let attester: BoxedAttester = Box::new(HangingAttester {
+                    max_extends: 3,
+                    extends,
+                    started: Default::default(),
+                });
+                let contents: Vec<String> = (0..1000).map(|i| format!("event-{i}")).collect();
+                let events: Vec<EventEntry> = contents
+                    .iter()
+                    .map(|content| EventEntry::new("domain", "operation", content).unwrap())
+                    .collect();
+                let mut eventlog = eventlog.lock().await;
+                let _ = extend_events(
+                    &attester,
+                    &mut eventlog,
+                    HashAlgorithm::Sha384,
+                    &events,
+                    17,
+                    false,
+                )
+                .await;
+            }
+        });
+        // Killed while the fourth extension hangs, after its entry is
+        // written
+        while started.recv().await.unwrap() < 3 {}
+        writer.abort();
+        assert!(writer.await.unwrap_err().is_cancelled());
+
+        eventlog.lock().await.close_async().await.unwrap();
+        let log = std::fs::read_to_string(&path).unwrap();
+        assert!(log.ends_with('\n'));
+        let entries: Vec<&str> = log.lines().skip(1).collect();
+        assert_eq!(entries.len(), 4);
+        for (i, entry) in entries.iter().enumerate() {
+            let event = EventEntry::parse(entry).unwrap();
+            assert_eq!(event.to_string(), format!("domain operation event-{i}"));
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_rejects_calls() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+"#,
+        )
+        .unwrap();
+
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        aa.get_evidence(b"runtime data").await.unwrap();
+        aa.shutdown().await.unwrap();
+
+        let e = aa.get_evidence(b"runtime data").await.unwrap_err();
+        assert!(matches!(e, Error::Shutdown));
+        let e = aa.get_event_log(0).await.unwrap_err();
+        assert!(matches!(e, Error::Shutdown));
+    }
+
+    #[test]
+    fn unwritable_eventlog_fails_construction() {
+        // A regular file cannot be the parent dir of the eventlog
+        let file = tempfile::NamedTempFile::new().unwrap();
+        let path = file.path().join("eventlog");
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+file_path = "{}"
+"#,
+            path.display()
+        ))
+        .unwrap();
+
+        let e = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap_err();
+        let Error::EventLogIo(e) = e else {
+            panic!("unexpected error {e:?}");
+        };
+        assert!(
+            format!("{e:#}").contains(&file.path().display().to_string()),
+            "{e:#}"
+        );
+        assert!(
+            format!("{e:#}").contains("initialize the `file` eventlog backend"),
+            "{e:#}"
+        );
+    }
+
+    #[test]
+    fn invalid_config_fails_construction() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+init_pcr = 24
+"#,
+        )
+        .unwrap();
+
+        let e = AttestationAgent::new_with_config(config).unwrap_err();
+        let Error::ConfigInvalid(errors) = e else {
+            panic!("unexpected error {e:?}");
+        };
+        assert_eq!(errors[0].key, "eventlog_config.init_pcr");
+    }
+
+    #[cfg(feature = "tdx-attester")]
+    #[test]
+    fn tdx_attester_reads_eventlog_of_aa() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+file_path = "/var/lib/attestation-agent/eventlog"
+
+[attester.tdx]
+aa_eventlog_path = "/run/other/eventlog"
+"#,
+        )
+        .unwrap();
+
+        let options = super::attester_options(&config);
+        assert_eq!(
+            options.tdx.aa_eventlog_path,
+            Path::new("/var/lib/attestation-agent/eventlog")
+        );
+    }
+
+    #[test]
+    fn register_index_validation() {
+        let mock: BoxedAttester = Box::new(MockAttester::new(0));
+        assert!(check_register_index(&mock, Tee::Tdx, 17).is_ok());
+        let e = check_register_index(&mock, Tee::Tdx, 3).unwrap_err();
+        assert!(e.to_string().contains("8..=23"));
+
+        let sample: BoxedAttester = Box::<attester::sample::SampleAttester>::default();
+        assert!(check_register_index(&sample, Tee::Sample, 17).is_err());
+    }
+
+    #[test]
+    fn register_bank_validation() {
+        /// Has sha256 registers only, like a vTPM.
+        struct Sha256Attester;
+
+        #[async_trait::async_trait]
+        impl Attester for Sha256Attester {
+            async fn get_evidence(&self, _report_data: Vec<u8>) -> Result<String> {
+                Ok(String::new())
+            }
+
+            fn capabilities(&self) -> attester::AttesterCapabilities {
+                attester::AttesterCapabilities {
+                    supports_runtime_measurement: true,
+                    report_data_len: 64,
+                    supported_hash_algorithms: vec![HashAlgorithm::Sha256],
+                    init_data_supported: false,
+                }
+            }
+        }
+
+        let vtpm = Sha256Attester.capabilities();
+        assert!(check_register_bank(&vtpm, Tee::AzSnpVtpm, HashAlgorithm::Sha256).is_ok());
+        let e = check_register_bank(&vtpm, Tee::AzSnpVtpm, HashAlgorithm::Sha384).unwrap_err();
+        assert!(
+            matches!(
+                &e,
+                Error::UnsupportedRegisterBank { supported, .. }
+                    if supported == &[HashAlgorithm::Sha256]
+            ),
+            "{e:?}"
+        );
+        assert!(e.to_string().contains("sha256 banks"), "{e}");
+
+        // Attesters that do not report their banks are not checked
+        let mock = MockAttester::new(0).capabilities();
+        assert!(check_register_bank(&mock, Tee::Tdx, HashAlgorithm::Sha512).is_ok());
+    }
+
+    #[tokio::test]
+    async fn operation_timeout() {
+        let e = with_timeout(1, "get token", "https://kbs:8080", async {
+            std::future::pending::<crate::Result<()>>().await
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "get token timed out after 1 seconds waiting for https://kbs:8080"
+        );
+
+        // 0 means no limit
+        let res = with_timeout(0, "get evidence", "sample attester", async {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            Ok(1)
+        })
+        .await;
+        assert_eq!(res.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled_eventlog() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+"#,
+        )
+        .unwrap();
+
+        // The sample attester has no register, which is fine without eventlog
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        aa.init().await.unwrap();
+        let e = aa
+            .extend_runtime_measurement("domain", "operation", "content", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)));
+        assert!(aa.get_event_log(0).await.unwrap().is_empty());
+        assert_eq!(aa.eventlog_path().await, None);
+
+        // Nor can its registers be read
+        let e = aa
+            .get_runtime_measurement(999, HashAlgorithm::Sha384)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e,
+            Error::UnsupportedRegister {
+                register_index: 999,
+                ..
+            }
+        ));
+
+        // Neither the sample attester has a firmware eventlog
+        let combined: CombinedEventLog =
+            serde_json::from_str(&aa.get_combined_event_log().await.unwrap()).unwrap();
+        assert!(combined.registers.is_empty());
+    }
+
+    #[rstest::rstest]
+    #[case("primary", "sample")]
+    #[case("composite", "composite")]
+    #[tokio::test]
+    async fn evidence_label(#[case] evidence_mode: &str, #[case] expected: &str) {
+        let config = Config::from_toml_str(&format!(
+            r#"
+[evidence_config]
+evidence_mode = "{evidence_mode}"
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        let evidence = aa.get_evidence_ex(b"nonce").await.unwrap();
+        assert_eq!(evidence.tee, expected);
+
+        // Secondary attesters are only created in the composite mode
+        assert_eq!(
+            aa.secondary_attesters.get().is_some(),
+            evidence_mode == "composite"
+        );
+        if evidence_mode == "composite" {
+            let composite: CompositeEvidence = serde_json::from_slice(&evidence.evidence).unwrap();
+            assert_eq!(composite.primary, "sample");
+            assert!(composite.evidence.contains_key("sample"));
+        }
+    }
+
+    struct MockTokenGetter;
+
+    #[async_trait::async_trait]
+    impl TokenGetter for MockTokenGetter {
+        fn name(&self) -> &str {
+            "mock_as"
+        }
+
+        async fn get_token(&self) -> Result<TokenResponse> {
+            Ok(TokenResponse::new("token".into(), None))
+        }
+    }
+
+    #[tokio::test]
+    async fn registered_token_getter() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+"#,
+        )
+        .unwrap();
+        // Getters are registered while AA is shared
+        let aa = Arc::new(AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap());
+        let e = aa.get_token("mock").await.unwrap_err();
+        assert!(matches!(e, Error::UnsupportedTokenType(_)), "{e}");
+        #[cfg(feature = "token")]
+        assert!(matches!(
+            aa.evict_token("mock").await,
+            Err(Error::UnsupportedTokenType(_))
+        ));
+
+        let shared = aa.clone();
+        tokio::spawn(async move {
+            shared
+                .register_token_getter("mock", Box::new(MockTokenGetter))
+                .unwrap()
+        })
+        .await
+        .unwrap();
+        assert_eq!(aa.get_token("mock").await.unwrap(), b"token");
+        assert_eq!(aa.refresh_token("mock").await.unwrap(), b"token");
+        #[cfg(feature = "token")]
+        assert!(!aa.evict_token("mock").await.unwrap());
+        let response = aa.get_token_ex("mock").await.unwrap();
+        assert_eq!(response.token, "token");
+        assert_eq!(response.tee_keypair, None);
+        let e = aa
+            .get_token_with_nonce("mock", Some(vec![0; 32]))
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::InvalidArgument(_)), "{e}");
+
+        #[cfg(feature = "kbs")]
+        assert!(aa
+            .register_token_getter("kbs", Box::new(MockTokenGetter))
+            .is_err());
+    }
+
+    #[rstest::rstest]
+    #[case::none(None, vec![], None)]
+    #[case::single(None, vec!["kbs"], Some("kbs"))]
+    #[case::several(None, vec!["kbs", "coco_as"], None)]
+    #[case::configured(Some("coco_as"), vec!["kbs", "coco_as"], Some("coco_as"))]
+    fn default_token_type(
+        #[case] default_type: Option<&str>,
+        #[case] available: Vec<&str>,
+        #[case] expected: Option<&str>,
+    ) {
+        match super::default_token_type(default_type, &available) {
+            Ok(token_type) => assert_eq!(Some(token_type.as_str()), expected),
+            Err(e) => {
+                assert!(expected.is_none(), "{e}");
+                assert!(matches!(e, Error::InvalidArgument(_)), "{e}");
+                for token_type in available {
+                    assert!(e.to_string().contains(token_type), "{e}");
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn empty_token_type() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+"#,
+        )
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        aa.register_token_getter("mock", Box::new(MockTokenGetter))
+            .unwrap();
+        // Only the configured token types are counted
+        if !cfg!(any(feature = "kbs", feature = "coco_as")) {
+            assert_eq!(aa.get_token("").await.unwrap(), b"token");
+        } else {
+            let e = aa.get_token("").await.unwrap_err();
+            assert!(matches!(e, Error::InvalidArgument(_)), "{e}");
+            assert!(e.to_string().contains("mock"), "{e}");
+        }
+
+        aa.update_configuration("[token_configs]\ndefault_type = \"mock\"\n")
+            .await
+            .unwrap();
+        assert_eq!(aa.get_token("").await.unwrap(), b"token");
+        assert_eq!(aa.refresh_token("").await.unwrap(), b"token");
+
+        // Explicit token types are not resolved
+        let e = aa.get_token(" ").await.unwrap_err();
+        assert!(matches!(e, Error::UnsupportedTokenType(_)), "{e}");
+    }
+
+    #[cfg(feature = "kbs")]
+    #[tokio::test]
+    async fn empty_token_type_of_only_configured() {
+        // The other token types of the build are not configured
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[eventlog_config]
+enabled = false
+"#,
+        )
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        assert_eq!(aa.resolve_token_type("").await.unwrap(), "kbs");
+
+        aa.register_token_getter("mock", Box::new(MockTokenGetter))
+            .unwrap();
+        let e = aa.resolve_token_type("").await.unwrap_err();
+        assert!(e.to_string().contains("kbs, mock"), "{e}");
+    }
+
+    #[cfg(feature = "cert")]
+    #[tokio::test]
+    async fn certificate_takes_no_params() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[token_configs.cert]
+sans = ["workload.example.org"]
+
+[eventlog_config]
+enabled = false
+"#,
+        )
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+
+        let e = aa
+            .get_token_with_nonce("cert", Some(vec![0; 32]))
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::InvalidArgument(_)), "{e}");
+        let e = aa
+            .get_token_with_params("cert", serde_json::json!({"tenant": "a"}))
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::InvalidArgument(_)), "{e}");
+        assert!(aa.certificate_private_key().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn update_reports_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+eventlog_algorithm = "sha512"
+file_path = "{}"
+"#,
+            dir.path().join("eventlog").display()
+        ))
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        assert_eq!(aa.eventlog_path().await, Some(dir.path().join("eventlog")));
+
+        // Only the given section changes, the others are kept
+        let changed = aa
+            .update_configuration("[token_configs]\ncache = true\n")
+            .await
+            .unwrap();
+        assert_eq!(changed, vec!["token_configs.cache"]);
+        let config = aa.config.read().await.clone();
+        assert_eq!(
+            config.eventlog_config.eventlog_algorithm,
+            HashAlgorithm::Sha512
+        );
+
+        let e = aa
+            .update_configuration("[eventlog_config]\neventlog_algorithm = \"sha384\"\n")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                e,
+                Error::ConfigImmutable("eventlog_config.eventlog_algorithm")
+            ),
+            "{e:?}"
+        );
+
+        // `init_pcr` is only immutable once the INIT entry is recorded
+        let changed = aa
+            .update_configuration("[eventlog_config]\ninit_pcr = 16\n")
+            .await
+            .unwrap();
+        assert_eq!(changed, vec!["eventlog_config.init_pcr"]);
+        aa.eventlog
+            .lock()
+            .await
+            .write_init_log("INIT sha512/00", 17, &[0; 64])
+            .unwrap();
+        let e = aa
+            .update_configuration("[eventlog_config]\ninit_pcr = 17\n")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(e, Error::ConfigImmutable("eventlog_config.init_pcr")),
+            "{e:?}"
+        );
+        assert_eq!(aa.config.read().await.eventlog_config.init_pcr, 16);
+    }
+
+    #[test]
+    fn agent_can_be_shared() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<AttestationAgent>();
+    }
+
+    /// Implements only the methods without a default, s.t. the defaults of
+    /// the methods added to [`AttestationAPIs`] are tested.
+    struct MinimalApis;
+
+    #[async_trait::async_trait]
+    impl AttestationAPIs for MinimalApis {
+        async fn get_token(&self, _token_type: &str) -> crate::error::Result<Vec<u8>> {
+            Ok(br#"{"token": "header.claims.signature"}"#.to_vec())
+        }
+
+        async fn get_token_with_params(
+            &self,
+            _token_type: &str,
+            _params: Value,
+        ) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn get_token_with_nonce(
+            &self,
+            _token_type: &str,
+            _nonce: Option<Vec<u8>>,
+        ) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn refresh_token(&self, _token_type: &str) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn get_evidence(&self, _runtime_data: &[u8]) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn get_evidence_ex(&self, _runtime_data: &[u8]) -> crate::error::Result<Evidence> {
+            unimplemented!()
+        }
+
+        async fn get_evidence_with(
+            &self,
+            _runtime_data: RuntimeData,
+        ) -> crate::error::Result<Evidence> {
+            unimplemented!()
+        }
+
+        async fn get_evidence_with_eventlog(
+            &self,
+            _runtime_data: &[u8],
+        ) -> crate::error::Result<EvidenceWithEventLog> {
+            unimplemented!()
+        }
+
+        async fn get_additional_evidence(
+            &self,
+            _runtime_data: &[u8],
+        ) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn get_derived_key(
+            &self,
+            _context: &[u8],
+            _length: usize,
+        ) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn extend_runtime_measurement(
+            &self,
+            _domain: &str,
+            _operation: &str,
+            _content: &str,
+            _register_index: Option<u64>,
+        ) -> crate::error::Result<()> {
+            unimplemented!()
+        }
+
+        async fn extend_runtime_measurement_batch(
+            &self,
+            _events: &[(&str, &str, &str)],
+            _register_index: Option<u64>,
+        ) -> crate::error::Result<()> {
+            unimplemented!()
+        }
+
+        async fn get_event_log(&self, start_index: usize) -> crate::error::Result<Vec<String>> {
+            Ok(vec![format!("entry {start_index}")])
+        }
+
+        async fn get_runtime_measurement(
+            &self,
+            _register_index: u64,
+            _algorithm: HashAlgorithm,
+        ) -> crate::error::Result<Vec<u8>> {
+            unimplemented!()
+        }
+
+        async fn verify_event_log(&self) -> crate::error::Result<()> {
+            unimplemented!()
+        }
+
+        async fn check_init_data(&self, init_data: &[u8]) -> crate::error::Result<InitdataResult> {
+            match init_data {
+                [] => Ok(InitdataResult::Unsupported),
+                _ => Ok(InitdataResult::Ok),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn trait_defaults() {
+        let apis = MinimalApis;
+        let response = apis.get_token_ex("mock").await.unwrap();
+        assert_eq!(response.token, "header.claims.signature");
+
+        let result = apis
+            .check_init_data_with(InitData::Digest(vec![1; 32]))
+            .await
+            .unwrap();
+        assert!(matches!(result, InitdataResult::Ok));
+        let e = apis
+            .check_init_data_with(InitData::Document(b"algorithm = \"sha384\"".to_vec()))
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+
+        let e = apis
+            .extend_runtime_measurement_binary("domain", "operation", &[0xff], None)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+
+        assert_eq!(
+            apis.get_register_event_log(1, None).await.unwrap(),
+            ["entry 1"]
+        );
+        let e = apis.get_register_event_log(1, Some(17)).await.unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+
+        let e = apis.get_event_log_json(0, None).await.unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+
+        let e = apis
+            .query_event_log(EventFilter::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+
+        let e = apis.get_combined_event_log().await.unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+
+        let e = apis
+            .extend_runtime_measurement_ex("domain", "operation", "content", None)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
+        let e = apis
+            .extend_runtime_measurement_batch_ex(&[("domain", "operation", "content")], None)
+            .await
+            .unwrap_err();
+        assert!(matches!(e, Error::Unsupported(_)), "{e:?}");
     }
 }
@@ -0,0 +1,149 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::future::Future;
+
+use rand::Rng;
+use tracing::warn;
+
+use crate::{config::retry::RetryConfig, telemetry};
+
+/// Run `attempt` until it succeeds, fails with an error that `is_retryable`
+/// rejects, or `config.max_attempts` is reached. Each attempt starts over,
+/// e.g. with a new challenge and new evidence, s.t. a challenge that went
+/// stale is not reused. The error tells how many attempts were made, and
+/// why each of them failed. The error of the last attempt is its cause.
+pub(crate) async fn retry<T, F, Fut>(
+    config: &RetryConfig,
+    operation: &str,
+    is_retryable: impl Fn(&anyhow::Error) -> bool,
+    mut attempt: F,
+) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let max_attempts = config.max_attempts.max(1);
+    let mut attempts = 0;
+    let mut failures = Vec::new();
+    loop {
+        attempts += 1;
+        let e = match attempt().await {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+
+        let failed = if !is_retryable(&e) {
+            Some(format!("{operation} failed at attempt {attempts}"))
+        } else if attempts >= max_attempts {
+            Some(format!("{operation} failed after {attempts} attempts"))
+        } else {
+            None
+        };
+        if let Some(failed) = failed {
+            if failures.is_empty() {
+                return Err(e.context(failed));
+            }
+            return Err(e.context(format!(
+                "{failed}, the attempts before failed with: {}",
+                failures.join("; ")
+            )));
+        }
+        failures.push(format!("attempt {attempts}: {e:#}"));
+
+        let backoff = config.backoff(attempts, rand::thread_rng().gen());
+        warn!(
+            target: telemetry::TOKEN,
+            operation,
+            attempt = attempts,
+            max_attempts,
+            ?backoff,
+            error = ?e,
+            "Retrying after a transient failure"
+        );
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+    use tokio::time::Instant;
+
+    use super::retry;
+    use crate::config::retry::RetryConfig;
+
+    const CONFIG: RetryConfig = RetryConfig {
+        max_attempts: 5,
+        initial_backoff_ms: 100,
+        max_backoff_ms: 300,
+        jitter: false,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn backoff_schedule() {
+        let start = Instant::now();
+        let mut attempts = Vec::new();
+        let e = retry(
+            &CONFIG,
+            "get token",
+            |_| true,
+            || {
+                attempts.push(start.elapsed());
+                async { Err::<(), _>(anyhow!("connection refused")) }
+            },
+        )
+        .await
+        .unwrap_err();
+
+        let millis = |ms: &[u64]| ms.iter().map(|ms| Duration::from_millis(*ms)).collect();
+        let expected: Vec<Duration> = millis(&[0, 100, 300, 600, 900]);
+        assert_eq!(attempts, expected);
+        assert!(format!("{e:#}").contains("after 5 attempts"), "{e:#}");
+        assert!(
+            format!("{e}").contains("attempt 4: connection refused"),
+            "{e}"
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stop_at_success_or_permanent_failure() {
+        let mut attempts = 0;
+        let value = retry(
+            &CONFIG,
+            "get token",
+            |_| true,
+            || {
+                attempts += 1;
+                let result = if attempts < 3 {
+                    Err(anyhow!("503"))
+                } else {
+                    Ok(attempts)
+                };
+                async move { result }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(value, 3);
+
+        let mut attempts = 0;
+        let e = retry(
+            &CONFIG,
+            "get token",
+            |_| false,
+            || {
+                attempts += 1;
+                async { Err::<(), _>(anyhow!("403")) }
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(attempts, 1);
+        assert!(format!("{e:#}").contains("at attempt 1"), "{e:#}");
+    }
+}
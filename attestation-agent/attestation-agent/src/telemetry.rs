@@ -0,0 +1,41 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Targets of the tracing events of AA. They are stable, s.t. the events
+//! can be filtered by area, e.g. `RUST_LOG=attestation_agent::eventlog=debug`.
+
+use std::time::Instant;
+
+/// Outcome of the [`crate::AttestationAPIs`] operations
+pub(crate) const API: &str = "attestation_agent::api";
+
+/// Getting and caching tokens
+pub(crate) const TOKEN: &str = "attestation_agent::token";
+
+/// Getting evidence from the attesters
+pub(crate) const EVIDENCE: &str = "attestation_agent::evidence";
+
+/// Initializing and extending the eventlog
+pub(crate) const EVENTLOG: &str = "attestation_agent::eventlog";
+
+/// Loading the configuration
+pub(crate) const CONFIG: &str = "attestation_agent::config";
+
+/// Records the elapsed time of an operation in its span when dropped, i.e.
+/// when the instrumented operation returns.
+pub(crate) struct Elapsed(Instant);
+
+impl Elapsed {
+    pub(crate) fn start() -> Self {
+        Self(Instant::now())
+    }
+}
+
+impl Drop for Elapsed {
+    fn drop(&mut self) {
+        let elapsed_ms = self.0.elapsed().as_millis() as u64;
+        tracing::debug!(target: API, elapsed_ms, "operation finished");
+    }
+}
@@ -0,0 +1,590 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{
+    collections::HashMap,
+    fs::{DirBuilder, OpenOptions},
+    io::Write,
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use super::{response::JwtClaims, TokenResponse, TokenType};
+use crate::telemetry;
+
+struct CachedToken {
+    token: TokenResponse,
+
+    /// The `exp` claim of the token, or when the opaque token reaches its
+    /// max age, in seconds since the UNIX epoch.
+    expires_at: u64,
+
+    /// The `nbf` claim of the token, if any.
+    not_before: Option<u64>,
+}
+
+/// The key of a token in the cache besides its type: the URL of the
+/// service, qualified with the audience of the token, if any.
+pub fn cache_key(url: &str, audience: Option<&str>) -> String {
+    match audience {
+        Some(audience) => format!("{url}#audience={audience}"),
+        None => url.to_string(),
+    }
+}
+
+/// Permission bits of the group and others, none of which may be set on a
+/// persisted token.
+const SHARED_MODE_BITS: u32 = 0o077;
+
+/// The content of the file a token is persisted in, see [`persist`].
+#[derive(Deserialize, Serialize)]
+pub struct PersistedToken {
+    token_type: String,
+
+    /// URL of the service that issued the token.
+    url: String,
+
+    /// The token, base64url encoded.
+    token: String,
+}
+
+/// In-memory cache of the fetched tokens, keyed by the token type and the
+/// URL of the service that issued the token, qualified with the audience
+/// of the token, if any. Each token expires on its own. JWTs are cached if
+/// they carry an `exp` claim, and other tokens for the opaque max age, if
+/// any.
+#[derive(Default)]
+pub struct TokenCache {
+    entries: HashMap<(TokenType, String), CachedToken>,
+}
+
+impl TokenCache {
+    /// Get the cached token if it is still valid for more than `skew`
+    /// seconds, and is valid already, up to `leeway` seconds of clock
+    /// difference.
+    pub fn get(
+        &self,
+        token_type: TokenType,
+        url: &str,
+        skew: u64,
+        leeway: u64,
+    ) -> Option<TokenResponse> {
+        let entry = self.entries.get(&(token_type, url.to_string()))?;
+        let now = now();
+        if now.saturating_add(skew) >= entry.expires_at {
+            debug!(target: telemetry::TOKEN, ?token_type, url, "cached token is about to expire");
+            return None;
+        }
+        if entry
+            .not_before
+            .is_some_and(|not_before| not_before > now.saturating_add(leeway))
+        {
+            debug!(target: telemetry::TOKEN, ?token_type, url, "cached token is not valid yet");
+            return None;
+        }
+
+        Some(entry.token.clone())
+    }
+
+    /// The `exp` claim of the cached token, even if it is about to expire.
+    pub fn expiry(&self, token_type: TokenType, url: &str) -> Option<u64> {
+        let entry = self.entries.get(&(token_type, url.to_string()))?;
+        Some(entry.expires_at)
+    }
+
+    /// Cache the token. A token that is not a JWT is cached for
+    /// `opaque_max_age` seconds, if not `0`.
+    pub fn insert(
+        &mut self,
+        token_type: TokenType,
+        url: String,
+        token: TokenResponse,
+        opaque_max_age: u64,
+    ) {
+        let (expires_at, not_before) = match token_claims(&token) {
+            Some(JwtClaims {
+                exp: Some(exp),
+                nbf,
+            }) => (exp, nbf),
+            None if opaque_max_age != 0 => (now().saturating_add(opaque_max_age), None),
+            _ => {
+                debug!(target: telemetry::TOKEN, ?token_type, url, "token has no expiry, skip caching");
+                return;
+            }
+        };
+
+        self.entries.insert(
+            (token_type, url),
+            CachedToken {
+                token,
+                expires_at,
+                not_before,
+            },
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Drop the cached tokens of the given type that the service at `url`
+    /// issued, for any audience, e.g. as it is no longer the service of
+    /// the token type. The number of tokens dropped.
+    pub fn evict(&mut self, token_type: TokenType, url: &str) -> usize {
+        let audience_prefix = cache_key(url, Some(""));
+        let cached = self.entries.len();
+        self.entries.retain(|(cached_type, key), _| {
+            *cached_type != token_type || (key != url && !key.starts_with(&audience_prefix))
+        });
+        cached - self.entries.len()
+    }
+
+    /// The cached token to [`persist`], s.t. it can be loaded after AA
+    /// restarts. `None` if no token is cached, if it is opaque, as its age
+    /// would not be known once loaded, or if it comes with the TEE key
+    /// pair, which is only written to disk with `persist_tee_keypair`.
+    pub fn persisted(
+        &self,
+        token_type: TokenType,
+        url: &str,
+        persist_tee_keypair: bool,
+    ) -> Option<PersistedToken> {
+        let entry = self.entries.get(&(token_type, url.to_string()))?;
+        if token_claims(&entry.token).is_none() {
+            debug!(target: telemetry::TOKEN, ?token_type, "Opaque token is not persisted");
+            return None;
+        }
+        if entry.token.tee_keypair.is_some() && !persist_tee_keypair {
+            warn!(
+                target: telemetry::TOKEN,
+                ?token_type,
+                "Token is not persisted, as persist_tee_keypair is not set"
+            );
+            return None;
+        }
+
+        // In the shape of the `get_token` API, as it has always been
+        let token = entry.token.clone().into_bytes().ok()?;
+        Some(PersistedToken {
+            token_type: token_type.as_ref().to_string(),
+            url: url.to_string(),
+            token: URL_SAFE_NO_PAD.encode(token),
+        })
+    }
+
+    /// Load the token persisted at `path` by [`persist`] into the cache, if
+    /// it is of the service at `url` and has not expired. A file that
+    /// cannot be used is removed. Whether a token is loaded.
+    pub fn load(&mut self, token_type: TokenType, url: &str, path: &Path) -> bool {
+        let token = match read_persisted(token_type, url, path) {
+            Ok(Some(token)) => token,
+            Ok(None) => return false,
+            Err(e) => {
+                warn!(
+                    target: telemetry::TOKEN,
+                    ?token_type,
+                    path = %path.display(),
+                    error = ?e,
+                    "Remove persisted token"
+                );
+                if let Err(e) = std::fs::remove_file(path) {
+                    warn!(target: telemetry::TOKEN, path = %path.display(), error = %e, "Removing persisted token failed");
+                }
+                return false;
+            }
+        };
+
+        info!(target: telemetry::TOKEN, ?token_type, path = %path.display(), "Loaded persisted token");
+        self.insert(token_type, url.to_string(), token, 0);
+        true
+    }
+}
+
+/// Write the token to `path`, s.t. it is loaded after AA restarts. The
+/// file is replaced atomically, and is only readable and writable by AA. It
+/// is written on the blocking thread pool, s.t. the cache need not be
+/// locked meanwhile.
+pub async fn persist(token: PersistedToken, path: &Path) -> Result<()> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || write_persisted(&token, &path))
+        .await
+        .context("persisting token panicked")?
+}
+
+fn write_persisted(token: &PersistedToken, path: &Path) -> Result<()> {
+    let content = serde_json::to_vec(token)?;
+    if let Some(parent) = path.parent() {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)
+            .with_context(|| format!("create token cache dir {}", parent.display()))?;
+    }
+    let mut tmp_name = path.file_name().context("no file name")?.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    // A file left by an interrupted write may have other permissions
+    match std::fs::remove_file(&tmp_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(e).with_context(|| format!("remove {}", tmp_path.display()));
+        }
+        _ => {}
+    }
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .with_context(|| format!("create {}", tmp_path.display()))?;
+    file.write_all(&content)
+        .and_then(|_| file.sync_all())
+        .with_context(|| format!("write {}", tmp_path.display()))?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("rename {} to {}", tmp_path.display(), path.display()))?;
+    debug!(target: telemetry::TOKEN, token_type = token.token_type, path = %path.display(), "Persisted token");
+    Ok(())
+}
+
+/// The token persisted at `path`, `None` if there is none. The token must be
+/// of the type and service asked for, and must not have expired.
+fn read_persisted(token_type: TokenType, url: &str, path: &Path) -> Result<Option<TokenResponse>> {
+    let metadata = match std::fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).context("stat"),
+    };
+    let mode = metadata.permissions().mode();
+    if mode & SHARED_MODE_BITS != 0 {
+        bail!(
+            "accessible by the group or others, its mode is {:o}",
+            mode & 0o777
+        );
+    }
+
+    let content = std::fs::read(path).context("read")?;
+    let persisted: PersistedToken = serde_json::from_slice(&content).context("parse")?;
+    if persisted.token_type != token_type.as_ref() || persisted.url != url {
+        bail!(
+            "issued by {} `{}` rather than the configured service",
+            persisted.token_type,
+            persisted.url
+        );
+    }
+    let token = URL_SAFE_NO_PAD
+        .decode(&persisted.token)
+        .context("token is not base64url encoded")?;
+    let token = TokenResponse::from_bytes(&token)?;
+    match token_claims(&token).and_then(|claims| claims.exp) {
+        Some(expires_at) if expires_at > now() => Ok(Some(token)),
+        Some(_) => bail!("token has expired"),
+        None => bail!("token has no expiry"),
+    }
+}
+
+/// Seconds since the UNIX epoch.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Get the claims of the token returned by a token getter, `None` if it is
+/// not a JWT.
+fn token_claims(token: &TokenResponse) -> Option<JwtClaims> {
+    JwtClaims::parse(&token.token)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::now;
+
+    fn jwt(claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"ES256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("{header}.{claims}.c2lnbmF0dXJl")
+    }
+
+    #[cfg(feature = "kbs")]
+    #[test]
+    fn cache_kbs_token() {
+        use super::{cache_key, TokenCache, TokenResponse, TokenType};
+
+        let mut cache = TokenCache::default();
+        let url = "https://kbs:8080";
+        let message = |exp: u64| TokenResponse::new(jwt(json!({"exp": exp})), Some("key".into()));
+
+        let token = message(now() + 3600);
+        cache.insert(TokenType::Kbs, url.into(), token.clone(), 0);
+        assert_eq!(cache.get(TokenType::Kbs, url, 30, 60), Some(token));
+        assert!(cache
+            .get(TokenType::Kbs, "https://other-kbs:8080", 30, 60)
+            .is_none());
+
+        // Within the skew before expiry
+        cache.insert(TokenType::Kbs, url.into(), message(now() + 10), 0);
+        assert!(cache.get(TokenType::Kbs, url, 30, 60).is_none());
+
+        cache.insert(TokenType::Kbs, url.into(), message(now() + 3600), 0);
+        cache.clear();
+        assert!(cache.get(TokenType::Kbs, url, 30, 60).is_none());
+
+        let audience = cache_key(url, Some("tenant"));
+        let other = "https://other-kbs:8080";
+        cache.insert(TokenType::Kbs, url.into(), message(now() + 3600), 0);
+        cache.insert(TokenType::Kbs, audience.clone(), message(now() + 3600), 0);
+        cache.insert(TokenType::Kbs, other.into(), message(now() + 3600), 0);
+        assert_eq!(cache.evict(TokenType::Kbs, url), 2);
+        assert!(cache.get(TokenType::Kbs, &audience, 30, 60).is_none());
+        assert_eq!(cache.evict(TokenType::Kbs, url), 0);
+        // The tokens of the other KBS are kept
+        assert!(cache.get(TokenType::Kbs, other, 30, 60).is_some());
+    }
+
+    #[cfg(feature = "kbs")]
+    #[rstest]
+    #[case::valid(jwt(json!({"exp": now() + 3600})), 0, true)]
+    #[case::expired(jwt(json!({"exp": now() - 1})), 0, false)]
+    #[case::no_expiry(jwt(json!({"iss": "kbs"})), 300, false)]
+    #[case::not_yet_valid(jwt(json!({"exp": now() + 3600, "nbf": now() + 120})), 0, false)]
+    #[case::valid_within_leeway(jwt(json!({"exp": now() + 3600, "nbf": now() + 30})), 0, true)]
+    #[case::opaque("opaque".into(), 300, true)]
+    #[case::opaque_not_cached("opaque".into(), 0, false)]
+    #[case::malformed("a.bm90IGpzb24.c".into(), 300, true)]
+    fn serve_valid_tokens(
+        #[case] token: String,
+        #[case] opaque_max_age: u64,
+        #[case] served: bool,
+    ) {
+        use super::{TokenCache, TokenResponse, TokenType};
+
+        let url = "https://kbs:8080";
+        let mut cache = TokenCache::default();
+        let token = TokenResponse::new(token, None);
+        cache.insert(TokenType::Kbs, url.into(), token.clone(), opaque_max_age);
+        assert_eq!(
+            cache.get(TokenType::Kbs, url, 30, 60),
+            served.then_some(token)
+        );
+    }
+
+    #[cfg(feature = "kbs")]
+    #[test]
+    fn opaque_tokens_reach_max_age() {
+        use super::{TokenCache, TokenResponse, TokenType};
+
+        let url = "https://kbs:8080";
+        let mut cache = TokenCache::default();
+        let inserted = now();
+        let opaque = TokenResponse::new("opaque".into(), None);
+        cache.insert(TokenType::Kbs, url.into(), opaque, 40);
+        let expiry = cache.expiry(TokenType::Kbs, url).unwrap();
+        assert!((inserted + 40..=now() + 40).contains(&expiry));
+        assert!(cache.get(TokenType::Kbs, url, 30, 60).is_some());
+        assert!(cache.get(TokenType::Kbs, url, 40, 60).is_none());
+
+        // They are not persisted, as their age is lost
+        assert!(cache.persisted(TokenType::Kbs, url, true).is_none());
+    }
+
+    #[cfg(feature = "kbs")]
+    #[tokio::test]
+    async fn persist_kbs_token() {
+        use std::os::unix::fs::PermissionsExt;
+
+        use super::{persist, TokenCache, TokenResponse, TokenType};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache/kbs-token");
+        let url = "https://kbs:8080";
+        let message = |exp: u64| TokenResponse::new(jwt(json!({"exp": exp})), Some("key".into()));
+
+        let token = message(now() + 3600);
+        let mut cache = TokenCache::default();
+        cache.insert(TokenType::Kbs, url.into(), token.clone(), 0);
+        // The TEE key pair is only written to disk on opt-in, unlike tokens
+        // without one
+        assert!(cache.persisted(TokenType::Kbs, url, false).is_none());
+        let other_url = "https://other-kbs:8080";
+        let plain = TokenResponse::new(jwt(json!({"exp": now() + 3600})), None);
+        cache.insert(TokenType::Kbs, other_url.into(), plain, 0);
+        assert!(cache.persisted(TokenType::Kbs, other_url, false).is_some());
+        let persisted = cache.persisted(TokenType::Kbs, url, true).unwrap();
+        persist(persisted, &path).await.unwrap();
+        let mode = |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode();
+        assert_eq!(mode(&path) & 0o777, 0o600);
+        assert_eq!(mode(path.parent().unwrap()) & 0o777, 0o700);
+
+        // As after a restart
+        let mut cache = TokenCache::default();
+        assert!(cache.load(TokenType::Kbs, url, &path));
+        assert_eq!(cache.get(TokenType::Kbs, url, 30, 60), Some(token.clone()));
+
+        // A token of another KBS is not served, and removed
+        let mut cache = TokenCache::default();
+        assert!(!cache.load(TokenType::Kbs, "https://other-kbs:8080", &path));
+        assert!(!path.exists());
+        assert!(!cache.load(TokenType::Kbs, url, &path));
+
+        // So are expired and corrupt tokens
+        cache.insert(TokenType::Kbs, url.into(), message(now() - 1), 0);
+        let persisted = cache.persisted(TokenType::Kbs, url, true).unwrap();
+        persist(persisted, &path).await.unwrap();
+        assert!(!TokenCache::default().load(TokenType::Kbs, url, &path));
+        assert!(!path.exists());
+
+        std::fs::write(&path, "{").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        assert!(!TokenCache::default().load(TokenType::Kbs, url, &path));
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "kbs")]
+    #[tokio::test]
+    async fn serve_persisted_token_after_restart() {
+        use std::sync::atomic::Ordering;
+
+        use kbs_types::Tee;
+
+        use crate::{token::http_stub, AttestationAPIs, AttestationAgent, Config};
+
+        let (url, requests) = http_stub::start(move |request| {
+            if request.contains("/kbs/v0/auth") {
+                return http_stub::response("200 OK", r#"{"nonce":"MTIzNDU2","extra-params":""}"#);
+            }
+            let token = jwt(json!({ "exp": now() + 3600 }));
+            http_stub::response("200 OK", &json!({ "token": token }).to_string())
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs]
+cache = true
+
+[token_configs.kbs]
+url = "{url}"
+cache_path = "{}"
+persist_tee_keypair = true
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+"#,
+            dir.path().join("kbs-token").display()
+        ))
+        .unwrap();
+
+        let aa = AttestationAgent::with_config_and_tee(config.clone(), Tee::Sample).unwrap();
+        let token = aa.get_token("kbs").await.unwrap();
+        let attested = requests.load(Ordering::SeqCst);
+        drop(aa);
+
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        assert_eq!(aa.get_token("kbs").await.unwrap(), token);
+        assert_eq!(requests.load(Ordering::SeqCst), attested);
+    }
+
+    #[cfg(all(feature = "kbs", feature = "coco_as"))]
+    #[tokio::test]
+    async fn cache_tokens_per_service() {
+        use std::sync::atomic::Ordering;
+
+        use kbs_types::Tee;
+
+        use crate::{token::http_stub, AttestationAPIs, AttestationAgent, Config, Error};
+
+        let kbs_token = jwt(json!({ "iss": "kbs", "exp": now() + 3600 }));
+        let kbs_response = json!({ "token": kbs_token }).to_string();
+        let (kbs_url, kbs_requests) = http_stub::start(move |request| {
+            if request.contains("/kbs/v0/auth") {
+                return http_stub::response("200 OK", r#"{"nonce":"MTIzNDU2","extra-params":""}"#);
+            }
+            http_stub::response("200 OK", &kbs_response)
+        })
+        .await;
+        let as_token = jwt(json!({ "iss": "as", "exp": now() + 3600 }));
+        let as_response = as_token.clone();
+        let (as_url, as_requests) =
+            http_stub::start(move |_| http_stub::response("200 OK", &as_response)).await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs]
+cache = true
+
+[token_configs.kbs]
+url = "{kbs_url}"
+
+[token_configs.coco_as]
+url = "{as_url}"
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        let is_kbs_token = |token: Vec<u8>| String::from_utf8(token).unwrap().contains(&kbs_token);
+
+        // Both services are attested to at once, and serve their own tokens
+        let (kbs, coco_as, kbs_again, coco_as_again) = tokio::join!(
+            aa.get_token("kbs"),
+            aa.get_token("coco_as"),
+            aa.get_token("kbs"),
+            aa.get_token("coco_as"),
+        );
+        assert!(is_kbs_token(kbs.unwrap()));
+        assert!(is_kbs_token(kbs_again.unwrap()));
+        assert_eq!(coco_as.unwrap(), as_token.as_bytes());
+        assert_eq!(coco_as_again.unwrap(), as_token.as_bytes());
+        let kbs_attested = kbs_requests.load(Ordering::SeqCst);
+        assert_eq!(as_requests.load(Ordering::SeqCst), 1);
+
+        // A service that is configured otherwise still issued the tokens
+        aa.update_configuration("[token_configs.coco_as]\ntimeout_seconds = 30\n")
+            .await
+            .unwrap();
+        aa.get_token("coco_as").await.unwrap();
+        assert_eq!(as_requests.load(Ordering::SeqCst), 1);
+
+        // Only the tokens of the service that changed are dropped
+        let other_as_token = jwt(json!({ "iss": "other-as", "exp": now() + 3600 }));
+        let other_as_response = other_as_token.clone();
+        let (other_as_url, other_as_requests) =
+            http_stub::start(move |_| http_stub::response("200 OK", &other_as_response)).await;
+        let update = format!("[token_configs.coco_as]\nurl = \"{other_as_url}\"\n");
+        aa.update_configuration(&update).await.unwrap();
+        assert!(is_kbs_token(aa.get_token("kbs").await.unwrap()));
+        assert_eq!(kbs_requests.load(Ordering::SeqCst), kbs_attested);
+        assert_eq!(
+            aa.get_token("coco_as").await.unwrap(),
+            other_as_token.as_bytes()
+        );
+        assert_eq!(other_as_requests.load(Ordering::SeqCst), 1);
+
+        assert!(aa.evict_token("kbs").await.unwrap());
+        assert!(!aa.evict_token("kbs").await.unwrap());
+        assert!(is_kbs_token(aa.get_token("kbs").await.unwrap()));
+        assert!(kbs_requests.load(Ordering::SeqCst) > kbs_attested);
+        aa.get_token("coco_as").await.unwrap();
+        assert_eq!(other_as_requests.load(Ordering::SeqCst), 1);
+
+        let e = aa.evict_token("unknown").await.unwrap_err();
+        assert!(matches!(e, Error::UnsupportedTokenType(_)), "{e}");
+    }
+}
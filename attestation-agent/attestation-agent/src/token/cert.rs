@@ -0,0 +1,411 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Workload identity certificates bound to attestation. A key pair is
+//! generated in the guest, and the certificate signing request of it is
+//! sent to KBS over the session of the RCAR handshake, s.t. KBS only
+//! issues the certificate to a guest that it attested.
+
+use std::{fmt, sync::Mutex};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use rcgen::{
+    Certificate, CertificateParams, DistinguishedName, DnType, KeyPair, SanType, SignatureAlgorithm,
+};
+use rsa::pkcs8::EncodePrivateKey;
+use x509_parser::pem::Pem;
+use zeroize::Zeroizing;
+
+use super::{
+    kbs::{KbsTokenGetter, PreferredEndpoint},
+    TokenGetter, TokenResponse,
+};
+use crate::config::{
+    cert::{CertConfig, KeyAlgorithm, SubjectAltName},
+    kbs::KbsConfig,
+};
+
+/// The step of getting a certificate that failed.
+#[derive(Debug, thiserror::Error)]
+pub enum CertificateError {
+    #[error("generate the key pair of the certificate")]
+    GenerateKey(#[source] anyhow::Error),
+
+    #[error("build the certificate signing request")]
+    BuildRequest(#[source] anyhow::Error),
+
+    /// The RCAR handshake with KBS failed, s.t. no certificate was
+    /// requested.
+    #[error("attest to KBS")]
+    Attest(#[source] anyhow::Error),
+
+    /// KBS did not issue the certificate of the attested guest.
+    #[error("request the certificate from KBS")]
+    Issue(#[source] kbs_protocol::Error),
+
+    /// The chain KBS answered with is malformed, or is not of the key.
+    #[error("KBS issued an invalid certificate chain")]
+    InvalidChain(#[source] anyhow::Error),
+}
+
+/// The private key of a certificate in PKCS#8 PEM. It is zeroized on drop,
+/// and is not shown by `Debug`.
+#[derive(Clone)]
+pub struct PrivateKey(Zeroizing<String>);
+
+impl PrivateKey {
+    pub fn pem(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for PrivateKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PrivateKey(***)")
+    }
+}
+
+/// Gets a certificate from KBS, as the PEM chain issued for a key pair
+/// generated on each request. The private key of the certificate is got
+/// with [`CertTokenGetter::private_key`].
+pub struct CertTokenGetter {
+    config: CertConfig,
+    kbs: KbsConfig,
+    preferred: PreferredEndpoint,
+    private_key: Mutex<Option<PrivateKey>>,
+}
+
+#[async_trait]
+impl TokenGetter for CertTokenGetter {
+    fn name(&self) -> &str {
+        "cert"
+    }
+
+    async fn get_token(&self) -> Result<TokenResponse> {
+        let request = CertificateRequest::new(&self.config)?;
+        let chain = KbsTokenGetter::new(&self.kbs)
+            .with_preferred(&self.preferred)
+            .with_csr(&self.config.kbs_path, request.csr)
+            .get_token()
+            .await
+            .map_err(|e| {
+                let issued = e.chain().any(|cause| cause.is::<CertificateError>());
+                if issued {
+                    e
+                } else {
+                    CertificateError::Attest(e).into()
+                }
+            })?;
+        check_chain(chain.token.as_bytes(), &request.public_key)
+            .map_err(CertificateError::InvalidChain)?;
+
+        *self.private_key.lock().expect("private key lock poisoned") = Some(request.private_key);
+        Ok(chain)
+    }
+}
+
+impl CertTokenGetter {
+    pub fn new(config: &CertConfig, kbs: &KbsConfig) -> Self {
+        Self {
+            config: config.clone(),
+            kbs: kbs.clone(),
+            preferred: PreferredEndpoint::default(),
+            private_key: Mutex::default(),
+        }
+    }
+
+    /// Share the preferred endpoint with the other getters of the same
+    /// KBS, see [`PreferredEndpoint`].
+    pub fn with_preferred(mut self, preferred: &PreferredEndpoint) -> Self {
+        self.preferred = preferred.clone();
+        self
+    }
+
+    /// The private key of the certificate that `get_token` got last, if
+    /// any.
+    pub fn private_key(&self) -> Option<PrivateKey> {
+        self.private_key
+            .lock()
+            .expect("private key lock poisoned")
+            .clone()
+    }
+}
+
+/// A key pair generated for a certificate, and the request of it.
+struct CertificateRequest {
+    private_key: PrivateKey,
+
+    /// DER SubjectPublicKeyInfo of the key pair
+    public_key: Vec<u8>,
+
+    /// PEM certificate signing request
+    csr: String,
+}
+
+impl CertificateRequest {
+    fn new(config: &CertConfig) -> Result<Self, CertificateError> {
+        let key_pair =
+            generate_key_pair(config.key_algorithm).map_err(CertificateError::GenerateKey)?;
+        let public_key = key_pair.public_key_der();
+        let sans = config
+            .subject_alt_names()
+            .map_err(CertificateError::BuildRequest)?;
+
+        let mut params = CertificateParams::default();
+        params.alg = signature_algorithm(config.key_algorithm);
+        params.distinguished_name = DistinguishedName::new();
+        if let Some(common_name) = &config.common_name {
+            params
+                .distinguished_name
+                .push(DnType::CommonName, common_name.clone());
+        }
+        params.subject_alt_names = sans
+            .into_iter()
+            .map(|san| match san {
+                SubjectAltName::Dns(name) => SanType::DnsName(name),
+                SubjectAltName::Ip(ip) => SanType::IpAddress(ip),
+                SubjectAltName::Uri(uri) => SanType::URI(uri),
+            })
+            .collect();
+        params.key_pair = Some(key_pair);
+
+        let build = || -> Result<(String, PrivateKey)> {
+            let certificate = Certificate::from_params(params)?;
+            let csr = certificate.serialize_request_pem()?;
+            let private_key = Zeroizing::new(certificate.serialize_private_key_pem());
+            Ok((csr, PrivateKey(private_key)))
+        };
+        let (csr, private_key) = build().map_err(CertificateError::BuildRequest)?;
+
+        Ok(Self {
+            private_key,
+            public_key,
+            csr,
+        })
+    }
+}
+
+fn signature_algorithm(algorithm: KeyAlgorithm) -> &'static SignatureAlgorithm {
+    match algorithm {
+        KeyAlgorithm::EcdsaP256 => &rcgen::PKCS_ECDSA_P256_SHA256,
+        KeyAlgorithm::EcdsaP384 => &rcgen::PKCS_ECDSA_P384_SHA384,
+        KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa3072 | KeyAlgorithm::Rsa4096 => {
+            &rcgen::PKCS_RSA_SHA256
+        }
+    }
+}
+
+fn generate_key_pair(algorithm: KeyAlgorithm) -> Result<KeyPair> {
+    let bits = match algorithm {
+        KeyAlgorithm::EcdsaP256 | KeyAlgorithm::EcdsaP384 => {
+            return KeyPair::generate(signature_algorithm(algorithm))
+                .with_context(|| format!("generate {algorithm:?} key pair"));
+        }
+        KeyAlgorithm::Rsa2048 => 2048,
+        KeyAlgorithm::Rsa3072 => 3072,
+        KeyAlgorithm::Rsa4096 => 4096,
+    };
+
+    // ring, which rcgen signs with, cannot generate RSA keys
+    let key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), bits)
+        .with_context(|| format!("generate {bits} bit RSA key"))?;
+    let der = key.to_pkcs8_der().context("encode RSA key")?;
+    KeyPair::from_der_and_sign_algo(der.as_bytes(), signature_algorithm(algorithm))
+        .context("load RSA key")
+}
+
+/// Check that the chain is of PEM certificates, the first one of the key
+/// with the given SubjectPublicKeyInfo.
+fn check_chain(chain: &[u8], public_key: &[u8]) -> Result<()> {
+    let mut pems = Pem::iter_from_buffer(chain);
+    let leaf = pems
+        .next()
+        .context("no PEM certificate in the chain")?
+        .context("malformed PEM")?;
+    let leaf = leaf.parse_x509().context("malformed X.509 certificate")?;
+    if leaf.public_key().raw != public_key {
+        bail!("the first certificate is not of the requested key");
+    }
+
+    for pem in pems {
+        let pem = pem.context("malformed PEM")?;
+        pem.parse_x509().context("malformed X.509 certificate")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use rcgen::{
+        BasicConstraints, Certificate, CertificateParams, CertificateSigningRequest, IsCa,
+    };
+    use rstest::rstest;
+    use x509_parser::{extensions::GeneralName, pem::Pem};
+
+    use super::{CertTokenGetter, CertificateError};
+    use crate::{
+        config::{
+            cert::{CertConfig, KeyAlgorithm},
+            kbs::KbsConfig,
+        },
+        token::{http_stub, TokenGetter},
+    };
+
+    /// A KBS that attests any guest, and issues the certificates from a
+    /// test CA. The certificate requests are answered with
+    /// `issue_status`, the PEM chain if it is `200 OK`.
+    /// A KBS that issues the certificates at `/kbs/v0/{path}`.
+    async fn kbs_with_ca(
+        path: &'static str,
+        issue_status: &'static str,
+        csrs: &Arc<Mutex<Vec<String>>>,
+    ) -> String {
+        let mut params = CertificateParams::new(vec![]);
+        params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(params).unwrap();
+        let csrs = csrs.clone();
+        let (url, _) = http_stub::start_with_request(move |head, body| {
+            if head.contains("/kbs/v0/auth") {
+                return http_stub::response("200 OK", r#"{"nonce":"MTIzNDU2","extra-params":""}"#);
+            }
+            if head.contains("/kbs/v0/attest") {
+                return http_stub::response("200 OK", r#"{"token":"e30.e30.c2ln"}"#);
+            }
+            if !head.starts_with(&format!("POST /kbs/v0/{path} ")) {
+                return http_stub::response("404 Not Found", "");
+            }
+
+            let request: serde_json::Value = serde_json::from_str(body).unwrap();
+            let csr = request["csr"].as_str().unwrap();
+            csrs.lock().unwrap().push(csr.to_string());
+            if !issue_status.starts_with("200") {
+                return http_stub::response(issue_status, "");
+            }
+            let leaf = CertificateSigningRequest::from_pem(csr)
+                .unwrap()
+                .serialize_pem_with_signer(&ca)
+                .unwrap();
+            let chain = format!("{leaf}{}", ca.serialize_pem().unwrap());
+            http_stub::response("200 OK", &chain)
+        })
+        .await;
+        url
+    }
+
+    #[rstest]
+    #[case::ecdsa_p256(KeyAlgorithm::EcdsaP256)]
+    #[case::ecdsa_p384(KeyAlgorithm::EcdsaP384)]
+    #[case::rsa_2048(KeyAlgorithm::Rsa2048)]
+    #[tokio::test]
+    async fn issue_certificate(#[case] key_algorithm: KeyAlgorithm) {
+        let csrs = Arc::new(Mutex::new(Vec::new()));
+        let url = kbs_with_ca("plugin/certificate", "200 OK", &csrs).await;
+        let config = CertConfig {
+            key_algorithm,
+            common_name: Some("workload".into()),
+            sans: vec![
+                "workload.example.org".into(),
+                "10.0.0.1".into(),
+                "spiffe://example.org/workload".into(),
+            ],
+            kbs_path: "plugin/certificate".into(),
+        };
+        let getter = CertTokenGetter::new(&config, &KbsConfig::with_url(url));
+        assert!(getter.private_key().is_none());
+
+        let chain = getter.get_token().await.unwrap();
+        assert_eq!(csrs.lock().unwrap().len(), 1);
+        let pems: Vec<Pem> = Pem::iter_from_buffer(chain.token.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(pems.len(), 2);
+        let leaf = pems[0].parse_x509().unwrap();
+        assert_eq!(leaf.subject().to_string(), "CN=workload");
+        let sans = leaf.subject_alternative_name().unwrap().unwrap();
+        assert_eq!(
+            sans.value.general_names,
+            vec![
+                GeneralName::DNSName("workload.example.org"),
+                GeneralName::IPAddress(&[10, 0, 0, 1]),
+                GeneralName::URI("spiffe://example.org/workload"),
+            ]
+        );
+
+        // The private key is of the certificate
+        let private_key = getter.private_key().unwrap();
+        let key_pair = rcgen::KeyPair::from_pem(private_key.pem()).unwrap();
+        assert_eq!(leaf.public_key().raw, key_pair.public_key_der());
+        assert_eq!(format!("{private_key:?}"), "PrivateKey(***)");
+    }
+
+    #[rstest]
+    #[case::rejected("403 Forbidden", false)]
+    #[case::failing("500 Internal Server Error", true)]
+    #[case::unauthorized("401 Unauthorized", false)]
+    #[tokio::test]
+    async fn issue_failed(#[case] issue_status: &'static str, #[case] retryable: bool) {
+        let csrs = Arc::new(Mutex::new(Vec::new()));
+        let url = kbs_with_ca("certificate", issue_status, &csrs).await;
+        let getter = CertTokenGetter::new(&CertConfig::default(), &KbsConfig::with_url(url));
+
+        let e = getter.get_token().await.unwrap_err();
+        assert!(
+            e.chain().any(|cause| matches!(
+                cause.downcast_ref::<CertificateError>(),
+                Some(CertificateError::Issue(_))
+            )),
+            "{e:#}"
+        );
+        // The status KBS answered with, also after a new handshake
+        assert!(format!("{e:#}").contains(&issue_status[..3]), "{e:#}");
+        assert_eq!(crate::token::is_retryable(&e), retryable);
+        assert!(getter.private_key().is_none());
+    }
+
+    #[tokio::test]
+    async fn attest_failed() {
+        let (url, _) = http_stub::start(|_| http_stub::response("403 Forbidden", "")).await;
+        let getter = CertTokenGetter::new(&CertConfig::default(), &KbsConfig::with_url(url));
+
+        let e = getter.get_token().await.unwrap_err();
+        assert!(
+            matches!(
+                e.downcast_ref::<CertificateError>(),
+                Some(CertificateError::Attest(_))
+            ),
+            "{e:#}"
+        );
+    }
+
+    #[tokio::test]
+    async fn chain_of_other_key() {
+        let other = Certificate::from_params(CertificateParams::new(vec![])).unwrap();
+        let chain = other.serialize_pem().unwrap();
+        let (url, _) = http_stub::start(move |request| {
+            if request.contains("/kbs/v0/auth") {
+                http_stub::response("200 OK", r#"{"nonce":"MTIzNDU2","extra-params":""}"#)
+            } else if request.contains("/kbs/v0/attest") {
+                http_stub::response("200 OK", r#"{"token":"e30.e30.c2ln"}"#)
+            } else {
+                http_stub::response("200 OK", &chain)
+            }
+        })
+        .await;
+        let getter = CertTokenGetter::new(&CertConfig::default(), &KbsConfig::with_url(url));
+
+        let e = getter.get_token().await.unwrap_err();
+        assert!(
+            matches!(
+                e.downcast_ref::<CertificateError>(),
+                Some(CertificateError::InvalidChain(_))
+            ),
+            "{e:#}"
+        );
+        assert!(getter.private_key().is_none());
+    }
+}
@@ -3,59 +3,1046 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use crate::config::coco_as::CoCoASConfig;
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
 
-use super::GetToken;
+use crate::config::coco_as::{
+    load_identity, read_root_certs, service, CoCoASConfig, CoCoASProtocol,
+};
+
+use super::{
+    proxy::ProxyRules, response::jwt_payload, transport::Endpoint, TokenGetter, TokenResponse,
+};
 use anyhow::*;
 use async_trait::async_trait;
 use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use base64::Engine;
+use http_body_util::Full;
+use hyper::body::Bytes;
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE, HOST},
+    StatusCode,
+};
+use serde_json::{json, Map, Value};
+use tracing::{debug, warn};
+
+use crate::{config::HashAlgorithm, telemetry};
+
+#[cfg(feature = "coco_as_grpc")]
+pub(crate) mod grpc;
 
 #[derive(Default)]
 pub struct CoCoASTokenGetter {
     as_uri: String,
+    protocol: Option<CoCoASProtocol>,
+    proxy: Option<String>,
+    no_proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    extra_headers: Vec<(String, String)>,
+    policy_ids: Vec<String>,
+    audience: Option<String>,
+    claims: BTreeMap<String, String>,
+    cert_path: Option<PathBuf>,
+    client_cert: Option<String>,
+    client_key: Option<String>,
+    client_key_passphrase: Option<String>,
+    params: Value,
+    nonce: Option<Vec<u8>>,
 }
 
+/// AS answered the attestation request with an error status.
+#[derive(Debug, thiserror::Error)]
+#[error("Remote Attestation Failed with status {status}, AS Response: {response:?}")]
+pub(crate) struct StatusError {
+    pub(crate) status: u16,
+    response: String,
+}
+
+/// AS does not know a policy that the evidence is to be checked against,
+/// which it answers with `404 Not Found`, or `NOT_FOUND` over gRPC.
+#[derive(Debug, thiserror::Error)]
+#[error("AS does not know the policies {policy_ids:?}, AS Response: {response:?}")]
+pub(crate) struct UnknownPolicyError {
+    pub(crate) policy_ids: Vec<String>,
+    response: String,
+}
+
+/// The nonce of the caller is bound into the report data by its digest
+const NONCE_HASH_ALGORITHM: HashAlgorithm = HashAlgorithm::Sha384;
+
 #[async_trait]
-impl GetToken for CoCoASTokenGetter {
-    async fn get_token(&self) -> Result<Vec<u8>> {
+impl TokenGetter for CoCoASTokenGetter {
+    fn name(&self) -> &str {
+        "coco_as"
+    }
+
+    async fn get_token(&self) -> Result<TokenResponse> {
+        let timer = crate::metrics::Timer::start();
+        let token = self
+            .request_token()
+            .await
+            .and_then(TokenResponse::from_token);
+        crate::metrics::token_request_finished(
+            "coco_as",
+            &self.as_uri,
+            timer.elapsed(),
+            token.as_ref().map(|_| ()),
+        );
+        token
+    }
+}
+
+impl CoCoASTokenGetter {
+    async fn request_token(&self) -> Result<Vec<u8>> {
         let tee_type = attester::detect_tee_type();
         let attester = attester::BoxedAttester::try_from(tee_type)?;
-        let evidence = attester.get_evidence(vec![]).await?;
+        let report_data = self
+            .nonce
+            .as_deref()
+            .map(nonce_report_data)
+            .unwrap_or_default();
+        let timer = crate::metrics::Timer::start();
+        let evidence = attester.get_evidence(report_data).await?;
+        crate::metrics::token_phase_finished("coco_as", &self.as_uri, "evidence", timer.elapsed());
 
-        let request_body = serde_json::json!({
+        let mut request_body = serde_json::json!({
             "tee": serde_json::to_string(&tee_type)?,
             "evidence": URL_SAFE_NO_PAD.encode(evidence.as_bytes()),
         });
+        if let Some(nonce) = &self.nonce {
+            // AS checks the runtime data against the report data and puts
+            // it into the claims of the token
+            request_body["runtime_data"] = json!({ "raw": URL_SAFE_NO_PAD.encode(nonce) });
+            request_body["runtime_data_hash_algorithm"] = NONCE_HASH_ALGORITHM.to_string().into();
+        }
+        merge_params(&mut request_body, &self.params);
+        // The policies of the params of the request come first
+        if !self.policy_ids.is_empty() {
+            if let Value::Object(body) = &mut request_body {
+                body.entry("policy_ids")
+                    .or_insert_with(|| json!(self.policy_ids));
+            }
+        }
+        self.add_token_claims(&mut request_body)?;
+        let policy_ids: Vec<String> = request_body
+            .get("policy_ids")
+            .map(|ids| serde_json::from_value(ids.clone()))
+            .transpose()
+            .context("`policy_ids` is not a list of strings")?
+            .unwrap_or_default();
 
-        let client = reqwest::Client::new();
-        let attest_endpoint = format!("{}/attestation", self.as_uri);
-        let res = client
-            .post(attest_endpoint)
-            .header("Content-Type", "application/json")
-            .json(&request_body)
-            .send()
-            .await?;
+        let (protocol, as_uri) = service(&self.as_uri, self.protocol)?;
+        let endpoint = Endpoint::parse(&as_uri)?;
+        let timer = crate::metrics::Timer::start();
+        let token = match protocol {
+            CoCoASProtocol::Rest => {
+                let token = self
+                    .attest(&as_uri, &endpoint, &request_body, policy_ids)
+                    .await;
+                if let Result::Ok(token) = &token {
+                    warn_ignored_claims(token, &request_body);
+                }
+                token
+            }
+            #[cfg(feature = "coco_as_grpc")]
+            CoCoASProtocol::Grpc => {
+                self.attest_grpc(&as_uri, endpoint, &request_body, policy_ids)
+                    .await
+            }
+            #[cfg(not(feature = "coco_as_grpc"))]
+            CoCoASProtocol::Grpc => {
+                bail!("the gRPC API of AS needs AA built with the `coco_as_grpc` feature")
+            }
+        };
+        crate::metrics::token_phase_finished("coco_as", &self.as_uri, "attest", timer.elapsed());
+        token
+    }
 
-        match res.status() {
-            reqwest::StatusCode::OK => {
-                let token = res.text().await?;
-                Ok(token.as_bytes().to_vec())
+    pub fn new(config: &CoCoASConfig) -> Self {
+        Self {
+            as_uri: config.url.clone(),
+            protocol: config.protocol,
+            proxy: config.proxy.as_deref().map(str::to_string),
+            no_proxy: config.no_proxy.clone(),
+            connect_timeout: crate::config::timeout(config.connect_timeout_seconds),
+            request_timeout: crate::config::timeout(config.request_timeout_seconds),
+            extra_headers: config
+                .extra_headers
+                .iter()
+                .map(|(name, value)| (name.clone(), str::to_string(value)))
+                .collect(),
+            policy_ids: config.policy_ids.clone(),
+            audience: config.audience.clone(),
+            claims: config.claims.clone(),
+            cert_path: config.cert_path.clone(),
+            client_cert: config.client_cert.clone(),
+            client_key: config.client_key.as_deref().map(str::to_string),
+            client_key_passphrase: config.client_key_passphrase.as_deref().map(str::to_string),
+            params: Value::Null,
+            nonce: None,
+        }
+    }
+
+    /// Request the token with the REST API of AS at `as_uri`.
+    async fn attest(
+        &self,
+        as_uri: &str,
+        endpoint: &Endpoint,
+        request_body: &Value,
+        policy_ids: Vec<String>,
+    ) -> Result<Vec<u8>> {
+        let (status, response) = match endpoint {
+            Endpoint::Tcp => {
+                let res = self
+                    .http_client()?
+                    .post(format!("{as_uri}/attestation"))
+                    .header(CONTENT_TYPE, "application/json")
+                    .json(request_body)
+                    .send()
+                    .await?;
+                (res.status(), res.text().await?)
             }
             _ => {
-                bail!(
-                    "Remote Attestation Failed, AS Response: {:?}",
-                    res.text().await?
-                );
+                let mut request = hyper::Request::post("/attestation")
+                    .header(HOST, "localhost")
+                    .header(CONTENT_TYPE, "application/json");
+                for (name, value) in &self.header_map()? {
+                    request = request.header(name, value);
+                }
+                let request = request
+                    .body(Full::new(Bytes::from(serde_json::to_vec(request_body)?)))
+                    .context("build AS request")?;
+                let res = endpoint
+                    .send(as_uri, request, self.connect_timeout, self.request_timeout)
+                    .await?;
+                let status = res.status();
+                let response = String::from_utf8_lossy(res.body()).into_owned();
+                (status, response)
+            }
+        };
+
+        match status {
+            StatusCode::OK => Ok(response.into_bytes()),
+            status => {
+                if status == StatusCode::NOT_FOUND && !policy_ids.is_empty() {
+                    bail!(UnknownPolicyError {
+                        policy_ids,
+                        response,
+                    });
+                }
+                let e = anyhow!(StatusError {
+                    status: status.as_u16(),
+                    response,
+                });
+                // The status does not tell whether they are the cause
+                if status.is_client_error() && requests_token_claims(request_body) {
+                    return Err(e.context(
+                        "AS rejected the request with `audience` or `claims`, which its API may not support",
+                    ));
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// The HTTP client to AS, honoring the proxy environment variables
+    /// unless a proxy is configured.
+    fn http_client(&self) -> Result<reqwest::Client> {
+        self.http_client_builder()?
+            .build()
+            .context("build AS http client")
+    }
+
+    /// The builder of [`Self::http_client`]. The root certs and the client
+    /// certificate are read on each request, s.t. renewed ones are picked
+    /// up.
+    fn http_client_builder(&self) -> Result<reqwest::ClientBuilder> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        builder = builder.default_headers(self.header_map()?);
+
+        let rules = ProxyRules::new(self.proxy.as_deref(), self.no_proxy.as_deref())?;
+        builder = builder.proxy(rules.into_proxy());
+
+        if let Some(bundle) = read_root_certs(self.cert_path.as_deref())? {
+            for cert in reqwest::Certificate::from_pem_bundle(bundle.as_bytes())
+                .context("invalid AS root certs")?
+            {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        let identity = load_identity(
+            self.client_cert.as_deref(),
+            self.client_key.as_deref(),
+            self.client_key_passphrase.as_deref(),
+        )
+        .context("load AS client certificate")?;
+        if let Some(identity) = identity {
+            builder = builder.identity(identity);
+        }
+
+        Ok(builder)
+    }
+
+    /// The `extra_headers` to send with the request, see
+    /// [`kbs_protocol::header_map`].
+    fn header_map(&self) -> Result<HeaderMap> {
+        kbs_protocol::header_map(
+            self.extra_headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )
+    }
+
+    /// Extra parameters merged into the attestation request, e.g. the
+    /// `policy_ids` to check the evidence against.
+    pub fn with_params(mut self, params: Value) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Set the `audience` and the `claims` of the token in the request
+    /// body, the ones of the params first.
+    fn add_token_claims(&self, body: &mut Value) -> Result<()> {
+        let Value::Object(body) = body else {
+            return Ok(());
+        };
+
+        if let Some(audience) = &self.audience {
+            body.entry("audience")
+                .or_insert_with(|| audience.as_str().into());
+        }
+        if body
+            .get("audience")
+            .is_some_and(|audience| !audience.is_string())
+        {
+            bail!("`audience` is not a string");
+        }
+
+        let mut claims: Map<String, Value> = self
+            .claims
+            .iter()
+            .map(|(name, value)| (name.clone(), value.as_str().into()))
+            .collect();
+        match body.get("claims") {
+            None => {}
+            Some(Value::Object(params)) => {
+                for (name, value) in params {
+                    if !value.is_string() {
+                        bail!("the claim `{name}` is not a string");
+                    }
+                    claims.insert(name.clone(), value.clone());
+                }
             }
+            Some(_) => bail!("`claims` is not a map of strings"),
         }
+        if !claims.is_empty() {
+            body.insert("claims".into(), Value::Object(claims));
+        }
+        Ok(())
+    }
+
+    /// Bind a nonce of the caller into the evidence. The SHA-384 digest of
+    /// the nonce is the report data, and the nonce is sent to AS as the raw
+    /// runtime data.
+    pub fn with_nonce(mut self, nonce: Option<Vec<u8>>) -> Self {
+        self.nonce = nonce;
+        self
     }
 }
 
-impl CoCoASTokenGetter {
-    pub fn new(config: &CoCoASConfig) -> Self {
-        Self {
-            as_uri: config.url.clone(),
+/// The report data of the evidence that binds the nonce of the caller,
+/// s.t. `SHA-384(nonce)`. The nonce itself is sent as the raw runtime data
+/// for AS to recompute it.
+fn nonce_report_data(nonce: &[u8]) -> Vec<u8> {
+    NONCE_HASH_ALGORITHM.digest(nonce)
+}
+
+/// Whether the request asks for an audience or custom claims of the token.
+fn requests_token_claims(body: &Value) -> bool {
+    body.get("audience").is_some() || body.get("claims").is_some()
+}
+
+/// Warn about the requested audience and claims that are not in the token,
+/// as AS ignored them, s.t. it is told why the verifiers reject the token.
+/// Opaque tokens are not checked.
+fn warn_ignored_claims(token: &[u8], body: &Value) {
+    if !requests_token_claims(body) {
+        return;
+    }
+    let Some(payload) = std::str::from_utf8(token).ok().and_then(jwt_payload) else {
+        debug!(target: telemetry::TOKEN, "Token is not a JWT, its audience and claims are not checked");
+        return;
+    };
+
+    if let Some(audience) = body.get("audience").and_then(Value::as_str) {
+        let granted = match payload.get("aud") {
+            Some(Value::String(aud)) => aud == audience,
+            Some(Value::Array(aud)) => aud.iter().any(|aud| aud.as_str() == Some(audience)),
+            _ => false,
+        };
+        if !granted {
+            warn!(target: telemetry::TOKEN, audience, "AS ignored the requested audience, the token is not for it");
         }
     }
+
+    if let Some(Value::Object(claims)) = body.get("claims") {
+        let ignored: Vec<&str> = claims
+            .iter()
+            .filter(|(name, value)| payload.get(name.as_str()) != Some(value))
+            .map(|(name, _)| name.as_str())
+            .collect();
+        if !ignored.is_empty() {
+            warn!(target: telemetry::TOKEN, claims = ?ignored, "AS ignored the requested claims, the token does not carry them");
+        }
+    }
+}
+
+/// Add the fields of `params` to the request body. Unknown fields are passed
+/// through as is, while the fields set by AA, e.g. the evidence, are kept.
+fn merge_params(body: &mut Value, params: &Value) {
+    let (Value::Object(body), Value::Object(params)) = (body, params) else {
+        return;
+    };
+
+    for (key, value) in params {
+        body.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::Permissions,
+        os::unix::fs::PermissionsExt,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use kbs_types::Tee;
+    use rstest::rstest;
+    use serde_json::{json, Value};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+    use tokio_rustls::{
+        rustls::{server::WebPkiClientVerifier, RootCertStore, ServerConfig},
+        TlsAcceptor,
+    };
+
+    use super::{merge_params, nonce_report_data, CoCoASTokenGetter};
+    use crate::{
+        config::coco_as::CoCoASConfig,
+        token::{http_stub, TokenGetter},
+        AttestationAPIs, AttestationAgent, Config, Error,
+    };
+
+    #[tokio::test]
+    async fn request_through_proxy() {
+        let (proxy, requests) = http_stub::start(|_| http_stub::response("200 OK", "token")).await;
+        let config = |no_proxy: Option<&str>| CoCoASConfig {
+            proxy: Some(proxy.clone()),
+            no_proxy: no_proxy.map(ToString::to_string),
+            ..CoCoASConfig::with_url("http://127.0.0.1:1")
+        };
+
+        let token = CoCoASTokenGetter::new(&config(None))
+            .get_token()
+            .await
+            .unwrap();
+        assert_eq!(token.token, "token");
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Connected directly, where nothing listens
+        CoCoASTokenGetter::new(&config(Some("127.0.0.1")))
+            .get_token()
+            .await
+            .unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn request_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("as.sock");
+        let requests = http_stub::start_unix(&socket, |request| {
+            assert!(request.starts_with("POST /attestation "), "{request}");
+            http_stub::response("200 OK", "token")
+        })
+        .await;
+        let config = CoCoASConfig {
+            // Not used for the socket
+            proxy: Some("http://127.0.0.1:1".into()),
+            ..CoCoASConfig::with_url(format!("unix://{}", socket.display()))
+        };
+
+        let getter = CoCoASTokenGetter::new(&config);
+        assert_eq!(getter.get_token().await.unwrap().token, "token");
+        assert_eq!(getter.get_token().await.unwrap().token, "token");
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    /// Serve a token over TLS to the clients with a certificate of the test
+    /// CA, at the returned URL.
+    async fn start_mtls_server() -> String {
+        let ca = include_bytes!("../../test/tls/ca.pem");
+        let mut roots = RootCertStore::empty();
+        for cert in rustls_pemfile::certs(&mut &ca[..]) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+            .build()
+            .unwrap();
+        let certs = rustls_pemfile::certs(&mut &include_bytes!("../../test/tls/server.pem")[..])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let key =
+            rustls_pemfile::private_key(&mut &include_bytes!("../../test/tls/server.key")[..])
+                .unwrap()
+                .unwrap();
+        let config = ServerConfig::builder()
+            .with_client_cert_verifier(verifier)
+            .with_single_cert(certs, key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("https://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    // The handshake fails for the clients without certificate
+                    let Ok(mut stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+                    let mut request = vec![0; 8192];
+                    let _ = stream.read(&mut request).await;
+                    let response =
+                        "HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\ntoken";
+                    let _ = stream.write_all(response.as_bytes()).await;
+                    let _ = stream.shutdown().await;
+                });
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn request_with_client_certificate() {
+        let url = start_mtls_server().await;
+        let config = CoCoASConfig {
+            cert_path: Some("test/tls/ca.pem".into()),
+            client_cert: Some("test/tls/client.pem".into()),
+            client_key: Some("test/tls/client.key".into()),
+            ..CoCoASConfig::with_url(&url)
+        };
+        let getter = CoCoASTokenGetter::new(&config);
+        assert_eq!(getter.get_token().await.unwrap().token, "token");
+
+        // The key given inline, encrypted with the passphrase
+        let config = CoCoASConfig {
+            client_key: Some(include_str!("../../test/tls/client.encrypted.key").into()),
+            client_key_passphrase: Some("aa-test-passphrase".into()),
+            ..config
+        };
+        let getter = CoCoASTokenGetter::new(&config);
+        assert_eq!(getter.get_token().await.unwrap().token, "token");
+
+        // The server is not trusted without its root cert
+        let untrusted = CoCoASConfig {
+            cert_path: None,
+            ..config.clone()
+        };
+        CoCoASTokenGetter::new(&untrusted)
+            .get_token()
+            .await
+            .unwrap_err();
+
+        // The server rejects the handshake of a client without certificate
+        let config = CoCoASConfig {
+            client_cert: None,
+            client_key: None,
+            client_key_passphrase: None,
+            ..config
+        };
+        let e = CoCoASTokenGetter::new(&config)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(e.chain().any(|cause| cause.is::<reqwest::Error>()), "{e:#}");
+    }
+
+    #[tokio::test]
+    async fn stalled_service_times_out() {
+        let url = http_stub::start_stalled().await;
+        let config = CoCoASConfig {
+            request_timeout_seconds: 1,
+            ..CoCoASConfig::with_url(url)
+        };
+
+        let e = tokio::time::timeout(
+            Duration::from_secs(10),
+            CoCoASTokenGetter::new(&config).get_token(),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+        assert!(crate::token::is_retryable(&e), "{e:#}");
+    }
+
+    #[tokio::test]
+    async fn retry_unavailable_service() {
+        let config = |url: &str, max_attempts: Option<u32>| {
+            let max_attempts = max_attempts
+                .map(|attempts| format!("max_attempts = {attempts}"))
+                .unwrap_or_default();
+            Config::from_toml_str(&format!(
+                r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "{url}"
+{max_attempts}
+
+[retry]
+initial_backoff_ms = 10
+jitter = false
+
+[eventlog_config]
+enabled = false
+"#
+            ))
+            .unwrap()
+        };
+        let unavailable_twice = || {
+            let requests = AtomicUsize::new(0);
+            move |_: &str| match requests.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => http_stub::response("503 Service Unavailable", "down"),
+                _ => http_stub::response("200 OK", "token"),
+            }
+        };
+
+        let (url, requests) = http_stub::start(unavailable_twice()).await;
+        let aa = AttestationAgent::with_config_and_tee(config(&url, Some(3)), Tee::Sample).unwrap();
+        assert_eq!(aa.get_token("coco_as").await.unwrap(), b"token");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+
+        // Not retried unless opted in
+        let (url, requests) = http_stub::start(unavailable_twice()).await;
+        let aa = AttestationAgent::with_config_and_tee(config(&url, None), Tee::Sample).unwrap();
+        aa.get_token("coco_as").await.unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // The error tells each failure
+        let (url, requests) = http_stub::start(unavailable_twice()).await;
+        let aa = AttestationAgent::with_config_and_tee(config(&url, Some(2)), Tee::Sample).unwrap();
+        let e = aa.get_token("coco_as").await.unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+        let Error::TokenFetch { source, .. } = e else {
+            panic!("unexpected error {e:?}");
+        };
+        let e = format!("{source:#}");
+        assert!(e.contains("failed after 2 attempts"), "{e}");
+        assert!(
+            e.contains("attempt 1: Remote Attestation Failed with status 503"),
+            "{e}"
+        );
+    }
+
+    /// Start an AS stub that answers with `status` and `response`, and
+    /// records the bodies of the requests.
+    async fn recording_stub(
+        status: &'static str,
+        response: &'static str,
+    ) -> (String, Arc<Mutex<Vec<Value>>>) {
+        let bodies = Arc::new(Mutex::new(Vec::new()));
+        let recorded = bodies.clone();
+        let (url, _) = http_stub::start_with_request(move |_, body| {
+            recorded
+                .lock()
+                .unwrap()
+                .push(serde_json::from_str(body).unwrap());
+            http_stub::response(status, response)
+        })
+        .await;
+        (url, bodies)
+    }
+
+    #[rstest]
+    #[case::none(vec![], Value::Null, None)]
+    #[case::configured(vec!["a", "b"], Value::Null, Some(json!(["a", "b"])))]
+    #[case::per_call(vec!["a", "b"], json!({"policy_ids": ["c"]}), Some(json!(["c"])))]
+    #[case::per_call_only(vec![], json!({"policy_ids": ["c"]}), Some(json!(["c"])))]
+    #[tokio::test]
+    async fn request_policy_ids(
+        #[case] configured: Vec<&str>,
+        #[case] params: Value,
+        #[case] expected: Option<Value>,
+    ) {
+        let (url, bodies) = recording_stub("200 OK", "token").await;
+        let config = CoCoASConfig {
+            policy_ids: configured.iter().map(ToString::to_string).collect(),
+            ..CoCoASConfig::with_url(url)
+        };
+
+        CoCoASTokenGetter::new(&config)
+            .with_params(params)
+            .get_token()
+            .await
+            .unwrap();
+        let body = bodies.lock().unwrap().pop().unwrap();
+        assert_eq!(body.get("policy_ids"), expected.as_ref());
+
+        // Without policies, the request is the same as before they could be
+        // configured
+        if expected.is_none() {
+            let mut keys: Vec<&String> = body.as_object().unwrap().keys().collect();
+            keys.sort();
+            assert_eq!(keys, ["evidence", "tee"]);
+        }
+    }
+
+    #[rstest]
+    #[case::none(None, vec![], Value::Null, None, None)]
+    #[case::configured(
+        Some("https://a.example.com"),
+        vec![("tenant", "a")],
+        Value::Null,
+        Some(json!("https://a.example.com")),
+        Some(json!({"tenant": "a"}))
+    )]
+    #[case::per_call(
+        Some("https://a.example.com"),
+        vec![("tenant", "a"), ("zone", "1")],
+        json!({"audience": "https://b.example.com", "claims": {"tenant": "b"}}),
+        Some(json!("https://b.example.com")),
+        Some(json!({"tenant": "b", "zone": "1"}))
+    )]
+    #[case::per_call_only(
+        None,
+        vec![],
+        json!({"claims": {"tenant": "b"}}),
+        None,
+        Some(json!({"tenant": "b"}))
+    )]
+    #[tokio::test]
+    async fn request_audience_and_claims(
+        #[case] audience: Option<&str>,
+        #[case] claims: Vec<(&str, &str)>,
+        #[case] params: Value,
+        #[case] expected_audience: Option<Value>,
+        #[case] expected_claims: Option<Value>,
+    ) {
+        let (url, bodies) = recording_stub("200 OK", "token").await;
+        let config = CoCoASConfig {
+            audience: audience.map(ToString::to_string),
+            claims: claims
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            ..CoCoASConfig::with_url(url)
+        };
+
+        CoCoASTokenGetter::new(&config)
+            .with_params(params)
+            .get_token()
+            .await
+            .unwrap();
+        let body = bodies.lock().unwrap().pop().unwrap();
+        assert_eq!(body.get("audience"), expected_audience.as_ref());
+        assert_eq!(body.get("claims"), expected_claims.as_ref());
+    }
+
+    #[rstest]
+    #[case::audience(json!({"audience": 1}))]
+    #[case::claims(json!({"claims": ["tenant"]}))]
+    #[case::claim(json!({"claims": {"tenant": 1}}))]
+    #[tokio::test]
+    async fn invalid_audience_and_claims(#[case] params: Value) {
+        let (url, bodies) = recording_stub("200 OK", "token").await;
+        CoCoASTokenGetter::new(&CoCoASConfig::with_url(url))
+            .with_params(params)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(bodies.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejected_audience() {
+        let (url, _) = recording_stub("400 Bad Request", "unknown field `audience`").await;
+        let config = CoCoASConfig {
+            audience: Some("https://a.example.com".into()),
+            ..CoCoASConfig::with_url(url)
+        };
+
+        let e = CoCoASTokenGetter::new(&config)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(
+            e.to_string()
+                .contains("AS rejected the request with `audience` or `claims`"),
+            "{e:#}"
+        );
+        assert!(format!("{e:#}").contains("status 400"), "{e:#}");
+        assert!(!crate::token::is_retryable(&e));
+
+        // Not without requesting them
+        let (url, _) = recording_stub("400 Bad Request", "unknown field `audience`").await;
+        let e = CoCoASTokenGetter::new(&CoCoASConfig::with_url(url))
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(!e.to_string().contains("AS rejected"), "{e:#}");
+    }
+
+    #[tokio::test]
+    async fn tokens_are_cached_per_audience() {
+        let expires_at = crate::token::cache::now() + 3600;
+        let claims = URL_SAFE_NO_PAD.encode(json!({ "exp": expires_at }).to_string());
+        let jwt: &'static str = Box::leak(format!("e30.{claims}.c2ln").into_boxed_str());
+        let (url, bodies) = recording_stub("200 OK", jwt).await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs]
+cache = true
+
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "{url}"
+audience = "https://default.example.com"
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+
+        for audience in ["https://a.example.com", "https://b.example.com"] {
+            for _ in 0..2 {
+                aa.get_token_with_params("coco_as", json!({ "audience": audience }))
+                    .await
+                    .unwrap();
+            }
+        }
+        aa.get_token("coco_as").await.unwrap();
+        aa.get_token("coco_as").await.unwrap();
+
+        let audiences: Vec<Value> = bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|body| body["audience"].clone())
+            .collect();
+        assert_eq!(
+            audiences,
+            [
+                "https://a.example.com",
+                "https://b.example.com",
+                "https://default.example.com"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_policy() {
+        let (url, bodies) = recording_stub("404 Not Found", "no such policy").await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "{url}"
+policy_ids = ["workload"]
+
+[retry]
+max_attempts = 3
+initial_backoff_ms = 10
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        let e = aa.get_token("coco_as").await.unwrap_err();
+        let Error::UnknownPolicy { policy_ids, .. } = e else {
+            panic!("unexpected error {e:?}");
+        };
+        assert_eq!(policy_ids, ["workload"]);
+        assert_eq!(bodies.lock().unwrap().len(), 1);
+
+        // Other errors that mention a policy keep their status
+        let (url, _) = recording_stub("400 Bad Request", "Policy `workload` not found").await;
+        let config = CoCoASConfig {
+            policy_ids: vec!["workload".into()],
+            ..CoCoASConfig::with_url(url)
+        };
+        let e = CoCoASTokenGetter::new(&config)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(e.is::<super::StatusError>(), "{e:#}");
+    }
+
+    #[tokio::test]
+    async fn extra_headers_survive_retries() {
+        let dir = tempfile::tempdir().unwrap();
+        let api_key = dir.path().join("api-key");
+        std::fs::write(&api_key, "key-1\n").unwrap();
+        std::fs::set_permissions(&api_key, Permissions::from_mode(0o600)).unwrap();
+
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let recorded = heads.clone();
+        let (url, _) = http_stub::start_with_request(move |head, _| {
+            let mut heads = recorded.lock().unwrap();
+            heads.push(head.to_lowercase());
+            if heads.len() == 1 {
+                return http_stub::response("503 Service Unavailable", "down");
+            }
+            http_stub::response("200 OK", "token")
+        })
+        .await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "{url}"
+
+[token_configs.coco_as.extra_headers]
+X-Tenant-Id = "tenant-a"
+X-Api-Key = "file://{}"
+
+[retry]
+max_attempts = 2
+initial_backoff_ms = 10
+
+[eventlog_config]
+enabled = false
+"#,
+            api_key.display()
+        ))
+        .unwrap();
+
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        assert_eq!(aa.get_token("coco_as").await.unwrap(), b"token");
+        let heads = heads.lock().unwrap();
+        assert_eq!(heads.len(), 2);
+        for head in heads.iter() {
+            assert!(
+                head.lines().any(|line| line == "x-tenant-id: tenant-a"),
+                "{head}"
+            );
+            assert!(
+                head.lines().any(|line| line == "x-api-key: key-1"),
+                "{head}"
+            );
+        }
+    }
+
+    #[cfg(feature = "metrics")]
+    #[tokio::test]
+    async fn metrics_of_token_requests() {
+        let (url, _) = http_stub::start(|_| http_stub::response("200 OK", "token")).await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs]
+cache = true
+
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "{url}"
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        assert_eq!(aa.get_token("coco_as").await.unwrap(), b"token");
+        assert_eq!(aa.get_token("coco_as").await.unwrap(), b"token");
+
+        // Each stub listens on its own port
+        let endpoint = url.trim_start_matches("http://");
+        let labels = format!("token_type=\"coco_as\",endpoint=\"{endpoint}\"");
+        let text = crate::metrics::gather();
+        for line in [
+            format!("aa_token_requests_total{{{labels},result=\"success\"}} 1"),
+            format!("aa_token_request_seconds_count{{{labels}}} 1"),
+            format!("aa_token_request_phase_seconds_count{{{labels},phase=\"evidence\"}} 1"),
+            format!("aa_token_request_phase_seconds_count{{{labels},phase=\"attest\"}} 1"),
+            format!("aa_token_cache_lookups_total{{{labels},result=\"miss\"}} 1"),
+            format!("aa_token_cache_lookups_total{{{labels},result=\"hit\"}} 1"),
+        ] {
+            assert!(text.contains(&format!("{line}\n")), "{line} not in {text}");
+        }
+
+        // A failure is counted by the class of its error
+        let (url, _) =
+            http_stub::start(|_| http_stub::response("403 Forbidden", "policy denied")).await;
+        CoCoASTokenGetter::new(&CoCoASConfig::with_url(&url))
+            .get_token()
+            .await
+            .unwrap_err();
+        let endpoint = url.trim_start_matches("http://");
+        let line = format!(
+            "aa_token_requests_total{{token_type=\"coco_as\",endpoint=\"{endpoint}\",result=\"rejected\"}} 1\n"
+        );
+        let text = crate::metrics::gather();
+        assert!(text.contains(&line), "{line} not in {text}");
+    }
+
+    #[test]
+    fn merge_request_params() {
+        let mut body = json!({"tee": "\"tdx\"", "evidence": "ZXZpZGVuY2U"});
+        merge_params(
+            &mut body,
+            &json!({"policy_ids": ["default"], "evidence": "forged", "future": {"a": 1}}),
+        );
+        assert_eq!(
+            body,
+            json!({
+                "tee": "\"tdx\"",
+                "evidence": "ZXZpZGVuY2U",
+                "policy_ids": ["default"],
+                "future": {"a": 1},
+            })
+        );
+    }
+
+    #[test]
+    fn nonce_is_hashed_into_report_data() {
+        let report_data = nonce_report_data(b"session-1");
+        assert_eq!(report_data.len(), 48);
+        assert_eq!(
+            report_data,
+            crate::config::HashAlgorithm::Sha384.digest(b"session-1")
+        );
+        assert_ne!(report_data, nonce_report_data(b"session-2"));
+    }
 }
@@ -0,0 +1,452 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! The gRPC API of CoCo AS, see `protos/attestation-service.proto`. The
+//! token is requested with the same evidence, runtime data and policies as
+//! with the REST API, and returned as is.
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::Value;
+use tonic::{
+    metadata::{Ascii, MetadataKey, MetadataValue},
+    transport::{Certificate, ClientTlsConfig, Endpoint, Identity, Uri},
+    Code, Request,
+};
+
+use super::{requests_token_claims, CoCoASTokenGetter, UnknownPolicyError};
+use crate::{
+    config::coco_as::{load_identity_pem, read_root_certs},
+    token::transport,
+};
+
+pub(crate) mod proto {
+    tonic::include_proto!("attestation");
+}
+
+use proto::{
+    attestation_request::{InitData, RuntimeData},
+    attestation_service_client::AttestationServiceClient,
+    AttestationRequest,
+};
+
+/// AS answered the attestation request with an error status.
+#[derive(Debug, thiserror::Error)]
+#[error("Remote Attestation Failed with gRPC status {code:?}, AS Response: {message:?}")]
+pub(crate) struct StatusError {
+    code: Code,
+    message: String,
+}
+
+impl StatusError {
+    /// AS is unavailable or overloaded, or the request timed out, which
+    /// tonic tells as cancelled.
+    pub(crate) fn is_retryable(&self) -> bool {
+        matches!(
+            self.code,
+            Code::Unavailable | Code::ResourceExhausted | Code::DeadlineExceeded | Code::Cancelled
+        )
+    }
+}
+
+impl CoCoASTokenGetter {
+    /// Request the token with the gRPC API of AS at `as_uri`, connected to
+    /// as told by `socket`. The fields of the REST `request_body` that the
+    /// gRPC request has no field for are not sent.
+    pub(super) async fn attest_grpc(
+        &self,
+        as_uri: &str,
+        socket: transport::Endpoint,
+        request_body: &Value,
+        policy_ids: Vec<String>,
+    ) -> Result<Vec<u8>> {
+        // Rather than AS silently issuing a token without them
+        if requests_token_claims(request_body) {
+            bail!("the gRPC API of AS takes neither `audience` nor `claims`");
+        }
+        let mut request = Request::new(attestation_request(request_body, &policy_ids)?);
+        // The headers of the HTTP crate of tonic are not the ones of
+        // reqwest, s.t. they are copied into the metadata
+        for (name, value) in &self.header_map()? {
+            let key = MetadataKey::<Ascii>::from_bytes(name.as_str().as_bytes())
+                .with_context(|| format!("invalid metadata name `{name}`"))?;
+            let mut value = MetadataValue::try_from(value.as_bytes())
+                .map_err(|_| anyhow!("invalid value of header `{name}`"))?;
+            value.set_sensitive(true);
+            request.metadata_mut().append(key, value);
+        }
+
+        let channel = match socket {
+            transport::Endpoint::Tcp => {
+                self.grpc_endpoint(as_uri, self.grpc_tls_config()?)?
+                    .connect()
+                    .await?
+            }
+            // The URI of the endpoint is not connected to, but is the
+            // authority of the requests
+            socket => {
+                let as_uri = as_uri.to_string();
+                self.grpc_endpoint("http://localhost", ClientTlsConfig::new())?
+                    .connect_with_connector(tower::service_fn(move |_: Uri| {
+                        let socket = socket.clone();
+                        let as_uri = as_uri.clone();
+                        async move { socket.connect(&as_uri, None).await }
+                    }))
+                    .await?
+            }
+        };
+        match AttestationServiceClient::new(channel)
+            .attestation_evaluate(request)
+            .await
+        {
+            Ok(response) => Ok(response.into_inner().attestation_token.into_bytes()),
+            Err(status) => {
+                let message = status.message().to_string();
+                if status.code() == Code::NotFound && !policy_ids.is_empty() {
+                    bail!(UnknownPolicyError {
+                        policy_ids,
+                        response: message,
+                    });
+                }
+                bail!(StatusError {
+                    code: status.code(),
+                    message,
+                });
+            }
+        }
+    }
+
+    /// The TLS config of the channel to AS, with the root certs and the
+    /// client certificate for mutual TLS, which are read on each request as
+    /// with the REST API.
+    fn grpc_tls_config(&self) -> Result<ClientTlsConfig> {
+        let mut tls = ClientTlsConfig::new();
+        if let Some(bundle) = read_root_certs(self.cert_path.as_deref())? {
+            tls = tls.ca_certificate(Certificate::from_pem(bundle));
+        }
+        let identity = load_identity_pem(
+            self.client_cert.as_deref(),
+            self.client_key.as_deref(),
+            self.client_key_passphrase.as_deref(),
+        )
+        .context("load AS client certificate")?;
+        if let Some((cert, key)) = identity {
+            tls = tls.identity(Identity::from_pem(cert, key));
+        }
+        Ok(tls)
+    }
+
+    /// The endpoint of AS, over TLS with `tls` for an `https://` URL.
+    fn grpc_endpoint(&self, as_uri: &str, tls: ClientTlsConfig) -> Result<Endpoint> {
+        // The error of the URL would show the credentials in it
+        let mut endpoint =
+            Endpoint::from_shared(as_uri.to_string()).map_err(|_| anyhow!("invalid AS URL"))?;
+        if let Some(timeout) = self.connect_timeout {
+            endpoint = endpoint.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if as_uri.starts_with("https://") {
+            // Not kept as the source, as it would be taken for a failure to
+            // connect
+            endpoint = endpoint
+                .tls_config(tls)
+                .map_err(|e| anyhow!("configure TLS to AS: {e}"))?;
+        }
+        Ok(endpoint)
+    }
+}
+
+/// The gRPC request with the fields of the REST request body, where the
+/// TEE is a JSON string and the runtime and init data are `{"raw": ..}` or
+/// `{"structured": ..}`.
+fn attestation_request(body: &Value, policy_ids: &[String]) -> Result<AttestationRequest> {
+    let field = |name: &str| body.get(name).and_then(Value::as_str).unwrap_or_default();
+    let tee = serde_json::from_str(field("tee")).context("`tee` is not a JSON string")?;
+
+    let runtime_data = data(body, "runtime_data")?.map(|(raw, data)| match raw {
+        true => RuntimeData::RawRuntimeData(data),
+        false => RuntimeData::StructuredRuntimeData(data),
+    });
+    let init_data = data(body, "init_data")?.map(|(raw, data)| match raw {
+        true => InitData::RawInitData(data),
+        false => InitData::StructuredInitData(data),
+    });
+
+    Ok(AttestationRequest {
+        tee,
+        evidence: field("evidence").to_string(),
+        runtime_data,
+        init_data,
+        runtime_data_hash_algorithm: field("runtime_data_hash_algorithm").to_string(),
+        init_data_hash_algorithm: field("init_data_hash_algorithm").to_string(),
+        policy_ids: policy_ids.to_vec(),
+    })
+}
+
+/// Whether the data at `key` is raw, with the raw data in base64url or the
+/// structured data as a JSON document.
+fn data(body: &Value, key: &str) -> Result<Option<(bool, String)>> {
+    let Some(data) = body.get(key) else {
+        return Ok(None);
+    };
+    match (data.get("raw"), data.get("structured")) {
+        (Some(Value::String(raw)), None) => Ok(Some((true, raw.clone()))),
+        (None, Some(structured)) => Ok(Some((false, structured.to_string()))),
+        _ => bail!("`{key}` is neither `{{\"raw\": ..}}` nor `{{\"structured\": ..}}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use kbs_types::Tee;
+    use serde_json::json;
+    use tokio::net::{TcpListener, UnixListener};
+    use tokio_stream::wrappers::{TcpListenerStream, UnixListenerStream};
+    use tonic::{
+        transport::{Certificate, Identity, Server, ServerTlsConfig},
+        Request, Response, Status,
+    };
+
+    use super::{
+        attestation_request,
+        proto::{
+            attestation_request::{InitData, RuntimeData},
+            AttestationRequest,
+        },
+    };
+    use crate::{
+        config::coco_as::{CoCoASConfig, CoCoASProtocol},
+        token::{coco_as::CoCoASTokenGetter, TokenGetter},
+        AttestationAPIs, AttestationAgent, Config,
+    };
+    use server::{
+        attestation_service_server::{AttestationService, AttestationServiceServer},
+        AttestationRequest as ServedRequest, AttestationResponse,
+    };
+
+    /// The server side of the API, with its own message types.
+    mod server {
+        include!(concat!(env!("OUT_DIR"), "/server/attestation.rs"));
+    }
+
+    /// An AS that answers each request with the next of `statuses`, and
+    /// with a token once they are used up. It records the requests with
+    /// the `x-tenant-id` metadata.
+    #[derive(Clone, Default)]
+    struct AsStub {
+        statuses: Arc<Mutex<Vec<Status>>>,
+        requests: Arc<Mutex<Vec<(ServedRequest, Option<String>)>>>,
+    }
+
+    #[tonic::async_trait]
+    impl AttestationService for AsStub {
+        async fn attestation_evaluate(
+            &self,
+            request: Request<ServedRequest>,
+        ) -> Result<Response<AttestationResponse>, Status> {
+            let tenant = request
+                .metadata()
+                .get("x-tenant-id")
+                .map(|value| value.to_str().unwrap().to_string());
+            self.requests
+                .lock()
+                .unwrap()
+                .push((request.into_inner(), tenant));
+            if let Some(status) = self.statuses.lock().unwrap().pop() {
+                return Err(status);
+            }
+            Ok(Response::new(AttestationResponse {
+                attestation_token: "token".into(),
+            }))
+        }
+    }
+
+    /// Serve the stub, over TLS if `tls` is set, at the returned address.
+    async fn start_server(stub: AsStub, tls: Option<ServerTlsConfig>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let mut server = Server::builder();
+        if let Some(tls) = tls {
+            server = server.tls_config(tls).unwrap();
+        }
+        tokio::spawn(
+            server
+                .add_service(AttestationServiceServer::new(stub))
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        address
+    }
+
+    fn config(url: &str, protocol: &str) -> Config {
+        Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "{url}"
+{protocol}
+policy_ids = ["workload"]
+
+[token_configs.coco_as.extra_headers]
+X-Tenant-Id = "tenant-a"
+
+[retry]
+max_attempts = 2
+initial_backoff_ms = 10
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn request_token() {
+        let stub = AsStub {
+            statuses: Arc::new(Mutex::new(vec![Status::unavailable("starting")])),
+            ..Default::default()
+        };
+        let address = start_server(stub.clone(), None).await;
+
+        // Told by the scheme, and retried as with the REST API
+        let aa = AttestationAgent::with_config_and_tee(
+            config(&format!("grpc://{address}"), ""),
+            Tee::Sample,
+        )
+        .unwrap();
+        assert_eq!(aa.get_token("coco_as").await.unwrap(), b"token");
+
+        // Or by the protocol
+        let aa = AttestationAgent::with_config_and_tee(
+            config(&format!("http://{address}"), r#"protocol = "grpc""#),
+            Tee::Sample,
+        )
+        .unwrap();
+        assert_eq!(aa.get_token("coco_as").await.unwrap(), b"token");
+
+        let requests = stub.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        for (request, tenant) in requests.iter() {
+            assert_eq!(request.tee, "sample");
+            assert!(!request.evidence.is_empty());
+            assert_eq!(request.policy_ids, ["workload"]);
+            assert_eq!(tenant.as_deref(), Some("tenant-a"));
+        }
+    }
+
+    #[tokio::test]
+    async fn request_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("as.sock");
+        let stub = AsStub::default();
+        tokio::spawn(
+            Server::builder()
+                .add_service(AttestationServiceServer::new(stub.clone()))
+                .serve_with_incoming(UnixListenerStream::new(
+                    UnixListener::bind(&socket).unwrap(),
+                )),
+        );
+
+        let config = CoCoASConfig {
+            protocol: Some(CoCoASProtocol::Grpc),
+            ..CoCoASConfig::with_url(format!("unix://{}", socket.display()))
+        };
+        let getter = CoCoASTokenGetter::new(&config);
+        assert_eq!(getter.get_token().await.unwrap().token, "token");
+        assert_eq!(stub.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unknown_policy() {
+        let stub = AsStub {
+            statuses: Arc::new(Mutex::new(vec![Status::not_found("no such policy")])),
+            ..Default::default()
+        };
+        let address = start_server(stub.clone(), None).await;
+        let aa = AttestationAgent::with_config_and_tee(
+            config(&format!("grpc://{address}"), ""),
+            Tee::Sample,
+        )
+        .unwrap();
+
+        let e = aa.get_token("coco_as").await.unwrap_err();
+        assert!(matches!(e, crate::Error::UnknownPolicy { .. }), "{e:?}");
+        assert_eq!(stub.requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn request_with_client_certificate() {
+        let identity = Identity::from_pem(
+            include_str!("../../../test/tls/server.pem"),
+            include_str!("../../../test/tls/server.key"),
+        );
+        let ca = Certificate::from_pem(include_str!("../../../test/tls/ca.pem"));
+        let tls = ServerTlsConfig::new().identity(identity).client_ca_root(ca);
+        let address = start_server(AsStub::default(), Some(tls)).await;
+
+        let config = CoCoASConfig {
+            cert_path: Some("test/tls/ca.pem".into()),
+            client_cert: Some("test/tls/client.pem".into()),
+            client_key: Some(include_str!("../../../test/tls/client.encrypted.key").into()),
+            client_key_passphrase: Some("aa-test-passphrase".into()),
+            ..CoCoASConfig::with_url(format!("grpcs://{address}"))
+        };
+        let getter = CoCoASTokenGetter::new(&config);
+        assert_eq!(getter.get_token().await.unwrap().token, "token");
+
+        // The server rejects the handshake of a client without certificate
+        let config = CoCoASConfig {
+            cert_path: Some("test/tls/ca.pem".into()),
+            ..CoCoASConfig::with_url(format!("grpcs://{address}"))
+        };
+        CoCoASTokenGetter::new(&config)
+            .get_token()
+            .await
+            .unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn unreachable_service_is_retryable() {
+        let config = CoCoASConfig::with_url("grpc://127.0.0.1:1");
+        let e = CoCoASTokenGetter::new(&config)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(crate::token::is_retryable(&e), "{e:#}");
+    }
+
+    #[test]
+    fn request_of_rest_body() {
+        let body = json!({
+            "tee": "\"tdx\"",
+            "evidence": "ZXZpZGVuY2U",
+            "runtime_data": {"raw": "bm9uY2U"},
+            "runtime_data_hash_algorithm": "sha384",
+            "init_data": {"structured": {"a": 1}},
+            "future": "not sent",
+        });
+        let request = attestation_request(&body, &["default".into()]).unwrap();
+        assert_eq!(
+            request,
+            AttestationRequest {
+                tee: "tdx".into(),
+                evidence: "ZXZpZGVuY2U".into(),
+                runtime_data: Some(RuntimeData::RawRuntimeData("bm9uY2U".into())),
+                init_data: Some(InitData::StructuredInitData(r#"{"a":1}"#.into())),
+                runtime_data_hash_algorithm: "sha384".into(),
+                init_data_hash_algorithm: String::new(),
+                policy_ids: vec!["default".into()],
+            }
+        );
+
+        let body = json!({"tee": "\"tdx\"", "runtime_data": "bm9uY2U"});
+        attestation_request(&body, &[]).unwrap_err();
+    }
+}
@@ -0,0 +1,482 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Tokens of Intel Trust Authority. The evidence binds the nonce that Intel
+//! Trust Authority issued for the request, s.t. the report data is the
+//! digest of the nonce, its issue time and the runtime data.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use attester::BoxedAttester;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use kbs_types::Tee;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use super::{proxy::ProxyRules, TokenGetter, TokenResponse};
+use crate::{
+    config::{ita::ItaConfig, HashAlgorithm},
+    telemetry,
+};
+
+/// Path of the API that issues the nonces to bind into the evidence.
+const NONCE_PATH: &str = "/appraisal/v1/nonce";
+
+/// Path of the API that appraises the evidence and issues the token.
+const ATTEST_PATH: &str = "/appraisal/v1/attest";
+
+/// Header of the API key of Intel Trust Authority.
+const API_KEY_HEADER: &str = "x-api-key";
+
+/// Intel Trust Authority answered a request with an error status.
+#[derive(Debug, thiserror::Error)]
+#[error("Intel Trust Authority answered the {request} request with status {status}, response: {response:?}")]
+pub(crate) struct StatusError {
+    pub(crate) status: u16,
+    request: &'static str,
+    response: String,
+}
+
+/// The nonce issued by Intel Trust Authority, which is sent back with the
+/// evidence as is. The fields are base64 encoded.
+#[derive(Debug, Deserialize, Serialize)]
+struct VerifierNonce {
+    val: String,
+    iat: String,
+    signature: String,
+}
+
+#[derive(Deserialize)]
+struct AttestResponse {
+    token: String,
+}
+
+/// The hash algorithm of the report data that Intel Trust Authority checks
+/// the evidence of the TEE with. `None` if it does not appraise the
+/// evidence of the TEE.
+pub fn report_data_algorithm(tee: Tee) -> Option<HashAlgorithm> {
+    match tee {
+        Tee::Tdx => Some(HashAlgorithm::Sha512),
+        Tee::Sgx => Some(HashAlgorithm::Sha256),
+        _ => None,
+    }
+}
+
+/// Gets the tokens of Intel Trust Authority for the evidence of the given
+/// attester, which must be of TDX or SGX.
+pub struct ItaTokenGetter<'a> {
+    url: String,
+    api_key: String,
+    policy_ids: Vec<String>,
+    policy_must_match: bool,
+    proxy: Option<String>,
+    no_proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    tee: Tee,
+    attester: &'a BoxedAttester,
+    params: Value,
+    nonce: Option<Vec<u8>>,
+}
+
+#[async_trait]
+impl TokenGetter for ItaTokenGetter<'_> {
+    fn name(&self) -> &str {
+        "ita"
+    }
+
+    async fn get_token(&self) -> Result<TokenResponse> {
+        let timer = crate::metrics::Timer::start();
+        let token = self.request_token().await;
+        crate::metrics::token_request_finished(
+            "ita",
+            &self.url,
+            timer.elapsed(),
+            token.as_ref().map(|_| ()),
+        );
+        token
+    }
+}
+
+impl<'a> ItaTokenGetter<'a> {
+    pub fn new(config: &ItaConfig, tee: Tee, attester: &'a BoxedAttester) -> Self {
+        Self {
+            url: config.url.trim_end_matches('/').to_string(),
+            api_key: config.api_key.to_string(),
+            policy_ids: config.policy_ids.clone(),
+            policy_must_match: config.policy_must_match,
+            proxy: config.proxy.as_deref().map(str::to_string),
+            no_proxy: config.no_proxy.clone(),
+            connect_timeout: crate::config::timeout(config.connect_timeout_seconds),
+            request_timeout: crate::config::timeout(config.request_timeout_seconds),
+            tee,
+            attester,
+            params: Value::Null,
+            nonce: None,
+        }
+    }
+
+    /// Extra parameters merged into the attestation request, e.g. the
+    /// `policy_ids` to check the evidence against.
+    pub fn with_params(mut self, params: Value) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// Send a nonce of the caller as the runtime data, s.t. it is bound
+    /// into the evidence and shows up in the token claims.
+    pub fn with_nonce(mut self, nonce: Option<Vec<u8>>) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    async fn request_token(&self) -> Result<TokenResponse> {
+        let Some(algorithm) = report_data_algorithm(self.tee) else {
+            bail!(
+                "Intel Trust Authority does not appraise the evidence of {:?}",
+                self.tee
+            );
+        };
+        let client = self.http_client()?;
+
+        let timer = crate::metrics::Timer::start();
+        let verifier_nonce = self.get_nonce(&client).await?;
+        crate::metrics::token_phase_finished("ita", &self.url, "nonce", timer.elapsed());
+
+        let runtime_data = self.nonce.clone().unwrap_or_default();
+        let report_data = report_data(algorithm, &verifier_nonce, &runtime_data)?;
+        let timer = crate::metrics::Timer::start();
+        let evidence = self.attester.get_evidence(report_data).await?;
+        crate::metrics::token_phase_finished("ita", &self.url, "evidence", timer.elapsed());
+        let evidence: Value = serde_json::from_str(&evidence).context("parse evidence")?;
+        let quote = evidence
+            .get("quote")
+            .and_then(Value::as_str)
+            .context("evidence has no quote")?;
+
+        let mut request_body = json!({
+            "quote": quote,
+            "verifier_nonce": verifier_nonce,
+        });
+        if !runtime_data.is_empty() {
+            request_body["runtime_data"] = STANDARD.encode(&runtime_data).into();
+        }
+        if let (Value::Object(body), Value::Object(params)) = (&mut request_body, &self.params) {
+            for (key, value) in params {
+                body.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+        // The policies of the params of the request come first
+        if let Value::Object(body) = &mut request_body {
+            if !self.policy_ids.is_empty() {
+                body.entry("policy_ids")
+                    .or_insert_with(|| json!(self.policy_ids));
+            }
+            if self.policy_must_match {
+                body.entry("policy_must_match").or_insert(true.into());
+            }
+        }
+
+        let timer = crate::metrics::Timer::start();
+        let token = self.attest(&client, &request_body).await;
+        crate::metrics::token_phase_finished("ita", &self.url, "attest", timer.elapsed());
+        token
+    }
+
+    /// Get a nonce from Intel Trust Authority to bind into the evidence.
+    async fn get_nonce(&self, client: &reqwest::Client) -> Result<VerifierNonce> {
+        let res = client
+            .get(format!("{}{NONCE_PATH}", self.url))
+            .send()
+            .await?;
+        let status = res.status();
+        if !status.is_success() {
+            bail!(StatusError {
+                status: status.as_u16(),
+                request: "nonce",
+                response: res.text().await?,
+            });
+        }
+
+        res.json()
+            .await
+            .context("parse nonce of Intel Trust Authority")
+    }
+
+    /// Send the evidence to Intel Trust Authority for appraisal, and return
+    /// the token it issues.
+    async fn attest(&self, client: &reqwest::Client, request_body: &Value) -> Result<Vec<u8>> {
+        let res = client
+            .post(format!("{}{ATTEST_PATH}", self.url))
+            .json(request_body)
+            .send()
+            .await?;
+        let status = res.status();
+        if !status.is_success() {
+            bail!(StatusError {
+                status: status.as_u16(),
+                request: "attest",
+                response: res.text().await?,
+            });
+        }
+
+        let response: AttestResponse = res
+            .json()
+            .await
+            .context("parse response of Intel Trust Authority")?;
+        debug!(target: telemetry::TOKEN, "Got Intel Trust Authority token");
+        Ok(TokenResponse::new(response.token, None))
+    }
+
+    /// The HTTP client to Intel Trust Authority, which sends the API key
+    /// with each request.
+    fn http_client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+
+        // The key is not in the error, as it is a credential
+        let mut api_key =
+            HeaderValue::from_str(&self.api_key).map_err(|_| anyhow!("invalid API key"))?;
+        api_key.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert(API_KEY_HEADER, api_key);
+        headers.insert(ACCEPT, HeaderValue::from_static("application/json"));
+        builder = builder.default_headers(headers);
+
+        let rules = ProxyRules::new(self.proxy.as_deref(), self.no_proxy.as_deref())?;
+        builder = builder.proxy(rules.into_proxy());
+
+        builder
+            .build()
+            .context("build Intel Trust Authority http client")
+    }
+}
+
+/// The report data of the evidence, the digest of the value and the issue
+/// time of the nonce of Intel Trust Authority, followed by the runtime data.
+fn report_data(
+    algorithm: HashAlgorithm,
+    nonce: &VerifierNonce,
+    runtime_data: &[u8],
+) -> Result<Vec<u8>> {
+    let val = STANDARD
+        .decode(&nonce.val)
+        .context("nonce value is not base64 encoded")?;
+    let iat = STANDARD
+        .decode(&nonce.iat)
+        .context("nonce issue time is not base64 encoded")?;
+    Ok(algorithm.digest(&[val, iat, runtime_data.to_vec()].concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use anyhow::Result;
+    use attester::{Attester, BoxedAttester};
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use kbs_types::Tee;
+    use rstest::rstest;
+    use serde_json::{json, Value};
+
+    use super::ItaTokenGetter;
+    use crate::{
+        config::{ita::ItaConfig, HashAlgorithm},
+        token::{http_stub, TokenGetter},
+        AttestationAPIs, AttestationAgent, Config, Error,
+    };
+
+    const NONCE: &str = r#"{"val":"dmFs","iat":"aWF0","signature":"c2ln"}"#;
+
+    /// Puts the report data into the quote, s.t. the tests can check it.
+    struct QuoteAttester;
+
+    #[async_trait::async_trait]
+    impl Attester for QuoteAttester {
+        async fn get_evidence(&self, report_data: Vec<u8>) -> Result<String> {
+            Ok(json!({ "quote": STANDARD.encode(report_data) }).to_string())
+        }
+    }
+
+    /// An Intel Trust Authority that records the API keys and the bodies
+    /// of the requests, and answers the attest request with `attest`.
+    async fn ita_stub(
+        attest: (&'static str, &'static str),
+    ) -> (String, Arc<Mutex<Vec<(String, Value)>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        let (url, _) = http_stub::start_with_request(move |head, body| {
+            let api_key = head
+                .lines()
+                .find_map(|line| line.strip_prefix("x-api-key: "))
+                .unwrap_or_default()
+                .to_string();
+            recorded
+                .lock()
+                .unwrap()
+                .push((api_key, serde_json::from_str(body).unwrap_or_default()));
+            if head.starts_with("GET /appraisal/v1/nonce ") {
+                return http_stub::response("200 OK", NONCE);
+            }
+            http_stub::response(attest.0, attest.1)
+        })
+        .await;
+        (url, requests)
+    }
+
+    #[rstest]
+    #[case::tdx(Tee::Tdx, HashAlgorithm::Sha512)]
+    #[case::sgx(Tee::Sgx, HashAlgorithm::Sha256)]
+    #[tokio::test]
+    async fn get_token(#[case] tee: Tee, #[case] algorithm: HashAlgorithm) {
+        let (url, requests) = ita_stub(("200 OK", r#"{"token":"ita-token"}"#)).await;
+        let config = ItaConfig {
+            policy_ids: vec!["workload".into()],
+            ..ItaConfig::with_url(format!("{url}/"), "key")
+        };
+        let attester: BoxedAttester = Box::new(QuoteAttester);
+
+        let token = ItaTokenGetter::new(&config, tee, &attester)
+            .with_nonce(Some(b"session".to_vec()))
+            .get_token()
+            .await
+            .unwrap();
+        assert_eq!(token.token, "ita-token");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|(api_key, _)| api_key == "key"));
+        let body = &requests[1].1;
+        assert_eq!(
+            body["verifier_nonce"],
+            serde_json::from_str::<Value>(NONCE).unwrap()
+        );
+        assert_eq!(body["runtime_data"], STANDARD.encode("session"));
+        assert_eq!(body["policy_ids"], json!(["workload"]));
+        assert!(body.get("policy_must_match").is_none());
+        let report_data = STANDARD.decode(body["quote"].as_str().unwrap()).unwrap();
+        assert_eq!(report_data, algorithm.digest(b"valiatsession"));
+    }
+
+    #[tokio::test]
+    async fn params_replace_policies() {
+        let (url, requests) = ita_stub(("200 OK", r#"{"token":"ita-token"}"#)).await;
+        let config = ItaConfig {
+            policy_ids: vec!["workload".into()],
+            policy_must_match: true,
+            ..ItaConfig::with_url(url, "key")
+        };
+        let attester: BoxedAttester = Box::new(QuoteAttester);
+
+        ItaTokenGetter::new(&config, Tee::Tdx, &attester)
+            .with_params(json!({"policy_ids": ["tenant"]}))
+            .get_token()
+            .await
+            .unwrap();
+        let body = requests.lock().unwrap().pop().unwrap().1;
+        assert_eq!(body["policy_ids"], json!(["tenant"]));
+        assert_eq!(body["policy_must_match"], true);
+        assert!(body.get("runtime_data").is_none());
+    }
+
+    #[rstest]
+    #[case::unauthorized("401 Unauthorized", false)]
+    #[case::policy_mismatch("400 Bad Request", false)]
+    #[case::unavailable("503 Service Unavailable", true)]
+    #[tokio::test]
+    async fn rejected_attestation(#[case] status: &'static str, #[case] retryable: bool) {
+        let (url, _) = ita_stub((status, r#"{"error":"rejected"}"#)).await;
+        let attester: BoxedAttester = Box::new(QuoteAttester);
+
+        let e = ItaTokenGetter::new(&ItaConfig::with_url(url, "key"), Tee::Tdx, &attester)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("attest request"), "{e}");
+        assert!(e.to_string().contains(&status[..3]), "{e}");
+        assert_eq!(crate::token::is_retryable(&e), retryable);
+    }
+
+    #[tokio::test]
+    async fn invalid_nonce() {
+        let (url, _) = http_stub::start(|_| http_stub::response("200 OK", r#"{"val":1}"#)).await;
+        let attester: BoxedAttester = Box::new(QuoteAttester);
+
+        let e = ItaTokenGetter::new(&ItaConfig::with_url(url, "key"), Tee::Tdx, &attester)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(e.to_string().contains("nonce"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn unsupported_tee() {
+        let (url, requests) = ita_stub(("200 OK", r#"{"token":"ita-token"}"#)).await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[token_configs.ita]
+url = "{url}"
+api_key = "key"
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+
+        let e = aa.get_token("ita").await.unwrap_err();
+        assert!(
+            matches!(
+                e,
+                Error::UnsupportedTokenTee {
+                    tee: Tee::Sample,
+                    ..
+                }
+            ),
+            "{e}"
+        );
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn not_configured() {
+        let config = Config::from_toml_str(
+            r#"
+[token_configs.kbs]
+url = "https://127.0.0.1:8080"
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+"#,
+        )
+        .unwrap();
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+
+        let e = aa.get_token("ita").await.unwrap_err();
+        let Error::ConfigInvalid(errors) = e else {
+            panic!("{e}");
+        };
+        assert_eq!(errors[0].key, "token_configs.ita");
+    }
+}
@@ -3,56 +3,920 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use crate::config::kbs::KbsConfig;
 
-use super::GetToken;
+use super::{proxy::ProxyRules, TokenGetter, TokenResponse};
 use anyhow::*;
 use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
 use kbs_protocol::{evidence_provider::NativeEvidenceProvider, KbsClientBuilder};
-use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, warn};
+use x509_parser::pem::Pem;
+
+use crate::telemetry;
+
+/// The KBS endpoint that served the last token, and the health of the
+/// others. The preferred endpoint is tried first by the following requests
+/// until it fails, and the endpoints that failed are tried after the
+/// healthy ones, s.t. the endpoints that are down are not tried first each
+/// time.
+#[derive(Clone, Debug, Default)]
+pub struct PreferredEndpoint(Arc<Mutex<EndpointHealth>>);
+
+#[derive(Debug, Default)]
+struct EndpointHealth {
+    preferred: Option<String>,
+
+    /// Consecutive failures of each endpoint, until it serves a token
+    failures: HashMap<String, u32>,
+}
+
+impl PreferredEndpoint {
+    fn lock(&self) -> std::sync::MutexGuard<'_, EndpointHealth> {
+        self.0.lock().expect("lock preferred KBS endpoint")
+    }
+
+    fn get(&self) -> Option<String> {
+        self.lock().preferred.clone()
+    }
+
+    #[cfg(test)]
+    fn set(&self, url: Option<String>) {
+        self.lock().preferred = url;
+    }
+
+    fn succeeded(&self, url: &str) {
+        let mut health = self.lock();
+        health.preferred = Some(url.to_string());
+        health.failures.remove(url);
+    }
+
+    /// The endpoint failed. It is only counted as unhealthy if it is
+    /// unavailable, rather than rejecting the request.
+    fn failed(&self, url: &str, unavailable: bool) {
+        let mut health = self.lock();
+        if health.preferred.as_deref() == Some(url) {
+            health.preferred = None;
+        }
+        if unavailable {
+            *health.failures.entry(url.to_string()).or_default() += 1;
+        }
+    }
+
+    /// The preferred endpoint first, if it is still configured, and then
+    /// the others in the configured order, the ones that failed less often
+    /// in a row first.
+    fn order<'a>(&self, endpoints: &'a [String]) -> Vec<&'a String> {
+        let health = self.lock();
+        let mut ordered: Vec<&String> = endpoints.iter().collect();
+        ordered.sort_by_key(|url| {
+            let preferred = health.preferred.as_ref() == Some(*url);
+            let failures = health.failures.get(*url).copied().unwrap_or_default();
+            (!preferred, failures)
+        });
+        ordered
+    }
+}
 
-#[derive(Serialize)]
-struct Message {
-    token: String,
-    tee_keypair: String,
+/// How the failure of a KBS endpoint is handled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    /// The endpoint is unreachable or failing, e.g. with a 5xx status, at
+    /// the challenge or the attest step. The next endpoint is tried.
+    Unavailable,
+
+    /// The challenge went stale before the evidence got to KBS. The
+    /// handshake with the same endpoint is restarted with a new challenge,
+    /// up to `stale_session_retries` times.
+    StaleSession,
+
+    /// KBS rejected the request, e.g. the evidence by its policy, or AA
+    /// failed on its own, e.g. to generate the evidence. Another endpoint
+    /// would fail the same.
+    Terminal,
 }
 
 #[derive(Default)]
 pub struct KbsTokenGetter {
-    kbs_host_url: String,
-    cert: Option<String>,
+    endpoints: Vec<String>,
+    preferred: PreferredEndpoint,
+    /// Inline root certs in PEM
+    certs: Vec<String>,
+    cert_path: Option<PathBuf>,
+    proxy: Option<String>,
+    no_proxy: Option<String>,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    extra_headers: Vec<(String, String)>,
+    params: Value,
+    nonce: Option<Vec<u8>>,
+    stale_session_retries: u32,
+    /// Path of the certificate endpoint, and the PEM certificate signing
+    /// request sent to it after the handshake
+    #[cfg(feature = "cert")]
+    csr: Option<(String, String)>,
 }
 
 #[async_trait]
-impl GetToken for KbsTokenGetter {
-    async fn get_token(&self) -> Result<Vec<u8>> {
+impl TokenGetter for KbsTokenGetter {
+    fn name(&self) -> &str {
+        "kbs"
+    }
+
+    async fn get_token(&self) -> Result<TokenResponse> {
+        let mut attempts = Vec::new();
+        for url in self.preferred.order(&self.endpoints) {
+            let kbs_url = super::redact_url(url);
+            let mut stale_retries = 0;
+            let (failure, e) = loop {
+                let e = match self.get_token_from(url).await {
+                    Result::Ok(token) => {
+                        info!(
+                            target: telemetry::TOKEN,
+                            kbs_url = %kbs_url,
+                            "KBS endpoint served the token"
+                        );
+                        self.preferred.succeeded(url);
+                        return Ok(token);
+                    }
+                    Err(e) => e,
+                };
+
+                let failure = classify(&e);
+                if failure != Failure::StaleSession || stale_retries >= self.stale_session_retries {
+                    break (failure, e);
+                }
+                // The evidence is bound to the stale challenge, so the
+                // handshake is restarted rather than the attest request
+                stale_retries += 1;
+                warn!(
+                    target: telemetry::TOKEN,
+                    kbs_url = %kbs_url,
+                    error = ?e,
+                    retry = stale_retries,
+                    "KBS attest session is stale, restarting the handshake"
+                );
+                crate::metrics::rcar_handshakes_retried(1);
+                attempts.push((kbs_url.clone(), e));
+            };
+
+            self.preferred.failed(url, failure != Failure::Terminal);
+            if failure == Failure::Terminal {
+                return Err(e.context(format!(
+                    "KBS endpoint {kbs_url} failed, as the other endpoints would"
+                )));
+            }
+
+            warn!(
+                target: telemetry::TOKEN,
+                kbs_url = %kbs_url,
+                error = ?e,
+                "KBS endpoint failed, trying the next one"
+            );
+            attempts.push((kbs_url, e));
+        }
+
+        // The last error is kept as the cause, s.t. it can be told whether
+        // to retry
+        let (last_url, last) = attempts.pop().context("no KBS endpoint is configured")?;
+        if attempts.is_empty() {
+            return Err(last);
+        }
+        let attempted: Vec<String> = attempts
+            .iter()
+            .map(|(kbs_url, e)| format!("{kbs_url} failed with {e:#}"))
+            .chain([format!("{last_url} failed")])
+            .collect();
+        Err(last.context(format!(
+            "no KBS endpoint served the token: {}",
+            attempted.join("; ")
+        )))
+    }
+}
+
+impl KbsTokenGetter {
+    async fn get_token_from(&self, url: &str) -> Result<TokenResponse> {
+        let timer = crate::metrics::Timer::start();
+        let token = self.request_token(url).await;
+        crate::metrics::token_request_finished(
+            "kbs",
+            url,
+            timer.elapsed(),
+            token.as_ref().map(|_| ()),
+        );
+        token
+    }
+
+    async fn request_token(&self, url: &str) -> Result<TokenResponse> {
         let evidence_provider = Box::new(NativeEvidenceProvider::new()?);
 
-        let mut builder =
-            KbsClientBuilder::with_evidence_provider(evidence_provider, &self.kbs_host_url);
+        // The HTTP client of kbs_protocol only connects over TCP
+        if super::transport::Endpoint::parse(url)? != super::transport::Endpoint::Tcp {
+            bail!("KBS is only reached over http:// or https://");
+        }
+        let mut builder = KbsClientBuilder::with_evidence_provider(evidence_provider, url);
+
+        // Rather than retrying the handshake with an endpoint that fails,
+        // the others are tried, and then the whole request is retried as
+        // given by `[retry]`
+        builder = builder
+            .set_rcar_attempts(1)
+            .set_timeouts(self.connect_timeout, self.request_timeout);
 
-        if let Some(cert) = &self.cert {
-            builder = builder.add_kbs_cert(cert);
+        for cert in self.root_certs()? {
+            builder = builder.add_kbs_cert(&cert);
+        }
+
+        for (name, value) in &self.extra_headers {
+            builder = builder.add_header(name, value);
+        }
+
+        let rules = ProxyRules::new(self.proxy.as_deref(), self.no_proxy.as_deref())?;
+        builder = builder.set_proxy(rules.into_proxy());
+
+        if !self.params.is_null() {
+            builder = builder.set_extra_params(&self.params.to_string());
+        }
+
+        if let Some(nonce) = &self.nonce {
+            builder = builder.set_client_nonce(nonce);
         }
 
         let mut client = builder.build()?;
 
-        let (token, tee_keypair) = client.get_token().await?;
-        let message = Message {
-            token: token.content,
-            tee_keypair: tee_keypair.to_pkcs1_pem()?.to_string(),
-        };
+        let res = client.get_token().await;
+        crate::metrics::rcar_handshakes_retried(client.rcar_handshake_retries());
+        #[cfg(feature = "metrics")]
+        {
+            let phases = client.rcar_phase_durations();
+            for (phase, elapsed) in [
+                ("challenge", phases.challenge),
+                ("evidence", phases.evidence),
+                ("attest", phases.attest),
+            ] {
+                if let Some(elapsed) = elapsed {
+                    crate::metrics::token_phase_finished("kbs", url, phase, elapsed);
+                }
+            }
+        }
+        let (token, tee_keypair) = res?;
 
-        let res = serde_json::to_vec(&message)?;
-        Ok(res)
+        // The certificate is requested over the attested session
+        #[cfg(feature = "cert")]
+        if let Some((path, csr)) = &self.csr {
+            let chain = client
+                .request_certificate(path, csr)
+                .await
+                .map_err(super::cert::CertificateError::Issue)?;
+            return Ok(TokenResponse::new(chain, None));
+        }
+
+        let tee_keypair = tee_keypair.to_pkcs1_pem()?.to_string();
+        Ok(TokenResponse::new(token.content, Some(tee_keypair)))
     }
-}
 
-impl KbsTokenGetter {
     pub fn new(config: &KbsConfig) -> Self {
         Self {
-            kbs_host_url: config.url.clone(),
-            cert: config.cert.clone(),
+            endpoints: config.endpoints().to_vec(),
+            preferred: PreferredEndpoint::default(),
+            certs: config.cert.iter().chain(&config.certs).cloned().collect(),
+            cert_path: config.cert_path.clone(),
+            proxy: config.proxy.as_deref().map(str::to_string),
+            no_proxy: config.no_proxy.clone(),
+            connect_timeout: crate::config::timeout(config.connect_timeout_seconds),
+            request_timeout: crate::config::timeout(config.request_timeout_seconds),
+            extra_headers: config
+                .extra_headers
+                .iter()
+                .map(|(name, value)| (name.clone(), str::to_string(value)))
+                .collect(),
+            params: Value::Null,
+            nonce: None,
+            stale_session_retries: config.stale_session_retries,
+            #[cfg(feature = "cert")]
+            csr: None,
         }
     }
+
+    /// Share the preferred endpoint with other token getters of the same
+    /// KBS, see [`PreferredEndpoint`].
+    pub fn with_preferred(mut self, preferred: &PreferredEndpoint) -> Self {
+        self.preferred = preferred.clone();
+        self
+    }
+
+    /// Extra parameters sent to KBS as the `extra-params` of the auth
+    /// request.
+    pub fn with_params(mut self, params: Value) -> Self {
+        self.params = params;
+        self
+    }
+
+    /// The trusted root certs of KBS, one PEM each: the inline ones and the
+    /// ones in the bundle at `cert_path`.
+    fn root_certs(&self) -> Result<Vec<String>> {
+        let mut certs = Vec::new();
+        for (index, cert) in self.certs.iter().enumerate() {
+            let cert = split_pem_bundle(cert.as_bytes())
+                .with_context(|| format!("invalid inline KBS root cert {index}"))?;
+            certs.extend(cert);
+        }
+
+        if let Some(cert_path) = &self.cert_path {
+            let bundle = std::fs::read(cert_path)
+                .with_context(|| format!("read KBS root certs {}", cert_path.display()))?;
+            let bundle = split_pem_bundle(&bundle)
+                .with_context(|| format!("invalid KBS root certs {}", cert_path.display()))?;
+            certs.extend(bundle);
+        }
+
+        Ok(certs)
+    }
+
+    /// Bind a nonce of the caller into the runtime data of the handshake,
+    /// see [`kbs_protocol::client_nonce_binding`].
+    pub fn with_nonce(mut self, nonce: Option<Vec<u8>>) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    /// Request the certificate of the PEM certificate signing request from
+    /// the endpoint at `path` over the attested session, s.t. `get_token`
+    /// returns the PEM certificate chain that was issued rather than the
+    /// token, see [`crate::config::cert::CertConfig::kbs_path`].
+    #[cfg(feature = "cert")]
+    pub fn with_csr(mut self, path: &str, csr: String) -> Self {
+        self.csr = Some((path.to_string(), csr));
+        self
+    }
+}
+
+fn classify(e: &Error) -> Failure {
+    match e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<kbs_protocol::Error>())
+    {
+        Some(kbs_protocol::Error::StaleSession(_)) => Failure::StaleSession,
+
+        // Too many requests to one endpoint are no reason not to try the
+        // others
+        Some(kbs_protocol::Error::RequestRejected { status, .. }) if *status != 429 => {
+            Failure::Terminal
+        }
+        Some(
+            kbs_protocol::Error::GetEvidence(_)
+            | kbs_protocol::Error::GenerateKeyPairFailed(_)
+            | kbs_protocol::Error::NativeEvidenceProvider(_),
+        ) => Failure::Terminal,
+        _ => Failure::Unavailable,
+    }
+}
+
+/// Split a PEM bundle into the PEM of each cert. Expired certs are rejected
+/// here, as the TLS handshake would fail later without telling which cert
+/// is the cause.
+fn split_pem_bundle(bundle: &[u8]) -> Result<Vec<String>> {
+    let mut certs = Vec::new();
+    for pem in Pem::iter_from_buffer(bundle) {
+        let pem = pem.context("malformed PEM")?;
+        let cert = pem.parse_x509().context("malformed X.509 certificate")?;
+        let validity = cert.validity();
+        if !validity.is_valid() {
+            bail!(
+                "certificate `{}` is not valid from {} to {}",
+                cert.subject(),
+                validity.not_before,
+                validity.not_after
+            );
+        }
+
+        certs.push(encode_pem(&pem.contents));
+    }
+
+    if certs.is_empty() {
+        bail!("no PEM certificate found");
+    }
+    Ok(certs)
+}
+
+fn encode_pem(der: &[u8]) -> String {
+    let encoded = STANDARD.encode(der);
+    let mut pem = String::from("-----BEGIN CERTIFICATE-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        pem.push('\n');
+    }
+    pem.push_str("-----END CERTIFICATE-----\n");
+    pem
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        env,
+        path::PathBuf,
+        sync::{atomic::Ordering, Arc, Mutex},
+        time::Duration,
+    };
+
+    use rstest::rstest;
+    use testcontainers::{clients, images::generic::GenericImage};
+
+    use super::{split_pem_bundle, KbsTokenGetter, PreferredEndpoint};
+    use crate::{
+        config::{kbs::KbsConfig, secret::Secret},
+        token::{http_stub, TokenGetter},
+    };
+
+    const CA: &str = include_str!("../../test/kbs-ca.crt");
+    const EXPIRED_CA: &str = include_str!("../../test/expired-ca.crt");
+
+    #[test]
+    fn split_bundle() {
+        let bundle = format!("{CA}{}", include_str!("../../test/kbs.crt"));
+        let certs = split_pem_bundle(bundle.as_bytes()).unwrap();
+        assert_eq!(certs.len(), 2);
+        assert_eq!(
+            split_pem_bundle(certs[0].as_bytes()).unwrap(),
+            vec![certs[0].clone()]
+        );
+
+        assert!(split_pem_bundle(b"").is_err());
+        let e = split_pem_bundle(EXPIRED_CA.as_bytes()).unwrap_err();
+        assert!(e.to_string().contains("AA-TEST-EXPIRED-CA"), "{e}");
+    }
+
+    #[test]
+    fn invalid_cert_file_is_named() {
+        let dir = tempfile::tempdir().unwrap();
+        let cert_path = dir.path().join("expired.pem");
+        std::fs::write(&cert_path, EXPIRED_CA).unwrap();
+        let config = KbsConfig {
+            cert_path: Some(cert_path.clone()),
+            ..KbsConfig::with_url("https://127.0.0.1:8080")
+        };
+
+        let e = KbsTokenGetter::new(&config).root_certs().unwrap_err();
+        assert!(
+            e.to_string().contains(&cert_path.display().to_string()),
+            "{e}"
+        );
+
+        let config = KbsConfig {
+            cert_path: Some(dir.path().join("missing.pem")),
+            ..KbsConfig::with_url("https://127.0.0.1:8080")
+        };
+        let e = KbsTokenGetter::new(&config).root_certs().unwrap_err();
+        assert!(e.to_string().contains("missing.pem"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn connect_through_proxy() {
+        let (proxy, requests) =
+            http_stub::start(|_| http_stub::response("502 Bad Gateway", "")).await;
+        let proxy = proxy.replace("http://", "http://user:secret@");
+        let config = |no_proxy: Option<&str>| KbsConfig {
+            proxy: Some(proxy.clone()),
+            no_proxy: no_proxy.map(ToString::to_string),
+            ..KbsConfig::with_url("https://127.0.0.1:1")
+        };
+
+        // The RCAR handshake is tunneled with CONNECT, which the stub refuses
+        let e = KbsTokenGetter::new(&config(None))
+            .get_token()
+            .await
+            .unwrap_err();
+        let proxied = requests.load(Ordering::SeqCst);
+        assert!(proxied > 0);
+        assert!(!format!("{e:?}").contains("secret"), "{e:?}");
+
+        KbsTokenGetter::new(&config(Some("127.0.0.1")))
+            .get_token()
+            .await
+            .unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), proxied);
+    }
+
+    /// A KBS endpoint that answers the RCAR handshake with the given status,
+    /// recording its name in `served` for each request.
+    async fn kbs_stub(
+        name: &'static str,
+        status: &'static str,
+        served: &Arc<Mutex<Vec<&str>>>,
+    ) -> String {
+        let served = served.clone();
+        let (url, _) = http_stub::start(move |request| {
+            served.lock().unwrap().push(name);
+            if !status.starts_with("200") {
+                return http_stub::response(status, "");
+            }
+
+            if request.contains("/kbs/v0/auth") {
+                http_stub::response(status, r#"{"nonce":"MTIzNDU2","extra-params":""}"#)
+            } else {
+                // Claims `{}`, s.t. the token never expires
+                http_stub::response(status, r#"{"token":"e30.e30.c2ln"}"#)
+            }
+        })
+        .await;
+        url
+    }
+
+    #[tokio::test]
+    async fn failover_in_order() {
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let down = kbs_stub("down", "503 Service Unavailable", &served).await;
+        let up = kbs_stub("up", "200 OK", &served).await;
+        let preferred = PreferredEndpoint::default();
+        let config = KbsConfig {
+            urls: vec!["http://127.0.0.1:1".into(), down.clone(), up.clone()],
+            ..KbsConfig::with_url("http://127.0.0.1:2")
+        };
+
+        KbsTokenGetter::new(&config)
+            .with_preferred(&preferred)
+            .get_token()
+            .await
+            .unwrap();
+        let served_first = served.lock().unwrap().clone();
+        assert!(served_first.contains(&"down"));
+        assert_eq!(served_first.last(), Some(&"up"));
+        assert_eq!(preferred.get(), Some(up.clone()));
+
+        // The endpoint that served the token is tried first
+        served.lock().unwrap().clear();
+        KbsTokenGetter::new(&config)
+            .with_preferred(&preferred)
+            .get_token()
+            .await
+            .unwrap();
+        assert_eq!(*served.lock().unwrap(), vec!["up", "up"]);
+    }
+
+    #[tokio::test]
+    async fn extra_headers_survive_failover() {
+        let heads = Arc::new(Mutex::new(Vec::new()));
+        let stub = |status: &'static str| {
+            let heads = heads.clone();
+            http_stub::start_with_request(move |head, _| {
+                heads.lock().unwrap().push(head.to_lowercase());
+                if !status.starts_with("200") {
+                    http_stub::response(status, "")
+                } else if head.contains("/kbs/v0/auth") {
+                    http_stub::response(status, r#"{"nonce":"MTIzNDU2","extra-params":""}"#)
+                } else {
+                    http_stub::response(status, r#"{"token":"e30.e30.c2ln"}"#)
+                }
+            })
+        };
+        let (down, _) = stub("503 Service Unavailable").await;
+        let (up, _) = stub("200 OK").await;
+        let config = KbsConfig {
+            urls: vec![down, up],
+            extra_headers: [("X-Tenant-Id".into(), Secret::from("tenant-a"))].into(),
+            ..KbsConfig::with_url("")
+        };
+
+        KbsTokenGetter::new(&config).get_token().await.unwrap();
+        let heads = heads.lock().unwrap();
+        // The auth request to the endpoint that is down, and the RCAR
+        // handshake with the other one
+        assert_eq!(heads.len(), 3);
+        for head in heads.iter() {
+            assert!(
+                head.lines().any(|line| line == "x-tenant-id: tenant-a"),
+                "{head}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn unix_socket_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let socket = dir.path().join("kbs.sock");
+        let requests = http_stub::start_unix(&socket, |_| http_stub::response("200 OK", "")).await;
+        let config = KbsConfig::with_url(format!("unix://{}", socket.display()));
+
+        let e = KbsTokenGetter::new(&config).get_token().await.unwrap_err();
+        assert!(format!("{e:#}").contains("http:// or https://"), "{e:#}");
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn rejection_is_terminal() {
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let rejecting = kbs_stub("rejecting", "403 Forbidden", &served).await;
+        let up = kbs_stub("up", "200 OK", &served).await;
+        let preferred = PreferredEndpoint::default();
+        preferred.set(Some(rejecting.clone()));
+        let config = KbsConfig {
+            urls: vec![up, rejecting],
+            ..KbsConfig::with_url("")
+        };
+
+        let e = KbsTokenGetter::new(&config)
+            .with_preferred(&preferred)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(format!("{e:#}").contains("403"), "{e:#}");
+        assert_eq!(*served.lock().unwrap(), vec!["rejecting"]);
+        assert_eq!(preferred.get(), None);
+    }
+
+    /// A KBS endpoint that serves the challenge with `auth_status`, and
+    /// answers the attest requests with `attest` in order, the last one
+    /// repeated, recording its name in `served` for each request.
+    async fn rcar_stub(
+        name: &'static str,
+        auth_status: &'static str,
+        attest: Vec<(&'static str, &'static str)>,
+        served: &Arc<Mutex<Vec<&str>>>,
+    ) -> String {
+        let served = served.clone();
+        let attest = Mutex::new(attest);
+        let (url, _) = http_stub::start(move |request| {
+            served.lock().unwrap().push(name);
+            if request.contains("/kbs/v0/auth") {
+                let challenge = if auth_status.starts_with("200") {
+                    r#"{"nonce":"MTIzNDU2","extra-params":""}"#
+                } else {
+                    ""
+                };
+                return http_stub::response(auth_status, challenge);
+            }
+
+            let mut attest = attest.lock().unwrap();
+            let (status, body) = match attest.len() {
+                1 => attest[0],
+                _ => attest.remove(0),
+            };
+            http_stub::response(status, body)
+        })
+        .await;
+        url
+    }
+
+    const TOKEN: (&str, &str) = ("200 OK", r#"{"token":"e30.e30.c2ln"}"#);
+
+    const STALE: (&str, &str) = (
+        "401 Unauthorized",
+        r#"{"type":"https://github.com/confidential-containers/kbs/errors/ExpiredCookie","detail":"expired"}"#,
+    );
+
+    const POLICY_REJECT: (&str, &str) = (
+        "401 Unauthorized",
+        r#"{"type":"https://github.com/confidential-containers/kbs/errors/PolicyReject","detail":"rejected"}"#,
+    );
+
+    /// How the failure of the first endpoint is handled, with the requests
+    /// each endpoint served: the auth and the attest request per handshake.
+    #[rstest]
+    #[case::challenge_unavailable("503 Service Unavailable", vec![TOKEN], Some(vec!["first", "up", "up"]))]
+    #[case::challenge_throttled("429 Too Many Requests", vec![TOKEN], Some(vec!["first", "up", "up"]))]
+    #[case::challenge_rejected("403 Forbidden", vec![TOKEN], None)]
+    #[case::attest_unavailable("200 OK", vec![("500 Internal Server Error", "")], Some(vec!["first", "first", "up", "up"]))]
+    #[case::attest_policy_reject("200 OK", vec![POLICY_REJECT], None)]
+    #[case::stale_once("200 OK", vec![STALE, TOKEN], Some(vec!["first", "first", "first", "first"]))]
+    #[case::stale_twice("200 OK", vec![STALE, STALE, TOKEN], Some(vec!["first"; 6]))]
+    #[case::stale_always("200 OK", vec![STALE], Some(vec!["first", "first", "first", "first", "first", "first", "up", "up"]))]
+    #[tokio::test]
+    async fn classify_endpoint_failures(
+        #[case] auth_status: &'static str,
+        #[case] attest: Vec<(&'static str, &'static str)>,
+        #[case] expected: Option<Vec<&str>>,
+    ) {
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let first = rcar_stub("first", auth_status, attest, &served).await;
+        let up = rcar_stub("up", "200 OK", vec![TOKEN], &served).await;
+        let config = KbsConfig {
+            urls: vec![first, up],
+            ..KbsConfig::with_url("")
+        };
+
+        let token = KbsTokenGetter::new(&config).get_token().await;
+        match expected {
+            Some(expected) => {
+                token.unwrap();
+                assert_eq!(*served.lock().unwrap(), expected);
+            }
+            None => {
+                token.unwrap_err();
+                assert!(served.lock().unwrap().iter().all(|name| *name == "first"));
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::restarted(2, true)]
+    #[case::not_restarted(0, false)]
+    #[tokio::test]
+    async fn restart_stale_handshake(#[case] stale_session_retries: u32, #[case] served: bool) {
+        let challenges = Arc::new(Mutex::new(0));
+        let attested = Arc::new(Mutex::new(Vec::new()));
+        let (url, _) = {
+            let challenges = challenges.clone();
+            let attested = attested.clone();
+            http_stub::start_with_request(move |head, body| {
+                if head.contains("/kbs/v0/auth") {
+                    let mut challenges = challenges.lock().unwrap();
+                    *challenges += 1;
+                    let challenge =
+                        format!(r#"{{"nonce":"nonce-{challenges}","extra-params":""}}"#);
+                    return http_stub::response("200 OK", &challenge);
+                }
+
+                let mut attested = attested.lock().unwrap();
+                let attest: serde_json::Value = serde_json::from_str(body).unwrap();
+                attested.push(attest["tee-evidence"].to_string());
+                match attested.len() {
+                    1 => http_stub::response(STALE.0, STALE.1),
+                    _ => http_stub::response(TOKEN.0, TOKEN.1),
+                }
+            })
+            .await
+        };
+        let config = KbsConfig {
+            stale_session_retries,
+            ..KbsConfig::with_url(url)
+        };
+
+        let token = KbsTokenGetter::new(&config).get_token().await;
+        assert_eq!(token.is_ok(), served);
+        let attested = attested.lock().unwrap();
+        if served {
+            // A new challenge is attested with new evidence
+            assert_eq!(*challenges.lock().unwrap(), 2);
+            assert_eq!(attested.len(), 2);
+            assert_ne!(attested[0], attested[1]);
+        } else {
+            assert_eq!(attested.len(), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_endpoints_are_tried_last() {
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let down = kbs_stub("down", "503 Service Unavailable", &served).await;
+        let failing = kbs_stub("failing", "502 Bad Gateway", &served).await;
+        let preferred = PreferredEndpoint::default();
+        let config = KbsConfig {
+            urls: vec![down.clone(), failing.clone()],
+            ..KbsConfig::with_url("")
+        };
+
+        // The attempted endpoints are in the error
+        let e = KbsTokenGetter::new(&config)
+            .with_preferred(&preferred)
+            .get_token()
+            .await
+            .unwrap_err();
+        assert!(
+            format!("{e}").contains(&format!("{down} failed with")),
+            "{e}"
+        );
+        assert!(format!("{e}").contains(&format!("{failing} failed")), "{e}");
+        assert!(crate::token::is_retryable(&e));
+
+        // Up again, the endpoint that failed less often is tried first
+        let up = kbs_stub("up", "200 OK", &served).await;
+        preferred.failed(&down, true);
+        served.lock().unwrap().clear();
+        let config = KbsConfig {
+            urls: vec![down, up, failing],
+            ..KbsConfig::with_url("")
+        };
+        KbsTokenGetter::new(&config)
+            .with_preferred(&preferred)
+            .get_token()
+            .await
+            .unwrap();
+        assert_eq!(*served.lock().unwrap(), vec!["up", "up"]);
+    }
+
+    #[tokio::test]
+    async fn stalled_kbs_times_out() {
+        let url = http_stub::start_stalled().await;
+        let config = KbsConfig {
+            request_timeout_seconds: 1,
+            ..KbsConfig::with_url(url)
+        };
+
+        let e = tokio::time::timeout(
+            Duration::from_secs(10),
+            KbsTokenGetter::new(&config).get_token(),
+        )
+        .await
+        .unwrap()
+        .unwrap_err();
+        assert!(crate::token::is_retryable(&e), "{e:#}");
+    }
+
+    /// Each attempt of `token_configs.kbs.max_attempts` starts over with a
+    /// new challenge.
+    #[tokio::test]
+    async fn retry_with_new_challenge() {
+        use kbs_types::Tee;
+
+        use crate::{AttestationAPIs, AttestationAgent, Config};
+
+        let served = Arc::new(Mutex::new(Vec::new()));
+        let unavailable = ("503 Service Unavailable", "");
+        let url = rcar_stub(
+            "kbs",
+            "200 OK",
+            vec![unavailable, unavailable, TOKEN],
+            &served,
+        )
+        .await;
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs.kbs]
+url = "{url}"
+max_attempts = 3
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[retry]
+initial_backoff_ms = 10
+jitter = false
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+
+        let aa = AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap();
+        aa.get_token("kbs").await.unwrap();
+        // An auth and an attest request per attempt
+        assert_eq!(served.lock().unwrap().len(), 6);
+    }
+
+    /// Launch a KBS serving HTTPS with a cert of the test CA, and get a
+    /// token with and without the CA trusted. This needs docker and pulls
+    /// the KBS image, so run it with `cargo test -- --ignored`.
+    #[ignore]
+    #[tokio::test]
+    #[serial_test::serial]
+    async fn self_signed_kbs() {
+        let test_dir = env::current_dir().expect("get cwd").join("test");
+        let mount = |name: &str| {
+            test_dir
+                .join(name)
+                .into_os_string()
+                .to_string_lossy()
+                .to_string()
+        };
+        let image = GenericImage::new(
+            "ghcr.io/confidential-containers/staged-images/kbs",
+            "latest",
+        )
+        .with_exposed_port(8085)
+        .with_volume(mount("kbs-config-https.toml"), "/etc/kbs-config.toml")
+        .with_volume(mount("kbs.crt"), "/etc/kbs.crt")
+        .with_volume(mount("kbs.key"), "/etc/kbs.key")
+        .with_volume(mount("start_kbs.sh"), "/usr/local/bin/start_kbs.sh")
+        .with_entrypoint("/usr/local/bin/start_kbs.sh");
+        let docker = clients::Cli::default();
+        let kbs = docker.run(image);
+
+        let port = kbs.get_host_port_ipv4(8085);
+        let url = format!("https://127.0.0.1:{port}");
+
+        // Retry until the KBS is up rather than waiting for a fixed time
+        let trusted = KbsTokenGetter::new(&KbsConfig {
+            cert_path: Some(PathBuf::from(mount("kbs-ca.crt"))),
+            ..KbsConfig::with_url(&url)
+        });
+        let started = async {
+            while trusted.get_token().await.is_err() {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(60), started)
+            .await
+            .expect("get token with the CA trusted");
+
+        let untrusted = KbsTokenGetter::new(&KbsConfig::with_url(&url));
+        assert!(untrusted.get_token().await.is_err());
+
+        let inline = KbsTokenGetter::new(&KbsConfig {
+            certs: vec![CA.to_string()],
+            ..KbsConfig::with_url(&url)
+        });
+        inline
+            .get_token()
+            .await
+            .expect("get token with the inline CA");
+    }
 }
@@ -0,0 +1,269 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{sync::Weak, time::Duration};
+
+use rand::Rng;
+use serde_json::Value;
+use tokio::{sync::watch, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+use super::{cache::now, TokenType};
+use crate::{telemetry, token_url, AttestationAgent, Error, Result};
+
+/// A refresh is at least this long after the one before, s.t. a token that
+/// is issued with less time to live than the margin is not fetched over
+/// and over.
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Background task that gets a new KBS token before the cached one
+/// expires, see [`AttestationAgent::start_token_refresh`]. The task idles
+/// while refreshing is disabled or no token is cached, and stops when AA
+/// is shut down, and once this is dropped.
+pub struct TokenRefresher {
+    task: JoinHandle<()>,
+}
+
+impl Drop for TokenRefresher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl TokenRefresher {
+    /// Refresh the KBS token of `aa` until `closed` is set. `changed` is
+    /// signaled when the configuration or the cached KBS token changes. The
+    /// task holds no reference that keeps AA alive.
+    pub(crate) fn start(
+        aa: Weak<AttestationAgent>,
+        closed: watch::Receiver<bool>,
+        changed: watch::Receiver<()>,
+    ) -> Self {
+        Self {
+            task: tokio::spawn(refresh_before_expiry(aa, closed, changed)),
+        }
+    }
+
+    /// Whether the task has stopped, e.g. as AA is shut down.
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+async fn refresh_before_expiry(
+    aa: Weak<AttestationAgent>,
+    mut closed: watch::Receiver<bool>,
+    mut changed: watch::Receiver<()>,
+) {
+    let mut failures = 0;
+    let mut due = false;
+    loop {
+        let Some(agent) = aa.upgrade() else {
+            return;
+        };
+        let delay = match refresh(&agent, due).await {
+            Ok(delay) => {
+                failures = 0;
+                delay.map(|delay| delay.max(MIN_REFRESH_INTERVAL))
+            }
+            Err(Error::Shutdown) => return,
+            Err(e) => {
+                // The token is fetched with retries already, so this backs
+                // off further on persistent failures
+                failures += 1;
+                let retry_config = agent.config.read().await.retry.clone();
+                let backoff = retry_config.backoff(failures, rand::thread_rng().gen());
+                warn!(
+                    target: telemetry::TOKEN,
+                    failures,
+                    ?backoff,
+                    error = ?e,
+                    "Refreshing the KBS token failed"
+                );
+                Some(backoff)
+            }
+        };
+        drop(agent);
+        // The token cached by the refresh itself is no change
+        changed.borrow_and_update();
+
+        // Wait for the refresh, or for a change that may move it, unless AA
+        // is shut down or dropped before
+        let refresh_due = async {
+            match delay {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = closed.wait_for(|closed| *closed) => return,
+            () = refresh_due => due = true,
+            result = changed.changed() => {
+                if result.is_err() {
+                    return;
+                }
+                due = false;
+            }
+        }
+    }
+}
+
+/// Get a new KBS token into the cache if the refresh is `due`, or if the
+/// cached token expires within the margin, and tell how long until the
+/// token is to be refreshed again. `None` if there is nothing to refresh
+/// until the configuration or the cached token changes, as refreshing is
+/// disabled, or no token with an expiry is cached. Only a token that a
+/// caller got is refreshed, s.t. AA does not attest on its own.
+async fn refresh(aa: &AttestationAgent, due: bool) -> Result<Option<Duration>> {
+    let token_configs = aa.config.read().await.token_configs.clone();
+    if !token_configs.kbs.auto_refresh || !token_configs.cache {
+        debug!(target: telemetry::TOKEN, "KBS token refresh is disabled");
+        return Ok(None);
+    }
+    let url = token_url(&token_configs, TokenType::Kbs);
+    let margin = token_configs.kbs.refresh_margin_seconds;
+
+    let Some(mut expires_at) = aa.token_cache.lock().await.expiry(TokenType::Kbs, &url) else {
+        debug!(target: telemetry::TOKEN, "No KBS token is cached to refresh");
+        return Ok(None);
+    };
+    if due || expires_at <= now().saturating_add(margin) {
+        aa.get_token_with_cache(TokenType::Kbs.as_ref(), Value::Null, None, true)
+            .await?;
+        let Some(refreshed) = aa.token_cache.lock().await.expiry(TokenType::Kbs, &url) else {
+            warn!(
+                target: telemetry::TOKEN,
+                "KBS token has no expiry, it is not refreshed"
+            );
+            return Ok(None);
+        };
+        info!(target: telemetry::TOKEN, expires_at = refreshed, "Refreshed the KBS token");
+        expires_at = refreshed;
+    }
+
+    let refresh_at = expires_at.saturating_sub(margin);
+    Ok(Some(Duration::from_secs(refresh_at.saturating_sub(now()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use kbs_types::Tee;
+    use serde_json::json;
+    use tokio::sync::mpsc;
+
+    use crate::{
+        token::{cache::now, http_stub},
+        AttestationAPIs, AttestationAgent, Config,
+    };
+
+    /// AA with a KBS stub that issues tokens living for 3 seconds, and the
+    /// receiver of a message per attestation to the stub.
+    async fn agent(auto_refresh: bool) -> (Arc<AttestationAgent>, mpsc::UnboundedReceiver<()>) {
+        let (attested, attestations) = mpsc::unbounded_channel();
+        let (url, _) = http_stub::start(move |request| {
+            if request.contains("/kbs/v0/auth") {
+                return http_stub::response("200 OK", r#"{"nonce":"MTIzNDU2","extra-params":""}"#);
+            }
+
+            attested.send(()).unwrap();
+            let claims = URL_SAFE_NO_PAD.encode(json!({ "exp": now() + 3 }).to_string());
+            let token = format!("e30.{claims}.c2ln");
+            http_stub::response("200 OK", &json!({ "token": token }).to_string())
+        })
+        .await;
+
+        let config = Config::from_toml_str(&format!(
+            r#"
+[token_configs]
+cache = true
+cache_skew_seconds = 0
+
+[token_configs.kbs]
+url = "{url}"
+auto_refresh = {auto_refresh}
+refresh_margin_seconds = 1
+
+[token_configs.coco_as]
+url = "http://127.0.0.1:8000"
+
+[eventlog_config]
+enabled = false
+"#
+        ))
+        .unwrap();
+        let aa = Arc::new(AttestationAgent::with_config_and_tee(config, Tee::Sample).unwrap());
+        (aa, attestations)
+    }
+
+    /// Let the refresher run for a while, which takes no time as the clock
+    /// is paused, and count the attestations meanwhile.
+    async fn attestations_within(
+        attestations: &mut mpsc::UnboundedReceiver<()>,
+        secs: u64,
+    ) -> usize {
+        tokio::time::sleep(Duration::from_secs(secs)).await;
+        let mut count = 0;
+        while attestations.try_recv().is_ok() {
+            count += 1;
+        }
+        count
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_short_lived_tokens() {
+        let (aa, mut attestations) = agent(true).await;
+        let refresher = aa.start_token_refresh().await.unwrap();
+
+        // Nothing is refreshed before a caller gets a token
+        assert_eq!(attestations_within(&mut attestations, 60).await, 0);
+
+        // The token lives for 3 seconds, and is refreshed 1 second before
+        // it expires
+        aa.get_token("kbs").await.unwrap();
+        assert_eq!(attestations.recv().await, Some(()));
+        tokio::time::timeout(Duration::from_secs(10), async {
+            for _ in 0..3 {
+                assert_eq!(attestations.recv().await, Some(()));
+            }
+        })
+        .await
+        .unwrap();
+
+        // The refreshed token is served from the cache
+        aa.get_token("kbs").await.unwrap();
+        assert!(attestations.try_recv().is_err());
+
+        aa.shutdown().await.unwrap();
+        while !refresher.is_finished() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refresh_follows_configuration() {
+        let (aa, mut attestations) = agent(false).await;
+        let _refresher = aa.start_token_refresh().await.unwrap();
+        aa.get_token("kbs").await.unwrap();
+        assert_eq!(attestations_within(&mut attestations, 60).await, 1);
+
+        for _ in 0..2 {
+            aa.update_configuration("[token_configs.kbs]\nauto_refresh = true\n")
+                .await
+                .unwrap();
+            aa.get_token("kbs").await.unwrap();
+            assert!(attestations_within(&mut attestations, 10).await >= 4);
+
+            aa.update_configuration("[token_configs.kbs]\nauto_refresh = false\n")
+                .await
+                .unwrap();
+            // A refresh may be under way meanwhile
+            attestations_within(&mut attestations, 5).await;
+            assert_eq!(attestations_within(&mut attestations, 60).await, 0);
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::fmt;
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// What a token service returned, see [`crate::AttestationAPIs::get_token_ex`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct TokenResponse {
+    /// The token, e.g. a JWT.
+    pub token: String,
+
+    /// The TEE key pair in PKCS#1 PEM, whose public key is bound into the
+    /// KBS token, to decrypt the resources that KBS returns. Only KBS
+    /// tokens come with one.
+    pub tee_keypair: Option<String>,
+
+    /// The `exp` claim of the token, if it is a JWT that carries one.
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// The token and TEE key pair returned by the KBS token getter, in the
+/// shape the `get_token` API has always returned them.
+#[derive(Serialize, Deserialize)]
+struct KbsMessage {
+    token: String,
+
+    #[serde(default)]
+    tee_keypair: Option<String>,
+}
+
+impl fmt::Debug for TokenResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenResponse")
+            .field("token", &self.token)
+            .field("tee_keypair", &self.tee_keypair.as_ref().map(|_| "***"))
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl TokenResponse {
+    pub fn new(token: String, tee_keypair: Option<String>) -> Self {
+        let expires_at = JwtClaims::parse(&token)
+            .and_then(|claims| claims.exp)
+            .and_then(|exp| i64::try_from(exp).ok())
+            .and_then(|exp| DateTime::from_timestamp(exp, 0));
+        Self {
+            token,
+            tee_keypair,
+            expires_at,
+        }
+    }
+
+    /// The token of a service that returns it as is, without a TEE key
+    /// pair.
+    #[cfg(feature = "coco_as")]
+    pub(crate) fn from_token(token: Vec<u8>) -> Result<Self> {
+        let token = String::from_utf8(token).context("token is not UTF-8")?;
+        Ok(Self::new(token, None))
+    }
+
+    /// Parse the output of the `get_token` API. A token that comes with the
+    /// TEE key pair is the JSON object `{"token": ..., "tee_keypair": ...}`,
+    /// while other tokens are returned as is.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let token = std::str::from_utf8(bytes).context("token is not UTF-8")?;
+        match serde_json::from_str::<KbsMessage>(token) {
+            Ok(message) => Ok(Self::new(message.token, message.tee_keypair)),
+            Err(_) => Ok(Self::new(token.trim().to_string(), None)),
+        }
+    }
+
+    /// Serialize the response as the `get_token` API returns it, see
+    /// [`TokenResponse::from_bytes`].
+    pub fn into_bytes(self) -> Result<Vec<u8>> {
+        let Some(tee_keypair) = self.tee_keypair else {
+            return Ok(self.token.into_bytes());
+        };
+        let message = KbsMessage {
+            token: self.token,
+            tee_keypair: Some(tee_keypair),
+        };
+        Ok(serde_json::to_vec(&message)?)
+    }
+}
+
+/// The claims of a JWT that tell when it is valid, in seconds since the
+/// UNIX epoch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct JwtClaims {
+    pub(crate) exp: Option<u64>,
+    pub(crate) nbf: Option<u64>,
+}
+
+impl JwtClaims {
+    /// Parse the claims from the payload of the JWT, `None` if the token is
+    /// not a JWT. The signature is not verified, as the claims are only
+    /// used to decide when to fetch a new token.
+    pub(crate) fn parse(jwt: &str) -> Option<Self> {
+        let claims = jwt_payload(jwt)?;
+        Some(Self {
+            exp: claims.get("exp").and_then(Value::as_u64),
+            nbf: claims.get("nbf").and_then(Value::as_u64),
+        })
+    }
+}
+
+/// All the claims of the payload of the JWT, `None` if the token is not a
+/// JWT. The signature is not verified.
+pub(crate) fn jwt_payload(jwt: &str) -> Option<Map<String, Value>> {
+    let mut parts = jwt.split('.');
+    let (Some(_header), Some(payload), Some(_signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let Value::Object(claims) = serde_json::from_slice(&payload).ok()? else {
+        return None;
+    };
+    Some(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use chrono::DateTime;
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::{JwtClaims, TokenResponse};
+
+    fn jwt(claims: serde_json::Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"ES256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("{header}.{claims}.c2lnbmF0dXJl")
+    }
+
+    #[rstest]
+    #[case::expiry(json!({"exp": 1700000000}), Some(1700000000), None)]
+    #[case::not_before(json!({"exp": 1700000000, "nbf": 1600000000}), Some(1700000000), Some(1600000000))]
+    #[case::neither(json!({"iss": "kbs"}), None, None)]
+    #[case::not_numbers(json!({"exp": "tomorrow", "nbf": -1}), None, None)]
+    fn parse_jwt_claims(
+        #[case] claims: serde_json::Value,
+        #[case] exp: Option<u64>,
+        #[case] nbf: Option<u64>,
+    ) {
+        assert_eq!(JwtClaims::parse(&jwt(claims)), Some(JwtClaims { exp, nbf }));
+    }
+
+    #[rstest]
+    #[case::opaque("not a jwt")]
+    #[case::not_base64("a.!!!.c")]
+    #[case::not_json("a.bm90IGpzb24.c")]
+    #[case::not_an_object("a.WzFd.c")]
+    #[case::too_many_parts("a.e30.c.d")]
+    fn parse_malformed_jwt(#[case] token: &str) {
+        assert_eq!(JwtClaims::parse(token), None);
+    }
+
+    #[test]
+    fn parse_token_only() {
+        let token = jwt(json!({"exp": 1700000000}));
+        let response = TokenResponse::from_bytes(format!("{token}\n").as_bytes()).unwrap();
+        assert_eq!(response.token, token);
+        assert_eq!(response.tee_keypair, None);
+        assert_eq!(response.expires_at, DateTime::from_timestamp(1700000000, 0));
+        assert_eq!(response.into_bytes().unwrap(), token.as_bytes());
+
+        // Tokens that are not JWTs are passed through as well
+        let response = TokenResponse::from_bytes(b"opaque").unwrap();
+        assert_eq!(response.token, "opaque");
+        assert_eq!(response.expires_at, None);
+
+        let message = json!({"token": token});
+        let response = TokenResponse::from_bytes(message.to_string().as_bytes()).unwrap();
+        assert_eq!(response.token, token);
+        assert_eq!(response.tee_keypair, None);
+    }
+
+    #[test]
+    fn parse_token_with_keypair() {
+        let token = jwt(json!({"exp": 1700000000}));
+        let message = json!({"token": token, "tee_keypair": "key"});
+        let response = TokenResponse::from_bytes(message.to_string().as_bytes()).unwrap();
+        assert_eq!(
+            response,
+            TokenResponse {
+                token: token.clone(),
+                tee_keypair: Some("key".into()),
+                expires_at: DateTime::from_timestamp(1700000000, 0),
+            }
+        );
+        let debug = format!("{response:?}");
+        assert!(debug.contains(r#"tee_keypair: Some("***")"#), "{debug}");
+        assert!(!debug.contains(r#""key""#), "{debug}");
+
+        // The same bytes as the KBS token getter has always returned
+        let bytes = response.into_bytes().unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&bytes).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn invalid_token() {
+        TokenResponse::from_bytes(b"\xff\xfe").unwrap_err();
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{collections::HashMap, future::Future, hash::Hash, sync::Arc};
+
+use anyhow::Result;
+use tokio::sync::OnceCell;
+
+/// Deduplicates concurrent calls for the same key. Callers arriving while a
+/// call is in flight wait for it and share its result. If the call fails,
+/// the waiters do not share the error but retry the call one at a time, s.t.
+/// one failed attestation does not fail every pending request.
+pub struct SingleFlight<K, V> {
+    calls: std::sync::Mutex<HashMap<K, Arc<OnceCell<V>>>>,
+}
+
+impl<K, V> Default for SingleFlight<K, V> {
+    fn default() -> Self {
+        Self {
+            calls: Default::default(),
+        }
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> SingleFlight<K, V> {
+    pub async fn run<F, Fut>(&self, key: K, f: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>>,
+    {
+        let call = self
+            .calls
+            .lock()
+            .expect("single flight lock poisoned")
+            .entry(key.clone())
+            .or_default()
+            .clone();
+
+        let value = call.get_or_try_init(f).await?.clone();
+
+        // The call is done, s.t. later callers start a new one. The entry
+        // might already be replaced if another waiter got here first.
+        let mut calls = self.calls.lock().expect("single flight lock poisoned");
+        if matches!(calls.get(&key), Some(c) if Arc::ptr_eq(c, &call)) {
+            calls.remove(&key);
+        }
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use anyhow::bail;
+
+    use super::SingleFlight;
+
+    async fn mock_get_token(calls: &AtomicUsize, fail_first: bool) -> anyhow::Result<Vec<u8>> {
+        let n = calls.fetch_add(1, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if fail_first && n == 0 {
+            bail!("KBS unreachable");
+        }
+        Ok(format!("token-{n}").into_bytes())
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_calls_share_one_fetch() {
+        let flight = Arc::new(SingleFlight::<&str, Vec<u8>>::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..64)
+            .map(|_| {
+                let flight = flight.clone();
+                let calls = calls.clone();
+                tokio::spawn(
+                    async move { flight.run("kbs", || mock_get_token(&calls, false)).await },
+                )
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(handle.await.unwrap().unwrap(), b"token-0");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // A call after the flight is done fetches again
+        let token = flight
+            .run("kbs", || mock_get_token(&calls, false))
+            .await
+            .unwrap();
+        assert_eq!(token, b"token-1");
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn waiters_retry_after_leader_fails() {
+        let flight = Arc::new(SingleFlight::<&str, Vec<u8>>::default());
+        let calls = Arc::new(AtomicUsize::new(0));
+
+        let leader = {
+            let flight = flight.clone();
+            let calls = calls.clone();
+            tokio::spawn(async move { flight.run("kbs", || mock_get_token(&calls, true)).await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let waiters: Vec<_> = (0..16)
+            .map(|_| {
+                let flight = flight.clone();
+                let calls = calls.clone();
+                tokio::spawn(
+                    async move { flight.run("kbs", || mock_get_token(&calls, true)).await },
+                )
+            })
+            .collect();
+
+        assert!(leader.await.unwrap().is_err());
+        for waiter in waiters {
+            assert_eq!(waiter.await.unwrap().unwrap(), b"token-1");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Token services reached over a unix socket or vsock, e.g. an attestation
+//! proxy in the guest or on the host, rather than over TCP.
+//!
+//! The HTTP client of the token getters only connects over TCP. So the
+//! requests to such a service are sent with hyper over a connection to the
+//! socket of the service, which is made for each request. The responses are
+//! handled the same as with a service reached over TCP.
+
+use std::{path::PathBuf, time::Duration};
+
+#[cfg(feature = "coco_as")]
+use anyhow::Context;
+use anyhow::{anyhow, bail, Result};
+#[cfg(feature = "coco_as")]
+use http_body_util::{BodyExt, Full};
+#[cfg(feature = "coco_as")]
+use hyper::{body::Bytes, Request, Response};
+#[cfg(feature = "coco_as")]
+use hyper_util::rt::TokioIo;
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::UnixStream,
+};
+use url::Url;
+
+/// Schemes of the URLs of the token services.
+pub(crate) const SERVICE_SCHEMES: &[&str] = &["http", "https", "unix", "vsock"];
+
+/// How a token service is connected to, by the scheme of its URL.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Endpoint {
+    /// `http://` and `https://`, connected to by the HTTP client.
+    Tcp,
+
+    /// `unix:///path/of/socket`, serving plain HTTP.
+    Unix(PathBuf),
+
+    /// `vsock://<cid>:<port>`, serving plain HTTP.
+    #[cfg(feature = "vsock")]
+    Vsock { cid: u32, port: u32 },
+}
+
+impl Endpoint {
+    pub(crate) fn parse(url: &str) -> Result<Self> {
+        // The error of the URL would show the credentials in it
+        let parsed = Url::parse(url).map_err(|_| anyhow!("not a valid URL"))?;
+        match parsed.scheme() {
+            "http" | "https" => Ok(Self::Tcp),
+            "unix" => {
+                if parsed.host_str().is_some_and(|host| !host.is_empty()) {
+                    bail!("a unix URL has no host, e.g. `unix:///run/as.sock`");
+                }
+                if parsed.path().is_empty() || parsed.path().ends_with('/') {
+                    bail!("no socket path in the unix URL");
+                }
+                Ok(Self::Unix(PathBuf::from(parsed.path())))
+            }
+            #[cfg(not(feature = "vsock"))]
+            "vsock" => bail!("a vsock URL needs AA built with the `vsock` feature"),
+            #[cfg(feature = "vsock")]
+            "vsock" => {
+                let (Some(cid), Some(port)) = (parsed.host_str(), parsed.port()) else {
+                    bail!("no CID and port in the vsock URL");
+                };
+                let cid = cid
+                    .parse()
+                    .map_err(|_| anyhow!("the host of a vsock URL is a CID"))?;
+                Ok(Self::Vsock {
+                    cid,
+                    port: port.into(),
+                })
+            }
+            scheme => bail!(
+                "scheme `{scheme}` is not one of {}",
+                SERVICE_SCHEMES.join(", ")
+            ),
+        }
+    }
+
+    /// Connect to the service at `url` over its socket, within `timeout`
+    /// if any.
+    pub(crate) async fn connect(
+        &self,
+        url: &str,
+        timeout: Option<Duration>,
+    ) -> Result<Box<dyn Stream>, ConnectError> {
+        let connected = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.connect_stream())
+                .await
+                .unwrap_or_else(|_| Err(std::io::ErrorKind::TimedOut.into())),
+            None => self.connect_stream().await,
+        };
+        connected.map_err(|source| ConnectError {
+            endpoint: url.to_string(),
+            source,
+        })
+    }
+
+    async fn connect_stream(&self) -> std::io::Result<Box<dyn Stream>> {
+        match self {
+            Self::Tcp => Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "TCP is connected to by the HTTP client",
+            )),
+            Self::Unix(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+            #[cfg(feature = "vsock")]
+            Self::Vsock { cid, port } => Ok(Box::new(
+                tokio_vsock::VsockStream::connect(*cid, *port).await?,
+            )),
+        }
+    }
+
+    /// Send the request to the service at `url` on a new connection to its
+    /// socket, and read the whole response. `request_timeout` limits the
+    /// request from connecting to reading the response.
+    #[cfg(feature = "coco_as")]
+    pub(crate) async fn send(
+        &self,
+        url: &str,
+        request: Request<Full<Bytes>>,
+        connect_timeout: Option<Duration>,
+        request_timeout: Option<Duration>,
+    ) -> Result<Response<Bytes>> {
+        let exchange = async {
+            let stream = self.connect(url, connect_timeout).await?;
+            let (mut sender, connection) =
+                hyper::client::conn::http1::handshake(TokioIo::new(stream))
+                    .await
+                    .context("HTTP handshake")?;
+            // The connection is closed once the sender is dropped
+            tokio::spawn(connection);
+            let response = sender.send_request(request).await.context("send request")?;
+            let (parts, body) = response.into_parts();
+            let body = body.collect().await.context("read response")?.to_bytes();
+            Ok::<_, anyhow::Error>(Response::from_parts(parts, body))
+        };
+        match request_timeout {
+            Some(timeout) => {
+                tokio::time::timeout(timeout, exchange)
+                    .await
+                    .map_err(|_| TimedOut {
+                        endpoint: url.to_string(),
+                    })?
+            }
+            None => exchange.await,
+        }
+    }
+}
+
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+/// The token service cannot be connected to, which may be fixed by
+/// retrying.
+#[derive(Debug, thiserror::Error)]
+#[error("connect to {endpoint}")]
+pub(crate) struct ConnectError {
+    endpoint: String,
+    #[source]
+    source: std::io::Error,
+}
+
+/// The token service did not answer within the request timeout, which may
+/// be fixed by retrying.
+#[derive(Debug, thiserror::Error)]
+#[error("request to {endpoint} timed out")]
+pub(crate) struct TimedOut {
+    endpoint: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use super::{ConnectError, Endpoint};
+
+    #[rstest]
+    #[case("https://kbs.example.com:8080", Endpoint::Tcp)]
+    #[case("http://127.0.0.1:8080/prefix", Endpoint::Tcp)]
+    #[case("unix:///run/as.sock", Endpoint::Unix(PathBuf::from("/run/as.sock")))]
+    fn parse_endpoint(#[case] url: &str, #[case] expected: Endpoint) {
+        assert_eq!(Endpoint::parse(url).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "ftp://as.example.com",
+        "scheme `ftp` is not one of http, https, unix, vsock"
+    )]
+    #[case(
+        "as.example.com:8080",
+        "scheme `as.example.com` is not one of http, https, unix, vsock"
+    )]
+    #[case("unix://host/run/as.sock", "a unix URL has no host")]
+    #[case("unix:///run/", "no socket path in the unix URL")]
+    fn invalid_endpoint(#[case] url: &str, #[case] expected: &str) {
+        let e = Endpoint::parse(url).unwrap_err();
+        assert!(e.to_string().contains(expected), "{e}");
+    }
+
+    #[cfg(feature = "vsock")]
+    #[rstest]
+    #[case("vsock://2:50000", Ok(Endpoint::Vsock { cid: 2, port: 50000 }))]
+    #[case("vsock://2", Err("no CID and port in the vsock URL"))]
+    #[case("vsock://host:50000", Err("the host of a vsock URL is a CID"))]
+    #[case("vsock://4294967296:50000", Err("the host of a vsock URL is a CID"))]
+    fn parse_vsock_endpoint(#[case] url: &str, #[case] expected: Result<Endpoint, &str>) {
+        match (Endpoint::parse(url), expected) {
+            (Ok(endpoint), Ok(expected)) => assert_eq!(endpoint, expected),
+            (Err(e), Err(expected)) => assert!(e.to_string().contains(expected), "{e}"),
+            (parsed, expected) => panic!("{url}: {parsed:?}, expected {expected:?}"),
+        }
+    }
+
+    #[cfg(not(feature = "vsock"))]
+    #[test]
+    fn vsock_needs_feature() {
+        let e = Endpoint::parse("vsock://2:50000").unwrap_err();
+        assert!(e.to_string().contains("`vsock` feature"), "{e}");
+    }
+
+    #[tokio::test]
+    async fn missing_socket_is_a_connect_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("unix://{}", dir.path().join("as.sock").display());
+        let e = Endpoint::parse(&url)
+            .unwrap()
+            .connect(&url, None)
+            .await
+            .err()
+            .unwrap();
+        let e = anyhow::Error::from(e);
+        assert!(e.downcast_ref::<ConnectError>().is_some(), "{e:#}");
+        assert!(crate::token::is_retryable(&e));
+    }
+}
@@ -0,0 +1,65 @@
+#!/usr/bin/env python3
+#
+# Copyright (c) 2026 The Confidential Containers Authors
+#
+# SPDX-License-Identifier: Apache-2.0
+#
+# Write the RTMRs that a CCEL and the AAEL of PCR 17 replay to, as the
+# expected values of the combined eventlog, e.g.
+#
+#   ./tdx_rtmrs.py ccel.bin ../../../deps/aael/test/aael.log > tdx_rtmrs.json
+#
+# The CCEL is in the TCG2 crypto agile format with SHA-384 digests, and
+# names the RTMRs by their CC measurement register index, RTMR 0 being 1.
+# Events of type EV_NO_ACTION are not extended, and the space after the last
+# event is filled with 0xff or zeros. PCR 17 maps to RTMR 3, see
+# the TD event log of the td-shim spec, and each AAEL entry is extended
+# with the SHA-384 digest of its line.
+
+import hashlib
+import json
+import struct
+import sys
+
+EV_NO_ACTION = 0x3
+TPM_ALG_SHA384 = 0xC
+
+rtmrs = [bytes(48)] * 4
+
+
+def extend(rtmr, digest):
+    rtmrs[rtmr] = hashlib.sha384(rtmrs[rtmr] + digest).digest()
+
+
+with open(sys.argv[1], "rb") as ccel:
+    log = ccel.read()
+
+# The first event is in the SHA-1 format, and lists the digest sizes
+_, _, event_size = struct.unpack_from("<II20xI", log, 0)
+offset = 32 + event_size
+sizes = {}
+count = struct.unpack_from("<I", log, 32 + 24)[0]
+for i in range(count):
+    alg, size = struct.unpack_from("<HH", log, 32 + 28 + 4 * i)
+    sizes[alg] = size
+
+while log[offset:].strip(b"\xff") and log[offset:].strip(b"\x00"):
+    index, event_type, count = struct.unpack_from("<III", log, offset)
+    offset += 12
+    digest = None
+    for _ in range(count):
+        (alg,) = struct.unpack_from("<H", log, offset)
+        offset += 2
+        if alg == TPM_ALG_SHA384:
+            digest = log[offset : offset + sizes[alg]]
+        offset += sizes[alg]
+    (size,) = struct.unpack_from("<I", log, offset)
+    offset += 4 + size
+    if event_type != EV_NO_ACTION:
+        extend(index - 1, digest)
+
+with open(sys.argv[2], "rb") as aael:
+    for entry in aael.read().splitlines():
+        extend(3, hashlib.sha384(entry).digest())
+
+print(json.dumps([rtmr.hex() for rtmr in rtmrs], indent=2))
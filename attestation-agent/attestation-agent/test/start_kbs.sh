@@ -0,0 +1,2 @@
+#! /bin/bash
+kbs --config-file /etc/kbs-config.toml
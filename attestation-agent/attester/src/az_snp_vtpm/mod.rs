@@ -3,15 +3,49 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::Attester;
+use super::tpm::{self, TpmOptions};
+use super::{
+    Attester, AttesterCapabilities, AttesterError, HashAlgorithm, TPM_PCRS, VTPM_REPORT_DATA_SIZE,
+};
+use crate::detect::{Host, Platform};
 use anyhow::{bail, Context, Result};
-use az_snp_vtpm::{imds, is_snp_cvm, vtpm};
+use az_snp_vtpm::{imds, vtpm};
+use base64::Engine;
+use kbs_types::Tee;
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// SMBIOS system vendor of Hyper-V VMs, which Azure CVMs are.
+const AZURE_SYS_VENDOR: &str = "Microsoft Corporation";
+const SYS_VENDOR_PATH: &str = "/sys/class/dmi/id/sys_vendor";
+
+fn is_hyperv_vm(platform: &impl Platform) -> bool {
+    platform
+        .read_to_string(SYS_VENDOR_PATH)
+        .is_ok_and(|vendor| vendor.trim() == AZURE_SYS_VENDOR)
+}
+
+/// The certificate of the vTPM AK, which Azure provisions in NV.
+fn get_ak_cert() -> Result<Option<Vec<u8>>> {
+    let options = TpmOptions::default();
+    let mut context = tpm::connect(&options.tcti)?;
+    tpm::read_nv_index(&mut context, options.ak_cert_index)
+}
 
 pub fn detect_platform() -> bool {
-    match is_snp_cvm() {
-        Ok(is_snp) => is_snp,
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    // Only ask the vTPM for the HCL report on Hyper-V, other VMs might have
+    // no TPM or a TPM without the NV index.
+    if !is_hyperv_vm(platform) {
+        return false;
+    }
+
+    match platform.hcl_report_tee() {
+        Ok(tee) => tee == Tee::AzSnpVtpm,
         Err(err) => {
             debug!("Failed to retrieve Azure HCL data from vTPM: {}", err);
             false
@@ -27,6 +61,11 @@ struct Evidence {
     quote: vtpm::Quote,
     report: Vec<u8>,
     vcek: String,
+    /// DER of the certificate of the vTPM AK, base64 encoded. The verifier
+    /// binds the AK to the SNP report by the HCL runtime data in `report`,
+    /// the certificate is informational.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ak_cert: Option<String>,
 }
 
 #[async_trait::async_trait]
@@ -36,16 +75,61 @@ impl Attester for AzSnpVtpmAttester {
         let quote = vtpm::get_quote(&report_data)?;
         let certs = imds::get_certs()?;
         let vcek = certs.vcek;
+        let ak_cert = get_ak_cert()
+            .context("read vTPM AK cert")?
+            .map(|cert| base64::engine::general_purpose::STANDARD.encode(cert));
 
         let evidence = Evidence {
             quote,
             report,
             vcek,
+            ak_cert,
         };
 
         Ok(serde_json::to_string(&evidence)?)
     }
 
+    fn report_data_len(&self) -> Option<usize> {
+        Some(VTPM_REPORT_DATA_SIZE)
+    }
+
+    fn runtime_measurement_registers(&self) -> Option<RangeInclusive<u64>> {
+        Some(TPM_PCRS)
+    }
+
+    fn capabilities(&self) -> AttesterCapabilities {
+        AttesterCapabilities {
+            supports_runtime_measurement: true,
+            report_data_len: VTPM_REPORT_DATA_SIZE,
+            // Runtime measurements are extended into the SHA-256 PCR bank
+            supported_hash_algorithms: vec![HashAlgorithm::Sha256],
+            init_data_supported: false,
+        }
+    }
+
+    async fn get_runtime_measurement(
+        &self,
+        register_index: u64,
+        algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        if algorithm != HashAlgorithm::Sha256 {
+            return Err(
+                AttesterError::Unsupported("reading PCRs of other banks than sha256").into(),
+            );
+        }
+        if !TPM_PCRS.contains(&register_index) {
+            bail!("Invalid PCR index: {}", register_index);
+        }
+
+        // The quote carries the whole SHA-256 PCR bank, the nonce is unused
+        let quote = vtpm::get_quote(&[0; 32]).context("read PCRs from vTPM")?;
+        let pcr = quote
+            .pcrs_sha256()
+            .nth(register_index as usize)
+            .with_context(|| format!("PCR {register_index} is missing in the quote"))?;
+        Ok(pcr.to_vec())
+    }
+
     async fn extend_runtime_measurement(
         &self,
         event_digest: Vec<u8>,
@@ -55,7 +139,7 @@ impl Attester for AzSnpVtpmAttester {
             .as_slice()
             .try_into()
             .context("expected sha256 digest")?;
-        if register_index > 23 {
+        if !TPM_PCRS.contains(&register_index) {
             bail!("Invalid PCR index: {}", register_index);
         }
         let pcr: u8 = register_index as u8;
@@ -65,3 +149,29 @@ impl Attester for AzSnpVtpmAttester {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_runtime_measurement_registers() {
+        let registers = AzSnpVtpmAttester.runtime_measurement_registers().unwrap();
+        assert!(registers.contains(&0));
+        assert!(registers.contains(&17));
+        assert!(!registers.contains(&24));
+    }
+
+    #[test]
+    fn test_capabilities() {
+        assert_eq!(
+            AzSnpVtpmAttester.capabilities(),
+            AttesterCapabilities {
+                supports_runtime_measurement: true,
+                report_data_len: 32,
+                supported_hash_algorithms: vec![HashAlgorithm::Sha256],
+                init_data_supported: false,
+            }
+        );
+    }
+}
@@ -3,17 +3,26 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::Attester;
+use super::{
+    Attester, AttesterCapabilities, AttesterError, HashAlgorithm, TPM_PCRS, VTPM_REPORT_DATA_SIZE,
+};
+use crate::detect::{Host, Platform};
 use anyhow::*;
 use az_tdx_vtpm::vtpm::Quote as TpmQuote;
-use az_tdx_vtpm::{hcl, imds, is_tdx_cvm, vtpm};
+use az_tdx_vtpm::{hcl, imds, vtpm};
+use kbs_types::Tee;
 use log::{debug, info};
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 use std::result::Result::Ok;
 
 pub fn detect_platform() -> bool {
-    match is_tdx_cvm() {
-        Ok(tdx) => tdx,
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    match platform.hcl_report_tee() {
+        Ok(tee) => tee == Tee::AzTdxVtpm,
         Err(err) => {
             debug!("Couldn't perform Azure TDX platform detection: {err}");
             false
@@ -49,6 +58,47 @@ impl Attester for AzTdxVtpmAttester {
         Ok(serde_json::to_string(&evidence)?)
     }
 
+    fn report_data_len(&self) -> Option<usize> {
+        Some(VTPM_REPORT_DATA_SIZE)
+    }
+
+    fn runtime_measurement_registers(&self) -> Option<RangeInclusive<u64>> {
+        Some(TPM_PCRS)
+    }
+
+    fn capabilities(&self) -> AttesterCapabilities {
+        AttesterCapabilities {
+            supports_runtime_measurement: true,
+            report_data_len: VTPM_REPORT_DATA_SIZE,
+            // Runtime measurements are extended into the SHA-256 PCR bank
+            supported_hash_algorithms: vec![HashAlgorithm::Sha256],
+            init_data_supported: false,
+        }
+    }
+
+    async fn get_runtime_measurement(
+        &self,
+        register_index: u64,
+        algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        if algorithm != HashAlgorithm::Sha256 {
+            return Err(
+                AttesterError::Unsupported("reading PCRs of other banks than sha256").into(),
+            );
+        }
+        if !TPM_PCRS.contains(&register_index) {
+            bail!("Invalid PCR index: {}", register_index);
+        }
+
+        // The quote carries the whole SHA-256 PCR bank, the nonce is unused
+        let quote = vtpm::get_quote(&[0; 32]).context("read PCRs from vTPM")?;
+        let pcr = quote
+            .pcrs_sha256()
+            .nth(register_index as usize)
+            .with_context(|| format!("PCR {register_index} is missing in the quote"))?;
+        Ok(pcr.to_vec())
+    }
+
     async fn extend_runtime_measurement(
         &self,
         event_digest: Vec<u8>,
@@ -58,7 +108,7 @@ impl Attester for AzTdxVtpmAttester {
             .as_slice()
             .try_into()
             .context("expected sha256 digest")?;
-        if register_index > 23 {
+        if !TPM_PCRS.contains(&register_index) {
             bail!("Invalid PCR index: {}", register_index);
         }
         let pcr: u8 = register_index as u8;
@@ -68,3 +118,21 @@ impl Attester for AzTdxVtpmAttester {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        assert_eq!(
+            AzTdxVtpmAttester.capabilities(),
+            AttesterCapabilities {
+                supports_runtime_measurement: true,
+                report_data_len: 32,
+                supported_hash_algorithms: vec![HashAlgorithm::Sha256],
+                init_data_supported: false,
+            }
+        );
+    }
+}
@@ -3,20 +3,32 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::Attester;
+use super::tsm_report::*;
+use super::{Attester, AttesterError};
+use crate::detect::{Host, Platform};
 use anyhow::*;
 use base64::Engine;
 use nix::fcntl::{open, OFlag};
 use nix::sys::stat::Mode;
 use nix::unistd::close;
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+
+mod token;
 
 const CCA_DEVICE_PATH: &str = "/dev/cca_attestation";
 
-// NOTE: The path might be different when the CCA feature is public available, will come back to update the actual path if needed.
+/// Size in bytes of the challenge of the realm token.
+const CCA_CHALLENGE_SIZE: usize = 64;
+
+/// The arm-cca-guest driver registers its configfs-tsm provider only if the
+/// RSI is present. Older kernels have the device of the out-of-tree driver.
 pub fn detect_platform() -> bool {
-    Path::new(CCA_DEVICE_PATH).exists()
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    TsmReportPath::new_in(&platform.path(TSM_REPORT_PATH), TsmReportProvider::Cca).is_ok()
+        || platform.exists(CCA_DEVICE_PATH)
 }
 
 #[derive(Debug, Default)]
@@ -41,12 +53,38 @@ nix::ioctl_readwrite!(cca_attestation_request, b'A', 1, cca_ioctl_request);
 #[async_trait::async_trait]
 impl Attester for CCAAttester {
     async fn get_evidence(&self, mut challenge: Vec<u8>) -> Result<String> {
-        challenge.resize(64, 0);
-        let token = attestation(challenge)?;
+        if challenge.len() > CCA_CHALLENGE_SIZE {
+            bail!("CCA Attester: Challenge must be no more than {CCA_CHALLENGE_SIZE} bytes");
+        }
+        challenge.resize(CCA_CHALLENGE_SIZE, 0);
+
+        let token = TsmReportPath::new(TsmReportProvider::Cca).map_or_else(
+            |notsm| {
+                attestation(challenge.clone()).context(format!(
+                    "CCA Attester: token generation using ioctl() fallback failed after a TSM report error ({notsm})"
+                ))
+            },
+            |tsm| {
+                tsm.attestation_report(TsmReportData::Cca(challenge.clone()))
+                    .context("CCA Attester: token generation using TSM reports failed")
+            },
+        )?;
+        token::check_token(&token, &challenge).context("CCA Attester: invalid token")?;
+
         let evidence = CcaEvidence { token };
         let ev = serde_json::to_string(&evidence).context("Serialize CCA evidence failed")?;
         Ok(ev)
     }
+
+    /// Realms have no runtime measurement registers that the guest can
+    /// extend yet.
+    async fn extend_runtime_measurement(
+        &self,
+        _event_digest: Vec<u8>,
+        _register_index: u64,
+    ) -> Result<()> {
+        Err(AttesterError::Unsupported("runtime measurement").into())
+    }
 }
 
 fn attestation(challenge: Vec<u8>) -> Result<Vec<u8>, Error> {
@@ -96,6 +134,33 @@ fn attestation(challenge: Vec<u8>) -> Result<Vec<u8>, Error> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_cca_capabilities() {
+        assert_eq!(
+            CCAAttester::default().capabilities(),
+            crate::AttesterCapabilities {
+                supports_runtime_measurement: false,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![],
+                init_data_supported: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cca_runtime_measurement_unsupported() {
+        let attester = CCAAttester::default();
+        assert_eq!(attester.runtime_measurement_registers(), None);
+        let e = attester
+            .extend_runtime_measurement(vec![0; 32], 17)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<AttesterError>(),
+            Some(AttesterError::Unsupported(_))
+        ));
+    }
+
     #[ignore]
     #[tokio::test]
     async fn test_cca_get_evidence() {
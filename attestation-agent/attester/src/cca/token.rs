@@ -0,0 +1,231 @@
+// Copyright (c) 2024 Arm Ltd.
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Sanity checks of the CCA attestation token, see the "Realm Management
+//! Monitor specification" of Arm. Only the CBOR structure is walked, the
+//! signatures are left to the verifier.
+
+use anyhow::{bail, ensure, Context, Result};
+
+/// CBOR tag of the CCA token collection.
+const CCA_TOKEN_COLLECTION_TAG: u64 = 399;
+
+/// Key of the CCA platform token in the collection.
+const CCA_PLATFORM_TOKEN_KEY: u64 = 44234;
+
+/// Key of the CCA realm token in the collection.
+const CCA_REALM_TOKEN_KEY: u64 = 44241;
+
+/// CBOR tag of COSE_Sign1.
+const COSE_SIGN1_TAG: u64 = 18;
+
+/// Key of the challenge in the claims of the realm token.
+const REALM_CHALLENGE_KEY: u64 = 10;
+
+const MAJOR_UINT: u8 = 0;
+const MAJOR_NINT: u8 = 1;
+const MAJOR_BSTR: u8 = 2;
+const MAJOR_TSTR: u8 = 3;
+const MAJOR_ARRAY: u8 = 4;
+const MAJOR_MAP: u8 = 5;
+const MAJOR_TAG: u8 = 6;
+
+/// Reader of the definite length CBOR items of the token.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Read the head of an item: its major type and argument.
+    fn head(&mut self) -> Result<(u8, u64)> {
+        let (&initial, rest) = self.data.split_first().context("truncated CBOR")?;
+        let major = initial >> 5;
+        let len = match initial & 0x1f {
+            info @ 0..=23 => {
+                self.data = rest;
+                return Ok((major, info as u64));
+            }
+            24 => 1,
+            25 => 2,
+            26 => 4,
+            27 => 8,
+            info => bail!("unsupported CBOR additional information {info}"),
+        };
+        ensure!(rest.len() >= len, "truncated CBOR");
+        let (argument, rest) = rest.split_at(len);
+        self.data = rest;
+        Ok((
+            major,
+            argument.iter().fold(0, |acc, b| (acc << 8) | *b as u64),
+        ))
+    }
+
+    fn bytes(&mut self, len: u64) -> Result<&'a [u8]> {
+        let len = usize::try_from(len)?;
+        ensure!(self.data.len() >= len, "truncated CBOR");
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn byte_string(&mut self) -> Result<&'a [u8]> {
+        let (major, len) = self.head()?;
+        ensure!(major == MAJOR_BSTR, "expected a byte string");
+        self.bytes(len)
+    }
+
+    /// Skip a whole item.
+    fn skip(&mut self) -> Result<()> {
+        let (major, argument) = self.head()?;
+        match major {
+            MAJOR_BSTR | MAJOR_TSTR => {
+                self.bytes(argument)?;
+            }
+            MAJOR_ARRAY => {
+                for _ in 0..argument {
+                    self.skip()?;
+                }
+            }
+            MAJOR_MAP => {
+                for _ in 0..argument.saturating_mul(2) {
+                    self.skip()?;
+                }
+            }
+            MAJOR_TAG => self.skip()?,
+            // Integers and simple values have no content
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Read a map keyed by integers, calling `f` with the reader at each
+    /// value, which `f` must consume.
+    fn int_map(&mut self, mut f: impl FnMut(u64, &mut Self) -> Result<()>) -> Result<()> {
+        let (major, entries) = self.head()?;
+        ensure!(major == MAJOR_MAP, "expected a map");
+        for _ in 0..entries {
+            let (major, key) = self.head()?;
+            ensure!(
+                major == MAJOR_UINT || major == MAJOR_NINT,
+                "expected an integer key"
+            );
+            f(key, self)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        ensure!(self.data.is_empty(), "trailing bytes after CBOR item");
+        Ok(())
+    }
+}
+
+/// The payload of a COSE_Sign1 structure, tagged or not.
+fn cose_sign1_payload(data: &[u8]) -> Result<&[u8]> {
+    let mut reader = Reader::new(data);
+    if data.first() == Some(&((MAJOR_TAG << 5) | COSE_SIGN1_TAG as u8)) {
+        reader.head()?;
+    }
+    let (major, len) = reader.head()?;
+    ensure!(major == MAJOR_ARRAY && len == 4, "expected a COSE_Sign1");
+    // Protected and unprotected headers
+    reader.skip()?;
+    reader.skip()?;
+    let payload = reader.byte_string()?;
+    // Signature
+    reader.skip()?;
+    reader.finish()?;
+    Ok(payload)
+}
+
+/// Check that the token is a CCA token collection with a platform and a
+/// realm token, and that the realm token carries the given challenge.
+pub fn check_token(token: &[u8], challenge: &[u8]) -> Result<()> {
+    let mut reader = Reader::new(token);
+    let (major, tag) = reader.head()?;
+    ensure!(
+        major == MAJOR_TAG && tag == CCA_TOKEN_COLLECTION_TAG,
+        "not a CCA token collection"
+    );
+
+    let mut platform_token = None;
+    let mut realm_token = None;
+    reader.int_map(|key, reader| {
+        let token = reader.byte_string()?;
+        match key {
+            CCA_PLATFORM_TOKEN_KEY => platform_token = Some(token),
+            CCA_REALM_TOKEN_KEY => realm_token = Some(token),
+            _ => {}
+        }
+        Ok(())
+    })?;
+    reader.finish()?;
+
+    let platform_token = platform_token.context("platform token is missing")?;
+    cose_sign1_payload(platform_token).context("invalid platform token")?;
+
+    let realm_token = realm_token.context("realm token is missing")?;
+    let claims = cose_sign1_payload(realm_token).context("invalid realm token")?;
+    let mut realm_challenge = None;
+    let mut reader = Reader::new(claims);
+    reader.int_map(|key, reader| {
+        if key == REALM_CHALLENGE_KEY {
+            realm_challenge = Some(reader.byte_string()?);
+        } else {
+            reader.skip()?;
+        }
+        Ok(())
+    })?;
+    reader.finish().context("invalid realm claims")?;
+
+    let realm_challenge = realm_challenge.context("realm challenge is missing")?;
+    ensure!(
+        realm_challenge == challenge,
+        "realm challenge does not match the report data"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A token of the structure of a CCA token collection, with dummy claim
+    /// values and signatures, whose realm challenge is the bytes 0 to 63.
+    const TOKEN: &[u8] = include_bytes!("../../test/cca/token.cbor");
+
+    fn challenge() -> Vec<u8> {
+        (0..64).collect()
+    }
+
+    #[test]
+    fn valid_token() {
+        check_token(TOKEN, &challenge()).unwrap();
+    }
+
+    #[test]
+    fn wrong_challenge() {
+        let e = check_token(TOKEN, &[0; 64]).unwrap_err();
+        assert!(e.to_string().contains("realm challenge"), "{e}");
+    }
+
+    #[test]
+    fn truncated_token() {
+        check_token(&TOKEN[..TOKEN.len() - 1], &challenge()).unwrap_err();
+        check_token(&TOKEN[..10], &challenge()).unwrap_err();
+    }
+
+    #[test]
+    fn not_a_collection() {
+        let mut token = TOKEN.to_vec();
+        // Tag 398 instead of 399
+        token[2] = 0x8e;
+        check_token(&token, &challenge()).unwrap_err();
+    }
+}
@@ -4,32 +4,49 @@
 //
 
 use super::Attester;
-use anyhow::{bail, Context, Ok, Result};
+use crate::detect::{Host, Platform};
+use anyhow::{anyhow, bail, Context, Ok, Result};
 use codicon::Decoder;
 use csv_rs::{
     api::guest::{AttestationReport, CsvGuest},
     certs::{ca, csv},
 };
 use serde::{Deserialize, Serialize};
-use std::path::Path;
 
 use hyper::body::HttpBody as _;
 use hyper::Client;
 use hyper_tls::HttpsConnector;
 
+/// Key Distribution Service of Hygon, which serves the HSK and CEK of a chip
+/// by its serial number.
+const HYGON_KDS_URL: &str = "https://cert.hygon.cn/hsk_cek";
+
 pub fn detect_platform() -> bool {
-    Path::new("/dev/csv-guest").exists()
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    platform.exists("/dev/csv-guest")
 }
 
+/// Certificates from the Hygon root key to the key that signs the report.
 #[derive(Serialize, Deserialize)]
 struct CertificateChain {
+    /// Hygon Signing Key, signed by the Hygon Root Key, from the KDS.
     hsk: ca::Certificate,
+    /// Chip Endorsement Key, signed by the HSK, from the KDS.
     cek: csv::Certificate,
+    /// Platform Endorsement Key, signed by the CEK, from the firmware. It
+    /// signs the attestation report.
     pek: csv::Certificate,
 }
 
+/// Evidence of the CSV attester, serialized as JSON, which is what the CSV
+/// verifier of the Attestation Service takes.
 #[derive(Serialize, Deserialize)]
 struct CsvEvidence {
+    /// Attestation report of the firmware, whose `report_data` is the report
+    /// data of the caller padded to 64 bytes.
     attestation_report: AttestationReport,
     cert_chain: CertificateChain,
     // Base64 Encoded CSV Serial Number (Used to identify HYGON chip ID)
@@ -39,8 +56,13 @@ struct CsvEvidence {
 #[derive(Debug, Default)]
 pub struct CsvAttester {}
 
+// TODO: check_init_data is not supported, as the CSV report has no field
+// that the host sets at launch. Compare the init data digest with it once
+// the firmware has one, and csv-rs exposes it.
 #[async_trait::async_trait]
 impl Attester for CsvAttester {
+    // TODO: sniff the version of the report, once csv-rs has the report
+    // format of CSV2 firmware. It only parses the one of CSV for now.
     async fn get_evidence(&self, mut report_data: Vec<u8>) -> Result<String> {
         if report_data.len() > 64 {
             bail!("CSV Attester: Report data must be no more than 64 bytes");
@@ -48,30 +70,43 @@ impl Attester for CsvAttester {
         report_data.resize(64, 0);
 
         let data = report_data.as_slice().try_into()?;
-        let mut csv_guest = CsvGuest::open().unwrap();
+        let mut csv_guest =
+            CsvGuest::open().map_err(|e| anyhow!("CSV Attester: open CSV guest device: {e:?}"))?;
 
-        let (attestation_report, report_signer) = csv_guest.get_report(Some(data), None).unwrap();
+        let (attestation_report, report_signer) = csv_guest
+            .get_report(Some(data), None)
+            .map_err(|e| anyhow!("CSV Attester: get attestation report: {e:?}"))?;
 
-        let cert_data = download_hskcek_from_kds(&report_signer.sn).await?;
-        let mut cert_data = &cert_data[..];
-        let hsk = ca::Certificate::decode(&mut cert_data, ()).unwrap();
-        let cek = csv::Certificate::decode(&mut cert_data, ()).unwrap();
-        let pek = csv::Certificate::decode(&mut &report_signer.pek_cert[..], ())?;
+        let cert_data = download_hskcek_from_kds(HYGON_KDS_URL, &report_signer.sn)
+            .await
+            .context("CSV Attester: download HSK and CEK from KDS")?;
+        let cert_chain = decode_cert_chain(&cert_data, &report_signer.pek_cert)?;
 
         let evidence = CsvEvidence {
             attestation_report,
-            cert_chain: CertificateChain { hsk, cek, pek },
+            cert_chain,
             serial_number: report_signer.sn.to_vec(),
         };
         serde_json::to_string(&evidence).context("Serialize CSV evidence failed")
     }
 }
 
-async fn download_hskcek_from_kds(sn: &[u8]) -> Result<Vec<u8>> {
-    let mut kds_url = String::from("https://cert.hygon.cn/hsk_cek?snumber=");
-    let chip_id = std::str::from_utf8(sn)?.trim_end_matches('\0');
+/// Decode the HSK and CEK, which the KDS serves one after the other, and the
+/// PEK of the firmware.
+fn decode_cert_chain(hsk_cek: &[u8], pek: &[u8]) -> Result<CertificateChain> {
+    let mut hsk_cek = hsk_cek;
+    let hsk = ca::Certificate::decode(&mut hsk_cek, ()).context("CSV Attester: decode HSK")?;
+    let cek = csv::Certificate::decode(&mut hsk_cek, ()).context("CSV Attester: decode CEK")?;
+    let pek = csv::Certificate::decode(&mut &pek[..], ()).context("CSV Attester: decode PEK")?;
 
-    kds_url += chip_id;
+    Ok(CertificateChain { hsk, cek, pek })
+}
+
+/// Get the HSK and CEK of the chip of the serial number `sn` from the KDS at
+/// `kds_url`.
+async fn download_hskcek_from_kds(kds_url: &str, sn: &[u8]) -> Result<Vec<u8>> {
+    let chip_id = std::str::from_utf8(sn)?.trim_end_matches('\0');
+    let kds_url = format!("{kds_url}?snumber={chip_id}");
 
     let https = HttpsConnector::new();
     let client = Client::builder().build::<_, hyper::Body>(https);
@@ -83,6 +118,9 @@ async fn download_hskcek_from_kds(sn: &[u8]) -> Result<Vec<u8>> {
         .body(hyper::Body::empty())?;
 
     let response = client.request(request).await?;
+    if !response.status().is_success() {
+        bail!("KDS returned {}", response.status());
+    }
 
     let mut response_body = Vec::new();
     let mut response = response.into_body();
@@ -96,8 +134,90 @@ async fn download_hskcek_from_kds(sn: &[u8]) -> Result<Vec<u8>> {
 
 #[cfg(test)]
 mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
     use super::*;
 
+    /// A KDS that answers one request with the given status, and with the
+    /// target of the request as the body.
+    async fn kds_stub(status: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hsk_cek", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 1024];
+            let len = stream.read(&mut request).await.unwrap();
+            let request = String::from_utf8_lossy(&request[..len]);
+            let target = request.split_whitespace().nth(1).unwrap_or_default();
+            let response = format!(
+                "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{target}",
+                target.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        url
+    }
+
+    /// The serial number is NUL padded, the padding is not sent to the KDS.
+    #[tokio::test]
+    async fn kds_request() {
+        let kds = kds_stub("200 OK").await;
+        let body = download_hskcek_from_kds(&kds, b"NZA0000001\0\0\0\0\0\0")
+            .await
+            .unwrap();
+        assert_eq!(body, b"/hsk_cek?snumber=NZA0000001");
+    }
+
+    #[tokio::test]
+    async fn kds_error_status() {
+        let kds = kds_stub("404 Not Found").await;
+        let err = download_hskcek_from_kds(&kds, b"NZA0000001")
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("404"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn invalid_serial_number() {
+        // Fails before the KDS is asked
+        let result = download_hskcek_from_kds("http://127.0.0.1:1/hsk_cek", &[0xff, 0xfe]).await;
+        assert!(result.is_err());
+    }
+
+    /// Certificates that the KDS or the firmware cut short are an error
+    /// rather than a panic.
+    #[test]
+    fn truncated_certificates() {
+        let err = decode_cert_chain(&[], &[]).err().unwrap();
+        assert!(err.to_string().contains("decode HSK"), "{err:#}");
+
+        let err = decode_cert_chain(&[0; 16], &[]).err().unwrap();
+        assert!(err.to_string().contains("decode HSK"), "{err:#}");
+    }
+
+    #[tokio::test]
+    async fn report_data_too_long() {
+        let err = CsvAttester::default()
+            .get_evidence(vec![0; 65])
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no more than 64 bytes"), "{err:#}");
+    }
+
+    #[test]
+    fn test_csv_capabilities() {
+        assert_eq!(
+            CsvAttester::default().capabilities(),
+            crate::AttesterCapabilities {
+                supports_runtime_measurement: false,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![],
+                init_data_supported: false,
+            }
+        );
+    }
+
     #[ignore]
     #[tokio::test]
     async fn test_csv_get_evidence() {
@@ -0,0 +1,430 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Detection of the TEE platform that reports what was probed, s.t. a wrong
+//! pick, e.g. with nested virtualization or partially exposed devices, can
+//! be told apart from a failing device. See [`detect_tee_type_verbose`].
+
+use std::path::PathBuf;
+use std::{fmt, fs, io};
+
+use kbs_types::Tee;
+use serde::Serialize;
+
+use crate::{tee_available_on, TEE_DETECTION_ORDER};
+
+/// Environment variable that sets the TEE type instead of probing the
+/// platform, e.g. `AA_TEE_TYPE=tdx`.
+pub const TEE_TYPE_ENV: &str = "AA_TEE_TYPE";
+
+/// Files whose presence signals the given TEE. They are reported to tell
+/// what was found; the detection of the attester itself may check more,
+/// e.g. the HCL report in the vTPM of Azure CVMs.
+fn signal_paths(tee: Tee) -> &'static [&'static str] {
+    match tee {
+        Tee::Tdx => &["/dev/tdx_guest", "/sys/kernel/config/tsm/report"],
+        Tee::Sgx => &["/dev/attestation/attestation_type", "/dev/sgx_enclave"],
+        Tee::AzTdxVtpm | Tee::AzSnpVtpm => &["/dev/tpmrm0", "/sys/class/dmi/id/sys_vendor"],
+        Tee::Snp => &["/sys/devices/platform/sev-guest", "/dev/sev-guest"],
+        Tee::Csv => &["/dev/csv-guest"],
+        Tee::Cca => &["/dev/cca_attestation", "/sys/kernel/config/tsm/report"],
+        Tee::Se => &["/dev/uv", "/sys/firmware/uv/prot_virt_guest"],
+        _ => &[],
+    }
+}
+
+/// The system that the TEE platforms are probed on. The probes of the
+/// attesters read it rather than `/` and the vTPM, s.t. they can be tested
+/// on a fake system.
+pub(crate) trait Platform {
+    /// The path of an absolute path on the platform.
+    fn path(&self, path: &str) -> PathBuf;
+
+    /// The TEE type of the HCL report in the vTPM of an Azure CVM, i.e.
+    /// `AzTdxVtpm` or `AzSnpVtpm`.
+    #[cfg(any(feature = "az-snp-vtpm-attester", feature = "az-tdx-vtpm-attester"))]
+    fn hcl_report_tee(&self) -> anyhow::Result<Tee>;
+
+    fn exists(&self, path: &str) -> bool {
+        self.path(path).exists()
+    }
+
+    #[cfg_attr(
+        not(any(
+            feature = "sgx-attester",
+            feature = "se-attester",
+            feature = "az-snp-vtpm-attester"
+        )),
+        allow(dead_code)
+    )]
+    fn read_to_string(&self, path: &str) -> io::Result<String> {
+        fs::read_to_string(self.path(path))
+    }
+}
+
+/// The running system.
+pub(crate) struct Host;
+
+impl Platform for Host {
+    fn path(&self, path: &str) -> PathBuf {
+        PathBuf::from(path)
+    }
+
+    #[cfg(any(feature = "az-snp-vtpm-attester", feature = "az-tdx-vtpm-attester"))]
+    fn hcl_report_tee(&self) -> anyhow::Result<Tee> {
+        #[cfg(not(feature = "az-tdx-vtpm-attester"))]
+        use az_snp_vtpm::{hcl, vtpm};
+        #[cfg(feature = "az-tdx-vtpm-attester")]
+        use az_tdx_vtpm::{hcl, vtpm};
+
+        let report_type = hcl::HclReport::new(vtpm::get_report()?)?.report_type();
+        if report_type == hcl::ReportType::Tdx {
+            Ok(Tee::AzTdxVtpm)
+        } else if report_type == hcl::ReportType::Snp {
+            Ok(Tee::AzSnpVtpm)
+        } else {
+            anyhow::bail!("unknown type of HCL report")
+        }
+    }
+}
+
+/// Whether an attester of the given TEE is built in.
+fn built_in(tee: Tee) -> bool {
+    let tsm = cfg!(feature = "tsm-attester");
+    match tee {
+        Tee::Sample => true,
+        Tee::Tdx => cfg!(feature = "tdx-attester") || tsm,
+        Tee::Sgx => cfg!(feature = "sgx-attester"),
+        Tee::AzTdxVtpm => cfg!(feature = "az-tdx-vtpm-attester"),
+        Tee::AzSnpVtpm => cfg!(feature = "az-snp-vtpm-attester"),
+        Tee::Snp => cfg!(feature = "snp-attester"),
+        Tee::Csv => cfg!(feature = "csv-attester"),
+        Tee::Cca => cfg!(feature = "cca-attester") || tsm,
+        Tee::Se => cfg!(feature = "se-attester"),
+        _ => false,
+    }
+}
+
+/// Whether a signal file of a TEE exists.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct PathCheck {
+    pub path: PathBuf,
+    pub found: bool,
+}
+
+/// What was probed for a TEE.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TeeProbe {
+    pub tee: Tee,
+
+    /// Whether the attester of the TEE is built in. The platform is not
+    /// probed otherwise.
+    pub built_in: bool,
+
+    /// The signal files of the TEE.
+    pub checks: Vec<PathCheck>,
+
+    /// Whether the TEE is available, i.e. its attester can be used.
+    pub available: bool,
+}
+
+/// How the TEE type was determined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionSource {
+    /// Set by [`TEE_TYPE_ENV`], without probing.
+    Override,
+
+    /// The first available TEE in the order of preference.
+    Probe,
+
+    /// No TEE is available, the sample attester is used.
+    Fallback,
+}
+
+/// Result of [`detect_tee_type_verbose`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct TeeDetection {
+    pub tee: Tee,
+    pub source: DetectionSource,
+
+    /// The TEEs probed in the order of preference, empty if the TEE type is
+    /// overridden.
+    pub probes: Vec<TeeProbe>,
+}
+
+impl fmt::Display for TeeDetection {
+    /// One line summary, e.g. `tdx (probe): tdx available [/dev/tdx_guest
+    /// found, ...], sgx not built in, ...`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({:?})", self.tee, self.source)?;
+        for (i, probe) in self.probes.iter().enumerate() {
+            f.write_str(if i == 0 { ": " } else { ", " })?;
+            if !probe.built_in {
+                write!(f, "{:?} not built in", probe.tee)?;
+                continue;
+            }
+
+            let state = if probe.available {
+                "available"
+            } else {
+                "unavailable"
+            };
+            write!(f, "{:?} {state} [", probe.tee)?;
+            for (j, check) in probe.checks.iter().enumerate() {
+                let found = if check.found { "found" } else { "missing" };
+                let separator = if j == 0 { "" } else { ", " };
+                write!(f, "{separator}{} {found}", check.path.display())?;
+            }
+            f.write_str("]")?;
+        }
+        Ok(())
+    }
+}
+
+/// Probes the TEEs on a platform, the running system or a fake one in the
+/// tests.
+struct Prober<P> {
+    platform: P,
+}
+
+impl<P: Platform> Prober<P> {
+    fn probe(&self, tee: Tee) -> TeeProbe {
+        let built_in = built_in(tee);
+        let checks: Vec<_> = signal_paths(tee)
+            .iter()
+            .map(|path| PathCheck {
+                path: self.platform.path(path),
+                found: self.platform.exists(path),
+            })
+            .collect();
+        let available = built_in && tee_available_on(&self.platform, tee);
+
+        TeeProbe {
+            tee,
+            built_in,
+            checks,
+            available,
+        }
+    }
+
+    fn detect(&self, tee_override: Option<&str>) -> TeeDetection {
+        if let Some(tee_override) = tee_override {
+            match parse_tee(tee_override) {
+                Some(tee) => {
+                    return TeeDetection {
+                        tee,
+                        source: DetectionSource::Override,
+                        probes: Vec::new(),
+                    }
+                }
+                None => log::warn!("Ignoring {TEE_TYPE_ENV}: unknown TEE type `{tee_override}`"),
+            }
+        }
+
+        let probes: Vec<_> = TEE_DETECTION_ORDER
+            .iter()
+            .map(|tee| self.probe(*tee))
+            .collect();
+        match probes.iter().find(|probe| probe.available) {
+            Some(probe) => TeeDetection {
+                tee: probe.tee,
+                source: DetectionSource::Probe,
+                probes,
+            },
+            None => TeeDetection {
+                tee: Tee::Sample,
+                source: DetectionSource::Fallback,
+                probes,
+            },
+        }
+    }
+}
+
+/// Parse a TEE type as in the AA config, e.g. `tdx` or `azsnpvtpm`.
+fn parse_tee(tee: &str) -> Option<Tee> {
+    serde_json::from_value(serde_json::Value::String(tee.trim().to_lowercase())).ok()
+}
+
+/// Detect the TEE platform like [`crate::detect_tee_type`], and report the
+/// probe of every TEE. The TEE type of [`TEE_TYPE_ENV`] is taken without
+/// probing, if it is set.
+pub fn detect_tee_type_verbose() -> TeeDetection {
+    let tee_override = std::env::var(TEE_TYPE_ENV).ok();
+    Prober { platform: Host }.detect(tee_override.as_deref())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    /// A system of the given files under a temporary root, and of the given
+    /// HCL report, if any.
+    struct FakePlatform {
+        root: tempfile::TempDir,
+        #[cfg_attr(
+            not(any(feature = "az-snp-vtpm-attester", feature = "az-tdx-vtpm-attester")),
+            allow(dead_code)
+        )]
+        hcl_report_tee: Option<Tee>,
+    }
+
+    impl FakePlatform {
+        fn new(files: &[(&str, &str)]) -> Self {
+            let root = tempfile::tempdir().unwrap();
+            for (path, content) in files {
+                let path = root.path().join(path.trim_start_matches('/'));
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, content).unwrap();
+            }
+            Self {
+                root,
+                hcl_report_tee: None,
+            }
+        }
+
+        fn root(&self) -> &Path {
+            self.root.path()
+        }
+    }
+
+    impl Platform for &FakePlatform {
+        fn path(&self, path: &str) -> PathBuf {
+            self.root.path().join(path.trim_start_matches('/'))
+        }
+
+        #[cfg(any(feature = "az-snp-vtpm-attester", feature = "az-tdx-vtpm-attester"))]
+        fn hcl_report_tee(&self) -> anyhow::Result<Tee> {
+            self.hcl_report_tee
+                .ok_or_else(|| anyhow::anyhow!("no HCL report"))
+        }
+    }
+
+    fn detect_on(platform: &FakePlatform, tee_override: Option<&str>) -> TeeDetection {
+        Prober { platform }.detect(tee_override)
+    }
+
+    /// Whether the vendor attester of the TEE is built in. A fake platform
+    /// has no TSM reports, as their entries are made by ConfigFS.
+    fn vendor_built_in(tee: Tee) -> bool {
+        match tee {
+            Tee::Tdx => cfg!(feature = "tdx-attester"),
+            Tee::Snp => cfg!(feature = "snp-attester"),
+            Tee::Cca => cfg!(feature = "cca-attester"),
+            tee => built_in(tee),
+        }
+    }
+
+    const HYPERV: (&str, &str) = ("/sys/class/dmi/id/sys_vendor", "Microsoft Corporation\n");
+
+    /// The TEEs whose probes pass on the files are given in the order of
+    /// preference, the first one that is built in is detected.
+    #[rstest]
+    #[case(&[], &[])]
+    #[case(&[("/dev/tdx_guest", "")], &[Tee::Tdx])]
+    #[case(&[("/dev/attestation/attestation_type", "dcap")], &[Tee::Sgx])]
+    #[case(&[("/dev/attestation/attestation_type", "epid")], &[])]
+    // A TPM, even on Hyper-V, is no Azure CVM without an HCL report
+    #[case(&[("/dev/tpmrm0", "")], &[])]
+    #[case(&[("/dev/tpmrm0", ""), HYPERV], &[])]
+    #[case(&[("/dev/tpmrm0", ""), ("/dev/tdx_guest", "")], &[Tee::Tdx])]
+    #[case(&[("/sys/devices/platform/sev-guest", "")], &[Tee::Snp])]
+    #[case(&[("/dev/csv-guest", "")], &[Tee::Csv])]
+    #[case(&[("/dev/cca_attestation", "")], &[Tee::Cca])]
+    #[case(&[("/dev/uv", "")], &[])]
+    #[case(&[("/dev/uv", ""), ("/sys/firmware/uv/prot_virt_guest", "1\n")], &[Tee::Se])]
+    fn detect_by_files(#[case] files: &[(&str, &str)], #[case] available: &[Tee]) {
+        let platform = FakePlatform::new(files);
+        let detection = detect_on(&platform, None);
+
+        let expected = available
+            .iter()
+            .copied()
+            .find(|tee| vendor_built_in(*tee))
+            .unwrap_or(Tee::Sample);
+
+        assert_eq!(detection.tee, expected, "{detection}");
+        assert_eq!(detection.probes.len(), TEE_DETECTION_ORDER.len());
+        let source = if expected == Tee::Sample {
+            DetectionSource::Fallback
+        } else {
+            DetectionSource::Probe
+        };
+        assert_eq!(detection.source, source);
+    }
+
+    /// An Azure CVM might also show the signals of the bare TEE, its vTPM
+    /// attester is preferred then. The HCL report is faked by its type, as
+    /// no report recorded on a CVM is at hand.
+    #[rstest]
+    #[case(&[HYPERV, ("/dev/tdx_guest", "")], Tee::AzTdxVtpm, Tee::Tdx)]
+    #[case(&[HYPERV, ("/sys/devices/platform/sev-guest", "")], Tee::AzSnpVtpm, Tee::Snp)]
+    fn azure_vtpm_is_preferred(
+        #[case] files: &[(&str, &str)],
+        #[case] azure: Tee,
+        #[case] bare: Tee,
+    ) {
+        let mut platform = FakePlatform::new(files);
+        platform.hcl_report_tee = Some(azure);
+        let detection = detect_on(&platform, None);
+
+        let expected = [azure, bare]
+            .into_iter()
+            .find(|tee| vendor_built_in(*tee))
+            .unwrap_or(Tee::Sample);
+        assert_eq!(detection.tee, expected, "{detection}");
+    }
+
+    #[test]
+    fn probes_report_path_checks() {
+        let platform = FakePlatform::new(&[("/dev/csv-guest", "")]);
+        let detection = detect_on(&platform, None);
+
+        let csv = detection
+            .probes
+            .iter()
+            .find(|probe| probe.tee == Tee::Csv)
+            .unwrap();
+        assert_eq!(
+            csv.checks,
+            vec![PathCheck {
+                path: platform.root().join("dev/csv-guest"),
+                found: true,
+            }]
+        );
+        assert_eq!(csv.available, cfg!(feature = "csv-attester"));
+        if csv.built_in {
+            assert!(
+                detection.to_string().contains("csv-guest found"),
+                "{detection}"
+            );
+        }
+    }
+
+    #[rstest]
+    #[case("tdx", Some(Tee::Tdx))]
+    #[case(" SNP ", Some(Tee::Snp))]
+    #[case("azsnpvtpm", Some(Tee::AzSnpVtpm))]
+    #[case("sample", Some(Tee::Sample))]
+    #[case("nope", None)]
+    fn override_tee_type(#[case] tee_override: &str, #[case] expected: Option<Tee>) {
+        let platform = FakePlatform::new(&[("/dev/tdx_guest", "")]);
+        let detection = detect_on(&platform, Some(tee_override));
+
+        match expected {
+            Some(tee) => {
+                assert_eq!(detection.tee, tee);
+                assert_eq!(detection.source, DetectionSource::Override);
+                assert!(detection.probes.is_empty());
+            }
+            // An unknown TEE type is ignored
+            None => assert_ne!(detection.source, DetectionSource::Override),
+        }
+    }
+}
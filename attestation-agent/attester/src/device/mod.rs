@@ -0,0 +1,30 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Device attesters collect evidence from devices attached to the TEE, e.g.
+//! confidential computing GPUs, in addition to the CPU TEE evidence.
+
+use anyhow::*;
+use serde_json::Value;
+
+pub type BoxedDeviceAttester = Box<dyn DeviceAttester + Send + Sync>;
+
+/// Create the attester of the given device class, e.g. `nvidia_gpu`, as
+/// named in the AA config and as the key of the additional evidence.
+/// `None` if no attester of the class is built in.
+///
+/// No device attester is built in yet. A class is added here together with
+/// its attester, e.g. one of NVIDIA confidential computing GPUs on NVML.
+pub fn new_device_attester(class: &str) -> Result<Option<BoxedDeviceAttester>> {
+    log::debug!("No device attester of class `{class}` is built in");
+    Ok(None)
+}
+
+#[async_trait::async_trait]
+pub trait DeviceAttester {
+    /// Get the evidence of all the devices of this class. The parameter
+    /// `report_data` is the same one bound into the TEE evidence.
+    async fn get_evidence(&self, report_data: Vec<u8>) -> Result<Value>;
+}
@@ -3,9 +3,20 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::ops::RangeInclusive;
+
 use anyhow::*;
 use kbs_types::Tee;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+pub use aael::HashAlgorithm;
+pub use detect::{detect_tee_type_verbose, TeeDetection};
 
+use detect::{Host, Platform};
+
+pub mod detect;
+pub mod device;
 pub mod sample;
 pub mod utils;
 
@@ -33,45 +44,137 @@ pub mod csv;
 #[cfg(feature = "tsm-report")]
 pub mod tsm_report;
 
+#[cfg(feature = "tsm-attester")]
+pub mod tsm;
+
 #[cfg(feature = "se-attester")]
 pub mod se;
 
+#[cfg(feature = "tpm-attester")]
+pub mod tpm;
+
 pub type BoxedAttester = Box<dyn Attester + Send + Sync>;
 
+/// PCRs of a (v)TPM that runtime measurements can be extended into.
+pub const TPM_PCRS: RangeInclusive<u64> = 0..=23;
+
+/// Size in bytes of the nonce of the quotes of the Azure vTPMs.
+#[cfg(any(feature = "az-snp-vtpm-attester", feature = "az-tdx-vtpm-attester"))]
+const VTPM_REPORT_DATA_SIZE: usize = 32;
+
+/// Tables of [`AttesterOptions`] of all the attesters, including those that
+/// are not built in.
+pub const ATTESTER_OPTION_SECTIONS: &[&str] = &["se", "sgx", "snp", "tdx"];
+
+/// Options of the attesters, one table per TEE type, e.g. `[attester.tdx]`
+/// in the AA config. Only the attesters that are built in have a table, see
+/// the options type of each attester for its keys.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AttesterOptions {
+    #[cfg(feature = "tdx-attester")]
+    pub tdx: tdx::TdxOptions,
+
+    #[cfg(feature = "snp-attester")]
+    pub snp: snp::SnpOptions,
+
+    #[cfg(feature = "sgx-attester")]
+    pub sgx: sgx_dcap::SgxOptions,
+
+    #[cfg(feature = "se-attester")]
+    pub se: se::SeOptions,
+}
+
+/// Create the attester of the given TEE type with the given options. The
+/// options of the other TEE types are ignored.
+///
+/// A plain TPM has no TEE type in kbs-types, so the TPM attester is not
+/// created here, nor detected by [`detect_tee_type`]. It has to be created
+/// with `tpm::TpmAttester::new` by users of this crate, through its
+/// `tpm-attester` feature. AA does not offer it.
+#[cfg_attr(
+    not(any(
+        feature = "tdx-attester",
+        feature = "snp-attester",
+        feature = "sgx-attester",
+        feature = "se-attester"
+    )),
+    allow(unused_variables)
+)]
+pub fn new_attester(tee: Tee, options: &AttesterOptions) -> Result<BoxedAttester> {
+    // The vendor attester is not built in or its device is unavailable
+    #[cfg(feature = "tsm-attester")]
+    if !vendor_tee_available_on(&Host, tee) && tsm::detect_platform(tee) {
+        if let Some(provider) = tsm::provider_for_tee(tee) {
+            return Ok(Box::new(tsm::TsmAttester::new(provider)));
+        }
+    }
+
+    let attester: BoxedAttester = match tee {
+        Tee::Sample => Box::<sample::SampleAttester>::default(),
+        #[cfg(feature = "tdx-attester")]
+        Tee::Tdx => Box::new(tdx::TdxAttester::new(options.tdx.clone())),
+        #[cfg(feature = "sgx-attester")]
+        Tee::Sgx => Box::new(sgx_dcap::SgxDcapAttester::new(options.sgx.clone())?),
+        #[cfg(feature = "az-snp-vtpm-attester")]
+        Tee::AzSnpVtpm => Box::<az_snp_vtpm::AzSnpVtpmAttester>::default(),
+        #[cfg(feature = "az-tdx-vtpm-attester")]
+        Tee::AzTdxVtpm => Box::<az_tdx_vtpm::AzTdxVtpmAttester>::default(),
+        #[cfg(feature = "cca-attester")]
+        Tee::Cca => Box::<cca::CCAAttester>::default(),
+        #[cfg(feature = "snp-attester")]
+        Tee::Snp => Box::new(snp::SnpAttester::new(options.snp.clone())?),
+        #[cfg(feature = "csv-attester")]
+        Tee::Csv => Box::<csv::CsvAttester>::default(),
+        #[cfg(feature = "se-attester")]
+        Tee::Se => Box::new(se::SeAttester::new(options.se.clone())?),
+        _ => bail!("TEE is not supported!"),
+    };
+
+    Ok(attester)
+}
+
 impl TryFrom<Tee> for BoxedAttester {
     type Error = anyhow::Error;
 
+    /// Create the attester with the default options, see [`new_attester`].
     fn try_from(value: Tee) -> Result<Self> {
-        let attester: Box<dyn Attester + Send + Sync> = match value {
-            Tee::Sample => Box::<sample::SampleAttester>::default(),
-            #[cfg(feature = "tdx-attester")]
-            Tee::Tdx => Box::<tdx::TdxAttester>::default(),
-            #[cfg(feature = "sgx-attester")]
-            Tee::Sgx => Box::<sgx_dcap::SgxDcapAttester>::default(),
-            #[cfg(feature = "az-snp-vtpm-attester")]
-            Tee::AzSnpVtpm => Box::<az_snp_vtpm::AzSnpVtpmAttester>::default(),
-            #[cfg(feature = "az-tdx-vtpm-attester")]
-            Tee::AzTdxVtpm => Box::<az_tdx_vtpm::AzTdxVtpmAttester>::default(),
-            #[cfg(feature = "cca-attester")]
-            Tee::Cca => Box::<cca::CCAAttester>::default(),
-            #[cfg(feature = "snp-attester")]
-            Tee::Snp => Box::<snp::SnpAttester>::default(),
-            #[cfg(feature = "csv-attester")]
-            Tee::Csv => Box::<csv::CsvAttester>::default(),
-            #[cfg(feature = "se-attester")]
-            Tee::Se => Box::<se::SeAttester>::default(),
-            _ => bail!("TEE is not supported!"),
-        };
-
-        Ok(attester)
+        new_attester(value, &AttesterOptions::default())
     }
 }
 
+#[derive(Error, Debug)]
+pub enum AttesterError {
+    #[error("{0} is not supported by the attester of current platform")]
+    Unsupported(&'static str),
+}
+
 pub enum InitdataResult {
     Ok,
     Unsupported,
 }
 
+/// What the attester of a platform can do, s.t. callers can decide up front
+/// instead of probing the attester and handling the errors.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct AttesterCapabilities {
+    /// Whether runtime measurements can be extended.
+    pub supports_runtime_measurement: bool,
+
+    /// Size in bytes of the report data field of the evidence, `0` if the
+    /// platform does not take a fixed size report data. See
+    /// [`Attester::report_data_len`].
+    pub report_data_len: usize,
+
+    /// Hash algorithms of the runtime measurement registers. Empty if
+    /// runtime measurement is not supported.
+    pub supported_hash_algorithms: Vec<HashAlgorithm>,
+
+    /// Whether [`Attester::check_init_data`] checks the init data against
+    /// the hardware, rather than returning [`InitdataResult::Unsupported`].
+    pub init_data_supported: bool,
+}
+
 #[async_trait::async_trait]
 pub trait Attester {
     /// Call the hardware driver to get the Hardware specific evidence.
@@ -79,6 +182,20 @@ pub trait Attester {
     /// evidence to avoid reply attack.
     async fn get_evidence(&self, report_data: Vec<u8>) -> Result<String>;
 
+    /// Size in bytes of the report data field of the evidence. Longer report
+    /// data will be rejected. `None` means the platform does not take a fixed
+    /// size report data, e.g. IBM SE takes a serialized attestation request.
+    fn report_data_len(&self) -> Option<usize> {
+        Some(64)
+    }
+
+    /// Register indexes, in PCR numbering, that `extend_runtime_measurement`
+    /// accepts on this platform. `None` means the platform cannot extend
+    /// runtime measurement at all.
+    fn runtime_measurement_registers(&self) -> Option<RangeInclusive<u64>> {
+        None
+    }
+
     /// Extend TEE specific dynamic measurement register
     /// to enable dynamic measurement capabilities for input data at runtime.
     async fn extend_runtime_measurement(
@@ -89,53 +206,167 @@ pub trait Attester {
         bail!("Unimplemented")
     }
 
+    /// Read the current value of the runtime measurement register of the
+    /// given index, in PCR numbering, from the bank of the given hash
+    /// algorithm. Platforms that cannot read back their registers return
+    /// [`AttesterError::Unsupported`].
+    async fn get_runtime_measurement(
+        &self,
+        _register_index: u64,
+        _algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        Err(AttesterError::Unsupported("reading runtime measurement").into())
+    }
+
     async fn check_init_data(&self, _init_data: &[u8]) -> Result<InitdataResult> {
         Ok(InitdataResult::Unsupported)
     }
-}
 
-// Detect which TEE platform the KBC running environment is.
-pub fn detect_tee_type() -> Tee {
-    #[cfg(feature = "tdx-attester")]
-    if tdx::detect_platform() {
-        return Tee::Tdx;
+    /// Size in bytes of the host data field that `check_init_data` compares
+    /// the init data digest with, e.g. 48 for MRCONFIGID of TDX. Shorter
+    /// digests are padded with zeros. `None` if init data is not supported.
+    fn init_data_len(&self) -> Option<usize> {
+        None
     }
 
-    #[cfg(feature = "sgx-attester")]
-    if sgx_dcap::detect_platform() {
-        return Tee::Sgx;
+    /// Capabilities of the attester. Platforms override this to report the
+    /// register hash algorithms and init data support.
+    fn capabilities(&self) -> AttesterCapabilities {
+        AttesterCapabilities {
+            supports_runtime_measurement: self.runtime_measurement_registers().is_some(),
+            report_data_len: self.report_data_len().unwrap_or_default(),
+            supported_hash_algorithms: Vec::new(),
+            init_data_supported: false,
+        }
     }
 
-    #[cfg(feature = "az-tdx-vtpm-attester")]
-    if az_tdx_vtpm::detect_platform() {
-        return Tee::AzTdxVtpm;
+    /// Derive a key of `length` bytes that is bound to the TEE, from the
+    /// hardware sealing/derivation key. The `context` is mixed into the
+    /// derivation, s.t. different contexts get different keys. Platforms
+    /// without key derivation capability return [`AttesterError::Unsupported`].
+    ///
+    /// SNP derives the key with the `MSG_KEY_REQ` guest message. TDX has no
+    /// such interface: the TDX module does not give a TD a sealing key, and
+    /// TDG.MR.REPORT is the only attestation TDCALL the guest driver exposes,
+    /// so the TDX attester is unsupported.
+    async fn get_derived_key(&self, _context: &[u8], _length: usize) -> Result<Vec<u8>> {
+        Err(AttesterError::Unsupported("key derivation").into())
     }
 
-    #[cfg(feature = "az-snp-vtpm-attester")]
-    if az_snp_vtpm::detect_platform() {
-        return Tee::AzSnpVtpm;
+    /// The eventlog of the measurements of the firmware, e.g. the CCEL ACPI
+    /// table of TDX, in the TCG2 crypto agile format. `None` if the
+    /// platform has no firmware eventlog.
+    async fn get_firmware_eventlog(&self) -> Result<Option<Vec<u8>>> {
+        Ok(None)
     }
 
-    #[cfg(feature = "snp-attester")]
-    if snp::detect_platform() {
-        return Tee::Snp;
+    /// Index of the register in the numbering of the firmware eventlog
+    /// that the runtime measurement register of the given index, in PCR
+    /// numbering, is. The same numbering by default.
+    fn firmware_register_index(&self, register_index: u64) -> u64 {
+        register_index
     }
+}
+
+/// Check whether the current platform provides the given TEE. This is used to
+/// validate a TEE type that is forced by the caller rather than detected.
+pub fn tee_available(tee: Tee) -> bool {
+    tee_available_on(&Host, tee)
+}
 
-    #[cfg(feature = "csv-attester")]
-    if csv::detect_platform() {
-        return Tee::Csv;
+/// [`tee_available`] on the given platform.
+fn tee_available_on(platform: &impl Platform, tee: Tee) -> bool {
+    #[cfg(feature = "tsm-attester")]
+    if tsm::detect_platform_on(platform, tee) {
+        return true;
     }
 
-    #[cfg(feature = "cca-attester")]
-    if cca::detect_platform() {
-        return Tee::Cca;
+    vendor_tee_available_on(platform, tee)
+}
+
+/// Check whether the vendor attester of the given TEE is built in and its
+/// device is available on the platform.
+#[cfg_attr(
+    not(any(
+        feature = "tdx-attester",
+        feature = "sgx-attester",
+        feature = "az-tdx-vtpm-attester",
+        feature = "az-snp-vtpm-attester",
+        feature = "snp-attester",
+        feature = "csv-attester",
+        feature = "cca-attester",
+        feature = "se-attester",
+    )),
+    allow(unused_variables)
+)]
+fn vendor_tee_available_on(platform: &impl Platform, tee: Tee) -> bool {
+    match tee {
+        Tee::Sample => sample::detect_platform(),
+        #[cfg(feature = "tdx-attester")]
+        Tee::Tdx => tdx::detect_platform_on(platform),
+        #[cfg(feature = "sgx-attester")]
+        Tee::Sgx => sgx_dcap::detect_platform_on(platform),
+        #[cfg(feature = "az-tdx-vtpm-attester")]
+        Tee::AzTdxVtpm => az_tdx_vtpm::detect_platform_on(platform),
+        #[cfg(feature = "az-snp-vtpm-attester")]
+        Tee::AzSnpVtpm => az_snp_vtpm::detect_platform_on(platform),
+        #[cfg(feature = "snp-attester")]
+        Tee::Snp => snp::detect_platform_on(platform),
+        #[cfg(feature = "csv-attester")]
+        Tee::Csv => csv::detect_platform_on(platform),
+        #[cfg(feature = "cca-attester")]
+        Tee::Cca => cca::detect_platform_on(platform),
+        #[cfg(feature = "se-attester")]
+        Tee::Se => se::detect_platform_on(platform),
+        _ => false,
     }
+}
 
-    #[cfg(feature = "se-attester")]
-    if se::detect_platform() {
-        return Tee::Se;
+/// TEE types in the order of preference of detection. The Azure vTPM based
+/// ones come before the bare ones, as an Azure CVM might also show the
+/// signals of the bare TEE, while only the vTPM path works there.
+pub(crate) const TEE_DETECTION_ORDER: &[Tee] = &[
+    Tee::AzTdxVtpm,
+    Tee::Tdx,
+    Tee::Sgx,
+    Tee::AzSnpVtpm,
+    Tee::Snp,
+    Tee::Csv,
+    Tee::Cca,
+    Tee::Se,
+];
+
+/// Detect all the TEE platforms that the running environment provides, in
+/// the same order of preference as [`detect_tee_type`]. e.g. on an Azure SNP
+/// CVM both the vTPM based and the generic SNP attesters might be usable.
+pub fn detect_all_tee_types() -> Vec<Tee> {
+    TEE_DETECTION_ORDER
+        .iter()
+        .copied()
+        .filter(|tee| tee_available(*tee))
+        .collect()
+}
+
+// Detect which TEE platform the KBC running environment is. The TEE type of
+// the `AA_TEE_TYPE` environment variable is taken instead, if it is set. See
+// `detect_tee_type_verbose` for what was probed.
+pub fn detect_tee_type() -> Tee {
+    let detection = detect_tee_type_verbose();
+    if detection.source == detect::DetectionSource::Fallback {
+        log::warn!("No TEE platform detected. Sample Attester will be used.");
     }
+    detection.tee
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AttesterOptions, ATTESTER_OPTION_SECTIONS};
 
-    log::warn!("No TEE platform detected. Sample Attester will be used.");
-    Tee::Sample
+    #[test]
+    fn option_sections_are_listed() {
+        let options = serde_json::to_value(AttesterOptions::default()).unwrap();
+        for section in options.as_object().unwrap().keys() {
+            assert!(ATTESTER_OPTION_SECTIONS.contains(&section.as_str()));
+        }
+    }
 }
@@ -4,6 +4,7 @@
 //
 
 use super::Attester;
+use crate::utils::derive_key;
 use anyhow::*;
 use base64::Engine;
 use serde::{Deserialize, Serialize};
@@ -13,6 +14,10 @@ pub fn detect_platform() -> bool {
     true
 }
 
+/// Fixed key material of the sample attester's key derivation. The derived
+/// keys are deterministic and MUST NOT be used out of tests.
+const SAMPLE_DERIVATION_KEY: &[u8] = b"sample attester derivation key";
+
 // A simple example of TEE evidence.
 #[derive(Serialize, Deserialize, Debug)]
 struct SampleQuote {
@@ -33,4 +38,53 @@ impl Attester for SampleAttester {
 
         serde_json::to_string(&evidence).context("Serialize sample evidence failed")
     }
+
+    async fn get_derived_key(&self, context: &[u8], length: usize) -> Result<Vec<u8>> {
+        derive_key(SAMPLE_DERIVATION_KEY, context, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_sample_derived_key() {
+        let attester = SampleAttester::default();
+        let key = attester.get_derived_key(b"context a", 32).await.unwrap();
+        assert_eq!(key.len(), 32);
+        assert_eq!(
+            key,
+            attester.get_derived_key(b"context a", 32).await.unwrap()
+        );
+        assert_ne!(
+            key,
+            attester.get_derived_key(b"context b", 32).await.unwrap()
+        );
+        assert!(attester
+            .get_derived_key(b"context a", 255 * 32 + 1)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_sample_runtime_measurement_registers() {
+        // Sample attester has no register to extend
+        assert!(SampleAttester::default()
+            .runtime_measurement_registers()
+            .is_none());
+    }
+
+    #[test]
+    fn test_sample_capabilities() {
+        assert_eq!(
+            SampleAttester::default().capabilities(),
+            crate::AttesterCapabilities {
+                supports_runtime_measurement: false,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![],
+                init_data_supported: false,
+            }
+        );
+    }
 }
@@ -3,20 +3,37 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use super::Attester;
+use super::{Attester, AttesterError};
+use crate::detect::{Host, Platform};
 use anyhow::*;
 use log::debug;
 use pv::{
-    misc,
     request::BootHdrTags,
     uv::{AttestationCmd, ConfigUid, UvDevice},
 };
 use serde::{Deserialize, Serialize};
 use serde_json;
 use serde_with::{base64::Base64, serde_as};
+use std::path::PathBuf;
+
+/// Device of the Ultravisor, which the attestation requests go through.
+const UV_DEVICE_PATH: &str = "/dev/uv";
+const PV_GUEST_PATH: &str = "/sys/firmware/uv/prot_virt_guest";
+
+/// Maximum size in bytes of the user data of an attestation request.
+const SE_USER_DATA_MAX_SIZE: usize = 256;
 
 pub fn detect_platform() -> bool {
-    misc::pv_guest_bit_set()
+    detect_platform_on(&Host)
+}
+
+/// Whether the platform runs a Secure Execution guest, like
+/// `misc::pv_guest_bit_set`, and has the Ultravisor device.
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    let guest_bit = platform
+        .read_to_string(PV_GUEST_PATH)
+        .is_ok_and(|bit| bit.starts_with('1'));
+    guest_bit && platform.exists(UV_DEVICE_PATH)
 }
 
 #[repr(C)]
@@ -33,6 +50,23 @@ pub struct SeAttestationRequest {
     encr_request_nonce: Vec<u8>,
     #[serde_as(as = "Base64")]
     image_hdr_tags: BootHdrTags,
+    /// Data that the UV binds into the response, e.g. the digest of the
+    /// runtime data, added by the caller rather than the verifier.
+    #[serde_as(as = "Option<Base64>")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    user_data: Option<Vec<u8>>,
+}
+
+impl SeAttestationRequest {
+    /// The user data of the request, a single zero byte if the caller gave
+    /// none.
+    fn user_data(&self) -> Result<Vec<u8>> {
+        let user_data = self.user_data.clone().unwrap_or_else(|| vec![0]);
+        if user_data.len() > SE_USER_DATA_MAX_SIZE {
+            bail!("SE Attester: User data must be no more than {SE_USER_DATA_MAX_SIZE} bytes");
+        }
+        Ok(user_data)
+    }
 }
 
 #[repr(C)]
@@ -55,14 +89,43 @@ pub struct SeAttestationResponse {
     image_hdr_tags: BootHdrTags,
 }
 
+/// Options of the SE attester, the `[attester.se]` table of the AA config.
+///
+/// ```toml
+/// [attester.se]
+/// host_key_documents = ["/etc/attestation-agent/se/HKD-8651-000201C048.crt"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SeOptions {
+    /// Host key documents of the hosts that the guest may run on, which
+    /// the attestation requests of the verifier are encrypted for. If any
+    /// is given, the attester is not created unless all of them can be
+    /// read, s.t. a guest provisioned without them fails on startup rather
+    /// than on the first request.
+    pub host_key_documents: Vec<PathBuf>,
+}
+
 #[derive(Debug, Default)]
 pub struct SeAttester {}
 
+impl SeAttester {
+    pub fn new(options: SeOptions) -> Result<Self> {
+        for document in &options.host_key_documents {
+            std::fs::metadata(document).with_context(|| {
+                format!("SE Attester: read host key document {}", document.display())
+            })?;
+        }
+        Ok(Self {})
+    }
+}
+
 #[async_trait::async_trait]
 impl Attester for SeAttester {
     async fn get_evidence(&self, req: Vec<u8>) -> Result<String> {
         // req is serialized SeAttestationRequest String bytes
         let request: SeAttestationRequest = serde_json::from_slice(&req)?;
+        let user_data = request.user_data()?;
         let SeAttestationRequest {
             request_blob,
             measurement_size,
@@ -70,8 +133,8 @@ impl Attester for SeAttester {
             encr_measurement_key,
             encr_request_nonce,
             image_hdr_tags,
+            ..
         } = request;
-        let user_data = vec![0];
         let mut uvc: AttestationCmd = AttestationCmd::new_request(
             request_blob.into(),
             Some(user_data.to_vec()),
@@ -97,4 +160,105 @@ impl Attester for SeAttester {
         debug!("response json: {response:#?}");
         Ok(serde_json::to_string(&response)?)
     }
+
+    fn report_data_len(&self) -> Option<usize> {
+        // The report data of SE is a serialized `SeAttestationRequest`
+        None
+    }
+
+    /// SE has no runtime measurement registers.
+    async fn extend_runtime_measurement(
+        &self,
+        _event_digest: Vec<u8>,
+        _register_index: u64,
+    ) -> Result<()> {
+        Err(AttesterError::Unsupported("runtime measurement").into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(user_data: Option<&str>) -> Result<SeAttestationRequest> {
+        let mut request = serde_json::json!({
+            "request_blob": "AAAA",
+            "measurement_size": 64,
+            "additional_size": 0,
+            "encr_measurement_key": "AAAA",
+            "encr_request_nonce": "AAAA",
+            "image_hdr_tags": base64::Engine::encode(
+                &base64::engine::general_purpose::STANDARD,
+                [0u8; std::mem::size_of::<BootHdrTags>()],
+            ),
+        });
+        if let Some(user_data) = user_data {
+            request["user_data"] = user_data.into();
+        }
+        Ok(serde_json::from_value(request)?)
+    }
+
+    #[test]
+    fn test_se_user_data() {
+        assert_eq!(request(None).unwrap().user_data().unwrap(), vec![0]);
+        assert_eq!(
+            request(Some("AQID")).unwrap().user_data().unwrap(),
+            vec![1, 2, 3]
+        );
+
+        let too_long = base64::Engine::encode(
+            &base64::engine::general_purpose::STANDARD,
+            [0u8; SE_USER_DATA_MAX_SIZE + 1],
+        );
+        request(Some(&too_long)).unwrap().user_data().unwrap_err();
+    }
+
+    #[test]
+    fn test_se_options() {
+        let options: SeOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, SeOptions::default());
+        SeAttester::new(options).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let document = dir.path().join("HKD.crt");
+        std::fs::write(&document, "host key document").unwrap();
+        let options: SeOptions = serde_json::from_value(serde_json::json!({
+            "host_key_documents": [document],
+        }))
+        .unwrap();
+        SeAttester::new(options).unwrap();
+
+        let missing = dir.path().join("missing.crt");
+        let e = SeAttester::new(SeOptions {
+            host_key_documents: vec![document, missing],
+        })
+        .unwrap_err();
+        assert!(e.to_string().contains("missing.crt"), "{e}");
+    }
+
+    #[test]
+    fn test_se_capabilities() {
+        assert_eq!(
+            SeAttester::default().capabilities(),
+            crate::AttesterCapabilities {
+                supports_runtime_measurement: false,
+                // The report data is not of a fixed size
+                report_data_len: 0,
+                supported_hash_algorithms: vec![],
+                init_data_supported: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_se_runtime_measurement_unsupported() {
+        let e = SeAttester::default()
+            .extend_runtime_measurement(vec![0; 32], 17)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<AttesterError>(),
+            Some(AttesterError::Unsupported(_))
+        ));
+    }
 }
@@ -3,7 +3,10 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use std::path::PathBuf;
+
 use super::Attester;
+use crate::detect::{Host, Platform};
 use anyhow::{bail, Context, Result};
 use base64::Engine;
 use occlum_dcap::{sgx_report_data_t, DcapQuote};
@@ -11,25 +14,32 @@ use serde::{Deserialize, Serialize};
 
 const OCCLUM_ENV: &str = "OCCLUM";
 
+/// Socket of the AESM service, as aesmd listens by default.
+pub const DEFAULT_AESM_SOCKET: &str = "/var/run/aesmd/aesm.socket";
+
 enum SgxLibOsType {
     Invalid,
     Occlum,
     Gramine,
 }
 
-fn get_libos_type() -> SgxLibOsType {
+fn get_libos_type(platform: &impl Platform) -> SgxLibOsType {
     if std::env::var(OCCLUM_ENV).is_ok() {
         return SgxLibOsType::Occlum;
     }
 
-    match std::fs::read_to_string("/dev/attestation/attestation_type") {
+    match platform.read_to_string("/dev/attestation/attestation_type") {
         Ok(d) if d == "dcap" => SgxLibOsType::Gramine,
         _ => SgxLibOsType::Invalid,
     }
 }
 
 pub fn detect_platform() -> bool {
-    match get_libos_type() {
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    match get_libos_type(platform) {
         SgxLibOsType::Invalid => false,
         SgxLibOsType::Occlum => true,
         SgxLibOsType::Gramine => true,
@@ -42,9 +52,41 @@ struct SgxDcapAttesterEvidence {
     quote: String,
 }
 
+/// Options of the SGX attester, the `[attester.sgx]` table of the AA config.
+///
+/// ```toml
+/// [attester.sgx]
+/// aesm_socket = "/var/run/aesmd/aesm.socket"
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SgxOptions {
+    /// Socket of the AESM service that the libOS generates the quotes
+    /// through, usually [`DEFAULT_AESM_SOCKET`] mounted into the enclave's
+    /// file system. If set, the attester is not created without the
+    /// socket, s.t. a platform without a running AESM fails on startup
+    /// rather than on the first quote. Unset by default, as Occlum and
+    /// Gramine may reach AESM on the host side.
+    pub aesm_socket: Option<PathBuf>,
+}
+
 #[derive(Debug, Default)]
 pub struct SgxDcapAttester {}
 
+impl SgxDcapAttester {
+    pub fn new(options: SgxOptions) -> Result<Self> {
+        if let Some(aesm_socket) = &options.aesm_socket {
+            if !aesm_socket.exists() {
+                bail!(
+                    "SGX Attester: AESM socket {} does not exist",
+                    aesm_socket.display()
+                );
+            }
+        }
+        Ok(Self {})
+    }
+}
+
 #[async_trait::async_trait]
 impl Attester for SgxDcapAttester {
     async fn get_evidence(&self, mut report_data: Vec<u8>) -> Result<String> {
@@ -89,6 +131,39 @@ impl Attester for SgxDcapAttester {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_sgx_capabilities() {
+        assert_eq!(
+            SgxDcapAttester::default().capabilities(),
+            crate::AttesterCapabilities {
+                supports_runtime_measurement: false,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![],
+                init_data_supported: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_sgx_options() {
+        let options: SgxOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, SgxOptions::default());
+        SgxDcapAttester::new(options).unwrap();
+
+        let options: SgxOptions =
+            serde_json::from_str(r#"{"aesm_socket": "/nonexistent/aesm.socket"}"#).unwrap();
+        let e = SgxDcapAttester::new(options).unwrap_err();
+        assert!(e.to_string().contains("/nonexistent/aesm.socket"), "{e}");
+
+        let dir = tempfile::tempdir().unwrap();
+        let aesm_socket = dir.path().join("aesm.socket");
+        let _listener = std::os::unix::net::UnixListener::bind(&aesm_socket).unwrap();
+        SgxDcapAttester::new(SgxOptions {
+            aesm_socket: Some(aesm_socket),
+        })
+        .unwrap();
+    }
+
     #[ignore]
     #[tokio::test]
     async fn test_sgx_get_evidence() {
@@ -3,21 +3,31 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
-use crate::utils::pad;
-use crate::InitdataResult;
+use crate::detect::{Host, Platform};
+use crate::utils::{derive_key, pad};
+use crate::{AttesterCapabilities, InitdataResult};
 
 use super::Attester;
 use anyhow::*;
 use serde::{Deserialize, Serialize};
 use sev::firmware::guest::AttestationReport;
-use sev::firmware::guest::Firmware;
+use sev::firmware::guest::{DerivedKey, Firmware, GuestFieldSelect};
 use sev::firmware::host::CertTableEntry;
-use std::path::Path;
 
 mod hostdata;
 
+/// Guest fields mixed into the SNP derived key: guest policy (bit 0) and
+/// launch measurement (bit 3). Refer to `MSG_KEY_REQ` in the SEV-SNP ABI spec.
+const DERIVED_KEY_GUEST_FIELDS: u64 = 0b1001;
+
+const SNP_HOSTDATA_SIZE: usize = 32;
+
 pub fn detect_platform() -> bool {
-    Path::new("/sys/devices/platform/sev-guest").exists()
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    platform.exists("/sys/devices/platform/sev-guest")
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,8 +36,42 @@ struct SnpEvidence {
     cert_chain: Option<Vec<CertTableEntry>>,
 }
 
+/// The least privileged VMPL. VMPL 0 is the most privileged one.
+pub const MAX_VMPL: u32 = 3;
+
+/// Options of the SNP attester, the `[attester.snp]` table of the AA config.
+///
+/// ```toml
+/// [attester.snp]
+/// vmpl = 0
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SnpOptions {
+    /// VMPL that the attestation reports and the derived keys are requested
+    /// for, `0` to [`MAX_VMPL`]. The guest must run at this or a more
+    /// privileged VMPL, e.g. it is usually `0` without an SVSM and the
+    /// guest runs at VMPL 1 or higher with one.
+    pub vmpl: u32,
+}
+
 #[derive(Debug, Default)]
-pub struct SnpAttester {}
+pub struct SnpAttester {
+    options: SnpOptions,
+}
+
+impl SnpAttester {
+    pub fn new(options: SnpOptions) -> Result<Self> {
+        if options.vmpl > MAX_VMPL {
+            bail!(
+                "SNP Attester: VMPL {} is out of range 0..={MAX_VMPL}",
+                options.vmpl
+            );
+        }
+
+        Ok(Self { options })
+    }
+}
 
 #[async_trait::async_trait]
 impl Attester for SnpAttester {
@@ -42,7 +86,7 @@ impl Attester for SnpAttester {
         let data = report_data.as_slice().try_into()?;
 
         let (report, certs) = firmware
-            .get_ext_report(None, Some(data), Some(0))
+            .get_ext_report(None, Some(data), Some(self.options.vmpl))
             .context("Failed to get attestation report")?;
 
         let evidence = SnpEvidence {
@@ -55,11 +99,77 @@ impl Attester for SnpAttester {
 
     async fn check_init_data(&self, init_data: &[u8]) -> Result<InitdataResult> {
         let hostdata = hostdata::get_snp_host_data().context("Get HOSTDATA failed")?;
-        let init_data: [u8; 32] = pad(init_data);
+        let init_data: [u8; SNP_HOSTDATA_SIZE] = pad(init_data);
         if init_data != hostdata {
             bail!("HOSTDATA does not match.");
         }
 
         Ok(InitdataResult::Ok)
     }
+
+    fn init_data_len(&self) -> Option<usize> {
+        Some(SNP_HOSTDATA_SIZE)
+    }
+
+    fn capabilities(&self) -> AttesterCapabilities {
+        AttesterCapabilities {
+            supports_runtime_measurement: false,
+            report_data_len: 64,
+            supported_hash_algorithms: Vec::new(),
+            init_data_supported: true,
+        }
+    }
+
+    async fn get_derived_key(&self, context: &[u8], length: usize) -> Result<Vec<u8>> {
+        let mut firmware = Firmware::open()?;
+        let request = DerivedKey::new(
+            false,
+            GuestFieldSelect(DERIVED_KEY_GUEST_FIELDS),
+            self.options.vmpl,
+            0,
+            0,
+        );
+        let key = firmware
+            .get_derived_key(None, request)
+            .context("Failed to get SNP derived key")?;
+
+        derive_key(&key, context, length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let options: SnpOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, SnpOptions::default());
+        assert_eq!(options.vmpl, 0);
+    }
+
+    #[test]
+    fn test_snp_capabilities() {
+        assert_eq!(
+            SnpAttester::default().capabilities(),
+            AttesterCapabilities {
+                supports_runtime_measurement: false,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![],
+                init_data_supported: true,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case(0, true)]
+    #[case(MAX_VMPL, true)]
+    #[case(MAX_VMPL + 1, false)]
+    fn test_vmpl_range(#[case] vmpl: u32, #[case] valid: bool) {
+        let options: SnpOptions =
+            serde_json::from_value(serde_json::json!({ "vmpl": vmpl })).unwrap();
+        assert_eq!(SnpAttester::new(options).is_ok(), valid);
+    }
 }
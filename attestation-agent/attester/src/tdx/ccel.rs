@@ -0,0 +1,177 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! The CC eventlog (CCEL) ACPI table of TDX, which carries the measurements
+//! of the firmware into RTMR 0 and 1. Linux exposes the table at
+//! [`CCEL_HEADER_PATH`], and its log area at [`CCEL_PATH`].
+
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+
+use anyhow::{bail, ensure, Context, Result};
+use scroll::{Pread, LE};
+
+/// The log area of the CCEL table.
+pub const CCEL_PATH: &str = "/sys/firmware/acpi/tables/data/CCEL";
+
+/// The CCEL table itself, i.e. its header.
+pub const CCEL_HEADER_PATH: &str = "/sys/firmware/acpi/tables/CCEL";
+
+/// Default size limit in bytes of the log area put into the evidence, well
+/// above the 64 KiB that TDVF reserves.
+pub const DEFAULT_CCEL_MAX_SIZE: usize = 256 * 1024;
+
+const CCEL_SIGNATURE: &[u8; 4] = b"CCEL";
+
+/// CC type of TDX in the CCEL table.
+const CC_TYPE_TDX: u8 = 2;
+
+/// The CCEL table, see "CC Event Log ACPI Table" of the ACPI specification.
+#[derive(Debug, PartialEq, Eq, Pread)]
+pub struct CcelHeader {
+    pub signature: [u8; 4],
+    pub length: u32,
+    pub revision: u8,
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+    pub cc_type: u8,
+    pub cc_subtype: u8,
+    pub reserved: u16,
+
+    /// Log area minimum length, i.e. the size of the log area.
+    pub laml: u64,
+
+    /// Log area start address.
+    pub lasa: u64,
+}
+
+/// Size in bytes of [`CcelHeader`].
+const CCEL_HEADER_SIZE: usize = 56;
+
+impl CcelHeader {
+    /// Parse and check the CCEL table of TDX.
+    pub fn parse(table: &[u8]) -> Result<Self> {
+        let header: Self = table
+            .pread_with(0, LE)
+            .with_context(|| format!("CCEL table is {} bytes", table.len()))?;
+        ensure!(&header.signature == CCEL_SIGNATURE, "not a CCEL table");
+        ensure!(
+            header.length as usize == table.len() && table.len() >= CCEL_HEADER_SIZE,
+            "CCEL table has length {}, but is {} bytes",
+            header.length,
+            table.len()
+        );
+        let checksum = table.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        ensure!(checksum == 0, "CCEL table has a wrong checksum");
+        ensure!(
+            header.cc_type == CC_TYPE_TDX,
+            "CCEL table is of CC type {}, not of TDX",
+            header.cc_type
+        );
+        Ok(header)
+    }
+}
+
+/// Read the log area of the CCEL table at `path`, `None` if there is none.
+/// The table at `header_path` is checked if it exists. The log area must be
+/// no more than `max_size` bytes.
+pub fn read(path: &Path, header_path: &Path, max_size: usize) -> Result<Option<Vec<u8>>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("open {}", path.display())),
+    };
+
+    match std::fs::read(header_path) {
+        Ok(table) => {
+            let header = CcelHeader::parse(&table)?;
+            if header.laml > max_size as u64 {
+                bail!(
+                    "CCEL log area is {} bytes, more than the limit of {max_size} bytes",
+                    header.laml
+                );
+            }
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            log::debug!("No CCEL table at {}", header_path.display())
+        }
+        Err(e) => return Err(e).with_context(|| format!("read {}", header_path.display())),
+    }
+
+    // The size of the files of ACPI tables is not reliable, so the limit is
+    // checked while reading
+    let mut log = Vec::new();
+    file.take(max_size as u64 + 1)
+        .read_to_end(&mut log)
+        .with_context(|| format!("read {}", path.display()))?;
+    if log.len() > max_size {
+        bail!("CCEL log area is more than the limit of {max_size} bytes");
+    }
+    Ok(Some(log))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// A synthetic CCEL table, built by hand in the layout of the ACPI spec
+    /// rather than dumped from a TD, with a log area of 64 KiB at
+    /// 0x7f000000.
+    const TABLE: &[u8] = include_bytes!("../../test/tdx/ccel_header.bin");
+
+    #[test]
+    fn parse_table() {
+        let header = CcelHeader::parse(TABLE).unwrap();
+        assert_eq!(&header.signature, b"CCEL");
+        assert_eq!(header.length, 56);
+        assert_eq!(header.cc_type, CC_TYPE_TDX);
+        assert_eq!(header.laml, 0x10000);
+        assert_eq!(header.lasa, 0x7f00_0000);
+    }
+
+    #[test]
+    fn invalid_table() {
+        CcelHeader::parse(&TABLE[..40]).unwrap_err();
+
+        let mut table = TABLE.to_vec();
+        table[9] ^= 1;
+        let e = CcelHeader::parse(&table).unwrap_err();
+        assert!(e.to_string().contains("checksum"), "{e}");
+
+        // SEV, with the checksum fixed up
+        let mut table = TABLE.to_vec();
+        table[36] = 1;
+        table[9] = table[9].wrapping_add(1);
+        let e = CcelHeader::parse(&table).unwrap_err();
+        assert!(e.to_string().contains("CC type 1"), "{e}");
+    }
+
+    #[test]
+    fn read_log_area() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data");
+        let header_path = dir.path().join("CCEL");
+
+        assert_eq!(read(&path, &header_path, 16).unwrap(), None);
+
+        fs::write(&path, [0xa5; 16]).unwrap();
+        assert_eq!(read(&path, &header_path, 16).unwrap(), Some(vec![0xa5; 16]));
+        let e = read(&path, &header_path, 15).unwrap_err();
+        assert!(e.to_string().contains("limit of 15 bytes"), "{e}");
+
+        // The table tells the size of the log area before reading it
+        fs::write(&header_path, TABLE).unwrap();
+        let e = read(&path, &header_path, 16).unwrap_err();
+        assert!(e.to_string().contains("65536 bytes"), "{e}");
+        assert!(read(&path, &header_path, DEFAULT_CCEL_MAX_SIZE).is_ok());
+    }
+}
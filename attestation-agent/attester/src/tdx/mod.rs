@@ -3,28 +3,47 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use self::ccel::{CCEL_HEADER_PATH, CCEL_PATH, DEFAULT_CCEL_MAX_SIZE};
 use self::rtmr::TdxRtmrEvent;
 
 use super::tsm_report::*;
 use super::Attester;
+use crate::detect::{Host, Platform};
 use crate::utils::pad;
-use crate::InitdataResult;
+use crate::{AttesterCapabilities, AttesterError, HashAlgorithm, InitdataResult};
 use anyhow::*;
 use base64::Engine;
 use scroll::Pread;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use tdx_attest_rs::tdx_report_t;
 
+pub mod ccel;
+pub mod qgs;
 mod report;
 mod rtmr;
 
 const TDX_REPORT_DATA_SIZE: usize = 64;
-const CCEL_PATH: &str = "/sys/firmware/acpi/tables/data/CCEL";
+const TDX_MRCONFIGID_SIZE: usize = 48;
+const DEFAULT_QGS_TIMEOUT_SECONDS: u64 = 30;
+
+/// PCRs that map to RTMR 2 and 3, which are left for the OS and the
+/// applications to extend. RTMR 0 and 1 are extended by the firmware.
+const RUNTIME_MEASUREMENT_PCRS: RangeInclusive<u64> = 8..=23;
+
+/// PCRs that map to an RTMR, see [`pcr_to_rtmr`]. PCR 0 is MRTD.
+const RTMR_PCRS: RangeInclusive<u64> = 1..=23;
 
 pub fn detect_platform() -> bool {
-    TsmReportPath::new(TsmReportProvider::Tdx).is_ok() || Path::new("/dev/tdx_guest").exists()
+    detect_platform_on(&Host)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform) -> bool {
+    TsmReportPath::new_in(&platform.path(TSM_REPORT_PATH), TsmReportProvider::Tdx).is_ok()
+        || platform.exists("/dev/tdx_guest")
 }
 
 fn get_quote_ioctl(report_data: &Vec<u8>) -> Result<Vec<u8>> {
@@ -55,21 +74,211 @@ fn runtime_measurement_extend_available() -> bool {
     true
 }
 
+// The match follows https://github.com/confidential-containers/td-shim/blob/main/doc/tdshim_spec.md#td-event-log
+fn pcr_to_rtmr(register_index: u64) -> u64 {
+    match register_index {
+        1 | 7 => 0,
+        2..=6 => 1,
+        8..=15 => 2,
+        _ => 3,
+    }
+}
+
+/// The TD report with the given report data, for the QGS to sign it into a
+/// quote.
+fn get_td_report_raw(report_data: &[u8]) -> Result<[u8; qgs::TD_REPORT_SIZE]> {
+    let tdx_report_data = tdx_attest_rs::tdx_report_data_t {
+        d: report_data.try_into()?,
+    };
+    let mut report = tdx_report_t { d: [0; 1024] };
+    match tdx_attest_rs::tdx_att_get_report(Some(&tdx_report_data), &mut report) {
+        tdx_attest_rs::tdx_attest_error_t::TDX_ATTEST_SUCCESS => Ok(report.d),
+        error_code => bail!(
+            "TDX Attester: Failed to get TD report. Error code: {:?}",
+            error_code
+        ),
+    }
+}
+
+fn get_td_report() -> Result<report::TdReport> {
+    let mut report = tdx_report_t { d: [0; 1024] };
+    match tdx_attest_rs::tdx_att_get_report(None, &mut report) {
+        tdx_attest_rs::tdx_attest_error_t::TDX_ATTEST_SUCCESS => {
+            log::debug!("Successfully get report")
+        }
+        error_code => {
+            bail!(
+                "TDX Attester: Failed to get TD report. Error code: {:?}",
+                error_code
+            );
+        }
+    };
+
+    report
+        .d
+        .pread::<report::TdReport>(0)
+        .context("Parse TD report failed")
+}
+
+/// The 48 bytes of the given RTMR. The TD report has the 4 RTMRs in a row,
+/// parsed as little endian words.
+fn rtmr_value(rtmrs: &[u64; 24], rtmr_index: u64) -> Vec<u8> {
+    let start = rtmr_index as usize * 6;
+    rtmrs[start..start + 6]
+        .iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect()
+}
+
 pub const DEFAULT_EVENTLOG_PATH: &str = "/run/attestation-agent/eventlog";
 
 #[derive(Serialize, Deserialize)]
 struct TdxEvidence {
     // Base64 encoded CC Eventlog ACPI table
     // refer to https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html#cc-event-log-acpi-table.
+    // Omitted if the platform has none, or it cannot be read.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     cc_eventlog: Option<String>,
     // Base64 encoded TD quote.
     quote: String,
     // Eventlog of Attestation Agent
     aa_eventlog: Option<String>,
+    // Eventlogs of Attestation Agent of the registers other than the one of
+    // `aa_eventlog`, keyed by the PCR index.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    aa_register_eventlogs: BTreeMap<u64, String>,
+}
+
+/// Where the TDX attester gets the quote from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteSource {
+    /// TSM reports of the kernel, or else the tdx-attest library, which
+    /// reaches the QGS as configured in `/etc/tdx-attest.conf` of the guest.
+    #[default]
+    Library,
+
+    /// The QGS at `qgs_address`, over vsock or a unix socket, for hosts that
+    /// expose it only that way. The TD report is still taken from the TDX
+    /// guest device.
+    Vsock,
+}
+
+/// Options of the TDX attester, the `[attester.tdx]` table of the AA config.
+///
+/// ```toml
+/// [attester.tdx]
+/// ccel_path = "/sys/firmware/acpi/tables/data/CCEL"
+/// ccel_header_path = "/sys/firmware/acpi/tables/CCEL"
+/// ccel_max_size = 262144
+/// aa_eventlog_path = "/run/attestation-agent/eventlog"
+/// quote_source = "vsock"
+/// qgs_address = "vsock://2:4050"
+/// qgs_timeout_seconds = 30
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TdxOptions {
+    /// The CC eventlog ACPI table put into the evidence.
+    pub ccel_path: PathBuf,
+
+    /// The CC eventlog ACPI table itself, whose header is checked against
+    /// the log area, if it exists.
+    pub ccel_header_path: PathBuf,
+
+    /// Size limit in bytes of the CC eventlog, s.t. the evidence does not
+    /// grow without bound. A larger eventlog is left out of the evidence.
+    pub ccel_max_size: usize,
+
+    /// The eventlog of AA put into the evidence, together with the
+    /// eventlogs of the other registers at `<aa_eventlog_path>.pcr<index>`.
+    /// AA sets this to its `eventlog_config.file_path`, so it only needs
+    /// to be given when the attester is used on its own.
+    pub aa_eventlog_path: PathBuf,
+
+    /// Where the quote is generated.
+    pub quote_source: QuoteSource,
+
+    /// The socket of the QGS if `quote_source` is `vsock`, either
+    /// `vsock://<cid>:<port>` or `unix://<path>`.
+    pub qgs_address: String,
+
+    /// Seconds to wait for the QGS to return the quote, `0` means no limit.
+    pub qgs_timeout_seconds: u64,
+}
+
+impl Default for TdxOptions {
+    fn default() -> Self {
+        Self {
+            ccel_path: CCEL_PATH.into(),
+            ccel_header_path: CCEL_HEADER_PATH.into(),
+            ccel_max_size: DEFAULT_CCEL_MAX_SIZE,
+            aa_eventlog_path: DEFAULT_EVENTLOG_PATH.into(),
+            quote_source: QuoteSource::default(),
+            qgs_address: qgs::DEFAULT_QGS_ADDRESS.into(),
+            qgs_timeout_seconds: DEFAULT_QGS_TIMEOUT_SECONDS,
+        }
+    }
 }
 
 #[derive(Debug, Default)]
-pub struct TdxAttester {}
+pub struct TdxAttester {
+    options: TdxOptions,
+}
+
+/// Read an eventlog of AA as a verifier replays it, i.e. one entry per
+/// line. The file may have a header and metadata before the entries, see
+/// [`aael::parse_str`], which are not sent.
+fn read_aa_eventlog(path: &Path) -> Result<String> {
+    let log = fs::read_to_string(path)?;
+    let entries = aael::parse_entries(&log)?;
+    Ok(entries.iter().map(|entry| format!("{entry}\n")).collect())
+}
+
+impl TdxAttester {
+    pub fn new(options: TdxOptions) -> Self {
+        Self { options }
+    }
+
+    /// The eventlog of AA of its `init_pcr`, and those of the other
+    /// registers by their PCR index, see [`read_aa_eventlog`].
+    fn read_aa_eventlogs(&self) -> (Option<String>, BTreeMap<u64, String>) {
+        let aa_eventlog = match read_aa_eventlog(&self.options.aa_eventlog_path) {
+            Result::Ok(el) => Some(el),
+            Result::Err(e) => {
+                log::warn!("Read AA Eventlog failed: {:?}", e);
+                None
+            }
+        };
+
+        let register_eventlogs = RUNTIME_MEASUREMENT_PCRS
+            .filter_map(|pcr| {
+                let mut path = self.options.aa_eventlog_path.clone().into_os_string();
+                path.push(format!(".pcr{pcr}"));
+                if !Path::new(&path).exists() {
+                    return None;
+                }
+                match read_aa_eventlog(Path::new(&path)) {
+                    Result::Ok(el) => Some((pcr, el)),
+                    Result::Err(e) => {
+                        log::warn!("Read AA Eventlog of PCR {pcr} failed: {:?}", e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        (aa_eventlog, register_eventlogs)
+    }
+
+    fn read_cc_eventlog(&self) -> Result<Option<Vec<u8>>> {
+        ccel::read(
+            &self.options.ccel_path,
+            &self.options.ccel_header_path,
+            self.options.ccel_max_size,
+        )
+    }
+}
 
 #[async_trait::async_trait]
 impl Attester for TdxAttester {
@@ -80,45 +289,63 @@ impl Attester for TdxAttester {
 
         report_data.resize(TDX_REPORT_DATA_SIZE, 0);
 
-        let quote_bytes = TsmReportPath::new(TsmReportProvider::Tdx).map_or_else(
-            |notsm| {
-                get_quote_ioctl(&report_data)
-                    .context(format!("TDX Attester: quote generation using ioctl() fallback failed after a TSM report error ({notsm})"))
-            },
-            |tsm| {
-                tsm.attestation_report(TsmReportData::Tdx(report_data.clone()))
-                    .context("TDX Attester: quote generation using TSM reports failed")
-            },
-        )?;
+        let quote_bytes = match self.options.quote_source {
+            QuoteSource::Library => TsmReportPath::new(TsmReportProvider::Tdx).map_or_else(
+                |notsm| {
+                    get_quote_ioctl(&report_data)
+                        .context(format!("TDX Attester: quote generation using ioctl() fallback failed after a TSM report error ({notsm})"))
+                },
+                |tsm| {
+                    tsm.attestation_report(TsmReportData::Tdx(report_data.clone()))
+                        .context("TDX Attester: quote generation using TSM reports failed")
+                },
+            )?,
+            QuoteSource::Vsock => {
+                let td_report = get_td_report_raw(&report_data)?;
+                qgs::get_quote(
+                    &self.options.qgs_address,
+                    &td_report,
+                    self.options.qgs_timeout_seconds,
+                )
+                .await
+                .context("TDX Attester: quote generation using the QGS over vsock failed")?
+            }
+        };
 
         let engine = base64::engine::general_purpose::STANDARD;
         let quote = engine.encode(quote_bytes);
 
-        let cc_eventlog = match std::fs::read(CCEL_PATH) {
-            Result::Ok(el) => Some(engine.encode(el)),
-            Result::Err(e) => {
-                log::warn!("Read CC Eventlog failed: {:?}", e);
+        let cc_eventlog = match self.read_cc_eventlog() {
+            Result::Ok(Some(el)) => Some(engine.encode(el)),
+            Result::Ok(None) => {
+                log::debug!(
+                    "No CC Eventlog at {}, left out of the evidence",
+                    self.options.ccel_path.display()
+                );
                 None
             }
-        };
-
-        let aa_eventlog = match fs::read_to_string(DEFAULT_EVENTLOG_PATH) {
-            Result::Ok(el) => Some(el),
             Result::Err(e) => {
-                log::warn!("Read AA Eventlog failed: {:?}", e);
+                log::warn!("Read CC Eventlog failed, left out of the evidence: {e:#}");
                 None
             }
         };
 
+        let (aa_eventlog, aa_register_eventlogs) = self.read_aa_eventlogs();
+
         let evidence = TdxEvidence {
             cc_eventlog,
             quote,
             aa_eventlog,
+            aa_register_eventlogs,
         };
 
         serde_json::to_string(&evidence).context("Serialize TDX evidence failed")
     }
 
+    fn runtime_measurement_registers(&self) -> Option<RangeInclusive<u64>> {
+        runtime_measurement_extend_available().then_some(RUNTIME_MEASUREMENT_PCRS)
+    }
+
     async fn extend_runtime_measurement(
         &self,
         event_digest: Vec<u8>,
@@ -127,14 +354,11 @@ impl Attester for TdxAttester {
         if !runtime_measurement_extend_available() {
             bail!("TDX Attester: Cannot extend runtime measurement on this system");
         }
+        if !RUNTIME_MEASUREMENT_PCRS.contains(&register_index) {
+            bail!("Invalid PCR index: {register_index}");
+        }
 
-        // The match follows https://github.com/confidential-containers/td-shim/blob/main/doc/tdshim_spec.md#td-event-log
-        let rtmr_index = match register_index {
-            1 | 7 => 0,
-            2..=6 => 1,
-            8..=15 => 2,
-            _ => 3,
-        };
+        let rtmr_index = pcr_to_rtmr(register_index);
 
         let extend_data: [u8; 48] = pad(&event_digest);
         let event: Vec<u8> = TdxRtmrEvent::default()
@@ -157,37 +381,257 @@ impl Attester for TdxAttester {
         Ok(())
     }
 
-    async fn check_init_data(&self, init_data: &[u8]) -> Result<InitdataResult> {
-        let mut report = tdx_report_t { d: [0; 1024] };
-        match tdx_attest_rs::tdx_att_get_report(None, &mut report) {
-            tdx_attest_rs::tdx_attest_error_t::TDX_ATTEST_SUCCESS => {
-                log::debug!("Successfully get report")
-            }
-            error_code => {
-                bail!(
-                    "TDX Attester: Failed to get TD report. Error code: {:?}",
-                    error_code
-                );
-            }
-        };
+    async fn get_runtime_measurement(
+        &self,
+        register_index: u64,
+        algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        if algorithm != HashAlgorithm::Sha384 {
+            return Err(AttesterError::Unsupported(
+                "reading RTMR with other algorithms than sha384",
+            )
+            .into());
+        }
+        if !RTMR_PCRS.contains(&register_index) {
+            bail!("Invalid PCR index: {register_index}");
+        }
 
-        let td_report = report
-            .d
-            .pread::<report::TdReport>(0)
-            .context("Parse TD report failed")?;
+        let td_report = get_td_report()?;
+        Ok(rtmr_value(
+            &td_report.tdinfo.rtmr,
+            pcr_to_rtmr(register_index),
+        ))
+    }
+
+    async fn check_init_data(&self, init_data: &[u8]) -> Result<InitdataResult> {
+        let td_report = get_td_report()?;
 
-        let init_data: [u8; 48] = pad(init_data);
+        let init_data: [u8; TDX_MRCONFIGID_SIZE] = pad(init_data);
         if init_data != td_report.tdinfo.mrconfigid {
             bail!("Init data does not match!");
         }
 
         Ok(InitdataResult::Ok)
     }
+
+    fn init_data_len(&self) -> Option<usize> {
+        Some(TDX_MRCONFIGID_SIZE)
+    }
+
+    fn capabilities(&self) -> AttesterCapabilities {
+        let supports_runtime_measurement = runtime_measurement_extend_available();
+        AttesterCapabilities {
+            supports_runtime_measurement,
+            report_data_len: TDX_REPORT_DATA_SIZE,
+            // RTMRs are SHA-384 registers
+            supported_hash_algorithms: supports_runtime_measurement
+                .then(|| vec![HashAlgorithm::Sha384])
+                .unwrap_or_default(),
+            init_data_supported: true,
+        }
+    }
+
+    /// The TDX module does not provide a sealing or derivation key to the
+    /// TD, so there is nothing to derive a key bound to the TD from.
+    async fn get_derived_key(&self, _context: &[u8], _length: usize) -> Result<Vec<u8>> {
+        Err(AttesterError::Unsupported("key derivation").into())
+    }
+
+    async fn get_firmware_eventlog(&self) -> Result<Option<Vec<u8>>> {
+        self.read_cc_eventlog()
+            .context("TDX Attester: Read CC Eventlog failed")
+    }
+
+    /// The CC measurement register index of the RTMR, see the CCEL of TDX.
+    fn firmware_register_index(&self, register_index: u64) -> u64 {
+        pcr_to_rtmr(register_index) + 1
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(8, 2)]
+    #[case(15, 2)]
+    #[case(16, 3)]
+    #[case(17, 3)]
+    #[case(23, 3)]
+    fn test_runtime_measurement_pcr_to_rtmr(#[case] pcr: u64, #[case] rtmr: u64) {
+        assert!(RUNTIME_MEASUREMENT_PCRS.contains(&pcr));
+        assert_eq!(pcr_to_rtmr(pcr), rtmr);
+    }
+
+    #[test]
+    fn test_firmware_pcrs_not_extendable() {
+        for pcr in 0..8 {
+            assert!(pcr_to_rtmr(pcr) < 2);
+            assert!(!RUNTIME_MEASUREMENT_PCRS.contains(&pcr));
+        }
+    }
+
+    #[test]
+    fn test_rtmr_value() {
+        let mut rtmrs = [0u64; 24];
+        rtmrs[18] = 0x0807060504030201;
+        rtmrs[23] = u64::MAX;
+
+        let rtmr3 = rtmr_value(&rtmrs, 3);
+        assert_eq!(rtmr3.len(), 48);
+        assert_eq!(&rtmr3[..8], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(&rtmr3[40..], &[0xff; 8]);
+        assert_eq!(rtmr_value(&rtmrs, 2), vec![0; 48]);
+    }
+
+    #[test]
+    fn test_tdx_capabilities() {
+        let capabilities = TdxAttester::default().capabilities();
+        let rtmr = runtime_measurement_extend_available();
+        assert_eq!(
+            capabilities,
+            AttesterCapabilities {
+                supports_runtime_measurement: rtmr,
+                report_data_len: 64,
+                supported_hash_algorithms: if rtmr {
+                    vec![HashAlgorithm::Sha384]
+                } else {
+                    vec![]
+                },
+                init_data_supported: true,
+            }
+        );
+    }
+
+    #[test]
+    fn test_options() {
+        let options: TdxOptions = serde_json::from_str("{}").unwrap();
+        assert_eq!(options, TdxOptions::default());
+        assert_eq!(options.ccel_path, Path::new(CCEL_PATH));
+        assert_eq!(options.aa_eventlog_path, Path::new(DEFAULT_EVENTLOG_PATH));
+
+        let options: TdxOptions =
+            serde_json::from_str(r#"{"aa_eventlog_path": "/tmp/eventlog"}"#).unwrap();
+        assert_eq!(options.aa_eventlog_path, Path::new("/tmp/eventlog"));
+        assert_eq!(options.ccel_path, Path::new(CCEL_PATH));
+        assert_eq!(options.quote_source, QuoteSource::Library);
+        assert_eq!(options.ccel_max_size, DEFAULT_CCEL_MAX_SIZE);
+
+        let options: TdxOptions =
+            serde_json::from_str(r#"{"quote_source": "vsock", "qgs_address": "vsock://3:4050"}"#)
+                .unwrap();
+        assert_eq!(options.quote_source, QuoteSource::Vsock);
+        assert_eq!(options.qgs_address, "vsock://3:4050");
+        assert_eq!(options.qgs_timeout_seconds, DEFAULT_QGS_TIMEOUT_SECONDS);
+        serde_json::from_str::<TdxOptions>(r#"{"quote_source": "qgs"}"#).unwrap_err();
+    }
+
+    #[rstest]
+    #[case(1, 1)]
+    #[case(3, 2)]
+    #[case(9, 3)]
+    #[case(17, 4)]
+    fn test_firmware_register_index(#[case] pcr: u64, #[case] mr_index: u64) {
+        assert_eq!(
+            TdxAttester::default().firmware_register_index(pcr),
+            mr_index
+        );
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(24)]
+    #[case(999)]
+    #[tokio::test]
+    async fn test_runtime_measurement_invalid_register(#[case] pcr: u64) {
+        let e = TdxAttester::default()
+            .get_runtime_measurement(pcr, HashAlgorithm::Sha384)
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), format!("Invalid PCR index: {pcr}"));
+    }
+
+    #[tokio::test]
+    async fn test_firmware_eventlog_missing() {
+        let attester = TdxAttester::new(TdxOptions {
+            ccel_path: "/nonexistent/CCEL".into(),
+            ..Default::default()
+        });
+        assert_eq!(attester.get_firmware_eventlog().await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_derived_key_unsupported() {
+        let e = TdxAttester::default()
+            .get_derived_key(b"context", 32)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<AttesterError>(),
+            Some(AttesterError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_evidence_without_cc_eventlog() {
+        let evidence = TdxEvidence {
+            cc_eventlog: None,
+            quote: "cXVvdGU=".into(),
+            aa_eventlog: None,
+            aa_register_eventlogs: BTreeMap::new(),
+        };
+        let json = serde_json::to_value(&evidence).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"quote": "cXVvdGU=", "aa_eventlog": null})
+        );
+        let evidence: TdxEvidence = serde_json::from_value(json).unwrap();
+        assert_eq!(evidence.cc_eventlog, None);
+    }
+
+    #[test]
+    fn test_aa_eventlogs() {
+        let dir = tempfile::tempdir().unwrap();
+        let aa_eventlog_path = dir.path().join("eventlog");
+        let attester = TdxAttester::new(TdxOptions {
+            aa_eventlog_path: aa_eventlog_path.clone(),
+            ..Default::default()
+        });
+        assert_eq!(attester.read_aa_eventlogs(), (None, BTreeMap::new()));
+
+        // The metadata of the entries is not sent
+        fs::write(
+            &aa_eventlog_path,
+            "[0 2026-10-15T08:00:00.000Z] INIT sha384/00\n\
+             [1 2026-10-15T08:00:01.000Z alg=sha384] domain operation content\n",
+        )
+        .unwrap();
+        // Nor the header
+        fs::write(
+            dir.path().join("eventlog.pcr18"),
+            "AAEL/1.0 algorithm=sha384 init_pcr=18\nINIT sha384/01\n",
+        )
+        .unwrap();
+        // Out of the runtime measurement registers
+        fs::write(dir.path().join("eventlog.pcr7"), "INIT sha384/02\n").unwrap();
+        // Nor the CEL record around the entry
+        fs::write(
+            dir.path().join("eventlog.pcr19"),
+            aael::cel::aael_to_cel("INIT sha384/03\n", HashAlgorithm::Sha384, 19),
+        )
+        .unwrap();
+        assert_eq!(
+            attester.read_aa_eventlogs(),
+            (
+                Some("INIT sha384/00\ndomain operation content\n".into()),
+                BTreeMap::from([
+                    (18, "INIT sha384/01\n".into()),
+                    (19, "INIT sha384/03\n".into())
+                ])
+            )
+        );
+    }
 
     #[ignore]
     #[tokio::test]
@@ -0,0 +1,431 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Client of the Quote Generation Service (QGS) over vsock or a unix socket,
+//! for hosts that do not expose the QGS to the tdx-attest library in the
+//! guest.
+//!
+//! The messages follow `qgs_msg_lib` of Intel DCAP. Each one is sent with
+//! its size as a 4 byte big endian prefix, and is a little endian header
+//! followed by the body of its type.
+
+use std::{path::PathBuf, time::Duration};
+
+use scroll::{Pread, LE};
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Address of the QGS on the host, as the QGS of DCAP listens by default.
+pub const DEFAULT_QGS_ADDRESS: &str = "vsock://2:4050";
+
+/// Size in bytes of the TD report, see `TDREPORT_STRUCT`.
+pub const TD_REPORT_SIZE: usize = 1024;
+
+const QGS_MSG_MAJOR_VERSION: u16 = 1;
+const QGS_MSG_MINOR_VERSION: u16 = 0;
+
+const GET_QUOTE_REQ: u32 = 0;
+const GET_QUOTE_RESP: u32 = 1;
+
+const QGS_MSG_SUCCESS: u32 = 0;
+
+/// Size in bytes of the size prefix of a message.
+const PREFIX_SIZE: usize = 4;
+
+/// Size in bytes of `qgs_msg_header_t`.
+const HEADER_SIZE: usize = 16;
+
+/// Size in bytes of the fixed fields of the body of `GET_QUOTE_REQ` and
+/// `GET_QUOTE_RESP`, i.e. two sizes.
+const BODY_SIZES_SIZE: usize = 8;
+
+/// Upper bound of the size of a response, far above a quote with its PCK
+/// certificate chain, s.t. a broken prefix cannot make us allocate
+/// arbitrary memory.
+const MAX_MESSAGE_SIZE: usize = 1 << 20;
+
+#[derive(Debug, Error)]
+pub enum QgsError {
+    #[error("invalid QGS address `{0}`, expected `vsock://<cid>:<port>` or `unix://<path>`")]
+    InvalidAddress(String),
+
+    #[error("connect to QGS at {address}")]
+    Connect {
+        address: String,
+        #[source]
+        source: std::io::Error,
+    },
+
+    #[error("QGS did not return the quote within {0} seconds")]
+    Timeout(u64),
+
+    #[error("QGS connection failed")]
+    Io(#[from] std::io::Error),
+
+    #[error("malformed QGS response: {0}")]
+    Malformed(String),
+
+    #[error("QGS failed to generate the quote, error code {0:#x}")]
+    Service(u32),
+}
+
+/// `qgs_msg_header_t`.
+#[derive(Debug, Pread)]
+struct Header {
+    major_version: u16,
+    minor_version: u16,
+    type_: u32,
+    size: u32,
+    error_code: u32,
+}
+
+/// Socket of the QGS.
+#[derive(Debug, PartialEq, Eq)]
+pub enum QgsAddress {
+    /// `vsock://<cid>:<port>`
+    Vsock { cid: u32, port: u32 },
+
+    /// `unix://<path>`, e.g. the socket of the QGS mounted into the guest.
+    Unix(PathBuf),
+}
+
+/// Parse a `vsock://<cid>:<port>` or `unix://<path>` address.
+pub fn parse_address(address: &str) -> Result<QgsAddress, QgsError> {
+    let invalid = || QgsError::InvalidAddress(address.to_string());
+    if let Some(path) = address.strip_prefix("unix://") {
+        if !path.starts_with('/') {
+            return Err(invalid());
+        }
+        return Ok(QgsAddress::Unix(path.into()));
+    }
+
+    let (cid, port) = address
+        .strip_prefix("vsock://")
+        .and_then(|rest| rest.split_once(':'))
+        .ok_or_else(invalid)?;
+    Ok(QgsAddress::Vsock {
+        cid: cid.parse().map_err(|_| invalid())?,
+        port: port.parse().map_err(|_| invalid())?,
+    })
+}
+
+/// The `GET_QUOTE_REQ` message of the TD report, with its size prefix.
+pub fn encode_get_quote_request(td_report: &[u8; TD_REPORT_SIZE]) -> Vec<u8> {
+    let size = HEADER_SIZE + BODY_SIZES_SIZE + TD_REPORT_SIZE;
+    let mut message = Vec::with_capacity(PREFIX_SIZE + size);
+    message.extend_from_slice(&(size as u32).to_be_bytes());
+
+    message.extend_from_slice(&QGS_MSG_MAJOR_VERSION.to_le_bytes());
+    message.extend_from_slice(&QGS_MSG_MINOR_VERSION.to_le_bytes());
+    message.extend_from_slice(&GET_QUOTE_REQ.to_le_bytes());
+    message.extend_from_slice(&(size as u32).to_le_bytes());
+    message.extend_from_slice(&QGS_MSG_SUCCESS.to_le_bytes());
+
+    message.extend_from_slice(&(TD_REPORT_SIZE as u32).to_le_bytes());
+    // No list of the IDs of the attestation keys, the QGS picks one
+    message.extend_from_slice(&0u32.to_le_bytes());
+    message.extend_from_slice(td_report);
+    message
+}
+
+/// The quote of a `GET_QUOTE_RESP` message, without its size prefix.
+pub fn decode_get_quote_response(message: &[u8]) -> Result<Vec<u8>, QgsError> {
+    let header: Header = message
+        .pread_with(0, LE)
+        .map_err(|_| QgsError::Malformed(format!("{} bytes are no message", message.len())))?;
+    if header.major_version != QGS_MSG_MAJOR_VERSION {
+        return Err(QgsError::Malformed(format!(
+            "unsupported version {}.{}",
+            header.major_version, header.minor_version
+        )));
+    }
+    if header.type_ != GET_QUOTE_RESP {
+        return Err(QgsError::Malformed(format!(
+            "unexpected message type {}",
+            header.type_
+        )));
+    }
+    if header.size as usize != message.len() {
+        return Err(QgsError::Malformed(format!(
+            "the header has size {}, but the message is {} bytes",
+            header.size,
+            message.len()
+        )));
+    }
+    if header.error_code != QGS_MSG_SUCCESS {
+        return Err(QgsError::Service(header.error_code));
+    }
+
+    let sizes = (
+        message.pread_with::<u32>(HEADER_SIZE, LE),
+        message.pread_with::<u32>(HEADER_SIZE + 4, LE),
+    );
+    let (Ok(id_size), Ok(quote_size)) = sizes else {
+        return Err(QgsError::Malformed("truncated body".into()));
+    };
+    let id_end = HEADER_SIZE + BODY_SIZES_SIZE + id_size as usize;
+    let quote = message
+        .get(id_end..)
+        .filter(|quote| quote.len() == quote_size as usize)
+        .ok_or_else(|| {
+            QgsError::Malformed(format!(
+                "the body has an ID of {id_size} bytes and a quote of {quote_size} bytes, but is {} bytes",
+                message.len() - HEADER_SIZE - BODY_SIZES_SIZE
+            ))
+        })?;
+    if quote.is_empty() {
+        return Err(QgsError::Malformed("empty quote".into()));
+    }
+    Ok(quote.to_vec())
+}
+
+/// Send the request and read the response, without its size prefix.
+async fn exchange<S>(stream: &mut S, request: &[u8]) -> Result<Vec<u8>, QgsError>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let closed = |e: std::io::Error| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => {
+            QgsError::Malformed("connection closed before the whole response".into())
+        }
+        _ => QgsError::Io(e),
+    };
+
+    stream.write_all(request).await?;
+    stream.flush().await?;
+
+    let mut prefix = [0; PREFIX_SIZE];
+    stream.read_exact(&mut prefix).await.map_err(closed)?;
+    let size = u32::from_be_bytes(prefix) as usize;
+    if !(HEADER_SIZE..=MAX_MESSAGE_SIZE).contains(&size) {
+        return Err(QgsError::Malformed(format!("message size {size}")));
+    }
+
+    let mut message = vec![0; size];
+    stream.read_exact(&mut message).await.map_err(closed)?;
+    Ok(message)
+}
+
+/// Get the quote of the TD report from the QGS at `address`. The whole
+/// exchange, including the connection, must be done within
+/// `timeout_seconds`, `0` means no limit.
+pub async fn get_quote(
+    address: &str,
+    td_report: &[u8; TD_REPORT_SIZE],
+    timeout_seconds: u64,
+) -> Result<Vec<u8>, QgsError> {
+    let qgs = parse_address(address)?;
+    let request = encode_get_quote_request(td_report);
+    let connect_error = |source| QgsError::Connect {
+        address: address.to_string(),
+        source,
+    };
+    let get_quote = async {
+        match qgs {
+            QgsAddress::Vsock { cid, port } => {
+                let mut stream = tokio_vsock::VsockStream::connect(cid, port)
+                    .await
+                    .map_err(connect_error)?;
+                exchange(&mut stream, &request).await
+            }
+            QgsAddress::Unix(path) => {
+                let mut stream = tokio::net::UnixStream::connect(path)
+                    .await
+                    .map_err(connect_error)?;
+                exchange(&mut stream, &request).await
+            }
+        }
+    };
+
+    let response = match timeout_seconds {
+        0 => get_quote.await?,
+        seconds => tokio::time::timeout(Duration::from_secs(seconds), get_quote)
+            .await
+            .map_err(|_| QgsError::Timeout(seconds))??,
+    };
+    decode_get_quote_response(&response)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    /// Synthetic exchange, built by hand in the layout of `qgs_msg_lib`
+    /// rather than captured from a QGS, with the bytes 0 to 255 repeated as
+    /// the TD report. The response carries no key ID and a dummy quote of
+    /// 64 bytes. The messages built by [`response`] cover the other cases.
+    ///
+    /// The codec is written after the same layout, so these tests do not
+    /// show that a QGS takes the request. That needs an exchange captured
+    /// on a TDX host, which is not at hand yet.
+    const REQUEST: &[u8] = include_bytes!("../../test/tdx/qgs_get_quote_request.bin");
+    const RESPONSE: &[u8] = include_bytes!("../../test/tdx/qgs_get_quote_response.bin");
+
+    fn td_report() -> [u8; TD_REPORT_SIZE] {
+        std::array::from_fn(|i| i as u8)
+    }
+
+    fn quote() -> Vec<u8> {
+        let mut quote = vec![4, 0, 2, 0, 0x81, 0, 0, 0];
+        quote.resize(64, 0xa5);
+        quote
+    }
+
+    /// A response of the given header fields and body.
+    fn response(major_version: u16, type_: u32, error_code: u32, body: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend_from_slice(&major_version.to_le_bytes());
+        message.extend_from_slice(&0u16.to_le_bytes());
+        message.extend_from_slice(&type_.to_le_bytes());
+        message.extend_from_slice(&((HEADER_SIZE + body.len()) as u32).to_le_bytes());
+        message.extend_from_slice(&error_code.to_le_bytes());
+        message.extend_from_slice(body);
+        message
+    }
+
+    fn body(id: &[u8], quote: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&(id.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(quote.len() as u32).to_le_bytes());
+        body.extend_from_slice(id);
+        body.extend_from_slice(quote);
+        body
+    }
+
+    #[test]
+    fn encode_request() {
+        assert_eq!(encode_get_quote_request(&td_report()), REQUEST);
+    }
+
+    #[test]
+    fn decode_response() {
+        let (prefix, message) = RESPONSE.split_at(PREFIX_SIZE);
+        assert_eq!(
+            u32::from_be_bytes(prefix.try_into().unwrap()) as usize,
+            message.len()
+        );
+        assert_eq!(decode_get_quote_response(message).unwrap(), quote());
+
+        // The ID of the attestation key that the QGS picked is skipped
+        let message = response(1, GET_QUOTE_RESP, 0, &body(&[7; 16], &quote()));
+        assert_eq!(decode_get_quote_response(&message).unwrap(), quote());
+    }
+
+    #[rstest]
+    #[case(response(2, GET_QUOTE_RESP, 0, &body(&[], &[1])), "unsupported version 2.0")]
+    #[case(response(1, GET_QUOTE_REQ, 0, &body(&[], &[1])), "unexpected message type 0")]
+    #[case(response(1, GET_QUOTE_RESP, 0, &[0; 4]), "truncated body")]
+    #[case(response(1, GET_QUOTE_RESP, 0, &body(&[], &[])), "empty quote")]
+    #[case(RESPONSE[PREFIX_SIZE..20].to_vec(), "the header has size 88")]
+    #[case(RESPONSE[PREFIX_SIZE..10].to_vec(), "10 bytes are no message")]
+    fn malformed_response(#[case] message: Vec<u8>, #[case] error: &str) {
+        let e = decode_get_quote_response(&message).unwrap_err();
+        assert!(matches!(e, QgsError::Malformed(_)), "{e:?}");
+        assert!(e.to_string().contains(error), "{e}");
+    }
+
+    #[test]
+    fn inconsistent_sizes() {
+        // The size of the header does not match the message
+        let mut message = RESPONSE[PREFIX_SIZE..].to_vec();
+        message.pop();
+        let e = decode_get_quote_response(&message).unwrap_err();
+        assert!(e.to_string().contains("the header has size"), "{e}");
+
+        // The quote size does not match the body
+        let mut body = body(&[], &quote());
+        body[4] += 1;
+        let message = response(1, GET_QUOTE_RESP, 0, &body);
+        let e = decode_get_quote_response(&message).unwrap_err();
+        assert!(e.to_string().contains("a quote of 65 bytes"), "{e}");
+    }
+
+    #[test]
+    fn service_error() {
+        // QGS_MSG_ERROR_INVALID_PARAMETER
+        let message = response(1, GET_QUOTE_RESP, 0x0001_2003, &body(&[], &[]));
+        let e = decode_get_quote_response(&message).unwrap_err();
+        assert!(matches!(e, QgsError::Service(0x0001_2003)), "{e:?}");
+    }
+
+    #[tokio::test]
+    async fn exchange_framing() {
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        let qgs = tokio::spawn(async move {
+            let mut request = vec![0; REQUEST.len()];
+            server.read_exact(&mut request).await.unwrap();
+            server.write_all(RESPONSE).await.unwrap();
+            request
+        });
+
+        let message = exchange(&mut client, REQUEST).await.unwrap();
+        assert_eq!(message, &RESPONSE[PREFIX_SIZE..]);
+        assert_eq!(qgs.await.unwrap(), REQUEST);
+    }
+
+    #[rstest]
+    #[case(&RESPONSE[..2], "connection closed")]
+    #[case(&RESPONSE[..RESPONSE.len() - 1], "connection closed")]
+    #[case(&[0, 0, 0, 4], "message size 4")]
+    #[case(&[0xff, 0xff, 0xff, 0xff], "message size 4294967295")]
+    #[tokio::test]
+    async fn exchange_malformed(#[case] response: &'static [u8], #[case] error: &str) {
+        let (mut client, mut server) = tokio::io::duplex(4096);
+        tokio::spawn(async move {
+            let mut request = vec![0; REQUEST.len()];
+            server.read_exact(&mut request).await.unwrap();
+            // The connection is closed when the server is dropped
+            server.write_all(response).await.unwrap();
+        });
+
+        let e = exchange(&mut client, REQUEST).await.unwrap_err();
+        assert!(matches!(e, QgsError::Malformed(_)), "{e:?}");
+        assert!(e.to_string().contains(error), "{e}");
+    }
+
+    #[rstest]
+    #[case(DEFAULT_QGS_ADDRESS, Some(QgsAddress::Vsock { cid: 2, port: 4050 }))]
+    #[case("vsock://3:1", Some(QgsAddress::Vsock { cid: 3, port: 1 }))]
+    #[case("unix:///var/run/tdx-qgs/qgs.socket", Some(QgsAddress::Unix("/var/run/tdx-qgs/qgs.socket".into())))]
+    #[case("vsock://host:4050", None)]
+    #[case("vsock://2", None)]
+    #[case("unix://qgs.socket", None)]
+    #[case("tcp://2:4050", None)]
+    fn address(#[case] address: &str, #[case] expected: Option<QgsAddress>) {
+        assert_eq!(parse_address(address).ok(), expected);
+    }
+
+    #[tokio::test]
+    async fn unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("qgs.socket");
+        let listener = tokio::net::UnixListener::bind(&path).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; REQUEST.len()];
+            stream.read_exact(&mut request).await.unwrap();
+            stream.write_all(RESPONSE).await.unwrap();
+        });
+
+        let address = format!("unix://{}", path.display());
+        let quote = get_quote(&address, &td_report(), 5).await.unwrap();
+        assert_eq!(quote, self::quote());
+    }
+
+    #[tokio::test]
+    async fn timeout() {
+        // Nothing listens at the port, so this fails before the timeout or
+        // on it, but never hangs
+        let e = get_quote("vsock://1:4050", &td_report(), 1)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(e, QgsError::Connect { .. } | QgsError::Timeout(1)),
+            "{e:?}"
+        );
+    }
+}
@@ -0,0 +1,639 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Attester of a plain TPM 2.0, e.g. the vTPM of a VM that has no other TEE.
+//!
+//! The evidence is a quote of the attestation key (AK) over the selected PCR
+//! bank, see [`TpmEvidence`]. kbs-types 0.6 has no TEE type of a TPM, so
+//! [`crate::detect_tee_type`] cannot report it yet, and the attester is
+//! created with [`TpmAttester::new`] instead.
+
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{bail, Context as _, Result};
+use base64::Engine;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tss_esapi::abstraction::nv;
+use tss_esapi::abstraction::pcr;
+use tss_esapi::attributes::ObjectAttributesBuilder;
+use tss_esapi::constants::CapabilityType;
+use tss_esapi::handles::{KeyHandle, NvIndexTpmHandle, PcrHandle, PersistentTpmHandle, TpmHandle};
+use tss_esapi::interface_types::algorithm::{
+    HashingAlgorithm, PublicAlgorithm, RsaSchemeAlgorithm,
+};
+use tss_esapi::interface_types::key_bits::RsaKeyBits;
+use tss_esapi::interface_types::resource_handles::{Hierarchy, NvAuth};
+use tss_esapi::structures::{
+    Attest, CapabilityData, Data, Digest, DigestValues, PcrSelectionList, PcrSelectionListBuilder,
+    PcrSlot, PublicBuilder, PublicKeyRsa, PublicRsaParametersBuilder, RsaExponent, RsaScheme,
+    Signature, SignatureScheme,
+};
+use tss_esapi::tcti_ldr::TctiNameConf;
+use tss_esapi::traits::Marshall;
+
+use super::{Attester, AttesterCapabilities, AttesterError, HashAlgorithm, TPM_PCRS};
+
+/// Device of the TPM resource manager of the kernel.
+const TPM_DEVICE: &str = "/dev/tpmrm0";
+
+/// Persistent handle of the AK, as provisioned e.g. on Azure CVMs.
+const DEFAULT_AK_HANDLE: u32 = 0x8100_0003;
+
+/// NV index of the certificate of the AK, as provisioned e.g. on Azure CVMs.
+const DEFAULT_AK_CERT_INDEX: u32 = 0x01C1_01D0;
+
+/// Size in bytes of the qualifying data of a quote, i.e. of a SHA-512
+/// digest.
+const QUALIFYING_DATA_SIZE: usize = 64;
+
+/// Times a quote is taken when the PCRs are extended while quoting.
+const QUOTE_ATTEMPTS: usize = 3;
+
+pub fn detect_platform() -> bool {
+    Path::new(TPM_DEVICE).exists()
+}
+
+/// Options of the TPM attester.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TpmOptions {
+    /// TCTI to connect to the TPM with, in the format of the tpm2-tools,
+    /// e.g. `swtpm:host=localhost,port=2321` for the swtpm simulator.
+    pub tcti: String,
+
+    /// PCR bank that is quoted.
+    pub pcr_bank: HashAlgorithm,
+
+    /// PCRs that are quoted.
+    pub pcrs: Vec<u64>,
+
+    /// Persistent handle of the AK. If there is no key at the handle, a
+    /// primary restricted signing key of the endorsement hierarchy is used,
+    /// which is the same on every boot as it is derived from the EPS.
+    pub ak_handle: u32,
+
+    /// NV index of the certificate of the AK, which is put into the
+    /// evidence if it exists.
+    pub ak_cert_index: u32,
+}
+
+impl Default for TpmOptions {
+    fn default() -> Self {
+        Self {
+            tcti: format!("device:{TPM_DEVICE}"),
+            pcr_bank: HashAlgorithm::Sha256,
+            pcrs: TPM_PCRS.collect(),
+            ak_handle: DEFAULT_AK_HANDLE,
+            ak_cert_index: DEFAULT_AK_CERT_INDEX,
+        }
+    }
+}
+
+/// Evidence of the TPM attester, serialized as JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TpmEvidence {
+    /// Marshalled `TPMS_ATTEST` of the quote, base64 encoded. Its
+    /// `extraData` is the report data, and its `pcrDigest` the digest of
+    /// `pcrs`.
+    pub quote: String,
+
+    /// Marshalled `TPMT_SIGNATURE` of the AK over `quote`, base64 encoded.
+    pub signature: String,
+
+    /// Marshalled `TPMT_PUBLIC` of the AK, base64 encoded.
+    pub ak_public: String,
+
+    /// DER of the certificate of the AK, base64 encoded, if the TPM has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ak_cert: Option<String>,
+
+    /// PCR bank that is quoted.
+    pub pcr_bank: HashAlgorithm,
+
+    /// Values of the quoted PCRs, hex encoded, by PCR index.
+    pub pcrs: BTreeMap<u64, String>,
+}
+
+#[derive(Debug, Default)]
+pub struct TpmAttester {
+    options: TpmOptions,
+
+    /// The active PCR banks, read from the TPM once.
+    banks: OnceLock<Vec<HashAlgorithm>>,
+}
+
+impl TpmAttester {
+    pub fn new(options: TpmOptions) -> Self {
+        Self {
+            options,
+            banks: OnceLock::new(),
+        }
+    }
+
+    fn context(&self) -> Result<tss_esapi::Context> {
+        connect(&self.options.tcti)
+    }
+
+    /// The active PCR banks of the TPM. If they cannot be read, only the
+    /// SHA-256 bank is assumed, which every PC client TPM has, and they are
+    /// read again on the next call.
+    fn banks(&self) -> Vec<HashAlgorithm> {
+        if let Some(banks) = self.banks.get() {
+            return banks.clone();
+        }
+
+        match self.context().and_then(|mut ctx| active_banks(&mut ctx)) {
+            Ok(banks) => self.banks.get_or_init(|| banks).clone(),
+            Err(e) => {
+                warn!("TPM Attester: cannot read the active PCR banks, assume sha256: {e:#}");
+                vec![HashAlgorithm::Sha256]
+            }
+        }
+    }
+
+    /// The persistent AK if there is one, or the primary AK of the
+    /// endorsement hierarchy, and whether the AK is a transient object that
+    /// has to be flushed after use.
+    fn load_ak(&self, context: &mut tss_esapi::Context) -> Result<(KeyHandle, bool)> {
+        let handle = TpmHandle::Persistent(PersistentTpmHandle::new(self.options.ak_handle)?);
+        match context.tr_from_tpm_public(handle) {
+            Ok(ak) => return Ok((ak.into(), false)),
+            Err(e) => debug!(
+                "No AK at {:#x}, use the primary AK: {e}",
+                self.options.ak_handle
+            ),
+        }
+
+        let attributes = ObjectAttributesBuilder::new()
+            .with_fixed_tpm(true)
+            .with_fixed_parent(true)
+            .with_sensitive_data_origin(true)
+            .with_user_with_auth(true)
+            .with_restricted(true)
+            .with_sign_encrypt(true)
+            .build()?;
+        let parameters = PublicRsaParametersBuilder::new_restricted_signing_key(
+            RsaScheme::create(RsaSchemeAlgorithm::RsaSsa, Some(HashingAlgorithm::Sha256))?,
+            RsaKeyBits::Rsa2048,
+            RsaExponent::default(),
+        )
+        .build()?;
+        let public = PublicBuilder::new()
+            .with_public_algorithm(PublicAlgorithm::Rsa)
+            .with_name_hashing_algorithm(HashingAlgorithm::Sha256)
+            .with_object_attributes(attributes)
+            .with_rsa_parameters(parameters)
+            .with_rsa_unique_identifier(PublicKeyRsa::default())
+            .build()?;
+        let ak = context
+            .execute_with_nullauth_session(|ctx| {
+                ctx.create_primary(Hierarchy::Endorsement, public, None, None, None, None)
+            })
+            .context("create primary AK")?;
+        Ok((ak.key_handle, true))
+    }
+
+    /// Quote the PCRs with the AK, and read the quoted values. The PCRs are
+    /// read before and after the quote, and the quote is taken again if they
+    /// were extended in between, s.t. the values match the quote.
+    fn quote(
+        &self,
+        context: &mut tss_esapi::Context,
+        ak: KeyHandle,
+        report_data: &[u8],
+        selection: PcrSelectionList,
+    ) -> Result<(Attest, Signature, BTreeMap<u64, Vec<u8>>)> {
+        for _ in 0..QUOTE_ATTEMPTS {
+            let before = read_pcrs(context, self.options.pcr_bank, &self.options.pcrs)?;
+            let (quote, signature) = context
+                .execute_with_nullauth_session(|ctx| {
+                    ctx.quote(
+                        ak,
+                        Data::try_from(report_data.to_vec())?,
+                        SignatureScheme::Null,
+                        selection.clone(),
+                    )
+                })
+                .context("TPM Attester: quote generation failed")?;
+            let after = read_pcrs(context, self.options.pcr_bank, &self.options.pcrs)?;
+            if before == after {
+                return Ok((quote, signature, after));
+            }
+            debug!("TPM Attester: PCRs were extended during the quote, quote again");
+        }
+
+        bail!("TPM Attester: PCRs were extended during each of {QUOTE_ATTEMPTS} quotes")
+    }
+}
+
+/// Connect to the TPM with the given TCTI, see [`TpmOptions::tcti`].
+pub(crate) fn connect(tcti: &str) -> Result<tss_esapi::Context> {
+    let conf = TctiNameConf::from_str(tcti).with_context(|| format!("invalid TCTI `{tcti}`"))?;
+    tss_esapi::Context::new(conf).context("connect to TPM")
+}
+
+/// Read the whole NV index, `None` if it is not defined.
+pub(crate) fn read_nv_index(
+    context: &mut tss_esapi::Context,
+    index: u32,
+) -> Result<Option<Vec<u8>>> {
+    let handle = NvIndexTpmHandle::new(index)?;
+    let defined = nv::list(context)?
+        .iter()
+        .any(|(public, _)| public.index_handle() == handle);
+    if !defined {
+        return Ok(None);
+    }
+
+    let data = nv::read_full(context, NvAuth::Owner, handle)
+        .with_context(|| format!("read NV index {index:#x}"))?;
+    Ok(Some(data))
+}
+
+fn hashing_algorithm(algorithm: HashAlgorithm) -> HashingAlgorithm {
+    match algorithm {
+        HashAlgorithm::Sha256 => HashingAlgorithm::Sha256,
+        HashAlgorithm::Sha384 => HashingAlgorithm::Sha384,
+        HashAlgorithm::Sha512 => HashingAlgorithm::Sha512,
+    }
+}
+
+/// The algorithm of a PCR bank, `None` for banks that runtime measurements
+/// cannot use, e.g. SHA-1 or SM3.
+fn hash_algorithm(algorithm: HashingAlgorithm) -> Option<HashAlgorithm> {
+    match algorithm {
+        HashingAlgorithm::Sha256 => Some(HashAlgorithm::Sha256),
+        HashingAlgorithm::Sha384 => Some(HashAlgorithm::Sha384),
+        HashingAlgorithm::Sha512 => Some(HashAlgorithm::Sha512),
+        _ => None,
+    }
+}
+
+/// The PCR banks that are allocated in the TPM and have PCRs assigned.
+fn active_banks(context: &mut tss_esapi::Context) -> Result<Vec<HashAlgorithm>> {
+    let (data, _) = context
+        .get_capability(CapabilityType::AssignedPcr, 0, 1)
+        .context("read PCR allocation")?;
+    let CapabilityData::AssignedPcr(selections) = data else {
+        bail!("unexpected capability data of the PCR allocation");
+    };
+
+    Ok(selections
+        .get_selections()
+        .iter()
+        .filter(|selection| !selection.is_empty())
+        .filter_map(|selection| hash_algorithm(selection.hashing_algorithm()))
+        .collect())
+}
+
+fn pcr_slot(register_index: u64) -> Result<PcrSlot> {
+    if !TPM_PCRS.contains(&register_index) {
+        bail!("Invalid PCR index: {register_index}");
+    }
+    Ok(PcrSlot::try_from(1u32 << register_index)?)
+}
+
+/// Read the given PCRs of the bank, by index.
+fn read_pcrs(
+    context: &mut tss_esapi::Context,
+    bank: HashAlgorithm,
+    indexes: &[u64],
+) -> Result<BTreeMap<u64, Vec<u8>>> {
+    let slots = indexes
+        .iter()
+        .map(|index| pcr_slot(*index))
+        .collect::<Result<Vec<_>>>()?;
+    let selection = PcrSelectionListBuilder::new()
+        .with_selection(hashing_algorithm(bank), &slots)
+        .build()?;
+    let data = pcr::read_all(context, selection).context("read PCRs")?;
+    let pcr_bank = data
+        .pcr_bank(hashing_algorithm(bank))
+        .with_context(|| format!("PCR bank {bank} is missing"))?;
+
+    indexes
+        .iter()
+        .zip(slots)
+        .map(|(index, slot)| {
+            let digest = pcr_bank
+                .get_digest(slot)
+                .with_context(|| format!("PCR {index} is missing"))?;
+            Ok((*index, digest.to_vec()))
+        })
+        .collect()
+}
+
+#[async_trait::async_trait]
+impl Attester for TpmAttester {
+    async fn get_evidence(&self, report_data: Vec<u8>) -> Result<String> {
+        if report_data.len() > QUALIFYING_DATA_SIZE {
+            bail!("TPM Attester: Report data must be no more than {QUALIFYING_DATA_SIZE} bytes");
+        }
+
+        let slots = self
+            .options
+            .pcrs
+            .iter()
+            .map(|index| pcr_slot(*index))
+            .collect::<Result<Vec<_>>>()?;
+        let selection = PcrSelectionListBuilder::new()
+            .with_selection(hashing_algorithm(self.options.pcr_bank), &slots)
+            .build()?;
+
+        let mut context = self.context()?;
+        let (ak, transient) = self.load_ak(&mut context)?;
+        let quoted = self
+            .quote(&mut context, ak, &report_data, selection)
+            .and_then(|(quote, signature, pcrs)| {
+                let (ak_public, _, _) = context.read_public(ak)?;
+                Ok((quote, signature, pcrs, ak_public))
+            });
+        // The primary AK is created again on the next quote
+        if transient {
+            if let Err(e) = context.flush_context(ak.into()) {
+                warn!("TPM Attester: cannot flush the primary AK: {e}");
+            }
+        }
+        let (quote, signature, pcrs, ak_public) = quoted?;
+        let ak_cert =
+            read_nv_index(&mut context, self.options.ak_cert_index).context("read AK cert")?;
+
+        let engine = base64::engine::general_purpose::STANDARD;
+        let evidence = TpmEvidence {
+            quote: engine.encode(quote.marshall()?),
+            signature: engine.encode(signature.marshall()?),
+            ak_public: engine.encode(ak_public.marshall()?),
+            ak_cert: ak_cert.map(|cert| engine.encode(cert)),
+            pcr_bank: self.options.pcr_bank,
+            pcrs: pcrs
+                .into_iter()
+                .map(|(index, value)| (index, hex::encode(value)))
+                .collect(),
+        };
+
+        Ok(serde_json::to_string(&evidence)?)
+    }
+
+    fn report_data_len(&self) -> Option<usize> {
+        Some(QUALIFYING_DATA_SIZE)
+    }
+
+    fn runtime_measurement_registers(&self) -> Option<RangeInclusive<u64>> {
+        Some(TPM_PCRS)
+    }
+
+    fn capabilities(&self) -> AttesterCapabilities {
+        AttesterCapabilities {
+            supports_runtime_measurement: true,
+            report_data_len: QUALIFYING_DATA_SIZE,
+            supported_hash_algorithms: self.banks(),
+            init_data_supported: false,
+        }
+    }
+
+    async fn get_runtime_measurement(
+        &self,
+        register_index: u64,
+        algorithm: HashAlgorithm,
+    ) -> Result<Vec<u8>> {
+        pcr_slot(register_index)?;
+        if !self.banks().contains(&algorithm) {
+            return Err(AttesterError::Unsupported("reading PCRs of an inactive bank").into());
+        }
+
+        let mut context = self.context()?;
+        let mut pcrs = read_pcrs(&mut context, algorithm, &[register_index])?;
+        Ok(pcrs.remove(&register_index).unwrap_or_default())
+    }
+
+    async fn extend_runtime_measurement(
+        &self,
+        event_digest: Vec<u8>,
+        register_index: u64,
+    ) -> Result<()> {
+        // The bank is the one of the algorithm of the digest
+        let Some(bank) = [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ]
+        .into_iter()
+        .find(|bank| bank.digest_len() == event_digest.len()) else {
+            bail!("no PCR bank of {} byte digests", event_digest.len());
+        };
+        pcr_slot(register_index)?;
+        if !self.banks().contains(&bank) {
+            bail!("PCR bank {bank} is not active");
+        }
+
+        info!(
+            "Extending PCR {} with {}",
+            register_index,
+            hex::encode(&event_digest)
+        );
+        let pcr = PcrHandle::try_from(register_index as u32)?;
+        let mut digests = DigestValues::new();
+        digests.set(hashing_algorithm(bank), Digest::try_from(event_digest)?);
+
+        let mut context = self.context()?;
+        context
+            .execute_with_nullauth_session(|ctx| ctx.pcr_extend(pcr, digests))
+            .with_context(|| format!("extend PCR {register_index}"))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! The tests marked `#[ignore]` need the swtpm simulator, started with
+    //!
+    //! ```sh
+    //! swtpm socket --tpm2 --server type=tcp,port=2321 --ctrl type=tcp,port=2322 \
+    //!     --tpmstate dir=/tmp/swtpm --flags not-need-init,startup-clear
+    //! ```
+
+    use sha2::{Digest as _, Sha256};
+    use tss_esapi::traits::UnMarshall;
+
+    use super::*;
+    use crate::InitdataResult;
+
+    fn swtpm_attester() -> TpmAttester {
+        TpmAttester::new(TpmOptions {
+            tcti: "swtpm:host=localhost,port=2321".into(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn evidence_schema() {
+        let evidence = TpmEvidence {
+            quote: "cXVvdGU=".into(),
+            signature: "c2lnbmF0dXJl".into(),
+            ak_public: "cHVibGlj".into(),
+            ak_cert: None,
+            pcr_bank: HashAlgorithm::Sha256,
+            pcrs: BTreeMap::from([(0, "00".repeat(32))]),
+        };
+        let json = serde_json::to_value(&evidence).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "quote": "cXVvdGU=",
+                "signature": "c2lnbmF0dXJl",
+                "ak_public": "cHVibGlj",
+                "pcr_bank": "sha256",
+                "pcrs": { "0": "00".repeat(32) },
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<TpmEvidence>(json).unwrap(),
+            evidence
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_input() {
+        let attester = swtpm_attester();
+        attester.get_evidence(vec![0; 65]).await.unwrap_err();
+        attester
+            .extend_runtime_measurement(vec![0; 32], 24)
+            .await
+            .unwrap_err();
+        // A SHA-1 digest
+        attester
+            .extend_runtime_measurement(vec![0; 20], 16)
+            .await
+            .unwrap_err();
+    }
+
+    #[test]
+    fn bank_algorithms() {
+        assert_eq!(
+            hash_algorithm(HashingAlgorithm::Sha384),
+            Some(HashAlgorithm::Sha384)
+        );
+        assert_eq!(hash_algorithm(HashingAlgorithm::Sha1), None);
+        assert_eq!(hash_algorithm(HashingAlgorithm::Sm3_256), None);
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha384,
+            HashAlgorithm::Sha512,
+        ] {
+            assert_eq!(
+                hash_algorithm(hashing_algorithm(algorithm)),
+                Some(algorithm)
+            );
+        }
+    }
+
+    #[test]
+    fn capabilities_without_tpm() {
+        let attester = TpmAttester::new(TpmOptions {
+            tcti: "device:/nonexistent/tpm".into(),
+            ..Default::default()
+        });
+        assert_eq!(
+            attester.capabilities(),
+            AttesterCapabilities {
+                supports_runtime_measurement: true,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![HashAlgorithm::Sha256],
+                init_data_supported: false,
+            }
+        );
+        // The fallback is not cached
+        assert!(attester.banks.get().is_none());
+    }
+
+    #[tokio::test]
+    async fn init_data_unsupported() {
+        let result = swtpm_attester().check_init_data(b"initdata").await.unwrap();
+        assert!(matches!(result, InitdataResult::Unsupported));
+    }
+
+    #[ignore]
+    #[test]
+    fn capabilities() {
+        let capabilities = swtpm_attester().capabilities();
+        assert!(capabilities.supports_runtime_measurement);
+        assert_eq!(capabilities.report_data_len, 64);
+        assert!(capabilities
+            .supported_hash_algorithms
+            .contains(&HashAlgorithm::Sha256));
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn get_evidence() {
+        let attester = swtpm_attester();
+        let report_data = vec![7; 64];
+
+        let evidence = attester.get_evidence(report_data.clone()).await.unwrap();
+        let evidence: TpmEvidence = serde_json::from_str(&evidence).unwrap();
+        let engine = base64::engine::general_purpose::STANDARD;
+        let quote = Attest::unmarshall(&engine.decode(&evidence.quote).unwrap()).unwrap();
+        assert_eq!(quote.extra_data().value(), report_data);
+        assert_eq!(evidence.pcr_bank, HashAlgorithm::Sha256);
+        assert_eq!(evidence.pcrs.len(), 24);
+        assert_eq!(evidence.ak_cert, None);
+    }
+
+    /// Number of the transient objects loaded in the TPM.
+    fn transient_objects(attester: &TpmAttester) -> usize {
+        const TRANSIENT_FIRST: u32 = 0x8000_0000;
+        let mut context = attester.context().unwrap();
+        let (data, _) = context
+            .get_capability(CapabilityType::Handles, TRANSIENT_FIRST, 64)
+            .unwrap();
+        let CapabilityData::Handles(handles) = data else {
+            panic!("unexpected capability data of the handles");
+        };
+        handles.len()
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn primary_ak_is_flushed() {
+        let attester = swtpm_attester();
+        let objects = transient_objects(&attester);
+        for _ in 0..3 {
+            attester.get_evidence(vec![7; 64]).await.unwrap();
+        }
+        assert_eq!(transient_objects(&attester), objects);
+    }
+
+    #[ignore]
+    #[tokio::test]
+    async fn extend_runtime_measurement() {
+        let attester = swtpm_attester();
+        let digest = Sha256::digest(b"event").to_vec();
+
+        let before = attester
+            .get_runtime_measurement(16, HashAlgorithm::Sha256)
+            .await
+            .unwrap();
+        attester
+            .extend_runtime_measurement(digest.clone(), 16)
+            .await
+            .unwrap();
+        let after = attester
+            .get_runtime_measurement(16, HashAlgorithm::Sha256)
+            .await
+            .unwrap();
+
+        let expected = Sha256::new()
+            .chain_update(before)
+            .chain_update(digest)
+            .finalize();
+        assert_eq!(after, expected.to_vec());
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Attester of the vendor neutral TSM report ABI of Linux under ConfigFS,
+//! which works for TDX and CCA guests without the vendor libraries. It is
+//! the fallback of the TEEs whose vendor attester is not built in or whose
+//! device is unavailable, and makes evidence of the same shape as the
+//! vendor attester, s.t. the verifier of the TEE takes it.
+//!
+//! SNP is left to its vendor attester, although the ABI has reports of it:
+//! its evidence has the report as parsed by the sev crate, which is not
+//! made here.
+
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use kbs_types::Tee;
+use serde::{Deserialize, Serialize};
+
+use super::tsm_report::*;
+use super::Attester;
+use crate::detect::{Host, Platform};
+
+/// Size in bytes of the report data, i.e. of `inblob`.
+const TSM_REPORT_DATA_SIZE: usize = 64;
+
+/// The provider of the TSM reports that the given TEE is attested with, if
+/// the TSM attester makes its evidence.
+pub fn provider_for_tee(tee: Tee) -> Option<TsmReportProvider> {
+    match tee {
+        Tee::Tdx => Some(TsmReportProvider::Tdx),
+        Tee::Cca => Some(TsmReportProvider::Cca),
+        _ => None,
+    }
+}
+
+/// Whether the TSM report ABI provides reports of the given TEE, and the
+/// TSM attester makes its evidence.
+pub fn detect_platform(tee: Tee) -> bool {
+    detect_platform_on(&Host, tee)
+}
+
+pub(crate) fn detect_platform_on(platform: &impl Platform, tee: Tee) -> bool {
+    provider_for_tee(tee).is_some_and(|provider| {
+        TsmReportPath::new_in(&platform.path(TSM_REPORT_PATH), provider).is_ok()
+    })
+}
+
+/// Evidence of TDX, as the TDX attester makes it without eventlogs, which
+/// the TSM report does not have.
+#[derive(Serialize, Deserialize)]
+struct TdxEvidence {
+    /// Base64 encoded TD quote, the `outblob` of the report.
+    quote: String,
+}
+
+/// Evidence of CCA, as the CCA attester makes it.
+#[derive(Serialize, Deserialize)]
+struct CcaEvidence {
+    /// CCA token, the `outblob` of the report.
+    token: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct TsmAttester {
+    provider: TsmReportProvider,
+    root: PathBuf,
+}
+
+impl TsmAttester {
+    pub fn new(provider: TsmReportProvider) -> Self {
+        Self {
+            provider,
+            root: TSM_REPORT_PATH.into(),
+        }
+    }
+}
+
+/// Request the report of the entry and make the evidence of the TEE of the
+/// provider out of it, serialized as JSON.
+fn evidence(
+    report: &TsmReportPath,
+    provider: TsmReportProvider,
+    report_data: Vec<u8>,
+) -> Result<String> {
+    let outblob = |data| {
+        report
+            .attestation_report(data)
+            .context("TSM Attester: report generation failed")
+    };
+
+    let evidence = match provider {
+        TsmReportProvider::Tdx => {
+            let quote = outblob(TsmReportData::Tdx(report_data))?;
+            let quote = base64::engine::general_purpose::STANDARD.encode(quote);
+            serde_json::to_string(&TdxEvidence { quote })
+        }
+        TsmReportProvider::Cca => {
+            let token = outblob(TsmReportData::Cca(report_data))?;
+            serde_json::to_string(&CcaEvidence { token })
+        }
+        TsmReportProvider::Sev => bail!("TSM Attester: no evidence of SNP is made of TSM reports"),
+    };
+    evidence.context("Serialize TSM evidence failed")
+}
+
+#[async_trait::async_trait]
+impl Attester for TsmAttester {
+    async fn get_evidence(&self, mut report_data: Vec<u8>) -> Result<String> {
+        if report_data.len() > TSM_REPORT_DATA_SIZE {
+            bail!("TSM Attester: Report data must be no more than {TSM_REPORT_DATA_SIZE} bytes");
+        }
+        report_data.resize(TSM_REPORT_DATA_SIZE, 0);
+
+        // The entry is removed when it is dropped, also on errors
+        let report = TsmReportPath::new_in(&self.root, self.provider)
+            .context("TSM Attester: create report entry")?;
+        evidence(&report, self.provider, report_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    /// An entry as ConfigFS populates it, with the outblob of the report.
+    fn report_entry(dir: &Path, provider: &str) -> TsmReportPath {
+        fs::write(dir.join("provider"), format!("{provider}\n")).unwrap();
+        fs::write(dir.join("generation"), "1\n").unwrap();
+        fs::write(dir.join("outblob"), b"report").unwrap();
+        TsmReportPath::from_path(dir.into())
+    }
+
+    /// The evidence has the shape of the one of the vendor attester.
+    #[rstest]
+    #[case(TsmReportProvider::Tdx, json!({"quote": "cmVwb3J0"}))]
+    #[case(TsmReportProvider::Cca, json!({"token": b"report"}))]
+    fn tsm_evidence(#[case] provider: TsmReportProvider, #[case] expected: serde_json::Value) {
+        let dir = tempfile::tempdir().unwrap();
+        let report = report_entry(dir.path(), provider.name());
+
+        let evidence = evidence(&report, provider, vec![7; 64]).unwrap();
+        let evidence: serde_json::Value = serde_json::from_str(&evidence).unwrap();
+        assert_eq!(evidence, expected);
+        assert_eq!(fs::read(dir.path().join("inblob")).unwrap(), vec![7; 64]);
+    }
+
+    #[test]
+    fn no_snp_evidence() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = report_entry(dir.path(), "sev_guest");
+
+        evidence(&report, TsmReportProvider::Sev, vec![0; 64]).unwrap_err();
+        assert!(!dir.path().join("inblob").exists());
+        assert_eq!(provider_for_tee(Tee::Snp), None);
+    }
+
+    #[test]
+    fn tsm_evidence_generation_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = report_entry(dir.path(), "tdx_guest");
+        fs::write(dir.path().join("generation"), "2\n").unwrap();
+
+        evidence(&report, TsmReportProvider::Tdx, vec![0; 64]).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn entry_is_removed_on_error() {
+        let root = tempfile::tempdir().unwrap();
+        let attester = TsmAttester {
+            provider: TsmReportProvider::Tdx,
+            root: root.path().into(),
+        };
+
+        // A plain directory has no `provider`, unlike an entry of ConfigFS
+        attester.get_evidence(vec![0; 64]).await.unwrap_err();
+        assert_eq!(fs::read_dir(root.path()).unwrap().count(), 0);
+
+        attester.get_evidence(vec![0; 65]).await.unwrap_err();
+    }
+
+    #[rstest]
+    #[case(TsmReportProvider::Tdx)]
+    #[case(TsmReportProvider::Sev)]
+    #[case(TsmReportProvider::Cca)]
+    fn capabilities(#[case] provider: TsmReportProvider) {
+        assert_eq!(
+            TsmAttester::new(provider).capabilities(),
+            crate::AttesterCapabilities {
+                supports_runtime_measurement: false,
+                report_data_len: 64,
+                supported_hash_algorithms: vec![],
+                init_data_supported: false,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn no_tsm_reports() {
+        let attester = TsmAttester {
+            provider: TsmReportProvider::Sev,
+            root: "/nonexistent/tsm/report".into(),
+        };
+        let e = attester.get_evidence(vec![0; 64]).await.unwrap_err();
+        assert!(matches!(
+            e.downcast_ref::<TsmReportError>(),
+            Some(TsmReportError::NoTsmReports)
+        ));
+    }
+}
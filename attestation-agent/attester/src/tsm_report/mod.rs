@@ -9,7 +9,7 @@ use strum::EnumString;
 use tempfile::tempdir_in;
 use thiserror::Error;
 
-const TSM_REPORT_PATH: &str = "/sys/kernel/config/tsm/report";
+pub(crate) const TSM_REPORT_PATH: &str = "/sys/kernel/config/tsm/report";
 
 #[derive(Error, Debug)]
 pub enum TsmReportError {
@@ -31,17 +31,31 @@ pub enum TsmReportError {
     InblobLen,
 }
 
-#[derive(PartialEq, Debug, EnumString)]
+#[derive(Clone, Copy, PartialEq, Debug, EnumString)]
 pub enum TsmReportProvider {
     #[strum(serialize = "tdx_guest\n")]
     Tdx,
     #[strum(serialize = "sev_guest\n")]
     Sev,
+    #[strum(serialize = "arm_cca_guest\n")]
+    Cca,
+}
+
+impl TsmReportProvider {
+    /// Name of the provider, as in the `provider` attribute.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Tdx => "tdx_guest",
+            Self::Sev => "sev_guest",
+            Self::Cca => "arm_cca_guest",
+        }
+    }
 }
 
 pub enum TsmReportData {
     Tdx(Vec<u8>),
     Sev(u8, Vec<u8>),
+    Cca(Vec<u8>),
 }
 
 /// TsmReportPath instance represents a unique path on ConfigFS
@@ -61,11 +75,18 @@ impl Drop for TsmReportPath {
 
 impl TsmReportPath {
     pub fn new(wanted: TsmReportProvider) -> Result<Self, TsmReportError> {
-        if !Path::new(TSM_REPORT_PATH).exists() {
+        Self::new_in(Path::new(TSM_REPORT_PATH), wanted)
+    }
+
+    /// Create a report entry under the given root rather than the one of
+    /// ConfigFS. The entry gets a unique name, s.t. concurrent requests do
+    /// not share one.
+    pub fn new_in(root: &Path, wanted: TsmReportProvider) -> Result<Self, TsmReportError> {
+        if !root.exists() {
             return Err(TsmReportError::NoTsmReports);
         }
 
-        let p = tempdir_in(TSM_REPORT_PATH).map_err(TsmReportError::Open)?;
+        let p = tempdir_in(root).map_err(TsmReportError::Open)?;
 
         // Remove the Drop set by tempdir_in() since it errors on ConfigFS
         // and leaks the created path. We implement our own Drop that removes the
@@ -79,6 +100,13 @@ impl TsmReportPath {
 
         Ok(Self { path })
     }
+
+    /// A report entry at the given path, which is populated by the test.
+    #[cfg(test)]
+    pub(crate) fn from_path(path: PathBuf) -> Self {
+        Self { path }
+    }
+
     pub fn attestation_report(
         &self,
         provider_data: TsmReportData,
@@ -86,10 +114,10 @@ impl TsmReportPath {
         let report_path = self.path.as_path();
 
         let report_data = match provider_data {
-            TsmReportData::Tdx(inblob) => inblob,
+            TsmReportData::Tdx(inblob) | TsmReportData::Cca(inblob) => inblob,
             TsmReportData::Sev(privlevel, inblob) => {
                 // TODO: untested
-                std::fs::write(report_path.join("privlevel"), vec![privlevel])
+                std::fs::write(report_path.join("privlevel"), privlevel.to_string())
                     .map_err(|e| TsmReportError::Access("privlevel", e))?;
                 inblob
             }
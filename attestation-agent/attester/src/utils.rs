@@ -3,6 +3,10 @@
 // SPDX-License-Identifier: Apache-2.0
 //
 
+use anyhow::{anyhow, Result};
+use hkdf::Hkdf;
+use sha2::Sha256;
+
 pub fn pad<const T: usize>(input: &[u8]) -> [u8; T] {
     let mut output = [0; T];
     let len = input.len();
@@ -13,3 +17,14 @@ pub fn pad<const T: usize>(input: &[u8]) -> [u8; T] {
     }
     output
 }
+
+/// Derive a key of `length` bytes from the given key material with
+/// HKDF-SHA256. The `context` is used as the HKDF info, s.t. different
+/// contexts derive different keys from the same key material.
+pub fn derive_key(key_material: &[u8], context: &[u8], length: usize) -> Result<Vec<u8>> {
+    let mut key = vec![0; length];
+    Hkdf::<Sha256>::new(None, key_material)
+        .expand(context, &mut key)
+        .map_err(|_| anyhow!("derived key length {length} is too long"))?;
+    Ok(key)
+}
@@ -0,0 +1,52 @@
+// Copyright (c) 2024 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha384, Sha512};
+
+/// Hash algorithms used to calculate runtime/init data binding
+#[derive(
+    Deserialize,
+    Serialize,
+    Clone,
+    Debug,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    strum::Display,
+    strum::EnumString,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum HashAlgorithm {
+    Sha256,
+    #[default]
+    Sha384,
+    Sha512,
+}
+
+fn hash_reportdata<D: Digest>(material: &[u8]) -> Vec<u8> {
+    D::new().chain_update(material).finalize().to_vec()
+}
+
+impl HashAlgorithm {
+    pub fn digest(&self, material: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha256 => hash_reportdata::<Sha256>(material),
+            HashAlgorithm::Sha384 => hash_reportdata::<Sha384>(material),
+            HashAlgorithm::Sha512 => hash_reportdata::<Sha512>(material),
+        }
+    }
+
+    /// Size in bytes of the digests of the algorithm.
+    pub fn digest_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha256 => Sha256::output_size(),
+            HashAlgorithm::Sha384 => Sha384::output_size(),
+            HashAlgorithm::Sha512 => Sha512::output_size(),
+        }
+    }
+}
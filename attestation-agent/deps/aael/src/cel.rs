@@ -0,0 +1,251 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Records of the TCG Canonical Event Log in its JSON encoding (CEL-JSON),
+//! which AA writes with `eventlog_config.format = "cel"`. The file holds one
+//! record per line, s.t. it is appended to like the AAEL text format.
+//!
+//! Each AAEL entry is a record of the `pcclient_std` content type, i.e. an
+//! event of the TCG PC Client Platform Firmware Profile like those of the
+//! firmware eventlog. Its event data is the entry, and its digest is the
+//! digest of the event data, which is the one extended into the register.
+//! The entries that are not extended, i.e. the `CONTINUE` entry, are
+//! `EV_NO_ACTION` events without digests. A verifier replays the records
+//! like the firmware events, and checks each digest against the event data.
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{is_header, EntryMetadata, HashAlgorithm, CONTINUE_ENTRY_PREFIX};
+
+/// Content type of the records of TCG PC Client events.
+pub const PCCLIENT_STD_CONTENT_TYPE: &str = "pcclient_std";
+
+/// Event type of the events that are not extended, e.g. the Spec ID event
+/// of the firmware eventlog.
+pub const EV_NO_ACTION: u32 = 0x3;
+
+/// Event type of the entries that are extended. The event data of `EV_IPL`
+/// is a string measured by the software after the firmware, as with the
+/// commands of a boot loader.
+pub const EV_IPL: u32 = 0xd;
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Record {
+    /// Index of the record in the file, from 0.
+    pub recnum: u64,
+
+    /// Register the digests are extended into, in PCR numbering. Entries
+    /// that are not extended, i.e. the `CONTINUE` entry, have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pcr: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub digests: Vec<Digest>,
+
+    pub content_type: String,
+
+    pub content: Content,
+
+    /// Not part of the digest, see [`EntryMetadata`]. This is not a field
+    /// of CEL-JSON, and is ignored by other CEL parsers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<EntryMetadata>,
+}
+
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Digest {
+    #[serde(rename = "hashAlg")]
+    pub hash_alg: HashAlgorithm,
+
+    /// Hex encoded digest
+    pub digest: String,
+}
+
+/// The content of a `pcclient_std` record.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct Content {
+    /// [`EV_IPL`], or [`EV_NO_ACTION`] for entries that are not extended.
+    pub event_type: u32,
+
+    /// Base64 encoded AAEL entry, e.g. of `domain operation content`
+    pub event_data: String,
+}
+
+impl Record {
+    /// The record of the entry, with the digest extended into the register
+    /// of `pcr` if it is extended.
+    pub fn new(recnum: u64, entry: &str, extended: Option<(u64, HashAlgorithm, &[u8])>) -> Self {
+        let (pcr, digests, event_type) = match extended {
+            Some((pcr, hash_alg, digest)) => (
+                Some(pcr),
+                vec![Digest {
+                    hash_alg,
+                    digest: hex::encode(digest),
+                }],
+                EV_IPL,
+            ),
+            None => (None, Vec::new(), EV_NO_ACTION),
+        };
+
+        Self {
+            recnum,
+            pcr,
+            digests,
+            content_type: PCCLIENT_STD_CONTENT_TYPE.to_string(),
+            content: Content {
+                event_type,
+                event_data: STANDARD.encode(entry),
+            },
+            metadata: None,
+        }
+    }
+
+    pub fn parse(line: &str) -> Result<Self> {
+        serde_json::from_str(line).context("malformed CEL record")
+    }
+
+    pub fn to_line(&self) -> String {
+        serde_json::to_string(self).expect("a CEL record serializes to JSON")
+    }
+
+    /// The AAEL entry in the event data of the record, without checking
+    /// its digest.
+    pub fn entry(&self) -> Result<String> {
+        let recnum = self.recnum;
+        if self.content_type != PCCLIENT_STD_CONTENT_TYPE {
+            bail!(
+                "CEL record {recnum} has content type `{}` rather than \
+                 `{PCCLIENT_STD_CONTENT_TYPE}`",
+                self.content_type
+            );
+        }
+
+        let data = STANDARD
+            .decode(&self.content.event_data)
+            .with_context(|| format!("event data of CEL record {recnum} is not base64"))?;
+        String::from_utf8(data)
+            .with_context(|| format!("event data of CEL record {recnum} is not an AAEL entry"))
+    }
+
+    /// The AAEL entry of the record, after checking that its digest of
+    /// `algorithm` is the digest of the entry. A record of an entry extended
+    /// with another algorithm, e.g. before `eventlog_algorithm` was changed,
+    /// has its digest checked instead.
+    pub fn into_entry(self, algorithm: HashAlgorithm) -> Result<String> {
+        let recnum = self.recnum;
+        let entry = self.entry()?;
+        if self.content.event_type == EV_NO_ACTION {
+            if !self.digests.is_empty() || !entry.starts_with(CONTINUE_ENTRY_PREFIX) {
+                bail!("CEL record {recnum} is an EV_NO_ACTION event of an extended entry");
+            }
+            return Ok(entry);
+        }
+        if self.content.event_type != EV_IPL {
+            bail!(
+                "CEL record {recnum} has event type {:#x} rather than EV_IPL",
+                self.content.event_type
+            );
+        }
+
+        let Some(digest) = self
+            .digests
+            .iter()
+            .find(|d| d.hash_alg == algorithm)
+            .or(self.digests.first())
+        else {
+            bail!("CEL record {recnum} has no digest");
+        };
+        let algorithm = digest.hash_alg;
+        if digest.digest != hex::encode(algorithm.digest(entry.as_bytes())) {
+            bail!("the {algorithm} digest of CEL record {recnum} does not match its entry");
+        }
+
+        Ok(entry)
+    }
+}
+
+/// Convert an AAEL eventlog into CEL-JSON records, one per line. The AAEL
+/// does not tell the register of each entry, so the entries are taken as
+/// extended into `pcr`. The [`crate::AaelHeader`] is not an entry, so it is
+/// not converted.
+pub fn aael_to_cel(aael: &str, algorithm: HashAlgorithm, pcr: u64) -> String {
+    let mut lines = aael.lines().peekable();
+    lines.next_if(|line| is_header(line));
+    lines
+        .enumerate()
+        .map(|(recnum, entry)| {
+            let digest = algorithm.digest(entry.as_bytes());
+            let extended = match entry.starts_with(CONTINUE_ENTRY_PREFIX) {
+                true => None,
+                false => Some((pcr, algorithm, digest.as_slice())),
+            };
+            Record::new(recnum as u64, entry, extended).to_line() + "\n"
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{aael_to_cel, Record};
+    use crate::HashAlgorithm;
+
+    const AAEL: &str = include_str!("../test/aael.log");
+
+    /// The CEL-JSON records of `aael.log`, written by `test/cel.py`
+    /// independently of this module.
+    const CEL: &str = include_str!("../test/cel.jsonl");
+
+    #[test]
+    fn convert_golden_aael() {
+        assert_eq!(aael_to_cel(AAEL, HashAlgorithm::Sha384, 17), CEL);
+
+        let entries: Vec<String> = CEL
+            .lines()
+            .map(|line| {
+                Record::parse(line)
+                    .unwrap()
+                    .into_entry(HashAlgorithm::Sha384)
+                    .unwrap()
+            })
+            .collect();
+        assert_eq!(entries, AAEL.lines().collect::<Vec<_>>());
+        assert_eq!(crate::parse_entries(CEL).unwrap(), entries);
+    }
+
+    #[test]
+    fn continue_entry_is_not_extended() {
+        let cel = aael_to_cel("CONTINUE sha384/00 eventlog.1\n", HashAlgorithm::Sha384, 17);
+        let record = Record::parse(cel.trim_end()).unwrap();
+        assert_eq!((record.pcr, record.digests.len()), (None, 0));
+        assert_eq!(record.content.event_type, super::EV_NO_ACTION);
+        assert!(record.into_entry(HashAlgorithm::Sha384).is_ok());
+    }
+
+    #[rstest]
+    // `domain operation content` -> `domain operation forged`
+    #[case(
+        r#""event_data":"ZG9tYWluIG9wZXJhdGlvbiBjb250ZW50""#,
+        r#""event_data":"ZG9tYWluIG9wZXJhdGlvbiBmb3JnZWQ=""#
+    )]
+    #[case(
+        r#""event_data":"ZG9tYWluIG9wZXJhdGlvbiBjb250ZW50""#,
+        r#""event_data":"*""#
+    )]
+    #[case(r#""hashAlg":"sha384""#, r#""hashAlg":"sha256""#)]
+    #[case(r#""pcr":17,"digests""#, r#""pcr":17,"_digests""#)]
+    #[case(r#""event_type":13"#, r#""event_type":3"#)]
+    #[case(r#""event_type":13"#, r#""event_type":6"#)]
+    #[case(r#""content_type":"pcclient_std""#, r#""content_type":"ima_template""#)]
+    fn tampered_records_are_rejected(#[case] from: &str, #[case] to: &str) {
+        let line = CEL.lines().nth(2).unwrap();
+        assert!(line.contains(from), "{line}");
+        let record = Record::parse(&line.replace(from, to)).unwrap();
+        assert!(record.into_entry(HashAlgorithm::Sha384).is_err());
+    }
+}
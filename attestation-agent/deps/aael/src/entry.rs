@@ -0,0 +1,404 @@
+// Copyright (c) 2024 Alibaba Cloud
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::{borrow::Cow, fmt::Display};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    HashAlgorithm, ALGORITHM_FIELD_PREFIX, BINARY_CONTENT_PREFIX, CHAIN_FIELD_PREFIX,
+    FIELD_SEPARATOR, METADATA_END, METADATA_START,
+};
+
+/// Metadata of an entry recorded with `eventlog_config.record_timestamps`
+/// or `eventlog_config.chain_entries`. It is not part of the entry whose
+/// digest is extended, s.t. the digest is the same with and without it, and
+/// replaying ignores it. An AAEL line starts with it as
+/// `[<seq> <timestamp> prev_chain_digest=<hex> alg=<algorithm>] `, where
+/// the fields after the sequence number are only there if recorded, while a
+/// CEL record carries it in a field of its own.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct EntryMetadata {
+    /// Sequence number of the entry in the eventlog, counting from the INIT
+    /// entry over rotated files.
+    pub seq: u64,
+
+    /// When the entry was recorded, in RFC 3339 format.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+
+    /// Hex encoded chain digest of the entries before this one, which is
+    /// calculated like extending a register with the entries.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_chain_digest: Option<String>,
+
+    /// Hash algorithm the entry is extended with, if not the one of the
+    /// [`AaelHeader`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithm: Option<HashAlgorithm>,
+}
+
+impl Display for EntryMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{METADATA_START}{}", self.seq)?;
+        if let Some(timestamp) = &self.timestamp {
+            write!(f, " {timestamp}")?;
+        }
+        if let Some(prev) = &self.prev_chain_digest {
+            write!(f, " {CHAIN_FIELD_PREFIX}{prev}")?;
+        }
+        if let Some(algorithm) = &self.algorithm {
+            write!(f, " {ALGORITHM_FIELD_PREFIX}{algorithm}")?;
+        }
+        write!(f, "{METADATA_END} ")
+    }
+}
+
+/// Split an AAEL line into its metadata, if any, and the entry.
+pub fn split_metadata(line: &str) -> Result<(Option<EntryMetadata>, &str), EventEntryError> {
+    let Some(rest) = line.strip_prefix(METADATA_START) else {
+        return Ok((None, line));
+    };
+    let (metadata, entry) = rest
+        .split_once(METADATA_END)
+        .ok_or(EventEntryError::Malformed)?;
+    let entry = entry
+        .strip_prefix(FIELD_SEPARATOR)
+        .ok_or(EventEntryError::Malformed)?;
+    let mut fields = metadata.split(FIELD_SEPARATOR);
+    let seq = fields.next().unwrap_or_default();
+    let mut metadata = EntryMetadata {
+        seq: seq.parse().map_err(|_| EventEntryError::Malformed)?,
+        timestamp: None,
+        prev_chain_digest: None,
+        algorithm: None,
+    };
+    for field in fields {
+        if let Some(algorithm) = field.strip_prefix(ALGORITHM_FIELD_PREFIX) {
+            if metadata.algorithm.is_some() {
+                return Err(EventEntryError::Malformed);
+            }
+            metadata.algorithm = Some(algorithm.parse().map_err(|_| EventEntryError::Malformed)?);
+            continue;
+        }
+        let (slot, value, valid) = match field.strip_prefix(CHAIN_FIELD_PREFIX) {
+            Some(prev) => (
+                &mut metadata.prev_chain_digest,
+                prev,
+                hex::decode(prev).is_ok(),
+            ),
+            None => (
+                &mut metadata.timestamp,
+                field,
+                DateTime::parse_from_rfc3339(field).is_ok(),
+            ),
+        };
+        if !valid || slot.is_some() {
+            return Err(EventEntryError::Malformed);
+        }
+        *slot = Some(value.to_string());
+    }
+    if metadata.timestamp.is_none()
+        && metadata.prev_chain_digest.is_none()
+        && metadata.algorithm.is_none()
+    {
+        return Err(EventEntryError::Malformed);
+    }
+
+    Ok((Some(metadata), entry))
+}
+
+/// Why the fields of an [`EventEntry`] cannot be recorded. The entry is a
+/// line of fields separated by spaces, and a verifier parses it back to
+/// calculate the digest, s.t. line breaks in any field, or a separator in
+/// any field but the last one, would make the log impossible to replay.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum EventEntryError {
+    #[error("event {0} must not contain line breaks")]
+    LineBreak(&'static str),
+
+    #[error("event {0} must not contain spaces")]
+    Separator(&'static str),
+
+    #[error("malformed eventlog entry, expected `domain operation content`")]
+    Malformed,
+
+    #[error("binary event content is not base64url encoded")]
+    BinaryEncoding,
+
+    #[error("event domain must not start with `{METADATA_START}`")]
+    MetadataPrefix,
+}
+
+pub struct EventEntry<'a> {
+    domain: &'a str,
+    operation: &'a str,
+
+    /// The content as in the line, which is encoded if binary
+    content: Cow<'a, str>,
+}
+
+impl<'a> EventEntry<'a> {
+    /// An entry of text content. Text that starts with
+    /// [`BINARY_CONTENT_PREFIX`] is recorded like binary content of its
+    /// UTF-8 bytes, see [`EventEntry::new_binary`], s.t. it is not taken for
+    /// binary content when parsing.
+    pub fn new(
+        domain: &'a str,
+        operation: &'a str,
+        content: &'a str,
+    ) -> Result<Self, EventEntryError> {
+        if content.starts_with(BINARY_CONTENT_PREFIX) {
+            return Self::new_binary(domain, operation, content.as_bytes());
+        }
+        Self::with_content(domain, operation, Cow::Borrowed(content))
+    }
+
+    /// An entry of binary content, e.g. a signature, which is recorded as
+    /// [`BINARY_CONTENT_PREFIX`] followed by its base64url encoding. The
+    /// digest is calculated over the line, like for text content.
+    pub fn new_binary(
+        domain: &'a str,
+        operation: &'a str,
+        content: &[u8],
+    ) -> Result<Self, EventEntryError> {
+        let content = format!("{BINARY_CONTENT_PREFIX}{}", URL_SAFE_NO_PAD.encode(content));
+        Self::with_content(domain, operation, Cow::Owned(content))
+    }
+
+    fn with_content(
+        domain: &'a str,
+        operation: &'a str,
+        content: Cow<'a, str>,
+    ) -> Result<Self, EventEntryError> {
+        for (name, field) in [
+            ("domain", domain),
+            ("operation", operation),
+            ("content", content.as_ref()),
+        ] {
+            if field.contains(['\n', '\r']) {
+                return Err(EventEntryError::LineBreak(name));
+            }
+        }
+
+        if domain.starts_with(METADATA_START) {
+            return Err(EventEntryError::MetadataPrefix);
+        }
+
+        // The content is the last field, s.t. it can contain spaces
+        for (name, field) in [("domain", domain), ("operation", operation)] {
+            if field.contains(FIELD_SEPARATOR) {
+                return Err(EventEntryError::Separator(name));
+            }
+        }
+
+        Ok(Self {
+            domain,
+            operation,
+            content,
+        })
+    }
+
+    /// Parse an entry from its line in the eventlog, which may start with
+    /// [`EntryMetadata`]. The metadata is not part of the entry.
+    pub fn parse(line: &'a str) -> Result<Self, EventEntryError> {
+        let (_, line) = split_metadata(line)?;
+        let mut fields = line.splitn(3, FIELD_SEPARATOR);
+        let (Some(domain), Some(operation), Some(content)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(EventEntryError::Malformed);
+        };
+
+        match content.strip_prefix(BINARY_CONTENT_PREFIX) {
+            Some(encoded) => {
+                URL_SAFE_NO_PAD
+                    .decode(encoded)
+                    .map_err(|_| EventEntryError::BinaryEncoding)?;
+                Self::with_content(domain, operation, Cow::Borrowed(content))
+            }
+            None => Self::new(domain, operation, content),
+        }
+    }
+
+    /// The content of the entry, decoded if it is binary.
+    pub fn content_bytes(&self) -> Cow<'_, [u8]> {
+        match self.content.strip_prefix(BINARY_CONTENT_PREFIX) {
+            Some(encoded) => Cow::Owned(
+                URL_SAFE_NO_PAD
+                    .decode(encoded)
+                    .expect("binary content is encoded by the entry"),
+            ),
+            None => Cow::Borrowed(self.content.as_bytes()),
+        }
+    }
+
+    /// Calculate the EventEntry's digest with the given [`HashAlgorithm`]
+    pub fn digest_with(&self, hash_alg: HashAlgorithm) -> Vec<u8> {
+        let log_entry = self.to_string();
+        hash_alg.digest(log_entry.as_bytes())
+    }
+}
+
+impl<'a> Display for EventEntry<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.domain, self.operation, self.content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{EventEntry, EventEntryError};
+    use crate::HashAlgorithm;
+
+    #[rstest]
+    #[case(
+        "domain",
+        "operation",
+        "content",
+        "65aad3b1620d4fe224d727579db2db87ff5c033f3e4424ae0fd72eb1149d3bd5",
+        HashAlgorithm::Sha256
+    )]
+    #[case("domain", "operation", "content", "26d944cb8d99096590252283b8c807b9508329b068703bdb7bac7eb6efe5b32fc0fadf1462662b95d2c708aa49c0bfe1", HashAlgorithm::Sha384)]
+    #[case("domain", "operation", "content", "6e75837e0fbf8367fa4550254b8f0f52eb659be0901340357ed91dda97f0ebca10537540a021eec78df9d29ade51609a01eaaa46d32e0218cdac1644dc9933b0", HashAlgorithm::Sha512)]
+    fn test_event_digest(
+        #[case] domain: &str,
+        #[case] operation: &str,
+        #[case] content: &str,
+        #[case] digest: &str,
+        #[case] hash_alg: HashAlgorithm,
+    ) {
+        let event = EventEntry::new(domain, operation, content).unwrap();
+        let dig = event.digest_with(hash_alg);
+        let dig_hex = dig.iter().map(|c| format!("{c:02x}")).collect::<String>();
+        assert_eq!(dig_hex, digest);
+    }
+
+    #[rstest]
+    #[case("[1 2026-10-15T08:00:00.000Z]domain operation content")]
+    #[case("[1] domain operation content")]
+    #[case("[one 2026-10-15T08:00:00.000Z] domain operation content")]
+    #[case("[1 yesterday] domain operation content")]
+    #[case("[1 2026-10-15T08:00:00.000Z 2026-10-15T08:00:00.000Z] domain operation content")]
+    #[case("[1 prev_chain_digest=xyz] domain operation content")]
+    fn test_malformed_metadata(#[case] line: &str) {
+        assert_eq!(
+            EventEntry::parse(line).err(),
+            Some(EventEntryError::Malformed)
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "github.com/confidential-containers",
+        "PullImage",
+        "docker.io/library/busybox:latest"
+    )]
+    #[case("domain", "operation", "content with spaces  and\ttabs ")]
+    #[case("domain", "operation", "")]
+    #[case("", "", "")]
+    #[case("domain", "operation", "INIT sha384/00")]
+    fn test_event_entry_round_trip(
+        #[case] domain: &str,
+        #[case] operation: &str,
+        #[case] content: &str,
+    ) {
+        let line = EventEntry::new(domain, operation, content)
+            .unwrap()
+            .to_string();
+        let parsed = EventEntry::parse(&line).unwrap();
+        assert_eq!(
+            (parsed.domain, parsed.operation, &*parsed.content),
+            (domain, operation, content)
+        );
+    }
+
+    #[rstest]
+    #[case(
+        "domain",
+        "operation",
+        "line\ninjected",
+        EventEntryError::LineBreak("content")
+    )]
+    #[case(
+        "domain",
+        "operation",
+        "carriage\rreturn",
+        EventEntryError::LineBreak("content")
+    )]
+    #[case(
+        "dom\nain",
+        "operation",
+        "content",
+        EventEntryError::LineBreak("domain")
+    )]
+    #[case(
+        "domain",
+        "opera tion",
+        "content",
+        EventEntryError::Separator("operation")
+    )]
+    #[case(
+        "do main",
+        "operation",
+        "content",
+        EventEntryError::Separator("domain")
+    )]
+    #[case("[domain", "operation", "content", EventEntryError::MetadataPrefix)]
+    fn test_hostile_event_entry(
+        #[case] domain: &str,
+        #[case] operation: &str,
+        #[case] content: &str,
+        #[case] error: EventEntryError,
+    ) {
+        assert_eq!(
+            EventEntry::new(domain, operation, content).err(),
+            Some(error)
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_entry() {
+        assert_eq!(
+            EventEntry::parse("domain-only").err(),
+            Some(EventEntryError::Malformed)
+        );
+        assert_eq!(
+            EventEntry::parse("domain operation base64url:not base64").err(),
+            Some(EventEntryError::BinaryEncoding)
+        );
+    }
+
+    #[test]
+    fn test_binary_event_entry() {
+        let event = EventEntry::new_binary("domain", "operation", &[0xff, 0x00, 0xfe]).unwrap();
+        let line = event.to_string();
+        assert_eq!(line, "domain operation base64url:_wD-");
+        assert_eq!(
+            hex::encode(event.digest_with(HashAlgorithm::Sha384)),
+            "f423d1e2b62dd8fbef4de0e3f479f2329338f88cbf889e165018ad0206a2b415742fbfd26482c3b5dcd48503b32b11fd"
+        );
+
+        let parsed = EventEntry::parse(&line).unwrap();
+        assert_eq!(&*parsed.content_bytes(), &[0xff, 0x00, 0xfe]);
+        assert_eq!(parsed.to_string(), line);
+
+        // Text content is hashed as is
+        let parsed = EventEntry::parse("domain operation content").unwrap();
+        assert_eq!(&*parsed.content_bytes(), b"content");
+
+        // unless it looks like binary content
+        let event = EventEntry::new("domain", "operation", "base64url:AA").unwrap();
+        let line = event.to_string();
+        assert_eq!(line, "domain operation base64url:YmFzZTY0dXJsOkFB");
+        let parsed = EventEntry::parse(&line).unwrap();
+        assert_eq!(&*parsed.content_bytes(), b"base64url:AA");
+    }
+}
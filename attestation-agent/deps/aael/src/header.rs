@@ -0,0 +1,92 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use std::fmt::Display;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{HashAlgorithm, AAEL_HEADER_PREFIX, AAEL_VERSION, FIELD_SEPARATOR};
+
+/// The first line of an AAEL file, which tells the version of the format
+/// and how the entries are recorded, e.g.
+/// `AAEL/1.0 algorithm=sha384 init_pcr=17`. It is not an entry, s.t. it is
+/// neither extended nor replayed. Legacy files without it are of version 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AaelHeader {
+    /// `(major, minor)`
+    pub version: (u32, u32),
+
+    /// Hash algorithm of the entries, see `eventlog_config.eventlog_algorithm`.
+    pub algorithm: HashAlgorithm,
+
+    /// Register the entries are extended into.
+    pub init_pcr: u64,
+}
+
+impl AaelHeader {
+    /// Parse the header. Fields of a later minor version that are not known
+    /// are skipped, while a later major version is an error.
+    pub fn parse(line: &str) -> Result<Self> {
+        let Some(header) = line.strip_prefix(AAEL_HEADER_PREFIX) else {
+            bail!("the AAEL header does not start with `{AAEL_HEADER_PREFIX}`");
+        };
+        let mut fields = header.split(FIELD_SEPARATOR);
+        let version = fields.next().unwrap_or_default();
+        let version = version
+            .split_once('.')
+            .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+            .with_context(|| format!("malformed AAEL version `{version}`"))?;
+        if version.0 != AAEL_VERSION.0 {
+            bail!(
+                "AAEL version {}.{} is not supported, only {}.x",
+                version.0,
+                version.1,
+                AAEL_VERSION.0
+            );
+        }
+
+        let (mut algorithm, mut init_pcr) = (None, None);
+        for field in fields {
+            let Some((key, value)) = field.split_once('=') else {
+                bail!("malformed AAEL header field `{field}`");
+            };
+            match key {
+                "algorithm" => {
+                    algorithm = Some(
+                        value
+                            .parse::<HashAlgorithm>()
+                            .ok()
+                            .with_context(|| format!("unknown AAEL header algorithm `{value}`"))?,
+                    );
+                }
+                "init_pcr" => init_pcr = Some(value.parse().context("AAEL header init_pcr")?),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            version,
+            algorithm: algorithm.context("the AAEL header has no algorithm")?,
+            init_pcr: init_pcr.context("the AAEL header has no init_pcr")?,
+        })
+    }
+}
+
+impl Display for AaelHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (major, minor) = self.version;
+        write!(
+            f,
+            "{AAEL_HEADER_PREFIX}{major}.{minor} algorithm={} init_pcr={}",
+            self.algorithm, self.init_pcr
+        )
+    }
+}
+
+/// Whether the line is an [`AaelHeader`], which only the first line of an
+/// AAEL file can be.
+pub fn is_header(line: &str) -> bool {
+    line.starts_with(AAEL_HEADER_PREFIX)
+}
@@ -0,0 +1,405 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+//! Parser of the eventlog that the attestation agent records in the AAEL
+//! format, s.t. a verifier, e.g. KBS or AS, can replay it without copying
+//! the parsing logic. It does not depend on the attesters nor on an async
+//! runtime.
+//!
+//! An AAEL file starts with the [`AaelHeader`], followed by a line for each
+//! entry, optionally prefixed with [`EntryMetadata`]. The first entry is
+//! `INIT <algorithm>/<hex register value>`, or
+//! `CONTINUE <algorithm>/<hex register value> <rotated file>` for a file
+//! that continues a rotated one, and the others are [`EventEntry`]s of the
+//! form `domain operation content`.
+
+use std::io::BufRead;
+
+use anyhow::{bail, Context, Result};
+
+mod algorithm;
+pub mod cel;
+mod entry;
+mod header;
+mod replay;
+
+pub use algorithm::HashAlgorithm;
+pub use entry::{split_metadata, EntryMetadata, EventEntry, EventEntryError};
+pub use header::{is_header, AaelHeader};
+pub use replay::{diverging_bank_entry, diverging_entry, replay, replay_bank};
+
+/// The INIT entry, which is the first entry of an eventlog, starts with this
+pub const INIT_ENTRY_PREFIX: &str = "INIT ";
+
+/// The first entry of an eventlog file that continues a rotated one starts
+/// with this, followed by `<algorithm>/<hex register value> <rotated file>`.
+/// It is not extended into the register.
+pub const CONTINUE_ENTRY_PREFIX: &str = "CONTINUE ";
+
+/// An AAEL file starts with the [`AaelHeader`], which starts with this
+pub const AAEL_HEADER_PREFIX: &str = "AAEL/";
+
+/// Version of the AAEL format the attestation agent records in, as
+/// `(major, minor)`. Files of a later minor version are parsed, but not of
+/// a later major version.
+pub const AAEL_VERSION: (u32, u32) = (1, 0);
+
+/// Fields of an eventlog entry are separated by this
+pub const FIELD_SEPARATOR: char = ' ';
+
+/// An AAEL line that starts with this carries [`EntryMetadata`] up to
+/// [`METADATA_END`]. Domains must not start with it, s.t. the metadata is
+/// told apart from the entry.
+pub const METADATA_START: char = '[';
+
+pub const METADATA_END: char = ']';
+
+/// The field of [`EntryMetadata::prev_chain_digest`] in an AAEL line starts
+/// with this.
+pub const CHAIN_FIELD_PREFIX: &str = "prev_chain_digest=";
+
+/// The field of [`EntryMetadata::algorithm`] in an AAEL line starts with
+/// this.
+pub const ALGORITHM_FIELD_PREFIX: &str = "alg=";
+
+/// The content of an entry made with [`EventEntry::new_binary`] is this,
+/// followed by the unpadded base64url encoding of the bytes. Text content
+/// that starts with it is encoded the same, s.t. the two are told apart
+/// when parsing.
+pub const BINARY_CONTENT_PREFIX: &str = "base64url:";
+
+/// An AAEL file as parsed by [`parse_str`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AaelLog {
+    /// `None` for a legacy file without it.
+    pub header: Option<AaelHeader>,
+
+    /// Hash algorithm of the entries that do not record one, which is the
+    /// one of the header, or of the first entry of a legacy file.
+    pub algorithm: HashAlgorithm,
+
+    pub lines: Vec<AaelLine>,
+}
+
+/// The line of an entry of an AAEL file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AaelLine {
+    pub metadata: Option<EntryMetadata>,
+
+    /// The entry without the metadata, whose digest is extended into the
+    /// register.
+    pub entry: String,
+}
+
+impl AaelLine {
+    /// The hash algorithm recorded with the entry, if any: in its metadata,
+    /// or in the INIT and `CONTINUE` entries.
+    pub fn recorded_algorithm(&self) -> Option<HashAlgorithm> {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.algorithm)
+            .or_else(|| entry_algorithm(&self.entry))
+    }
+}
+
+impl AaelLog {
+    /// The entries in order, as [`replay`] takes them.
+    pub fn entries(&self) -> Vec<String> {
+        self.lines.iter().map(|line| line.entry.clone()).collect()
+    }
+
+    /// The entries with the hash algorithm each is extended with, as
+    /// [`replay_bank`] takes them.
+    pub fn algorithm_entries(&self) -> Vec<(HashAlgorithm, String)> {
+        self.lines
+            .iter()
+            .map(|line| {
+                let algorithm = line.recorded_algorithm().unwrap_or(self.algorithm);
+                (algorithm, line.entry.clone())
+            })
+            .collect()
+    }
+
+    /// The value of the register bank of `hash_alg` after extending the
+    /// entries of the algorithm into it, see [`replay_bank`].
+    pub fn replay(&self, hash_alg: HashAlgorithm, initial: Vec<u8>) -> Vec<u8> {
+        replay_bank(&self.algorithm_entries(), hash_alg, initial)
+    }
+}
+
+/// The hash algorithm of the INIT or `CONTINUE` entry, `None` for other
+/// entries.
+pub fn entry_algorithm(entry: &str) -> Option<HashAlgorithm> {
+    let (algorithm, _) = entry
+        .strip_prefix(INIT_ENTRY_PREFIX)
+        .or_else(|| entry.strip_prefix(CONTINUE_ENTRY_PREFIX))?
+        .split_once('/')?;
+    algorithm.parse().ok()
+}
+
+/// Parse an AAEL file: the [`AaelHeader`] if there is one, then the INIT
+/// or a `CONTINUE` entry, then [`EventEntry`]s. A header of an unknown
+/// major version, or a malformed line, is an error.
+pub fn parse_str(log: &str) -> Result<AaelLog> {
+    parse_lines(log.lines().map(|line| Ok(line.to_string())))
+}
+
+/// The entries of an eventlog in order, as [`replay`] takes them, whether
+/// it is an AAEL file or CEL-JSON records, see [`cel`]. The records are
+/// told apart by the first line, which is a JSON object.
+pub fn parse_entries(log: &str) -> Result<Vec<String>> {
+    if !log.starts_with('{') {
+        return Ok(parse_str(log)?.entries());
+    }
+
+    log.lines()
+        .map(|line| cel::Record::parse(line)?.entry())
+        .collect()
+}
+
+/// Like [`parse_str`], reading the file line by line.
+pub fn parse_reader(reader: impl BufRead) -> Result<AaelLog> {
+    parse_lines(
+        reader
+            .lines()
+            .map(|line| line.context("failed to read eventlog")),
+    )
+}
+
+fn parse_lines(lines: impl Iterator<Item = Result<String>>) -> Result<AaelLog> {
+    let mut header = None;
+    let mut parsed = Vec::new();
+    for (index, line) in lines.enumerate() {
+        let line = line?;
+        if index == 0 && is_header(&line) {
+            header = Some(AaelHeader::parse(&line)?);
+            continue;
+        }
+        let (metadata, entry) = split_metadata(&line).with_context(|| format!("line {index}"))?;
+        let well_formed = if parsed.is_empty() {
+            entry.starts_with(INIT_ENTRY_PREFIX) || entry.starts_with(CONTINUE_ENTRY_PREFIX)
+        } else {
+            EventEntry::parse(entry).is_ok()
+        };
+        if !well_formed {
+            bail!("line {index} is malformed");
+        }
+        parsed.push(AaelLine {
+            metadata,
+            entry: entry.to_string(),
+        });
+    }
+
+    let algorithm = match &header {
+        Some(header) => header.algorithm,
+        None => parsed
+            .first()
+            .and_then(|line| entry_algorithm(&line.entry))
+            .unwrap_or_default(),
+    };
+    Ok(AaelLog {
+        header,
+        algorithm,
+        lines: parsed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, SecondsFormat};
+    use proptest::{collection::vec, option, prelude::*};
+    use rstest::rstest;
+
+    use super::{
+        cel, parse_entries, parse_reader, parse_str, replay, AaelHeader, AaelLine, EntryMetadata,
+        EventEntry, HashAlgorithm,
+    };
+
+    /// Chars that are easily mishandled among others. Only the content may
+    /// contain the separator.
+    const FIELD: &str = "[a-zA-Z0-9/:=\\[\\]{}\"\\\\\té✓🦀-]{0,16}";
+    const CONTENT: &str = "[ a-zA-Z0-9/:=\\[\\]{}\"\\\\\té✓🦀-]{0,16}";
+
+    fn algorithm() -> impl Strategy<Value = HashAlgorithm> {
+        prop_oneof![
+            Just(HashAlgorithm::Sha256),
+            Just(HashAlgorithm::Sha384),
+            Just(HashAlgorithm::Sha512),
+        ]
+    }
+
+    /// An entry as the agent records it, of text or binary content.
+    fn entry() -> impl Strategy<Value = String> {
+        let domain = FIELD.prop_filter("metadata prefix", |domain| !domain.starts_with('['));
+        let content = prop_oneof![
+            CONTENT.prop_map(Ok::<_, Vec<u8>>),
+            vec(any::<u8>(), 0..16).prop_map(Err),
+        ];
+        (domain, FIELD, content).prop_map(|(domain, operation, content)| {
+            let entry = match &content {
+                Ok(text) => EventEntry::new(&domain, &operation, text),
+                Err(bytes) => EventEntry::new_binary(&domain, &operation, bytes),
+            };
+            entry.unwrap().to_string()
+        })
+    }
+
+    /// Metadata as recorded with timestamps, chained entries or another
+    /// algorithm, at least one of which is there.
+    fn metadata() -> impl Strategy<Value = EntryMetadata> {
+        let timestamp = (0..i64::from(u32::MAX) * 1000).prop_map(|millis| {
+            DateTime::from_timestamp_millis(millis)
+                .unwrap()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+        });
+        (
+            any::<u64>(),
+            option::of(timestamp),
+            option::of("[0-9a-f]{96}"),
+            option::of(algorithm()),
+        )
+            .prop_map(
+                |(seq, timestamp, prev_chain_digest, algorithm)| EntryMetadata {
+                    seq,
+                    timestamp,
+                    prev_chain_digest,
+                    algorithm,
+                },
+            )
+            .prop_filter("no recorded field", |metadata| {
+                metadata.timestamp.is_some()
+                    || metadata.prev_chain_digest.is_some()
+                    || metadata.algorithm.is_some()
+            })
+    }
+
+    fn lines() -> impl Strategy<Value = Vec<AaelLine>> {
+        vec(
+            (option::of(metadata()), entry())
+                .prop_map(|(metadata, entry)| AaelLine { metadata, entry }),
+            0..32,
+        )
+    }
+
+    proptest! {
+        #[test]
+        fn test_written_log_parses_back(
+            header in option::of((algorithm(), 0..24u64)),
+            lines in lines(),
+        ) {
+            let header = header.map(|(algorithm, init_pcr)| AaelHeader {
+                version: (1, 0),
+                algorithm,
+                init_pcr,
+            });
+            let mut log = header
+                .as_ref()
+                .map(|header| format!("{header}\n"))
+                .unwrap_or_default();
+            let init = AaelLine {
+                metadata: None,
+                entry: "INIT sha384/00".to_string(),
+            };
+            let lines = [vec![init], lines].concat();
+            for line in &lines {
+                let metadata = line.metadata.as_ref().map(ToString::to_string);
+                log += &format!("{}{}\n", metadata.unwrap_or_default(), line.entry);
+            }
+
+            let parsed = parse_str(&log).unwrap();
+            prop_assert_eq!(&parsed.header, &header);
+            prop_assert_eq!(&parsed.lines, &lines);
+            for line in &parsed.lines[1..] {
+                let event = EventEntry::parse(&line.entry).unwrap();
+                prop_assert_eq!(event.to_string(), line.entry.clone());
+            }
+            prop_assert_eq!(parse_entries(&log).unwrap(), parsed.entries());
+            prop_assert_eq!(parse_reader(log.as_bytes()).unwrap(), parsed);
+        }
+
+        #[test]
+        fn test_written_cel_parses_back(
+            algorithm in algorithm(),
+            pcr in 0..24u64,
+            lines in lines(),
+        ) {
+            let mut log = String::new();
+            let mut records = Vec::new();
+            for (recnum, line) in lines.iter().enumerate() {
+                let digest = algorithm.digest(line.entry.as_bytes());
+                let extended = Some((pcr, algorithm, &digest[..]));
+                let mut record = cel::Record::new(recnum as u64, &line.entry, extended);
+                record.metadata = line.metadata.clone();
+                log += &format!("{}\n", record.to_line());
+                records.push(record);
+            }
+
+            let parsed: Vec<_> = log
+                .lines()
+                .map(|line| cel::Record::parse(line).unwrap())
+                .collect();
+            prop_assert_eq!(parsed, records);
+            let entries: Vec<_> = lines.iter().map(|line| line.entry.clone()).collect();
+            prop_assert_eq!(parse_entries(&log).unwrap(), entries);
+        }
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let log = "AAEL/1.0 algorithm=sha384 init_pcr=17\nINIT sha384/00\n[2 alg=sha256] domain operation content with spaces\n";
+        let parsed = parse_str(log).unwrap();
+        assert_eq!(
+            parsed.header,
+            Some(AaelHeader {
+                version: (1, 0),
+                algorithm: HashAlgorithm::Sha384,
+                init_pcr: 17,
+            })
+        );
+        assert_eq!(
+            parsed.lines[1],
+            AaelLine {
+                metadata: Some(EntryMetadata {
+                    seq: 2,
+                    timestamp: None,
+                    prev_chain_digest: None,
+                    algorithm: Some(HashAlgorithm::Sha256),
+                }),
+                entry: "domain operation content with spaces".into(),
+            }
+        );
+        assert_eq!(
+            parsed.algorithm_entries(),
+            vec![
+                (HashAlgorithm::Sha384, "INIT sha384/00".to_string()),
+                (
+                    HashAlgorithm::Sha256,
+                    "domain operation content with spaces".to_string()
+                ),
+            ]
+        );
+        assert_eq!(parse_reader(log.as_bytes()).unwrap(), parsed);
+    }
+
+    #[test]
+    fn test_parse_legacy_log() {
+        let parsed = parse_str("INIT sha256/00\ndomain operation content\n").unwrap();
+        assert_eq!(parsed.header, None);
+        assert_eq!(parsed.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(
+            parsed.replay(HashAlgorithm::Sha256, vec![0; 32]),
+            replay(&parsed.entries(), HashAlgorithm::Sha256, vec![0; 32])
+        );
+    }
+
+    #[rstest]
+    #[case::later_major("AAEL/2.0 algorithm=sha384 init_pcr=17\nINIT sha384/00\n")]
+    #[case::no_init("domain operation content\n")]
+    #[case::malformed_entry("INIT sha384/00\ndomain-only\n")]
+    #[case::malformed_metadata("INIT sha384/00\n[1] domain operation content\n")]
+    fn test_parse_malformed_log(#[case] log: &str) {
+        assert!(parse_str(log).is_err());
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright (c) 2026 The Confidential Containers Authors
+//
+// SPDX-License-Identifier: Apache-2.0
+//
+
+use crate::{is_header, HashAlgorithm, CONTINUE_ENTRY_PREFIX};
+
+/// Calculate the value of a register after extending the digests of the
+/// given entries into it in order, starting from `initial`. This is how a
+/// verifier replays the eventlog. An eventlog that continues a rotated one
+/// starts from the register value in its `CONTINUE` entry instead.
+pub fn replay(entries: &[String], hash_alg: HashAlgorithm, initial: Vec<u8>) -> Vec<u8> {
+    replay_steps(entries, hash_alg, initial)
+        .pop()
+        .expect("replay starts from the initial value")
+}
+
+/// Like [`replay`] for the register bank of `hash_alg`, given the hash
+/// algorithm each entry is extended with. The entries of other algorithms
+/// are extended into other banks, so they are skipped.
+pub fn replay_bank(
+    entries: &[(HashAlgorithm, String)],
+    hash_alg: HashAlgorithm,
+    initial: Vec<u8>,
+) -> Vec<u8> {
+    replay(&bank_entries(entries, hash_alg).1, hash_alg, initial)
+}
+
+/// Like [`diverging_entry`] for the register bank of `hash_alg`, see
+/// [`replay_bank`]. The index counts the entries of all algorithms.
+pub fn diverging_bank_entry(
+    entries: &[(HashAlgorithm, String)],
+    hash_alg: HashAlgorithm,
+    initial: Vec<u8>,
+    current: &[u8],
+) -> Option<usize> {
+    let (indices, bank) = bank_entries(entries, hash_alg);
+    diverging_entry(&bank, hash_alg, initial, current).map(|index| indices[index])
+}
+
+/// The entries of the algorithm with their index among all entries.
+fn bank_entries(
+    entries: &[(HashAlgorithm, String)],
+    hash_alg: HashAlgorithm,
+) -> (Vec<usize>, Vec<String>) {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, (algorithm, _))| *algorithm == hash_alg)
+        .map(|(index, (_, entry))| (index, entry.clone()))
+        .unzip()
+}
+
+/// Index of the first entry that is not reflected in `current`, i.e. the
+/// replay of the entries before it gives `current`. `None` if the whole
+/// eventlog replays to `current`, or if no prefix of it does, as then the
+/// diverging entry cannot be told from the register value alone.
+pub fn diverging_entry(
+    entries: &[String],
+    hash_alg: HashAlgorithm,
+    initial: Vec<u8>,
+    current: &[u8],
+) -> Option<usize> {
+    let steps = replay_steps(entries, hash_alg, initial);
+    steps
+        .iter()
+        .rposition(|register| register == current)
+        .filter(|&index| index < entries.len())
+}
+
+/// The register values while replaying the entries as [`replay`] does:
+/// the value before the first entry, followed by the value after each
+/// entry. A `CONTINUE` entry sets the value instead of extending it. The
+/// [`AaelHeader`] of an AAEL file read as is leaves the value as it is.
+fn replay_steps(entries: &[String], hash_alg: HashAlgorithm, initial: Vec<u8>) -> Vec<Vec<u8>> {
+    let mut steps = vec![initial];
+    let first = usize::from(entries.first().is_some_and(|entry| is_header(entry)));
+    for (index, entry) in entries.iter().enumerate() {
+        let register = steps.last().expect("replay starts from the initial value");
+        if index < first {
+            steps.push(register.clone());
+            continue;
+        }
+        let register = match continued_register(entry, hash_alg).filter(|_| index == first) {
+            Some(continued) => continued,
+            None => {
+                let digest = hash_alg.digest(entry.as_bytes());
+                hash_alg.digest(&[register.as_slice(), &digest].concat())
+            }
+        };
+        steps.push(register);
+    }
+    steps
+}
+
+/// The register value of a `CONTINUE` entry of the given algorithm.
+fn continued_register(entry: &str, hash_alg: HashAlgorithm) -> Option<Vec<u8>> {
+    let (register, _rotated) = entry.strip_prefix(CONTINUE_ENTRY_PREFIX)?.split_once(' ')?;
+    let register = register.strip_prefix(&format!("{hash_alg}/"))?;
+    hex::decode(register).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diverging_bank_entry, replay, replay_bank};
+    use crate::HashAlgorithm;
+
+    #[test]
+    fn test_replay_mixed_banks() {
+        let entries: Vec<(HashAlgorithm, String)> = [
+            (HashAlgorithm::Sha256, "INIT sha256/00"),
+            (HashAlgorithm::Sha256, "domain operation first"),
+            (HashAlgorithm::Sha384, "domain operation second"),
+            (HashAlgorithm::Sha384, "domain operation third"),
+        ]
+        .map(|(algorithm, entry)| (algorithm, entry.to_string()))
+        .into();
+
+        let sha256 = replay_bank(&entries, HashAlgorithm::Sha256, vec![0; 32]);
+        let sha384 = replay_bank(&entries, HashAlgorithm::Sha384, vec![0; 48]);
+        assert_eq!(sha256.len(), 32);
+        assert_eq!(
+            sha384,
+            replay(
+                &[entries[2].1.clone(), entries[3].1.clone()],
+                HashAlgorithm::Sha384,
+                vec![0; 48]
+            )
+        );
+
+        // The diverging entry counts the entries of both banks
+        let partial = replay(&[entries[2].1.clone()], HashAlgorithm::Sha384, vec![0; 48]);
+        assert_eq!(
+            diverging_bank_entry(&entries, HashAlgorithm::Sha384, vec![0; 48], &partial),
+            Some(3)
+        );
+        assert_eq!(
+            diverging_bank_entry(&entries, HashAlgorithm::Sha256, vec![0; 32], &sha256),
+            None
+        );
+    }
+}
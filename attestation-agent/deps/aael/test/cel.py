@@ -0,0 +1,35 @@
+#!/usr/bin/env python3
+#
+# Copyright (c) 2026 The Confidential Containers Authors
+#
+# SPDX-License-Identifier: Apache-2.0
+#
+# Write the CEL-JSON records of an AAEL file, extended into PCR 17 with
+# SHA-384, as the golden file of the CEL serializer, e.g.
+#
+#   ./cel.py aael.log > cel.jsonl
+#
+# Each entry is a TCG PC Client event of type EV_IPL (0xd) whose event data
+# is the entry, and whose digest is the digest of the event data.
+
+import base64
+import hashlib
+import json
+import sys
+
+EV_IPL = 0xD
+PCR = 17
+
+with open(sys.argv[1], "rb") as aael:
+    for recnum, entry in enumerate(aael.read().splitlines()):
+        record = {
+            "recnum": recnum,
+            "pcr": PCR,
+            "digests": [{"hashAlg": "sha384", "digest": hashlib.sha384(entry).hexdigest()}],
+            "content_type": "pcclient_std",
+            "content": {
+                "event_type": EV_IPL,
+                "event_data": base64.b64encode(entry).decode(),
+            },
+        }
+        print(json.dumps(record, separators=(",", ":")))
@@ -7,9 +7,10 @@
 use std::time::Duration;
 
 use anyhow::*;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 
 use crate::{
-    client::ClientTee,
+    client::{ClientTee, RCAR_MAX_ATTEMPT},
     evidence_provider::EvidenceProvider,
     keypair::TeeKeyPair,
     token_provider::{Token, TokenProvider},
@@ -19,12 +20,35 @@ use super::client::KbsClient;
 
 const KBS_REQ_TIMEOUT_SEC: u64 = 60;
 
+/// The headers to send with every request, e.g. the ones an API gateway in
+/// front of the service asks for. The values are marked sensitive and are
+/// not in the errors, as they may be credentials.
+pub fn header_map<'a>(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<HeaderMap> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("invalid header name `{name}`"))?;
+        let mut value = HeaderValue::from_str(value)
+            .map_err(|_| anyhow!("invalid value of header `{name}`"))?;
+        value.set_sensitive(true);
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
 pub struct KbsClientBuilder<T> {
     provider: T,
     kbs_certs: Vec<String>,
     kbs_host_url: String,
     token: Option<String>,
     tee_key: Option<String>,
+    extra_params: String,
+    client_nonce: Option<Vec<u8>>,
+    proxy: Option<reqwest::Proxy>,
+    rcar_attempts: u32,
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    headers: Vec<(String, String)>,
 }
 
 impl KbsClientBuilder<Box<dyn EvidenceProvider>> {
@@ -38,6 +62,13 @@ impl KbsClientBuilder<Box<dyn EvidenceProvider>> {
             kbs_host_url: kbs_host_url.trim_end_matches('/').to_string(),
             token: None,
             tee_key: None,
+            extra_params: String::new(),
+            client_nonce: None,
+            proxy: None,
+            rcar_attempts: RCAR_MAX_ATTEMPT,
+            connect_timeout: None,
+            request_timeout: Some(Duration::from_secs(KBS_REQ_TIMEOUT_SEC)),
+            headers: vec![],
         }
     }
 }
@@ -50,6 +81,13 @@ impl KbsClientBuilder<Box<dyn TokenProvider>> {
             kbs_host_url: kbs_host_url.trim_end_matches('/').to_string(),
             token: None,
             tee_key: None,
+            extra_params: String::new(),
+            client_nonce: None,
+            proxy: None,
+            rcar_attempts: RCAR_MAX_ATTEMPT,
+            connect_timeout: None,
+            request_timeout: Some(Duration::from_secs(KBS_REQ_TIMEOUT_SEC)),
+            headers: vec![],
         }
     }
 }
@@ -70,20 +108,90 @@ impl<T> KbsClientBuilder<T> {
         self
     }
 
+    /// Set the `extra-params` of the RCAR auth request, which are passed to
+    /// KBS as is.
+    pub fn set_extra_params(mut self, extra_params: &str) -> Self {
+        self.extra_params = extra_params.to_string();
+        self
+    }
+
+    /// Bind a nonce of the caller into the runtime data of the RCAR
+    /// handshake, see [`crate::client_nonce_binding`].
+    pub fn set_client_nonce(mut self, nonce: &[u8]) -> Self {
+        self.client_nonce = Some(nonce.to_vec());
+        self
+    }
+
+    /// Connect to KBS through the given proxy, e.g. one that chooses the
+    /// proxy of each request by its URL with [`reqwest::Proxy::custom`].
+    /// HTTPS requests are tunneled with CONNECT. It replaces the proxy
+    /// environment variables, which apply otherwise.
+    pub fn set_proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Number of RCAR handshakes tried before getting a token fails,
+    /// [`RCAR_MAX_ATTEMPT`] by default. A request that KBS rejects is not
+    /// retried. Set it to `1` to handle the failures of the handshake, see
+    /// [`crate::Error`], e.g. to fail over to another KBS.
+    pub fn set_rcar_attempts(mut self, attempts: u32) -> Self {
+        self.rcar_attempts = attempts.max(1);
+        self
+    }
+
+    /// Limit connecting to KBS, including the TLS handshake, and each
+    /// request, from connecting to reading the response. `None` means no
+    /// limit. Requests are limited to 60 seconds by default.
+    pub fn set_timeouts(
+        mut self,
+        connect_timeout: Option<Duration>,
+        request_timeout: Option<Duration>,
+    ) -> Self {
+        self.connect_timeout = connect_timeout;
+        self.request_timeout = request_timeout;
+        self
+    }
+
+    /// Send the header with every request to KBS, e.g. one an API gateway
+    /// in front of KBS routes on. The value is not logged, as it may be a
+    /// credential.
+    pub fn add_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
     pub fn build(self) -> Result<KbsClient<T>> {
-        let mut http_client_builder = reqwest::Client::builder()
-            .cookie_store(true)
-            .user_agent(format!(
-                "attestation-agent-kbs-client/{}",
-                env!("CARGO_PKG_VERSION")
-            ))
-            .timeout(Duration::from_secs(KBS_REQ_TIMEOUT_SEC));
+        let mut http_client_builder =
+            reqwest::Client::builder()
+                .cookie_store(true)
+                .user_agent(format!(
+                    "attestation-agent-kbs-client/{}",
+                    env!("CARGO_PKG_VERSION")
+                ));
+        if let Some(timeout) = self.connect_timeout {
+            http_client_builder = http_client_builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.request_timeout {
+            http_client_builder = http_client_builder.timeout(timeout);
+        }
+
+        let headers = header_map(
+            self.headers
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        )?;
+        http_client_builder = http_client_builder.default_headers(headers);
 
         for customer_root_cert in &self.kbs_certs {
             let cert = reqwest::Certificate::from_pem(customer_root_cert.as_bytes())?;
             http_client_builder = http_client_builder.add_root_certificate(cert);
         }
 
+        if let Some(proxy) = self.proxy {
+            http_client_builder = http_client_builder.proxy(proxy);
+        }
+
         #[cfg(feature = "rust-crypto")]
         {
             http_client_builder = http_client_builder.use_rustls_tls();
@@ -108,6 +216,12 @@ impl<T> KbsClientBuilder<T> {
                 .build()
                 .context("Build KBS http client")?,
             kbs_host_url: self.kbs_host_url,
+            extra_params: self.extra_params,
+            client_nonce: self.client_nonce,
+            rcar_retries: 0,
+            rcar_attempts: self.rcar_attempts,
+            #[cfg(feature = "metrics")]
+            rcar_phases: Default::default(),
         };
 
         Ok(client)
@@ -145,4 +259,19 @@ x13TMfDeczAFBgMrZXADQQBpP6ABBkzVj3mF55nWUtP5vxwq3t91wqQJ6NyC7WsT
         .build()
         .expect("build client failed");
     }
+
+    #[rstest]
+    #[case::name("X Tenant", "a")]
+    #[case::value("X-Tenant-Id", "a\r\nX-Injected: b")]
+    fn invalid_header(#[case] name: &str, #[case] value: &str) {
+        let e = KbsClientBuilder::with_evidence_provider(
+            Box::<MockedEvidenceProvider>::default(),
+            "test.io",
+        )
+        .add_header(name, value)
+        .build()
+        .err()
+        .expect("invalid header accepted");
+        assert!(!e.to_string().contains("X-Injected"), "{e}");
+    }
 }
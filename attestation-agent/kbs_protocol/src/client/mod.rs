@@ -19,6 +19,9 @@ pub mod rcar_client;
 #[cfg(feature = "passport")]
 pub mod token_client;
 
+#[cfg(feature = "metrics")]
+use std::time::Duration;
+
 use kbs_types::Tee;
 
 use crate::{keypair::TeeKeyPair, token_provider::Token};
@@ -46,10 +49,45 @@ pub struct KbsClient<T> {
 
     /// token
     pub(crate) token: Option<Token>,
+
+    /// `extra-params` of the RCAR auth request
+    pub(crate) extra_params: String,
+
+    /// Nonce of the caller bound into the runtime data of the RCAR handshake
+    pub(crate) client_nonce: Option<Vec<u8>>,
+
+    /// Number of RCAR handshakes retried after a failure
+    pub(crate) rcar_retries: u64,
+
+    /// Number of RCAR handshakes tried before getting a token fails
+    pub(crate) rcar_attempts: u32,
+
+    /// Durations of the phases of the last RCAR handshake
+    #[cfg(feature = "metrics")]
+    pub(crate) rcar_phases: RcarPhaseDurations,
+}
+
+/// Time the phases of an RCAR handshake took, e.g. to record them as
+/// metrics. A phase that was not reached is `None`.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RcarPhaseDurations {
+    /// The auth request, until the challenge is got
+    pub challenge: Option<Duration>,
+
+    /// Generating the evidence for the challenge
+    pub evidence: Option<Duration>,
+
+    /// The attest request, until its response is got
+    pub attest: Option<Duration>,
 }
 
 pub const KBS_PROTOCOL_VERSION: &str = "0.1.0";
 
 pub const KBS_GET_RESOURCE_MAX_ATTEMPT: u64 = 3;
 
+/// When executing get token, RCAR handshake should retry if failed to
+/// make the logic robust. This constant is the max retry times by default.
+pub const RCAR_MAX_ATTEMPT: u32 = 5;
+
 pub const KBS_PREFIX: &str = "kbs/v0";
@@ -4,13 +4,16 @@
 //
 
 use std::time::Duration;
+#[cfg(feature = "metrics")]
+use std::time::Instant;
 
 use anyhow::{bail, Context};
 use async_trait::async_trait;
-use kbs_types::{Attestation, Challenge, ErrorInformation, Request, Response, Tee};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use kbs_types::{Attestation, Challenge, ErrorInformation, Request, Response, Tee, TeePubKey};
 use log::{debug, warn};
 use resource_uri::ResourceUri;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 use sha2::{Digest, Sha384};
 
@@ -25,13 +28,20 @@ use crate::{
     Error, Result,
 };
 
-/// When executing get token, RCAR handshake should retry if failed to
-/// make the logic robust. This constant is the max retry times.
-const RCAR_MAX_ATTEMPT: i32 = 5;
+#[cfg(feature = "metrics")]
+use crate::client::RcarPhaseDurations;
 
 /// The interval (seconds) between RCAR handshake retries.
 const RCAR_RETRY_TIMEOUT_SECOND: u64 = 1;
 
+/// The body of a certificate request, see
+/// [`KbsClient::request_certificate`].
+#[derive(Serialize, Debug)]
+struct CertificateRequest<'a> {
+    /// PEM certificate signing request of the key to certify
+    csr: &'a str,
+}
+
 #[derive(Deserialize, Debug, Clone)]
 struct AttestationResponseData {
     // Attestation token in JWT format
@@ -59,23 +69,113 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
         Ok((token, tee_key))
     }
 
+    /// Number of RCAR handshakes this client retried after a failure.
+    pub fn rcar_handshake_retries(&self) -> u64 {
+        self.rcar_retries
+    }
+
+    /// Durations of the phases of the last RCAR handshake, see
+    /// [`RcarPhaseDurations`].
+    #[cfg(feature = "metrics")]
+    pub fn rcar_phase_durations(&self) -> RcarPhaseDurations {
+        self.rcar_phases
+    }
+
+    /// Request a certificate of the key of `csr`, a PEM certificate signing
+    /// request, from the endpoint at `path` under [`KBS_PREFIX`] over the
+    /// attested session. KBS has no such endpoint itself, s.t. it is served
+    /// by a plugin or a proxy of KBS. The RCAR handshake is performed first
+    /// if the client has no token yet. The PEM certificate chain that was
+    /// issued is returned, the certificate of the key first.
+    pub async fn request_certificate(&mut self, path: &str, csr: &str) -> Result<String> {
+        if self.token.is_none() {
+            self.repeat_rcar_handshake().await?;
+        }
+
+        let remote_url = format!(
+            "{}/{KBS_PREFIX}/{}",
+            self.kbs_host_url,
+            path.trim_start_matches('/')
+        );
+        let request = CertificateRequest { csr };
+        let mut last_error = Error::UnAuthorized;
+        for attempt in 1..=KBS_GET_RESOURCE_MAX_ATTEMPT {
+            debug!("KBS client: requesting certificate, attempt {attempt}");
+
+            let res = self
+                .http_client
+                .post(&remote_url)
+                .header("Content-Type", "application/json")
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| Error::HttpError(format!("post failed: {e}")))?;
+
+            match res.status() {
+                reqwest::StatusCode::OK => {
+                    return res
+                        .text()
+                        .await
+                        .map_err(|e| Error::KbsResponseDeserializationFailed(e.to_string()));
+                }
+                reqwest::StatusCode::UNAUTHORIZED => {
+                    last_error = Error::RequestRejected {
+                        status: reqwest::StatusCode::UNAUTHORIZED.as_u16(),
+                        message: res.text().await.unwrap_or_default(),
+                    };
+                    if attempt < KBS_GET_RESOURCE_MAX_ATTEMPT {
+                        warn!("KBS session expired before the certificate was issued. Perform a new RCAR handshake");
+                        self.rcar_handshake().await.map_err(handshake_error)?;
+                    }
+                }
+                status if status.is_client_error() => {
+                    return Err(Error::RequestRejected {
+                        status: status.as_u16(),
+                        message: res.text().await.unwrap_or_default(),
+                    });
+                }
+                status => {
+                    return Err(Error::KbsInternalError(format!(
+                        "certificate request failed with status {status}, Response: {:?}",
+                        res.text().await.unwrap_or_default()
+                    )));
+                }
+            }
+        }
+
+        // KBS rejected the session of each new handshake
+        Err(last_error)
+    }
+
     /// Call rcar_hanshake several times and handle errors.
     async fn repeat_rcar_handshake(&mut self) -> Result<()> {
         let mut retry_count = 1;
         loop {
-            let res = self
-                .rcar_handshake()
-                .await
-                .map_err(|e| Error::RcarHandshake(e.to_string()));
+            let res = self.rcar_handshake().await;
 
             match res {
                 Ok(_) => break,
                 Err(e) => {
-                    if retry_count >= RCAR_MAX_ATTEMPT {
-                        return Err(Error::RcarHandshake(format!("Unable to get token. RCAR handshake retried {RCAR_MAX_ATTEMPT} times. Final attempt failed with: {e}")));
+                    // Retrying a rejected request only delays the failure
+                    if let Some(Error::RequestRejected { status, message }) = e.downcast_ref() {
+                        return Err(Error::RequestRejected {
+                            status: *status,
+                            message: message.clone(),
+                        });
+                    }
+
+                    if retry_count >= self.rcar_attempts {
+                        // The failures that are told apart, e.g. a stale
+                        // session, are kept for the caller to handle
+                        let e = e.context(format!(
+                            "Unable to get token. RCAR handshake tried {retry_count} times. Final attempt failed"
+                        ));
+                        return Err(handshake_error(e));
                     } else {
+                        let e = Error::RcarHandshake(format!("{e:#}"));
                         warn!("RCAR handshake failed: {e}, retry {retry_count}...");
                         retry_count += 1;
+                        self.rcar_retries += 1;
                         tokio::time::sleep(Duration::from_secs(RCAR_RETRY_TIMEOUT_SECOND)).await;
                     }
                 }
@@ -90,6 +190,10 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
     /// Note: if RCAR succeeds, the http client will record the cookie with the kbs server,
     /// which means that this client can be then used to retrieve resources.
     async fn rcar_handshake(&mut self) -> anyhow::Result<()> {
+        #[cfg(feature = "metrics")]
+        {
+            self.rcar_phases = RcarPhaseDurations::default();
+        }
         let auth_endpoint = format!("{}/{KBS_PREFIX}/auth", self.kbs_host_url);
 
         let tee = match &self._tee {
@@ -104,32 +208,64 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
         let request = Request {
             version: String::from(KBS_PROTOCOL_VERSION),
             tee,
-            extra_params: String::new(),
+            extra_params: self.extra_params.clone(),
         };
 
         debug!("send auth request to {auth_endpoint}");
 
-        let challenge = self
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
+        let auth_response = self
             .http_client
             .post(auth_endpoint)
             .header("Content-Type", "application/json")
             .json(&request)
             .send()
-            .await?
+            .await
+            .context("send auth request")?;
+        let status = auth_response.status();
+        if status.is_client_error() {
+            bail!(Error::RequestRejected {
+                status: status.as_u16(),
+                message: auth_response.text().await?,
+            });
+        }
+        if status.is_server_error() {
+            bail!(Error::KbsInternalError(format!(
+                "auth request failed with status {status}, Response: {:?}",
+                auth_response.text().await?
+            )));
+        }
+        let challenge = auth_response
+            .error_for_status()?
             .json::<Challenge>()
-            .await?;
+            .await
+            .context("deserialize challenge")?;
+        #[cfg(feature = "metrics")]
+        {
+            self.rcar_phases.challenge = Some(started.elapsed());
+        }
 
         debug!("get challenge: {challenge:#?}");
         let tee_pubkey = self.tee_key.export_pubkey()?;
-        let runtime_data = json!({
+        let mut runtime_data = json!({
             "tee-pubkey": tee_pubkey,
             "nonce": challenge.nonce,
         });
+        if let Some(nonce) = &self.client_nonce {
+            runtime_data["client-nonce"] = client_nonce_binding(nonce, &tee_pubkey)?.into();
+        }
         let runtime_data =
             serde_json::to_string(&runtime_data).context("serialize runtime data failed")?;
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
         let evidence = self
             .generate_evidence(tee, runtime_data, challenge.nonce)
             .await?;
+        #[cfg(feature = "metrics")]
+        {
+            self.rcar_phases.evidence = Some(started.elapsed());
+        }
         debug!("get evidence with challenge: {evidence}");
 
         let attest_endpoint = format!("{}/{KBS_PREFIX}/attest", self.kbs_host_url);
@@ -139,13 +275,20 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
         };
 
         debug!("send attest request.");
+        #[cfg(feature = "metrics")]
+        let started = Instant::now();
         let attest_response = self
             .http_client
             .post(attest_endpoint)
             .header("Content-Type", "application/json")
             .json(&attest)
             .send()
-            .await?;
+            .await
+            .context("send attest request")?;
+        #[cfg(feature = "metrics")]
+        {
+            self.rcar_phases.attest = Some(started.elapsed());
+        }
 
         match attest_response.status() {
             reqwest::StatusCode::OK => {
@@ -155,13 +298,26 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
             }
             reqwest::StatusCode::UNAUTHORIZED => {
                 let error_info = attest_response.json::<ErrorInformation>().await?;
-                bail!("KBS attest unauthorized, Error Info: {:?}", error_info);
+                if is_stale_session(&error_info) {
+                    // Retried with a new handshake, s.t. a new challenge
+                    bail!(Error::StaleSession(format!("{error_info:?}")));
+                }
+                bail!(Error::RequestRejected {
+                    status: reqwest::StatusCode::UNAUTHORIZED.as_u16(),
+                    message: format!("KBS attest unauthorized, Error Info: {error_info:?}"),
+                });
+            }
+            status if status.is_client_error() => {
+                bail!(Error::RequestRejected {
+                    status: status.as_u16(),
+                    message: attest_response.text().await?,
+                });
             }
-            _ => {
-                bail!(
-                    "KBS Server Internal Failed, Response: {:?}",
+            status => {
+                bail!(Error::KbsInternalError(format!(
+                    "attest request failed with status {status}, Response: {:?}",
                     attest_response.text().await?
-                );
+                )));
             }
         }
 
@@ -180,7 +336,8 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
 
         let ehd = match tee {
             // IBM SE uses nonce as runtime_data to pass attestation_request
-            Tee::Se => nonce.into_bytes(),
+            Tee::Se => se_attestation_request(&nonce, &hasher.finalize())
+                .map_err(|e| Error::GetEvidence(format!("{e:#}")))?,
             _ => hasher.finalize().to_vec(),
         };
 
@@ -195,6 +352,63 @@ impl KbsClient<Box<dyn EvidenceProvider>> {
     }
 }
 
+/// The attestation request of IBM SE that KBS sends as the nonce, with the
+/// digest of the runtime data as its user data, which the UV binds into the
+/// attestation response.
+fn se_attestation_request(nonce: &str, runtime_data_digest: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut request: serde_json::Value =
+        serde_json::from_str(nonce).context("parse SE attestation request")?;
+    request
+        .as_object_mut()
+        .context("SE attestation request is not an object")?
+        .insert(
+            "user_data".into(),
+            STANDARD.encode(runtime_data_digest).into(),
+        );
+    Ok(serde_json::to_vec(&request)?)
+}
+
+/// The error of a failed RCAR handshake. The failures that are told apart,
+/// e.g. a stale session, are kept, and a KBS that cannot be connected to or
+/// times out is [`Error::Unreachable`].
+fn handshake_error(e: anyhow::Error) -> Error {
+    let unreachable = e.chain().any(|cause| {
+        cause
+            .downcast_ref::<reqwest::Error>()
+            .is_some_and(|e| e.is_connect() || e.is_timeout())
+    });
+    match e.downcast::<Error>() {
+        Ok(e) => e,
+        Err(e) if unreachable => Error::Unreachable(format!("{e:#}")),
+        Err(e) => Error::RcarHandshake(format!("{e:#}")),
+    }
+}
+
+/// KBS no longer knows the session of the challenge, e.g. it expired while
+/// the evidence was being generated, rather than rejecting the evidence.
+fn is_stale_session(error_info: &ErrorInformation) -> bool {
+    let error_type = error_info.error_type.rsplit('/').next().unwrap_or_default();
+    STALE_SESSION_ERRORS.contains(&error_type)
+}
+
+/// Types of the KBS errors of [`is_stale_session`], prefixed by a URL.
+const STALE_SESSION_ERRORS: &[&str] = &["CookieNotFound", "ExpiredCookie", "InvalidCookie"];
+
+/// The value of the `client-nonce` field that binds a nonce of the caller
+/// into the runtime data of the RCAR handshake, next to the KBS challenge
+/// nonce. It is the standard base64 encoding of
+/// `SHA-384(nonce || tee-pubkey)`, where `tee-pubkey` is the JSON
+/// serialization of the TEE public key as sent in the runtime data, s.t.
+/// the nonce is only valid together with the key of this handshake.
+pub fn client_nonce_binding(nonce: &[u8], tee_pubkey: &TeePubKey) -> anyhow::Result<String> {
+    let tee_pubkey = serde_json::to_vec(tee_pubkey).context("serialize tee pubkey failed")?;
+    let digest = Sha384::new()
+        .chain_update(nonce)
+        .chain_update(tee_pubkey)
+        .finalize();
+    Ok(STANDARD.encode(digest))
+}
+
 #[async_trait]
 impl KbsClientCapabilities for KbsClient<Box<dyn EvidenceProvider>> {
     async fn get_resource(&mut self, resource_uri: ResourceUri) -> Result<Vec<u8>> {
@@ -232,9 +446,7 @@ impl KbsClientCapabilities for KbsClient<Box<dyn EvidenceProvider>> {
                             .await
                             .map_err(|e| Error::KbsResponseDeserializationFailed(e.to_string()))?,
                     );
-                    self.rcar_handshake()
-                        .await
-                        .map_err(|e| Error::RcarHandshake(e.to_string()))?;
+                    self.rcar_handshake().await.map_err(handshake_error)?;
 
                     continue;
                 }
@@ -271,12 +483,93 @@ mod test {
     use testcontainers::{clients, images::generic::GenericImage};
     use tokio::fs;
 
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use kbs_types::{ErrorInformation, TeePubKey};
+    use sha2::{Digest, Sha384};
+
+    use super::{handshake_error, is_stale_session, se_attestation_request};
     use crate::{
-        evidence_provider::NativeEvidenceProvider, KbsClientBuilder, KbsClientCapabilities,
+        client_nonce_binding, evidence_provider::NativeEvidenceProvider, Error, KbsClientBuilder,
+        KbsClientCapabilities,
     };
 
     const CONTENT: &[u8] = b"test content";
 
+    #[test]
+    fn se_user_data_is_runtime_data_digest() {
+        let digest = Sha384::digest(b"runtime data");
+        let request =
+            se_attestation_request(r#"{"request_blob":"AAAA","measurement_size":64}"#, &digest)
+                .unwrap();
+        let request: serde_json::Value = serde_json::from_slice(&request).unwrap();
+        assert_eq!(request["measurement_size"], 64);
+        assert_eq!(request["user_data"], STANDARD.encode(digest));
+
+        se_attestation_request("not json", &digest).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn unreachable_kbs() {
+        // Nothing listens on the port once the listener is dropped
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let e = reqwest::get(format!("http://127.0.0.1:{port}"))
+            .await
+            .unwrap_err();
+        let e = anyhow::Error::new(e).context("send auth request");
+        assert!(matches!(handshake_error(e), Error::Unreachable(_)));
+
+        let e = anyhow::anyhow!("deserialize challenge");
+        assert!(matches!(handshake_error(e), Error::RcarHandshake(_)));
+
+        let e = anyhow::Error::new(Error::StaleSession("expired".into())).context("attest");
+        assert!(matches!(handshake_error(e), Error::StaleSession(_)));
+    }
+
+    #[test]
+    fn stale_session_is_recognized() {
+        let error_info = |error_type: &str| ErrorInformation {
+            error_type: error_type.into(),
+            detail: String::new(),
+        };
+        assert!(is_stale_session(&error_info(
+            "https://github.com/confidential-containers/kbs/errors/ExpiredCookie"
+        )));
+        assert!(is_stale_session(&error_info("CookieNotFound")));
+        assert!(!is_stale_session(&error_info(
+            "https://github.com/confidential-containers/kbs/errors/PolicyReject"
+        )));
+    }
+
+    #[test]
+    fn client_nonce_binding_covers_key() {
+        let key = |k_mod: &str| TeePubKey {
+            kty: "RSA".into(),
+            alg: "RSA1_5".into(),
+            k_mod: k_mod.into(),
+            k_exp: "AQAB".into(),
+        };
+
+        let binding = client_nonce_binding(b"session-1", &key("abc")).unwrap();
+        let expected = Sha384::new()
+            .chain_update(b"session-1")
+            .chain_update(serde_json::to_vec(&key("abc")).unwrap())
+            .finalize();
+        assert_eq!(binding, STANDARD.encode(expected));
+
+        assert_ne!(
+            binding,
+            client_nonce_binding(b"session-1", &key("abd")).unwrap()
+        );
+        assert_ne!(
+            binding,
+            client_nonce_binding(b"session-2", &key("abc")).unwrap()
+        );
+    }
+
     #[tokio::test]
     #[serial_test::serial]
     async fn test_client() {
@@ -39,9 +39,25 @@ pub enum Error {
     #[error("RCAR handshake failed: {0}")]
     RcarHandshake(String),
 
+    /// KBS answered with a 4xx status, so the same request to the same KBS
+    /// would fail again.
+    #[error("KBS rejected the request with status {status}: {message}")]
+    RequestRejected { status: u16, message: String },
+
     #[error("KBS resource not found: {0}")]
     ResourceNotFound(String),
 
+    /// KBS could not be connected to, or did not answer in time. The same
+    /// request may succeed once KBS is reachable.
+    #[error("KBS is unreachable: {0}")]
+    Unreachable(String),
+
+    /// KBS no longer knows the session of the challenge, e.g. it expired
+    /// while the evidence was being generated. A new handshake with the
+    /// same KBS may succeed.
+    #[error("KBS attest session is stale: {0}")]
+    StaleSession(String),
+
     #[error("request unautorized")]
     UnAuthorized,
 }
@@ -79,7 +79,11 @@ pub mod keypair;
 pub mod token_provider;
 
 pub use api::*;
-pub use builder::KbsClientBuilder;
+pub use builder::{header_map, KbsClientBuilder};
+#[cfg(feature = "background_check")]
+pub use client::rcar_client::client_nonce_binding;
+#[cfg(feature = "metrics")]
+pub use client::RcarPhaseDurations;
 pub use error::{Error, Result};
 pub use keypair::TeeKeyPair;
 pub use token_provider::Token;
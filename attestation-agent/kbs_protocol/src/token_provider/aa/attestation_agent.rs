@@ -31,6 +31,8 @@ pub struct GetEvidenceRequest {
     // message fields
     // @@protoc_insertion_point(field:attestation_agent.GetEvidenceRequest.RuntimeData)
     pub RuntimeData: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:attestation_agent.GetEvidenceRequest.IncludeEventLog)
+    pub IncludeEventLog: bool,
     // special fields
     // @@protoc_insertion_point(special_field:attestation_agent.GetEvidenceRequest.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -48,13 +50,18 @@ impl GetEvidenceRequest {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut fields = ::std::vec::Vec::with_capacity(2);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "RuntimeData",
             |m: &GetEvidenceRequest| { &m.RuntimeData },
             |m: &mut GetEvidenceRequest| { &mut m.RuntimeData },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "IncludeEventLog",
+            |m: &GetEvidenceRequest| { &m.IncludeEventLog },
+            |m: &mut GetEvidenceRequest| { &mut m.IncludeEventLog },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetEvidenceRequest>(
             "GetEvidenceRequest",
             fields,
@@ -76,6 +83,9 @@ impl ::protobuf::Message for GetEvidenceRequest {
                 10 => {
                     self.RuntimeData = is.read_bytes()?;
                 },
+                16 => {
+                    self.IncludeEventLog = is.read_bool()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -91,6 +101,9 @@ impl ::protobuf::Message for GetEvidenceRequest {
         if !self.RuntimeData.is_empty() {
             my_size += ::protobuf::rt::bytes_size(1, &self.RuntimeData);
         }
+        if self.IncludeEventLog != false {
+            my_size += 1 + 1;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -100,6 +113,9 @@ impl ::protobuf::Message for GetEvidenceRequest {
         if !self.RuntimeData.is_empty() {
             os.write_bytes(1, &self.RuntimeData)?;
         }
+        if self.IncludeEventLog != false {
+            os.write_bool(2, self.IncludeEventLog)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -118,12 +134,14 @@ impl ::protobuf::Message for GetEvidenceRequest {
 
     fn clear(&mut self) {
         self.RuntimeData.clear();
+        self.IncludeEventLog = false;
         self.special_fields.clear();
     }
 
     fn default_instance() -> &'static GetEvidenceRequest {
         static instance: GetEvidenceRequest = GetEvidenceRequest {
             RuntimeData: ::std::vec::Vec::new(),
+            IncludeEventLog: false,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -275,6 +293,8 @@ pub struct GetTokenRequest {
     // message fields
     // @@protoc_insertion_point(field:attestation_agent.GetTokenRequest.TokenType)
     pub TokenType: ::std::string::String,
+    // @@protoc_insertion_point(field:attestation_agent.GetTokenRequest.ExtraParams)
+    pub ExtraParams: ::std::string::String,
     // special fields
     // @@protoc_insertion_point(special_field:attestation_agent.GetTokenRequest.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -292,13 +312,18 @@ impl GetTokenRequest {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut fields = ::std::vec::Vec::with_capacity(2);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "TokenType",
             |m: &GetTokenRequest| { &m.TokenType },
             |m: &mut GetTokenRequest| { &mut m.TokenType },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ExtraParams",
+            |m: &GetTokenRequest| { &m.ExtraParams },
+            |m: &mut GetTokenRequest| { &mut m.ExtraParams },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetTokenRequest>(
             "GetTokenRequest",
             fields,
@@ -320,6 +345,9 @@ impl ::protobuf::Message for GetTokenRequest {
                 10 => {
                     self.TokenType = is.read_string()?;
                 },
+                18 => {
+                    self.ExtraParams = is.read_string()?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -335,6 +363,9 @@ impl ::protobuf::Message for GetTokenRequest {
         if !self.TokenType.is_empty() {
             my_size += ::protobuf::rt::string_size(1, &self.TokenType);
         }
+        if !self.ExtraParams.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.ExtraParams);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -344,6 +375,9 @@ impl ::protobuf::Message for GetTokenRequest {
         if !self.TokenType.is_empty() {
             os.write_string(1, &self.TokenType)?;
         }
+        if !self.ExtraParams.is_empty() {
+            os.write_string(2, &self.ExtraParams)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -362,12 +396,14 @@ impl ::protobuf::Message for GetTokenRequest {
 
     fn clear(&mut self) {
         self.TokenType.clear();
+        self.ExtraParams.clear();
         self.special_fields.clear();
     }
 
     fn default_instance() -> &'static GetTokenRequest {
         static instance: GetTokenRequest = GetTokenRequest {
             TokenType: ::std::string::String::new(),
+            ExtraParams: ::std::string::String::new(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -525,6 +561,8 @@ pub struct ExtendRuntimeMeasurementRequest {
     pub Content: ::std::string::String,
     // @@protoc_insertion_point(field:attestation_agent.ExtendRuntimeMeasurementRequest.RegisterIndex)
     pub RegisterIndex: ::std::option::Option<u64>,
+    // @@protoc_insertion_point(field:attestation_agent.ExtendRuntimeMeasurementRequest.BinaryContent)
+    pub BinaryContent: ::std::option::Option<::std::vec::Vec<u8>>,
     // special fields
     // @@protoc_insertion_point(special_field:attestation_agent.ExtendRuntimeMeasurementRequest.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -542,7 +580,7 @@ impl ExtendRuntimeMeasurementRequest {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut fields = ::std::vec::Vec::with_capacity(5);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
             "Domain",
@@ -564,6 +602,11 @@ impl ExtendRuntimeMeasurementRequest {
             |m: &ExtendRuntimeMeasurementRequest| { &m.RegisterIndex },
             |m: &mut ExtendRuntimeMeasurementRequest| { &mut m.RegisterIndex },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "BinaryContent",
+            |m: &ExtendRuntimeMeasurementRequest| { &m.BinaryContent },
+            |m: &mut ExtendRuntimeMeasurementRequest| { &mut m.BinaryContent },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ExtendRuntimeMeasurementRequest>(
             "ExtendRuntimeMeasurementRequest",
             fields,
@@ -594,6 +637,9 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementRequest {
                 32 => {
                     self.RegisterIndex = ::std::option::Option::Some(is.read_uint64()?);
                 },
+                42 => {
+                    self.BinaryContent = ::std::option::Option::Some(is.read_bytes()?);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -618,6 +664,9 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementRequest {
         if let Some(v) = self.RegisterIndex {
             my_size += ::protobuf::rt::uint64_size(4, v);
         }
+        if let Some(v) = self.BinaryContent.as_ref() {
+            my_size += ::protobuf::rt::bytes_size(5, &v);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -636,6 +685,9 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementRequest {
         if let Some(v) = self.RegisterIndex {
             os.write_uint64(4, v)?;
         }
+        if let Some(v) = self.BinaryContent.as_ref() {
+            os.write_bytes(5, v)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -657,6 +709,7 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementRequest {
         self.Operation.clear();
         self.Content.clear();
         self.RegisterIndex = ::std::option::Option::None;
+        self.BinaryContent = ::std::option::Option::None;
         self.special_fields.clear();
     }
 
@@ -666,6 +719,7 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementRequest {
             Operation: ::std::string::String::new(),
             Content: ::std::string::String::new(),
             RegisterIndex: ::std::option::Option::None,
+            BinaryContent: ::std::option::Option::None,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -692,6 +746,15 @@ impl ::protobuf::reflect::ProtobufValue for ExtendRuntimeMeasurementRequest {
 // @@protoc_insertion_point(message:attestation_agent.ExtendRuntimeMeasurementResponse)
 #[derive(PartialEq,Clone,Default,Debug)]
 pub struct ExtendRuntimeMeasurementResponse {
+    // message fields
+    // @@protoc_insertion_point(field:attestation_agent.ExtendRuntimeMeasurementResponse.Digest)
+    pub Digest: ::std::vec::Vec<u8>,
+    // @@protoc_insertion_point(field:attestation_agent.ExtendRuntimeMeasurementResponse.Algorithm)
+    pub Algorithm: ::std::string::String,
+    // @@protoc_insertion_point(field:attestation_agent.ExtendRuntimeMeasurementResponse.RegisterIndex)
+    pub RegisterIndex: u64,
+    // @@protoc_insertion_point(field:attestation_agent.ExtendRuntimeMeasurementResponse.RegisterValue)
+    pub RegisterValue: ::std::option::Option<::std::vec::Vec<u8>>,
     // special fields
     // @@protoc_insertion_point(special_field:attestation_agent.ExtendRuntimeMeasurementResponse.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -709,8 +772,28 @@ impl ExtendRuntimeMeasurementResponse {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut fields = ::std::vec::Vec::with_capacity(4);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Digest",
+            |m: &ExtendRuntimeMeasurementResponse| { &m.Digest },
+            |m: &mut ExtendRuntimeMeasurementResponse| { &mut m.Digest },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Algorithm",
+            |m: &ExtendRuntimeMeasurementResponse| { &m.Algorithm },
+            |m: &mut ExtendRuntimeMeasurementResponse| { &mut m.Algorithm },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "RegisterIndex",
+            |m: &ExtendRuntimeMeasurementResponse| { &m.RegisterIndex },
+            |m: &mut ExtendRuntimeMeasurementResponse| { &mut m.RegisterIndex },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "RegisterValue",
+            |m: &ExtendRuntimeMeasurementResponse| { &m.RegisterValue },
+            |m: &mut ExtendRuntimeMeasurementResponse| { &mut m.RegisterValue },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<ExtendRuntimeMeasurementResponse>(
             "ExtendRuntimeMeasurementResponse",
             fields,
@@ -729,6 +812,18 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementResponse {
     fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
         while let Some(tag) = is.read_raw_tag_or_eof()? {
             match tag {
+                10 => {
+                    self.Digest = is.read_bytes()?;
+                },
+                18 => {
+                    self.Algorithm = is.read_string()?;
+                },
+                24 => {
+                    self.RegisterIndex = is.read_uint64()?;
+                },
+                34 => {
+                    self.RegisterValue = ::std::option::Option::Some(is.read_bytes()?);
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -741,12 +836,36 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementResponse {
     #[allow(unused_variables)]
     fn compute_size(&self) -> u64 {
         let mut my_size = 0;
+        if !self.Digest.is_empty() {
+            my_size += ::protobuf::rt::bytes_size(1, &self.Digest);
+        }
+        if !self.Algorithm.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.Algorithm);
+        }
+        if self.RegisterIndex != 0 {
+            my_size += ::protobuf::rt::uint64_size(3, self.RegisterIndex);
+        }
+        if let Some(v) = self.RegisterValue.as_ref() {
+            my_size += ::protobuf::rt::bytes_size(4, &v);
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
     }
 
     fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.Digest.is_empty() {
+            os.write_bytes(1, &self.Digest)?;
+        }
+        if !self.Algorithm.is_empty() {
+            os.write_string(2, &self.Algorithm)?;
+        }
+        if self.RegisterIndex != 0 {
+            os.write_uint64(3, self.RegisterIndex)?;
+        }
+        if let Some(v) = self.RegisterValue.as_ref() {
+            os.write_bytes(4, v)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -764,11 +883,19 @@ impl ::protobuf::Message for ExtendRuntimeMeasurementResponse {
     }
 
     fn clear(&mut self) {
+        self.Digest.clear();
+        self.Algorithm.clear();
+        self.RegisterIndex = 0;
+        self.RegisterValue = ::std::option::Option::None;
         self.special_fields.clear();
     }
 
     fn default_instance() -> &'static ExtendRuntimeMeasurementResponse {
         static instance: ExtendRuntimeMeasurementResponse = ExtendRuntimeMeasurementResponse {
+            Digest: ::std::vec::Vec::new(),
+            Algorithm: ::std::string::String::new(),
+            RegisterIndex: 0,
+            RegisterValue: ::std::option::Option::None,
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -1382,32 +1509,1094 @@ impl ::protobuf::reflect::ProtobufValue for UpdateConfigurationResponse {
     type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
 }
 
+// @@protoc_insertion_point(message:attestation_agent.GetTeeTypeRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetTeeTypeRequest {
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetTeeTypeRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetTeeTypeRequest {
+    fn default() -> &'a GetTeeTypeRequest {
+        <GetTeeTypeRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetTeeTypeRequest {
+    pub fn new() -> GetTeeTypeRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetTeeTypeRequest>(
+            "GetTeeTypeRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetTeeTypeRequest {
+    const NAME: &'static str = "GetTeeTypeRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetTeeTypeRequest {
+        GetTeeTypeRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetTeeTypeRequest {
+        static instance: GetTeeTypeRequest = GetTeeTypeRequest {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetTeeTypeRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetTeeTypeRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetTeeTypeRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetTeeTypeRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetTeeTypeResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetTeeTypeResponse {
+    // message fields
+    // @@protoc_insertion_point(field:attestation_agent.GetTeeTypeResponse.tee)
+    pub tee: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetTeeTypeResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetTeeTypeResponse {
+    fn default() -> &'a GetTeeTypeResponse {
+        <GetTeeTypeResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetTeeTypeResponse {
+    pub fn new() -> GetTeeTypeResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "tee",
+            |m: &GetTeeTypeResponse| { &m.tee },
+            |m: &mut GetTeeTypeResponse| { &mut m.tee },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetTeeTypeResponse>(
+            "GetTeeTypeResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetTeeTypeResponse {
+    const NAME: &'static str = "GetTeeTypeResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.tee = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if !self.tee.is_empty() {
+            my_size += ::protobuf::rt::string_size(1, &self.tee);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if !self.tee.is_empty() {
+            os.write_string(1, &self.tee)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetTeeTypeResponse {
+        GetTeeTypeResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.tee.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetTeeTypeResponse {
+        static instance: GetTeeTypeResponse = GetTeeTypeResponse {
+            tee: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetTeeTypeResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetTeeTypeResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetTeeTypeResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetTeeTypeResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetEventLogRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetEventLogRequest {
+    // message fields
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogRequest.StartIndex)
+    pub StartIndex: u64,
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogRequest.RegisterIndex)
+    pub RegisterIndex: ::std::option::Option<u64>,
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogRequest.Format)
+    pub Format: ::std::string::String,
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogRequest.Filter)
+    pub Filter: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetEventLogRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetEventLogRequest {
+    fn default() -> &'a GetEventLogRequest {
+        <GetEventLogRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetEventLogRequest {
+    pub fn new() -> GetEventLogRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "StartIndex",
+            |m: &GetEventLogRequest| { &m.StartIndex },
+            |m: &mut GetEventLogRequest| { &mut m.StartIndex },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "RegisterIndex",
+            |m: &GetEventLogRequest| { &m.RegisterIndex },
+            |m: &mut GetEventLogRequest| { &mut m.RegisterIndex },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Format",
+            |m: &GetEventLogRequest| { &m.Format },
+            |m: &mut GetEventLogRequest| { &mut m.Format },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Filter",
+            |m: &GetEventLogRequest| { &m.Filter },
+            |m: &mut GetEventLogRequest| { &mut m.Filter },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetEventLogRequest>(
+            "GetEventLogRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetEventLogRequest {
+    const NAME: &'static str = "GetEventLogRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.StartIndex = is.read_uint64()?;
+                },
+                16 => {
+                    self.RegisterIndex = ::std::option::Option::Some(is.read_uint64()?);
+                },
+                26 => {
+                    self.Format = is.read_string()?;
+                },
+                34 => {
+                    self.Filter = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.StartIndex != 0 {
+            my_size += ::protobuf::rt::uint64_size(1, self.StartIndex);
+        }
+        if let Some(v) = self.RegisterIndex {
+            my_size += ::protobuf::rt::uint64_size(2, v);
+        }
+        if !self.Format.is_empty() {
+            my_size += ::protobuf::rt::string_size(3, &self.Format);
+        }
+        if !self.Filter.is_empty() {
+            my_size += ::protobuf::rt::string_size(4, &self.Filter);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.StartIndex != 0 {
+            os.write_uint64(1, self.StartIndex)?;
+        }
+        if let Some(v) = self.RegisterIndex {
+            os.write_uint64(2, v)?;
+        }
+        if !self.Format.is_empty() {
+            os.write_string(3, &self.Format)?;
+        }
+        if !self.Filter.is_empty() {
+            os.write_string(4, &self.Filter)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetEventLogRequest {
+        GetEventLogRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.StartIndex = 0;
+        self.RegisterIndex = ::std::option::Option::None;
+        self.Format.clear();
+        self.Filter.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetEventLogRequest {
+        static instance: GetEventLogRequest = GetEventLogRequest {
+            StartIndex: 0,
+            RegisterIndex: ::std::option::Option::None,
+            Format: ::std::string::String::new(),
+            Filter: ::std::string::String::new(),
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetEventLogRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetEventLogRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetEventLogRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetEventLogRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetEventLogResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetEventLogResponse {
+    // message fields
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogResponse.Entries)
+    pub Entries: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogResponse.Json)
+    pub Json: ::std::string::String,
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetEventLogResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetEventLogResponse {
+    fn default() -> &'a GetEventLogResponse {
+        <GetEventLogResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetEventLogResponse {
+    pub fn new() -> GetEventLogResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(2);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "Entries",
+            |m: &GetEventLogResponse| { &m.Entries },
+            |m: &mut GetEventLogResponse| { &mut m.Entries },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "Json",
+            |m: &GetEventLogResponse| { &m.Json },
+            |m: &mut GetEventLogResponse| { &mut m.Json },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetEventLogResponse>(
+            "GetEventLogResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetEventLogResponse {
+    const NAME: &'static str = "GetEventLogResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.Entries.push(is.read_string()?);
+                },
+                18 => {
+                    self.Json = is.read_string()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        for value in &self.Entries {
+            my_size += ::protobuf::rt::string_size(1, &value);
+        };
+        if !self.Json.is_empty() {
+            my_size += ::protobuf::rt::string_size(2, &self.Json);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        for v in &self.Entries {
+            os.write_string(1, &v)?;
+        };
+        if !self.Json.is_empty() {
+            os.write_string(2, &self.Json)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetEventLogResponse {
+        GetEventLogResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.Entries.clear();
+        self.Json.clear();
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetEventLogResponse {
+        static instance: ::protobuf::rt::Lazy<GetEventLogResponse> = ::protobuf::rt::Lazy::new();
+        instance.get(GetEventLogResponse::new)
+    }
+}
+
+impl ::protobuf::MessageFull for GetEventLogResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetEventLogResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetEventLogResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetEventLogResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetEventLogPathRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetEventLogPathRequest {
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetEventLogPathRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetEventLogPathRequest {
+    fn default() -> &'a GetEventLogPathRequest {
+        <GetEventLogPathRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetEventLogPathRequest {
+    pub fn new() -> GetEventLogPathRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetEventLogPathRequest>(
+            "GetEventLogPathRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetEventLogPathRequest {
+    const NAME: &'static str = "GetEventLogPathRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetEventLogPathRequest {
+        GetEventLogPathRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetEventLogPathRequest {
+        static instance: GetEventLogPathRequest = GetEventLogPathRequest {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetEventLogPathRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetEventLogPathRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetEventLogPathRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetEventLogPathRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetEventLogPathResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetEventLogPathResponse {
+    // message fields
+    // @@protoc_insertion_point(field:attestation_agent.GetEventLogPathResponse.Path)
+    pub Path: ::std::option::Option<::std::string::String>,
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetEventLogPathResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetEventLogPathResponse {
+    fn default() -> &'a GetEventLogPathResponse {
+        <GetEventLogPathResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetEventLogPathResponse {
+    pub fn new() -> GetEventLogPathResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
+            "Path",
+            |m: &GetEventLogPathResponse| { &m.Path },
+            |m: &mut GetEventLogPathResponse| { &mut m.Path },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetEventLogPathResponse>(
+            "GetEventLogPathResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetEventLogPathResponse {
+    const NAME: &'static str = "GetEventLogPathResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                10 => {
+                    self.Path = ::std::option::Option::Some(is.read_string()?);
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if let Some(v) = self.Path.as_ref() {
+            my_size += ::protobuf::rt::string_size(1, &v);
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if let Some(v) = self.Path.as_ref() {
+            os.write_string(1, v)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetEventLogPathResponse {
+        GetEventLogPathResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.Path = ::std::option::Option::None;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetEventLogPathResponse {
+        static instance: GetEventLogPathResponse = GetEventLogPathResponse {
+            Path: ::std::option::Option::None,
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetEventLogPathResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetEventLogPathResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetEventLogPathResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetEventLogPathResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetCapabilitiesRequest)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetCapabilitiesRequest {
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetCapabilitiesRequest.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetCapabilitiesRequest {
+    fn default() -> &'a GetCapabilitiesRequest {
+        <GetCapabilitiesRequest as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetCapabilitiesRequest {
+    pub fn new() -> GetCapabilitiesRequest {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(0);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetCapabilitiesRequest>(
+            "GetCapabilitiesRequest",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetCapabilitiesRequest {
+    const NAME: &'static str = "GetCapabilitiesRequest";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetCapabilitiesRequest {
+        GetCapabilitiesRequest::new()
+    }
+
+    fn clear(&mut self) {
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetCapabilitiesRequest {
+        static instance: GetCapabilitiesRequest = GetCapabilitiesRequest {
+            special_fields: ::protobuf::SpecialFields::new(),
+        };
+        &instance
+    }
+}
+
+impl ::protobuf::MessageFull for GetCapabilitiesRequest {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetCapabilitiesRequest").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetCapabilitiesRequest {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetCapabilitiesRequest {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
+// @@protoc_insertion_point(message:attestation_agent.GetCapabilitiesResponse)
+#[derive(PartialEq,Clone,Default,Debug)]
+pub struct GetCapabilitiesResponse {
+    // message fields
+    // @@protoc_insertion_point(field:attestation_agent.GetCapabilitiesResponse.SupportsRuntimeMeasurement)
+    pub SupportsRuntimeMeasurement: bool,
+    // @@protoc_insertion_point(field:attestation_agent.GetCapabilitiesResponse.ReportDataLen)
+    pub ReportDataLen: u64,
+    // @@protoc_insertion_point(field:attestation_agent.GetCapabilitiesResponse.SupportedHashAlgorithms)
+    pub SupportedHashAlgorithms: ::std::vec::Vec<::std::string::String>,
+    // @@protoc_insertion_point(field:attestation_agent.GetCapabilitiesResponse.InitDataSupported)
+    pub InitDataSupported: bool,
+    // special fields
+    // @@protoc_insertion_point(special_field:attestation_agent.GetCapabilitiesResponse.special_fields)
+    pub special_fields: ::protobuf::SpecialFields,
+}
+
+impl<'a> ::std::default::Default for &'a GetCapabilitiesResponse {
+    fn default() -> &'a GetCapabilitiesResponse {
+        <GetCapabilitiesResponse as ::protobuf::Message>::default_instance()
+    }
+}
+
+impl GetCapabilitiesResponse {
+    pub fn new() -> GetCapabilitiesResponse {
+        ::std::default::Default::default()
+    }
+
+    fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
+        let mut fields = ::std::vec::Vec::with_capacity(4);
+        let mut oneofs = ::std::vec::Vec::with_capacity(0);
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "SupportsRuntimeMeasurement",
+            |m: &GetCapabilitiesResponse| { &m.SupportsRuntimeMeasurement },
+            |m: &mut GetCapabilitiesResponse| { &mut m.SupportsRuntimeMeasurement },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "ReportDataLen",
+            |m: &GetCapabilitiesResponse| { &m.ReportDataLen },
+            |m: &mut GetCapabilitiesResponse| { &mut m.ReportDataLen },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_vec_simpler_accessor::<_, _>(
+            "SupportedHashAlgorithms",
+            |m: &GetCapabilitiesResponse| { &m.SupportedHashAlgorithms },
+            |m: &mut GetCapabilitiesResponse| { &mut m.SupportedHashAlgorithms },
+        ));
+        fields.push(::protobuf::reflect::rt::v2::make_simpler_field_accessor::<_, _>(
+            "InitDataSupported",
+            |m: &GetCapabilitiesResponse| { &m.InitDataSupported },
+            |m: &mut GetCapabilitiesResponse| { &mut m.InitDataSupported },
+        ));
+        ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<GetCapabilitiesResponse>(
+            "GetCapabilitiesResponse",
+            fields,
+            oneofs,
+        )
+    }
+}
+
+impl ::protobuf::Message for GetCapabilitiesResponse {
+    const NAME: &'static str = "GetCapabilitiesResponse";
+
+    fn is_initialized(&self) -> bool {
+        true
+    }
+
+    fn merge_from(&mut self, is: &mut ::protobuf::CodedInputStream<'_>) -> ::protobuf::Result<()> {
+        while let Some(tag) = is.read_raw_tag_or_eof()? {
+            match tag {
+                8 => {
+                    self.SupportsRuntimeMeasurement = is.read_bool()?;
+                },
+                16 => {
+                    self.ReportDataLen = is.read_uint64()?;
+                },
+                26 => {
+                    self.SupportedHashAlgorithms.push(is.read_string()?);
+                },
+                32 => {
+                    self.InitDataSupported = is.read_bool()?;
+                },
+                tag => {
+                    ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
+                },
+            };
+        }
+        ::std::result::Result::Ok(())
+    }
+
+    // Compute sizes of nested messages
+    #[allow(unused_variables)]
+    fn compute_size(&self) -> u64 {
+        let mut my_size = 0;
+        if self.SupportsRuntimeMeasurement != false {
+            my_size += 1 + 1;
+        }
+        if self.ReportDataLen != 0 {
+            my_size += ::protobuf::rt::uint64_size(2, self.ReportDataLen);
+        }
+        for value in &self.SupportedHashAlgorithms {
+            my_size += ::protobuf::rt::string_size(3, &value);
+        };
+        if self.InitDataSupported != false {
+            my_size += 1 + 1;
+        }
+        my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
+        self.special_fields.cached_size().set(my_size as u32);
+        my_size
+    }
+
+    fn write_to_with_cached_sizes(&self, os: &mut ::protobuf::CodedOutputStream<'_>) -> ::protobuf::Result<()> {
+        if self.SupportsRuntimeMeasurement != false {
+            os.write_bool(1, self.SupportsRuntimeMeasurement)?;
+        }
+        if self.ReportDataLen != 0 {
+            os.write_uint64(2, self.ReportDataLen)?;
+        }
+        for v in &self.SupportedHashAlgorithms {
+            os.write_string(3, &v)?;
+        };
+        if self.InitDataSupported != false {
+            os.write_bool(4, self.InitDataSupported)?;
+        }
+        os.write_unknown_fields(self.special_fields.unknown_fields())?;
+        ::std::result::Result::Ok(())
+    }
+
+    fn special_fields(&self) -> &::protobuf::SpecialFields {
+        &self.special_fields
+    }
+
+    fn mut_special_fields(&mut self) -> &mut ::protobuf::SpecialFields {
+        &mut self.special_fields
+    }
+
+    fn new() -> GetCapabilitiesResponse {
+        GetCapabilitiesResponse::new()
+    }
+
+    fn clear(&mut self) {
+        self.SupportsRuntimeMeasurement = false;
+        self.ReportDataLen = 0;
+        self.SupportedHashAlgorithms.clear();
+        self.InitDataSupported = false;
+        self.special_fields.clear();
+    }
+
+    fn default_instance() -> &'static GetCapabilitiesResponse {
+        static instance: ::protobuf::rt::Lazy<GetCapabilitiesResponse> = ::protobuf::rt::Lazy::new();
+        instance.get(GetCapabilitiesResponse::new)
+    }
+}
+
+impl ::protobuf::MessageFull for GetCapabilitiesResponse {
+    fn descriptor() -> ::protobuf::reflect::MessageDescriptor {
+        static descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::MessageDescriptor> = ::protobuf::rt::Lazy::new();
+        descriptor.get(|| file_descriptor().message_by_package_relative_name("GetCapabilitiesResponse").unwrap()).clone()
+    }
+}
+
+impl ::std::fmt::Display for GetCapabilitiesResponse {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+        ::protobuf::text_format::fmt(self, f)
+    }
+}
+
+impl ::protobuf::reflect::ProtobufValue for GetCapabilitiesResponse {
+    type RuntimeType = ::protobuf::reflect::rt::RuntimeTypeMessage<Self>;
+}
+
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x17attestation-agent.proto\x12\x11attestation_agent\"6\n\x12GetEviden\
+    \n\x17attestation-agent.proto\x12\x11attestation_agent\"`\n\x12GetEviden\
     ceRequest\x12\x20\n\x0bRuntimeData\x18\x01\x20\x01(\x0cR\x0bRuntimeData\
-    \"1\n\x13GetEvidenceResponse\x12\x1a\n\x08Evidence\x18\x01\x20\x01(\x0cR\
-    \x08Evidence\"/\n\x0fGetTokenRequest\x12\x1c\n\tTokenType\x18\x01\x20\
-    \x01(\tR\tTokenType\"(\n\x10GetTokenResponse\x12\x14\n\x05Token\x18\x01\
-    \x20\x01(\x0cR\x05Token\"\xae\x01\n\x1fExtendRuntimeMeasurementRequest\
-    \x12\x16\n\x06Domain\x18\x01\x20\x01(\tR\x06Domain\x12\x1c\n\tOperation\
-    \x18\x02\x20\x01(\tR\tOperation\x12\x18\n\x07Content\x18\x03\x20\x01(\tR\
-    \x07Content\x12)\n\rRegisterIndex\x18\x04\x20\x01(\x04H\0R\rRegisterInde\
-    x\x88\x01\x01B\x10\n\x0e_RegisterIndex\"\"\n\x20ExtendRuntimeMeasurement\
-    Response\"K\n\x11InitDataPlaintext\x12\x18\n\x07Content\x18\x01\x20\x01(\
-    \x0cR\x07Content\x12\x1c\n\tAlgorithm\x18\x02\x20\x01(\tR\tAlgorithm\".\
-    \n\x14CheckInitDataRequest\x12\x16\n\x06Digest\x18\x01\x20\x01(\x0cR\x06\
-    Digest\"\x17\n\x15CheckInitDataResponse\"4\n\x1aUpdateConfigurationReque\
-    st\x12\x16\n\x06config\x18\x01\x20\x01(\tR\x06config\"\x1d\n\x1bUpdateCo\
-    nfigurationResponse2\xac\x04\n\x17AttestationAgentService\x12\\\n\x0bGet\
-    Evidence\x12%.attestation_agent.GetEvidenceRequest\x1a&.attestation_agen\
-    t.GetEvidenceResponse\x12S\n\x08GetToken\x12\".attestation_agent.GetToke\
-    nRequest\x1a#.attestation_agent.GetTokenResponse\x12\x83\x01\n\x18Extend\
-    RuntimeMeasurement\x122.attestation_agent.ExtendRuntimeMeasurementReques\
-    t\x1a3.attestation_agent.ExtendRuntimeMeasurementResponse\x12b\n\rCheckI\
-    nitData\x12'.attestation_agent.CheckInitDataRequest\x1a(.attestation_age\
-    nt.CheckInitDataResponse\x12t\n\x13UpdateConfiguration\x12-.attestation_\
-    agent.UpdateConfigurationRequest\x1a..attestation_agent.UpdateConfigurat\
-    ionResponseb\x06proto3\
+    \x12(\n\x0fIncludeEventLog\x18\x02\x20\x01(\x08R\x0fIncludeEventLog\"1\n\
+    \x13GetEvidenceResponse\x12\x1a\n\x08Evidence\x18\x01\x20\x01(\x0cR\x08E\
+    vidence\"Q\n\x0fGetTokenRequest\x12\x1c\n\tTokenType\x18\x01\x20\x01(\tR\
+    \tTokenType\x12\x20\n\x0bExtraParams\x18\x02\x20\x01(\tR\x0bExtraParams\
+    \"(\n\x10GetTokenResponse\x12\x14\n\x05Token\x18\x01\x20\x01(\x0cR\x05To\
+    ken\"\xeb\x01\n\x1fExtendRuntimeMeasurementRequest\x12\x16\n\x06Domain\
+    \x18\x01\x20\x01(\tR\x06Domain\x12\x1c\n\tOperation\x18\x02\x20\x01(\tR\
+    \tOperation\x12\x18\n\x07Content\x18\x03\x20\x01(\tR\x07Content\x12)\n\r\
+    RegisterIndex\x18\x04\x20\x01(\x04H\0R\rRegisterIndex\x88\x01\x01\x12)\n\
+    \rBinaryContent\x18\x05\x20\x01(\x0cH\x01R\rBinaryContent\x88\x01\x01B\
+    \x10\n\x0e_RegisterIndexB\x10\n\x0e_BinaryContent\"\xbb\x01\n\x20ExtendR\
+    untimeMeasurementResponse\x12\x16\n\x06Digest\x18\x01\x20\x01(\x0cR\x06D\
+    igest\x12\x1c\n\tAlgorithm\x18\x02\x20\x01(\tR\tAlgorithm\x12$\n\rRegist\
+    erIndex\x18\x03\x20\x01(\x04R\rRegisterIndex\x12)\n\rRegisterValue\x18\
+    \x04\x20\x01(\x0cH\0R\rRegisterValue\x88\x01\x01B\x10\n\x0e_RegisterValu\
+    e\"K\n\x11InitDataPlaintext\x12\x18\n\x07Content\x18\x01\x20\x01(\x0cR\
+    \x07Content\x12\x1c\n\tAlgorithm\x18\x02\x20\x01(\tR\tAlgorithm\".\n\x14\
+    CheckInitDataRequest\x12\x16\n\x06Digest\x18\x01\x20\x01(\x0cR\x06Digest\
+    \"\x17\n\x15CheckInitDataResponse\"4\n\x1aUpdateConfigurationRequest\x12\
+    \x16\n\x06config\x18\x01\x20\x01(\tR\x06config\"\x1d\n\x1bUpdateConfigur\
+    ationResponse\"\x13\n\x11GetTeeTypeRequest\"&\n\x12GetTeeTypeResponse\
+    \x12\x10\n\x03tee\x18\x01\x20\x01(\tR\x03tee\"\xa1\x01\n\x12GetEventLogR\
+    equest\x12\x1e\n\nStartIndex\x18\x01\x20\x01(\x04R\nStartIndex\x12)\n\rR\
+    egisterIndex\x18\x02\x20\x01(\x04H\0R\rRegisterIndex\x88\x01\x01\x12\x16\
+    \n\x06Format\x18\x03\x20\x01(\tR\x06Format\x12\x16\n\x06Filter\x18\x04\
+    \x20\x01(\tR\x06FilterB\x10\n\x0e_RegisterIndex\"C\n\x13GetEventLogRespo\
+    nse\x12\x18\n\x07Entries\x18\x01\x20\x03(\tR\x07Entries\x12\x12\n\x04Jso\
+    n\x18\x02\x20\x01(\tR\x04Json\"\x18\n\x16GetEventLogPathRequest\";\n\x17\
+    GetEventLogPathResponse\x12\x17\n\x04Path\x18\x01\x20\x01(\tH\0R\x04Path\
+    \x88\x01\x01B\x07\n\x05_Path\"\x18\n\x16GetCapabilitiesRequest\"\xe7\x01\
+    \n\x17GetCapabilitiesResponse\x12>\n\x1aSupportsRuntimeMeasurement\x18\
+    \x01\x20\x01(\x08R\x1aSupportsRuntimeMeasurement\x12$\n\rReportDataLen\
+    \x18\x02\x20\x01(\x04R\rReportDataLen\x128\n\x17SupportedHashAlgorithms\
+    \x18\x03\x20\x03(\tR\x17SupportedHashAlgorithms\x12,\n\x11InitDataSuppor\
+    ted\x18\x04\x20\x01(\x08R\x11InitDataSupported2\xb9\x07\n\x17Attestation\
+    AgentService\x12\\\n\x0bGetEvidence\x12%.attestation_agent.GetEvidenceRe\
+    quest\x1a&.attestation_agent.GetEvidenceResponse\x12S\n\x08GetToken\x12\
+    \".attestation_agent.GetTokenRequest\x1a#.attestation_agent.GetTokenResp\
+    onse\x12\x83\x01\n\x18ExtendRuntimeMeasurement\x122.attestation_agent.Ex\
+    tendRuntimeMeasurementRequest\x1a3.attestation_agent.ExtendRuntimeMeasur\
+    ementResponse\x12b\n\rCheckInitData\x12'.attestation_agent.CheckInitData\
+    Request\x1a(.attestation_agent.CheckInitDataResponse\x12t\n\x13UpdateCon\
+    figuration\x12-.attestation_agent.UpdateConfigurationRequest\x1a..attest\
+    ation_agent.UpdateConfigurationResponse\x12Y\n\nGetTeeType\x12$.attestat\
+    ion_agent.GetTeeTypeRequest\x1a%.attestation_agent.GetTeeTypeResponse\
+    \x12\\\n\x0bGetEventLog\x12%.attestation_agent.GetEventLogRequest\x1a&.a\
+    ttestation_agent.GetEventLogResponse\x12h\n\x0fGetEventLogPath\x12).atte\
+    station_agent.GetEventLogPathRequest\x1a*.attestation_agent.GetEventLogP\
+    athResponse\x12h\n\x0fGetCapabilities\x12).attestation_agent.GetCapabili\
+    tiesRequest\x1a*.attestation_agent.GetCapabilitiesResponseb\x06proto3\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
@@ -1425,7 +2614,7 @@ pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
     file_descriptor.get(|| {
         let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
             let mut deps = ::std::vec::Vec::with_capacity(0);
-            let mut messages = ::std::vec::Vec::with_capacity(11);
+            let mut messages = ::std::vec::Vec::with_capacity(19);
             messages.push(GetEvidenceRequest::generated_message_descriptor_data());
             messages.push(GetEvidenceResponse::generated_message_descriptor_data());
             messages.push(GetTokenRequest::generated_message_descriptor_data());
@@ -1437,6 +2626,14 @@ pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
             messages.push(CheckInitDataResponse::generated_message_descriptor_data());
             messages.push(UpdateConfigurationRequest::generated_message_descriptor_data());
             messages.push(UpdateConfigurationResponse::generated_message_descriptor_data());
+            messages.push(GetTeeTypeRequest::generated_message_descriptor_data());
+            messages.push(GetTeeTypeResponse::generated_message_descriptor_data());
+            messages.push(GetEventLogRequest::generated_message_descriptor_data());
+            messages.push(GetEventLogResponse::generated_message_descriptor_data());
+            messages.push(GetEventLogPathRequest::generated_message_descriptor_data());
+            messages.push(GetEventLogPathResponse::generated_message_descriptor_data());
+            messages.push(GetCapabilitiesRequest::generated_message_descriptor_data());
+            messages.push(GetCapabilitiesResponse::generated_message_descriptor_data());
             let mut enums = ::std::vec::Vec::with_capacity(0);
             ::protobuf::reflect::GeneratedFileDescriptor::new_generated(
                 file_descriptor_proto(),